@@ -0,0 +1,109 @@
+//! Rasterization benchmarks, driven through the same `OffscreenRenderer`
+//! entry point that embedders use for profiling.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use displai::*;
+
+fn primitives() -> Vec<(&'static str, Vec<Command>)> {
+    let filled = |cmd: Command| vec![Command::Fill(Some(COLOR_PALETTE[2])), Command::Size(3), cmd];
+    vec![
+        (
+            "dot",
+            vec![Command::Size(10), Command::Dot { x: 400, y: 300 }],
+        ),
+        (
+            "stroke",
+            vec![
+                Command::Size(5),
+                Command::Stroke {
+                    x1: 50,
+                    y1: 60,
+                    x2: 750,
+                    y2: 500,
+                },
+            ],
+        ),
+        (
+            "rect_filled",
+            filled(Command::Rect {
+                x1: 100,
+                y1: 100,
+                x2: 700,
+                y2: 500,
+            }),
+        ),
+        (
+            "circle_filled",
+            filled(Command::Circle {
+                x: 400,
+                y: 300,
+                r: 200,
+            }),
+        ),
+        (
+            "oval_filled",
+            filled(Command::Oval {
+                x: 400,
+                y: 300,
+                rx: 300,
+                ry: 150,
+            }),
+        ),
+        (
+            "triangle_filled",
+            filled(Command::Triangle {
+                x1: 100,
+                y1: 500,
+                x2: 700,
+                y2: 100,
+            }),
+        ),
+        (
+            "polyline",
+            vec![
+                Command::Size(3),
+                parse_command("polyline 10,100 200,400 400,100 600,400 790,100").unwrap(),
+            ],
+        ),
+        ("clear", vec![Command::Clear]),
+        (
+            // Alternate colors so every fill repaints the whole canvas
+            "bucket_full_canvas",
+            vec![
+                Command::Fill(Some(COLOR_PALETTE[2])),
+                Command::Bucket { x: 400, y: 300 },
+                Command::Fill(Some(COLOR_PALETTE[7])),
+                Command::Bucket { x: 400, y: 300 },
+            ],
+        ),
+    ]
+}
+
+fn bench_primitives(c: &mut Criterion) {
+    let mut group = c.benchmark_group("primitives");
+    for (name, commands) in primitives() {
+        group.bench_function(name, |b| {
+            let mut renderer = OffscreenRenderer::new();
+            b.iter(|| {
+                for cmd in &commands {
+                    black_box(renderer.execute(black_box(cmd)));
+                }
+                renderer.reset_timings();
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_generated_workload(c: &mut Criterion) {
+    let commands: Vec<Command> = CommandGenerator::new(1).take(200).collect();
+    c.bench_function("generated_workload", |b| {
+        b.iter(|| {
+            let mut renderer = OffscreenRenderer::new();
+            renderer.execute_all(black_box(&commands))
+        });
+    });
+}
+
+criterion_group!(benches, bench_primitives, bench_generated_workload);
+criterion_main!(benches);
@@ -0,0 +1,130 @@
+//! Access control for protocol clients.
+//!
+//! This module handles:
+//! - `Access`, whether a client may only observe the canvas or also change it
+//! - `AccessControl`, each client's access: a default per transport (stdin or
+//!   socket), raised or lowered for a connection that sends `auth <token>`
+//!
+//! Read-only clients can watch (`state`, `stats`, `subscribe`, `getpixel`,
+//! `pick`, `getregion`, and snapshots to the default path or as base64) but nothing
+//! that draws, clears, changes tool state, or writes a file of their choosing.
+//! Untrusted viewers can then share the socket with the agents doing the
+//! drawing. The local mouse and keyboard are never restricted.
+
+use std::collections::HashMap;
+
+use crate::command::{AliasCommand, Command};
+use crate::locks::{ClientId, STDIN_CLIENT};
+
+/// What a client may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Access {
+    /// Observe only: read-back, snapshot, and subscribe commands
+    ReadOnly,
+    /// Any command
+    #[default]
+    ReadWrite,
+}
+
+impl Access {
+    /// Parse `read-only` or `read-write`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "read-only" => Some(Access::ReadOnly),
+            "read-write" => Some(Access::ReadWrite),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::ReadOnly => "read-only",
+            Access::ReadWrite => "read-write",
+        }
+    }
+
+    /// Whether a client with this access may run `cmd`
+    pub fn allows(self, cmd: &Command) -> bool {
+        match self {
+            Access::ReadWrite => true,
+            Access::ReadOnly => matches!(
+                cmd,
+                Command::Snapshot(None)
+                    | Command::SnapshotBase64
+                    | Command::SnapshotRegion { path: None, .. }
+                    | Command::State
+                    | Command::Layout
+                    | Command::Alias(AliasCommand::Get(_))
+                    | Command::Stats(None)
+                    | Command::Subscribe
+                    | Command::GetPixel { .. }
+                    | Command::Pick { .. }
+                    | Command::GetRegion { .. }
+                    | Command::Auth(_)
+            ),
+        }
+    }
+}
+
+/// The access of every client
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessControl {
+    /// Access of stdin, unless it authenticates
+    pub stdin: Access,
+    /// Access of each socket connection, unless it authenticates
+    pub socket: Access,
+    /// Tokens accepted by `auth`, and the access each grants
+    pub tokens: Vec<(String, Access)>,
+    /// Clients that authenticated, and the access their token granted
+    granted: HashMap<ClientId, Access>,
+}
+
+impl AccessControl {
+    pub fn new(stdin: Access, socket: Access, tokens: Vec<(String, Access)>) -> Self {
+        AccessControl {
+            stdin,
+            socket,
+            tokens,
+            granted: HashMap::new(),
+        }
+    }
+
+    /// The access `client` has now
+    pub fn access(&self, client: ClientId) -> Access {
+        match self.granted.get(&client) {
+            Some(&access) => access,
+            None if client == STDIN_CLIENT => self.stdin,
+            None => self.socket,
+        }
+    }
+
+    /// Give `client` the access `token` grants, returning it
+    pub fn authenticate(&mut self, client: ClientId, token: &str) -> Result<Access, String> {
+        let access = self
+            .tokens
+            .iter()
+            .find(|(known, _)| known == token)
+            .map(|&(_, access)| access)
+            .ok_or("invalid token")?;
+        self.granted.insert(client, access);
+        Ok(access)
+    }
+
+    /// Forget what a closed connection authenticated as
+    pub fn forget(&mut self, client: ClientId) {
+        self.granted.remove(&client);
+    }
+
+    /// Check a command from `client`, returning why it's refused if its
+    /// access doesn't allow it
+    pub fn check(&self, client: ClientId, cmd: &Command) -> Result<(), String> {
+        if self.access(client).allows(cmd) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} is not allowed for read-only clients",
+                cmd.name()
+            ))
+        }
+    }
+}
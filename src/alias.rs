@@ -0,0 +1,207 @@
+//! Command aliases: short verbs standing for a run of commands.
+//!
+//! This module handles:
+//! - `Aliases`, the bodies defined with `alias <name> "<command>; <command>"`
+//! - The aliases parsing on this thread knows, so a line naming one parses as
+//!   the commands it stands for
+//! - Splitting a body into its command lines, and how deep aliases naming
+//!   aliases have gone
+//! - Positional arguments: `$1` to `$9` in a body are replaced by the words
+//!   after the alias's name (`alias box "rect $1,$2 $3,$4"`, then
+//!   `box 10 40 100 100`)
+//!
+//! Arguments are put in after the body is split into lines, so one can't add
+//! commands with a `;`, and a `"quoted argument"` keeps its quotes, ready for
+//! commands that take quoted text.
+//!
+//! An alias is kept as the text it was defined with and parsed afresh each
+//! time it is used, so palette indices in it mean the palette of the moment
+//! and an alias naming another picks up the other's latest definition. An
+//! alias can't expand more than `MAX_ALIAS_DEPTH` aliases deep, so two that
+//! name each other fail to parse rather than loop.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+use crate::command::{CommandOutput, CommandResult};
+
+/// Most aliases defined at once
+pub const MAX_ALIASES: usize = 64;
+/// Longest alias name
+pub const MAX_ALIAS_NAME_LEN: usize = 32;
+/// Longest alias body, in bytes
+pub const MAX_ALIAS_LEN: usize = 1000;
+/// Most aliases one use can pass through, counting itself
+pub const MAX_ALIAS_DEPTH: usize = 8;
+/// Most commands one use of an alias may expand to
+pub const MAX_ALIAS_COMMANDS: usize = 256;
+/// Most positional arguments an alias takes, `$1` to `$9`
+pub const MAX_ALIAS_ARGS: usize = 9;
+
+thread_local! {
+    static CURRENT: RefCell<Aliases> = RefCell::new(Aliases::new());
+    /// Aliases being expanded on this thread, outermost first
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Whether `name` is a valid alias name: a letter, then letters, digits,
+/// `_`, or `-`
+///
+/// (Names of commands are valid here; `validate` refuses those.)
+pub fn is_valid_alias_name(name: &str) -> bool {
+    name.len() <= MAX_ALIAS_NAME_LEN
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The command lines of an alias body: its `;`-separated parts, trimmed,
+/// leaving out empty ones
+pub fn alias_lines(body: &str) -> impl Iterator<Item = &str> {
+    body.split(';')
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+}
+
+/// How many arguments an alias body takes: the highest `$N` in it
+pub fn alias_params(body: &str) -> usize {
+    body.as_bytes()
+        .windows(2)
+        .filter(|pair| pair[0] == b'$' && (b'1'..=b'9').contains(&pair[1]))
+        .map(|pair| (pair[1] - b'0') as usize)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The arguments of an alias use: its words, a `"quoted"` one (quotes and
+/// all) running to its closing quote; None if a quote isn't closed or isn't
+/// followed by whitespace
+pub fn alias_args(args: &str) -> Option<Vec<&str>> {
+    let mut words = Vec::new();
+    let mut rest = args.trim_start();
+    while !rest.is_empty() {
+        let end = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')? + 2;
+                if !rest[end..].is_empty() && !rest[end..].starts_with(char::is_whitespace) {
+                    return None;
+                }
+                end
+            }
+            None => rest.find(char::is_whitespace).unwrap_or(rest.len()),
+        };
+        words.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some(words)
+}
+
+/// `line` with each `$N` replaced by argument N (from 1), if there is one
+pub fn substitute_args(line: &str, args: &[&str]) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(dollar) = rest.find('$') {
+        out.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let arg = after
+            .bytes()
+            .next()
+            .filter(u8::is_ascii_digit)
+            .and_then(|digit| (digit - b'0').checked_sub(1))
+            .and_then(|i| args.get(i as usize));
+        match arg {
+            Some(arg) => {
+                out.push_str(arg);
+                rest = &after[1..];
+            }
+            None => {
+                out.push('$');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Alias bodies by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Aliases {
+    bodies: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.bodies.get(name).map(String::as_str)
+    }
+
+    /// Define (or redefine) alias `name`
+    pub fn define(&mut self, name: &str, body: &str) -> Result<(), String> {
+        if self.bodies.len() >= MAX_ALIASES && !self.bodies.contains_key(name) {
+            return Err(format!("too many aliases (max {})", MAX_ALIASES));
+        }
+        self.bodies.insert(name.to_string(), body.to_string());
+        Ok(())
+    }
+
+    /// Remove alias `name`, returning whether it was defined
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.bodies.remove(name).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+}
+
+/// The body of alias `name` as parsing on this thread knows it
+pub fn alias_body(name: &str) -> Option<String> {
+    CURRENT.with(|current| current.borrow().get(name).map(str::to_string))
+}
+
+/// Run `f` with `aliases` as the aliases parsing knows, restoring the
+/// previous ones after
+pub fn with_aliases<R>(aliases: &Aliases, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.replace(aliases.clone()));
+    let result = f();
+    CURRENT.with(|current| current.replace(previous));
+    result
+}
+
+/// Run `f` (expanding one alias) one alias deeper, or return None without
+/// running it if that is deeper than `MAX_ALIAS_DEPTH`
+pub fn nested_alias<R>(f: impl FnOnce() -> Option<R>) -> Option<R> {
+    let depth = DEPTH.with(Cell::get);
+    if depth >= MAX_ALIAS_DEPTH {
+        return None;
+    }
+    DEPTH.with(|current| current.set(depth + 1));
+    let result = f();
+    DEPTH.with(|current| current.set(depth));
+    result
+}
+
+/// The one response to an alias from those of its commands, in order: the
+/// first error, or the outputs of those with any, joined by `; `
+pub fn alias_response(responses: impl IntoIterator<Item = CommandResult>) -> CommandResult {
+    let mut outputs = Vec::new();
+    for response in responses {
+        if let CommandOutput::Text(text) = response? {
+            outputs.push(text);
+        }
+    }
+    Ok(if outputs.is_empty() {
+        CommandOutput::Ok
+    } else {
+        CommandOutput::Text(outputs.join("; "))
+    })
+}
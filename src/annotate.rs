@@ -0,0 +1,93 @@
+//! Screenshot annotation (`--annotate`).
+//!
+//! This module handles:
+//! - Sizing the window so its canvas holds the image exactly (padded with
+//!   white where the image is smaller than the smallest window)
+//! - The red pen an annotation starts with
+//! - Where the annotated copy goes: beside the image, with `-annotated` added
+//!   to its name
+//! - Saving that copy, cropped back to the image, for Ctrl+S
+//!
+//! The image is the canvas's starting pixels, as with `--load`, so undo goes
+//! back as far as the bare screenshot and no further.
+
+use std::path::Path;
+
+use image::RgbaImage;
+
+use crate::canvas::{Dimensions, MIN_HEIGHT, MIN_WIDTH};
+use crate::command::save_region_image;
+use crate::snapshot::snapshot_format;
+use crate::{BOTTOM_TOOLBAR_HEIGHT, CANVAS_TOP, TITLE_BAR_HEIGHT};
+
+/// Palette index of the pen an annotation starts with (red)
+pub const ANNOTATE_COLOR: usize = 2;
+/// Brush size an annotation starts with, thick enough to stand out
+pub const ANNOTATE_BRUSH_SIZE: usize = 3;
+/// Added to the image's file name for the annotated copy
+pub const ANNOTATED_SUFFIX: &str = "-annotated";
+
+/// Where the annotated copy of the image at `path` is saved: beside it, as
+/// `<name>-annotated.<ext>`, or `.png` for formats snapshots can't write
+pub fn annotated_path(path: &str) -> String {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let ext = match path.extension() {
+        Some(ext) if snapshot_format(&path.display().to_string()).is_ok() => {
+            ext.to_string_lossy().into_owned()
+        }
+        _ => "png".to_string(),
+    };
+    path.with_file_name(format!("{}{}.{}", stem, ANNOTATED_SUFFIX, ext))
+        .display()
+        .to_string()
+}
+
+/// An image being annotated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Where the annotated copy is saved (see `annotated_path`)
+    pub output: String,
+    /// The image's size, which the saved copy is cropped to
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Annotation {
+    /// Annotate `img`, read from `path`
+    pub fn new(path: &str, img: &RgbaImage) -> Self {
+        Annotation {
+            output: annotated_path(path),
+            width: img.width() as usize,
+            height: img.height() as usize,
+        }
+    }
+
+    /// The window whose canvas holds the image, at least the smallest window;
+    /// an image too large for the largest window is an error
+    pub fn window_size(&self) -> Result<Dimensions, String> {
+        if self.width == 0 || self.height == 0 {
+            return Err("an empty image can't be annotated".to_string());
+        }
+        let height = self.height + TITLE_BAR_HEIGHT + BOTTOM_TOOLBAR_HEIGHT;
+        Dimensions::new(self.width.max(MIN_WIDTH), height.max(MIN_HEIGHT)).map_err(|e| {
+            format!(
+                "image {}x{} is too large to annotate: {}",
+                self.width, self.height, e
+            )
+        })
+    }
+
+    /// Save the image's part of the canvas in `buffer` to `output`
+    pub fn save(&self, buffer: &[u32]) -> Result<(), String> {
+        save_region_image(
+            buffer,
+            (0, CANVAS_TOP),
+            (self.width - 1, CANVAS_TOP + self.height - 1),
+            &self.output,
+        )
+    }
+}
@@ -0,0 +1,59 @@
+//! Announcing that displai is (re)started and ready for commands.
+//!
+//! This module handles:
+//! - The instance ID that tells one run of displai from the next
+//! - The ready file (`--ready-file`), written atomically once the socket is
+//!   listening and removed on a clean exit
+//! - The UDP beacon (`--beacon HOST:PORT`), one datagram sent at startup
+//!
+//! Both carry the line a subscriber gets as its first event (`ready <instance>
+//! [socket]`), so a client daemon can watch for any of them, see an instance
+//! it hasn't drawn for, and send its scene again.
+
+use std::io;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An ID for this run of displai: its process ID and the millisecond it
+/// started, so a restart never reuses one even if the pid comes round again
+pub fn instance_id() -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis());
+    format!("{}-{}", std::process::id(), started)
+}
+
+/// Whether `addr` is a `HOST:PORT` a beacon can be sent to
+pub fn is_beacon_address(addr: &str) -> bool {
+    addr.rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p > 0))
+}
+
+/// Write `line` to the ready file at `path`, by renaming a finished file
+/// into place so a watcher never reads it half written
+pub fn write_ready_file(path: &str, line: &str) -> io::Result<()> {
+    let partial = format!("{}.{}.tmp", path, std::process::id());
+    std::fs::write(&partial, format!("{}\n", line))?;
+    std::fs::rename(&partial, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&partial);
+    })
+}
+
+/// Remove the ready file at `path`, so it doesn't announce a stopped process
+pub fn remove_ready_file(path: &str) {
+    if Path::new(path).exists() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Send `line` as one UDP datagram to `addr` (`HOST:PORT`)
+pub fn send_beacon(addr: &str, line: &str) -> io::Result<()> {
+    let socket = UdpSocket::bind(if addr.starts_with('[') {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    })?;
+    socket.send_to(line.as_bytes(), addr)?;
+    Ok(())
+}
@@ -0,0 +1,307 @@
+//! Anti-aliased rendering.
+//!
+//! This module handles:
+//! - Whether drawing on this thread is anti-aliased (`aa on|off`, `--aa`)
+//! - How much of each pixel a shape covers: brush dots and lines by their
+//!   distance from pixel centers, ellipses and rounded boxes by an estimate
+//!   of their signed distance, and polygons by their spans across sub-rows
+//! - Compositing a color (or a gradient's colors) onto the canvas by that
+//!   coverage
+//!
+//! Shapes are drawn from several pieces (a circle's outline from many short
+//! brush lines, an arrow from its shaft and heads), and where pieces overlap
+//! their soft edges would blend twice and darken. So each shape gathers its
+//! coverage in one mask, keeping the most any piece gives a pixel, and
+//! blends once when it is done. Off (the default), the drawing functions set
+//! hard pixels exactly as before. A translucent color, or a blend mode other
+//! than normal, has the same problem with hard pixels, so those are gathered
+//! in a mask too (each at full coverage) and composited once.
+
+use std::cell::{Cell, RefCell};
+
+use crate::blend::composite;
+use crate::gradient::Paint;
+use crate::{canvas_bottom, window_width, CANVAS_TOP};
+
+/// Sub-rows each pixel row is sampled at when covering a polygon
+const POLYGON_SUBROWS: usize = 16;
+
+thread_local! {
+    static ANTIALIAS: Cell<bool> = const { Cell::new(false) };
+    static COVERAGE: RefCell<Option<Coverage>> = const { RefCell::new(None) };
+}
+
+/// Whether drawing on this thread is anti-aliased
+pub fn antialiasing() -> bool {
+    ANTIALIAS.with(Cell::get)
+}
+
+/// Run `f` with anti-aliasing on or off, restoring the previous setting after
+pub fn with_antialiasing<R>(on: bool, f: impl FnOnce() -> R) -> R {
+    let previous = ANTIALIAS.with(|current| current.replace(on));
+    let result = f();
+    ANTIALIAS.with(|current| current.set(previous));
+    result
+}
+
+/// Coverage gathered for the shape being drawn, by canvas pixel
+struct Coverage {
+    alpha: Vec<f32>,
+    /// First and last column and row covered
+    bounds: Option<(usize, usize, usize, usize)>,
+}
+
+impl Coverage {
+    /// Raise (x, y)'s coverage to `alpha`, if it is on the canvas
+    fn add(&mut self, x: isize, y: isize, alpha: f32) {
+        let (width, bottom) = (window_width(), canvas_bottom());
+        if alpha <= 0.0
+            || x < 0
+            || x >= width as isize
+            || y < CANVAS_TOP as isize
+            || y >= bottom as isize
+        {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let i = y * width + x;
+        self.alpha[i] = self.alpha[i].max(alpha.min(1.0));
+        self.bounds = Some(match self.bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    }
+}
+
+/// Draw one shape in `paint` (a color, or a gradient placed over the shape)
+/// with `draw`
+///
+/// With anti-aliasing on, or paint that doesn't just replace pixels (see
+/// `replaces`), the coverage `draw` adds (through the `cover_` functions and
+/// `set_pixel`) is composited into `buffer` once it returns, each pixel in
+/// its color from the paint; shapes drawn within another's `draw` add to its
+/// mask, and take its paint. Otherwise this just runs `draw`.
+pub fn draw_covered<R>(
+    buffer: &mut [u32],
+    paint: impl Into<Paint>,
+    draw: impl FnOnce(&mut [u32]) -> R,
+) -> R {
+    let paint = paint.into();
+    let open = COVERAGE.with(|coverage| coverage.borrow().is_some());
+    if open || (!antialiasing() && paint.replaces()) {
+        return draw(buffer);
+    }
+    COVERAGE.with(|coverage| {
+        *coverage.borrow_mut() = Some(Coverage {
+            alpha: vec![0.0; buffer.len()],
+            bounds: None,
+        })
+    });
+    let result = draw(buffer);
+    let Some(coverage) = COVERAGE.with(|coverage| coverage.borrow_mut().take()) else {
+        return result;
+    };
+    if let Some((x0, y0, x1, y1)) = coverage.bounds {
+        let width = window_width();
+        for y in y0..=y1 {
+            let row = y * width + x0..=y * width + x1;
+            let pixels = buffer[row.clone()].iter_mut().zip(&coverage.alpha[row]);
+            for (x, (pixel, &alpha)) in (x0..).zip(pixels) {
+                if alpha > 0.0 {
+                    *pixel = composite(*pixel, paint.at(x, y), alpha);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Cover all of pixel (x, y) in the shape being drawn by `draw_covered`;
+/// false if no shape is gathering coverage
+pub fn cover_pixel(x: usize, y: usize) -> bool {
+    COVERAGE.with(|mask| match mask.borrow_mut().as_mut() {
+        Some(mask) => {
+            mask.add(
+                x.min(isize::MAX as usize) as isize,
+                y.min(isize::MAX as usize) as isize,
+                1.0,
+            );
+            true
+        }
+        None => false,
+    })
+}
+
+/// Add coverage to the shape being drawn by `draw_covered`, for each pixel
+/// in the box (clipped to the canvas) from `coverage` of its center
+fn cover_box((left, top, right, bottom): (f64, f64, f64, f64), coverage: impl Fn(f64, f64) -> f32) {
+    let (width, canvas_end) = (window_width() as f64, canvas_bottom() as f64);
+    let first_x = left.floor().max(0.0);
+    let last_x = right.ceil().min(width - 1.0);
+    let first_y = top.floor().max(CANVAS_TOP as f64);
+    let last_y = bottom.ceil().min(canvas_end - 1.0);
+    if !(first_x <= last_x && first_y <= last_y) {
+        return;
+    }
+    COVERAGE.with(|mask| {
+        let mut mask = mask.borrow_mut();
+        let Some(mask) = mask.as_mut() else {
+            return;
+        };
+        for y in first_y as isize..=last_y as isize {
+            for x in first_x as isize..=last_x as isize {
+                mask.add(x, y, coverage(x as f64, y as f64));
+            }
+        }
+    });
+}
+
+/// Length of (dx, dy), by `sqrt` (which IEEE 754 rounds exactly, unlike
+/// `hypot`), so coverage is the same on every platform
+fn length(dx: f64, dy: f64) -> f64 {
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Coverage of a pixel whose center lies `distance` outside an edge
+/// (negative inside)
+fn edge_coverage(distance: f64) -> f32 {
+    (0.5 - distance).clamp(0.0, 1.0) as f32
+}
+
+/// Cover a line from (x0, y0) to (x1, y1) with round ends, `radius` either
+/// side of the pixel centers it joins
+pub fn cover_capsule(x0: f64, y0: f64, x1: f64, y1: f64, radius: f64) {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let length2 = dx * dx + dy * dy;
+    let bounds = (
+        x0.min(x1) - radius - 1.0,
+        y0.min(y1) - radius - 1.0,
+        x0.max(x1) + radius + 1.0,
+        y0.max(y1) + radius + 1.0,
+    );
+    cover_box(bounds, |x, y| {
+        // Distance to the nearest point of the segment
+        let t = if length2 > 0.0 {
+            (((x - x0) * dx + (y - y0) * dy) / length2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        edge_coverage(length(x - (x0 + t * dx), y - (y0 + t * dy)) - radius)
+    });
+}
+
+/// Cover a disc of `radius` around the pixel center (cx, cy)
+pub fn cover_disc(cx: f64, cy: f64, radius: f64) {
+    cover_capsule(cx, cy, cx, cy, radius);
+}
+
+/// Cover an ellipse around (cx, cy) whose edge passes through pixel centers
+/// `rx` and `ry` from it
+pub fn cover_ellipse(cx: f64, cy: f64, rx: f64, ry: f64) {
+    if rx <= 0.0 || ry <= 0.0 {
+        return;
+    }
+    let bounds = (cx - rx - 1.0, cy - ry - 1.0, cx + rx + 1.0, cy + ry + 1.0);
+    cover_box(bounds, |x, y| {
+        let (nx, ny) = ((x - cx) / rx, (y - cy) / ry);
+        let level = nx * nx + ny * ny - 1.0;
+        // The level set's value over its slope approximates the distance
+        let slope = 2.0 * length(nx / rx, ny / ry);
+        if slope < f64::EPSILON {
+            return edge_coverage(-rx.min(ry));
+        }
+        edge_coverage(level / slope)
+    });
+}
+
+/// Cover a box from (left, top) to (right, bottom), pixels inclusive, with
+/// corners rounded through pixel centers `r` in from its sides
+pub fn cover_rounded_box(left: f64, top: f64, right: f64, bottom: f64, r: f64) {
+    let (cx, cy) = ((left + right) / 2.0, (top + bottom) / 2.0);
+    // Half extents of the covered area, its edges half a pixel out
+    let (hw, hh) = ((right - left) / 2.0 + 0.5, (bottom - top) / 2.0 + 0.5);
+    let r = r.min(hw).min(hh) + 0.5;
+    cover_box(
+        (left - 1.0, top - 1.0, right + 1.0, bottom + 1.0),
+        |x, y| {
+            let qx = (x - cx).abs() - hw + r;
+            let qy = (y - cy).abs() - hh + r;
+            let outside = length(qx.max(0.0), qy.max(0.0));
+            edge_coverage(outside + qx.max(qy).min(0.0) - r)
+        },
+    );
+}
+
+/// Cover a closed polygon (even-odd rule) whose vertices lie on pixel
+/// corners, pixel (x, y) spanning x..x+1 and y..y+1
+///
+/// Each row is sampled at `POLYGON_SUBROWS` sub-rows; along a sub-row, the
+/// span between two crossings covers the pixels it passes by how much of
+/// each it spans.
+pub fn cover_polygon(points: &[(f64, f64)]) {
+    if points.len() < 3 {
+        return;
+    }
+    let width = window_width();
+    let top = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let bottom = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let first_y = top.floor().max(CANVAS_TOP as f64) as usize;
+    let end_y = (bottom.ceil().max(0.0) as usize).min(canvas_bottom());
+    let step = 1.0 / POLYGON_SUBROWS as f64;
+    // Coverage of whole pixels (as a running difference) and of span ends
+    let mut runs = vec![0.0f64; width + 1];
+    let mut ends = vec![0.0f64; width + 1];
+    let mut edges: Vec<((f64, f64), (f64, f64))> = Vec::new();
+    let mut crossings: Vec<f64> = Vec::new();
+
+    for y in first_y..end_y {
+        let (row_top, row_bottom) = (y as f64, y as f64 + 1.0);
+        edges.clear();
+        for (i, &from) in points.iter().enumerate() {
+            let to = points[(i + 1) % points.len()];
+            if from.1.min(to.1) < row_bottom && from.1.max(to.1) >= row_top {
+                edges.push((from, to));
+            }
+        }
+        if edges.is_empty() {
+            continue;
+        }
+        runs.fill(0.0);
+        ends.fill(0.0);
+        for sub in 0..POLYGON_SUBROWS {
+            let yc = row_top + (sub as f64 + 0.5) * step;
+            crossings.clear();
+            for &((x0, y0), (x1, y1)) in &edges {
+                // Half-open test, so a vertex shared by two edges crosses once
+                if (y0 <= yc) != (y1 <= yc) {
+                    crossings.push(x0 + (yc - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+            for pair in crossings.chunks_exact(2) {
+                let a = pair[0].clamp(0.0, width as f64);
+                let b = pair[1].clamp(0.0, width as f64);
+                let (first, last) = (a.floor() as usize, b.floor() as usize);
+                if first == last {
+                    ends[first] += (b - a) * step;
+                    continue;
+                }
+                ends[first] += (first as f64 + 1.0 - a) * step;
+                ends[last] += (b - last as f64) * step;
+                runs[first + 1] += step;
+                runs[last] -= step;
+            }
+        }
+        COVERAGE.with(|mask| {
+            let mut mask = mask.borrow_mut();
+            let Some(mask) = mask.as_mut() else {
+                return;
+            };
+            let mut whole = 0.0;
+            for x in 0..width {
+                whole += runs[x];
+                mask.add(x as isize, y as isize, (whole + ends[x]) as f32);
+            }
+        });
+    }
+}
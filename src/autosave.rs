@@ -0,0 +1,276 @@
+//! Autosaving the session and restoring it on the next start.
+//!
+//! This module handles:
+//! - Where sessions are kept: `--session DIR`, and none without it, so
+//!   instances never save over each other
+//! - The tool settings worth keeping, written as the command lines that set
+//!   them, and applying those lines to a fresh `AppState`
+//! - `Autosave`, writing the canvas and settings every `AUTOSAVE_INTERVAL`
+//!   when either changed, on a background thread, and once more at exit
+//! - Reading a saved session back (restored unless `--no-restore`)
+//!
+//! A session is two files in its directory: `session.png`, the canvas, and
+//! `session.txt`, one setting per line (`edge 2`, `brush shape square`, the
+//! palette slots that differ from the default, and `tool rect`, as a keymap
+//! names the tool). Each file is written beside itself and renamed over the
+//! old one, so a crash mid-save leaves the last session whole.
+
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use image::RgbaImage;
+
+use crate::canvas::{with_dimensions, Dimensions};
+use crate::command::{save_canvas_image, Command};
+use crate::import::load_image;
+use crate::keymap::Action;
+use crate::palette::{with_palette, Palette};
+use crate::state::AppState;
+
+/// How often a changed session is saved
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// The canvas, in the session directory
+pub const SESSION_CANVAS: &str = "session.png";
+/// The tool settings, in the session directory
+pub const SESSION_SETTINGS: &str = "session.txt";
+
+/// The tool settings of `state` as the lines that set them again
+pub fn session_settings(state: &AppState) -> Vec<String> {
+    let default = Palette::new();
+    let mut lines: Vec<String> = (0..state.palette.colors().len())
+        .filter(|&i| state.palette.get(i) != default.get(i))
+        .map(|i| format!("palette set {} #{:06X}", i, state.palette.colors()[i]))
+        .collect();
+    let commands = [
+        Command::Edge(state.edge_color),
+        Command::Fill(state.fill_color),
+        Command::FillGradient(state.gradient),
+        Command::Size(state.brush_size),
+        Command::StrokeStyle(state.stroke_style),
+        Command::BrushShape(state.brush_shape),
+        Command::Shadow(state.shadow),
+        Command::Snap(state.snap),
+        Command::Antialias(state.antialias),
+        Command::Blend(state.blend),
+    ];
+    // Colors are written as indices into the session's own palette
+    with_palette(&state.palette, || {
+        lines.extend(commands.iter().map(Command::to_string));
+    });
+    lines.push(Action::Tool(state.current_tool).to_string());
+    lines
+}
+
+/// Apply lines written by `session_settings` to `state`
+///
+/// Fails on the first line that doesn't parse or is refused, naming it;
+/// lines before it stay applied.
+pub fn apply_session_settings(state: &mut AppState, lines: &[String]) -> Result<(), String> {
+    for (n, line) in lines.iter().enumerate() {
+        let error = |e: String| format!("line {}: {}: {}", n + 1, line, e);
+        if let Some(Action::Tool(tool)) = Action::parse(line) {
+            state.current_tool = tool;
+            continue;
+        }
+        let cmd = state.parse(line).map_err(|e| error(e.to_string()))?;
+        if !matches!(
+            cmd,
+            Command::Palette(_)
+                | Command::Edge(_)
+                | Command::Fill(_)
+                | Command::FillGradient(_)
+                | Command::Size(_)
+                | Command::StrokeStyle(_)
+                | Command::BrushShape(_)
+                | Command::Shadow(_)
+                | Command::Snap(_)
+                | Command::Antialias(_)
+                | Command::Blend(_)
+        ) {
+            return Err(error("not a tool setting".to_string()));
+        }
+        if let Err(e) = state.run_command(&cmd) {
+            return Err(error(e.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// A session read back from its directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedSession {
+    /// The canvas, without the title bar and toolbar
+    pub canvas: RgbaImage,
+    /// Lines for `apply_session_settings` (empty if none were saved)
+    pub settings: Vec<String>,
+}
+
+/// Read the session saved in `dir`, if there is one
+///
+/// There is none until a canvas has been saved there; a canvas without
+/// settings restores with the default tools.
+pub fn read_session(dir: &str) -> Result<Option<SavedSession>, String> {
+    let canvas_path = Path::new(dir).join(SESSION_CANVAS);
+    if !canvas_path.exists() {
+        return Ok(None);
+    }
+    let canvas = load_image(&canvas_path.to_string_lossy())?;
+    let settings_path = Path::new(dir).join(SESSION_SETTINGS);
+    let settings = match std::fs::read_to_string(&settings_path) {
+        Ok(text) => text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("cannot read {}: {}", settings_path.display(), e)),
+    };
+    Ok(Some(SavedSession { canvas, settings }))
+}
+
+/// Write the canvas portion of `buffer` (laid out for `dims`) and `settings`
+/// to `dir`, creating it if needed
+pub fn write_session(
+    dir: &str,
+    buffer: &[u32],
+    dims: Dimensions,
+    settings: &[String],
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir, e))?;
+    let dir = Path::new(dir);
+    let replace = |from: PathBuf, to: &str| {
+        std::fs::rename(&from, dir.join(to))
+            .map_err(|e| format!("cannot replace {}: {}", dir.join(to).display(), e))
+    };
+
+    let canvas = dir.join("session.new.png");
+    with_dimensions(dims, || {
+        save_canvas_image(buffer, &canvas.to_string_lossy())
+    })?;
+    replace(canvas, SESSION_CANVAS)?;
+
+    let text = dir.join("session.new.txt");
+    let mut contents = settings.join("\n");
+    contents.push('\n');
+    std::fs::write(&text, contents)
+        .map_err(|e| format!("cannot write {}: {}", text.display(), e))?;
+    replace(text, SESSION_SETTINGS)
+}
+
+/// What was last written, to skip saves that would change nothing
+#[derive(Debug, Clone, PartialEq)]
+struct Saved {
+    pixels: Vec<u32>,
+    dims: Dimensions,
+    settings: Vec<String>,
+}
+
+/// Saves the session to its directory now and then while the app runs
+///
+/// Owned by the run loop, which offers the canvas and settings each pass;
+/// only changed sessions are written, at most once per interval, and the
+/// PNG is encoded on a background thread so drawing doesn't stall.
+pub struct Autosave {
+    dir: String,
+    interval: Duration,
+    last_offer: Instant,
+    /// What the last save (or the restored session) held
+    saved: Option<Saved>,
+    writer: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl Autosave {
+    /// Save to `dir` every `interval`, counting from `now`
+    pub fn new(dir: &str, interval: Duration, now: Instant) -> Self {
+        Autosave {
+            dir: dir.to_string(),
+            interval,
+            last_offer: now,
+            saved: None,
+            writer: None,
+        }
+    }
+
+    /// The directory sessions are saved to
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+
+    /// Take what was just restored as already saved, so it isn't written
+    /// again until something changes
+    pub fn mark_saved(&mut self, state: &AppState) {
+        self.saved = Some(Saved {
+            pixels: state.canvas.pixels().to_vec(),
+            dims: state.canvas.dimensions(),
+            settings: session_settings(state),
+        });
+    }
+
+    /// Whether a save is still being written
+    pub fn is_writing(&self) -> bool {
+        self.writer.as_ref().is_some_and(|w| !w.is_finished())
+    }
+
+    /// Start saving `state` if the interval has passed since the last check
+    /// and it changed since the last save
+    ///
+    /// Returns whether a save was started. A save still being written holds
+    /// the next one off until it is done; its error, if any, is returned as
+    /// `Err`.
+    pub fn offer(&mut self, state: &AppState, now: Instant) -> Result<bool, String> {
+        if now.duration_since(self.last_offer) < self.interval || self.is_writing() {
+            return Ok(false);
+        }
+        self.last_offer = now;
+        self.collect()?;
+        let Some(current) = self.changed(state) else {
+            return Ok(false);
+        };
+        let dir = self.dir.clone();
+        let Saved {
+            pixels,
+            dims,
+            settings,
+        } = current.clone();
+        self.writer = Some(std::thread::spawn(move || {
+            write_session(&dir, &pixels, dims, &settings)
+        }));
+        self.saved = Some(current);
+        Ok(true)
+    }
+
+    /// Wait for any save in progress, then save `state` if it changed
+    pub fn finish(mut self, state: &AppState) -> Result<(), String> {
+        self.collect()?;
+        if let Some(current) = self.changed(state) {
+            write_session(&self.dir, &current.pixels, current.dims, &current.settings)?;
+        }
+        Ok(())
+    }
+
+    /// `state` as it would be saved, unless that is what was saved last
+    fn changed(&self, state: &AppState) -> Option<Saved> {
+        let (dims, settings) = (state.canvas.dimensions(), session_settings(state));
+        let unchanged = self.saved.as_ref().is_some_and(|saved| {
+            saved.dims == dims
+                && saved.settings == settings
+                && saved.pixels[..] == state.canvas.pixels()[..]
+        });
+        (!unchanged).then(|| Saved {
+            pixels: state.canvas.pixels().to_vec(),
+            dims,
+            settings,
+        })
+    }
+
+    /// Wait for the save in progress, if any, returning its error
+    fn collect(&mut self) -> Result<(), String> {
+        match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err("session writer panicked".to_string()),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Bezier tool input.
+//!
+//! This module handles:
+//! - Dragging out a curve's endpoints
+//! - Bending it by dragging its control point, which finishes the curve
+//! - Previewing the curve, and the handles joining its ends to the control
+//!   point
+//!
+//! Like the polygon tool, the preview is drawn onto the presented frame only;
+//! the curve reaches the canvas (and history) once the control point is let go.
+
+use crate::drawing::{bezier_points, draw_line, set_pixel};
+
+/// Shortest drag, on either axis, that places a curve's endpoints
+pub const MIN_BEZIER_DRAG: usize = 2;
+
+/// Where a curve being placed with the Bezier tool is
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Stage {
+    #[default]
+    Idle,
+    /// Dragging from the start point toward the end point
+    Ends {
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+    /// Endpoints placed; waiting for (or dragging) the control point
+    Bend {
+        start: (usize, usize),
+        end: (usize, usize),
+        control: (usize, usize),
+        dragging: bool,
+    },
+}
+
+/// A quadratic curve being placed with the Bezier tool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BezierBuilder {
+    stage: Stage,
+}
+
+impl BezierBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a press at (x, y): the start point of a new curve, or the
+    /// control point of one whose endpoints are placed
+    pub fn press(&mut self, x: usize, y: usize) {
+        self.stage = match self.stage {
+            Stage::Bend { start, end, .. } => Stage::Bend {
+                start,
+                end,
+                control: (x, y),
+                dragging: true,
+            },
+            _ => Stage::Ends {
+                start: (x, y),
+                end: (x, y),
+            },
+        };
+    }
+
+    /// Follow the pointer while the button is held
+    pub fn drag(&mut self, x: usize, y: usize) {
+        match &mut self.stage {
+            Stage::Ends { end, .. } => *end = (x, y),
+            Stage::Bend {
+                control,
+                dragging: true,
+                ..
+            } => *control = (x, y),
+            _ => {}
+        }
+    }
+
+    /// Handle the button's release at (x, y), returning the finished curve's
+    /// control points (start, control, end) when it bends the curve
+    ///
+    /// Releasing the first drag places the endpoints, with the control point
+    /// between them; a drag shorter than `MIN_BEZIER_DRAG` is dropped.
+    pub fn release(&mut self, x: usize, y: usize) -> Option<Vec<(usize, usize)>> {
+        self.drag(x, y);
+        match self.stage {
+            Stage::Ends { start, end } => {
+                let moved = start.0.abs_diff(end.0).max(start.1.abs_diff(end.1));
+                self.stage = if moved < MIN_BEZIER_DRAG {
+                    Stage::Idle
+                } else {
+                    Stage::Bend {
+                        start,
+                        end,
+                        control: ((start.0 + end.0) / 2, (start.1 + end.1) / 2),
+                        dragging: false,
+                    }
+                };
+                None
+            }
+            Stage::Bend {
+                start,
+                end,
+                control,
+                dragging: true,
+            } => {
+                self.stage = Stage::Idle;
+                Some(vec![start, control, end])
+            }
+            _ => None,
+        }
+    }
+
+    /// Abandon the curve in progress
+    pub fn cancel(&mut self) {
+        self.stage = Stage::Idle;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    /// Draw the curve as it stands as a 1px line: a straight line while the
+    /// endpoints are dragged, then the curve with its handles
+    pub fn draw_preview(&self, frame: &mut [u32], color: u32) {
+        match self.stage {
+            Stage::Idle => {}
+            Stage::Ends { start, end } => draw_line(frame, start.0, start.1, end.0, end.1, color),
+            Stage::Bend {
+                start,
+                end,
+                control,
+                ..
+            } => {
+                for (x, y) in bezier_points(&[start, control, end]) {
+                    set_pixel(frame, x, y, color);
+                }
+                draw_line(frame, start.0, start.1, control.0, control.1, color);
+                draw_line(frame, control.0, control.1, end.0, end.1, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Translucent colors and blend modes.
+//!
+//! This module handles:
+//! - Colors with an alpha channel (`#RRGGBBAA`), packed into the same `u32`
+//!   as every other color
+//! - The blend mode drawing on this thread uses (`blend normal|multiply|
+//!   screen|overlay`)
+//! - `composite`, which puts a drawn color over a canvas pixel
+//!
+//! A color is `0xRRGGBB`; a translucent one keeps its transparency (255
+//! minus its alpha) in the top byte, so palette entries, named colors, and
+//! `#RRGGBB` all stay opaque and unchanged. The canvas only ever holds opaque
+//! pixels: each drawn pixel is mixed with the one beneath by the blend mode,
+//! then laid over it by the color's alpha (source-over).
+
+use std::cell::Cell;
+use std::fmt;
+
+thread_local! {
+    static BLEND_MODE: Cell<BlendMode> = const { Cell::new(BlendMode::Normal) };
+}
+
+/// How a drawn color mixes with the canvas beneath it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The drawn color replaces the canvas
+    #[default]
+    Normal,
+    /// Channels multiplied, so drawing only darkens
+    Multiply,
+    /// Inverted channels multiplied, so drawing only lightens
+    Screen,
+    /// Multiply over dark channels and screen over light ones, raising
+    /// contrast
+    Overlay,
+}
+
+impl BlendMode {
+    /// Every mode, in the order the protocol lists them
+    pub const ALL: [BlendMode; 4] = [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Overlay,
+    ];
+
+    /// The mode's protocol name
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendMode::Normal => "normal",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Overlay => "overlay",
+        }
+    }
+
+    /// The mode called `name`
+    pub fn parse(name: &str) -> Option<BlendMode> {
+        BlendMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    /// One channel of `color` mixed with the channel `base` beneath it
+    fn mix(self, base: u32, color: u32) -> u32 {
+        // Products of two channels, rounded back to 0-255
+        let product = |a: u32, b: u32| (a * b + 127) / 255;
+        match self {
+            BlendMode::Normal => color,
+            BlendMode::Multiply => product(base, color),
+            BlendMode::Screen => 255 - product(255 - base, 255 - color),
+            BlendMode::Overlay if base < 128 => product(2 * base, color),
+            BlendMode::Overlay => 255 - product(2 * (255 - base), 255 - color),
+        }
+    }
+}
+
+impl fmt::Display for BlendMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The blend mode drawing on this thread uses
+pub fn blend_mode() -> BlendMode {
+    BLEND_MODE.with(Cell::get)
+}
+
+/// Run `f` with `mode` as the blend mode, restoring the previous one after
+pub fn with_blend_mode<R>(mode: BlendMode, f: impl FnOnce() -> R) -> R {
+    let previous = BLEND_MODE.with(|current| current.replace(mode));
+    let result = f();
+    BLEND_MODE.with(|current| current.set(previous));
+    result
+}
+
+/// Opacity of `color`, from 0 (invisible) to 255 (opaque)
+pub fn alpha(color: u32) -> u8 {
+    255 - (color >> 24) as u8
+}
+
+/// `color` with its opacity set to `alpha`
+pub fn with_alpha(color: u32, alpha: u8) -> u32 {
+    (color & 0xFFFFFF) | (255 - alpha as u32) << 24
+}
+
+/// Whether `color` is fully opaque
+pub fn is_opaque(color: u32) -> bool {
+    color <= 0xFFFFFF
+}
+
+/// `color` as hex: `#RRGGBB`, or `#RRGGBBAA` when it is translucent
+pub fn hex_color(color: u32) -> String {
+    if is_opaque(color) {
+        format!("#{:06X}", color)
+    } else {
+        format!("#{:06X}{:02X}", color & 0xFFFFFF, alpha(color))
+    }
+}
+
+/// Whether drawing `color` just replaces canvas pixels: it is opaque and the
+/// blend mode is normal
+pub fn replaces(color: u32) -> bool {
+    is_opaque(color) && blend_mode() == BlendMode::Normal
+}
+
+/// Blend `color` over `base` with coverage `alpha` in 0.0..=1.0
+pub fn blend(base: u32, color: u32, alpha: f32) -> u32 {
+    let channel = |shift: u32| {
+        let b = ((base >> shift) & 0xFF) as f32;
+        let c = ((color >> shift) & 0xFF) as f32;
+        ((b + (c - b) * alpha).round() as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+/// The opaque pixel left when `color` is drawn over `base` covering
+/// `coverage` (0.0..=1.0) of it, mixed by the blend mode
+pub fn composite(base: u32, color: u32, coverage: f32) -> u32 {
+    let mode = blend_mode();
+    let mixed = match mode {
+        BlendMode::Normal => color & 0xFFFFFF,
+        _ => [16, 8, 0].into_iter().fold(0, |mixed, shift| {
+            let channel = |c: u32| (c >> shift) & 0xFF;
+            mixed | mode.mix(channel(base), channel(color)) << shift
+        }),
+    };
+    let opacity = alpha(color) as f32 / 255.0 * coverage;
+    if opacity >= 1.0 {
+        mixed
+    } else {
+        blend(base, mixed, opacity)
+    }
+}
@@ -0,0 +1,121 @@
+//! Brush shapes.
+//!
+//! This module handles:
+//! - `BrushShape`, set by `brush shape circle|square|slash|spray` or the
+//!   toolbar's brush button
+//! - The shape brush dots and lines on this thread are stamped with
+//! - Where a spray stamp's specks land
+//!
+//! Every brush line is a row of stamps, one per pixel along it (see
+//! `draw_brush_line`), so a shape only needs to say what one stamp covers.
+//! Spray specks are scattered by a generator seeded from the stamp's
+//! position, so the same stroke sprays the same specks every time it is
+//! drawn, replayed, or redone.
+
+use std::cell::Cell;
+use std::fmt;
+
+thread_local! {
+    static BRUSH_SHAPE: Cell<BrushShape> = const { Cell::new(BrushShape::Circle) };
+}
+
+/// What one stamp of the brush covers, `size - 1` pixels out from its center
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BrushShape {
+    /// A filled disc (the default)
+    #[default]
+    Circle,
+    /// A filled square
+    Square,
+    /// A 45° nib like a calligraphy pen's, `/`: thin along it, broad across
+    Slash,
+    /// Specks scattered inside the disc
+    Spray,
+}
+
+impl BrushShape {
+    /// Every shape, in the order the protocol lists them and the toolbar
+    /// button cycles through them
+    pub const ALL: [BrushShape; 4] = [
+        BrushShape::Circle,
+        BrushShape::Square,
+        BrushShape::Slash,
+        BrushShape::Spray,
+    ];
+
+    /// The shape's protocol name
+    pub fn name(self) -> &'static str {
+        match self {
+            BrushShape::Circle => "circle",
+            BrushShape::Square => "square",
+            BrushShape::Slash => "slash",
+            BrushShape::Spray => "spray",
+        }
+    }
+
+    /// The shape called `name`
+    pub fn parse(name: &str) -> Option<BrushShape> {
+        BrushShape::ALL
+            .into_iter()
+            .find(|shape| shape.name() == name)
+    }
+
+    /// The shape after this one on the toolbar button, wrapping around
+    pub fn next(self) -> BrushShape {
+        let index = BrushShape::ALL.iter().position(|&shape| shape == self);
+        BrushShape::ALL[index.map_or(0, |i| (i + 1) % BrushShape::ALL.len())]
+    }
+}
+
+impl fmt::Display for BrushShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The brush shape drawing on this thread stamps with
+pub fn brush_shape() -> BrushShape {
+    BRUSH_SHAPE.with(Cell::get)
+}
+
+/// Run `f` with `shape` as the brush shape, restoring the previous one after
+pub fn with_brush_shape<R>(shape: BrushShape, f: impl FnOnce() -> R) -> R {
+    let previous = BRUSH_SHAPE.with(|current| current.replace(shape));
+    let result = f();
+    BRUSH_SHAPE.with(|current| current.set(previous));
+    result
+}
+
+/// Offsets from its center of the specks a spray stamp of `radius` at
+/// (x, y) puts down: about half a speck per pixel of radius, all within it
+pub fn spray_offsets(x: usize, y: usize, radius: usize) -> Vec<(isize, isize)> {
+    if radius == 0 {
+        return vec![(0, 0)];
+    }
+    let r = radius as i64;
+    let mut seed =
+        (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (y as u64) ^ (radius as u64) << 48;
+    let mut next = || {
+        // splitmix64
+        seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    let span = (2 * r + 1) as u64;
+    let wanted = (radius / 2).max(1);
+    let mut specks = Vec::with_capacity(wanted);
+    // Points of the square around the disc, kept if inside it (most are)
+    for _ in 0..wanted * 4 {
+        let dx = (next() % span) as i64 - r;
+        let dy = (next() % span) as i64 - r;
+        if dx * dx + dy * dy <= r * r {
+            specks.push((dx as isize, dy as isize));
+            if specks.len() == wanted {
+                break;
+            }
+        }
+    }
+    specks
+}
@@ -0,0 +1,187 @@
+//! Canvas pixel storage for the displai application.
+//!
+//! This module handles:
+//! - The `Canvas` type that owns a window-sized pixel buffer
+//! - Slice access so drawing primitives can operate on it directly
+//! - `Dimensions`, the window size, and the per-thread current size that
+//!   drawing, UI, and export code lay themselves out against
+//!
+//! Drawing functions take plain `&mut [u32]` buffers, so the size they draw
+//! into is ambient: `window_width()`/`window_height()` report the current
+//! thread's size (800x600 unless changed with `set_dimensions` or
+//! `with_dimensions`). `AppState::execute` scopes it to its own canvas.
+
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+
+use crate::{BOTTOM_TOOLBAR_HEIGHT, HEIGHT, TITLE_BAR_HEIGHT, WHITE, WIDTH};
+
+/// Narrowest supported window (the toolbar's rows must fit)
+pub const MIN_WIDTH: usize = 544;
+/// Shortest supported window (title bar, toolbar, and some canvas)
+pub const MIN_HEIGHT: usize = 200;
+/// Largest supported window side
+pub const MAX_DIMENSION: usize = 8192;
+
+/// Window size in pixels; the canvas is the area between title bar and toolbar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimensions {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Dimensions {
+    /// Check a size against `MIN_WIDTH`/`MIN_HEIGHT`/`MAX_DIMENSION`
+    pub fn new(width: usize, height: usize) -> Result<Self, String> {
+        if !(MIN_WIDTH..=MAX_DIMENSION).contains(&width)
+            || !(MIN_HEIGHT..=MAX_DIMENSION).contains(&height)
+        {
+            return Err(format!(
+                "size {}x{} out of range ({}x{} to {}x{})",
+                width, height, MIN_WIDTH, MIN_HEIGHT, MAX_DIMENSION, MAX_DIMENSION
+            ));
+        }
+        Ok(Dimensions { width, height })
+    }
+
+    /// Parse `WIDTHxHEIGHT` (e.g. `1920x1080`)
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (w, h) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("invalid size '{}' (expected WIDTHxHEIGHT)", s))?;
+        match (w.parse::<usize>(), h.parse::<usize>()) {
+            (Ok(w), Ok(h)) => Self::new(w, h),
+            _ => Err(format!("invalid size '{}' (expected WIDTHxHEIGHT)", s)),
+        }
+    }
+
+    /// First row below the drawable canvas (top of the bottom toolbar)
+    pub fn canvas_bottom(&self) -> usize {
+        self.height - BOTTOM_TOOLBAR_HEIGHT
+    }
+
+    /// Height of the drawable canvas area
+    pub fn canvas_height(&self) -> usize {
+        self.canvas_bottom() - TITLE_BAR_HEIGHT
+    }
+
+    /// Number of pixels in a window-sized buffer
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+impl Default for Dimensions {
+    fn default() -> Self {
+        Dimensions {
+            width: WIDTH,
+            height: HEIGHT,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Dimensions> = const {
+        Cell::new(Dimensions {
+            width: WIDTH,
+            height: HEIGHT,
+        })
+    };
+}
+
+/// The window size drawing and UI code on this thread lay out against
+pub fn dimensions() -> Dimensions {
+    CURRENT.with(Cell::get)
+}
+
+/// Set the window size for this thread (the app sets it once at startup)
+pub fn set_dimensions(dims: Dimensions) {
+    CURRENT.with(|current| current.set(dims));
+}
+
+/// Run `f` with this thread's window size set to `dims`, restoring it afterwards
+pub fn with_dimensions<R>(dims: Dimensions, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.replace(dims));
+    let result = f();
+    set_dimensions(previous);
+    result
+}
+
+/// Current window width
+pub fn window_width() -> usize {
+    dimensions().width
+}
+
+/// Current window height
+pub fn window_height() -> usize {
+    dimensions().height
+}
+
+/// Current first row below the drawable canvas
+pub fn canvas_bottom() -> usize {
+    dimensions().canvas_bottom()
+}
+
+/// A window-sized pixel buffer (index = `y * width + x`, format `0xRRGGBB`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    dims: Dimensions,
+    pixels: Vec<u32>,
+}
+
+impl Canvas {
+    /// Create a new all-white canvas for a `width` x `height` window
+    ///
+    /// Panics if the size is outside the range `Dimensions::new` accepts.
+    pub fn new(width: usize, height: usize) -> Self {
+        let dims = Dimensions::new(width, height).expect("invalid canvas size");
+        Self::with_size(dims)
+    }
+
+    /// Create a new all-white canvas of the given size
+    pub fn with_size(dims: Dimensions) -> Self {
+        Canvas {
+            dims,
+            pixels: vec![WHITE; dims.pixel_count()],
+        }
+    }
+
+    /// Size of the window this canvas covers
+    pub fn dimensions(&self) -> Dimensions {
+        self.dims
+    }
+
+    pub fn width(&self) -> usize {
+        self.dims.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.dims.height
+    }
+
+    /// Get the raw pixel buffer
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+}
+
+impl Default for Canvas {
+    /// An 800x600 canvas
+    fn default() -> Self {
+        Self::with_size(Dimensions::default())
+    }
+}
+
+impl Deref for Canvas {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        &self.pixels
+    }
+}
+
+impl DerefMut for Canvas {
+    fn deref_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+}
@@ -0,0 +1,152 @@
+//! High-frequency frame capture for the displai application.
+//!
+//! This module handles:
+//! - `FrameCapture`, appending raw canvas frames to a single growing file
+//! - `read_capture`, decoding a capture file back into frames
+//!
+//! Capturing every frame as a PNG would allocate and recompress each time, so
+//! frames are written uncompressed through one buffered writer with a reused
+//! scratch buffer. Convert the file to video or PNGs offline.
+//!
+//! File format: the magic `DSPLRAW1`, then width and height as little-endian
+//! `u32`, then each frame as `width * height` RGB byte triples.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::command::{failed, reply, CommandResult};
+use crate::{canvas_bottom, window_width, CANVAS_TOP};
+
+/// Magic bytes at the start of every capture file
+pub const CAPTURE_MAGIC: &[u8; 8] = b"DSPLRAW1";
+
+/// An open capture file receiving canvas frames
+pub struct FrameCapture {
+    path: String,
+    writer: BufWriter<File>,
+    scratch: Vec<u8>,
+    frames: u64,
+}
+
+impl FrameCapture {
+    /// Create (or truncate) a capture file at `path` and write its header
+    pub fn start(path: &str) -> Result<Self, String> {
+        let width = window_width();
+        let height = canvas_bottom() - CANVAS_TOP;
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut writer = BufWriter::with_capacity(width * height * 3, file);
+        writer
+            .write_all(CAPTURE_MAGIC)
+            .and_then(|_| writer.write_all(&(width as u32).to_le_bytes()))
+            .and_then(|_| writer.write_all(&(height as u32).to_le_bytes()))
+            .map_err(|e| e.to_string())?;
+
+        Ok(FrameCapture {
+            path: path.to_string(),
+            writer,
+            scratch: Vec::with_capacity(width * height * 3),
+            frames: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of frames written so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Append the canvas portion of a window buffer as one frame
+    pub fn write_frame(&mut self, buffer: &[u32]) -> Result<(), String> {
+        self.scratch.clear();
+        for &pixel in &buffer[CANVAS_TOP * window_width()..canvas_bottom() * window_width()] {
+            self.scratch
+                .extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+        }
+        self.writer
+            .write_all(&self.scratch)
+            .map_err(|e| e.to_string())?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flush and close the file, returning the number of frames written
+    pub fn finish(mut self) -> Result<u64, String> {
+        self.writer.flush().map_err(|e| e.to_string())?;
+        Ok(self.frames)
+    }
+}
+
+/// Apply a `capture` command: start (restarting any active capture) or stop,
+/// returning the protocol response
+pub fn apply_capture_command(
+    capture: &mut Option<FrameCapture>,
+    target: Option<&str>,
+) -> CommandResult {
+    let finished = capture.take().map(|active| {
+        let path = active.path().to_string();
+        (path, active.finish())
+    });
+    match target {
+        Some(path) => match FrameCapture::start(path) {
+            Ok(started) => {
+                *capture = Some(started);
+                reply(format!("capturing {}", path))
+            }
+            Err(e) => failed(e),
+        },
+        None => match finished {
+            Some((path, Ok(frames))) => reply(format!("captured {} frames to {}", frames, path)),
+            Some((_, Err(e))) => failed(e),
+            None => failed("not capturing"),
+        },
+    }
+}
+
+/// A decoded capture file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureFile {
+    pub width: usize,
+    pub height: usize,
+    /// Frames as `0xRRGGBB` pixels (index = `y * width + x`)
+    pub frames: Vec<Vec<u32>>,
+}
+
+/// Read a capture file written by `FrameCapture`
+pub fn read_capture(path: &str) -> Result<CaptureFile, String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut header = [0u8; 16];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| "capture header truncated".to_string())?;
+    if &header[..8] != CAPTURE_MAGIC {
+        return Err("not a displai capture file".to_string());
+    }
+    let width = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    let height = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).map_err(|e| e.to_string())?;
+    let frame_bytes = width * height * 3;
+    if frame_bytes == 0 || data.len() % frame_bytes != 0 {
+        return Err("capture ends with a partial frame".to_string());
+    }
+
+    let frames = data
+        .chunks(frame_bytes)
+        .map(|frame| {
+            frame
+                .chunks(3)
+                .map(|rgb| (rgb[0] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[2] as u32)
+                .collect()
+        })
+        .collect();
+
+    Ok(CaptureFile {
+        width,
+        height,
+        frames,
+    })
+}
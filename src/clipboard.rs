@@ -0,0 +1,149 @@
+//! The system clipboard.
+//!
+//! This module handles:
+//! - Putting canvas pixels on the system clipboard as an image, for `copy`
+//!   and Ctrl+C
+//! - Taking an image off the system clipboard, for `paste` and Ctrl+V
+//!
+//! The system clipboard is behind the `clipboard` feature. Without it `copy`
+//! and `paste` answer with an error, and Ctrl+C/Ctrl+V in the window work
+//! within the selection tool's own clipboard only.
+
+use image::RgbaImage;
+
+use crate::command::{failed, reply, CommandOutput, CommandResult};
+use crate::import::paste_image;
+use crate::locks::Region;
+use crate::selection::Clip;
+use crate::{canvas_bottom, window_width, CANVAS_TOP, WHITE};
+
+/// Why `copy` and `paste` fail in a build without the `clipboard` feature
+#[cfg(not(feature = "clipboard"))]
+const NO_CLIPBOARD: &str = "the system clipboard needs a build with --features clipboard";
+
+/// Whether this build can reach the system clipboard (the `clipboard` feature)
+pub fn clipboard_supported() -> bool {
+    cfg!(feature = "clipboard")
+}
+
+/// A clip's pixels as an opaque RGBA image
+pub fn clip_image(clip: &Clip) -> RgbaImage {
+    RgbaImage::from_fn(clip.width as u32, clip.height as u32, |x, y| {
+        let pixel = clip.pixels[y as usize * clip.width + x as usize];
+        image::Rgba([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 255])
+    })
+}
+
+/// An image as a clip, its transparent parts laid over white
+pub fn image_clip(img: &RgbaImage) -> Clip {
+    let blend = |over: u8, alpha: u8, under: u32| {
+        let (over, alpha, under) = (over as u32, alpha as u32, under & 0xFF);
+        (over * alpha + under * (255 - alpha) + 127) / 255
+    };
+    let pixels = img
+        .pixels()
+        .map(|p| {
+            let [r, g, b, a] = p.0;
+            blend(r, a, WHITE >> 16) << 16 | blend(g, a, WHITE >> 8) << 8 | blend(b, a, WHITE)
+        })
+        .collect();
+    Clip {
+        width: img.width() as usize,
+        height: img.height() as usize,
+        pixels,
+    }
+}
+
+/// Put `clip` on the system clipboard as an image
+pub fn copy_to_clipboard(clip: &Clip) -> Result<(), String> {
+    #[cfg(feature = "clipboard")]
+    {
+        let img = clip_image(clip);
+        with_clipboard(|clipboard| {
+            clipboard.set_image(arboard::ImageData {
+                width: img.width() as usize,
+                height: img.height() as usize,
+                bytes: img.into_raw().into(),
+            })
+        })
+    }
+    #[cfg(not(feature = "clipboard"))]
+    {
+        let _ = clip;
+        Err(NO_CLIPBOARD.to_string())
+    }
+}
+
+/// The image on the system clipboard
+pub fn paste_from_clipboard() -> Result<RgbaImage, String> {
+    #[cfg(feature = "clipboard")]
+    {
+        let data = with_clipboard(|clipboard| clipboard.get_image())?;
+        RgbaImage::from_raw(
+            data.width as u32,
+            data.height as u32,
+            data.bytes.into_owned(),
+        )
+        .ok_or_else(|| "the clipboard image is damaged".to_string())
+    }
+    #[cfg(not(feature = "clipboard"))]
+    Err(NO_CLIPBOARD.to_string())
+}
+
+/// Copy the canvas, or the rectangle between two corners (inclusive, either
+/// order), to the system clipboard, returning the size copied
+pub fn copy_canvas(
+    buffer: &[u32],
+    corners: Option<(usize, usize, usize, usize)>,
+) -> Result<(usize, usize), String> {
+    let region = match corners {
+        Some((x1, y1, x2, y2)) => Region::around(x1, y1, x2, y2, 0),
+        None => Region::new(0, CANVAS_TOP, window_width(), canvas_bottom() - CANVAS_TOP),
+    };
+    copy_to_clipboard(&Clip::copy(buffer, region))?;
+    Ok((region.width, region.height))
+}
+
+/// Answer `copy`: `copied WxH`, or an error
+pub fn copy_response(result: &Result<(usize, usize), String>) -> CommandResult {
+    match result {
+        Ok((width, height)) => reply(format!("copied {}x{}", width, height)),
+        Err(e) => failed(e.clone()),
+    }
+}
+
+/// Draw the system clipboard's image with its top-left corner at (x, y),
+/// failing if there is none
+pub fn paste_clipboard(buffer: &mut [u32], x: usize, y: usize) -> CommandResult {
+    let img = paste_from_clipboard()?;
+    paste_image(buffer, &img, x, y, 1.0);
+    Ok(CommandOutput::Ok)
+}
+
+#[cfg(feature = "clipboard")]
+thread_local! {
+    /// The clipboard connection, opened on first use and kept so that what
+    /// was copied stays on offer (on X11 the owner must be around to hand it
+    /// over)
+    static CLIPBOARD: std::cell::RefCell<Option<arboard::Clipboard>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Run `f` on this thread's clipboard connection, opening it if need be
+#[cfg(feature = "clipboard")]
+fn with_clipboard<T>(
+    f: impl FnOnce(&mut arboard::Clipboard) -> Result<T, arboard::Error>,
+) -> Result<T, String> {
+    CLIPBOARD.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        if cell.is_none() {
+            let clipboard =
+                arboard::Clipboard::new().map_err(|e| format!("no system clipboard: {}", e))?;
+            *cell = Some(clipboard);
+        }
+        f(cell.as_mut().expect("clipboard was just opened")).map_err(|e| match e {
+            arboard::Error::ContentNotAvailable => "the clipboard holds no image".to_string(),
+            e => format!("clipboard: {}", e),
+        })
+    })
+}
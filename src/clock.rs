@@ -0,0 +1,78 @@
+//! Text shown by clock and countdown objects.
+//!
+//! This module handles:
+//! - Formatting a wall-clock time with a small `strftime`-style format
+//! - Formatting the time left on a countdown
+//!
+//! Clocks show UTC: the app has no time zone database, and a fixed zone keeps
+//! snapshots from different machines comparable.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Format used by `clock x,y` when none is given
+pub const DEFAULT_CLOCK_FORMAT: &str = "%H:%M:%S";
+/// Longest countdown, in seconds (99:59:59)
+pub const MAX_COUNTDOWN_SECS: u64 = 99 * 3600 + 59 * 60 + 59;
+
+/// Seconds since the Unix epoch (0 for times before it)
+pub fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Civil (year, month, day) of a day counted from 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Format a UTC time given as Unix seconds
+///
+/// Supports `%Y` (year), `%m` (month), `%d` (day), `%H` (hour), `%M`
+/// (minute), `%S` (second), and `%%`; anything else is copied as written.
+pub fn format_clock(format: &str, unix_secs: u64) -> String {
+    let secs_of_day = unix_secs % 86_400;
+    let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+    let mut out = String::with_capacity(format.len() + 8);
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&year.to_string()),
+            Some('m') => out.push_str(&format!("{:02}", month)),
+            Some('d') => out.push_str(&format!("{:02}", day)),
+            Some('H') => out.push_str(&format!("{:02}", secs_of_day / 3600)),
+            Some('M') => out.push_str(&format!("{:02}", secs_of_day / 60 % 60)),
+            Some('S') => out.push_str(&format!("{:02}", secs_of_day % 60)),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// Format the seconds left on a countdown as `MM:SS`, or `H:MM:SS` from an hour up
+pub fn format_countdown(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
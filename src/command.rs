@@ -5,9 +5,59 @@
 //! - Parsing commands from text input
 //! - Executing commands and modifying application state
 
-use crate::drawing::{clear_canvas, draw_brush_line, draw_circle, draw_shape_with_fill};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::alias::{
+    alias_args, alias_body, alias_lines, alias_params, is_valid_alias_name, nested_alias,
+    substitute_args, MAX_ALIAS_COMMANDS, MAX_ALIAS_LEN,
+};
+use crate::antialias::draw_covered;
+use crate::blend::{hex_color, is_opaque, BlendMode};
+use crate::brush::BrushShape;
+use crate::clipboard::{copy_canvas, copy_response, paste_clipboard};
+use crate::clock::{DEFAULT_CLOCK_FORMAT, MAX_COUNTDOWN_SECS};
+use crate::colors::{named_color_index, palette_index, parse_color_value};
+use crate::config::{parse_fps, MAX_FPS, MIN_FPS};
+use crate::dash::{StrokeStyle, DEFAULT_DASH, DEFAULT_DOT};
+use crate::dialog::{DialogKind, MAX_DIALOG_MESSAGE_LEN};
+use crate::drawing::{
+    clear_canvas, default_arrow_head, draw_arc_with_fill, draw_arrow, draw_bezier, draw_brush_dot,
+    draw_brush_line, draw_polygon_with_fill, draw_rounded_rect_with_fill, draw_shape_with_fill,
+    flood_fill, ngon_vertices, star_vertices, ArcShape, Arrow, RoundedRect, MAX_SIDES,
+};
+use crate::events::is_valid_event_name;
+use crate::filters::{apply_filter, Filter};
+use crate::gif::{is_valid_gif_interval, MAX_GIF_INTERVAL_MS, MIN_GIF_INTERVAL_MS};
+use crate::gradient::{Gradient, GradientShape};
+use crate::icc::{save_profiled, write_profiled, ColorProfile};
+use crate::import::{is_valid_image_scale, load_image, paste_image, MAX_IMAGE_SCALE};
+use crate::legend::{
+    draw_legend, legend_size, LegendEntry, MAX_LEGEND_ENTRIES, MAX_LEGEND_LABEL_LEN,
+};
+use crate::notify::{NotifyLevel, DEFAULT_NOTIFY_SECS, MAX_NOTIFY_LEN, MAX_NOTIFY_SECS};
+use crate::objects::{
+    CheckStyle, INPUT_HEIGHT, MAX_BLINK_MS, MAX_TEXT_SCALE, MIN_BLINK_MS, MIN_INPUT_WIDTH,
+    MIN_SLIDER_WIDTH, SLIDER_HEIGHT,
+};
+use crate::palette::{current_palette, PALETTE_LEN};
+use crate::panes::{PaneScale, Panes};
+use crate::poster::check_tile_grid;
+use crate::project::check_project_path;
+use crate::readback::{pick_response, pixel_response, region_response};
+use crate::record::check_replay_speed;
+use crate::resize::CanvasSize;
+use crate::shadow::{Shadow, DEFAULT_SHADOW_COLOR, MAX_SHADOW_BLUR, MAX_SHADOW_OFFSET};
+use crate::snap::{DEFAULT_SNAP_TOLERANCE, MAX_SNAP_TOLERANCE};
+use crate::snapshot::{base64_snapshot_response, snapshot_format, snapshot_response};
+use crate::sound::{DEFAULT_BEEP_FREQ, DEFAULT_BEEP_MS, MAX_BEEP_FREQ, MAX_BEEP_MS, MIN_BEEP_FREQ};
+use crate::tabs::{default_snapshot_path, TabCommand};
+use crate::transform::{transform_canvas, CanvasTransform};
+use crate::vars::is_valid_var_name;
 use crate::{
-    ToolMode, CANVAS_BOTTOM, CANVAS_TOP, COLOR_PALETTE, MAX_BRUSH_SIZE, MIN_BRUSH_SIZE, WIDTH,
+    canvas_bottom, dimensions, window_width, ToolMode, BLACK, CANVAS_TOP, MAX_BRUSH_SIZE,
+    MIN_BRUSH_SIZE,
 };
 
 /// A point with optional color and size overrides
@@ -15,18 +65,190 @@ use crate::{
 pub struct AttributedPoint {
     pub x: usize,
     pub y: usize,
-    pub color: Option<usize>, // None = use current edge color
-    pub size: Option<usize>,  // None = use current brush size
+    pub color: Option<u32>,  // 0xRRGGBB (None = use current edge color)
+    pub size: Option<usize>, // None = use current brush size
+}
+
+/// Operations on retained objects (`obj ...`, `clock`, `countdown`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectCommand {
+    /// Create a text object in the current edge color; replies with its ID
+    Text {
+        x: usize,
+        y: usize,
+        text: String,
+        scale: usize,
+    },
+    SetText {
+        id: usize,
+        text: String,
+    },
+    Delete(usize),
+    List,
+    /// Blink an object, shown and hidden for `ms` each (0 = stop blinking)
+    Blink {
+        id: usize,
+        ms: u64,
+    },
+    /// Create a clock showing the UTC time in a `format_clock` format
+    Clock {
+        x: usize,
+        y: usize,
+        format: String,
+    },
+    /// Create a `w` x `h` button that sends `event` to subscribers when clicked
+    Button {
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        label: String,
+        event: String,
+    },
+    /// Create a `w` wide slider that sets `var` between `min` and `max`
+    Slider {
+        x: usize,
+        y: usize,
+        w: usize,
+        min: i64,
+        max: i64,
+        var: String,
+    },
+    /// Create a checkbox or toggle switch that flips `var` between `on` and `off`
+    Check {
+        x: usize,
+        y: usize,
+        style: CheckStyle,
+        label: String,
+        var: String,
+    },
+    /// Create a `w` wide text input that edits `var` and submits it on Enter
+    Input {
+        x: usize,
+        y: usize,
+        w: usize,
+        var: String,
+    },
+    /// Create a countdown from `secs` seconds to zero
+    Countdown {
+        x: usize,
+        y: usize,
+        secs: u64,
+    },
+}
+
+/// Operations on named variables (`var ...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarCommand {
+    Set { name: String, value: String },
+    Get(String),
+}
+
+/// Operations on command aliases (`alias ...`, `unalias`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum AliasCommand {
+    /// Make `name` stand for the `;`-separated commands of `body`
+    Define { name: String, body: String },
+    /// Report an alias's body
+    Get(String),
+    /// Forget an alias
+    Remove(String),
+}
+
+/// Operations on the color palette (`palette ...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteCommand {
+    /// Replace the color in one slot
+    Set {
+        index: usize,
+        color: u32,
+    },
+    Get,
+    /// Restore the default colors
+    Reset,
+}
+
+/// Operations on the modal dialog (`dialog ...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogCommand {
+    /// Open a dialog asking `message`
+    Open { kind: DialogKind, message: String },
+    /// Close the open dialog without answering it
+    Close,
+}
+
+/// Operations on the animated GIF recording (`record gif ...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum GifCommand {
+    /// Record to `path`, a frame every `interval_ms` (None = on each change)
+    Start {
+        path: String,
+        interval_ms: Option<u64>,
+    },
+    /// Stop and finish encoding the GIF
+    Stop,
+}
+
+/// Operations on the split view (`compare ...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompareCommand {
+    /// Show the image at the path beside the canvas
+    Image(String),
+    /// Show the canvas as it is now beside the canvas as it changes
+    Checkpoint,
+    /// Move the divider to a column
+    Divider(usize),
+    /// Close the split view
+    Off,
+}
+
+/// Comparisons with a reference image (`diff ...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffCommand {
+    /// Reply with how many pixels differ from the image at the path
+    Image(String),
+    /// Tint pixels that differ from the image at the path, until turned off
+    Overlay(String),
+    /// Remove the overlay
+    OverlayOff,
+}
+
+/// Operations on the script step debugger (`debug ...`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugCommand {
+    /// Load the script at the path, to run one command per `step`
+    Step(String),
+    /// Reply with the next command to step
+    Status,
+    /// Unload the script
+    Stop,
 }
 
 /// Commands that can be sent via stdin
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
-    Snapshot,
-    Color(usize),        // Legacy: sets edge color
-    Edge(Option<usize>), // Set edge color (None = transparent)
-    Fill(Option<usize>), // Set fill color (None = transparent)
+    Snapshot(Option<String>), // Save the canvas image (None = canvas.png)
+    SnapshotBase64,           // Reply with the canvas PNG, base64-encoded
+    /// Save the rectangle between two corners (inclusive, either order) of
+    /// the canvas image (None = canvas.png)
+    SnapshotRegion {
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        path: Option<String>,
+    },
+    Color(usize),      // Legacy: sets edge color
+    Edge(Option<u32>), // Set edge color as 0xRRGGBB (None = transparent)
+    Fill(Option<u32>), // Set fill color as 0xRRGGBB (None = transparent)
+    /// Fill shapes with a gradient instead of one color, or stop (None;
+    /// applied by AppState)
+    FillGradient(Option<Gradient>),
     Size(usize),
+    /// Draw later outlines solid, dashed, or dotted (applied by AppState)
+    StrokeStyle(StrokeStyle),
+    /// Stamp later brush dots and lines in this shape (applied by AppState)
+    BrushShape(BrushShape),
     Stroke {
         x1: usize,
         y1: usize,
@@ -38,7 +260,20 @@ pub enum Command {
         y: usize,
     },
     Clear,
+    /// Flip, rotate, or scale everything drawn on the canvas
+    Canvas(CanvasTransform),
+    /// Crop or resize the canvas, changing the window's size (applied by
+    /// AppState, and by the session, which starts the history over)
+    CanvasSize(CanvasSize),
+    /// Run a filter over the canvas, or the rectangle between two corners
+    Filter {
+        filter: Filter,
+        region: Option<(usize, usize, usize, usize)>,
+    },
     State,
+    /// Where each pane lies and who holds it, and every region lock
+    /// (applied by AppState and the window loop)
+    Layout,
     // Shape commands
     Line {
         x1: usize,
@@ -57,6 +292,14 @@ pub enum Command {
         x2: usize,
         y2: usize,
     },
+    // Rectangle with corners rounded to radius r
+    RoundedRect {
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        r: usize,
+    },
     Circle {
         x: usize,
         y: usize,
@@ -74,13 +317,968 @@ pub enum Command {
         x2: usize,
         y2: usize,
     },
+    // Line from (x1, y1) with an arrowhead at (x2, y2), and at (x1, y1) too if `both`
+    Arrow {
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        head: Option<usize>, // Head length (None = sized to the brush)
+        both: bool,
+    },
+    // Arcs and pie slices, with angles in degrees clockwise from 3 o'clock
+    Arc {
+        x: usize,
+        y: usize,
+        r: usize,
+        start: f64,
+        end: f64,
+    },
+    Pie {
+        x: usize,
+        y: usize,
+        r: usize,
+        start: f64,
+        end: f64,
+    },
+    // Regular polygon of `sides` corners `r` from the center, turned `rotation`
+    // degrees clockwise from a corner straight up
+    Ngon {
+        x: usize,
+        y: usize,
+        r: usize,
+        sides: usize,
+        rotation: f64,
+    },
+    // Star of `points` tips `outer` from the center, with inner corners `inner` from it
+    Star {
+        x: usize,
+        y: usize,
+        outer: usize,
+        inner: usize,
+        points: usize,
+    },
+    Bucket {
+        x: usize,
+        y: usize,
+    }, // Flood-fill the region under the point with the fill color
+    Polygon(Vec<(usize, usize)>), // Closed polygon through three or more vertices
     // Batch commands for performance (with optional per-point color/size attributes)
     Polyline(Vec<AttributedPoint>), // Connected line segments
     Points(Vec<AttributedPoint>),   // Multiple dots
+    Bezier(Vec<(usize, usize)>),    // Quadratic (3 control points) or cubic (4) curve
+    Fps(u32),                       // Set the maximum frame rate (applied by the run loop)
+    Capture(Option<String>),        // Start raw frame capture to a path, or stop (None)
+    GroupBegin(String),             // Start a labeled group of commands (one history entry)
+    GroupEnd,
+    Undo, // Revert the most recent history entry (applied by the run loop)
+    Redo,
+    Stats(Option<bool>),    // Query stats (None) or show/hide the stats overlay
+    Shadow(Option<Shadow>), // Shadow beneath later shapes (None = off; applied by AppState)
+    Snap(Option<f64>),      // Shape snap for freehand strokes, with its tolerance (None = off)
+    Antialias(bool),        // Anti-aliased drawing on or off (applied by AppState)
+    Blend(BlendMode),       // How drawing mixes with the canvas (applied by AppState)
+    /// Space snapshots and exports are converted to and tagged with (None =
+    /// untagged sRGB; applied by AppState)
+    ColorProfile(Option<ColorProfile>),
+    Panes(Panes),                // Split the canvas into panes (applied by AppState)
+    InPane(usize, Box<Command>), // Run a drawing command in a pane, from its corner (`@N ...`)
+    Scale(Option<PaneScale>), // Set a pane's data range and axes, or drop it (None; applied by AppState)
+    Plot(Vec<(f64, f64)>),    // Polyline through points in a pane's data units
+    /// Append a sample to a pane's scrolling time series (applied by AppState)
+    TsPlot {
+        pane: usize,
+        value: f64,
+    },
+    /// Double-buffer a pane, or stop (applied by AppState)
+    Hold {
+        pane: usize,
+        on: bool,
+    },
+    /// Show everything drawn in a held pane since its last flip (applied by AppState)
+    Flip(usize),
+    Object(ObjectCommand), // Create, edit, or delete retained objects (applied by AppState)
+    Var(VarCommand),       // Set or query a variable used by text templates
+    Palette(PaletteCommand), // Reprogram or query the palette (applied by AppState)
+    Subscribe,             // Send this connection events (applied by the run loop)
+    Dialog(DialogCommand), // Open or close the modal dialog (applied by AppState)
+    /// Define, show, or remove an alias (applied by AppState)
+    Alias(AliasCommand),
+    /// An alias used as a command: the commands it stands for (with its
+    /// arguments put in), run in turn (applied by AppState and the window loop)
+    RunAlias {
+        name: String,
+        args: Vec<String>,
+        commands: Vec<Command>,
+    },
+    /// Paste the image file at `path` with its top-left corner at (x, y),
+    /// `scale` times its size
+    Image {
+        path: String,
+        x: usize,
+        y: usize,
+        scale: f64,
+    },
+    /// Draw a boxed legend with its top-left corner at (x, y): a swatch and
+    /// label per entry, in the edge color
+    Legend {
+        x: usize,
+        y: usize,
+        entries: Vec<LegendEntry>,
+    },
+    /// Show a toast over the canvas for `secs` seconds, with a beep if `beep`
+    /// (applied by the run loop)
+    Notify {
+        text: String,
+        secs: u64,
+        level: NotifyLevel,
+        beep: bool,
+    },
+    /// Play a `freq` Hz tone for `ms` milliseconds (applied by the run loop;
+    /// silent without the `sound` feature)
+    Beep {
+        freq: u32,
+        ms: u64,
+    },
+    /// Write the shapes drawn so far to `path` as SVG (applied by AppState)
+    ExportSvg(String),
+    /// Write the freehand strokes drawn so far to `path` as simplified point
+    /// lists in JSON (applied by AppState)
+    ExportStrokes(String),
+    /// Write the canvas to the directory `dir` as a `cols`x`rows` grid of
+    /// overlapping PNG tiles with alignment marks, for printing as a poster
+    /// (applied by AppState)
+    ExportTiles {
+        dir: String,
+        cols: usize,
+        rows: usize,
+    },
+    /// Write the canvas, palette, tool settings, and undo history to a
+    /// `.displai` project file (applied by the session, which owns the history)
+    Save(String),
+    /// Open, show, or close one of several canvases (applied by the session,
+    /// which keeps the others)
+    Tab(TabCommand),
+    /// Replace the drawing with a `.displai` project file's, undo history
+    /// and all (applied by the session)
+    Load(String),
+    /// Put the canvas, or the rectangle between two corners (inclusive,
+    /// either order), on the system clipboard as an image (applied by AppState)
+    Copy(Option<(usize, usize, usize, usize)>),
+    /// Draw the system clipboard's image with its top-left corner at (x, y)
+    /// (applied by AppState)
+    Paste {
+        x: usize,
+        y: usize,
+    },
+    /// Start logging executed commands to a path, or stop (None; applied by
+    /// the run loop)
+    Record(Option<String>),
+    /// Append execution time to every response (applied by the run loop)
+    Timing(bool),
+    /// Start or stop recording the canvas as an animated GIF (applied by the
+    /// run loop)
+    Gif(GifCommand),
+    /// Play a recording back at `speed` times its recorded pace (applied by
+    /// the run loop)
+    Replay {
+        path: String,
+        speed: f64,
+    },
+    /// Draw the strokes of a stroke file point by point at `speed` times their
+    /// recorded pace (applied by the run loop)
+    PlayStrokes {
+        path: String,
+        speed: f64,
+    },
+    /// Give the sending connection exclusive draw rights over a rectangle
+    /// (applied by the run loop)
+    LockRegion {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// Pause any replay (and tell subscribers) when a command changes a pixel
+    /// in a rectangle (applied by the run loop)
+    Watch {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// Stop one watch, or all of them (None; applied by the run loop)
+    Unwatch(Option<usize>),
+    /// Continue a replay paused by a watch (applied by the run loop)
+    Resume,
+    /// Open, adjust, or close the split view comparing the canvas with a
+    /// reference (applied by the run loop)
+    Compare(CompareCommand),
+    /// Count, or highlight, the pixels that differ from a reference image
+    /// (applied by the run loop)
+    Diff(DiffCommand),
+    /// Release one of the sender's locks, or all of them (None; applied by
+    /// the run loop)
+    UnlockRegion(Option<usize>),
+    /// Present a token for the access it grants (applied by the run loop)
+    Auth(String),
+    /// Load, query, or unload a script to step through (applied by the run
+    /// loop)
+    Debug(DebugCommand),
+    /// Run the next command of the script being debugged, replying as that
+    /// command would (applied by the run loop)
+    Step,
+    /// Reply with the canvas color at a point
+    GetPixel {
+        x: usize,
+        y: usize,
+    },
+    /// Reply with the canvas color at a point as a palette index, or as
+    /// `#RRGGBB` with the closest index
+    Pick {
+        x: usize,
+        y: usize,
+    },
+    /// Reply with a rectangle of canvas pixels, run-length encoded
+    GetRegion {
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+    },
+}
+
+impl Command {
+    /// The protocol verb for this command
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Snapshot(_) | Command::SnapshotBase64 | Command::SnapshotRegion { .. } => {
+                "snapshot"
+            }
+            Command::Color(_) => "color",
+            Command::Edge(_) => "edge",
+            Command::Fill(_) => "fill",
+            Command::FillGradient(_) => "fill-gradient",
+            Command::Size(_) => "size",
+            Command::StrokeStyle(_) => "stroke-style",
+            Command::BrushShape(_) => "brush",
+            Command::Stroke { .. } => "stroke",
+            Command::Dot { .. } => "dot",
+            Command::Clear => "clear",
+            Command::Canvas(_) | Command::CanvasSize(_) | Command::Tab(_) => "canvas",
+            Command::Filter { .. } => "filter",
+            Command::State => "state",
+            Command::Layout => "layout",
+            Command::Line { .. } => "line",
+            Command::Square { .. } => "square",
+            Command::Rect { .. } => "rect",
+            Command::RoundedRect { .. } => "rrect",
+            Command::Circle { .. } => "circle",
+            Command::Oval { .. } => "oval",
+            Command::Triangle { .. } => "triangle",
+            Command::Arrow { .. } => "arrow",
+            Command::Arc { .. } => "arc",
+            Command::Pie { .. } => "pie",
+            Command::Bucket { .. } => "bucket",
+            Command::Polyline(_) => "polyline",
+            Command::Points(_) => "points",
+            Command::Polygon(_) => "polygon",
+            Command::Ngon { .. } => "ngon",
+            Command::Star { .. } => "star",
+            Command::Bezier(_) => "bezier",
+            Command::Fps(_) => "fps",
+            Command::Capture(_) => "capture",
+            Command::GroupBegin(_) | Command::GroupEnd => "group",
+            Command::Undo => "undo",
+            Command::Redo => "redo",
+            Command::Stats(_) => "stats",
+            Command::Subscribe => "subscribe",
+            Command::Shadow(_) => "shadow",
+            Command::Snap(_) => "snap",
+            Command::Antialias(_) => "aa",
+            Command::ColorProfile(_) => "color-profile",
+            Command::Blend(_) => "blend",
+            Command::Panes(_) => "panes",
+            Command::InPane(_, cmd) => cmd.name(),
+            Command::Scale(_) => "scale",
+            Command::Plot(_) => "plot",
+            Command::TsPlot { .. } => "tsplot",
+            Command::Hold { .. } => "hold",
+            Command::Flip(_) => "flip",
+            Command::Object(ObjectCommand::Clock { .. }) => "clock",
+            Command::Object(ObjectCommand::Countdown { .. }) => "countdown",
+            Command::Object(ObjectCommand::Button { .. }) => "button",
+            Command::Object(ObjectCommand::Slider { .. }) => "slider",
+            Command::Object(ObjectCommand::Input { .. }) => "input",
+            Command::Object(ObjectCommand::Check { style, .. }) => style.name(),
+            Command::Object(_) => "obj",
+            Command::Var(_) => "var",
+            Command::Alias(AliasCommand::Remove(_)) => "unalias",
+            Command::Alias(_) | Command::RunAlias { .. } => "alias",
+            Command::Palette(_) => "palette",
+            Command::Dialog(_) => "dialog",
+            Command::Image { .. } => "image",
+            Command::Legend { .. } => "legend",
+            Command::Notify { .. } => "notify",
+            Command::Beep { .. } => "beep",
+            Command::ExportSvg(_) | Command::ExportStrokes(_) | Command::ExportTiles { .. } => {
+                "export"
+            }
+            Command::Save(_) => "save",
+            Command::Load(_) => "load",
+            Command::Copy(_) => "copy",
+            Command::Paste { .. } => "paste",
+            Command::Record(_) => "record",
+            Command::Timing(_) => "timing",
+            Command::Gif(_) => "record",
+            Command::Replay { .. } => "replay",
+            Command::PlayStrokes { .. } => "playstrokes",
+            Command::LockRegion { .. } => "lockregion",
+            Command::Watch { .. } => "watch",
+            Command::Unwatch(_) => "unwatch",
+            Command::Resume => "resume",
+            Command::Compare(_) => "compare",
+            Command::Diff(_) => "diff",
+            Command::UnlockRegion(_) => "unlockregion",
+            Command::Auth(_) => "auth",
+            Command::Debug(_) => "debug",
+            Command::Step => "step",
+            Command::GetPixel { .. } => "getpixel",
+            Command::Pick { .. } => "pick",
+            Command::GetRegion { .. } => "getregion",
+        }
+    }
+
+    /// The geometry of an arc or pie slice command
+    pub fn arc_shape(&self) -> Option<ArcShape> {
+        let (&x, &y, &r, &start, &end, pie) = match self {
+            Command::Arc {
+                x,
+                y,
+                r,
+                start,
+                end,
+            } => (x, y, r, start, end, false),
+            Command::Pie {
+                x,
+                y,
+                r,
+                start,
+                end,
+            } => (x, y, r, start, end, true),
+            _ => return None,
+        };
+        Some(ArcShape {
+            cx: x,
+            cy: y,
+            r,
+            start,
+            end,
+            pie,
+        })
+    }
+
+    /// The corners of a polygon, regular polygon, or star command
+    pub fn polygon_vertices(&self) -> Option<Vec<(usize, usize)>> {
+        match *self {
+            Command::Polygon(ref points) => Some(points.clone()),
+            Command::Ngon {
+                x,
+                y,
+                r,
+                sides,
+                rotation,
+            } => Some(ngon_vertices(x, y, r, sides, rotation)),
+            Command::Star {
+                x,
+                y,
+                outer,
+                inner,
+                points,
+            } => Some(star_vertices(x, y, outer, inner, points)),
+            _ => None,
+        }
+    }
+
+    /// The geometry of a rounded rectangle command
+    pub fn rounded_rect(&self) -> Option<RoundedRect> {
+        match *self {
+            Command::RoundedRect { x1, y1, x2, y2, r } => Some(RoundedRect::new(x1, y1, x2, y2, r)),
+            _ => None,
+        }
+    }
+
+    /// The geometry of an arrow command, its head sized to `brush_size` when
+    /// the command doesn't give one
+    pub fn arrow(&self, brush_size: usize) -> Option<Arrow> {
+        match *self {
+            Command::Arrow {
+                x1,
+                y1,
+                x2,
+                y2,
+                head,
+                both,
+            } => Some(Arrow {
+                x1,
+                y1,
+                x2,
+                y2,
+                head: head.unwrap_or_else(|| default_arrow_head(brush_size)),
+                both,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The tool and bounding box (as a drag from corner to corner) of a shape command
+    ///
+    /// Squares are given by top-left and size, circles and ovals by center and
+    /// radii; all are converted to the corners `draw_shape_with_fill` expects.
+    pub fn shape_bounds(&self) -> Option<(ToolMode, usize, usize, usize, usize)> {
+        match *self {
+            Command::Line { x1, y1, x2, y2 } => Some((ToolMode::Line, x1, y1, x2, y2)),
+            Command::Square { x, y, size } => Some((
+                ToolMode::Square,
+                x,
+                y,
+                x.saturating_add(size),
+                y.saturating_add(size),
+            )),
+            Command::Rect { x1, y1, x2, y2 } => Some((ToolMode::Rectangle, x1, y1, x2, y2)),
+            Command::Circle { x, y, r } => Some((
+                ToolMode::Circle,
+                x.saturating_sub(r),
+                y.saturating_sub(r),
+                x.saturating_add(r),
+                y.saturating_add(r),
+            )),
+            Command::Oval { x, y, rx, ry } => Some((
+                ToolMode::Oval,
+                x.saturating_sub(rx),
+                y.saturating_sub(ry),
+                x.saturating_add(rx),
+                y.saturating_add(ry),
+            )),
+            Command::Triangle { x1, y1, x2, y2 } => Some((ToolMode::Triangle, x1, y1, x2, y2)),
+            _ => None,
+        }
+    }
+
+    /// Whether this command changes canvas pixels
+    ///
+    /// Tool state changes (color, size, fps) and read-only commands are not
+    /// mutating; repeating them is harmless.
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Command::Stroke { .. }
+                | Command::Dot { .. }
+                | Command::Image { .. }
+                | Command::Clear
+                | Command::Canvas(_)
+                | Command::CanvasSize(_)
+                | Command::Tab(_)
+                | Command::Load(_)
+                | Command::Paste { .. }
+                | Command::Filter { .. }
+                | Command::Line { .. }
+                | Command::Square { .. }
+                | Command::Rect { .. }
+                | Command::RoundedRect { .. }
+                | Command::Circle { .. }
+                | Command::Oval { .. }
+                | Command::Triangle { .. }
+                | Command::Arrow { .. }
+                | Command::Arc { .. }
+                | Command::Pie { .. }
+                | Command::Bucket { .. }
+                | Command::Polygon(_)
+                | Command::Ngon { .. }
+                | Command::Star { .. }
+                | Command::Polyline(_)
+                | Command::Points(_)
+                | Command::Bezier(_)
+                | Command::Plot(_)
+                | Command::TsPlot { .. }
+                | Command::Legend { .. }
+                | Command::Scale(Some(PaneScale { axes: true, .. }))
+        ) || matches!(self, Command::InPane(_, cmd) if cmd.is_mutating())
+            || matches!(self, Command::RunAlias { commands, .. } if commands.iter().any(Command::is_mutating))
+    }
+
+    /// Whether `@N` may prefix this command: drawing commands (but not
+    /// `tsplot`, which names its pane, aliases, or `canvas`, which moves the
+    /// whole canvas), and `scale`
+    pub fn runs_in_pane(&self) -> bool {
+        match self {
+            Command::TsPlot { .. }
+            | Command::RunAlias { .. }
+            | Command::Canvas(_)
+            | Command::CanvasSize(_)
+            | Command::Tab(_)
+            | Command::Load(_) => false,
+            _ => self.is_mutating() || matches!(self, Command::Scale(_)),
+        }
+    }
+
+    /// The command with its canvas coordinates moved right by `dx` and down
+    /// by `dy` (commands without coordinates are unchanged)
+    pub fn translated(&self, dx: usize, dy: usize) -> Command {
+        let mut cmd = self.clone();
+        let shift = |x: &mut usize, y: &mut usize| {
+            *x = x.saturating_add(dx);
+            *y = y.saturating_add(dy);
+        };
+        match &mut cmd {
+            Command::Stroke { x1, y1, x2, y2 }
+            | Command::Line { x1, y1, x2, y2 }
+            | Command::Rect { x1, y1, x2, y2 }
+            | Command::RoundedRect { x1, y1, x2, y2, .. }
+            | Command::Arrow { x1, y1, x2, y2, .. }
+            | Command::Triangle { x1, y1, x2, y2 } => {
+                shift(x1, y1);
+                shift(x2, y2);
+            }
+            Command::Dot { x, y }
+            | Command::Bucket { x, y }
+            | Command::Square { x, y, .. }
+            | Command::Circle { x, y, .. }
+            | Command::Oval { x, y, .. }
+            | Command::Arc { x, y, .. }
+            | Command::Pie { x, y, .. }
+            | Command::Ngon { x, y, .. }
+            | Command::Star { x, y, .. }
+            | Command::Image { x, y, .. }
+            | Command::Paste { x, y }
+            | Command::Legend { x, y, .. } => shift(x, y),
+            Command::Filter {
+                region: Some((x1, y1, x2, y2)),
+                ..
+            } => {
+                shift(x1, y1);
+                shift(x2, y2);
+            }
+            Command::Polygon(points) | Command::Bezier(points) => {
+                for (x, y) in points {
+                    shift(x, y);
+                }
+            }
+            Command::Polyline(points) | Command::Points(points) => {
+                for point in points {
+                    shift(&mut point.x, &mut point.y);
+                }
+            }
+            _ => {}
+        }
+        cmd
+    }
+}
+
+impl fmt::Display for AttributedPoint {
+    /// Format as `x,y[:color[:size]]` (a size without a color is dropped)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)?;
+        if let Some(color) = self.color {
+            write!(f, ":{}", fmt_color(&Some(color)))?;
+            if let Some(size) = self.size {
+                write!(f, ":{}", size)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format an optional color as the protocol writes it: the palette index for
+/// palette colors, `#RRGGBB` (or `#RRGGBBAA`, if translucent) for any other,
+/// or `none`
+pub fn fmt_color(color: &Option<u32>) -> String {
+    match color {
+        Some(c) => match palette_index(*c) {
+            Some(i) => i.to_string(),
+            None => hex_color(*c),
+        },
+        None => "none".to_string(),
+    }
+}
+
+/// The tool settings as `state` reports them: `edge:<color> fill:<color>
+/// size:<n>`
+pub fn settings_summary(
+    edge_color: &Option<u32>,
+    fill_color: &Option<u32>,
+    brush_size: usize,
+) -> String {
+    format!(
+        "edge:{} fill:{} size:{}",
+        fmt_color(edge_color),
+        fmt_color(fill_color),
+        brush_size
+    )
+}
+
+/// Format a list of attributed points separated by spaces
+fn fmt_point_list(points: &[AttributedPoint]) -> String {
+    points
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl fmt::Display for Command {
+    /// Format the command in protocol syntax, so that `parse_command` reads it back
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Snapshot(None) => write!(f, "snapshot"),
+            Command::SnapshotBase64 => write!(f, "snapshot base64"),
+            Command::Snapshot(Some(path)) => write!(f, "snapshot {}", path),
+            Command::SnapshotRegion {
+                x1,
+                y1,
+                x2,
+                y2,
+                path,
+            } => {
+                write!(f, "snapshot region {},{} {},{}", x1, y1, x2, y2)?;
+                match path {
+                    Some(path) => write!(f, " {}", path),
+                    None => Ok(()),
+                }
+            }
+            Command::Color(i) => write!(f, "color {}", i),
+            Command::Edge(c) => write!(f, "edge {}", fmt_color(c)),
+            Command::Fill(c) => write!(f, "fill {}", fmt_color(c)),
+            Command::FillGradient(None) => write!(f, "fill-gradient off"),
+            Command::FillGradient(Some(g)) => {
+                let (from, to) = (fmt_color(&Some(g.from)), fmt_color(&Some(g.to)));
+                match g.shape {
+                    GradientShape::Linear { angle } => {
+                        write!(f, "fill-gradient linear {} {} {}", from, to, angle)
+                    }
+                    GradientShape::Radial => write!(f, "fill-gradient radial {} {}", from, to),
+                }
+            }
+            Command::Size(s) => write!(f, "size {}", s),
+            Command::StrokeStyle(style) => write!(f, "stroke-style {}", style),
+            Command::BrushShape(shape) => write!(f, "brush shape {}", shape),
+            Command::Stroke { x1, y1, x2, y2 } => write!(f, "stroke {},{} {},{}", x1, y1, x2, y2),
+            Command::Dot { x, y } => write!(f, "dot {},{}", x, y),
+            Command::Clear => write!(f, "clear"),
+            Command::Canvas(transform) => write!(f, "canvas {}", transform),
+            Command::CanvasSize(change) => write!(f, "canvas {}", change),
+            Command::Tab(op) => write!(f, "canvas {}", op),
+            Command::Filter { filter, region } => {
+                write!(f, "filter {}", filter)?;
+                match region {
+                    Some((x1, y1, x2, y2)) => write!(f, " {},{} {},{}", x1, y1, x2, y2),
+                    None => Ok(()),
+                }
+            }
+            Command::State => write!(f, "state"),
+            Command::Layout => write!(f, "layout"),
+            Command::Line { x1, y1, x2, y2 } => write!(f, "line {},{} {},{}", x1, y1, x2, y2),
+            Command::Square { x, y, size } => write!(f, "square {},{} {}", x, y, size),
+            Command::Rect { x1, y1, x2, y2 } => write!(f, "rect {},{} {},{}", x1, y1, x2, y2),
+            Command::RoundedRect { x1, y1, x2, y2, r } => {
+                write!(f, "rrect {},{} {},{} {}", x1, y1, x2, y2, r)
+            }
+            Command::Circle { x, y, r } => write!(f, "circle {},{} {}", x, y, r),
+            Command::Oval { x, y, rx, ry } => write!(f, "oval {},{} {},{}", x, y, rx, ry),
+            Command::Triangle { x1, y1, x2, y2 } => {
+                write!(f, "triangle {},{} {},{}", x1, y1, x2, y2)
+            }
+            Command::Arrow {
+                x1,
+                y1,
+                x2,
+                y2,
+                head,
+                both,
+            } => {
+                write!(f, "arrow {},{} {},{}", x1, y1, x2, y2)?;
+                if let Some(head) = head {
+                    write!(f, " {}", head)?;
+                }
+                if *both {
+                    write!(f, " both")?;
+                }
+                Ok(())
+            }
+            Command::Arc {
+                x,
+                y,
+                r,
+                start,
+                end,
+            } => write!(f, "arc {},{} {} {} {}", x, y, r, start, end),
+            Command::Pie {
+                x,
+                y,
+                r,
+                start,
+                end,
+            } => write!(f, "pie {},{} {} {} {}", x, y, r, start, end),
+            Command::Bucket { x, y } => write!(f, "bucket {},{}", x, y),
+            Command::Polyline(points) => write!(f, "polyline {}", fmt_point_list(points)),
+            Command::Points(points) => write!(f, "points {}", fmt_point_list(points)),
+            Command::Polygon(points) => {
+                write!(f, "polygon")?;
+                for (x, y) in points {
+                    write!(f, " {},{}", x, y)?;
+                }
+                Ok(())
+            }
+            Command::Ngon {
+                x,
+                y,
+                r,
+                sides,
+                rotation,
+            } => {
+                write!(f, "ngon {},{} {} {}", x, y, r, sides)?;
+                if *rotation != 0.0 {
+                    write!(f, " {}", rotation)?;
+                }
+                Ok(())
+            }
+            Command::Star {
+                x,
+                y,
+                outer,
+                inner,
+                points,
+            } => write!(f, "star {},{} {} {} {}", x, y, outer, inner, points),
+            Command::Bezier(points) => {
+                write!(f, "bezier")?;
+                for (x, y) in points {
+                    write!(f, " {},{}", x, y)?;
+                }
+                Ok(())
+            }
+            Command::Legend { x, y, entries } => {
+                write!(f, "legend {},{}", x, y)?;
+                for entry in entries {
+                    let color = fmt_color(&Some(entry.color));
+                    write!(f, " entry \"{}\" {}", entry.label, color)?;
+                }
+                Ok(())
+            }
+            Command::Fps(fps) => write!(f, "fps {}", fps),
+            Command::Capture(Some(path)) => write!(f, "capture {}", path),
+            Command::Capture(None) => write!(f, "capture stop"),
+            Command::GroupBegin(label) => write!(f, "group begin \"{}\"", label),
+            Command::GroupEnd => write!(f, "group end"),
+            Command::Undo => write!(f, "undo"),
+            Command::Redo => write!(f, "redo"),
+            Command::Stats(None) => write!(f, "stats"),
+            Command::Stats(Some(true)) => write!(f, "stats on"),
+            Command::Stats(Some(false)) => write!(f, "stats off"),
+            Command::Subscribe => write!(f, "subscribe"),
+            Command::Shadow(None) => write!(f, "shadow off"),
+            Command::Snap(None) => write!(f, "snap off"),
+            Command::Snap(Some(tolerance)) => write!(f, "snap on {}", tolerance),
+            Command::Antialias(true) => write!(f, "aa on"),
+            Command::Antialias(false) => write!(f, "aa off"),
+            Command::Blend(mode) => write!(f, "blend {}", mode),
+            Command::ColorProfile(None) => write!(f, "color-profile off"),
+            Command::ColorProfile(Some(profile)) => write!(f, "color-profile {}", profile.name()),
+            Command::Panes(panes) if *panes == Panes::default() => write!(f, "panes off"),
+            Command::Panes(panes) if panes.margin == 0 => write!(f, "panes {}", panes),
+            Command::Panes(panes) => write!(f, "panes {} {}", panes, panes.margin),
+            Command::InPane(index, cmd) => write!(f, "@{} {}", index, cmd),
+            Command::Scale(Some(scale)) => write!(f, "scale {}", scale),
+            Command::Scale(None) => write!(f, "scale off"),
+            Command::Plot(points) => {
+                write!(f, "plot")?;
+                for (x, y) in points {
+                    write!(f, " {},{}", x, y)?;
+                }
+                Ok(())
+            }
+            Command::TsPlot { pane, value } => write!(f, "tsplot {} {}", pane, value),
+            Command::Hold { pane, on } => {
+                write!(f, "hold {} {}", pane, if *on { "on" } else { "off" })
+            }
+            Command::Flip(pane) => write!(f, "flip {}", pane),
+            Command::Shadow(Some(s)) => {
+                write!(f, "shadow on {},{} #{:06X} {}", s.dx, s.dy, s.color, s.blur)
+            }
+            Command::Object(ObjectCommand::Text { x, y, text, scale }) => {
+                write!(f, "obj text {},{} \"{}\"", x, y, text)?;
+                if *scale != 1 {
+                    write!(f, " {}", scale)?;
+                }
+                Ok(())
+            }
+            Command::Object(ObjectCommand::SetText { id, text }) => {
+                write!(f, "obj settext {} \"{}\"", id, text)
+            }
+            Command::Object(ObjectCommand::Delete(id)) => write!(f, "obj delete {}", id),
+            Command::Object(ObjectCommand::List) => write!(f, "obj list"),
+            Command::Object(ObjectCommand::Blink { id, ms }) => {
+                write!(f, "obj blink {} {}", id, ms)
+            }
+            Command::Object(ObjectCommand::Clock { x, y, format }) => {
+                write!(f, "clock {},{}", x, y)?;
+                if format != DEFAULT_CLOCK_FORMAT {
+                    write!(f, " \"{}\"", format)?;
+                }
+                Ok(())
+            }
+            Command::Object(ObjectCommand::Countdown { x, y, secs }) => {
+                write!(f, "countdown {},{} {}", x, y, secs)
+            }
+            Command::Object(ObjectCommand::Button {
+                x,
+                y,
+                w,
+                h,
+                label,
+                event,
+            }) => write!(f, "button {},{},{},{} \"{}\" {}", x, y, w, h, label, event),
+            Command::Object(ObjectCommand::Slider {
+                x,
+                y,
+                w,
+                min,
+                max,
+                var,
+            }) => write!(f, "slider {},{},{} {} {} {}", x, y, w, min, max, var),
+            Command::Object(ObjectCommand::Input { x, y, w, var }) => {
+                write!(f, "input {},{},{} {}", x, y, w, var)
+            }
+            Command::Object(ObjectCommand::Check {
+                x,
+                y,
+                style,
+                label,
+                var,
+            }) => write!(f, "{} {},{} \"{}\" {}", style.name(), x, y, label, var),
+            Command::Var(VarCommand::Set { name, value }) => {
+                if value.is_empty() || value.contains(char::is_whitespace) {
+                    write!(f, "var set {} \"{}\"", name, value)
+                } else {
+                    write!(f, "var set {} {}", name, value)
+                }
+            }
+            Command::Var(VarCommand::Get(name)) => write!(f, "var get {}", name),
+            Command::Alias(AliasCommand::Define { name, body }) => {
+                write!(f, "alias {} \"{}\"", name, body)
+            }
+            Command::Alias(AliasCommand::Get(name)) => write!(f, "alias {}", name),
+            Command::Alias(AliasCommand::Remove(name)) => write!(f, "unalias {}", name),
+            Command::RunAlias { name, args, .. } => {
+                write!(f, "{}", name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                Ok(())
+            }
+            Command::Palette(PaletteCommand::Set { index, color }) => {
+                write!(f, "palette set {} #{:06X}", index, color)
+            }
+            Command::Palette(PaletteCommand::Get) => write!(f, "palette get"),
+            Command::Palette(PaletteCommand::Reset) => write!(f, "palette reset"),
+            Command::Dialog(DialogCommand::Open { kind, message }) => {
+                write!(f, "dialog {} \"{}\"", kind.name(), message)
+            }
+            Command::Dialog(DialogCommand::Close) => write!(f, "dialog close"),
+            Command::Image { path, x, y, scale } => {
+                write!(f, "image load {} {},{}", path, x, y)?;
+                if *scale != 1.0 {
+                    write!(f, " {}", scale)?;
+                }
+                Ok(())
+            }
+            Command::Notify {
+                text,
+                secs,
+                level,
+                beep,
+            } => {
+                write!(f, "notify \"{}\" {} {}", text, secs, level.name())?;
+                if *beep {
+                    write!(f, " beep")?;
+                }
+                Ok(())
+            }
+            Command::Beep { freq, ms } => write!(f, "beep {} {}", freq, ms),
+            Command::ExportSvg(path) => write!(f, "export svg {}", path),
+            Command::ExportStrokes(path) => write!(f, "export strokes {}", path),
+            Command::ExportTiles { dir, cols, rows } => {
+                write!(f, "export tiles {} {}x{}", dir, cols, rows)
+            }
+            Command::Save(path) => write!(f, "save {}", path),
+            Command::Load(path) => write!(f, "load {}", path),
+            Command::Copy(None) => write!(f, "copy"),
+            Command::Copy(Some((x1, y1, x2, y2))) => write!(f, "copy {},{} {},{}", x1, y1, x2, y2),
+            Command::Paste { x, y } => write!(f, "paste {},{}", x, y),
+            Command::Record(Some(path)) => write!(f, "record start {}", path),
+            Command::Record(None) => write!(f, "record stop"),
+            Command::Gif(GifCommand::Start { path, interval_ms }) => {
+                write!(f, "record gif {}", path)?;
+                if let Some(ms) = interval_ms {
+                    write!(f, " {}", ms)?;
+                }
+                Ok(())
+            }
+            Command::Gif(GifCommand::Stop) => write!(f, "record gif stop"),
+            Command::Timing(on) => write!(f, "timing {}", if *on { "on" } else { "off" }),
+            Command::Replay { path, speed } => {
+                write!(f, "replay {}", path)?;
+                if *speed != 1.0 {
+                    write!(f, " {}", speed)?;
+                }
+                Ok(())
+            }
+            Command::PlayStrokes { path, speed } => {
+                write!(f, "playstrokes {}", path)?;
+                if *speed != 1.0 {
+                    write!(f, " {}", speed)?;
+                }
+                Ok(())
+            }
+            Command::LockRegion {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "lockregion {},{},{},{}", x, y, width, height),
+            Command::UnlockRegion(Some(id)) => write!(f, "unlockregion {}", id),
+            Command::UnlockRegion(None) => write!(f, "unlockregion"),
+            Command::Watch {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "watch {},{},{},{}", x, y, width, height),
+            Command::Unwatch(Some(id)) => write!(f, "unwatch {}", id),
+            Command::Unwatch(None) => write!(f, "unwatch"),
+            Command::Resume => write!(f, "resume"),
+            Command::Compare(CompareCommand::Image(path)) => write!(f, "compare {}", path),
+            Command::Compare(CompareCommand::Checkpoint) => write!(f, "compare checkpoint"),
+            Command::Compare(CompareCommand::Divider(x)) => write!(f, "compare divider {}", x),
+            Command::Compare(CompareCommand::Off) => write!(f, "compare off"),
+            Command::Diff(DiffCommand::Image(path)) => write!(f, "diff {}", path),
+            Command::Diff(DiffCommand::Overlay(path)) => write!(f, "diff overlay {}", path),
+            Command::Diff(DiffCommand::OverlayOff) => write!(f, "diff overlay off"),
+            Command::Auth(token) => write!(f, "auth {}", token),
+            Command::Debug(DebugCommand::Step(path)) => write!(f, "debug step {}", path),
+            Command::Debug(DebugCommand::Status) => write!(f, "debug"),
+            Command::Debug(DebugCommand::Stop) => write!(f, "debug stop"),
+            Command::Step => write!(f, "step"),
+            Command::GetPixel { x, y } => write!(f, "getpixel {},{}", x, y),
+            Command::Pick { x, y } => write!(f, "pick {},{}", x, y),
+            Command::GetRegion { x1, y1, x2, y2 } => {
+                write!(f, "getregion {},{} {},{}", x1, y1, x2, y2)
+            }
+        }
+    }
 }
 
 /// Parse a point with optional color and size attributes
-/// Format: x,y or x,y:color or x,y:color:size
+/// Format: x,y or x,y:color or x,y:color:size (color: palette index or `#RRGGBB`)
 pub fn parse_attributed_point(s: &str) -> Option<AttributedPoint> {
     // Split on colon first to separate coords from attributes
     let parts: Vec<&str> = s.split(':').collect();
@@ -95,12 +1293,7 @@ pub fn parse_attributed_point(s: &str) -> Option<AttributedPoint> {
 
     // Parse optional color from second part
     let color = if parts.len() >= 2 {
-        let c = parts[1].parse::<usize>().ok()?;
-        if c < COLOR_PALETTE.len() {
-            Some(c)
-        } else {
-            return None; // Invalid color index
-        }
+        Some(parse_color(parts[1])?)
     } else {
         None
     };
@@ -116,14 +1309,1052 @@ pub fn parse_attributed_point(s: &str) -> Option<AttributedPoint> {
     Some(AttributedPoint { x, y, color, size })
 }
 
-/// Parse a space-separated list of attributed points
-fn parse_attributed_list(args: &str) -> Option<Vec<AttributedPoint>> {
-    args.split_whitespace()
-        .map(parse_attributed_point)
-        .collect()
+/// Parse a space-separated list of attributed points
+fn parse_attributed_list(args: &str) -> Option<Vec<AttributedPoint>> {
+    args.split_whitespace()
+        .map(parse_attributed_point)
+        .collect()
+}
+
+/// Parse a palette index (`0-13`) or a CSS color name (`steelblue`)
+fn parse_color_index(s: &str) -> Option<usize> {
+    match s.parse::<usize>() {
+        Ok(i) => Some(i).filter(|&i| i < PALETTE_LEN),
+        Err(_) => named_color_index(s),
+    }
+}
+
+/// Parse an edge, fill, or point color: a palette index, a CSS color name
+/// (as its nearest palette color), or an exact `#RRGGBB`
+fn parse_color(s: &str) -> Option<u32> {
+    if s.starts_with('#') {
+        parse_color_value(s)
+    } else {
+        parse_color_index(s).and_then(|i| current_palette().get(i))
+    }
+}
+
+/// Parse the arguments of `fill-gradient linear <from> <to> [angle]`,
+/// `fill-gradient radial <from> <to>`, or `fill-gradient off`
+fn parse_fill_gradient(args: &[&str]) -> Option<Command> {
+    let (shape, from, to) = match args {
+        ["off"] => return Some(Command::FillGradient(None)),
+        ["linear", from, to] => (GradientShape::Linear { angle: 0.0 }, from, to),
+        ["linear", from, to, angle] => (
+            GradientShape::Linear {
+                angle: angle.parse().ok()?,
+            },
+            from,
+            to,
+        ),
+        ["radial", from, to] => (GradientShape::Radial, from, to),
+        _ => return None,
+    };
+    Some(Command::FillGradient(Some(Gradient {
+        shape,
+        from: parse_color(from)?,
+        to: parse_color(to)?,
+    })))
+}
+
+/// Parse the arguments of `stroke-style solid|dashed|dotted [dash gap]`
+fn parse_stroke_style(args: &[&str]) -> Option<Command> {
+    let (kind, lengths) = args.split_first()?;
+    let (dash, gap) = match lengths {
+        [] if *kind == "dotted" => DEFAULT_DOT,
+        [] => DEFAULT_DASH,
+        [dash, gap] => (dash.parse().ok()?, gap.parse().ok()?),
+        _ => return None,
+    };
+    Some(Command::StrokeStyle(match *kind {
+        "solid" if lengths.is_empty() => StrokeStyle::Solid,
+        "dashed" => StrokeStyle::Dashed { dash, gap },
+        "dotted" => StrokeStyle::Dotted { dash, gap },
+        _ => return None,
+    }))
+}
+
+/// Parse the arguments of `filter <filter> [x1,y1 x2,y2]`
+fn parse_filter(args: &[&str]) -> Option<Command> {
+    let (filter, corners) = Filter::parse(args)?;
+    let region = match parse_point_list(corners)?[..] {
+        [] => None,
+        [(x1, y1), (x2, y2)] => Some((x1, y1, x2, y2)),
+        _ => return None,
+    };
+    Some(Command::Filter { filter, region })
+}
+
+/// Parse the arguments of `group begin ["label"]` / `group end`
+///
+/// The label may be quoted (`"my chart"`) or bare (`my chart`), and may not
+/// itself contain quotes.
+fn parse_group(args: &str) -> Option<Command> {
+    let args = args.trim();
+    if args == "end" {
+        return Some(Command::GroupEnd);
+    }
+    let label = args.strip_prefix("begin")?;
+    if !label.is_empty() && !label.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let label = label.trim();
+    let label = match label.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"')?,
+        None => label,
+    };
+    if label.contains('"') {
+        return None;
+    }
+    Some(Command::GroupBegin(label.to_string()))
+}
+
+/// Split a leading `"quoted string"` off `args`, returning it and the rest
+///
+/// The string may not itself contain quotes.
+fn parse_quoted(args: &str) -> Option<(&str, &str)> {
+    let quoted = args.trim_start().strip_prefix('"')?;
+    let end = quoted.find('"')?;
+    let rest = &quoted[end + 1..];
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((&quoted[..end], rest))
+}
+
+/// Parse the arguments of `legend x,y entry "label" <color> [entry ...]`
+fn parse_legend(args: &str) -> Option<Command> {
+    let (point, mut rest) = args.trim().split_once(char::is_whitespace)?;
+    let (x, y) = point.split_once(',')?;
+    let mut entries = Vec::new();
+    while !rest.trim().is_empty() {
+        let after = rest.trim_start().strip_prefix("entry")?;
+        if !after.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let (label, after) = parse_quoted(after)?;
+        let after = after.trim_start();
+        let (color, after) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+        entries.push(LegendEntry {
+            label: label.to_string(),
+            color: parse_color(color)?,
+        });
+        rest = after;
+    }
+    if entries.is_empty() {
+        return None;
+    }
+    Some(Command::Legend {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        entries,
+    })
+}
+
+/// Parse the arguments of `obj text x,y "text" [scale]`, `obj settext <id> "text"`,
+/// `obj delete <id>`, `obj blink <id> <ms>`, and `obj list`
+fn parse_object(args: &str) -> Option<Command> {
+    let args = args.trim();
+    let (op, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let rest = rest.trim_start();
+    let op = match op {
+        "text" => {
+            let (point, rest) = rest.split_once(char::is_whitespace)?;
+            let (x, y) = point.split_once(',')?;
+            let (text, rest) = parse_quoted(rest)?;
+            let scale = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => 1,
+                [scale] => scale
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|s| (1..=MAX_TEXT_SCALE).contains(s))?,
+                _ => return None,
+            };
+            ObjectCommand::Text {
+                x: x.parse().ok()?,
+                y: y.parse().ok()?,
+                text: text.to_string(),
+                scale,
+            }
+        }
+        "settext" => {
+            let (id, rest) = rest.split_once(char::is_whitespace)?;
+            let (text, rest) = parse_quoted(rest)?;
+            if !rest.trim().is_empty() {
+                return None;
+            }
+            ObjectCommand::SetText {
+                id: id.parse().ok()?,
+                text: text.to_string(),
+            }
+        }
+        "delete" => ObjectCommand::Delete(rest.parse().ok()?),
+        "list" if rest.is_empty() => ObjectCommand::List,
+        "blink" => {
+            let (id, ms) = rest.split_once(char::is_whitespace)?;
+            ObjectCommand::Blink {
+                id: id.parse().ok()?,
+                ms: ms.trim().parse().ok()?,
+            }
+        }
+        _ => return None,
+    };
+    Some(Command::Object(op))
+}
+
+/// Parse the arguments of `clock x,y [format|"format"]`
+fn parse_clock(args: &str) -> Option<Command> {
+    let args = args.trim();
+    let (point, format) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let (x, y) = point.split_once(',')?;
+    let format = format.trim();
+    let format = if format.is_empty() {
+        DEFAULT_CLOCK_FORMAT
+    } else if format.starts_with('"') {
+        let (quoted, rest) = parse_quoted(format)?;
+        if !rest.trim().is_empty() {
+            return None;
+        }
+        quoted
+    } else if format.contains(char::is_whitespace) || format.contains('"') {
+        return None;
+    } else {
+        format
+    };
+    Some(Command::Object(ObjectCommand::Clock {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        format: format.to_string(),
+    }))
+}
+
+/// Parse the arguments of `button x,y,w,h "label" <event-name>`
+fn parse_button(args: &str) -> Option<Command> {
+    let (rect, rest) = args.trim().split_once(char::is_whitespace)?;
+    let [x, y, w, h] = rect
+        .split(',')
+        .map(|n| n.parse().ok())
+        .collect::<Option<Vec<usize>>>()?[..]
+    else {
+        return None;
+    };
+    let (label, rest) = parse_quoted(rest)?;
+    let event = rest.trim();
+    if event.is_empty() || event.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(Command::Object(ObjectCommand::Button {
+        x,
+        y,
+        w,
+        h,
+        label: label.to_string(),
+        event: event.to_string(),
+    }))
+}
+
+/// Parse the arguments of `checkbox x,y "label" varname` (or `toggle ...`)
+fn parse_check(style: CheckStyle, args: &str) -> Option<Command> {
+    let (point, rest) = args.trim().split_once(char::is_whitespace)?;
+    let (x, y) = point.split_once(',')?;
+    let (x, y) = (x.parse().ok()?, y.parse().ok()?);
+    let (label, rest) = parse_quoted(rest)?;
+    let var = rest.trim();
+    if var.is_empty() || var.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(Command::Object(ObjectCommand::Check {
+        x,
+        y,
+        style,
+        label: label.to_string(),
+        var: var.to_string(),
+    }))
+}
+
+/// Parse the arguments of `slider x,y,w min max varname`
+fn parse_slider(args: &[&str]) -> Option<Command> {
+    let [rect, min, max, var] = args else {
+        return None;
+    };
+    let [x, y, w] = rect
+        .split(',')
+        .map(|n| n.parse().ok())
+        .collect::<Option<Vec<usize>>>()?[..]
+    else {
+        return None;
+    };
+    Some(Command::Object(ObjectCommand::Slider {
+        x,
+        y,
+        w,
+        min: min.parse().ok()?,
+        max: max.parse().ok()?,
+        var: var.to_string(),
+    }))
+}
+
+/// Parse the arguments of `input x,y,w varname`
+fn parse_input(args: &[&str]) -> Option<Command> {
+    let [rect, var] = args else {
+        return None;
+    };
+    let [x, y, w] = rect
+        .split(',')
+        .map(|n| n.parse().ok())
+        .collect::<Option<Vec<usize>>>()?[..]
+    else {
+        return None;
+    };
+    Some(Command::Object(ObjectCommand::Input {
+        x,
+        y,
+        w,
+        var: var.to_string(),
+    }))
+}
+
+/// Parse the arguments of `snapshot region x1,y1 x2,y2 [path]` (the path is
+/// the rest of the line, so it may hold spaces)
+fn parse_snapshot_region(args: &str) -> Option<Command> {
+    let (p1, rest) = args.trim().split_once(char::is_whitespace)?;
+    let rest = rest.trim_start();
+    let (p2, path) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let (x1, y1) = p1.split_once(',')?;
+    let (x2, y2) = p2.split_once(',')?;
+    let path = path.trim();
+    Some(Command::SnapshotRegion {
+        x1: x1.parse().ok()?,
+        y1: y1.parse().ok()?,
+        x2: x2.parse().ok()?,
+        y2: y2.parse().ok()?,
+        path: (!path.is_empty()).then(|| path.to_string()),
+    })
+}
+
+/// Parse the arguments of `dialog confirm "message"`, `dialog prompt "message"`,
+/// or `dialog close`
+fn parse_dialog(args: &str) -> Option<Command> {
+    let args = args.trim();
+    let (verb, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let kind = match verb {
+        "close" if rest.is_empty() => return Some(Command::Dialog(DialogCommand::Close)),
+        "confirm" => DialogKind::Confirm,
+        "prompt" => DialogKind::Prompt,
+        _ => return None,
+    };
+    let (message, rest) = parse_quoted(rest)?;
+    if !rest.trim().is_empty() {
+        return None;
+    }
+    Some(Command::Dialog(DialogCommand::Open {
+        kind,
+        message: message.to_string(),
+    }))
+}
+
+/// Parse the arguments of `notify "text" [secs] [level] [beep]`
+fn parse_notify(args: &str) -> Option<Command> {
+    let (text, rest) = parse_quoted(args)?;
+    let mut words = rest.split_whitespace().collect::<Vec<_>>();
+    let beep = words.last() == Some(&"beep");
+    if beep {
+        words.pop();
+    }
+    let (secs, level) = match words[..] {
+        [] => (DEFAULT_NOTIFY_SECS, NotifyLevel::default()),
+        [secs, level] => (secs.parse().ok()?, NotifyLevel::parse(level)?),
+        // A lone argument is the duration if it is a number, else the level
+        [arg] => match arg.parse() {
+            Ok(secs) => (secs, NotifyLevel::default()),
+            Err(_) => (DEFAULT_NOTIFY_SECS, NotifyLevel::parse(arg)?),
+        },
+        _ => return None,
+    };
+    Some(Command::Notify {
+        text: text.to_string(),
+        secs,
+        level,
+        beep,
+    })
+}
+
+/// Parse the arguments of `record start <path>`, `record stop`,
+/// `record gif <path> [ms]`, or `record gif stop`
+fn parse_record(args: &str) -> Option<Command> {
+    let args = args.trim();
+    if args == "stop" {
+        return Some(Command::Record(None));
+    }
+    if let Some(rest) = args.strip_prefix("gif") {
+        return parse_record_gif(rest);
+    }
+    // The path is the rest of the line, so it may hold spaces
+    let path = args.strip_prefix("start")?;
+    if !path.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(Command::Record(Some(path.trim().to_string())))
+}
+
+/// Parse the arguments of `record gif <path> [ms]` or `record gif stop` (the
+/// path may hold spaces, so a trailing number is taken as the interval)
+fn parse_record_gif(args: &str) -> Option<Command> {
+    if !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let args = args.trim();
+    if args == "stop" {
+        return Some(Command::Gif(GifCommand::Stop));
+    }
+    let (path, interval_ms) = match args.rsplit_once(char::is_whitespace) {
+        Some((path, ms)) => match ms.parse::<u64>() {
+            Ok(ms) => (path.trim_end(), Some(ms)),
+            Err(_) => (args, None),
+        },
+        None => (args, None),
+    };
+    Some(Command::Gif(GifCommand::Start {
+        path: path.to_string(),
+        interval_ms,
+    }))
+}
+
+/// Parse the arguments of `replay <path> [speed]` or `replay <path> --realtime`
+fn parse_replay(args: &str) -> Option<Command> {
+    let (path, speed) = parse_path_and_speed(args)?;
+    Some(Command::Replay { path, speed })
+}
+
+/// Parse the arguments of `playstrokes <path> [speed]` (or `--realtime`)
+fn parse_playstrokes(args: &str) -> Option<Command> {
+    let (path, speed) = parse_path_and_speed(args)?;
+    Some(Command::PlayStrokes { path, speed })
+}
+
+/// Parse `<path> [speed]` or `<path> --realtime` (the path may hold spaces,
+/// so a trailing number is taken as the speed)
+fn parse_path_and_speed(args: &str) -> Option<(String, f64)> {
+    let args = args.trim();
+    // `--realtime` spells out the default: the original timing, at speed 1
+    if let Some((path, "--realtime")) = args.rsplit_once(char::is_whitespace) {
+        return Some((path.trim_end().to_string(), 1.0));
+    }
+    if args == "--realtime" {
+        return None;
+    }
+    let (path, speed) = match args.rsplit_once(char::is_whitespace) {
+        Some((path, speed)) => match speed.parse::<f64>() {
+            Ok(speed) => (path.trim_end(), speed),
+            Err(_) => (args, 1.0),
+        },
+        None => (args, 1.0),
+    };
+    if path.is_empty() {
+        return None;
+    }
+    Some((path.to_string(), speed))
+}
+
+/// Parse the arguments of `compare <path>`, `compare checkpoint`,
+/// `compare divider <x>`, or `compare off` (the path may hold spaces)
+fn parse_compare(args: &str) -> Option<Command> {
+    if !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let op = match args.trim() {
+        "" => return None,
+        "checkpoint" => CompareCommand::Checkpoint,
+        "off" => CompareCommand::Off,
+        args => match args.split_once(char::is_whitespace) {
+            Some(("divider", x)) => CompareCommand::Divider(x.trim().parse().ok()?),
+            _ => CompareCommand::Image(args.to_string()),
+        },
+    };
+    Some(Command::Compare(op))
+}
+
+/// Parse the arguments of `diff <path>`, `diff overlay <path>`, or
+/// `diff overlay off` (paths may hold spaces)
+fn parse_diff(args: &str) -> Option<Command> {
+    if !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let op = match args.trim() {
+        "" | "overlay" => return None,
+        "overlay off" => DiffCommand::OverlayOff,
+        args => match args.split_once(char::is_whitespace) {
+            Some(("overlay", path)) => DiffCommand::Overlay(path.trim().to_string()),
+            _ => DiffCommand::Image(args.to_string()),
+        },
+    };
+    Some(Command::Diff(op))
+}
+
+/// Parse a rectangle written `x,y,w,h`
+fn parse_rect(rect: &str) -> Option<(usize, usize, usize, usize)> {
+    let [x, y, width, height] = rect.split(',').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    Some((
+        x.parse().ok()?,
+        y.parse().ok()?,
+        width.parse().ok()?,
+        height.parse().ok()?,
+    ))
+}
+
+/// Parse the arguments of `debug`, `debug step <path>`, or `debug stop` (the
+/// path may hold spaces)
+fn parse_debug(args: &str) -> Option<Command> {
+    if !args.is_empty() && !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    match args.trim() {
+        "" => Some(Command::Debug(DebugCommand::Status)),
+        "stop" => Some(Command::Debug(DebugCommand::Stop)),
+        args => {
+            let path = args.strip_prefix("step")?;
+            if !path.starts_with(char::is_whitespace) {
+                return None;
+            }
+            Some(Command::Debug(DebugCommand::Step(path.trim().to_string())))
+        }
+    }
+}
+
+/// Parse the arguments of `beep [freq] [ms]`
+fn parse_beep(args: &str) -> Option<Command> {
+    let (freq, ms) = match args.split_whitespace().collect::<Vec<_>>()[..] {
+        [] => (DEFAULT_BEEP_FREQ, DEFAULT_BEEP_MS),
+        [freq] => (freq.parse().ok()?, DEFAULT_BEEP_MS),
+        [freq, ms] => (freq.parse().ok()?, ms.parse().ok()?),
+        _ => return None,
+    };
+    Some(Command::Beep { freq, ms })
+}
+
+/// Parse the arguments of `image load <path> x,y [scale]` (the path may hold
+/// spaces, so the position and scale are read from the end of the line)
+fn parse_image(args: &str) -> Option<Command> {
+    let args = args.trim().strip_prefix("load")?;
+    if !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (head, last) = args.trim().rsplit_once(char::is_whitespace)?;
+    let (rest, point, scale) = if last.contains(',') {
+        (head, last, 1.0)
+    } else {
+        let (rest, point) = head.trim_end().rsplit_once(char::is_whitespace)?;
+        (rest, point, last.parse::<f64>().ok()?)
+    };
+    let (x, y) = point.split_once(',')?;
+    let path = rest.trim();
+    if path.is_empty() || !scale.is_finite() {
+        return None;
+    }
+    Some(Command::Image {
+        path: path.to_string(),
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        scale,
+    })
+}
+
+/// Parse the arguments of `palette set <index> <color>`, `palette get`, or
+/// `palette reset`
+fn parse_palette(args: &[&str]) -> Option<Command> {
+    let op = match args {
+        ["set", index, color] => PaletteCommand::Set {
+            index: index.parse().ok()?,
+            color: parse_color_value(color)?,
+        },
+        ["get"] => PaletteCommand::Get,
+        ["reset"] => PaletteCommand::Reset,
+        _ => return None,
+    };
+    Some(Command::Palette(op))
+}
+
+/// Parse the arguments of `var set <name> <value|"value">` / `var get <name>`
+fn parse_var(args: &str) -> Option<Command> {
+    let args = args.trim();
+    let (op, rest) = args.split_once(char::is_whitespace)?;
+    let rest = rest.trim_start();
+    let (name, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if !is_valid_var_name(name) {
+        return None;
+    }
+    let value = value.trim();
+    match op {
+        "get" if value.is_empty() => Some(Command::Var(VarCommand::Get(name.to_string()))),
+        "set" => {
+            let value = if value.starts_with('"') {
+                let (quoted, rest) = parse_quoted(value)?;
+                if !rest.trim().is_empty() {
+                    return None;
+                }
+                quoted
+            } else if value.is_empty() || value.contains(char::is_whitespace) {
+                return None;
+            } else {
+                value
+            };
+            if value.contains('"') {
+                return None;
+            }
+            Some(Command::Var(VarCommand::Set {
+                name: name.to_string(),
+                value: value.to_string(),
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Parse the arguments of `alias <name> "<command>; <command>"` / `alias <name>`
+fn parse_alias(args: &str) -> Option<Command> {
+    let args = args.trim();
+    let (name, body) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    if !is_valid_alias_name(name) {
+        return None;
+    }
+    let name = name.to_string();
+    if body.is_empty() {
+        return Some(Command::Alias(AliasCommand::Get(name)));
+    }
+    let (body, rest) = parse_quoted(body)?;
+    if !rest.trim().is_empty() {
+        return None;
+    }
+    Some(Command::Alias(AliasCommand::Define {
+        name,
+        body: body.to_string(),
+    }))
+}
+
+/// Parse a line naming alias `name` as the commands it stands for, with
+/// `args` put in for `$1`, `$2`, ... and aliases among them replaced by
+/// theirs (None if the number of arguments is wrong, a command doesn't
+/// parse, or it expands too deep or to too many commands)
+fn parse_alias_use(name: &str, args: &str) -> Option<Command> {
+    let body = alias_body(name)?;
+    let args = alias_args(args)?;
+    if args.len() != alias_params(&body) {
+        return None;
+    }
+    let commands = nested_alias(|| {
+        let mut commands = Vec::new();
+        for line in alias_lines(&body) {
+            match parse_command(&substitute_args(line, &args))? {
+                Command::RunAlias {
+                    commands: inner, ..
+                } => commands.extend(inner),
+                cmd => commands.push(cmd),
+            }
+            if commands.len() > MAX_ALIAS_COMMANDS {
+                return None;
+            }
+        }
+        Some(commands)
+    })?;
+    Some(Command::RunAlias {
+        name: name.to_string(),
+        args: args.into_iter().map(str::to_string).collect(),
+        commands,
+    })
+}
+
+/// Parse the arguments of `shadow on dx,dy [color] [blur]` / `shadow off`
+fn parse_shadow(args: &[&str]) -> Option<Command> {
+    match args {
+        ["off"] => Some(Command::Shadow(None)),
+        ["on", offset, rest @ ..] if rest.len() <= 2 => {
+            let (dx, dy) = offset.split_once(',')?;
+            let color = match rest.first() {
+                Some(color) => parse_color_value(color)?,
+                None => DEFAULT_SHADOW_COLOR,
+            };
+            let blur = match rest.get(1) {
+                Some(blur) => blur.parse::<usize>().ok()?,
+                None => 0,
+            };
+            Some(Command::Shadow(Some(Shadow {
+                dx: dx.parse().ok()?,
+                dy: dy.parse().ok()?,
+                color,
+                blur,
+            })))
+        }
+        _ => None,
+    }
+}
+
+/// Parse plain `x,y` points
+fn parse_point_list<T: FromStr>(args: &[&str]) -> Option<Vec<(T, T)>> {
+    args.iter()
+        .map(|p| {
+            let (x, y) = p.split_once(',')?;
+            Some((x.parse().ok()?, y.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse the arguments of `arrow x1,y1 x2,y2 [head] [both]`
+fn parse_arrow(args: &[&str]) -> Option<Command> {
+    let (ends, rest) = args.split_at_checked(2)?;
+    let [(x1, y1), (x2, y2)] = parse_point_list(ends)?[..] else {
+        return None;
+    };
+    let (both, rest) = match rest.split_last() {
+        Some((&"both", rest)) => (true, rest),
+        _ => (false, rest),
+    };
+    let head = match rest {
+        [] => None,
+        [head] => Some(head.parse().ok()?),
+        _ => return None,
+    };
+    Some(Command::Arrow {
+        x1,
+        y1,
+        x2,
+        y2,
+        head,
+        both,
+    })
+}
+
+/// Parse the arguments of `ngon x,y r sides [rotation]`
+fn parse_ngon(args: &[&str]) -> Option<Command> {
+    let (center, r, sides, rotation) = match args {
+        [center, r, sides] => (center, r, sides, 0.0),
+        [center, r, sides, rotation] => (center, r, sides, rotation.parse().ok()?),
+        _ => return None,
+    };
+    let (x, y) = center.split_once(',')?;
+    Some(Command::Ngon {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        r: r.parse().ok()?,
+        sides: sides.parse().ok()?,
+        rotation,
+    })
+}
+
+/// Parse the arguments of `star x,y outer inner points`
+fn parse_star(args: &[&str]) -> Option<Command> {
+    let [center, outer, inner, points] = args else {
+        return None;
+    };
+    let (x, y) = center.split_once(',')?;
+    Some(Command::Star {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        outer: outer.parse().ok()?,
+        inner: inner.parse().ok()?,
+        points: points.parse().ok()?,
+    })
+}
+
+/// Parse the arguments of `arc x,y r start end`, or of `pie` if `pie` is set
+fn parse_arc(args: &[&str], pie: bool) -> Option<Command> {
+    let [center, r, start, end] = args else {
+        return None;
+    };
+    let (x, y) = center.split_once(',')?;
+    let (x, y, r) = (x.parse().ok()?, y.parse().ok()?, r.parse().ok()?);
+    let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+    Some(if pie {
+        Command::Pie {
+            x,
+            y,
+            r,
+            start,
+            end,
+        }
+    } else {
+        Command::Arc {
+            x,
+            y,
+            r,
+            start,
+            end,
+        }
+    })
+}
+
+/// Parse the arguments of `scale x0,y0 x1,y1 [logx] [logy] [axes]` / `scale off`
+fn parse_scale(args: &[&str]) -> Option<Command> {
+    if args == ["off"] {
+        return Some(Command::Scale(None));
+    }
+    let (corners, options) = args.split_at_checked(2)?;
+    let [(x0, y0), (x1, y1)] = parse_point_list(corners)?[..] else {
+        return None;
+    };
+    let mut scale = PaneScale {
+        x0,
+        y0,
+        x1,
+        y1,
+        log_x: false,
+        log_y: false,
+        axes: false,
+    };
+    for option in options {
+        let flag = match *option {
+            "logx" => &mut scale.log_x,
+            "logy" => &mut scale.log_y,
+            "axes" => &mut scale.axes,
+            _ => return None,
+        };
+        if std::mem::replace(flag, true) {
+            return None;
+        }
+    }
+    Some(Command::Scale(Some(scale)))
+}
+
+/// Parse the arguments of `panes CxR [margin]` / `panes off`
+fn parse_panes(args: &[&str]) -> Option<Command> {
+    let (layout, margin) = match args {
+        ["off"] => return Some(Command::Panes(Panes::default())),
+        [layout] => (layout, 0),
+        [layout, margin] => (layout, margin.parse().ok()?),
+        _ => return None,
+    };
+    let (cols, rows) = layout.split_once('x')?;
+    Some(Command::Panes(Panes {
+        cols: cols.parse().ok()?,
+        rows: rows.parse().ok()?,
+        margin,
+    }))
+}
+
+/// Parse the arguments of `snap on [tolerance]` / `snap off`
+fn parse_snap(args: &[&str]) -> Option<Command> {
+    match args {
+        ["off"] => Some(Command::Snap(None)),
+        ["on"] => Some(Command::Snap(Some(DEFAULT_SNAP_TOLERANCE))),
+        ["on", tolerance] => Some(Command::Snap(Some(tolerance.parse().ok()?))),
+        _ => None,
+    }
+}
+
+/// Every protocol verb (the first word of a command line)
+///
+/// A line that doesn't parse is an unknown command if its first word isn't
+/// one of these, and a known command with bad arguments if it is.
+pub const COMMAND_VERBS: [&str; 89] = [
+    "snapshot",
+    "color",
+    "edge",
+    "fill",
+    "fill-gradient",
+    "size",
+    "stroke-style",
+    "brush",
+    "stroke",
+    "dot",
+    "clear",
+    "canvas",
+    "filter",
+    "state",
+    "layout",
+    "line",
+    "square",
+    "rect",
+    "rrect",
+    "circle",
+    "oval",
+    "triangle",
+    "arrow",
+    "arc",
+    "pie",
+    "bucket",
+    "polyline",
+    "points",
+    "polygon",
+    "ngon",
+    "star",
+    "bezier",
+    "fps",
+    "capture",
+    "group",
+    "undo",
+    "redo",
+    "stats",
+    "subscribe",
+    "shadow",
+    "snap",
+    "aa",
+    "blend",
+    "color-profile",
+    "panes",
+    "scale",
+    "plot",
+    "tsplot",
+    "hold",
+    "flip",
+    "clock",
+    "countdown",
+    "button",
+    "slider",
+    "input",
+    "obj",
+    "var",
+    "alias",
+    "unalias",
+    "palette",
+    "dialog",
+    "image",
+    "legend",
+    "notify",
+    "beep",
+    "export",
+    "save",
+    "load",
+    "copy",
+    "paste",
+    "record",
+    "timing",
+    "replay",
+    "playstrokes",
+    "lockregion",
+    "watch",
+    "unwatch",
+    "resume",
+    "compare",
+    "diff",
+    "unlockregion",
+    "auth",
+    "debug",
+    "step",
+    "getpixel",
+    "pick",
+    "getregion",
+    "checkbox",
+    "toggle",
+];
+
+/// What a command line that succeeded answers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandOutput {
+    /// Done, with nothing to report: answered `ok`
+    Ok,
+    /// A reply of its own (`saved canvas.png`, `edge:0 fill:none size:1`, ...)
+    Text(String),
+}
+
+impl fmt::Display for CommandOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandOutput::Ok => write!(f, "ok"),
+            CommandOutput::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Why a command line failed; answered `error: <reason>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// The first word isn't a command (empty for a blank line)
+    Unknown(String),
+    /// A command whose arguments didn't parse
+    InvalidArguments(&'static str),
+    /// The command parsed, but was refused or failed when run
+    Failed(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Unknown(verb) if verb.is_empty() => write!(f, "empty command"),
+            CommandError::Unknown(verb) => write!(f, "unknown command '{}'", verb),
+            CommandError::InvalidArguments(verb) => write!(f, "invalid arguments for '{}'", verb),
+            CommandError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// A failure reported as text (by a check, a file, a lookup) is a command
+/// that failed when run
+impl From<String> for CommandError {
+    fn from(reason: String) -> Self {
+        CommandError::Failed(reason)
+    }
+}
+
+/// What running a command comes to: its output, or why it failed
+pub type CommandResult = Result<CommandOutput, CommandError>;
+
+/// A command that failed for `reason`
+pub fn failed(reason: impl Into<String>) -> CommandResult {
+    Err(CommandError::Failed(reason.into()))
+}
+
+/// A command that succeeded, replying `text`
+pub fn reply(text: impl Into<String>) -> CommandResult {
+    Ok(CommandOutput::Text(text.into()))
+}
+
+/// A step that succeeded with nothing to report, or failed for a reason
+pub fn done(result: Result<(), String>) -> CommandResult {
+    result
+        .map(|()| CommandOutput::Ok)
+        .map_err(CommandError::Failed)
+}
+
+/// A result as the text of its reply, None for `ok`: the output, or
+/// `error: <reason>` (`response_line` without the `ok`)
+pub fn response_text(result: &CommandResult) -> Option<String> {
+    match result {
+        Ok(CommandOutput::Ok) => None,
+        result => Some(response_line(result)),
+    }
+}
+
+/// The protocol line answering a result: its output, or `error: <reason>`
+pub fn response_line(result: &CommandResult) -> String {
+    match result {
+        Ok(output) => output.to_string(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+/// Parse a command line, saying why it isn't a command when it doesn't parse
+///
+/// `parse_command` gives the same commands without the reason.
+pub fn parse_command_line(input: &str) -> Result<Command, CommandError> {
+    if let Some(cmd) = parse_command(input) {
+        return Ok(cmd);
+    }
+    let mut words = input.split_whitespace();
+    // A pane prefix is judged by the command it holds
+    let verb = match words.next() {
+        Some(prefix) if prefix.starts_with('@') => words.next().unwrap_or(prefix),
+        verb => verb.unwrap_or(""),
+    };
+    match COMMAND_VERBS.iter().find(|&&known| known == verb) {
+        Some(known) => Err(CommandError::InvalidArguments(known)),
+        None => match alias_body(verb) {
+            Some(body) => Err(CommandError::Failed(format!(
+                "alias '{}' takes {}, and its commands must parse",
+                verb,
+                match alias_params(&body) {
+                    0 => "no arguments".to_string(),
+                    1 => "1 argument".to_string(),
+                    n => format!("{} arguments", n),
+                }
+            ))),
+            None => Err(CommandError::Unknown(verb.to_string())),
+        },
+    }
 }
 
-/// Parse a command string into a Command enum
+/// Parse a command string into a Command enum (None if it isn't one; see
+/// `parse_command_line` for why)
 pub fn parse_command(input: &str) -> Option<Command> {
     let input = input.trim();
     let parts: Vec<&str> = input.split_whitespace().collect();
@@ -133,16 +2364,44 @@ pub fn parse_command(input: &str) -> Option<Command> {
     }
 
     match parts[0] {
-        "snapshot" => Some(Command::Snapshot),
+        "snapshot" if parts.get(1) == Some(&"region") => {
+            let args = input["snapshot".len()..].trim_start();
+            parse_snapshot_region(&args["region".len()..])
+        }
+        "snapshot" => {
+            // snapshot [path|base64] (a path is the rest of the line, so it may hold spaces)
+            let path = input["snapshot".len()..].trim();
+            match path {
+                "base64" => Some(Command::SnapshotBase64),
+                "" => Some(Command::Snapshot(None)),
+                _ => Some(Command::Snapshot(Some(path.to_string()))),
+            }
+        }
         "clear" => Some(Command::Clear),
+        "canvas" => CanvasSize::parse(&parts[1..])
+            .map(Command::CanvasSize)
+            .or_else(|| TabCommand::parse(&parts[1..]).map(Command::Tab))
+            .or_else(|| CanvasTransform::parse(&parts[1..]).map(Command::Canvas)),
+        "filter" => parse_filter(&parts[1..]),
         "state" => Some(Command::State),
+        "layout" if parts.len() == 1 => Some(Command::Layout),
+        "undo" => Some(Command::Undo),
+        "redo" => Some(Command::Redo),
+        "subscribe" if parts.len() == 1 => Some(Command::Subscribe),
+        "timing" => match parts[1..] {
+            ["on"] => Some(Command::Timing(true)),
+            ["off"] => Some(Command::Timing(false)),
+            _ => None,
+        },
+        "stats" => match parts.get(1) {
+            None => Some(Command::Stats(None)),
+            Some(&"on") => Some(Command::Stats(Some(true))),
+            Some(&"off") => Some(Command::Stats(Some(false))),
+            Some(_) => None,
+        },
         "color" => {
             if parts.len() >= 2 {
-                parts[1]
-                    .parse::<usize>()
-                    .ok()
-                    .filter(|&i| i < COLOR_PALETTE.len())
-                    .map(Command::Color)
+                parse_color_index(parts[1]).map(Command::Color)
             } else {
                 None
             }
@@ -152,11 +2411,7 @@ pub fn parse_command(input: &str) -> Option<Command> {
                 if parts[1] == "none" {
                     Some(Command::Edge(None))
                 } else {
-                    parts[1]
-                        .parse::<usize>()
-                        .ok()
-                        .filter(|&i| i < COLOR_PALETTE.len())
-                        .map(|i| Command::Edge(Some(i)))
+                    parse_color(parts[1]).map(|c| Command::Edge(Some(c)))
                 }
             } else {
                 None
@@ -167,16 +2422,18 @@ pub fn parse_command(input: &str) -> Option<Command> {
                 if parts[1] == "none" {
                     Some(Command::Fill(None))
                 } else {
-                    parts[1]
-                        .parse::<usize>()
-                        .ok()
-                        .filter(|&i| i < COLOR_PALETTE.len())
-                        .map(|i| Command::Fill(Some(i)))
+                    parse_color(parts[1]).map(|c| Command::Fill(Some(c)))
                 }
             } else {
                 None
             }
         }
+        "fill-gradient" => parse_fill_gradient(&parts[1..]),
+        "stroke-style" => parse_stroke_style(&parts[1..]),
+        "brush" => match parts[1..] {
+            ["shape", shape] => BrushShape::parse(shape).map(Command::BrushShape),
+            _ => None,
+        },
         "size" => {
             if parts.len() >= 2 {
                 parts[1]
@@ -273,6 +2530,18 @@ pub fn parse_command(input: &str) -> Option<Command> {
                 None
             }
         }
+        "rrect" => {
+            // rrect x1,y1 x2,y2 radius
+            let [first, second, r] = &parts[1..] else {
+                return None;
+            };
+            let corners = parse_point_list(&[first, second])?;
+            let [(x1, y1), (x2, y2)] = corners[..] else {
+                return None;
+            };
+            let r = r.parse::<usize>().ok()?;
+            Some(Command::RoundedRect { x1, y1, x2, y2, r })
+        }
         "circle" => {
             // circle x,y r
             if parts.len() >= 3 {
@@ -325,6 +2594,18 @@ pub fn parse_command(input: &str) -> Option<Command> {
                 None
             }
         }
+        "arc" | "pie" => parse_arc(&parts[1..], parts[0] == "pie"),
+        "arrow" => parse_arrow(&parts[1..]),
+        "ngon" => parse_ngon(&parts[1..]),
+        "star" => parse_star(&parts[1..]),
+        "bucket" => {
+            // bucket x,y
+            let (x, y) = parts.get(1)?.split_once(',')?;
+            Some(Command::Bucket {
+                x: x.parse().ok()?,
+                y: y.parse().ok()?,
+            })
+        }
         "polyline" => {
             // polyline x1,y1[:c[:s]] x2,y2[:c[:s]] x3,y3[:c[:s]] ...
             if parts.len() >= 3 {
@@ -339,6 +2620,24 @@ pub fn parse_command(input: &str) -> Option<Command> {
                 None
             }
         }
+        "polygon" => {
+            // polygon x1,y1 x2,y2 x3,y3 ...
+            let points = parse_point_list(&parts[1..])?;
+            if points.len() >= 3 {
+                Some(Command::Polygon(points))
+            } else {
+                None
+            }
+        }
+        "bezier" => {
+            // bezier x1,y1 cx,cy x2,y2 / bezier x1,y1 c1x,c1y c2x,c2y x2,y2
+            let points = parse_point_list(&parts[1..])?;
+            if (3..=4).contains(&points.len()) {
+                Some(Command::Bezier(points))
+            } else {
+                None
+            }
+        }
         "points" => {
             // points x1,y1[:c[:s]] x2,y2[:c[:s]] x3,y3[:c[:s]] ...
             if parts.len() >= 2 {
@@ -353,235 +2652,1203 @@ pub fn parse_command(input: &str) -> Option<Command> {
                 None
             }
         }
-        _ => None,
+        "fps" => {
+            // fps <1-240>
+            if parts.len() >= 2 {
+                parse_fps(parts[1]).map(Command::Fps)
+            } else {
+                None
+            }
+        }
+        "group" => parse_group(&input["group".len()..]),
+        "shadow" => parse_shadow(&parts[1..]),
+        "snap" => parse_snap(&parts[1..]),
+        "aa" => match parts[1..] {
+            ["on"] => Some(Command::Antialias(true)),
+            ["off"] => Some(Command::Antialias(false)),
+            _ => None,
+        },
+        "blend" => match parts[1..] {
+            [mode] => BlendMode::parse(mode).map(Command::Blend),
+            _ => None,
+        },
+        "color-profile" => match parts[1..] {
+            ["off"] => Some(Command::ColorProfile(None)),
+            [name] => ColorProfile::parse(name).map(|profile| Command::ColorProfile(Some(profile))),
+            _ => None,
+        },
+        "panes" => parse_panes(&parts[1..]),
+        "scale" => parse_scale(&parts[1..]),
+        // plot x,y [x,y ...]
+        "plot" if parts.len() >= 2 => parse_point_list(&parts[1..]).map(Command::Plot),
+        // tsplot <pane> <value>
+        "tsplot" if parts.len() == 3 => Some(Command::TsPlot {
+            pane: parts[1].parse().ok()?,
+            value: parts[2].parse().ok()?,
+        }),
+        // hold <pane> on|off
+        "hold" if parts.len() == 3 => Some(Command::Hold {
+            pane: parts[1].parse().ok()?,
+            on: match parts[2] {
+                "on" => true,
+                "off" => false,
+                _ => return None,
+            },
+        }),
+        // flip <pane>
+        "flip" if parts.len() == 2 => Some(Command::Flip(parts[1].parse().ok()?)),
+        verb if verb.starts_with('@') => {
+            // @N command (a pane can't hold another)
+            let index = verb[1..].parse().ok()?;
+            match parse_command(&input[verb.len()..])? {
+                Command::InPane(..) => None,
+                cmd => Some(Command::InPane(index, Box::new(cmd))),
+            }
+        }
+        "obj" => parse_object(&input["obj".len()..]),
+        "var" => parse_var(&input["var".len()..]),
+        "alias" => parse_alias(&input["alias".len()..]),
+        "unalias" => match parts[1..] {
+            [name] if is_valid_alias_name(name) => {
+                Some(Command::Alias(AliasCommand::Remove(name.to_string())))
+            }
+            _ => None,
+        },
+        "palette" => parse_palette(&parts[1..]),
+        "dialog" => parse_dialog(&input["dialog".len()..]),
+        "image" => parse_image(&input["image".len()..]),
+        "legend" => parse_legend(&input["legend".len()..]),
+        "notify" => parse_notify(&input["notify".len()..]),
+        "beep" => parse_beep(&input["beep".len()..]),
+        "record" => parse_record(&input["record".len()..]),
+        "compare" => parse_compare(&input["compare".len()..]),
+        "diff" => parse_diff(&input["diff".len()..]),
+        // save|load <path>, the path the rest of the line so it may hold spaces
+        "save" if parts.len() >= 2 => Some(Command::Save(input["save".len()..].trim().to_string())),
+        "load" if parts.len() >= 2 => Some(Command::Load(input["load".len()..].trim().to_string())),
+        // copy [x1,y1 x2,y2], the whole canvas without corners
+        "copy" => match parse_point_list(&parts[1..])?[..] {
+            [] => Some(Command::Copy(None)),
+            [(x1, y1), (x2, y2)] => Some(Command::Copy(Some((x1, y1, x2, y2)))),
+            _ => None,
+        },
+        // paste [x,y], at the canvas's top-left corner by default
+        "paste" => match parse_point_list(&parts[1..])?[..] {
+            [] => Some(Command::Paste {
+                x: 0,
+                y: CANVAS_TOP,
+            }),
+            [(x, y)] => Some(Command::Paste { x, y }),
+            _ => None,
+        },
+        "replay" => parse_replay(&input["replay".len()..]),
+        "playstrokes" => parse_playstrokes(&input["playstrokes".len()..]),
+        "export" => {
+            // The path is the rest of the line, so it may hold spaces
+            let rest = input["export".len()..].trim();
+            let (kind, path) = rest.split_once(char::is_whitespace)?;
+            let path = path.trim().to_string();
+            match kind {
+                "svg" => Some(Command::ExportSvg(path)),
+                "strokes" => Some(Command::ExportStrokes(path)),
+                "tiles" => {
+                    // export tiles <dir> <cols>x<rows>, the grid last
+                    let (dir, grid) = path.rsplit_once(char::is_whitespace)?;
+                    let (cols, rows) = grid.split_once('x')?;
+                    Some(Command::ExportTiles {
+                        dir: dir.trim().to_string(),
+                        cols: cols.parse().ok()?,
+                        rows: rows.parse().ok()?,
+                    })
+                }
+                _ => None,
+            }
+        }
+        "lockregion" => {
+            // lockregion x,y,w,h
+            let [rect] = parts[1..] else {
+                return None;
+            };
+            let (x, y, width, height) = parse_rect(rect)?;
+            Some(Command::LockRegion {
+                x,
+                y,
+                width,
+                height,
+            })
+        }
+        "watch" => {
+            // watch x,y,w,h
+            let [rect] = parts[1..] else {
+                return None;
+            };
+            let (x, y, width, height) = parse_rect(rect)?;
+            Some(Command::Watch {
+                x,
+                y,
+                width,
+                height,
+            })
+        }
+        "unwatch" => match parts[1..] {
+            [] => Some(Command::Unwatch(None)),
+            [id] => id.parse().ok().map(|id| Command::Unwatch(Some(id))),
+            _ => None,
+        },
+        "resume" if parts.len() == 1 => Some(Command::Resume),
+        "auth" => match parts[1..] {
+            [token] => Some(Command::Auth(token.to_string())),
+            _ => None,
+        },
+        "debug" => parse_debug(&input["debug".len()..]),
+        "step" if parts.len() == 1 => Some(Command::Step),
+        "unlockregion" => match parts[1..] {
+            [] => Some(Command::UnlockRegion(None)),
+            [id] => id.parse().ok().map(|id| Command::UnlockRegion(Some(id))),
+            _ => None,
+        },
+        "getpixel" => {
+            // getpixel x,y
+            let [point] = parts[1..] else {
+                return None;
+            };
+            let (x, y) = point.split_once(',')?;
+            Some(Command::GetPixel {
+                x: x.parse().ok()?,
+                y: y.parse().ok()?,
+            })
+        }
+        "pick" => {
+            // pick x,y
+            let [point] = parts[1..] else {
+                return None;
+            };
+            let (x, y) = point.split_once(',')?;
+            Some(Command::Pick {
+                x: x.parse().ok()?,
+                y: y.parse().ok()?,
+            })
+        }
+        "getregion" => {
+            // getregion x1,y1 x2,y2
+            let [p1, p2] = parts[1..] else {
+                return None;
+            };
+            let (x1, y1) = p1.split_once(',')?;
+            let (x2, y2) = p2.split_once(',')?;
+            Some(Command::GetRegion {
+                x1: x1.parse().ok()?,
+                y1: y1.parse().ok()?,
+                x2: x2.parse().ok()?,
+                y2: y2.parse().ok()?,
+            })
+        }
+        "clock" => parse_clock(&input["clock".len()..]),
+        "button" => parse_button(&input["button".len()..]),
+        "slider" => parse_slider(&parts[1..]),
+        "input" => parse_input(&parts[1..]),
+        "checkbox" => parse_check(CheckStyle::Checkbox, &input["checkbox".len()..]),
+        "toggle" => parse_check(CheckStyle::Toggle, &input["toggle".len()..]),
+        "countdown" => {
+            // countdown x,y <secs>
+            match parts[1..] {
+                [point, secs] => {
+                    let (x, y) = point.split_once(',')?;
+                    Some(Command::Object(ObjectCommand::Countdown {
+                        x: x.parse().ok()?,
+                        y: y.parse().ok()?,
+                        secs: secs
+                            .parse::<u64>()
+                            .ok()
+                            .filter(|&s| s <= MAX_COUNTDOWN_SECS)?,
+                    }))
+                }
+                _ => None,
+            }
+        }
+        "capture" => {
+            // capture <path> | capture stop
+            match parts.get(1) {
+                Some(&"stop") => Some(Command::Capture(None)),
+                Some(path) => Some(Command::Capture(Some(path.to_string()))),
+                None => None,
+            }
+        }
+        // Anything else may be an alias
+        name => parse_alias_use(name, &input[name.len()..]),
+    }
+}
+
+/// Check that a palette index is in range
+fn check_color_index(index: usize) -> Result<(), String> {
+    if index < PALETTE_LEN {
+        Ok(())
+    } else {
+        Err(format!(
+            "color {} is outside the palette (0-{})",
+            index,
+            PALETTE_LEN - 1
+        ))
+    }
+}
+
+/// Check that a brush size is in range
+fn check_brush_size(size: usize) -> Result<(), String> {
+    if (MIN_BRUSH_SIZE..=MAX_BRUSH_SIZE).contains(&size) {
+        Ok(())
+    } else {
+        Err(format!(
+            "size {} is outside {}-{}",
+            size, MIN_BRUSH_SIZE, MAX_BRUSH_SIZE
+        ))
+    }
+}
+
+/// Check that a `what` rectangle is at least 1x1 and lies on the canvas
+pub(crate) fn check_rect(
+    what: &str,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err(format!("{} must be at least 1x1", what));
+    }
+    let overflow = || format!("{} at {},{} overflows", what, x, y);
+    let right = x.checked_add(width - 1).ok_or_else(overflow)?;
+    let bottom = y.checked_add(height - 1).ok_or_else(overflow)?;
+    check_point(x, y)?;
+    check_point(right, bottom)
+}
+
+/// Check that a point lies in the drawable canvas area
+pub(crate) fn check_point(x: usize, y: usize) -> Result<(), String> {
+    if x < window_width() && (CANVAS_TOP..canvas_bottom()).contains(&y) {
+        Ok(())
+    } else {
+        Err(format!(
+            "point {},{} is outside the canvas (0-{}, {}-{})",
+            x,
+            y,
+            window_width() - 1,
+            CANVAS_TOP,
+            canvas_bottom() - 1
+        ))
+    }
+}
+
+/// Check that a center + radii shape fits on the canvas without overflowing
+fn check_extent(x: usize, y: usize, rx: usize, ry: usize) -> Result<(), String> {
+    let overflow = || format!("shape at {},{} overflows", x, y);
+    let left = x.checked_sub(rx).ok_or_else(overflow)?;
+    let top = y.checked_sub(ry).ok_or_else(overflow)?;
+    let right = x.checked_add(rx).ok_or_else(overflow)?;
+    let bottom = y.checked_add(ry).ok_or_else(overflow)?;
+    check_point(left, top)?;
+    check_point(right, bottom)
+}
+
+/// Check that object text can be written back in protocol syntax
+fn check_object_text(text: &str) -> Result<(), String> {
+    if text.contains(['"', '\n']) {
+        Err("object text may not contain quotes or newlines".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that a color has no alpha channel, where only opaque colors belong
+fn check_opaque(color: u32) -> Result<(), String> {
+    if is_opaque(color) {
+        Ok(())
+    } else {
+        Err(format!("color {} must be opaque", hex_color(color)))
+    }
+}
+
+/// Check that a variable name can be written back in protocol syntax
+fn check_var_name(name: &str) -> Result<(), String> {
+    if is_valid_var_name(name) {
+        Ok(())
+    } else {
+        Err(format!("invalid variable name '{}'", name))
+    }
+}
+
+/// Check that `name` is a valid alias name and not already a command's
+fn check_alias_name(name: &str) -> Result<(), String> {
+    if !is_valid_alias_name(name) {
+        Err(format!("invalid alias name '{}'", name))
+    } else if COMMAND_VERBS.contains(&name) {
+        Err(format!("'{}' is already a command", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that a list of attributed points is on the canvas with valid overrides
+fn check_attributed_points(points: &[AttributedPoint]) -> Result<(), String> {
+    for pt in points {
+        check_point(pt.x, pt.y)?;
+        if let Some(size) = pt.size {
+            check_brush_size(size)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that a command is safe to execute and its geometry lies on the canvas
+///
+/// Palette indices, colors, and brush sizes must be in range, shape extents must not
+/// overflow, and every point and shape bound must be inside the drawable area.
+/// Commands that pass can be drawn without any clipping.
+pub fn validate(cmd: &Command) -> Result<(), String> {
+    match cmd {
+        Command::Snapshot(Some(path)) => snapshot_format(path).map(|_| ()),
+        Command::SnapshotRegion {
+            x1,
+            y1,
+            x2,
+            y2,
+            path,
+        } => {
+            check_point(*x1, *y1)?;
+            check_point(*x2, *y2)?;
+            match path {
+                Some(path) => snapshot_format(path).map(|_| ()),
+                None => Ok(()),
+            }
+        }
+        Command::Snapshot(None)
+        | Command::SnapshotBase64
+        | Command::Clear
+        | Command::State
+        | Command::Layout => Ok(()),
+        // Every color is valid, the top byte being its transparency
+        Command::Edge(_) | Command::Fill(_) => Ok(()),
+        Command::FillGradient(Some(gradient)) => gradient.check(),
+        Command::FillGradient(None) => Ok(()),
+        Command::Color(i) => check_color_index(*i),
+        Command::Size(size) => check_brush_size(*size),
+        Command::StrokeStyle(style) => style.check(),
+        Command::BrushShape(_) => Ok(()),
+        Command::Canvas(transform) => transform.check(),
+        Command::CanvasSize(change) => change.layout(dimensions()).map(|_| ()),
+        Command::Tab(op) => op.check(),
+        Command::Filter { filter, region } => {
+            filter.check()?;
+            match region {
+                Some((x1, y1, x2, y2)) => {
+                    check_point(*x1, *y1)?;
+                    check_point(*x2, *y2)
+                }
+                None => Ok(()),
+            }
+        }
+        Command::Dot { x, y }
+        | Command::Bucket { x, y }
+        | Command::GetPixel { x, y }
+        | Command::Pick { x, y }
+        | Command::Paste { x, y } => check_point(*x, *y),
+        Command::Copy(Some((x1, y1, x2, y2))) => {
+            check_point(*x1, *y1)?;
+            check_point(*x2, *y2)
+        }
+        Command::Image { x, y, scale, .. } => {
+            if !is_valid_image_scale(*scale) {
+                return Err(format!(
+                    "image scale {} is out of range (above 0, up to {})",
+                    scale, MAX_IMAGE_SCALE
+                ));
+            }
+            check_point(*x, *y)
+        }
+        Command::Stroke { x1, y1, x2, y2 }
+        | Command::GetRegion { x1, y1, x2, y2 }
+        | Command::Line { x1, y1, x2, y2 }
+        | Command::Rect { x1, y1, x2, y2 }
+        | Command::RoundedRect { x1, y1, x2, y2, .. }
+        | Command::Triangle { x1, y1, x2, y2 } => {
+            check_point(*x1, *y1)?;
+            check_point(*x2, *y2)
+        }
+        Command::Arrow {
+            x1,
+            y1,
+            x2,
+            y2,
+            head,
+            ..
+        } => {
+            if *head == Some(0) {
+                return Err("arrow head size must be at least 1".to_string());
+            }
+            check_point(*x1, *y1)?;
+            check_point(*x2, *y2)
+        }
+        Command::Square { x, y, size } => {
+            let overflow = || format!("square at {},{} overflows", x, y);
+            let x2 = x.checked_add(*size).ok_or_else(overflow)?;
+            let y2 = y.checked_add(*size).ok_or_else(overflow)?;
+            check_point(*x, *y)?;
+            check_point(x2, y2)
+        }
+        Command::LockRegion {
+            x,
+            y,
+            width,
+            height,
+        } => check_rect("lock region", *x, *y, *width, *height),
+        Command::Watch {
+            x,
+            y,
+            width,
+            height,
+        } => check_rect("watch region", *x, *y, *width, *height),
+        Command::Compare(CompareCommand::Divider(x)) => {
+            if *x <= window_width() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "divider {} is outside the window (0-{})",
+                    x,
+                    window_width()
+                ))
+            }
+        }
+        Command::Circle { x, y, r } => check_extent(*x, *y, *r, *r),
+        Command::Oval { x, y, rx, ry } => check_extent(*x, *y, *rx, *ry),
+        Command::Arc {
+            x,
+            y,
+            r,
+            start,
+            end,
+        }
+        | Command::Pie {
+            x,
+            y,
+            r,
+            start,
+            end,
+        } => {
+            if !start.is_finite() || !end.is_finite() {
+                return Err(format!("{} angles must be numbers", cmd.name()));
+            }
+            check_extent(*x, *y, *r, *r)
+        }
+        Command::Ngon {
+            x,
+            y,
+            r,
+            sides,
+            rotation,
+        } => {
+            if !(3..=MAX_SIDES).contains(sides) {
+                return Err(format!("ngon needs 3 to {} sides", MAX_SIDES));
+            }
+            if !rotation.is_finite() {
+                return Err("ngon rotation must be a number".to_string());
+            }
+            check_extent(*x, *y, *r, *r)
+        }
+        Command::Star {
+            x,
+            y,
+            outer,
+            inner,
+            points,
+        } => {
+            if !(3..=MAX_SIDES).contains(points) {
+                return Err(format!("star needs 3 to {} points", MAX_SIDES));
+            }
+            let r = (*outer).max(*inner);
+            check_extent(*x, *y, r, r)
+        }
+        Command::Polyline(points) | Command::Points(points) => check_attributed_points(points),
+        Command::Polygon(points) | Command::Bezier(points) => {
+            points.iter().try_for_each(|&(x, y)| check_point(x, y))
+        }
+        Command::Legend { x, y, entries } => {
+            if entries.len() > MAX_LEGEND_ENTRIES {
+                return Err(format!(
+                    "legend has {} entries (max {})",
+                    entries.len(),
+                    MAX_LEGEND_ENTRIES
+                ));
+            }
+            for entry in entries {
+                if entry.label.chars().count() > MAX_LEGEND_LABEL_LEN {
+                    return Err(format!(
+                        "legend label is longer than {} characters",
+                        MAX_LEGEND_LABEL_LEN
+                    ));
+                }
+            }
+            // The whole box must fit the canvas
+            let (width, height) = legend_size(entries);
+            check_point(*x, *y)?;
+            check_point(x.saturating_add(width - 1), y.saturating_add(height - 1))
+        }
+        Command::Fps(fps) => {
+            if (MIN_FPS..=MAX_FPS).contains(fps) {
+                Ok(())
+            } else {
+                Err(format!("fps {} is outside {}-{}", fps, MIN_FPS, MAX_FPS))
+            }
+        }
+        Command::ExportTiles { cols, rows, .. } => check_tile_grid(*cols, *rows),
+        Command::Save(path) | Command::Load(path) => check_project_path(path),
+        Command::Capture(_)
+        | Command::GroupEnd
+        | Command::Undo
+        | Command::Redo
+        | Command::Stats(_)
+        | Command::Subscribe
+        | Command::Copy(None)
+        | Command::Palette(PaletteCommand::Get | PaletteCommand::Reset)
+        | Command::Dialog(DialogCommand::Close)
+        | Command::ExportSvg(_)
+        | Command::ExportStrokes(_)
+        | Command::Record(_)
+        | Command::Timing(_)
+        | Command::UnlockRegion(_)
+        | Command::Auth(_)
+        | Command::Debug(_)
+        | Command::Step
+        | Command::Unwatch(_)
+        | Command::Resume
+        | Command::Compare(
+            CompareCommand::Image(_) | CompareCommand::Checkpoint | CompareCommand::Off,
+        )
+        | Command::Diff(_)
+        | Command::Gif(
+            GifCommand::Stop
+            | GifCommand::Start {
+                interval_ms: None, ..
+            },
+        ) => Ok(()),
+        Command::Gif(GifCommand::Start {
+            interval_ms: Some(ms),
+            ..
+        }) => {
+            if is_valid_gif_interval(*ms) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "gif interval {} is outside {}-{}",
+                    ms, MIN_GIF_INTERVAL_MS, MAX_GIF_INTERVAL_MS
+                ))
+            }
+        }
+        Command::Replay { speed, .. } | Command::PlayStrokes { speed, .. } => {
+            check_replay_speed(cmd.name(), *speed)
+        }
+        Command::Dialog(DialogCommand::Open { message, .. }) => {
+            check_object_text(message)?;
+            if message.trim().is_empty() || message.chars().count() > MAX_DIALOG_MESSAGE_LEN {
+                return Err(format!(
+                    "dialog message must be 1-{} characters",
+                    MAX_DIALOG_MESSAGE_LEN
+                ));
+            }
+            Ok(())
+        }
+        Command::Notify { text, secs, .. } => {
+            check_object_text(text)?;
+            if text.trim().is_empty() || text.chars().count() > MAX_NOTIFY_LEN {
+                return Err(format!(
+                    "notify text must be 1-{} characters",
+                    MAX_NOTIFY_LEN
+                ));
+            }
+            if !(1..=MAX_NOTIFY_SECS).contains(secs) {
+                return Err(format!(
+                    "notify duration {} is outside 1-{}",
+                    secs, MAX_NOTIFY_SECS
+                ));
+            }
+            Ok(())
+        }
+        Command::Beep { freq, ms } => {
+            if !(MIN_BEEP_FREQ..=MAX_BEEP_FREQ).contains(freq) {
+                return Err(format!(
+                    "beep frequency {} is outside {}-{}",
+                    freq, MIN_BEEP_FREQ, MAX_BEEP_FREQ
+                ));
+            }
+            if !(1..=MAX_BEEP_MS).contains(ms) {
+                return Err(format!("beep length {} is outside 1-{}", ms, MAX_BEEP_MS));
+            }
+            Ok(())
+        }
+        Command::Shadow(None)
+        | Command::Snap(None)
+        | Command::Antialias(_)
+        | Command::Blend(_)
+        | Command::ColorProfile(_) => Ok(()),
+        Command::Panes(panes) => panes.check(),
+        Command::Scale(Some(scale)) => scale.check(),
+        Command::Scale(None) => Ok(()),
+        Command::Plot(points) => {
+            if points.iter().all(|(x, y)| x.is_finite() && y.is_finite()) {
+                Ok(())
+            } else {
+                Err("plot points must be numbers".to_string())
+            }
+        }
+        Command::TsPlot { pane, value } => {
+            if *pane == 0 {
+                Err("panes are numbered from 1".to_string())
+            } else if !value.is_finite() {
+                Err("tsplot value must be a number".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        Command::Hold { pane, .. } | Command::Flip(pane) => {
+            if *pane == 0 {
+                Err("panes are numbered from 1".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        // The pane's position (and so where the command lands) is only
+        // known when it runs
+        Command::InPane(index, cmd) => {
+            if *index == 0 {
+                Err("panes are numbered from 1".to_string())
+            } else if !cmd.runs_in_pane() {
+                Err(format!("@{} only prefixes drawing commands", index))
+            } else {
+                Ok(())
+            }
+        }
+        Command::Snap(Some(tolerance)) => {
+            if *tolerance > 0.0 && *tolerance <= MAX_SNAP_TOLERANCE {
+                Ok(())
+            } else {
+                Err(format!(
+                    "snap tolerance {} is outside 0-{} (above 0)",
+                    tolerance, MAX_SNAP_TOLERANCE
+                ))
+            }
+        }
+        Command::Shadow(Some(shadow)) => {
+            if shadow.dx.abs() > MAX_SHADOW_OFFSET || shadow.dy.abs() > MAX_SHADOW_OFFSET {
+                Err(format!(
+                    "shadow offset {},{} is outside -{}..{}",
+                    shadow.dx, shadow.dy, MAX_SHADOW_OFFSET, MAX_SHADOW_OFFSET
+                ))
+            } else if shadow.blur > MAX_SHADOW_BLUR {
+                Err(format!(
+                    "shadow blur {} is outside 0-{}",
+                    shadow.blur, MAX_SHADOW_BLUR
+                ))
+            } else if !is_opaque(shadow.color) {
+                Err(format!(
+                    "shadow color {} must be opaque",
+                    hex_color(shadow.color)
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        Command::GroupBegin(label) => {
+            if label.contains(['"', '\n']) {
+                Err("group label may not contain quotes or newlines".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        Command::Object(ObjectCommand::Text { x, y, text, scale }) => {
+            check_point(*x, *y)?;
+            check_object_text(text)?;
+            if (1..=MAX_TEXT_SCALE).contains(scale) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "text scale {} is outside 1-{}",
+                    scale, MAX_TEXT_SCALE
+                ))
+            }
+        }
+        Command::Object(ObjectCommand::SetText { text, .. }) => check_object_text(text),
+        Command::Object(ObjectCommand::Delete(_) | ObjectCommand::List) => Ok(()),
+        Command::Object(ObjectCommand::Blink { ms, .. }) => {
+            if *ms == 0 || (MIN_BLINK_MS..=MAX_BLINK_MS).contains(ms) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "blink {}ms is outside {}-{}ms (0 stops blinking)",
+                    ms, MIN_BLINK_MS, MAX_BLINK_MS
+                ))
+            }
+        }
+        Command::Object(ObjectCommand::Clock { x, y, format }) => {
+            check_point(*x, *y)?;
+            if format.contains(['"', '\n']) {
+                Err("clock format may not contain quotes or newlines".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        Command::Object(ObjectCommand::Countdown { x, y, secs }) => {
+            check_point(*x, *y)?;
+            if *secs <= MAX_COUNTDOWN_SECS {
+                Ok(())
+            } else {
+                Err(format!(
+                    "countdown {} is outside 0-{} seconds",
+                    secs, MAX_COUNTDOWN_SECS
+                ))
+            }
+        }
+        Command::Object(ObjectCommand::Button {
+            x,
+            y,
+            w,
+            h,
+            label,
+            event,
+        }) => {
+            if *w == 0 || *h == 0 {
+                return Err(format!("button size {}x{} is empty", w, h));
+            }
+            check_point(*x, *y)?;
+            check_point(x.saturating_add(w - 1), y.saturating_add(h - 1))?;
+            check_object_text(label)?;
+            if is_valid_event_name(event) {
+                Ok(())
+            } else {
+                Err(format!("invalid event name '{}'", event))
+            }
+        }
+        Command::Object(ObjectCommand::Slider {
+            x,
+            y,
+            w,
+            min,
+            max,
+            var,
+        }) => {
+            if *w < MIN_SLIDER_WIDTH {
+                return Err(format!(
+                    "slider width {} is below the minimum {}",
+                    w, MIN_SLIDER_WIDTH
+                ));
+            }
+            if min >= max {
+                return Err(format!("slider range {}..{} is empty", min, max));
+            }
+            check_point(*x, *y)?;
+            check_point(x.saturating_add(w - 1), y.saturating_add(SLIDER_HEIGHT - 1))?;
+            check_var_name(var)
+        }
+        Command::Object(ObjectCommand::Check {
+            x,
+            y,
+            style,
+            label,
+            var,
+        }) => {
+            let (w, h) = style.size(label);
+            check_point(*x, *y)?;
+            check_point(x.saturating_add(w - 1), y.saturating_add(h - 1))?;
+            check_object_text(label)?;
+            check_var_name(var)
+        }
+        Command::Object(ObjectCommand::Input { x, y, w, var }) => {
+            if *w < MIN_INPUT_WIDTH {
+                return Err(format!(
+                    "input width {} is below the minimum {}",
+                    w, MIN_INPUT_WIDTH
+                ));
+            }
+            check_point(*x, *y)?;
+            check_point(x.saturating_add(w - 1), y.saturating_add(INPUT_HEIGHT - 1))?;
+            check_var_name(var)
+        }
+        Command::Var(VarCommand::Set { name, value }) => {
+            check_var_name(name)?;
+            if value.contains(['"', '\n']) {
+                Err("variable value may not contain quotes or newlines".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        Command::Var(VarCommand::Get(name)) => check_var_name(name),
+        Command::Alias(AliasCommand::Define { name, body }) => {
+            check_alias_name(name)?;
+            if body.contains(['"', '\n']) {
+                Err("alias body may not contain quotes or newlines".to_string())
+            } else if body.len() > MAX_ALIAS_LEN {
+                Err(format!("alias body is longer than {} bytes", MAX_ALIAS_LEN))
+            } else if alias_lines(body).next().is_none() {
+                Err("alias body has no commands".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        Command::Alias(AliasCommand::Get(name) | AliasCommand::Remove(name)) => {
+            check_alias_name(name)
+        }
+        Command::RunAlias { commands, .. } => commands.iter().try_for_each(validate),
+        Command::Palette(PaletteCommand::Set { index, color }) => {
+            check_color_index(*index)?;
+            check_opaque(*color)
+        }
     }
 }
 
 /// Execute a command, modifying the buffer and/or state
-/// Returns an optional response string to print to stdout
+/// Returns its output, or why it failed
 pub fn execute_command(
     cmd: &Command,
     buffer: &mut [u32],
-    edge_color_index: &mut Option<usize>,
-    fill_color_index: &mut Option<usize>,
+    edge_color: &mut Option<u32>,
+    fill_color: &mut Option<u32>,
     brush_size: &mut usize,
-) -> Option<String> {
+) -> CommandResult {
     match cmd {
-        Command::Snapshot => {
-            if let Err(e) = save_canvas_png(buffer, "canvas.png") {
-                Some(format!("error: {}", e))
-            } else {
-                Some("saved canvas.png".to_string())
+        Command::Snapshot(path) => {
+            let default = default_snapshot_path();
+            let path = path.as_deref().unwrap_or(&default);
+            let result = save_canvas_image(buffer, path);
+            snapshot_response(path, &result)
+        }
+        Command::SnapshotBase64 => base64_snapshot_response(buffer),
+        Command::Image { path, x, y, scale } => match load_image(path) {
+            Ok(img) => {
+                paste_image(buffer, &img, *x, *y, *scale);
+                Ok(CommandOutput::Ok)
             }
+            Err(e) => Err(e.into()),
+        },
+        Command::Copy(corners) => copy_response(&copy_canvas(buffer, *corners)),
+        Command::Paste { x, y } => paste_clipboard(buffer, *x, *y),
+        Command::GetPixel { x, y } => pixel_response(buffer, *x, *y),
+        Command::Pick { x, y } => pick_response(buffer, *x, *y),
+        Command::GetRegion { x1, y1, x2, y2 } => region_response(buffer, (*x1, *y1), (*x2, *y2)),
+        Command::SnapshotRegion {
+            x1,
+            y1,
+            x2,
+            y2,
+            path,
+        } => {
+            let default = default_snapshot_path();
+            let path = path.as_deref().unwrap_or(&default);
+            let result = save_region_image(buffer, (*x1, *y1), (*x2, *y2), path);
+            snapshot_response(path, &result)
         }
         Command::Color(index) => {
-            *edge_color_index = Some(*index);
-            None
+            *edge_color = current_palette().get(*index);
+            Ok(CommandOutput::Ok)
         }
         Command::Edge(color_opt) => {
-            *edge_color_index = *color_opt;
-            None
+            *edge_color = *color_opt;
+            Ok(CommandOutput::Ok)
         }
         Command::Fill(color_opt) => {
-            *fill_color_index = *color_opt;
-            None
+            *fill_color = *color_opt;
+            Ok(CommandOutput::Ok)
         }
         Command::Size(size) => {
             *brush_size = *size;
-            None
+            Ok(CommandOutput::Ok)
         }
         Command::Stroke { x1, y1, x2, y2 } => {
-            if let Some(idx) = *edge_color_index {
-                let color = COLOR_PALETTE[idx];
+            if let Some(color) = *edge_color {
                 draw_brush_line(buffer, *x1, *y1, *x2, *y2, color, *brush_size);
             }
-            None
+            Ok(CommandOutput::Ok)
         }
         Command::Dot { x, y } => {
-            if let Some(idx) = *edge_color_index {
-                let color = COLOR_PALETTE[idx];
-                draw_circle(buffer, *x, *y, *brush_size, color);
+            if let Some(color) = *edge_color {
+                draw_brush_dot(buffer, *x, *y, *brush_size, color);
             }
-            None
+            Ok(CommandOutput::Ok)
         }
         Command::Clear => {
             clear_canvas(buffer);
-            None
+            Ok(CommandOutput::Ok)
         }
-        Command::State => {
-            let edge_str = match edge_color_index {
-                Some(i) => i.to_string(),
-                None => "none".to_string(),
-            };
-            let fill_str = match fill_color_index {
-                Some(i) => i.to_string(),
-                None => "none".to_string(),
+        Command::Canvas(transform) => match transform.check() {
+            Ok(()) => {
+                transform_canvas(buffer, transform);
+                Ok(CommandOutput::Ok)
+            }
+            Err(e) => Err(e.into()),
+        },
+        Command::Filter { filter, region } => {
+            let area = match region {
+                Some((x1, y1, x2, y2)) => clip_region((*x1, *y1), (*x2, *y2)),
+                None => Ok((0, CANVAS_TOP, window_width() - 1, canvas_bottom() - 1)),
             };
-            Some(format!(
-                "edge:{} fill:{} size:{}",
-                edge_str, fill_str, *brush_size
-            ))
-        }
-        Command::Line { x1, y1, x2, y2 } => {
-            let edge_color = edge_color_index.map(|i| COLOR_PALETTE[i]);
-            let fill_color = fill_color_index.map(|i| COLOR_PALETTE[i]);
-            draw_shape_with_fill(
-                buffer,
-                ToolMode::Line,
-                *x1,
-                *y1,
-                *x2,
-                *y2,
-                edge_color,
-                fill_color,
-                *brush_size,
-            );
-            None
-        }
-        Command::Square { x, y, size } => {
-            let edge_color = edge_color_index.map(|i| COLOR_PALETTE[i]);
-            let fill_color = fill_color_index.map(|i| COLOR_PALETTE[i]);
-            // Convert top-left + size to bounding box coordinates
-            let x2 = x + size;
-            let y2 = y + size;
-            draw_shape_with_fill(
-                buffer,
-                ToolMode::Square,
-                *x,
-                *y,
-                x2,
-                y2,
-                edge_color,
-                fill_color,
-                *brush_size,
-            );
-            None
+            match filter.check().and(area) {
+                Ok(area) => {
+                    apply_filter(buffer, filter, area);
+                    Ok(CommandOutput::Ok)
+                }
+                Err(e) => Err(e.into()),
+            }
         }
-        Command::Rect { x1, y1, x2, y2 } => {
-            let edge_color = edge_color_index.map(|i| COLOR_PALETTE[i]);
-            let fill_color = fill_color_index.map(|i| COLOR_PALETTE[i]);
-            draw_shape_with_fill(
-                buffer,
-                ToolMode::Rectangle,
-                *x1,
-                *y1,
-                *x2,
-                *y2,
-                edge_color,
-                fill_color,
-                *brush_size,
-            );
-            None
-        }
-        Command::Circle { x, y, r } => {
-            let edge_color = edge_color_index.map(|i| COLOR_PALETTE[i]);
-            let fill_color = fill_color_index.map(|i| COLOR_PALETTE[i]);
-            // Convert center + radius to bounding box coordinates
-            let x1 = x.saturating_sub(*r);
-            let y1 = y.saturating_sub(*r);
-            let x2 = x + r;
-            let y2 = y + r;
-            draw_shape_with_fill(
-                buffer,
-                ToolMode::Circle,
-                x1,
-                y1,
-                x2,
-                y2,
-                edge_color,
-                fill_color,
-                *brush_size,
-            );
-            None
-        }
-        Command::Oval { x, y, rx, ry } => {
-            let edge_color = edge_color_index.map(|i| COLOR_PALETTE[i]);
-            let fill_color = fill_color_index.map(|i| COLOR_PALETTE[i]);
-            // Convert center + radii to bounding box coordinates
-            let x1 = x.saturating_sub(*rx);
-            let y1 = y.saturating_sub(*ry);
-            let x2 = x + rx;
-            let y2 = y + ry;
+        Command::State => Ok(CommandOutput::Text(settings_summary(
+            edge_color,
+            fill_color,
+            *brush_size,
+        ))),
+        Command::Line { .. }
+        | Command::Square { .. }
+        | Command::Rect { .. }
+        | Command::Circle { .. }
+        | Command::Oval { .. }
+        | Command::Triangle { .. } => {
+            let (tool, x1, y1, x2, y2) = cmd.shape_bounds().expect("shape command");
             draw_shape_with_fill(
                 buffer,
-                ToolMode::Oval,
+                tool,
                 x1,
                 y1,
                 x2,
                 y2,
-                edge_color,
-                fill_color,
+                *edge_color,
+                *fill_color,
                 *brush_size,
             );
-            None
+            Ok(CommandOutput::Ok)
         }
-        Command::Triangle { x1, y1, x2, y2 } => {
-            let edge_color = edge_color_index.map(|i| COLOR_PALETTE[i]);
-            let fill_color = fill_color_index.map(|i| COLOR_PALETTE[i]);
-            draw_shape_with_fill(
-                buffer,
-                ToolMode::Triangle,
-                *x1,
-                *y1,
-                *x2,
-                *y2,
-                edge_color,
-                fill_color,
-                *brush_size,
-            );
-            None
+        Command::Bucket { x, y } => {
+            if let Some(color) = *fill_color {
+                flood_fill(buffer, *x, *y, color);
+            }
+            Ok(CommandOutput::Ok)
+        }
+        Command::Polygon(_) | Command::Ngon { .. } | Command::Star { .. } => {
+            let points = cmd.polygon_vertices().expect("polygon command");
+            draw_polygon_with_fill(buffer, &points, *edge_color, *fill_color, *brush_size);
+            Ok(CommandOutput::Ok)
+        }
+        Command::Bezier(points) => {
+            if let Some(color) = *edge_color {
+                draw_bezier(buffer, points, color, *brush_size);
+            }
+            Ok(CommandOutput::Ok)
+        }
+        Command::Arrow { .. } => {
+            if let Some(color) = *edge_color {
+                let arrow = cmd.arrow(*brush_size).expect("arrow command");
+                draw_arrow(buffer, &arrow, color, *brush_size);
+            }
+            Ok(CommandOutput::Ok)
+        }
+        Command::RoundedRect { .. } => {
+            let rect = cmd.rounded_rect().expect("rounded rect command");
+            draw_rounded_rect_with_fill(buffer, &rect, *edge_color, *fill_color, *brush_size);
+            Ok(CommandOutput::Ok)
+        }
+        Command::Legend { x, y, entries } => {
+            draw_legend(buffer, *x, *y, entries, edge_color.unwrap_or(BLACK));
+            Ok(CommandOutput::Ok)
+        }
+        Command::Arc { .. } | Command::Pie { .. } => {
+            let arc = cmd.arc_shape().expect("arc command");
+            draw_arc_with_fill(buffer, &arc, *edge_color, *fill_color, *brush_size);
+            Ok(CommandOutput::Ok)
         }
         Command::Polyline(points) => {
-            for window in points.windows(2) {
-                // Use the END point's attributes for this segment
-                let color_idx = window[1].color.or(*edge_color_index);
-                if let Some(idx) = color_idx {
-                    let color = COLOR_PALETTE[idx];
-                    let size = window[1].size.unwrap_or(*brush_size);
-                    draw_brush_line(
-                        buffer,
-                        window[0].x,
-                        window[0].y,
-                        window[1].x,
-                        window[1].y,
-                        color,
-                        size,
-                    );
-                }
-            }
-            None
+            // Use the END point's attributes for each segment
+            let segments: Vec<_> = points
+                .windows(2)
+                .filter_map(|w| Some((w[1].color.or(*edge_color)?, w)))
+                .collect();
+            // Consecutive segments in one color are composited as one shape,
+            // so translucent joins don't darken
+            for run in segments.chunk_by(|a, b| a.0 == b.0) {
+                let color = run[0].0;
+                draw_covered(buffer, color, |buffer| {
+                    for (_, w) in run {
+                        let size = w[1].size.unwrap_or(*brush_size);
+                        draw_brush_line(buffer, w[0].x, w[0].y, w[1].x, w[1].y, color, size);
+                    }
+                });
+            }
+            Ok(CommandOutput::Ok)
         }
         Command::Points(points) => {
             for pt in points {
-                let color_idx = pt.color.or(*edge_color_index);
-                if let Some(idx) = color_idx {
-                    let color = COLOR_PALETTE[idx];
+                if let Some(color) = pt.color.or(*edge_color) {
                     let size = pt.size.unwrap_or(*brush_size);
-                    draw_circle(buffer, pt.x, pt.y, size, color);
+                    draw_brush_dot(buffer, pt.x, pt.y, size, color);
                 }
             }
-            None
+            Ok(CommandOutput::Ok)
+        }
+        Command::Fps(_)
+        | Command::Capture(_)
+        | Command::GroupBegin(_)
+        | Command::GroupEnd
+        | Command::Undo
+        | Command::Redo
+        | Command::Stats(_)
+        | Command::Subscribe
+        | Command::Notify { .. }
+        | Command::Beep { .. }
+        | Command::Save(_)
+        | Command::Load(_)
+        | Command::Tab(_)
+        | Command::Record(_)
+        | Command::Replay { .. }
+        | Command::PlayStrokes { .. }
+        | Command::Timing(_)
+        | Command::Gif(_)
+        | Command::LockRegion { .. }
+        | Command::UnlockRegion(_)
+        | Command::Auth(_)
+        | Command::Debug(_)
+        | Command::Step
+        | Command::Watch { .. }
+        | Command::Unwatch(_)
+        | Command::Resume
+        | Command::Compare(_)
+        | Command::Diff(_) => {
+            // Frame pacing, capture, history, the stats overlay, event
+            // subscriptions, toasts, beeps, recording and replay, response
+            // timing, GIF recording, region locks, client access, the step
+            // debugger, watches, the split view, and diffs belong to the
+            // session, which applies them before they get here; run without
+            // one, they are refused rather than quietly doing nothing
+            failed(format!("{} needs a session", cmd.name()))
+        }
+        Command::Shadow(_)
+        | Command::Snap(_)
+        | Command::Antialias(_)
+        | Command::Blend(_)
+        | Command::ColorProfile(_)
+        | Command::FillGradient(_)
+        | Command::StrokeStyle(_)
+        | Command::BrushShape(_)
+        | Command::Panes(_)
+        | Command::Layout
+        | Command::InPane(..)
+        | Command::Scale(_)
+        | Command::Plot(_)
+        | Command::TsPlot { .. }
+        | Command::Hold { .. }
+        | Command::Flip(_)
+        | Command::Object(_)
+        | Command::Var(_)
+        | Command::Alias(_)
+        | Command::RunAlias { .. }
+        | Command::Palette(_)
+        | Command::Dialog(_)
+        | Command::ExportSvg(_)
+        | Command::ExportStrokes(_)
+        | Command::ExportTiles { .. }
+        | Command::CanvasSize(_) => {
+            // Shadow, shape snap, anti-aliasing, blend, and gradient
+            // settings, panes (and `layout`), their scales (which place plots and time
+            // series) and held views, retained objects, variables, aliases, the
+            // palette, the dialog, the display list, the stroke log, and the
+            // canvas's size live in AppState, which applies them
+            Ok(CommandOutput::Ok)
         }
     }
 }
 
-/// Save the canvas portion of the buffer to a PNG file
-pub fn save_canvas_png(buffer: &[u32], path: &str) -> Result<(), String> {
+/// Save the canvas portion of the buffer to an image file, in the format
+/// given by the path's extension (PNG, JPEG, BMP, or WebP)
+///
+/// Fails without writing if the extension is unknown or the directory doesn't exist.
+pub fn save_canvas_image(buffer: &[u32], path: &str) -> Result<(), String> {
+    save_image(&canvas_image(buffer), path)
+}
+
+/// Save the rectangle between two corners (inclusive, either order) of the
+/// canvas portion of the buffer to `path`, in the format its extension names
+///
+/// The rectangle is clipped to the canvas; one wholly outside it is an error.
+pub fn save_region_image(
+    buffer: &[u32],
+    (x1, y1): (usize, usize),
+    (x2, y2): (usize, usize),
+    path: &str,
+) -> Result<(), String> {
+    let (left, top, right, bottom) = clip_region((x1, y1), (x2, y2))?;
+    save_image(&region_image(buffer, left, top, right, bottom), path)
+}
+
+/// The rectangle between two corners (inclusive, either order) clipped to the
+/// canvas, as (left, top, right, bottom); one wholly outside it is an error
+pub fn clip_region(
+    (x1, y1): (usize, usize),
+    (x2, y2): (usize, usize),
+) -> Result<(usize, usize, usize, usize), String> {
+    let left = x1.min(x2);
+    let top = y1.min(y2).max(CANVAS_TOP);
+    let right = x1.max(x2).min(window_width() - 1);
+    let bottom = y1.max(y2).min(canvas_bottom() - 1);
+    if left > right || top > bottom {
+        return Err(format!(
+            "region {},{} {},{} is outside the canvas",
+            x1, y1, x2, y2
+        ));
+    }
+    Ok((left, top, right, bottom))
+}
+
+/// Save an image to `path` after checking its format and directory
+fn save_image(img: &image::RgbImage, path: &str) -> Result<(), String> {
+    let format = snapshot_format(path)?;
+    if let Some(dir) = Path::new(path).parent() {
+        if !dir.as_os_str().is_empty() && !dir.is_dir() {
+            return Err(format!("directory {} does not exist", dir.display()));
+        }
+    }
+    save_profiled(img, Path::new(path), format)
+}
+
+/// Encode the canvas portion of the buffer as PNG bytes in memory
+pub fn encode_canvas_png(buffer: &[u32]) -> Result<Vec<u8>, String> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    write_profiled(&canvas_image(buffer), &mut png, image::ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+/// The canvas portion of the buffer as an RGB image
+fn canvas_image(buffer: &[u32]) -> image::RgbImage {
+    region_image(
+        buffer,
+        0,
+        CANVAS_TOP,
+        window_width() - 1,
+        canvas_bottom() - 1,
+    )
+}
+
+/// The pixels from (left, top) to (right, bottom), inclusive, as an RGB image
+fn region_image(
+    buffer: &[u32],
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+) -> image::RgbImage {
     use image::{ImageBuffer, Rgb};
 
-    let canvas_height = CANVAS_BOTTOM - CANVAS_TOP;
-    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> =
-        ImageBuffer::new(WIDTH as u32, canvas_height as u32);
+    let (width, height) = (right - left + 1, bottom - top + 1);
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width as u32, height as u32);
 
-    for y in 0..canvas_height {
-        for x in 0..WIDTH {
-            let pixel = buffer[(y + CANVAS_TOP) * WIDTH + x];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = buffer[(y + top) * window_width() + x + left];
             let r = ((pixel >> 16) & 0xFF) as u8;
             let g = ((pixel >> 8) & 0xFF) as u8;
             let b = (pixel & 0xFF) as u8;
             img.put_pixel(x as u32, y as u32, Rgb([r, g, b]));
         }
     }
-
-    img.save(path).map_err(|e| e.to_string())
+    img
 }
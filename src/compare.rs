@@ -0,0 +1,93 @@
+//! Split view comparing the canvas with a reference.
+//!
+//! This module handles:
+//! - `CompareView`, a reference picture (a loaded image, or a checkpoint of
+//!   the canvas) and the divider between it and the live canvas
+//! - Drawing the split onto the presented frame: the live canvas left of the
+//!   divider, the reference right of it
+//! - Finding presses on the divider, so it can be dragged
+//!
+//! The reference is drawn onto the presented frame only, so snapshots,
+//! read-back commands, and history see the canvas alone.
+
+use image::RgbaImage;
+
+use crate::import::reference_frame;
+use crate::{canvas_bottom, window_width, CANVAS_TOP, DARK_GRAY, WHITE};
+
+/// How far from the divider (in pixels) a press still grabs it
+pub const DIVIDER_GRAB: usize = 4;
+/// Size of the grip drawn halfway down the divider
+const HANDLE_WIDTH: usize = 8;
+const HANDLE_HEIGHT: usize = 24;
+
+/// A reference shown beside the live canvas
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareView {
+    /// What the reference is, for responses (an image path, or `checkpoint`)
+    label: String,
+    /// Window-sized; only the canvas rows are shown
+    reference: Vec<u32>,
+    divider: usize,
+}
+
+impl CompareView {
+    /// Compare with `img`, laid out by `reference_frame`
+    pub fn from_image(img: &RgbaImage, label: &str) -> Self {
+        Self::with_reference(reference_frame(img), label)
+    }
+
+    /// Compare with a copy of a window-sized buffer as it is now
+    pub fn from_canvas(buffer: &[u32], label: &str) -> Self {
+        Self::with_reference(buffer.to_vec(), label)
+    }
+
+    fn with_reference(reference: Vec<u32>, label: &str) -> Self {
+        CompareView {
+            label: label.to_string(),
+            reference,
+            divider: window_width() / 2,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The first column showing the reference
+    pub fn divider(&self) -> usize {
+        self.divider
+    }
+
+    /// Move the divider to column `x` (clamped to the window)
+    pub fn set_divider(&mut self, x: usize) {
+        self.divider = x.min(window_width());
+    }
+
+    /// Whether a press at (x, y) grabs the divider
+    pub fn grabs(&self, x: usize, y: usize) -> bool {
+        (CANVAS_TOP..canvas_bottom()).contains(&y) && x.abs_diff(self.divider) <= DIVIDER_GRAB
+    }
+
+    /// Draw the reference right of the divider, and the divider with its grip
+    pub fn draw(&self, frame: &mut [u32]) {
+        let width = window_width();
+        for y in CANVAS_TOP..canvas_bottom() {
+            let row = y * width;
+            frame[row + self.divider..row + width]
+                .copy_from_slice(&self.reference[row + self.divider..row + width]);
+            for x in self.divider.saturating_sub(1)..(self.divider + 1).min(width) {
+                frame[row + x] = DARK_GRAY;
+            }
+        }
+        let top = CANVAS_TOP + (canvas_bottom() - CANVAS_TOP).saturating_sub(HANDLE_HEIGHT) / 2;
+        let left = self.divider.saturating_sub(HANDLE_WIDTH / 2);
+        for y in top..(top + HANDLE_HEIGHT).min(canvas_bottom()) {
+            for x in left..(left + HANDLE_WIDTH).min(width) {
+                // Grip lines across the handle
+                let grip = (y - top) % 4 == 2 && x > left && x + 1 < left + HANDLE_WIDTH;
+                frame[y * width + x] = if grip { WHITE } else { DARK_GRAY };
+            }
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Runtime configuration for the displai application.
+//!
+//! This module handles:
+//! - The `Config` struct holding startup options
+//! - Parsing command-line arguments into a `Config`, and the `--help` text
+//! - Restoring a canvas saved by an earlier run (`--persist`)
+//! - Opening an image to mark up (`--annotate`), the window sized to it
+//! - Where the session is autosaved (`--session`, `--no-restore`): only when
+//!   asked, so instances running side by side never share one
+//! - The default socket path: `DISPLAI_SOCKET`, or one per process under
+//!   `$XDG_RUNTIME_DIR`, so several instances can run side by side
+
+use std::path::Path;
+use std::time::Duration;
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::access::Access;
+use crate::annotate::Annotation;
+use crate::announce::is_beacon_address;
+use crate::canvas::Dimensions;
+use crate::import::load_image;
+use crate::keymap::Keymap;
+use crate::script::{parse_define, read_template_script};
+use crate::snapshot::snapshot_format;
+
+pub const DEFAULT_FPS: u32 = 60;
+pub const MIN_FPS: u32 = 1;
+pub const MAX_FPS: u32 = 240;
+/// Window title unless `--title` gives one
+pub const DEFAULT_TITLE: &str = "displai - v0.1";
+
+/// Environment variable naming the socket to listen on (`--socket` wins)
+pub const SOCKET_ENV: &str = "DISPLAI_SOCKET";
+
+/// Printed by `displai --help`
+pub const USAGE: &str = "\
+usage: displai [options]
+
+  --size WxH             window size (default 800x600)
+  --fps N                frame rate cap, 1-240 (default 60)
+  --title TEXT           window title
+  --load PATH            start with an image on the canvas
+  --persist PATH         restore the canvas from this PNG at startup (if it exists,
+                         instead of --load) and save it there on a clean exit
+  --annotate PATH        mark up an image: the window fits it, the pen starts red,
+                         and Ctrl+S saves the marked-up copy as NAME-annotated.EXT
+                         beside it
+  --session DIR          autosave the canvas and tool settings to DIR every 30s and
+                         on exit, and restore them at startup (default: no session)
+  --no-restore           start afresh instead of restoring the session (which is
+                         still saved over)
+  --aa                   draw with anti-aliased edges from the start (as `aa on`)
+  --script PATH          run the commands in PATH first, replying on stdout; ${NAME}
+                         in them is a --define or environment value; headless with
+                         neither stdin nor a socket, exit when it is done
+  --define KEY=VALUE     a value for ${KEY} in the script (repeatable; wins over the
+                         environment)
+  --headless             no window: serve commands against an off-screen canvas
+  --socket PATH          listen for commands on this Unix socket (default $DISPLAI_SOCKET,
+                         else $XDG_RUNTIME_DIR/displai-<pid>.sock); the path is
+                         printed to stderr once listening
+  --no-socket            don't listen on a socket
+  --ready-file PATH      once serving, write `ready <instance> [socket]` to PATH (replaced
+                         atomically; removed on exit), for clients to notice a restart
+  --beacon HOST:PORT     once serving, send the same line as one UDP datagram
+  --stdin / --no-stdin   read commands from stdin, or don't (default: read)
+  --stdin-access MODE    read-only or read-write (default read-write)
+  --socket-access MODE   read-only or read-write (default read-write)
+  --token TOKEN:MODE     a token `auth` accepts, and the access it grants (repeatable)
+  --keymap PATH          keyboard shortcut overrides, `chord = action` lines
+  --help                 show this help
+";
+
+/// Startup options for `run_with_config`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Maximum window update rate in frames per second
+    pub fps: u32,
+    /// Run without a window (commands only, against an off-screen canvas)
+    pub headless: bool,
+    /// Window size (the canvas is what the title bar and toolbar leave)
+    pub size: Dimensions,
+    /// Access of commands from stdin
+    pub stdin_access: Access,
+    /// Access of commands from socket connections
+    pub socket_access: Access,
+    /// Tokens a client can send with `auth`, and the access each grants
+    pub tokens: Vec<(String, Access)>,
+    /// Keyboard shortcuts in the window
+    pub keymap: Keymap,
+    /// Where to listen for socket connections (None: no socket)
+    pub socket: Option<String>,
+    /// File announcing this run once it serves commands (`--ready-file`)
+    pub ready_file: Option<String>,
+    /// `HOST:PORT` sent a UDP datagram announcing this run (`--beacon`)
+    pub beacon: Option<String>,
+    /// Whether to read commands from stdin
+    pub stdin: bool,
+    /// Image pasted at the canvas's top-left corner at startup (`--load`)
+    pub load: Option<RgbaImage>,
+    /// PNG the canvas is restored from at startup, if it exists, and saved
+    /// to on a clean exit (`--persist`)
+    pub persist: Option<String>,
+    /// The image being marked up, pasted as `load` with the window sized to
+    /// it (`--annotate`)
+    pub annotate: Option<Annotation>,
+    /// Directory the session is autosaved to and restored from (None: no
+    /// session)
+    pub session: Option<String>,
+    /// Whether to restore the saved session at startup (`--no-restore`)
+    pub restore: bool,
+    /// Window title
+    pub title: String,
+    /// Whether shapes start out drawn with anti-aliased edges (`--aa`)
+    pub antialias: bool,
+    /// Values for `${NAME}` in scripts, in the order given (`--define`)
+    pub defines: Vec<(String, String)>,
+    /// Command lines run before any others, their variables put in
+    /// (`--script`)
+    pub script: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            fps: DEFAULT_FPS,
+            headless: false,
+            size: Dimensions::default(),
+            stdin_access: Access::ReadWrite,
+            socket_access: Access::ReadWrite,
+            tokens: Vec::new(),
+            keymap: Keymap::default(),
+            socket: Some(default_socket_path()),
+            ready_file: None,
+            beacon: None,
+            stdin: true,
+            load: None,
+            persist: None,
+            annotate: None,
+            session: None,
+            restore: true,
+            title: DEFAULT_TITLE.to_string(),
+            antialias: false,
+            defines: Vec::new(),
+            script: Vec::new(),
+        }
+    }
+}
+
+/// The socket path to use without `--socket`
+///
+/// `env_socket` (from `DISPLAI_SOCKET`) wins; otherwise each process gets
+/// its own `displai-<pid>.sock` in `runtime_dir` (`XDG_RUNTIME_DIR`), or in
+/// `/tmp` without one. Empty values count as unset.
+pub fn socket_path_from(
+    env_socket: Option<String>,
+    runtime_dir: Option<String>,
+    pid: u32,
+) -> String {
+    if let Some(path) = env_socket.filter(|path| !path.is_empty()) {
+        return path;
+    }
+    let dir = runtime_dir
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| "/tmp".to_string());
+    format!("{}/displai-{}.sock", dir.trim_end_matches('/'), pid)
+}
+
+/// The socket path to use without `--socket`, from this process's
+/// environment and id (see `socket_path_from`)
+pub fn default_socket_path() -> String {
+    socket_path_from(
+        std::env::var(SOCKET_ENV).ok(),
+        std::env::var("XDG_RUNTIME_DIR").ok(),
+        std::process::id(),
+    )
+}
+
+/// Parse a frame rate, checking it is within MIN_FPS..=MAX_FPS
+pub fn parse_fps(s: &str) -> Option<u32> {
+    s.parse::<u32>()
+        .ok()
+        .filter(|fps| (MIN_FPS..=MAX_FPS).contains(fps))
+}
+
+/// Parse an access level (`read-only` or `read-write`) given to `flag`
+fn parse_access(flag: &str, value: &str) -> Result<Access, String> {
+    Access::parse(value)
+        .ok_or_else(|| format!("invalid {} '{}' (read-only or read-write)", flag, value))
+}
+
+/// Time between frames at the given frame rate
+pub fn frame_interval(fps: u32) -> Duration {
+    Duration::from_micros(1_000_000 / fps.max(MIN_FPS) as u64)
+}
+
+impl Config {
+    /// Parse command-line arguments (excluding the program name)
+    ///
+    /// Supported (see `USAGE`; options taking a value also accept
+    /// `--option=value`): `--fps N`, `--size WxH`, `--title TEXT`,
+    /// `--load PATH`, `--persist PATH`, `--annotate PATH`, `--aa`, `--headless`, `--socket PATH`, `--no-socket`,
+    /// `--session DIR`, `--no-restore`, `--ready-file PATH`, `--beacon HOST:PORT`, `--script PATH`, (repeatable)
+    /// `--define KEY=VALUE`,
+    /// `--stdin`, `--no-stdin`, `--stdin-access MODE`, `--socket-access MODE`,
+    /// and (repeatable) `--token TOKEN:MODE`, where MODE is `read-only` or
+    /// `read-write`, and `--keymap PATH` (shortcut overrides, see
+    /// `Keymap::apply`). A headless run needs stdin, a socket, or a script.
+    pub fn from_args<I>(args: I) -> Result<Config, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+        let mut script = None;
+        let mut size_given = false;
+        let mut load_given = false;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            match flag.as_str() {
+                "--fps" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--fps needs a value")?;
+                    config.fps = parse_fps(&value).ok_or_else(|| {
+                        format!("invalid --fps '{}' ({}-{})", value, MIN_FPS, MAX_FPS)
+                    })?;
+                }
+                "--size" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--size needs a value")?;
+                    config.size = Dimensions::parse(&value)?;
+                    size_given = true;
+                }
+                "--stdin-access" | "--socket-access" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or_else(|| format!("{} needs a value", flag))?;
+                    let access = parse_access(&flag, &value)?;
+                    if flag == "--stdin-access" {
+                        config.stdin_access = access;
+                    } else {
+                        config.socket_access = access;
+                    }
+                }
+                "--token" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--token needs a value")?;
+                    let (token, mode) = value
+                        .rsplit_once(':')
+                        .filter(|(token, _)| {
+                            !token.is_empty() && !token.contains(char::is_whitespace)
+                        })
+                        .ok_or("--token needs TOKEN:MODE")?;
+                    config
+                        .tokens
+                        .push((token.to_string(), parse_access("--token", mode)?));
+                }
+                "--keymap" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--keymap needs a value")?;
+                    config.keymap = Keymap::load(&value)?;
+                }
+                "--socket" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .filter(|value| !value.is_empty())
+                        .ok_or("--socket needs a value")?;
+                    config.socket = Some(value);
+                }
+                "--ready-file" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .filter(|value| !value.is_empty())
+                        .ok_or("--ready-file needs a value")?;
+                    config.ready_file = Some(value);
+                }
+                "--beacon" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--beacon needs a value")?;
+                    if !is_beacon_address(&value) {
+                        return Err(format!("--beacon needs HOST:PORT, not '{}'", value));
+                    }
+                    config.beacon = Some(value);
+                }
+                "--load" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--load needs a value")?;
+                    config.load = Some(load_image(&value)?);
+                    load_given = true;
+                }
+                "--annotate" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--annotate needs a value")?;
+                    let img = load_image(&value)?;
+                    let annotation = Annotation::new(&value, &img);
+                    config.size = annotation.window_size()?;
+                    config.load = Some(img);
+                    config.annotate = Some(annotation);
+                }
+                "--persist" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--persist needs a value")?;
+                    if snapshot_format(&value) != Ok(ImageFormat::Png) {
+                        return Err(format!("--persist needs a .png path, not '{}'", value));
+                    }
+                    config.persist = Some(value);
+                }
+                "--session" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .filter(|value| !value.is_empty())
+                        .ok_or("--session needs a value")?;
+                    config.session = Some(value);
+                }
+                "--script" => {
+                    script = Some(
+                        inline_value
+                            .or_else(|| args.next())
+                            .ok_or("--script needs a value")?,
+                    );
+                }
+                "--define" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--define needs a value")?;
+                    let define = parse_define(&value)
+                        .ok_or_else(|| format!("--define needs KEY=VALUE, not '{}'", value))?;
+                    config.defines.push(define);
+                }
+                "--title" => {
+                    config.title = inline_value
+                        .or_else(|| args.next())
+                        .ok_or("--title needs a value")?;
+                }
+                "--headless" if inline_value.is_none() => config.headless = true,
+                "--aa" if inline_value.is_none() => config.antialias = true,
+                "--no-restore" if inline_value.is_none() => config.restore = false,
+                "--no-socket" if inline_value.is_none() => config.socket = None,
+                "--stdin" if inline_value.is_none() => config.stdin = true,
+                "--no-stdin" if inline_value.is_none() => config.stdin = false,
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        if config.annotate.is_some() {
+            // The image decides the canvas, and is the canvas
+            for (given, flag) in [
+                (size_given, "--size"),
+                (load_given, "--load"),
+                (config.persist.is_some(), "--persist"),
+            ] {
+                if given {
+                    return Err(format!("--annotate can't be used with {}", flag));
+                }
+            }
+        }
+        // A canvas saved by an earlier run takes over from --load
+        if let Some(path) = &config.persist {
+            if Path::new(path).exists() {
+                config.load = Some(load_image(path)?);
+            }
+        }
+        // Read once every --define is known, before or after --script
+        if let Some(path) = script {
+            config.script = read_template_script(&path, &config.defines)?;
+        }
+        if config.headless && !config.stdin && config.socket.is_none() && config.script.is_empty() {
+            return Err(
+                "--headless needs stdin, a socket, or a script to take commands from".to_string(),
+            );
+        }
+        Ok(config)
+    }
+}
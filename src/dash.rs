@@ -0,0 +1,139 @@
+//! Dashed and dotted strokes.
+//!
+//! This module handles:
+//! - `StrokeStyle`, set by `stroke-style solid|dashed|dotted [dash gap]`
+//! - The style brush lines on this thread are drawn in, and how far along
+//!   the current shape's outline they have got
+//!
+//! A shape's outline is drawn as many brush lines (a rectangle's four sides,
+//! a circle's short chords), so the pattern can't restart with each: every
+//! brush line measures its pixels from the distance the ones before it
+//! reached, and adds its own length when it is done. `with_stroke_style`
+//! starts each shape at the beginning of the pattern.
+//!
+//! Lengths are those of what shows on the canvas: a dash of 8 drawn with a
+//! wide brush is 8 pixels long from end to end, its round ends included, and
+//! the gaps between dashes stay `gap` pixels wide.
+
+use std::cell::Cell;
+use std::fmt;
+
+/// Dash and gap lengths `stroke-style dashed` uses without any
+pub const DEFAULT_DASH: (usize, usize) = (8, 4);
+/// Dot and gap lengths `stroke-style dotted` uses without any
+pub const DEFAULT_DOT: (usize, usize) = (1, 3);
+/// Longest dash or gap
+pub const MAX_DASH_LEN: usize = 1000;
+
+thread_local! {
+    static STROKE_STYLE: Cell<StrokeStyle> = const { Cell::new(StrokeStyle::Solid) };
+    /// Distance along the current shape's outline drawn so far
+    static TRAVELED: Cell<f64> = const { Cell::new(0.0) };
+}
+
+/// How a shape's outline is broken up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StrokeStyle {
+    /// Unbroken (the default)
+    #[default]
+    Solid,
+    /// Dashes `dash` pixels long with `gap` pixels between them
+    Dashed { dash: usize, gap: usize },
+    /// Dots, each a brush dot drawn `dash` pixels long (1: round), `gap`
+    /// pixels apart
+    Dotted { dash: usize, gap: usize },
+}
+
+impl StrokeStyle {
+    /// Check the dash and gap lengths are 1 to `MAX_DASH_LEN`
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            StrokeStyle::Solid => Ok(()),
+            StrokeStyle::Dashed { dash, gap } | StrokeStyle::Dotted { dash, gap } => {
+                for (what, len) in [("dash", dash), ("gap", gap)] {
+                    if !(1..=MAX_DASH_LEN).contains(&len) {
+                        return Err(format!(
+                            "{} length {} is outside 1-{}",
+                            what, len, MAX_DASH_LEN
+                        ));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The lengths of the brush's path that are drawn and skipped in turn,
+    /// for a brush of `brush_size`; None if the stroke is solid
+    ///
+    /// A brush dot reaches `brush_size - 1` beyond the path either side, so
+    /// that is taken off each dash and added to each gap.
+    pub fn pattern(&self, brush_size: usize) -> Option<(f64, f64)> {
+        let (dash, gap) = match *self {
+            StrokeStyle::Solid => return None,
+            StrokeStyle::Dashed { dash, gap } | StrokeStyle::Dotted { dash, gap } => (dash, gap),
+        };
+        let reach = 2 * (brush_size.clamp(1, MAX_DASH_LEN) - 1);
+        let on = dash.saturating_sub(reach).max(1);
+        Some((on as f64, (gap + reach) as f64))
+    }
+}
+
+impl fmt::Display for StrokeStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrokeStyle::Solid => write!(f, "solid"),
+            StrokeStyle::Dashed { dash, gap } => write!(f, "dashed {} {}", dash, gap),
+            StrokeStyle::Dotted { dash, gap } => write!(f, "dotted {} {}", dash, gap),
+        }
+    }
+}
+
+/// The stroke style brush lines on this thread are drawn in
+pub fn stroke_style() -> StrokeStyle {
+    STROKE_STYLE.with(Cell::get)
+}
+
+/// Run `f` (one shape) with `style` as the stroke style, from the start of
+/// its pattern, restoring the previous style and distance after
+pub fn with_stroke_style<R>(style: StrokeStyle, f: impl FnOnce() -> R) -> R {
+    let previous = STROKE_STYLE.with(|current| current.replace(style));
+    let traveled = TRAVELED.with(|current| current.replace(0.0));
+    let result = f();
+    STROKE_STYLE.with(|current| current.set(previous));
+    TRAVELED.with(|current| current.set(traveled));
+    result
+}
+
+/// Move `length` further along the current outline, returning the distance
+/// reached before
+pub fn advance_stroke(length: f64) -> f64 {
+    TRAVELED.with(|current| {
+        let start = current.get();
+        current.set(start + length);
+        start
+    })
+}
+
+/// Whether the pixel `distance` along an outline is drawn, in a `pattern`
+/// of (on, off) lengths
+pub fn dash_on((on, off): (f64, f64), distance: f64) -> bool {
+    distance.round().rem_euclid(on + off) < on
+}
+
+/// The parts of the stretch of outline from `start` to `end` that are drawn
+/// in `pattern`, as distances from `start`, each from its first drawn pixel
+/// to its last
+pub fn dash_spans((on, off): (f64, f64), start: f64, end: f64) -> Vec<(f64, f64)> {
+    let period = on + off;
+    let mut spans = Vec::new();
+    let mut dash = (start / period).floor() * period;
+    while dash <= end {
+        let (from, to) = (dash.max(start), (dash + on - 1.0).min(end));
+        if from <= to {
+            spans.push((from - start, to - start));
+        }
+        dash += period;
+    }
+    spans
+}
@@ -0,0 +1,105 @@
+//! Stepping through command scripts.
+//!
+//! This module handles:
+//! - Reading a script: one protocol command per line, blank lines and `#`
+//!   comments skipped, `include`s expanded (see `read_included`)
+//! - `Stepper`, a loaded script handed out one command at a time by `step`
+//!   (or F10 in the window)
+//! - Showing the next command in the title bar while stepping
+//!
+//! Long generated drawing scripts are hard to debug when they run all at once;
+//! stepping shows what each command does before the next one runs.
+
+use crate::font::{draw_text, text_width, GLYPH_ADVANCE, GLYPH_HEIGHT};
+use crate::include::read_included;
+use crate::{window_width, BLACK, BUTTON_MARGIN, BUTTON_SIZE, TITLE_BAR_HEIGHT};
+
+/// Left margin of the status text in the title bar
+const STATUS_X: usize = 8;
+
+/// Read a script's command lines, in order
+///
+/// Command lines are not parsed here, so a line this build doesn't understand
+/// fails when it is stepped to, not when the script is loaded.
+pub fn read_script(path: &str) -> Result<Vec<String>, String> {
+    read_included(path, &|line| Ok(line.to_string()))
+}
+
+/// A script being stepped through
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stepper {
+    path: String,
+    lines: Vec<String>,
+    next: usize,
+}
+
+impl Stepper {
+    /// Load the script at `path`, ready to step to its first command
+    pub fn load(path: &str) -> Result<Self, String> {
+        let lines = read_script(path)?;
+        if lines.is_empty() {
+            return Err(format!("{} has no commands", path));
+        }
+        Ok(Stepper {
+            path: path.to_string(),
+            lines,
+            next: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of commands in the script
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Number of commands stepped so far
+    pub fn position(&self) -> usize {
+        self.next
+    }
+
+    /// The command the next step runs
+    pub fn peek(&self) -> Option<&str> {
+        self.lines.get(self.next).map(String::as_str)
+    }
+
+    /// Hand out the next command
+    pub fn advance(&mut self) -> Option<String> {
+        let line = self.lines.get(self.next)?.clone();
+        self.next += 1;
+        Some(line)
+    }
+
+    /// Whether every command has been stepped
+    pub fn is_finished(&self) -> bool {
+        self.next == self.lines.len()
+    }
+
+    /// `step N/M: <next command>` (N counting from 1), or `finished M/M`
+    pub fn status(&self) -> String {
+        match self.peek() {
+            Some(line) => format!("step {}/{}: {}", self.next + 1, self.len(), line),
+            None => format!("finished {}/{}", self.len(), self.len()),
+        }
+    }
+}
+
+/// Draw the stepper's status in the title bar, cut short before the close
+/// button
+pub fn draw_step_status(buffer: &mut [u32], stepper: &Stepper) {
+    let room = window_width().saturating_sub(STATUS_X + BUTTON_SIZE + 2 * BUTTON_MARGIN);
+    let mut status = stepper.status();
+    if text_width(&status) > room {
+        let keep = (room / GLYPH_ADVANCE).saturating_sub(3);
+        status = status.chars().take(keep).collect::<String>() + "...";
+    }
+    let y = (TITLE_BAR_HEIGHT - GLYPH_HEIGHT) / 2;
+    draw_text(buffer, STATUS_X, y, &status, BLACK);
+}
@@ -0,0 +1,294 @@
+//! Modal dialogs drawn over the canvas.
+//!
+//! This module handles:
+//! - `Dialog`, a confirm (Yes/No) or prompt (text field, OK/Cancel) box
+//!   centered on the canvas, opened with `dialog confirm` or `dialog prompt`
+//! - Laying a dialog out for the current window size, and drawing it
+//! - Answering a dialog with the mouse or keyboard, as a `DialogAnswer`
+//!
+//! A dialog is modal: while one is open the run loop gives it every click
+//! and key, and nothing beneath it is drawn on or pressed. Whoever opened it
+//! decides what the answer means; dialogs opened over the protocol send it
+//! to subscribers as a `confirm ...` or `prompt ...` event.
+
+use std::fmt;
+
+use crate::drawing::{draw_line, fill_rectangle};
+use crate::focus::KeyInput;
+use crate::font::{draw_text, text_width, GLYPH_ADVANCE, GLYPH_HEIGHT};
+use crate::objects::{is_input_char, INPUT_HEIGHT, INPUT_PADDING, MAX_INPUT_LEN};
+use crate::{canvas_bottom, window_width, BLACK, CANVAS_TOP, DARK_GRAY, GRAY, WHITE};
+
+/// Longest dialog message, in characters
+pub const MAX_DIALOG_MESSAGE_LEN: usize = 200;
+/// Width of a dialog (narrower if the window is), in pixels
+pub const DIALOG_WIDTH: usize = 320;
+/// Space between a dialog's border and its contents, in pixels
+pub const DIALOG_PADDING: usize = 12;
+/// Height of one line of a dialog's message, in pixels
+pub const DIALOG_LINE_HEIGHT: usize = GLYPH_HEIGHT + 4;
+/// Width of a dialog button, in pixels
+pub const DIALOG_BUTTON_WIDTH: usize = 64;
+/// Height of a dialog button, in pixels
+pub const DIALOG_BUTTON_HEIGHT: usize = 24;
+/// Space between a dialog's message, field, and buttons, in pixels
+pub const DIALOG_GAP: usize = 8;
+
+/// Which question a dialog asks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogKind {
+    /// A yes/no question
+    Confirm,
+    /// A request for a line of text
+    Prompt,
+}
+
+impl DialogKind {
+    /// Protocol word, also the first word of the answer event
+    pub fn name(&self) -> &'static str {
+        match self {
+            DialogKind::Confirm => "confirm",
+            DialogKind::Prompt => "prompt",
+        }
+    }
+
+    /// Labels of the accept and cancel buttons
+    pub fn buttons(&self) -> [&'static str; 2] {
+        match self {
+            DialogKind::Confirm => ["Yes", "No"],
+            DialogKind::Prompt => ["OK", "Cancel"],
+        }
+    }
+}
+
+/// How a dialog was answered
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogAnswer {
+    /// A confirm dialog: Yes (true) or No (false)
+    Confirmed(bool),
+    /// A prompt: the text entered, or None if cancelled
+    Prompted(Option<String>),
+}
+
+impl fmt::Display for DialogAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DialogAnswer::Confirmed(true) => write!(f, "confirm yes"),
+            DialogAnswer::Confirmed(false) => write!(f, "confirm no"),
+            DialogAnswer::Prompted(Some(text)) => write!(f, "prompt ok {}", text),
+            DialogAnswer::Prompted(None) => write!(f, "prompt cancel"),
+        }
+    }
+}
+
+/// Where a dialog's parts go, as (x, y, width, height) rectangles
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogLayout {
+    pub frame: (usize, usize, usize, usize),
+    /// Top-left of each message line
+    pub lines: Vec<(usize, usize, String)>,
+    /// The text field (prompts only)
+    pub field: Option<(usize, usize, usize, usize)>,
+    /// The accept and cancel buttons
+    pub buttons: [(usize, usize, usize, usize); 2],
+}
+
+/// Whether (x, y) is inside the rectangle `rect`
+fn contains((rx, ry, w, h): (usize, usize, usize, usize), x: usize, y: usize) -> bool {
+    (rx..rx + w).contains(&x) && (ry..ry + h).contains(&y)
+}
+
+/// Draw the one-pixel border of a rectangle
+fn outline(frame: &mut [u32], (x, y, w, h): (usize, usize, usize, usize), color: u32) {
+    let (right, bottom) = (x + w - 1, y + h - 1);
+    draw_line(frame, x, y, right, y, color);
+    draw_line(frame, x, bottom, right, bottom, color);
+    draw_line(frame, x, y, x, bottom, color);
+    draw_line(frame, right, y, right, bottom, color);
+}
+
+/// Break `text` into lines of at most `max_chars` characters, at spaces where
+/// possible
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let len = |s: &str| s.chars().count();
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let mut word = word;
+        // Words too long for a line are split wherever they overflow
+        while let Some((split, _)) = word.char_indices().nth(max_chars) {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+        }
+        if line.is_empty() {
+            line.push_str(word);
+        } else if len(&line) + 1 + len(word) <= max_chars {
+            line.push(' ');
+            line.push_str(word);
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// An open dialog
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dialog {
+    pub kind: DialogKind,
+    pub message: String,
+    /// Text typed into a prompt so far
+    pub text: String,
+}
+
+impl Dialog {
+    pub fn new(kind: DialogKind, message: &str) -> Self {
+        Dialog {
+            kind,
+            message: message.to_string(),
+            text: String::new(),
+        }
+    }
+
+    /// The answer given by the accept button (Yes, or OK with the text)
+    pub fn accept(&self) -> DialogAnswer {
+        match self.kind {
+            DialogKind::Confirm => DialogAnswer::Confirmed(true),
+            DialogKind::Prompt => DialogAnswer::Prompted(Some(self.text.clone())),
+        }
+    }
+
+    /// The answer given by the cancel button (No, or Cancel)
+    pub fn cancel(&self) -> DialogAnswer {
+        match self.kind {
+            DialogKind::Confirm => DialogAnswer::Confirmed(false),
+            DialogKind::Prompt => DialogAnswer::Prompted(None),
+        }
+    }
+
+    /// Handle a key press: Enter accepts, Escape cancels, and a prompt takes
+    /// typed characters and Backspace
+    ///
+    /// Returns the answer once the dialog is answered.
+    pub fn key(&mut self, key: KeyInput) -> Option<DialogAnswer> {
+        match key {
+            KeyInput::Enter => return Some(self.accept()),
+            KeyInput::Escape => return Some(self.cancel()),
+            // Characters that can't be typed (see `is_input_char`) are dropped
+            KeyInput::Char(c)
+                if self.kind == DialogKind::Prompt
+                    && is_input_char(c)
+                    && self.text.len() < MAX_INPUT_LEN =>
+            {
+                self.text.push(c);
+            }
+            KeyInput::Backspace => {
+                self.text.pop();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Handle a click at (x, y): returns the answer if a button was clicked
+    pub fn click(&self, x: usize, y: usize) -> Option<DialogAnswer> {
+        let [accept, cancel] = self.layout().buttons;
+        if contains(accept, x, y) {
+            Some(self.accept())
+        } else if contains(cancel, x, y) {
+            Some(self.cancel())
+        } else {
+            None
+        }
+    }
+
+    /// Lay the dialog out centered on the canvas
+    pub fn layout(&self) -> DialogLayout {
+        let w = DIALOG_WIDTH.min(window_width() - 2 * DIALOG_PADDING);
+        let inner = w - 2 * DIALOG_PADDING;
+        let wrapped = wrap(&self.message, (inner + 1) / GLYPH_ADVANCE);
+        let field_height = match self.kind {
+            DialogKind::Confirm => 0,
+            DialogKind::Prompt => INPUT_HEIGHT + DIALOG_GAP,
+        };
+        let h = 2 * DIALOG_PADDING
+            + wrapped.len() * DIALOG_LINE_HEIGHT
+            + DIALOG_GAP
+            + field_height
+            + DIALOG_BUTTON_HEIGHT;
+        let canvas_height = canvas_bottom() - CANVAS_TOP;
+        let x = (window_width() - w) / 2;
+        let y = CANVAS_TOP + canvas_height.saturating_sub(h) / 2;
+
+        let (left, top) = (x + DIALOG_PADDING, y + DIALOG_PADDING);
+        let lines = wrapped
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| (left, top + i * DIALOG_LINE_HEIGHT, line))
+            .collect::<Vec<_>>();
+        let below = top + lines.len() * DIALOG_LINE_HEIGHT + DIALOG_GAP;
+        let field = (self.kind == DialogKind::Prompt).then_some((left, below, inner, INPUT_HEIGHT));
+        let button_y = below + field_height;
+        let right = x + w - DIALOG_PADDING;
+        let cancel_x = right - DIALOG_BUTTON_WIDTH;
+        let accept_x = cancel_x - DIALOG_GAP - DIALOG_BUTTON_WIDTH;
+        DialogLayout {
+            frame: (x, y, w, h),
+            lines,
+            field,
+            buttons: [
+                (
+                    accept_x,
+                    button_y,
+                    DIALOG_BUTTON_WIDTH,
+                    DIALOG_BUTTON_HEIGHT,
+                ),
+                (
+                    cancel_x,
+                    button_y,
+                    DIALOG_BUTTON_WIDTH,
+                    DIALOG_BUTTON_HEIGHT,
+                ),
+            ],
+        }
+    }
+
+    /// Draw the dialog onto a window-sized frame
+    pub fn draw(&self, frame: &mut [u32]) {
+        let layout = self.layout();
+        let (x, y, w, h) = layout.frame;
+        fill_rectangle(frame, x, y, x + w - 1, y + h - 1, GRAY);
+        outline(frame, layout.frame, BLACK);
+        for (lx, ly, line) in &layout.lines {
+            draw_text(frame, *lx, *ly, line, BLACK);
+        }
+        if let Some(field) = layout.field {
+            let (fx, fy, fw, fh) = field;
+            fill_rectangle(frame, fx, fy, fx + fw - 1, fy + fh - 1, WHITE);
+            outline(frame, field, BLACK);
+            // Show the end of the text, with the caret after it
+            let room = fw.saturating_sub(2 * INPUT_PADDING + 2);
+            let mut shown = self.text.as_str();
+            while text_width(shown) > room {
+                shown = &shown[1..];
+            }
+            let (tx, ty) = (fx + INPUT_PADDING, fy + INPUT_PADDING);
+            draw_text(frame, tx, ty, shown, BLACK);
+            let caret = tx + text_width(shown) + 1;
+            draw_line(frame, caret, ty - 1, caret, ty + GLYPH_HEIGHT, BLACK);
+        }
+        for (rect, label) in layout.buttons.into_iter().zip(self.kind.buttons()) {
+            let (bx, by, bw, bh) = rect;
+            fill_rectangle(frame, bx, by, bx + bw - 1, by + bh - 1, WHITE);
+            outline(frame, rect, DARK_GRAY);
+            let lx = bx + bw.saturating_sub(text_width(label)) / 2;
+            let ly = by + bh.saturating_sub(GLYPH_HEIGHT) / 2;
+            draw_text(frame, lx, ly, label, BLACK);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Pixel differences between the canvas and a reference image.
+//!
+//! This module handles:
+//! - `DiffSummary`, how many canvas pixels differ from a reference and the
+//!   rectangle around them, for `diff <path>`
+//! - `DiffOverlay`, a reference whose mismatched pixels are tinted red on the
+//!   presented frame, for `diff overlay <path>`
+//!
+//! Both compare what a snapshot would save (the canvas with objects drawn
+//! over it) against the image laid out by `reference_frame`. The overlay
+//! compares every presented frame afresh, so the tint follows the drawing as
+//! it is corrected, and it never reaches the canvas, history, or snapshots.
+
+use image::RgbaImage;
+
+use crate::import::reference_frame;
+use crate::locks::Region;
+use crate::{canvas_bottom, window_width, CANVAS_TOP, RED};
+
+/// Color mismatched pixels are blended with
+pub const DIFF_TINT: u32 = RED;
+
+/// Blend `color` halfway toward `DIFF_TINT`
+fn tint(color: u32) -> u32 {
+    let mix =
+        |shift: u32| ((((color >> shift) & 0xFF) + ((DIFF_TINT >> shift) & 0xFF)) / 2) << shift;
+    mix(16) | mix(8) | mix(0)
+}
+
+/// How a frame differs from a reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffSummary {
+    /// Canvas pixels that differ
+    pub count: usize,
+    /// The smallest rectangle holding them (None if none differ)
+    pub bounds: Option<Region>,
+}
+
+impl DiffSummary {
+    /// The protocol reply: `diff N pixels`, then `in x,y,width,height` if any
+    /// differ
+    pub fn response(&self) -> String {
+        match self.bounds {
+            Some(r) => format!(
+                "diff {} pixels in {},{},{},{}",
+                self.count, r.x, r.y, r.width, r.height
+            ),
+            None => format!("diff {} pixels", self.count),
+        }
+    }
+}
+
+/// Compare the canvas rows of two window-sized buffers
+pub fn diff_frames(frame: &[u32], reference: &[u32]) -> DiffSummary {
+    let width = window_width();
+    let mut count = 0;
+    let mut corners: Option<(usize, usize, usize, usize)> = None;
+    for y in CANVAS_TOP..canvas_bottom() {
+        for x in 0..width {
+            let i = y * width + x;
+            if frame[i] & 0xFFFFFF == reference[i] & 0xFFFFFF {
+                continue;
+            }
+            count += 1;
+            corners = Some(match corners {
+                Some((x1, y1, x2, y2)) => (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+    DiffSummary {
+        count,
+        bounds: corners.map(|(x1, y1, x2, y2)| Region::around(x1, y1, x2, y2, 0)),
+    }
+}
+
+/// A reference image whose differences are tinted on the presented frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffOverlay {
+    /// The reference's path, for responses
+    label: String,
+    /// Window-sized; only the canvas rows are compared
+    reference: Vec<u32>,
+}
+
+impl DiffOverlay {
+    /// Highlight differences from `img`, laid out by `reference_frame`
+    pub fn from_image(img: &RgbaImage, label: &str) -> Self {
+        DiffOverlay {
+            label: label.to_string(),
+            reference: reference_frame(img),
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// How `frame` differs from the reference
+    pub fn summary(&self, frame: &[u32]) -> DiffSummary {
+        diff_frames(frame, &self.reference)
+    }
+
+    /// Tint the canvas pixels of `frame` that differ from the reference
+    ///
+    /// Call on a frame holding what a snapshot would save, before other
+    /// overlays are drawn over it.
+    pub fn draw(&self, frame: &mut [u32]) {
+        let width = window_width();
+        let rows = CANVAS_TOP * width..canvas_bottom() * width;
+        for (pixel, reference) in frame[rows.clone()].iter_mut().zip(&self.reference[rows]) {
+            if *pixel & 0xFFFFFF != reference & 0xFFFFFF {
+                *pixel = tint(*pixel);
+            }
+        }
+    }
+}
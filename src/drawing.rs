@@ -2,25 +2,146 @@
 //!
 //! This module handles:
 //! - Basic drawing primitives (pixels, lines, circles)
-//! - Shape drawing (square, rectangle, circle, oval, triangle)
-//! - Fill functions for shapes
+//! - Brush dots and lines stamped in the current brush shape (see `brush`)
+//! - Brush lines in the current stroke style (see `dash`), dashes running on
+//!   from one line of an outline to the next
+//! - Shape drawing (square, rectangle, circle, oval, triangle, polygon)
+//! - Arcs and pie slices, traced as a parametric stroke
+//! - Quadratic and cubic Bezier curves
+//! - Fill functions for shapes, in one color or a gradient (see `gradient`)
+//! - Flood fill (paint bucket)
 //! - Canvas clearing
+//!
+//! With anti-aliasing on (see `antialias`), lines, brush dots, outlines, and
+//! the curved or slanted edges of fills blend into the canvas by how much of
+//! each pixel they cover; rectangles, flood fills, and single pixels stay hard.
+//!
+//! Pixels are composited rather than set (see `set_pixel` and `blend`), so a
+//! translucent color shows the canvas through it and a blend mode other than
+//! normal mixes with it.
+//!
+//! With the `deterministic` feature, circle and oval outlines are traced with
+//! integer math instead of `sin`/`cos`, whose results vary between platform
+//! math libraries (anti-aliased, the traced pixels are joined by capsules),
+//! and arcs take their sines from a short series instead.
+//! Everything else here already uses only exact integer or basic IEEE float
+//! operations, so output is then bit-identical everywhere. Pane scales (see
+//! `panes`) are outside that guarantee: log axes and tick steps use `log10`
+//! and `powf`, so a `plot` on a log axis may land a pixel apart elsewhere.
+
+use crate::antialias::{
+    antialiasing, cover_capsule, cover_disc, cover_ellipse, cover_pixel, cover_polygon,
+    cover_rounded_box, draw_covered,
+};
+use crate::blend::composite;
+use crate::brush::{brush_shape, spray_offsets, BrushShape};
+use crate::dash::{advance_stroke, dash_on, dash_spans, stroke_style};
+use crate::gradient::Fill;
+use crate::{canvas_bottom, dimensions, window_width, ToolMode, CANVAS_TOP, WHITE};
+
+/// Upper bound on segments used to trace a circle or oval outline, so huge
+/// radii can't stall the frame
+pub const MAX_OUTLINE_STEPS: usize = 1 << 16;
+
+/// Coordinates beyond this are clamped before clipping, keeping the clip math
+/// exact; the slope change this causes is far below a pixel on the canvas
+const COORD_LIMIT: usize = 1 << 31;
+
+/// Clip a segment to the canvas area grown by `margin` pixels (Liang-Barsky)
+/// Endpoints already inside are returned unchanged; None if the segment misses entirely
+fn clip_segment(
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+    margin: usize,
+) -> Option<(isize, isize, isize, isize)> {
+    let (x0, y0) = (x0.min(COORD_LIMIT), y0.min(COORD_LIMIT));
+    let (x1, y1) = (x1.min(COORD_LIMIT), y1.min(COORD_LIMIT));
+    let dims = dimensions();
+    let margin = margin.min(dims.width + dims.height);
+    let min_x = -(margin as f64);
+    let max_x = (dims.width + margin) as f64;
+    let min_y = CANVAS_TOP as f64 - margin as f64;
+    let max_y = (dims.canvas_bottom() + margin) as f64;
+
+    let inside =
+        |x: usize, y: usize| (x as f64) <= max_x && (y as f64) >= min_y && (y as f64) <= max_y;
+    if inside(x0, y0) && inside(x1, y1) {
+        return Some((x0 as isize, y0 as isize, x1 as isize, y1 as isize));
+    }
 
-use crate::{ToolMode, CANVAS_BOTTOM, CANVAS_TOP, WHITE, WIDTH};
+    let (fx0, fy0) = (x0 as f64, y0 as f64);
+    let dx = x1 as f64 - fx0;
+    let dy = y1 as f64 - fy0;
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+
+    for (p, q) in [
+        (-dx, fx0 - min_x),
+        (dx, max_x - fx0),
+        (-dy, fy0 - min_y),
+        (dy, max_y - fy0),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return None;
+                }
+                t0 = t0.max(r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = t1.min(r);
+            }
+        }
+    }
+
+    Some((
+        (fx0 + t0 * dx).round() as isize,
+        (fy0 + t0 * dy).round() as isize,
+        (fx0 + t1 * dx).round() as isize,
+        (fy0 + t1 * dy).round() as isize,
+    ))
+}
+
+/// Distance from (x0, y0) to (x1, y1), by `sqrt` so it is the same on every
+/// platform
+fn distance(x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    (dx * dx + dy * dy).sqrt()
+}
 
-/// Set a single pixel, checking canvas bounds
+/// Draw a single pixel, checking canvas bounds
+///
+/// Every primitive draws through here: the pixel joins the mask of the
+/// shape being drawn, if one is open (see `draw_covered`), and is otherwise
+/// composited onto the canvas by the color's alpha and the blend mode.
 pub fn set_pixel(buffer: &mut [u32], x: usize, y: usize, color: u32) {
-    if x < WIDTH && (CANVAS_TOP..CANVAS_BOTTOM).contains(&y) {
-        buffer[y * WIDTH + x] = color;
+    let dims = dimensions();
+    if x < dims.width && (CANVAS_TOP..dims.canvas_bottom()).contains(&y) && !cover_pixel(x, y) {
+        let i = y * dims.width + x;
+        buffer[i] = composite(buffer[i], color, 1.0);
     }
 }
 
-/// Draw a line using Bresenham's algorithm
+/// Draw a line using Bresenham's algorithm (1px wide and soft-edged when
+/// anti-aliased)
 pub fn draw_line(buffer: &mut [u32], x0: usize, y0: usize, x1: usize, y1: usize, color: u32) {
-    let x0 = x0 as isize;
-    let y0 = y0 as isize;
-    let x1 = x1 as isize;
-    let y1 = y1 as isize;
+    let Some((x0, y0, x1, y1)) = clip_segment(x0, y0, x1, y1, 0) else {
+        return;
+    };
+    if antialiasing() {
+        let (x0, y0, x1, y1) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+        draw_covered(buffer, color, |_| cover_capsule(x0, y0, x1, y1, 0.5));
+        return;
+    }
 
     let dx = (x1 - x0).abs();
     let dy = -(y1 - y0).abs();
@@ -32,8 +153,8 @@ pub fn draw_line(buffer: &mut [u32], x0: usize, y0: usize, x1: usize, y1: usize,
     let mut y = y0;
 
     loop {
-        if x >= 0 && x < WIDTH as isize && y >= CANVAS_TOP as isize && y < CANVAS_BOTTOM as isize {
-            buffer[y as usize * WIDTH + x as usize] = color;
+        if x >= 0 && y >= 0 {
+            set_pixel(buffer, x as usize, y as usize, color);
         }
 
         if x == x1 && y == y1 {
@@ -53,16 +174,38 @@ pub fn draw_line(buffer: &mut [u32], x0: usize, y0: usize, x1: usize, y1: usize,
 }
 
 /// Draw a filled circle at the given center point
+///
+/// Anti-aliased, it is a disc `size - 0.5` across from its center, so a
+/// brush line of these is as wide as a hard one.
 pub fn draw_circle(buffer: &mut [u32], cx: usize, cy: usize, size: usize, color: u32) {
-    let radius = (size as isize) - 1;
-    if radius <= 0 {
+    if antialiasing() {
+        let dims = dimensions();
+        let radius = size.clamp(1, dims.width + dims.height) as f64 - 0.5;
+        let (cx, cy) = (cx.min(COORD_LIMIT) as f64, cy.min(COORD_LIMIT) as f64);
+        draw_covered(buffer, color, |_| cover_disc(cx, cy, radius));
+        return;
+    }
+    if size <= 1 {
         // Size 1: draw single pixel
         set_pixel(buffer, cx, cy, color);
         return;
     }
+    // A brush wider than the window covers it either way
+    let dims = dimensions();
+    let radius = size.min(dims.width + dims.height) - 1;
+    if cx > dims.width + radius || cy > dims.canvas_bottom() + radius {
+        return;
+    }
+
+    let radius = radius as isize;
+    let (cx_i, cy_i) = (cx as isize, cy as isize);
+    // Only visit offsets that can land on the canvas
+    let dy_range = (-radius).max(CANVAS_TOP as isize - cy_i)
+        ..=radius.min(dims.canvas_bottom() as isize - 1 - cy_i);
+    let dx_range = (-radius).max(-cx_i)..=radius.min(dims.width as isize - 1 - cx_i);
 
-    for dy in -radius..=radius {
-        for dx in -radius..=radius {
+    for dy in dy_range {
+        for dx in dx_range.clone() {
             if dx * dx + dy * dy <= radius * radius {
                 let x = cx as isize + dx;
                 let y = cy as isize + dy;
@@ -74,7 +217,58 @@ pub fn draw_circle(buffer: &mut [u32], cx: usize, cy: usize, size: usize, color:
     }
 }
 
-/// Draw a brush stroke line (circles along a line path)
+/// Stamp the brush once at (cx, cy), in the current brush shape
+///
+/// A circle is `draw_circle`; other shapes reach as far (`size - 1` pixels
+/// from the center) and are drawn hard even when anti-aliased.
+pub fn draw_brush_dot(buffer: &mut [u32], cx: usize, cy: usize, size: usize, color: u32) {
+    let shape = brush_shape();
+    if shape == BrushShape::Circle || size <= 1 {
+        draw_circle(buffer, cx, cy, size, color);
+        return;
+    }
+    let dims = dimensions();
+    let radius = size.min(dims.width + dims.height) - 1;
+    if cx > dims.width + radius || cy > dims.canvas_bottom() + radius {
+        return;
+    }
+    let (x, y, r) = (cx as isize, cy as isize, radius as isize);
+    // Spray specks can land on one pixel twice, so the stamp is one shape
+    draw_covered(buffer, color, |buffer| {
+        let mut plot = |dx: isize, dy: isize| {
+            if x + dx >= 0 && y + dy >= 0 {
+                set_pixel(buffer, (x + dx) as usize, (y + dy) as usize, color);
+            }
+        };
+        match shape {
+            BrushShape::Circle => {}
+            BrushShape::Square => {
+                // Only rows and columns that can land on the canvas
+                let rows = (-r).max(CANVAS_TOP as isize - y)
+                    ..=r.min(dims.canvas_bottom() as isize - 1 - y);
+                for dy in rows {
+                    for dx in (-r).max(-x)..=r.min(dims.width as isize - 1 - x) {
+                        plot(dx, dy);
+                    }
+                }
+            }
+            // Two pixels wide, so the nib leaves no holes moving across itself
+            BrushShape::Slash => {
+                for d in -r..=r {
+                    plot(d, -d);
+                    plot(d + 1, -d);
+                }
+            }
+            BrushShape::Spray => {
+                for (dx, dy) in spray_offsets(cx, cy, radius) {
+                    plot(dx, dy);
+                }
+            }
+        }
+    });
+}
+
+/// Draw a brush stroke line (brush stamps along a line path)
 pub fn draw_brush_line(
     buffer: &mut [u32],
     x0: usize,
@@ -84,11 +278,44 @@ pub fn draw_brush_line(
     color: u32,
     brush_size: usize,
 ) {
-    // Draw circles along the line using Bresenham's algorithm
-    let x0 = x0 as isize;
-    let y0 = y0 as isize;
-    let x1 = x1 as isize;
-    let y1 = y1 as isize;
+    // A dashed outline moves on by the whole line, however much is clipped,
+    // and its pixels are placed in the pattern by their distance from its
+    // start
+    let (start_x, start_y) = (x0.min(COORD_LIMIT) as f64, y0.min(COORD_LIMIT) as f64);
+    let dashes = stroke_style().pattern(brush_size).map(|pattern| {
+        let end = (x1.min(COORD_LIMIT) as f64, y1.min(COORD_LIMIT) as f64);
+        let length = distance(start_x, start_y, end.0, end.1);
+        (pattern, advance_stroke(length))
+    });
+    let along = |x: f64, y: f64| distance(start_x, start_y, x, y);
+
+    // Stamp the brush along the line using Bresenham's algorithm
+    let Some((x0, y0, x1, y1)) = clip_segment(x0, y0, x1, y1, brush_size) else {
+        return;
+    };
+    // Only round brushes have soft edges; other stamps follow the line
+    if antialiasing() && brush_shape() == BrushShape::Circle {
+        let dims = dimensions();
+        let radius = brush_size.clamp(1, dims.width + dims.height) as f64 - 0.5;
+        let (x0, y0, x1, y1) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+        let Some((pattern, traveled)) = dashes else {
+            draw_covered(buffer, color, |_| cover_capsule(x0, y0, x1, y1, radius));
+            return;
+        };
+        // A capsule for each dash, from its first pixel to its last
+        let (start, length) = (traveled + along(x0, y0), distance(x0, y0, x1, y1));
+        let point = |d: f64| match length > 0.0 {
+            true => (x0 + (x1 - x0) * d / length, y0 + (y1 - y0) * d / length),
+            false => (x0, y0),
+        };
+        draw_covered(buffer, color, |_| {
+            for (from, to) in dash_spans(pattern, start, start + length) {
+                let ((ax, ay), (bx, by)) = (point(from), point(to));
+                cover_capsule(ax, ay, bx, by, radius);
+            }
+        });
+        return;
+    }
 
     let dx = (x1 - x0).abs();
     let dy = -(y1 - y0).abs();
@@ -99,9 +326,13 @@ pub fn draw_brush_line(
     let mut x = x0;
     let mut y = y0;
 
-    loop {
-        if x >= 0 && y >= 0 {
-            draw_circle(buffer, x as usize, y as usize, brush_size, color);
+    // The dots overlap, so the line is composited as one shape
+    draw_covered(buffer, color, |buffer| loop {
+        let drawn = dashes.is_none_or(|(pattern, traveled)| {
+            dash_on(pattern, traveled + along(x as f64, y as f64))
+        });
+        if x >= 0 && y >= 0 && drawn {
+            draw_brush_dot(buffer, x as usize, y as usize, brush_size, color);
         }
 
         if x == x1 && y == y1 {
@@ -117,20 +348,76 @@ pub fn draw_brush_line(
             err += dx;
             y += sy;
         }
-    }
+    });
 }
 
 /// Clear the canvas area to white
 pub fn clear_canvas(buffer: &mut [u32]) {
-    for y in CANVAS_TOP..CANVAS_BOTTOM {
-        for x in 0..WIDTH {
-            buffer[y * WIDTH + x] = WHITE;
+    let width = window_width();
+    buffer[CANVAS_TOP * width..canvas_bottom() * width].fill(WHITE);
+}
+
+/// Flood-fill the contiguous region of same-colored pixels around (x, y)
+///
+/// Uses an iterative scanline fill (a stack of row spans, never recursion),
+/// confined to the canvas area. Every pixel of the region is the same, so
+/// each becomes `color` composited over it. Returns the number of pixels
+/// filled; a point off the canvas, or one that compositing wouldn't change,
+/// fills nothing.
+pub fn flood_fill(buffer: &mut [u32], x: usize, y: usize, color: u32) -> usize {
+    let dims = dimensions();
+    let (width, top, bottom) = (dims.width, CANVAS_TOP, dims.canvas_bottom());
+    if x >= width || !(top..bottom).contains(&y) {
+        return 0;
+    }
+    let target = buffer[y * width + x];
+    let color = composite(target, color, 1.0);
+    if target == color {
+        return 0;
+    }
+
+    let mut filled = 0;
+    // Seeds: a point on a row whose span still needs filling
+    let mut stack = vec![(x, y)];
+    while let Some((sx, sy)) = stack.pop() {
+        let row = sy * width;
+        if buffer[row + sx] != target {
+            continue;
+        }
+        // Grow the span left and right, then fill it
+        let mut left = sx;
+        while left > 0 && buffer[row + left - 1] == target {
+            left -= 1;
+        }
+        let mut right = sx;
+        while right + 1 < width && buffer[row + right + 1] == target {
+            right += 1;
+        }
+        buffer[row + left..=row + right].fill(color);
+        filled += right - left + 1;
+
+        // Seed each run of target pixels in the rows above and below the span
+        for ny in [sy.wrapping_sub(1), sy + 1] {
+            if !(top..bottom).contains(&ny) {
+                continue;
+            }
+            let nrow = ny * width;
+            let mut in_run = false;
+            for nx in left..=right {
+                let matches = buffer[nrow + nx] == target;
+                if matches && !in_run {
+                    stack.push((nx, ny));
+                }
+                in_run = matches;
+            }
         }
     }
+    filled
 }
 
 /// Draw a shape based on the current tool mode
 /// (x1, y1) is the drag start point, (x2, y2) is the drag end point
+#[allow(clippy::too_many_arguments)]
 pub fn draw_shape(
     buffer: &mut [u32],
     tool: ToolMode,
@@ -141,9 +428,14 @@ pub fn draw_shape(
     color: u32,
     brush_size: usize,
 ) {
-    match tool {
-        ToolMode::Brush => {
-            // Brush mode doesn't use this function
+    draw_covered(buffer, color, |buffer| match tool {
+        ToolMode::Brush
+        | ToolMode::Bucket
+        | ToolMode::Polygon
+        | ToolMode::Bezier
+        | ToolMode::Select
+        | ToolMode::Eyedropper => {
+            // Brush, bucket, polygon, bezier, select, and eyedropper modes don't use this function
         }
         ToolMode::Line => {
             draw_brush_line(buffer, x1, y1, x2, y2, color, brush_size);
@@ -154,20 +446,42 @@ pub fn draw_shape(
         ToolMode::Rectangle => {
             draw_shape_rectangle(buffer, x1, y1, x2, y2, color, brush_size);
         }
+        ToolMode::RoundedRect => {
+            let rect = RoundedRect::new(x1, y1, x2, y2, DRAG_CORNER_RADIUS);
+            draw_rounded_rect(buffer, &rect, color, brush_size);
+        }
+        ToolMode::Arrow => {
+            let arrow = Arrow {
+                x1,
+                y1,
+                x2,
+                y2,
+                head: default_arrow_head(brush_size),
+                both: false,
+            };
+            draw_arrow(buffer, &arrow, color, brush_size);
+        }
         ToolMode::Circle => {
+            #[cfg(feature = "deterministic")]
+            draw_shape_circle_fixed(buffer, x1, y1, x2, y2, color, brush_size);
+            #[cfg(not(feature = "deterministic"))]
             draw_shape_circle(buffer, x1, y1, x2, y2, color, brush_size);
         }
         ToolMode::Oval => {
+            #[cfg(feature = "deterministic")]
+            draw_shape_oval_fixed(buffer, x1, y1, x2, y2, color, brush_size);
+            #[cfg(not(feature = "deterministic"))]
             draw_shape_oval(buffer, x1, y1, x2, y2, color, brush_size);
         }
         ToolMode::Triangle => {
             draw_shape_triangle(buffer, x1, y1, x2, y2, color, brush_size);
         }
-    }
+    })
 }
 
 /// Draw a shape with optional edge and fill colors
 /// Fill is drawn first, then edge on top
+#[allow(clippy::too_many_arguments)]
 pub fn draw_shape_with_fill(
     buffer: &mut [u32],
     tool: ToolMode,
@@ -181,26 +495,7 @@ pub fn draw_shape_with_fill(
 ) {
     // Draw fill first (if any)
     if let Some(fill) = fill_color {
-        match tool {
-            ToolMode::Brush | ToolMode::Line => {
-                // Lines don't have fill
-            }
-            ToolMode::Square => {
-                fill_square(buffer, x1, y1, x2, y2, fill);
-            }
-            ToolMode::Rectangle => {
-                fill_rectangle(buffer, x1, y1, x2, y2, fill);
-            }
-            ToolMode::Circle => {
-                fill_circle(buffer, x1, y1, x2, y2, fill);
-            }
-            ToolMode::Oval => {
-                fill_oval(buffer, x1, y1, x2, y2, fill);
-            }
-            ToolMode::Triangle => {
-                fill_triangle(buffer, x1, y1, x2, y2, fill);
-            }
-        }
+        fill_shape(buffer, tool, x1, y1, x2, y2, fill);
     }
 
     // Draw edge on top (if any)
@@ -209,8 +504,50 @@ pub fn draw_shape_with_fill(
     }
 }
 
+/// Fill the interior of a shape based on the tool mode, as dragged from
+/// (x1, y1) to (x2, y2)
+///
+/// Lines, arrows, and curves have no interior (polygons are filled by
+/// `fill_polygon`).
+pub fn fill_shape(
+    buffer: &mut [u32],
+    tool: ToolMode,
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    fill: impl Into<Fill>,
+) {
+    match tool {
+        ToolMode::Brush
+        | ToolMode::Line
+        | ToolMode::Arrow
+        | ToolMode::Bucket
+        | ToolMode::Polygon
+        | ToolMode::Bezier
+        | ToolMode::Select
+        | ToolMode::Eyedropper => {}
+        ToolMode::Square => fill_square(buffer, x1, y1, x2, y2, fill),
+        ToolMode::Rectangle => fill_rectangle(buffer, x1, y1, x2, y2, fill),
+        ToolMode::RoundedRect => {
+            let rect = RoundedRect::new(x1, y1, x2, y2, DRAG_CORNER_RADIUS);
+            fill_rounded_rect(buffer, &rect, fill);
+        }
+        ToolMode::Circle => fill_circle(buffer, x1, y1, x2, y2, fill),
+        ToolMode::Oval => fill_oval(buffer, x1, y1, x2, y2, fill),
+        ToolMode::Triangle => fill_triangle(buffer, x1, y1, x2, y2, fill),
+    }
+}
+
 /// Fill a square region (largest square that fits in drag bounds)
-pub fn fill_square(buffer: &mut [u32], x1: usize, y1: usize, x2: usize, y2: usize, color: u32) {
+pub fn fill_square(
+    buffer: &mut [u32],
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    fill: impl Into<Fill>,
+) {
     let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
     let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
 
@@ -218,27 +555,54 @@ pub fn fill_square(buffer: &mut [u32], x1: usize, y1: usize, x2: usize, y2: usiz
     let height = bottom - top;
     let side = width.min(height);
 
-    for y in top..=top + side {
-        for x in left..=left + side {
-            set_pixel(buffer, x, y, color);
+    let paint = fill.into().over(
+        left,
+        top,
+        left.saturating_add(side),
+        top.saturating_add(side),
+    );
+    let last_y = top.saturating_add(side).min(canvas_bottom() - 1);
+    let last_x = left.saturating_add(side).min(window_width() - 1);
+    draw_covered(buffer, paint, |buffer| {
+        for y in top..=last_y {
+            for x in left..=last_x {
+                set_pixel(buffer, x, y, paint.color());
+            }
         }
-    }
+    });
 }
 
 /// Fill a rectangle region
-pub fn fill_rectangle(buffer: &mut [u32], x1: usize, y1: usize, x2: usize, y2: usize, color: u32) {
+pub fn fill_rectangle(
+    buffer: &mut [u32],
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    fill: impl Into<Fill>,
+) {
     let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
     let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
 
-    for y in top..=bottom {
-        for x in left..=right {
-            set_pixel(buffer, x, y, color);
+    let paint = fill.into().over(left, top, right, bottom);
+    draw_covered(buffer, paint, |buffer| {
+        for y in top..=bottom.min(canvas_bottom() - 1) {
+            for x in left..=right.min(window_width() - 1) {
+                set_pixel(buffer, x, y, paint.color());
+            }
         }
-    }
+    });
 }
 
 /// Fill a circle region
-pub fn fill_circle(buffer: &mut [u32], x1: usize, y1: usize, x2: usize, y2: usize, color: u32) {
+pub fn fill_circle(
+    buffer: &mut [u32],
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    fill: impl Into<Fill>,
+) {
     let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
     let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
 
@@ -249,95 +613,674 @@ pub fn fill_circle(buffer: &mut [u32], x1: usize, y1: usize, x2: usize, y2: usiz
 
     let cx = left as f64 + diameter as f64 / 2.0;
     let cy = top as f64 + diameter as f64 / 2.0;
+    let paint = fill.into().over(
+        left,
+        top,
+        left.saturating_add(diameter),
+        top.saturating_add(diameter),
+    );
+    if antialiasing() && diameter > 0 {
+        draw_covered(buffer, paint, |_| cover_ellipse(cx, cy, radius, radius));
+        return;
+    }
 
-    for y in top..=top + diameter {
-        for x in left..=left + diameter {
-            let dx = x as f64 - cx;
-            let dy = y as f64 - cy;
-            if dx * dx + dy * dy <= radius * radius {
-                set_pixel(buffer, x, y, color);
+    let last_y = top.saturating_add(diameter).min(canvas_bottom() - 1);
+    let last_x = left.saturating_add(diameter).min(window_width() - 1);
+    draw_covered(buffer, paint, |buffer| {
+        for y in top..=last_y {
+            for x in left..=last_x {
+                let dx = x as f64 - cx;
+                let dy = y as f64 - cy;
+                if dx * dx + dy * dy <= radius * radius {
+                    set_pixel(buffer, x, y, paint.color());
+                }
             }
         }
-    }
+    });
 }
 
 /// Fill an oval region
-pub fn fill_oval(buffer: &mut [u32], x1: usize, y1: usize, x2: usize, y2: usize, color: u32) {
+pub fn fill_oval(
+    buffer: &mut [u32],
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    fill: impl Into<Fill>,
+) {
     let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
     let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
 
-    let cx = (left + right) as f64 / 2.0;
-    let cy = (top + bottom) as f64 / 2.0;
+    let cx = (left as f64 + right as f64) / 2.0;
+    let cy = (top as f64 + bottom as f64) / 2.0;
     let rx = (right - left) as f64 / 2.0;
     let ry = (bottom - top) as f64 / 2.0;
 
     if rx == 0.0 || ry == 0.0 {
         return;
     }
+    let paint = fill.into().over(left, top, right, bottom);
+    if antialiasing() {
+        draw_covered(buffer, paint, |_| cover_ellipse(cx, cy, rx, ry));
+        return;
+    }
 
-    for y in top..=bottom {
-        for x in left..=right {
-            let dx = (x as f64 - cx) / rx;
-            let dy = (y as f64 - cy) / ry;
-            if dx * dx + dy * dy <= 1.0 {
-                set_pixel(buffer, x, y, color);
+    draw_covered(buffer, paint, |buffer| {
+        for y in top..=bottom.min(canvas_bottom() - 1) {
+            for x in left..=right.min(window_width() - 1) {
+                let dx = (x as f64 - cx) / rx;
+                let dy = (y as f64 - cy) / ry;
+                if dx * dx + dy * dy <= 1.0 {
+                    set_pixel(buffer, x, y, paint.color());
+                }
             }
         }
-    }
+    });
 }
 
 /// Fill a triangle region using scanline algorithm
-pub fn fill_triangle(buffer: &mut [u32], x1: usize, y1: usize, x2: usize, y2: usize, color: u32) {
+pub fn fill_triangle(
+    buffer: &mut [u32],
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    fill: impl Into<Fill>,
+) {
     let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
     let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
     let pointing_up = y2 < y1;
+    let paint = fill.into().over(left, top, right, bottom);
+
+    let mid_x = left + (right - left) / 2;
+    let last_y = bottom.min(canvas_bottom() - 1);
+    if antialiasing() {
+        // The same triangle with its corners on the pixel grid
+        let [left, top, right, bottom, mid_x] = [
+            left,
+            top,
+            right.saturating_add(1),
+            bottom.saturating_add(1),
+            mid_x,
+        ]
+        .map(|v| v.min(COORD_LIMIT) as f64);
+        let corners = if pointing_up {
+            [(mid_x + 0.5, top), (left, bottom), (right, bottom)]
+        } else {
+            [(left, top), (right, top), (mid_x + 0.5, bottom)]
+        };
+        draw_covered(buffer, paint, |_| cover_polygon(&corners));
+        return;
+    }
 
-    let mid_x = (left + right) / 2;
+    draw_covered(buffer, paint, |buffer| {
+        if pointing_up {
+            // Apex at top, base at bottom
+            let apex = (mid_x as f64, top as f64);
+            let left_base = (left as f64, bottom as f64);
+            let right_base = (right as f64, bottom as f64);
+
+            for y in top..=last_y {
+                let yf = y as f64;
+                // Find x bounds at this y
+                let t = if bottom != top {
+                    (yf - top as f64) / (bottom - top) as f64
+                } else {
+                    0.0
+                };
+                let x_left = apex.0 + t * (left_base.0 - apex.0);
+                let x_right = apex.0 + t * (right_base.0 - apex.0);
+
+                for x in (x_left as usize)..=(x_right as usize).min(window_width() - 1) {
+                    set_pixel(buffer, x, y, paint.color());
+                }
+            }
+        } else {
+            // Apex at bottom, base at top
+            let apex = (mid_x as f64, bottom as f64);
+            let left_base = (left as f64, top as f64);
+            let right_base = (right as f64, top as f64);
+
+            for y in top..=last_y {
+                let yf = y as f64;
+                let t = if bottom != top {
+                    (bottom as f64 - yf) / (bottom - top) as f64
+                } else {
+                    0.0
+                };
+                let x_left = apex.0 + t * (left_base.0 - apex.0);
+                let x_right = apex.0 + t * (right_base.0 - apex.0);
+
+                for x in (x_left as usize)..=(x_right as usize).min(window_width() - 1) {
+                    set_pixel(buffer, x, y, paint.color());
+                }
+            }
+        }
+    });
+}
 
-    if pointing_up {
-        // Apex at top, base at bottom
-        let apex = (mid_x as f64, top as f64);
-        let left_base = (left as f64, bottom as f64);
-        let right_base = (right as f64, bottom as f64);
+/// Fill a closed polygon using a scanline algorithm (even-odd rule)
+///
+/// Each row is sampled through pixel centers, so convex, concave, and
+/// self-intersecting polygons all fill without gaps or double-counted vertices.
+/// Anti-aliased, a vertex is the top-left corner of its pixel, and each pixel
+/// is covered by how much of it lies inside.
+pub fn fill_polygon(buffer: &mut [u32], points: &[(usize, usize)], fill: impl Into<Fill>) {
+    if points.len() < 3 {
+        return;
+    }
+    let (xs, ys) = (points.iter().map(|p| p.0), points.iter().map(|p| p.1));
+    let (first_y, last_y) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(0));
+    let paint = fill.into().over(
+        xs.clone().min().unwrap_or(0),
+        first_y,
+        xs.max().unwrap_or(0),
+        last_y,
+    );
+    if antialiasing() {
+        let corners: Vec<(f64, f64)> = points
+            .iter()
+            .map(|&(x, y)| (x.min(COORD_LIMIT) as f64, y.min(COORD_LIMIT) as f64))
+            .collect();
+        draw_covered(buffer, paint, |_| cover_polygon(&corners));
+        return;
+    }
+    let top = first_y.max(CANVAS_TOP);
+    let bottom = last_y.min(canvas_bottom() - 1);
+    let mut crossings: Vec<f64> = Vec::with_capacity(points.len());
 
+    draw_covered(buffer, paint, |buffer| {
         for y in top..=bottom {
-            let yf = y as f64;
-            // Find x bounds at this y
-            let t = if bottom != top {
-                (yf - top as f64) / (bottom - top) as f64
-            } else {
-                0.0
-            };
-            let x_left = apex.0 + t * (left_base.0 - apex.0);
-            let x_right = apex.0 + t * (right_base.0 - apex.0);
+            let yc = y as f64 + 0.5;
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                let (fy0, fy1) = (y0 as f64, y1 as f64);
+                // Half-open test, so a vertex shared by two edges crosses once
+                if (fy0 <= yc) != (fy1 <= yc) {
+                    let t = (yc - fy0) / (fy1 - fy0);
+                    crossings.push(x0 as f64 + t * (x1 as f64 - x0 as f64));
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+
+            // Fill pixels whose centers lie between each pair of crossings
+            for pair in crossings.chunks_exact(2) {
+                let first = (pair[0] - 0.5).ceil().max(0.0) as usize;
+                let end = ((pair[1] - 0.5).ceil().max(0.0) as usize).min(window_width());
+                for x in first..end {
+                    set_pixel(buffer, x, y, paint.color());
+                }
+            }
+        }
+    });
+}
+
+/// Draw the outline of a closed polygon (the last point joins the first)
+pub fn draw_polygon(buffer: &mut [u32], points: &[(usize, usize)], color: u32, brush_size: usize) {
+    draw_covered(buffer, color, |buffer| {
+        for (i, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(i + 1) % points.len()];
+            draw_brush_line(buffer, x0, y0, x1, y1, color, brush_size);
+        }
+    });
+}
 
-            for x in (x_left as usize)..=(x_right as usize) {
-                set_pixel(buffer, x, y, color);
+/// Draw a closed polygon with optional edge and fill colors
+/// Fill is drawn first, then edge on top
+pub fn draw_polygon_with_fill(
+    buffer: &mut [u32],
+    points: &[(usize, usize)],
+    edge_color: Option<u32>,
+    fill_color: Option<u32>,
+    brush_size: usize,
+) {
+    if let Some(fill) = fill_color {
+        fill_polygon(buffer, points, fill);
+    }
+    if let Some(edge) = edge_color {
+        draw_polygon(buffer, points, edge, brush_size);
+    }
+}
+
+/// Most sides of an `ngon`, and points of a `star`
+pub const MAX_SIDES: usize = 1000;
+
+/// The corners of a regular polygon centered on (cx, cy), `r` from its
+/// center, the first `rotation` degrees clockwise from straight up (at most
+/// `MAX_SIDES` of them)
+pub fn ngon_vertices(
+    cx: usize,
+    cy: usize,
+    r: usize,
+    sides: usize,
+    rotation: f64,
+) -> Vec<(usize, usize)> {
+    let sides = sides.min(MAX_SIDES);
+    (0..sides)
+        .map(|i| {
+            let degrees = rotation + i as f64 * 360.0 / sides as f64;
+            vertex(cx, cy, r, degrees)
+        })
+        .collect()
+}
+
+/// The corners of a star centered on (cx, cy): `points` tips (at most
+/// `MAX_SIDES`) `outer` from its center, the first straight up, with corners
+/// `inner` from its center between them
+pub fn star_vertices(
+    cx: usize,
+    cy: usize,
+    outer: usize,
+    inner: usize,
+    points: usize,
+) -> Vec<(usize, usize)> {
+    let points = points.min(MAX_SIDES);
+    (0..points * 2)
+        .map(|i| {
+            let r = if i % 2 == 0 { outer } else { inner };
+            vertex(cx, cy, r, i as f64 * 180.0 / points as f64)
+        })
+        .collect()
+}
+
+/// The pixel `r` from (cx, cy) at `degrees` clockwise from straight up
+/// (pulled onto the window's top or left edge if past it)
+fn vertex(cx: usize, cy: usize, r: usize, degrees: f64) -> (usize, usize) {
+    let (sin, cos) = sin_cos_degrees(degrees % 360.0);
+    let x = cx as f64 + r as f64 * sin;
+    let y = cy as f64 - r as f64 * cos;
+    (x.round().max(0.0) as usize, y.round().max(0.0) as usize)
+}
+
+/// Sine and cosine of an angle in degrees
+#[cfg(not(feature = "deterministic"))]
+pub(crate) fn sin_cos_degrees(degrees: f64) -> (f64, f64) {
+    degrees.to_radians().sin_cos()
+}
+
+/// Sine and cosine of an angle in degrees, from Taylor series on the angle
+/// reduced to within 45 degrees of an axis (basic IEEE operations only)
+#[cfg(feature = "deterministic")]
+pub(crate) fn sin_cos_degrees(degrees: f64) -> (f64, f64) {
+    let quarter = (degrees / 90.0).round();
+    let x = (degrees - quarter * 90.0) * (std::f64::consts::PI / 180.0);
+    let x2 = x * x;
+    let (mut sin, mut sin_term) = (x, x);
+    let (mut cos, mut cos_term) = (1.0, 1.0);
+    // Eight terms each leave an error below 1e-16 for |x| <= pi/4
+    for k in 1..=8 {
+        let k = k as f64;
+        sin_term *= -x2 / ((2.0 * k) * (2.0 * k + 1.0));
+        cos_term *= -x2 / ((2.0 * k - 1.0) * (2.0 * k));
+        sin += sin_term;
+        cos += cos_term;
+    }
+    match (quarter as i64).rem_euclid(4) {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
+
+/// A circular arc, or a pie slice closed through its center
+///
+/// Angles are in degrees clockwise from 3 o'clock (the canvas's y axis points
+/// down). The arc runs from `start` to `end`, counterclockwise if `end` is
+/// smaller, and never more than one full turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArcShape {
+    pub cx: usize,
+    pub cy: usize,
+    pub r: usize,
+    pub start: f64,
+    pub end: f64,
+    /// Closed through the center rather than straight across (by the chord)
+    pub pie: bool,
+}
+
+impl ArcShape {
+    /// Degrees swept from `start`, negative when counterclockwise
+    pub fn sweep(&self) -> f64 {
+        (self.end - self.start).clamp(-360.0, 360.0)
+    }
+
+    /// The point `degrees` around the circle
+    pub fn point_at(&self, degrees: f64) -> (f64, f64) {
+        let r = self.r.min(COORD_LIMIT) as f64;
+        let (sin, cos) = sin_cos_degrees(degrees % 360.0);
+        (self.cx as f64 + r * cos, self.cy as f64 + r * sin)
+    }
+
+    /// Pixels along the arc from `start` to `end`, about half a pixel apart,
+    /// then the center for a pie slice
+    pub fn outline(&self) -> Vec<(usize, usize)> {
+        let sweep = self.sweep();
+        let length = self.r.min(COORD_LIMIT) as f64 * sweep.abs().to_radians();
+        let steps = (length * 2.0).clamp(1.0, MAX_OUTLINE_STEPS as f64) as usize;
+        let mut points: Vec<(usize, usize)> = (0..=steps)
+            .map(|i| {
+                let (x, y) = self.point_at(self.start + sweep * i as f64 / steps as f64);
+                (x.round().max(0.0) as usize, y.round().max(0.0) as usize)
+            })
+            .collect();
+        if self.pie {
+            points.push((self.cx, self.cy));
+        }
+        points.dedup();
+        points
+    }
+}
+
+/// Draw an arc or pie slice with optional edge and fill colors
+///
+/// A pie slice fills the wedge and outlines both radii; an arc fills the
+/// segment its chord cuts off but strokes only the curve. Fill is drawn
+/// first, then edge on top.
+pub fn draw_arc_with_fill(
+    buffer: &mut [u32],
+    arc: &ArcShape,
+    edge_color: Option<u32>,
+    fill_color: Option<u32>,
+    brush_size: usize,
+) {
+    let points = arc.outline();
+    if let Some(fill) = fill_color {
+        fill_polygon(buffer, &points, fill);
+    }
+    let Some(edge) = edge_color else {
+        return;
+    };
+    draw_covered(buffer, edge, |buffer| match points.as_slice() {
+        [(x, y)] => draw_circle(buffer, *x, *y, brush_size, edge),
+        _ if arc.pie => draw_polygon(buffer, &points, edge, brush_size),
+        _ => {
+            for pair in points.windows(2) {
+                let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                draw_brush_line(buffer, x0, y0, x1, y1, edge, brush_size);
             }
         }
-    } else {
-        // Apex at bottom, base at top
-        let apex = (mid_x as f64, bottom as f64);
-        let left_base = (left as f64, top as f64);
-        let right_base = (right as f64, top as f64);
+    });
+}
 
-        for y in top..=bottom {
-            let yf = y as f64;
-            let t = if bottom != top {
-                (bottom as f64 - yf) / (bottom - top) as f64
-            } else {
-                0.0
-            };
-            let x_left = apex.0 + t * (left_base.0 - apex.0);
-            let x_right = apex.0 + t * (right_base.0 - apex.0);
+/// Corner radius of rounded rectangles dragged out with the mouse
+pub const DRAG_CORNER_RADIUS: usize = 12;
+
+/// A rectangle with quarter-circle corners
+///
+/// The shape is defined row by row: `span` is the run of pixels each row
+/// covers, narrowing at the corners. The fill paints those runs and the
+/// outline traces their ends, so the edge always lies exactly on the fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundedRect {
+    pub left: usize,
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+    /// Corner radius, at most half the shorter side
+    pub r: usize,
+}
+
+impl RoundedRect {
+    /// The rounded rectangle between two corners (inclusive, either order)
+    pub fn new(x1: usize, y1: usize, x2: usize, y2: usize, r: usize) -> Self {
+        let (left, right) = (x1.min(x2), x1.max(x2));
+        let (top, bottom) = (y1.min(y2), y1.max(y2));
+        let r = r
+            .min((right - left) / 2)
+            .min((bottom - top) / 2)
+            .min(COORD_LIMIT);
+        RoundedRect {
+            left,
+            top,
+            right,
+            bottom,
+            r,
+        }
+    }
 
-            for x in (x_left as usize)..=(x_right as usize) {
-                set_pixel(buffer, x, y, color);
+    /// The first and last column row `y` covers (None outside the shape)
+    pub fn span(&self, y: usize) -> Option<(usize, usize)> {
+        if y < self.top || y > self.bottom {
+            return None;
+        }
+        // Rows from the nearest corner's center
+        let dy = if y < self.top + self.r {
+            self.top + self.r - y
+        } else {
+            y.saturating_sub(self.bottom - self.r)
+        };
+        let inset = self.r - (self.r * self.r - dy * dy).isqrt();
+        Some((self.left + inset, self.right - inset))
+    }
+
+    /// The columns of row `y` on the shape's outline: those with no covered
+    /// pixel above or below, and the row's ends (at most two runs)
+    pub fn outline_runs(&self, y: usize) -> [Option<(usize, usize)>; 2] {
+        let Some((left, right)) = self.span(y) else {
+            return [None, None];
+        };
+        let above = y.checked_sub(1).and_then(|y| self.span(y));
+        let below = y.checked_add(1).and_then(|y| self.span(y));
+        // The columns covered above and below both are inside the shape
+        match (above, below) {
+            (Some((a0, a1)), Some((b0, b1))) if a0.max(b0) <= a1.min(b1) => {
+                let (inner_left, inner_right) = (a0.max(b0), a1.min(b1));
+                [
+                    Some((left, inner_left.saturating_sub(1).max(left))),
+                    Some((inner_right.saturating_add(1).min(right), right)),
+                ]
+            }
+            _ => [Some((left, right)), None],
+        }
+    }
+}
+
+/// Fill a rounded rectangle, row by row
+pub fn fill_rounded_rect(buffer: &mut [u32], rect: &RoundedRect, fill: impl Into<Fill>) {
+    let paint = fill
+        .into()
+        .over(rect.left, rect.top, rect.right, rect.bottom);
+    if antialiasing() {
+        let [left, top, right, bottom, r] = [rect.left, rect.top, rect.right, rect.bottom, rect.r]
+            .map(|v| v.min(COORD_LIMIT) as f64);
+        draw_covered(buffer, paint, |_| {
+            cover_rounded_box(left, top, right, bottom, r)
+        });
+        return;
+    }
+    draw_covered(buffer, paint, |buffer| {
+        for y in rect.top.max(CANVAS_TOP)..=rect.bottom.min(canvas_bottom() - 1) {
+            if let Some((left, right)) = rect.span(y) {
+                fill_rectangle(buffer, left, y, right, y, paint.color());
             }
         }
+    });
+}
+
+/// Outline a rounded rectangle, stamping the brush on every outline pixel
+pub fn draw_rounded_rect(buffer: &mut [u32], rect: &RoundedRect, color: u32, brush_size: usize) {
+    // Outline pixels up to a brush's reach off the canvas still mark it
+    let reach = brush_size.min(window_width() + canvas_bottom());
+    let first = rect.top.max(CANVAS_TOP.saturating_sub(reach));
+    let last = rect.bottom.min(canvas_bottom() + reach);
+    draw_covered(buffer, color, |buffer| {
+        for y in first..=last {
+            for (left, right) in rect.outline_runs(y).into_iter().flatten() {
+                for x in left..=right.min(window_width() + reach) {
+                    draw_circle(buffer, x, y, brush_size, color);
+                }
+            }
+        }
+    });
+}
+
+/// Draw a rounded rectangle with optional edge and fill colors
+/// Fill is drawn first, then edge on top
+pub fn draw_rounded_rect_with_fill(
+    buffer: &mut [u32],
+    rect: &RoundedRect,
+    edge_color: Option<u32>,
+    fill_color: Option<u32>,
+    brush_size: usize,
+) {
+    if let Some(fill) = fill_color {
+        fill_rounded_rect(buffer, rect, fill);
+    }
+    if let Some(edge) = edge_color {
+        draw_rounded_rect(buffer, rect, edge, brush_size);
+    }
+}
+
+/// Shortest default arrowhead, whatever the brush size
+pub const MIN_ARROW_HEAD: usize = 10;
+
+/// Arrowhead length used when none is given: long enough to stand out from
+/// a shaft drawn with `brush_size`
+pub fn default_arrow_head(brush_size: usize) -> usize {
+    brush_size.saturating_mul(4).max(MIN_ARROW_HEAD)
+}
+
+/// An arrowhead's corners: its tip, then the two back corners
+pub type ArrowHead = [(usize, usize); 3];
+
+/// A line from (x1, y1) to (x2, y2) ending in a filled arrowhead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arrow {
+    pub x1: usize,
+    pub y1: usize,
+    pub x2: usize,
+    pub y2: usize,
+    /// Arrowhead length along the shaft; heads are half as wide as long
+    pub head: usize,
+    /// Whether (x1, y1) has a head too
+    pub both: bool,
+}
+
+impl Arrow {
+    /// The head pointing at one end, and where the shaft meets it; None when
+    /// the arrow is too short for one
+    ///
+    /// A head never runs past the other end, or past the middle when both
+    /// ends have one.
+    fn head_at(&self, at_end: bool) -> Option<(ArrowHead, (usize, usize))> {
+        let (tip, from) = if at_end {
+            ((self.x2, self.y2), (self.x1, self.y1))
+        } else {
+            ((self.x1, self.y1), (self.x2, self.y2))
+        };
+        let (dx, dy) = (tip.0 as f64 - from.0 as f64, tip.1 as f64 - from.1 as f64);
+        let length = (dx * dx + dy * dy).sqrt();
+        let room = if self.both { length / 2.0 } else { length };
+        let size = (self.head as f64).min(room);
+        if size < 1.0 {
+            return None;
+        }
+        let (ux, uy) = (dx / length, dy / length);
+        let (bx, by) = (tip.0 as f64 - ux * size, tip.1 as f64 - uy * size);
+        let half = size / 2.0;
+        // Corners past the window's top or left edge are pulled onto it
+        let point = |x: f64, y: f64| (x.round().max(0.0) as usize, y.round().max(0.0) as usize);
+        let corners = [
+            tip,
+            point(bx - uy * half, by + ux * half),
+            point(bx + uy * half, by - ux * half),
+        ];
+        Some((corners, point(bx, by)))
+    }
+
+    /// The filled arrowheads
+    pub fn heads(&self) -> Vec<ArrowHead> {
+        let tail = self.both.then(|| self.head_at(false)).flatten();
+        [self.head_at(true), tail]
+            .into_iter()
+            .flatten()
+            .map(|(corners, _)| corners)
+            .collect()
+    }
+
+    /// The shaft's ends: the arrow's own ends, or where a head starts
+    pub fn shaft(&self) -> ((usize, usize), (usize, usize)) {
+        let start = match self.both.then(|| self.head_at(false)).flatten() {
+            Some((_, base)) => base,
+            None => (self.x1, self.y1),
+        };
+        let end = self
+            .head_at(true)
+            .map_or((self.x2, self.y2), |(_, base)| base);
+        (start, end)
     }
 }
 
+/// Draw an arrow: the shaft with the brush, and each head filled
+///
+/// The shaft stops where a head begins so a wide brush's round end doesn't
+/// blunt the tip.
+pub fn draw_arrow(buffer: &mut [u32], arrow: &Arrow, color: u32, brush_size: usize) {
+    let ((x0, y0), (x1, y1)) = arrow.shaft();
+    draw_covered(buffer, color, |buffer| {
+        draw_brush_line(buffer, x0, y0, x1, y1, color, brush_size);
+        for head in arrow.heads() {
+            fill_polygon(buffer, &head, color);
+            draw_polygon(buffer, &head, color, 1);
+        }
+    });
+}
+
+/// Pixels along a Bezier curve through its control points (three for a
+/// quadratic curve, four for a cubic), about half a pixel apart
+///
+/// Points are found by de Casteljau's construction, which uses only basic
+/// float operations; other point counts give just the points themselves.
+pub fn bezier_points(controls: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    if !(3..=4).contains(&controls.len()) {
+        return controls.to_vec();
+    }
+    let controls: Vec<(f64, f64)> = controls
+        .iter()
+        .map(|&(x, y)| (x.min(COORD_LIMIT) as f64, y.min(COORD_LIMIT) as f64))
+        .collect();
+    // The curve is never longer than its control polygon
+    let length: f64 = controls
+        .windows(2)
+        .map(|w| distance(w[0].0, w[0].1, w[1].0, w[1].1))
+        .sum();
+    let steps = (length * 2.0).clamp(1.0, MAX_OUTLINE_STEPS as f64) as usize;
+    let mut scratch = controls.clone();
+    let mut points: Vec<(usize, usize)> = (0..=steps)
+        .map(|i| {
+            let t = i as f64 / steps as f64;
+            scratch.copy_from_slice(&controls);
+            for n in (1..scratch.len()).rev() {
+                for j in 0..n {
+                    scratch[j] = (
+                        scratch[j].0 + (scratch[j + 1].0 - scratch[j].0) * t,
+                        scratch[j].1 + (scratch[j + 1].1 - scratch[j].1) * t,
+                    );
+                }
+            }
+            (scratch[0].0.round() as usize, scratch[0].1.round() as usize)
+        })
+        .collect();
+    points.dedup();
+    points
+}
+
+/// Draw a Bezier curve (see `bezier_points`) with the brush
+pub fn draw_bezier(buffer: &mut [u32], controls: &[(usize, usize)], color: u32, brush_size: usize) {
+    let points = bezier_points(controls);
+    draw_covered(buffer, color, |buffer| {
+        if let [(x, y)] = points.as_slice() {
+            draw_circle(buffer, *x, *y, brush_size, color);
+        }
+        for pair in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            draw_brush_line(buffer, x0, y0, x1, y1, color, brush_size);
+        }
+    });
+}
+
 /// Draw a square from corner to corner (largest square that fits in drag bounds)
 pub fn draw_shape_square(
     buffer: &mut [u32],
@@ -355,8 +1298,8 @@ pub fn draw_shape_square(
     let height = bottom - top;
     let side = width.min(height);
 
-    let right = left + side;
-    let bottom = top + side;
+    let right = left.saturating_add(side);
+    let bottom = top.saturating_add(side);
 
     // Draw four sides
     draw_brush_line(buffer, left, top, right, top, color, brush_size); // Top
@@ -407,13 +1350,18 @@ pub fn draw_shape_circle(
     if radius < 1.0 {
         draw_circle(
             buffer,
-            (left + right) / 2,
-            (top + bottom) / 2,
+            left + (right - left) / 2,
+            top + (bottom - top) / 2,
             brush_size,
             color,
         );
         return;
     }
+    if left > window_width().saturating_add(brush_size)
+        || top > canvas_bottom().saturating_add(brush_size)
+    {
+        return;
+    }
 
     // Center the circle in the bounding box
     let cx = left as f64 + diameter as f64 / 2.0;
@@ -421,7 +1369,7 @@ pub fn draw_shape_circle(
 
     // Draw circle using parametric form with brush
     let circumference = 2.0 * std::f64::consts::PI * radius;
-    let steps = (circumference * 2.0).max(32.0) as usize;
+    let steps = (circumference * 2.0).clamp(32.0, MAX_OUTLINE_STEPS as f64) as usize;
 
     let mut prev_x = cx + radius;
     let mut prev_y = cy;
@@ -459,8 +1407,8 @@ pub fn draw_shape_oval(
     let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
     let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
 
-    let cx = (left + right) / 2;
-    let cy = (top + bottom) / 2;
+    let cx = left + (right - left) / 2;
+    let cy = top + (bottom - top) / 2;
     let rx = (right - left) / 2;
     let ry = (bottom - top) / 2;
 
@@ -470,7 +1418,15 @@ pub fn draw_shape_oval(
     }
 
     // Draw ellipse using parametric form
-    let steps = ((rx + ry) * 4).max(32);
+    if left > window_width().saturating_add(brush_size)
+        || top > canvas_bottom().saturating_add(brush_size)
+    {
+        return;
+    }
+    let steps = rx
+        .saturating_add(ry)
+        .saturating_mul(4)
+        .clamp(32, MAX_OUTLINE_STEPS);
 
     let mut prev_x = cx as f64 + rx as f64;
     let mut prev_y = cy as f64;
@@ -495,6 +1451,175 @@ pub fn draw_shape_oval(
     }
 }
 
+/// Largest radius traced by the fixed-point outlines, bounding the work for
+/// absurd shapes (the visible part of anything larger is effectively straight)
+const MAX_FIXED_RADIUS: i64 = 1 << 20;
+
+/// Trace an ellipse outline with the integer midpoint algorithm, stamping the
+/// brush at every outline pixel. The outline pixels are found with integer
+/// math alone, and anti-aliased they're joined by capsules (which take only
+/// basic float operations), so the output is bit-identical on every platform.
+fn trace_ellipse_fixed(
+    buffer: &mut [u32],
+    cx: usize,
+    cy: usize,
+    rx: usize,
+    ry: usize,
+    color: u32,
+    brush_size: usize,
+) {
+    let cx = cx.min(COORD_LIMIT) as i64;
+    let cy = cy.min(COORD_LIMIT) as i64;
+    let rx = (rx as i64).min(MAX_FIXED_RADIUS);
+    let ry = (ry as i64).min(MAX_FIXED_RADIUS);
+
+    // The quadrants are traced together, so a dashed outline is measured
+    // along one and mirrored into the others
+    let pattern = stroke_style().pattern(brush_size);
+    // Soft round brushes cover the step from the last outline pixel, as
+    // `draw_brush_line` does, rather than stamping hard-edged discs
+    let soft = antialiasing() && brush_shape() == BrushShape::Circle;
+    let dims = dimensions();
+    let radius = brush_size.clamp(1, dims.width + dims.height) as f64 - 0.5;
+    let (mut along, mut last) = (0.0, (0, ry));
+    let mut plot = |dx: i64, dy: i64| {
+        along += distance(last.0 as f64, last.1 as f64, dx as f64, dy as f64);
+        let (px, py) = std::mem::replace(&mut last, (dx, dy));
+        if pattern.is_some_and(|pattern| !dash_on(pattern, along)) {
+            return;
+        }
+        for (sx, sy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+            let (x, y) = (cx + sx * dx, cy + sy * dy);
+            if soft {
+                let (x0, y0) = ((cx + sx * px) as f64, (cy + sy * py) as f64);
+                cover_capsule(x0, y0, x as f64, y as f64, radius);
+            } else if x >= 0 && y >= 0 {
+                draw_circle(buffer, x as usize, y as usize, brush_size, color);
+            }
+        }
+    };
+
+    // Error terms are scaled by 4 to keep the half-pixel offsets integral,
+    // and kept in i128 since they grow with the fourth power of the radius
+    let rx2 = (rx * rx) as i128;
+    let ry2 = (ry * ry) as i128;
+    let mut x: i64 = 0;
+    let mut y: i64 = ry;
+    let mut dx: i128 = 0;
+    let mut dy: i128 = 2 * rx2 * y as i128;
+
+    // Region 1: slope shallower than -1
+    let mut d: i128 = 4 * ry2 - 4 * rx2 * ry as i128 + rx2;
+    while dx < dy {
+        plot(x, y);
+        x += 1;
+        dx += 2 * ry2;
+        if d < 0 {
+            d += 4 * (dx + ry2);
+        } else {
+            y -= 1;
+            dy -= 2 * rx2;
+            d += 4 * (dx - dy + ry2);
+        }
+    }
+
+    // Region 2: slope steeper than -1
+    let (xi, yi) = (x as i128, y as i128);
+    let mut d: i128 =
+        ry2 * (2 * xi + 1) * (2 * xi + 1) + 4 * rx2 * (yi - 1) * (yi - 1) - 4 * rx2 * ry2;
+    while y >= 0 {
+        plot(x, y);
+        y -= 1;
+        dy -= 2 * rx2;
+        if d > 0 {
+            d += 4 * (rx2 - dy);
+        } else {
+            x += 1;
+            dx += 2 * ry2;
+            d += 4 * (dx - dy + rx2);
+        }
+    }
+}
+
+/// Fixed-point variant of `draw_shape_circle` for the deterministic render mode
+///
+/// The circle is centered on whole pixels, so odd diameters sit half a pixel
+/// up-left of the floating-point version.
+pub fn draw_shape_circle_fixed(
+    buffer: &mut [u32],
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    color: u32,
+    brush_size: usize,
+) {
+    let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
+    let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
+
+    let diameter = (right - left).min(bottom - top);
+    let radius = diameter / 2;
+
+    if radius < 1 {
+        draw_circle(
+            buffer,
+            left + (right - left) / 2,
+            top + (bottom - top) / 2,
+            brush_size,
+            color,
+        );
+        return;
+    }
+    if left > window_width().saturating_add(brush_size)
+        || top > canvas_bottom().saturating_add(brush_size)
+    {
+        return;
+    }
+
+    draw_covered(buffer, color, |buffer| {
+        trace_ellipse_fixed(
+            buffer,
+            left + radius,
+            top + radius,
+            radius,
+            radius,
+            color,
+            brush_size,
+        )
+    });
+}
+
+/// Fixed-point variant of `draw_shape_oval` for the deterministic render mode
+pub fn draw_shape_oval_fixed(
+    buffer: &mut [u32],
+    x1: usize,
+    y1: usize,
+    x2: usize,
+    y2: usize,
+    color: u32,
+    brush_size: usize,
+) {
+    let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
+    let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
+
+    let rx = (right - left) / 2;
+    let ry = (bottom - top) / 2;
+
+    if rx == 0 || ry == 0 {
+        draw_brush_line(buffer, x1, y1, x2, y2, color, brush_size);
+        return;
+    }
+    if left > window_width().saturating_add(brush_size)
+        || top > canvas_bottom().saturating_add(brush_size)
+    {
+        return;
+    }
+
+    draw_covered(buffer, color, |buffer| {
+        trace_ellipse_fixed(buffer, left + rx, top + ry, rx, ry, color, brush_size)
+    });
+}
+
 /// Draw a triangle in the bounding box from drag start to end
 /// If dragging upward: apex at top (pointing up)
 /// If dragging downward: apex at bottom (pointing down)
@@ -511,7 +1636,7 @@ pub fn draw_shape_triangle(
     let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
     let pointing_up = y2 < y1; // Dragging upward = triangle points up
 
-    let mid_x = (left + right) / 2;
+    let mid_x = left + (right - left) / 2;
 
     if pointing_up {
         // Apex at top, base at bottom (pointing up)
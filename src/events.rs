@@ -0,0 +1,160 @@
+//! Events sent to subscribed clients.
+//!
+//! This module handles:
+//! - `Event`, one line on the event stream (`clicked 3`, `hover 3`, a
+//!   button's own event name, `change volume 7` from a slider, or
+//!   `submit name some text` from a text input, or `key a` for a key no
+//!   widget used, or `confirm yes` / `prompt ok name.png` from a dialog, or
+//!   `watch 2 rect 10,40 50,80` when a command changes a watched region, or
+//!   `ready <instance> [socket]`, the first event each subscriber gets)
+//! - `Subscribers`, the connections that sent `subscribe`
+//! - `PointerTracker`, which turns pointer movement and clicks over retained
+//!   objects into events
+//!
+//! Events are plain lines without a request ID, written as they happen, so a
+//! controller can tell them apart from tagged responses on the same connection.
+
+use std::fmt;
+use std::io::Write;
+
+use crate::dialog::DialogAnswer;
+
+/// Longest event name a widget may send
+pub const MAX_EVENT_NAME_LEN: usize = 32;
+
+/// Whether `name` is a valid event name (letters, digits, `_`, `-`)
+pub fn is_valid_event_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_EVENT_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Something a subscribed client is told about
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The user clicked the object with this ID
+    Clicked(usize),
+    /// The pointer moved onto the object with this ID
+    Hover(usize),
+    /// An event named by a widget, such as a button's `<event-name>`
+    Named(String),
+    /// A widget changed the variable `name` to `value`
+    Changed { name: String, value: String },
+    /// A text input editing `name` was submitted with `value`
+    Submitted { name: String, value: String },
+    /// A key press that no focused widget or shortcut used, by `KeyInput::name`
+    Key(String),
+    /// The user answered a dialog opened with `dialog confirm` or `dialog prompt`
+    Answered(DialogAnswer),
+    /// A command changed pixels in the region watched by `watch <id>`
+    Watch { id: usize, command: String },
+    /// Shape snap replaced a freehand stroke with the shape this command draws
+    Snapped(String),
+    /// This run of displai (see `instance_id`) is serving commands, on the
+    /// socket if it listens on one; a new instance means it was restarted
+    Ready {
+        instance: String,
+        socket: Option<String>,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Clicked(id) => write!(f, "clicked {}", id),
+            Event::Hover(id) => write!(f, "hover {}", id),
+            Event::Named(name) => write!(f, "{}", name),
+            Event::Changed { name, value } => write!(f, "change {} {}", name, value),
+            Event::Submitted { name, value } => write!(f, "submit {} {}", name, value),
+            Event::Key(name) => write!(f, "key {}", name),
+            Event::Answered(answer) => write!(f, "{}", answer),
+            Event::Watch { id, command } => write!(f, "watch {} {}", id, command),
+            Event::Snapped(command) => write!(f, "snap {}", command),
+            Event::Ready { instance, socket } => match socket {
+                Some(socket) => write!(f, "ready {} {}", instance, socket),
+                None => write!(f, "ready {}", instance),
+            },
+        }
+    }
+}
+
+/// Writers that receive every event, one line each
+///
+/// A subscriber whose write fails (the client hung up) is dropped.
+#[derive(Default)]
+pub struct Subscribers {
+    sinks: Vec<Box<dyn Write + Send>>,
+}
+
+impl Subscribers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, sink: Box<dyn Write + Send>) {
+        self.sinks.push(sink);
+    }
+
+    /// Add a subscriber whose first event is `event` (unless writing it fails)
+    pub fn add_greeted(&mut self, mut sink: Box<dyn Write + Send>, event: &Event) {
+        if write_event(&mut sink, &format!("{}\n", event)) {
+            self.sinks.push(sink);
+        }
+    }
+
+    /// Write an event to every subscriber
+    pub fn emit(&mut self, event: &Event) {
+        let line = format!("{}\n", event);
+        self.sinks.retain_mut(|sink| write_event(sink, &line));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+}
+
+/// Write one event line to a subscriber; false if it hung up
+fn write_event(sink: &mut Box<dyn Write + Send>, line: &str) -> bool {
+    sink.write_all(line.as_bytes())
+        .and_then(|_| sink.flush())
+        .is_ok()
+}
+
+/// Which object the pointer is over, to report each hover once
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PointerTracker {
+    hovered: Option<usize>,
+}
+
+impl PointerTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The events for one frame of input, given the ID of the object under
+    /// the pointer (if any) and whether the left button was just pressed
+    ///
+    /// Moving onto an object reports `hover` once; moving within it does not.
+    pub fn update(&mut self, over: Option<usize>, clicked: bool) -> Vec<Event> {
+        let mut events = Vec::new();
+        if over != self.hovered {
+            self.hovered = over;
+            events.extend(over.map(Event::Hover));
+        }
+        if clicked {
+            events.extend(over.map(Event::Clicked));
+        }
+        events
+    }
+
+    /// The object the pointer is over
+    pub fn hovered(&self) -> Option<usize> {
+        self.hovered
+    }
+}
@@ -0,0 +1,169 @@
+//! Image filters over what is already drawn.
+//!
+//! This module handles:
+//! - `Filter`, set out by `filter grayscale|invert|blur <radius>|brightness
+//!   <delta> [x1,y1 x2,y2]`
+//! - Applying one to a rectangle of a buffer, in place
+//!
+//! Grayscale, invert, and brightness change each pixel on its own. Blur is a
+//! box blur, run along the rows and then down the columns (the same as one
+//! square kernel `2 * radius + 1` wide, for far less work); near the edge of
+//! the rectangle it averages only the pixels inside it, so nothing outside is
+//! read or changed and the edges don't darken.
+
+use std::fmt;
+
+use crate::window_width;
+
+/// Widest blur radius
+pub const MAX_BLUR_RADIUS: usize = 50;
+/// Largest brightness change, up or down
+pub const MAX_BRIGHTNESS_DELTA: i32 = 255;
+
+/// A change to every pixel of a rectangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Each pixel's luma (ITU-R BT.601 weights) as a gray
+    Grayscale,
+    /// Each channel flipped, black for white
+    Invert,
+    /// Each pixel the average of those up to this far away across and down
+    Blur(usize),
+    /// This much added to each channel (negative darkens), held to 0-255
+    Brightness(i32),
+}
+
+impl Filter {
+    /// Parse the filter at the start of `filter ...`'s arguments, returning
+    /// it and the arguments after it
+    pub fn parse<'a>(args: &'a [&'a str]) -> Option<(Filter, &'a [&'a str])> {
+        match *args {
+            ["grayscale", ref rest @ ..] => Some((Filter::Grayscale, rest)),
+            ["invert", ref rest @ ..] => Some((Filter::Invert, rest)),
+            ["blur", radius, ref rest @ ..] => Some((Filter::Blur(radius.parse().ok()?), rest)),
+            ["brightness", delta, ref rest @ ..] => {
+                Some((Filter::Brightness(delta.parse().ok()?), rest))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check a blur radius is 1 to `MAX_BLUR_RADIUS` and a brightness change
+    /// is at most `MAX_BRIGHTNESS_DELTA` either way
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            Filter::Blur(radius) if !(1..=MAX_BLUR_RADIUS).contains(&radius) => Err(format!(
+                "blur radius {} is outside 1-{}",
+                radius, MAX_BLUR_RADIUS
+            )),
+            Filter::Brightness(delta) if delta.abs() > MAX_BRIGHTNESS_DELTA => Err(format!(
+                "brightness {} is outside -{max}-{max}",
+                delta,
+                max = MAX_BRIGHTNESS_DELTA
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    /// Format as `filter`'s arguments, e.g. `blur 3`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::Grayscale => write!(f, "grayscale"),
+            Filter::Invert => write!(f, "invert"),
+            Filter::Blur(radius) => write!(f, "blur {}", radius),
+            Filter::Brightness(delta) => write!(f, "brightness {}", delta),
+        }
+    }
+}
+
+/// Apply `filter` to the pixels of `buffer` from (left, top) to (right,
+/// bottom), inclusive
+pub fn apply_filter(
+    buffer: &mut [u32],
+    filter: &Filter,
+    (left, top, right, bottom): (usize, usize, usize, usize),
+) {
+    let width = window_width();
+    let rows = (top..=bottom).map(|y| y * width + left..=y * width + right);
+    match *filter {
+        Filter::Grayscale => {
+            for row in rows {
+                buffer[row]
+                    .iter_mut()
+                    .for_each(|pixel| *pixel = grayscale(*pixel));
+            }
+        }
+        Filter::Invert => {
+            for row in rows {
+                buffer[row].iter_mut().for_each(|pixel| *pixel ^= 0xFFFFFF);
+            }
+        }
+        Filter::Brightness(delta) => {
+            for row in rows {
+                let brighten =
+                    |pixel: &mut u32| *pixel = map_channels(*pixel, |c| c as i32 + delta);
+                buffer[row].iter_mut().for_each(brighten);
+            }
+        }
+        Filter::Blur(radius) => {
+            for row in rows {
+                box_blur(&mut buffer[row], 1, radius);
+            }
+            let (columns, stride) = (right - left + 1, width);
+            let area = &mut buffer[top * width + left..=bottom * width + right];
+            for column in 0..columns {
+                box_blur(&mut area[column..], stride, radius);
+            }
+        }
+    }
+}
+
+/// A pixel's luma as a gray
+fn grayscale(pixel: u32) -> u32 {
+    let channel = |shift: u32| (pixel >> shift) & 0xFF;
+    let luma = (299 * channel(16) + 587 * channel(8) + 114 * channel(0) + 500) / 1000;
+    (pixel & 0xFF00_0000) | luma << 16 | luma << 8 | luma
+}
+
+/// A pixel with `f` applied to its red, green, and blue, held to 0-255
+fn map_channels(pixel: u32, f: impl Fn(u32) -> i32) -> u32 {
+    [16, 8, 0]
+        .into_iter()
+        .fold(pixel & 0xFF00_0000, |out, shift| {
+            out | (f((pixel >> shift) & 0xFF).clamp(0, 255) as u32) << shift
+        })
+}
+
+/// Blur the pixels `line[0]`, `line[stride]`, `line[2 * stride]`, ... (as
+/// many as fit) along their line: each becomes the average of those within
+/// `radius` of it
+fn box_blur(line: &mut [u32], stride: usize, radius: usize) {
+    let pixels: Vec<u32> = line.iter().step_by(stride).copied().collect();
+    let len = pixels.len();
+    // Per-channel sums of the window, as it slides along
+    let mut sums = [0i64; 3];
+    let add = |sums: &mut [i64; 3], pixel: u32, sign: i64| {
+        for (sum, shift) in sums.iter_mut().zip([16, 8, 0]) {
+            *sum += sign * ((pixel >> shift) & 0xFF) as i64;
+        }
+    };
+    for &pixel in &pixels[..radius.min(len)] {
+        add(&mut sums, pixel, 1);
+    }
+    for i in 0..len {
+        if i + radius < len {
+            add(&mut sums, pixels[i + radius], 1);
+        }
+        if i > radius {
+            add(&mut sums, pixels[i - radius - 1], -1);
+        }
+        let count = ((i + radius).min(len - 1) + 1 - i.saturating_sub(radius)) as i64;
+        let average = |sum: i64| ((sum + count / 2) / count) as u32;
+        line[i * stride] = (pixels[i] & 0xFF00_0000)
+            | average(sums[0]) << 16
+            | average(sums[1]) << 8
+            | average(sums[2]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Keyboard routing.
+//!
+//! This module handles:
+//! - `KeyInput`, a key press as the run loop reports it, independent of the
+//!   windowing library
+//! - `route_key`, which gives each key to the focused widget first: Tab and
+//!   Shift+Tab move focus between widgets, Escape drops it, and the focused
+//!   widget uses the keys it understands
+//!
+//! Keys no widget uses come back as `KeyRoute::Unclaimed`; the run loop
+//! checks them against global shortcuts and passes the rest to subscribers
+//! as `key <name>` events.
+
+use crate::events::Event;
+use crate::objects::{ObjectKind, Objects};
+use crate::vars::Variables;
+
+/// A key press to route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyInput {
+    /// A typed character (space included)
+    Char(char),
+    Tab,
+    /// Shift+Tab
+    BackTab,
+    Enter,
+    Backspace,
+    Escape,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+}
+
+impl KeyInput {
+    /// Name sent to subscribers in a `key <name>` event
+    pub fn name(&self) -> String {
+        match self {
+            KeyInput::Char(' ') => "space".to_string(),
+            KeyInput::Char(c) => c.to_string(),
+            KeyInput::Tab => "tab".to_string(),
+            KeyInput::BackTab => "shift-tab".to_string(),
+            KeyInput::Enter => "enter".to_string(),
+            KeyInput::Backspace => "backspace".to_string(),
+            KeyInput::Escape => "escape".to_string(),
+            KeyInput::Left => "left".to_string(),
+            KeyInput::Right => "right".to_string(),
+            KeyInput::Up => "up".to_string(),
+            KeyInput::Down => "down".to_string(),
+            KeyInput::Home => "home".to_string(),
+            KeyInput::End => "end".to_string(),
+        }
+    }
+}
+
+/// Where a key press went
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyRoute {
+    /// Focus or the focused widget used the key (redraw); the event, if
+    /// any, goes to subscribers
+    Consumed(Option<Event>),
+    /// Nothing focused wanted the key
+    Unclaimed,
+}
+
+/// Route one key press to the focused widget
+///
+/// - Tab / Shift+Tab cycle focus through widgets (unclaimed if there are none)
+/// - Escape takes focus away
+/// - Text inputs take typed characters, Backspace, and Enter (submit)
+/// - Buttons, checkboxes, and toggles activate on Enter or Space
+/// - Sliders step with the arrow keys and jump to their ends with Home/End
+pub fn route_key(objects: &mut Objects, vars: &mut Variables, key: KeyInput) -> KeyRoute {
+    if let KeyInput::Tab | KeyInput::BackTab = key {
+        return match objects.focus_next(key == KeyInput::BackTab) {
+            Some(_) => KeyRoute::Consumed(None),
+            None => KeyRoute::Unclaimed,
+        };
+    }
+    let Some(id) = objects.focused() else {
+        return KeyRoute::Unclaimed;
+    };
+    if key == KeyInput::Escape {
+        objects.set_focus(None);
+        return KeyRoute::Consumed(None);
+    }
+    let Some(object) = objects.get(id) else {
+        return KeyRoute::Unclaimed;
+    };
+    match (&object.kind, key) {
+        (ObjectKind::Input { .. }, KeyInput::Char(c)) => {
+            objects.type_char(c, vars);
+            KeyRoute::Consumed(None)
+        }
+        (ObjectKind::Input { .. }, KeyInput::Backspace) => {
+            objects.backspace(vars);
+            KeyRoute::Consumed(None)
+        }
+        (ObjectKind::Input { .. }, KeyInput::Enter) => KeyRoute::Consumed(objects.submit(vars)),
+        (
+            ObjectKind::Button { .. } | ObjectKind::Check { .. },
+            KeyInput::Enter | KeyInput::Char(' '),
+        ) => KeyRoute::Consumed(objects.activate(vars)),
+        (&ObjectKind::Slider { min, max, .. }, _) => {
+            // Wide enough to reach either end from anywhere in range
+            let span = i128::from(max) - i128::from(min);
+            let delta = match key {
+                KeyInput::Left | KeyInput::Down => -1,
+                KeyInput::Right | KeyInput::Up => 1,
+                KeyInput::Home => -span,
+                KeyInput::End => span,
+                _ => return KeyRoute::Unclaimed,
+            };
+            KeyRoute::Consumed(objects.step_slider(id, delta, vars))
+        }
+        _ => KeyRoute::Unclaimed,
+    }
+}
@@ -0,0 +1,482 @@
+//! Deterministic command generation for property tests and fuzzers.
+//!
+//! This module handles:
+//! - A seeded pseudo-random `CommandGenerator` producing valid commands and raw lines
+//! - `apply_commands` for replaying a command list onto a fresh canvas
+//!
+//! Everything here is deterministic: the same seed always yields the same
+//! commands, and the same seed and commands always yield the same canvas.
+
+use crate::blend::BlendMode;
+use crate::brush::BrushShape;
+use crate::command::{AliasCommand, AttributedPoint, Command, COMMAND_VERBS};
+use crate::config::{MAX_FPS, MIN_FPS};
+use crate::dash::StrokeStyle;
+use crate::filters::Filter;
+use crate::gradient::{Gradient, GradientShape};
+use crate::icc::ColorProfile;
+use crate::legend::LegendEntry;
+use crate::panes::PaneScale;
+use crate::shadow::{Shadow, MAX_SHADOW_BLUR, MAX_SHADOW_OFFSET};
+use crate::transform::{CanvasTransform, FlipAxis, Resample};
+use crate::{
+    window_height, window_width, AppState, Canvas, COLOR_PALETTE, MAX_BRUSH_SIZE, MIN_BRUSH_SIZE,
+};
+
+/// Words that aren't verbs, mixed in so unknown commands are generated too
+const NON_VERBS: [&str; 2] = ["none", ""];
+
+/// First words used when generating raw lines for parser fuzzing: every
+/// protocol verb, then `NON_VERBS`
+const VERBS: [&str; COMMAND_VERBS.len() + NON_VERBS.len()] = {
+    let mut verbs = [""; COMMAND_VERBS.len() + NON_VERBS.len()];
+    let mut i = 0;
+    while i < COMMAND_VERBS.len() {
+        verbs[i] = COMMAND_VERBS[i];
+        i += 1;
+    }
+    while i < verbs.len() {
+        verbs[i] = NON_VERBS[i - COMMAND_VERBS.len()];
+        i += 1;
+    }
+    verbs
+};
+
+/// Coordinates at the edges of `usize` arithmetic, mixed in to shake out overflows
+const EXTREMES: [usize; 5] = [
+    usize::MAX,
+    usize::MAX - 1,
+    usize::MAX / 2,
+    isize::MAX as usize + 1,
+    1 << 32,
+];
+
+/// Argument fragments mixed into raw lines for parser fuzzing
+const FRAGMENTS: [&str; 24] = [
+    "0",
+    "13",
+    "14",
+    "-1",
+    "none",
+    ",",
+    ":",
+    "1,2",
+    "3,4:5",
+    "6,7:8:9",
+    "abc",
+    "99999999999999999999",
+    "begin",
+    "end",
+    "\"label\"",
+    "on",
+    "off",
+    "#808080",
+    "1,2:#FF8000",
+    "text",
+    "settext",
+    "set",
+    "{cpu}",
+    "blink",
+];
+
+/// Labels used when generating `group begin` commands
+const GROUP_LABELS: [&str; 3] = ["", "chart", "bar chart 2"];
+
+/// Seeded pseudo-random generator of protocol commands (xorshift64*)
+#[derive(Debug, Clone)]
+pub struct CommandGenerator {
+    state: u64,
+}
+
+impl CommandGenerator {
+    /// Create a generator from a seed (any value, including 0, is valid)
+    pub fn new(seed: u64) -> Self {
+        // Run the seed through a splitmix64 step so nearby seeds diverge and
+        // the xorshift state is never zero
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        CommandGenerator {
+            state: if z == 0 { 0x2545_F491_4F6C_DD1D } else { z },
+        }
+    }
+
+    /// Next raw 64-bit value
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Next value in `0..n` (returns 0 when `n` is 0)
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next_u64() % n as u64) as usize
+        }
+    }
+
+    /// A coordinate that usually lands on the canvas but can fall far outside it
+    fn coord(&mut self, limit: usize) -> usize {
+        if self.below(16) == 0 {
+            EXTREMES[self.below(EXTREMES.len())]
+        } else {
+            self.below(limit * 2)
+        }
+    }
+
+    fn point(&mut self) -> (usize, usize) {
+        (self.coord(window_width()), self.coord(window_height()))
+    }
+
+    /// An angle in half degrees, up to a turn either way
+    fn angle(&mut self) -> f64 {
+        self.below(1441) as f64 / 2.0 - 360.0
+    }
+
+    /// A data value for `scale` and `plot`: usually modest, sometimes huge,
+    /// tiny, or infinite (never NaN, which wouldn't compare equal to itself)
+    fn value(&mut self) -> f64 {
+        match self.below(16) {
+            0 => [f64::MAX, f64::INFINITY, f64::NEG_INFINITY, 1e-300][self.below(4)],
+            _ => self.angle(),
+        }
+    }
+
+    /// A palette color most of the time, otherwise any RGB color or none
+    fn color(&mut self) -> Option<u32> {
+        match self.below(8) {
+            0 => None,
+            1 => {
+                // Half of them translucent
+                let bits = self.next_u64();
+                Some(if bits >> 63 == 1 {
+                    bits as u32
+                } else {
+                    bits as u32 & 0xFFFFFF
+                })
+            }
+            _ => Some(COLOR_PALETTE[self.below(COLOR_PALETTE.len())]),
+        }
+    }
+
+    fn shadow(&mut self) -> Option<Shadow> {
+        if self.below(4) == 0 {
+            return None;
+        }
+        let offset = |rng: &mut Self| {
+            rng.below(2 * MAX_SHADOW_OFFSET as usize + 1) as isize - MAX_SHADOW_OFFSET
+        };
+        Some(Shadow {
+            dx: offset(self),
+            dy: offset(self),
+            color: self.next_u64() as u32 & 0xFFFFFF,
+            blur: self.below(MAX_SHADOW_BLUR + 1),
+        })
+    }
+
+    fn brush_size(&mut self) -> usize {
+        MIN_BRUSH_SIZE + self.below(MAX_BRUSH_SIZE - MIN_BRUSH_SIZE + 1)
+    }
+
+    fn attributed_points(&mut self, min: usize) -> Vec<AttributedPoint> {
+        let count = min + self.below(8);
+        (0..count)
+            .map(|_| {
+                let (x, y) = self.point();
+                // A size override can only be expressed alongside a color override
+                let color = if self.below(2) == 0 {
+                    self.color()
+                } else {
+                    None
+                };
+                let size = if color.is_some() && self.below(2) == 0 {
+                    Some(self.brush_size())
+                } else {
+                    None
+                };
+                AttributedPoint { x, y, color, size }
+            })
+            .collect()
+    }
+
+    /// Generate the next command
+    ///
+    /// Never produces `Snapshot`, `Capture`, or `save`, since they write to the
+    /// filesystem, `load`, since it reads from it, `copy` or `paste`, since
+    /// they reach the system clipboard, or `canvas crop`/`canvas resize`,
+    /// since `apply_commands` replays onto a canvas of one size.
+    pub fn next_command(&mut self) -> Command {
+        match self.below(44) {
+            0 => Command::Color(self.below(COLOR_PALETTE.len())),
+            1 => Command::Edge(self.color()),
+            2 => Command::Fill(self.color()),
+            3 => Command::Size(self.brush_size()),
+            4 => {
+                let (x1, y1) = self.point();
+                let (x2, y2) = self.point();
+                Command::Stroke { x1, y1, x2, y2 }
+            }
+            5 => {
+                let (x, y) = self.point();
+                Command::Dot { x, y }
+            }
+            6 => Command::Clear,
+            7 => match self.below(2) {
+                0 => Command::State,
+                _ => Command::Layout,
+            },
+            8 => {
+                let (x1, y1) = self.point();
+                let (x2, y2) = self.point();
+                Command::Line { x1, y1, x2, y2 }
+            }
+            9 => {
+                let (x, y) = self.point();
+                let size = self.coord(window_width() / 2);
+                Command::Square { x, y, size }
+            }
+            10 => {
+                let (x1, y1) = self.point();
+                let (x2, y2) = self.point();
+                Command::Rect { x1, y1, x2, y2 }
+            }
+            11 => {
+                let (x, y) = self.point();
+                let r = self.coord(window_width() / 4);
+                Command::Circle { x, y, r }
+            }
+            12 => {
+                let (x, y) = self.point();
+                let rx = self.coord(window_width() / 4);
+                let ry = self.coord(window_height() / 4);
+                Command::Oval { x, y, rx, ry }
+            }
+            13 => {
+                let (x1, y1) = self.point();
+                let (x2, y2) = self.point();
+                Command::Triangle { x1, y1, x2, y2 }
+            }
+            14 => Command::Polyline(self.attributed_points(2)),
+            15 => Command::Points(self.attributed_points(1)),
+            16 => Command::GroupBegin(GROUP_LABELS[self.below(GROUP_LABELS.len())].to_string()),
+            17 => Command::GroupEnd,
+            18 => Command::Shadow(self.shadow()),
+            19 => {
+                let (x, y) = self.point();
+                Command::Bucket { x, y }
+            }
+            20 => Command::Polygon((0..3 + self.below(4)).map(|_| self.point()).collect()),
+            21 => {
+                let (x, y) = self.point();
+                let r = self.coord(window_width() / 4);
+                let (start, end) = (self.angle(), self.angle());
+                Command::Arc {
+                    x,
+                    y,
+                    r,
+                    start,
+                    end,
+                }
+            }
+            22 => {
+                let (x, y) = self.point();
+                let r = self.coord(window_width() / 4);
+                let (start, end) = (self.angle(), self.angle());
+                Command::Pie {
+                    x,
+                    y,
+                    r,
+                    start,
+                    end,
+                }
+            }
+            23 => Command::Bezier((0..3 + self.below(2)).map(|_| self.point()).collect()),
+            24 => Command::Scale((self.below(4) > 0).then(|| PaneScale {
+                x0: self.value(),
+                y0: self.value(),
+                x1: self.value(),
+                y1: self.value(),
+                log_x: self.below(4) == 0,
+                log_y: self.below(4) == 0,
+                axes: self.below(2) == 0,
+            })),
+            25 => Command::Plot(
+                (0..1 + self.below(8))
+                    .map(|_| (self.value(), self.value()))
+                    .collect(),
+            ),
+            26 => {
+                let (x, y) = self.point();
+                let entries = (0..1 + self.below(4))
+                    .map(|_| LegendEntry {
+                        label: ["", "sales", "a b", "2024 (est.)"][self.below(4)].to_string(),
+                        color: self.color().unwrap_or(COLOR_PALETTE[0]),
+                    })
+                    .collect();
+                Command::Legend { x, y, entries }
+            }
+            27 => {
+                let (x1, y1) = self.point();
+                let (x2, y2) = self.point();
+                let r = self.coord(window_width() / 4);
+                Command::RoundedRect { x1, y1, x2, y2, r }
+            }
+            28 => {
+                let (x1, y1) = self.point();
+                let (x2, y2) = self.point();
+                let head = (self.below(2) == 0).then(|| self.coord(40).max(1));
+                Command::Arrow {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    head,
+                    both: self.below(2) == 0,
+                }
+            }
+            29 => {
+                let (x, y) = self.point();
+                Command::Ngon {
+                    x,
+                    y,
+                    r: self.coord(window_width() / 4),
+                    sides: 3 + self.below(10),
+                    rotation: self.angle(),
+                }
+            }
+            30 => {
+                let (x, y) = self.point();
+                Command::Star {
+                    x,
+                    y,
+                    outer: self.coord(window_width() / 4),
+                    inner: self.coord(window_width() / 8),
+                    points: 3 + self.below(10),
+                }
+            }
+            31 => Command::Antialias(self.below(2) == 0),
+            32 => Command::TsPlot {
+                pane: 1 + self.below(4),
+                value: self.value(),
+            },
+            33 => Command::Blend(BlendMode::ALL[self.below(BlendMode::ALL.len())]),
+            34 => Command::Hold {
+                pane: 1 + self.below(4),
+                on: self.below(2) == 0,
+            },
+            35 => Command::Flip(1 + self.below(4)),
+            36 => Command::FillGradient((self.below(3) > 0).then(|| Gradient {
+                shape: match self.below(2) {
+                    0 => GradientShape::Radial,
+                    _ => GradientShape::Linear {
+                        angle: self.below(360) as f64,
+                    },
+                },
+                from: COLOR_PALETTE[self.below(COLOR_PALETTE.len())],
+                to: COLOR_PALETTE[self.below(COLOR_PALETTE.len())],
+            })),
+            37 => Command::StrokeStyle({
+                let (dash, gap) = (1 + self.below(20), 1 + self.below(20));
+                match self.below(3) {
+                    0 => StrokeStyle::Solid,
+                    1 => StrokeStyle::Dashed { dash, gap },
+                    _ => StrokeStyle::Dotted { dash, gap },
+                }
+            }),
+            38 => Command::BrushShape(BrushShape::ALL[self.below(BrushShape::ALL.len())]),
+            39 => {
+                let name = ["dots", "cross", "box"][self.below(3)].to_string();
+                Command::Alias(match self.below(3) {
+                    0 => {
+                        let ((x1, y1), (x2, y2)) = (self.point(), self.point());
+                        let body = format!("dot {},{}; line {},{} {},{}", x1, y1, x1, y1, x2, y2);
+                        AliasCommand::Define { name, body }
+                    }
+                    1 => AliasCommand::Get(name),
+                    _ => AliasCommand::Remove(name),
+                })
+            }
+            40 => Command::Canvas(match self.below(4) {
+                0 => CanvasTransform::Flip(FlipAxis::Horizontal),
+                1 => CanvasTransform::Flip(FlipAxis::Vertical),
+                2 => CanvasTransform::Rotate([90, 180, 270][self.below(3)]),
+                _ => CanvasTransform::Scale {
+                    width: 1 + self.below(window_width()),
+                    height: 1 + self.below(window_height()),
+                    resample: [Resample::Nearest, Resample::Bilinear][self.below(2)],
+                },
+            }),
+            41 => Command::Filter {
+                filter: match self.below(4) {
+                    0 => Filter::Grayscale,
+                    1 => Filter::Invert,
+                    2 => Filter::Blur(1 + self.below(5)),
+                    _ => Filter::Brightness(self.below(101) as i32 - 50),
+                },
+                region: match self.below(2) {
+                    0 => None,
+                    _ => {
+                        let ((x1, y1), (x2, y2)) = (self.point(), self.point());
+                        Some((x1, y1, x2, y2))
+                    }
+                },
+            },
+            42 => Command::ColorProfile(match self.below(4) {
+                0 => None,
+                n => Some(ColorProfile::ALL[n - 1]),
+            }),
+            _ => Command::Fps(MIN_FPS + self.below((MAX_FPS - MIN_FPS + 1) as usize) as u32),
+        }
+    }
+
+    /// Generate a raw protocol line that may or may not be a valid command
+    pub fn next_line(&mut self) -> String {
+        if self.below(2) == 0 {
+            return self.next_command().to_string();
+        }
+        let mut line = VERBS[self.below(VERBS.len())].to_string();
+        for _ in 0..self.below(5) {
+            line.push(' ');
+            line.push_str(FRAGMENTS[self.below(FRAGMENTS.len())]);
+            if self.below(3) == 0 {
+                line.push_str(FRAGMENTS[self.below(FRAGMENTS.len())]);
+            }
+        }
+        line
+    }
+}
+
+impl Iterator for CommandGenerator {
+    type Item = Command;
+
+    fn next(&mut self) -> Option<Command> {
+        Some(self.next_command())
+    }
+}
+
+/// Apply a list of commands to a fresh canvas and return the result
+///
+/// The seed picks the starting edge color, fill color, and brush size, so the
+/// same command list can be exercised under different tool states. A
+/// `Snapshot` in the list still writes `canvas.png`, as it would at runtime.
+pub fn apply_commands(seed: u64, commands: &[Command]) -> Canvas {
+    let mut rng = CommandGenerator::new(seed);
+    let mut state = AppState {
+        edge_color: rng.color(),
+        fill_color: rng.color(),
+        brush_size: rng.brush_size(),
+        ..AppState::new()
+    };
+
+    for cmd in commands {
+        state.execute(cmd);
+    }
+
+    state.canvas
+}
@@ -0,0 +1,204 @@
+//! Animated GIF recording of the canvas.
+//!
+//! This module handles:
+//! - `GifRecorder`, taking canvas frames at a fixed interval or whenever the
+//!   canvas changes, and encoding them into an animated GIF
+//! - `apply_gif_command`, starting and stopping a recording for `record gif`
+//!
+//! GIF encoding quantizes every frame to a 256-color palette, which is far too
+//! slow for the UI thread, so frames are sent to a background encoder thread
+//! that appends them to the file as they arrive. Each frame is shown until the
+//! next one was taken, so a change-driven recording plays back at the pace the
+//! drawing happened.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::mpsc::{self, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+use crate::command::{failed, reply, CommandResult, GifCommand};
+use crate::{canvas_bottom, window_width, CANVAS_TOP};
+
+/// Shortest interval between timed frames, in milliseconds (browsers slow
+/// down GIFs with shorter frame delays)
+pub const MIN_GIF_INTERVAL_MS: u64 = 20;
+/// Longest interval between timed frames, in milliseconds
+pub const MAX_GIF_INTERVAL_MS: u64 = 60_000;
+/// Most frames in one recording; later frames are dropped
+pub const MAX_GIF_FRAMES: u64 = 3000;
+/// How long the final frame is shown, in milliseconds
+pub const LAST_GIF_FRAME_MS: u64 = 1000;
+/// Quantization speed passed to the encoder (1 = best quality, 30 = fastest)
+const GIF_ENCODER_SPEED: i32 = 10;
+
+/// Whether `ms` is a usable interval between timed frames
+pub fn is_valid_gif_interval(ms: u64) -> bool {
+    (MIN_GIF_INTERVAL_MS..=MAX_GIF_INTERVAL_MS).contains(&ms)
+}
+
+/// A frame on its way to the encoder, and when it was taken
+struct PendingFrame {
+    image: RgbaImage,
+    taken: Instant,
+}
+
+/// An animated GIF being recorded
+pub struct GifRecorder {
+    path: String,
+    /// Time between frames (None = a frame whenever the canvas changes)
+    interval: Option<Duration>,
+    sender: Option<Sender<PendingFrame>>,
+    encoder: Option<JoinHandle<Result<(), String>>>,
+    last_taken: Option<Instant>,
+    /// Canvas pixels of the last frame taken, to spot changes
+    last_pixels: Vec<u32>,
+    frames: u64,
+}
+
+impl GifRecorder {
+    /// Create (or truncate) a GIF at `path` and start its encoder thread
+    pub fn start(path: &str, interval: Option<Duration>) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let (sender, receiver) = mpsc::channel::<PendingFrame>();
+        let encoder = std::thread::spawn(move || {
+            let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), GIF_ENCODER_SPEED);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| e.to_string())?;
+            // A frame's delay is only known once the next one arrives
+            let mut previous: Option<PendingFrame> = None;
+            for frame in receiver {
+                if let Some(done) = previous.replace(frame) {
+                    let shown = previous.as_ref().unwrap().taken - done.taken;
+                    encode(&mut encoder, done.image, shown)?;
+                }
+            }
+            if let Some(last) = previous {
+                encode(
+                    &mut encoder,
+                    last.image,
+                    Duration::from_millis(LAST_GIF_FRAME_MS),
+                )?;
+            }
+            Ok(())
+        });
+        Ok(GifRecorder {
+            path: path.to_string(),
+            interval,
+            sender: Some(sender),
+            encoder: Some(encoder),
+            last_taken: None,
+            last_pixels: Vec::new(),
+            frames: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of frames taken so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Offer the current frame (a window-sized buffer) at `now`; it is taken
+    /// if the interval has passed, or (without an interval) if the canvas
+    /// changed since the last frame taken
+    ///
+    /// Returns whether the frame was taken.
+    pub fn offer(&mut self, buffer: &[u32], now: Instant) -> bool {
+        if self.frames >= MAX_GIF_FRAMES {
+            return false;
+        }
+        let canvas = &buffer[CANVAS_TOP * window_width()..canvas_bottom() * window_width()];
+        let due = match (self.interval, self.last_taken) {
+            (_, None) => true,
+            (Some(interval), Some(last)) => now.saturating_duration_since(last) >= interval,
+            (None, Some(_)) => canvas != self.last_pixels.as_slice(),
+        };
+        if !due {
+            return false;
+        }
+        let Some(sender) = &self.sender else {
+            return false;
+        };
+        let image = canvas_rgba(canvas);
+        if sender.send(PendingFrame { image, taken: now }).is_err() {
+            // The encoder failed; `finish` reports why
+            self.sender = None;
+            return false;
+        }
+        if self.interval.is_none() {
+            self.last_pixels.clear();
+            self.last_pixels.extend_from_slice(canvas);
+        }
+        self.last_taken = Some(now);
+        self.frames += 1;
+        true
+    }
+
+    /// Stop taking frames, wait for the encoder to write the rest, and return
+    /// how many frames the GIF holds
+    pub fn finish(mut self) -> Result<u64, String> {
+        self.sender = None;
+        match self.encoder.take().map(JoinHandle::join) {
+            Some(Ok(Ok(()))) | None => Ok(self.frames),
+            Some(Ok(Err(e))) => Err(e),
+            Some(Err(_)) => Err("gif encoder stopped unexpectedly".to_string()),
+        }
+    }
+}
+
+/// Encode one frame shown for `shown`
+fn encode(
+    encoder: &mut GifEncoder<BufWriter<File>>,
+    image: RgbaImage,
+    shown: Duration,
+) -> Result<(), String> {
+    let delay = Delay::from_saturating_duration(shown);
+    encoder
+        .encode_frame(Frame::from_parts(image, 0, 0, delay))
+        .map_err(|e| e.to_string())
+}
+
+/// The canvas rows of a window buffer as an opaque RGBA image
+fn canvas_rgba(canvas: &[u32]) -> RgbaImage {
+    let width = window_width();
+    let height = canvas.len() / width;
+    let bytes = canvas
+        .iter()
+        .flat_map(|&c| [(c >> 16) as u8, (c >> 8) as u8, c as u8, 0xFF])
+        .collect();
+    RgbaImage::from_raw(width as u32, height as u32, bytes).expect("buffer matches canvas size")
+}
+
+/// Apply a `record gif` command: start (restarting any active recording) or
+/// stop, returning the protocol response
+pub fn apply_gif_command(recorder: &mut Option<GifRecorder>, cmd: &GifCommand) -> CommandResult {
+    let finished = recorder.take().map(|active| {
+        let path = active.path().to_string();
+        (path, active.finish())
+    });
+    match cmd {
+        GifCommand::Start { path, interval_ms } => {
+            let interval = interval_ms.map(Duration::from_millis);
+            match GifRecorder::start(path, interval) {
+                Ok(started) => {
+                    *recorder = Some(started);
+                    reply(format!("recording gif {}", path))
+                }
+                Err(e) => failed(e),
+            }
+        }
+        GifCommand::Stop => match finished {
+            Some((path, Ok(frames))) => reply(format!("recorded {} frames to {}", frames, path)),
+            Some((_, Err(e))) => failed(e),
+            None => failed("not recording a gif"),
+        },
+    }
+}
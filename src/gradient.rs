@@ -0,0 +1,184 @@
+//! Gradient fills.
+//!
+//! This module handles:
+//! - The `Gradient` set by `fill-gradient linear|radial <from> <to> [angle]`
+//! - `Fill`, what a shape's interior is filled with: one color or a gradient
+//! - `Paint`, a fill laid over the box of the shape being filled, which gives
+//!   each pixel its own color
+//!
+//! A gradient has no position of its own: each shape spreads it across its
+//! own bounding box, so the same setting runs from edge to edge of a small
+//! square and of a rectangle covering the whole canvas alike.
+
+use crate::blend::replaces;
+use crate::drawing::sin_cos_degrees;
+
+/// How a gradient's colors spread across a shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientShape {
+    /// Along a direction `angle` degrees clockwise from pointing right, from
+    /// the shape's edge furthest back to the one furthest ahead
+    Linear { angle: f64 },
+    /// Out from the shape's center to the ellipse its box encloses, and
+    /// beyond it into the corners
+    Radial,
+}
+
+/// Two colors blended across a shape's interior
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    /// Color where the gradient starts (`0xRRGGBB`, maybe translucent)
+    pub from: u32,
+    /// Color where it ends
+    pub to: u32,
+}
+
+impl Gradient {
+    /// Check the angle of a linear gradient is a number
+    pub fn check(&self) -> Result<(), String> {
+        match self.shape {
+            GradientShape::Linear { angle } if !angle.is_finite() => {
+                Err("gradient angle must be a number".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The color `t` (0.0..=1.0) of the way from `from` to `to`, every byte
+    /// (transparency too) mixed on its own
+    pub fn color_at(&self, t: f64) -> u32 {
+        let t = t.clamp(0.0, 1.0);
+        [24, 16, 8, 0].into_iter().fold(0, |color, shift| {
+            let (a, b) = ((self.from >> shift) & 0xFF, (self.to >> shift) & 0xFF);
+            let mixed = a as f64 + (b as f64 - a as f64) * t;
+            color | (mixed.round() as u32) << shift
+        })
+    }
+}
+
+/// What a shape's interior is filled with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+    Color(u32),
+    Gradient(Gradient),
+}
+
+impl From<u32> for Fill {
+    fn from(color: u32) -> Self {
+        Fill::Color(color)
+    }
+}
+
+impl From<Gradient> for Fill {
+    fn from(gradient: Gradient) -> Self {
+        Fill::Gradient(gradient)
+    }
+}
+
+impl Fill {
+    /// The fill laid over the box from (left, top) to (right, bottom),
+    /// inclusive
+    pub fn over(self, left: usize, top: usize, right: usize, bottom: usize) -> Paint {
+        let gradient = match self {
+            Fill::Color(color) => return Paint::Solid(color),
+            Fill::Gradient(gradient) => gradient,
+        };
+        let [left, top, right, bottom] = [left, top, right, bottom].map(|v| v as f64);
+        let placed = match gradient.shape {
+            GradientShape::Linear { angle } => {
+                let (sin, cos) = sin_cos_degrees(angle % 360.0);
+                let along = |x: f64, y: f64| x * cos + y * sin;
+                let ends = [(left, top), (right, top), (left, bottom), (right, bottom)]
+                    .map(|(x, y)| along(x, y));
+                let start = ends.into_iter().fold(f64::INFINITY, f64::min);
+                let end = ends.into_iter().fold(f64::NEG_INFINITY, f64::max);
+                Placed::Linear {
+                    cos,
+                    sin,
+                    start,
+                    length: end - start,
+                }
+            }
+            GradientShape::Radial => Placed::Radial {
+                cx: (left + right) / 2.0,
+                cy: (top + bottom) / 2.0,
+                rx: (right - left) / 2.0,
+                ry: (bottom - top) / 2.0,
+            },
+        };
+        Paint::Gradient(gradient, placed)
+    }
+}
+
+/// A gradient's geometry over one shape's box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placed {
+    /// Positions along (cos, sin), `start` at the back edge and `length` to
+    /// the front one
+    Linear {
+        cos: f64,
+        sin: f64,
+        start: f64,
+        length: f64,
+    },
+    /// The enclosed ellipse's center and radii
+    Radial { cx: f64, cy: f64, rx: f64, ry: f64 },
+}
+
+/// A fill placed over a shape: the color of each of its pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Paint {
+    Solid(u32),
+    Gradient(Gradient, Placed),
+}
+
+impl From<u32> for Paint {
+    fn from(color: u32) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl Paint {
+    /// The color of pixel (x, y)
+    pub fn at(&self, x: usize, y: usize) -> u32 {
+        let (gradient, placed) = match self {
+            Paint::Solid(color) => return *color,
+            Paint::Gradient(gradient, placed) => (gradient, placed),
+        };
+        let (x, y) = (x as f64, y as f64);
+        let t = match *placed {
+            Placed::Linear {
+                cos,
+                sin,
+                start,
+                length,
+            } if length > 0.0 => (x * cos + y * sin - start) / length,
+            Placed::Linear { .. } => 0.0,
+            Placed::Radial { cx, cy, rx, ry } => {
+                // A box one pixel thin is a line out from the center
+                let (dx, dy) = ((x - cx) / rx.max(0.5), (y - cy) / ry.max(0.5));
+                (dx * dx + dy * dy).sqrt()
+            }
+        };
+        gradient.color_at(t)
+    }
+
+    /// One color standing for the whole paint: the solid color, or where
+    /// the gradient starts
+    pub fn color(&self) -> u32 {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::Gradient(gradient, _) => gradient.from,
+        }
+    }
+
+    /// Whether drawing this just replaces canvas pixels (see `replaces`);
+    /// never for a gradient, whose pixels differ
+    pub fn replaces(&self) -> bool {
+        match self {
+            Paint::Solid(color) => replaces(*color),
+            Paint::Gradient(..) => false,
+        }
+    }
+}
@@ -0,0 +1,512 @@
+//! Labeled, undoable history of canvas operations.
+//!
+//! This module handles:
+//! - Recording each mutating command or mouse action as one history entry
+//! - `group begin`/`group end` markers that fold a burst of commands into one labeled entry
+//! - Undo and redo, by restoring the pixels an entry changed
+//! - Writing the entries and redo stack as bytes for a project file, and
+//!   reading them back
+//! - The history panel (F4): the latest entries listed down the left of the
+//!   canvas, where clicking one undoes back to it
+//!
+//! History keeps a shadow copy of the canvas area as of the last recorded
+//! entry. Recording diffs the live buffer against it and stores only the
+//! bounding rectangle of changed pixels (before and after), so small strokes
+//! stay cheap. Only the canvas area (`CANVAS_TOP..canvas_bottom()`) is tracked;
+//! toolbar redraws never show up in a diff.
+//!
+//! Like the stats overlay, the panel is drawn onto the presented frame, never
+//! into the canvas, so it doesn't show up in snapshots or in the history
+//! itself.
+
+use crate::font::{draw_text, GLYPH_ADVANCE, GLYPH_HEIGHT};
+use crate::stats::STATS_BACKGROUND;
+use crate::{canvas_bottom, window_width, BLACK, CANVAS_TOP, DARK_GRAY, GRAY, WHITE};
+
+/// Most entries kept; the oldest are dropped beyond this
+pub const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// Most pixel data (in bytes, before and after combined) kept for undo
+pub const MAX_HISTORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Rows the history panel lists (the latest entries, oldest at the top)
+pub const HISTORY_PANEL_ROWS: usize = 16;
+
+/// Width of the history panel in pixels
+pub const HISTORY_PANEL_WIDTH: usize = 150;
+
+const PANEL_PADDING: usize = 4;
+const PANEL_ROW_HEIGHT: usize = GLYPH_HEIGHT + 5;
+
+/// One step in the history: a single operation, or a closed group of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub label: String,
+    /// Number of operations folded into this entry
+    pub operations: usize,
+}
+
+/// The pixels one entry changed, as a rectangle in window coordinates
+#[derive(Debug, Clone, PartialEq)]
+struct PixelDiff {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    before: Vec<u32>,
+    after: Vec<u32>,
+}
+
+impl PixelDiff {
+    fn bytes(&self) -> usize {
+        (self.before.len() + self.after.len()) * std::mem::size_of::<u32>()
+    }
+
+    /// Whether the rectangle lies in the canvas area, with a pixel for each
+    /// spot on both sides
+    fn fits(&self) -> bool {
+        let pixels = self.width * self.height;
+        self.x + self.width <= window_width()
+            && self.y >= CANVAS_TOP
+            && self.y + self.height <= canvas_bottom()
+            && self.before.len() == pixels
+            && self.after.len() == pixels
+    }
+
+    /// Copy one side of the diff into a window-sized buffer
+    fn apply(&self, buffer: &mut [u32], pixels: &[u32]) {
+        for row in 0..self.height {
+            let start = (self.y + row) * window_width() + self.x;
+            buffer[start..start + self.width]
+                .copy_from_slice(&pixels[row * self.width..(row + 1) * self.width]);
+        }
+    }
+}
+
+/// An open group collecting operations until `group end`
+#[derive(Debug, Clone, PartialEq)]
+struct OpenGroup {
+    label: String,
+    operations: usize,
+    depth: usize, // Nested `group begin`s fold into the outermost group
+}
+
+/// Ordered, bounded list of undoable history entries plus a redo stack
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: Vec<(HistoryEntry, PixelDiff)>,
+    redo: Vec<(HistoryEntry, PixelDiff)>,
+    open_group: Option<OpenGroup>,
+    shadow: Vec<u32>, // Window-sized copy; only the canvas rows are kept in sync
+    bytes: usize,
+    pixels_changed: u64,
+}
+
+impl History {
+    /// Create an empty history for a canvas that starts out white
+    pub fn new() -> Self {
+        History {
+            entries: Vec::new(),
+            redo: Vec::new(),
+            open_group: None,
+            shadow: vec![WHITE; window_width() * canvas_bottom()],
+            bytes: 0,
+            pixels_changed: 0,
+        }
+    }
+
+    /// Create an empty history for a canvas that starts out as the canvas
+    /// rows of `buffer` (e.g. an image loaded at startup), which undo won't
+    /// go back past
+    pub fn with_baseline(buffer: &[u32]) -> Self {
+        let mut history = History::new();
+        let len = history.shadow.len();
+        history.shadow.copy_from_slice(&buffer[..len]);
+        history
+    }
+
+    /// Diff the canvas area of `buffer` against the shadow and bring the shadow up to date
+    fn take_diff(&mut self, buffer: &[u32]) -> PixelDiff {
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for y in CANVAS_TOP..canvas_bottom() {
+            let row = y * window_width()..(y + 1) * window_width();
+            let (live, old) = (&buffer[row.clone()], &self.shadow[row]);
+            if live == old {
+                continue;
+            }
+            self.pixels_changed += live.iter().zip(old).filter(|(a, b)| a != b).count() as u64;
+            let first = live.iter().zip(old).position(|(a, b)| a != b).unwrap();
+            let last = live.iter().zip(old).rposition(|(a, b)| a != b).unwrap();
+            bounds = Some(match bounds {
+                None => (first, y, last, y),
+                Some((x0, y0, x1, _)) => (x0.min(first), y0, x1.max(last), y),
+            });
+        }
+
+        let Some((x0, y0, x1, y1)) = bounds else {
+            return PixelDiff {
+                x: 0,
+                y: CANVAS_TOP,
+                width: 0,
+                height: 0,
+                before: Vec::new(),
+                after: Vec::new(),
+            };
+        };
+        let (width, height) = (x1 - x0 + 1, y1 - y0 + 1);
+        let mut before = Vec::with_capacity(width * height);
+        let mut after = Vec::with_capacity(width * height);
+        for y in y0..=y1 {
+            let row = y * window_width() + x0..y * window_width() + x1 + 1;
+            before.extend_from_slice(&self.shadow[row.clone()]);
+            after.extend_from_slice(&buffer[row.clone()]);
+            self.shadow[row.clone()].copy_from_slice(&buffer[row]);
+        }
+        PixelDiff {
+            x: x0,
+            y: y0,
+            width,
+            height,
+            before,
+            after,
+        }
+    }
+
+    fn push(&mut self, entry: HistoryEntry, diff: PixelDiff) {
+        self.redo.clear();
+        self.bytes += diff.bytes();
+        self.entries.push((entry, diff));
+        while self.entries.len() > MAX_HISTORY_ENTRIES
+            || (self.bytes > MAX_HISTORY_BYTES && self.entries.len() > 1)
+        {
+            let (_, dropped) = self.entries.remove(0);
+            self.bytes -= dropped.bytes();
+        }
+    }
+
+    /// Record one operation that has just been applied to `buffer`
+    ///
+    /// Inside a group it joins the group; otherwise it becomes its own entry
+    /// labeled `label`, and the redo stack is cleared. Outside a group, an
+    /// operation that changed no pixels leaves no entry (and the redo stack
+    /// as it was).
+    pub fn record(&mut self, label: &str, buffer: &[u32]) {
+        match self.open_group.as_mut() {
+            Some(group) => group.operations += 1,
+            None => {
+                let diff = self.take_diff(buffer);
+                if diff.width == 0 {
+                    return;
+                }
+                self.push(
+                    HistoryEntry {
+                        label: label.to_string(),
+                        operations: 1,
+                    },
+                    diff,
+                );
+            }
+        }
+    }
+
+    /// Open a group; a nested `begin` is folded into the already-open group
+    pub fn begin_group(&mut self, label: &str) {
+        match self.open_group.as_mut() {
+            Some(group) => group.depth += 1,
+            None => {
+                self.open_group = Some(OpenGroup {
+                    label: label.to_string(),
+                    operations: 0,
+                    depth: 1,
+                })
+            }
+        }
+    }
+
+    /// Close the innermost group; closing the outermost one adds its entry,
+    /// covering every change made to `buffer` since the group began
+    /// (empty groups leave no entry)
+    pub fn end_group(&mut self, buffer: &[u32]) -> Result<(), String> {
+        let group = self.open_group.as_mut().ok_or("no open group")?;
+        group.depth -= 1;
+        if group.depth == 0 {
+            let group = self.open_group.take().expect("group is open");
+            if group.operations > 0 {
+                let diff = self.take_diff(buffer);
+                self.push(
+                    HistoryEntry {
+                        label: group.label,
+                        operations: group.operations,
+                    },
+                    diff,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Label of the open group, if any
+    pub fn open_group_label(&self) -> Option<&str> {
+        self.open_group.as_ref().map(|g| g.label.as_str())
+    }
+
+    /// Revert the most recent entry in `buffer`, returning its label
+    ///
+    /// Fails while a group is open, since its changes are not an entry yet.
+    pub fn undo(&mut self, buffer: &mut [u32]) -> Result<String, String> {
+        if self.open_group.is_some() {
+            return Err("cannot undo inside a group".to_string());
+        }
+        let (entry, diff) = self.entries.pop().ok_or("nothing to undo")?;
+        self.bytes -= diff.bytes();
+        diff.apply(buffer, &diff.before);
+        diff.apply(&mut self.shadow, &diff.before);
+        let label = entry.label.clone();
+        self.redo.push((entry, diff));
+        Ok(label)
+    }
+
+    /// Undo entries in `buffer` until `len` are left, returning how many were
+    /// undone (none if there are already no more than `len`)
+    ///
+    /// Fails while a group is open, like `undo`.
+    pub fn undo_to(&mut self, len: usize, buffer: &mut [u32]) -> Result<usize, String> {
+        let mut undone = 0;
+        while self.entries.len() > len {
+            self.undo(buffer)?;
+            undone += 1;
+        }
+        Ok(undone)
+    }
+
+    /// Reapply the most recently undone entry in `buffer`, returning its label
+    pub fn redo(&mut self, buffer: &mut [u32]) -> Result<String, String> {
+        if self.open_group.is_some() {
+            return Err("cannot redo inside a group".to_string());
+        }
+        let (entry, diff) = self.redo.pop().ok_or("nothing to redo")?;
+        diff.apply(buffer, &diff.after);
+        diff.apply(&mut self.shadow, &diff.after);
+        let label = entry.label.clone();
+        self.bytes += diff.bytes();
+        self.entries.push((entry, diff));
+        Ok(label)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Undoable entries, oldest first
+    pub fn entries(&self) -> Vec<&HistoryEntry> {
+        self.entries.iter().map(|(entry, _)| entry).collect()
+    }
+
+    /// Canvas pixels changed by all recorded entries (undo and redo not counted)
+    pub fn pixels_changed(&self) -> u64 {
+        self.pixels_changed
+    }
+
+    /// Bytes of pixel data held for undo
+    pub fn pixel_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The undoable entries and the redo stack as bytes, for `from_bytes`
+    ///
+    /// Positions are window coordinates, so the bytes only make sense to a
+    /// history read back at the same size. An open group isn't written.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.bytes + 1024);
+        for stack in [&self.entries, &self.redo] {
+            put_u32(&mut out, stack.len());
+            for (entry, diff) in stack {
+                put_u32(&mut out, entry.label.len());
+                out.extend_from_slice(entry.label.as_bytes());
+                put_u32(&mut out, entry.operations);
+                for n in [diff.x, diff.y, diff.width, diff.height] {
+                    put_u32(&mut out, n);
+                }
+                for pixel in diff.before.iter().chain(&diff.after) {
+                    out.extend_from_slice(&pixel.to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    /// A history written by `to_bytes`, for a canvas that is now the canvas
+    /// rows of `buffer` (as it was when the bytes were written)
+    ///
+    /// Fails if the bytes are cut short or an entry doesn't fit the canvas.
+    pub fn from_bytes(bytes: &[u8], buffer: &[u32]) -> Result<History, String> {
+        let mut history = History::with_baseline(buffer);
+        let mut reader = ByteReader { bytes };
+        let mut stacks = [Vec::new(), Vec::new()];
+        for stack in &mut stacks {
+            for _ in 0..reader.u32()? {
+                let len = reader.u32()?;
+                let label = String::from_utf8(reader.take(len)?.to_vec())
+                    .map_err(|_| "history label is not UTF-8".to_string())?;
+                let operations = reader.u32()?;
+                let (x, y) = (reader.u32()?, reader.u32()?);
+                let (width, height) = (reader.u32()?, reader.u32()?);
+                let pixels = width.checked_mul(height).ok_or("history is damaged")?;
+                let before = reader.pixels(pixels)?;
+                let after = reader.pixels(pixels)?;
+                let diff = PixelDiff {
+                    x,
+                    y,
+                    width,
+                    height,
+                    before,
+                    after,
+                };
+                if !diff.fits() {
+                    return Err("history doesn't fit the canvas".to_string());
+                }
+                stack.push((HistoryEntry { label, operations }, diff));
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err("history is damaged".to_string());
+        }
+        let [entries, redo] = stacks;
+        history.bytes = entries.iter().map(|(_, diff)| diff.bytes()).sum();
+        history.entries = entries;
+        history.redo = redo;
+        Ok(history)
+    }
+}
+
+fn put_u32(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+/// Reads `History::to_bytes`'s output from the front
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.bytes.len() {
+            return Err("history is cut short".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")) as usize)
+    }
+
+    fn pixels(&mut self, count: usize) -> Result<Vec<u32>, String> {
+        let len = count.checked_mul(4).ok_or("history is damaged")?;
+        Ok(self
+            .take(len)?
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")))
+            .collect())
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The history panel's rows, top to bottom: how many entries clicking the
+/// row keeps, and its text
+///
+/// The latest `HISTORY_PANEL_ROWS` entries are listed, named by their labels
+/// (with the operation count of a group); while they all fit, a `start` row
+/// above them undoes everything.
+pub fn history_panel_rows(entries: &[&HistoryEntry]) -> Vec<(usize, String)> {
+    let shown = entries.len().min(HISTORY_PANEL_ROWS);
+    let first = entries.len() - shown;
+    let mut rows = Vec::with_capacity(shown + 1);
+    if shown < HISTORY_PANEL_ROWS {
+        rows.push((0, "start".to_string()));
+    }
+    for (i, entry) in entries.iter().enumerate().skip(first) {
+        let text = match entry.operations {
+            0 | 1 => entry.label.clone(),
+            n => format!("{} ({})", entry.label, n),
+        };
+        rows.push((i + 1, text));
+    }
+    rows
+}
+
+/// Bounds of the history panel as (x, y, width, height), for `rows` rows
+pub fn history_panel_bounds(rows: usize) -> (usize, usize, usize, usize) {
+    let height = rows * PANEL_ROW_HEIGHT + PANEL_PADDING * 2;
+    (
+        PANEL_PADDING,
+        CANVAS_TOP + PANEL_PADDING,
+        HISTORY_PANEL_WIDTH,
+        height.min(canvas_bottom().saturating_sub(CANVAS_TOP + PANEL_PADDING * 2)),
+    )
+}
+
+/// Draw the history panel into a frame buffer, the latest entry (where the
+/// canvas is now) highlighted
+pub fn draw_history_panel(buffer: &mut [u32], entries: &[&HistoryEntry]) {
+    let rows = history_panel_rows(entries);
+    let (bx, by, width, height) = history_panel_bounds(rows.len());
+    let right = (bx + width).min(window_width());
+    for y in by..by + height {
+        for x in bx..right {
+            let edge = y == by || y == by + height - 1 || x == bx || x == bx + width - 1;
+            buffer[y * window_width() + x] = if edge { DARK_GRAY } else { STATS_BACKGROUND };
+        }
+    }
+    let chars = (width - PANEL_PADDING * 2) / GLYPH_ADVANCE;
+    for (i, (keep, text)) in rows.iter().enumerate() {
+        let top = by + PANEL_PADDING + i * PANEL_ROW_HEIGHT;
+        if top + PANEL_ROW_HEIGHT > by + height {
+            break;
+        }
+        if *keep == entries.len() {
+            for y in top..top + PANEL_ROW_HEIGHT - 1 {
+                buffer[y * window_width() + bx + 1..y * window_width() + right - 1].fill(GRAY);
+            }
+        }
+        let text: String = text.chars().take(chars).collect();
+        draw_text(buffer, bx + PANEL_PADDING, top + 2, &text, BLACK);
+    }
+}
+
+/// How many entries to keep when the history panel is clicked at (x, y),
+/// if the click is on one of its rows
+pub fn history_panel_entry_at(x: usize, y: usize, entries: &[&HistoryEntry]) -> Option<usize> {
+    let rows = history_panel_rows(entries);
+    let (bx, by, width, height) = history_panel_bounds(rows.len());
+    if !(bx..bx + width).contains(&x) || !(by..by + height).contains(&y) {
+        return None;
+    }
+    let row = y.checked_sub(by + PANEL_PADDING)? / PANEL_ROW_HEIGHT;
+    rows.get(row).map(|(keep, _)| *keep)
+}
+
+/// Whether (x, y) is on the history panel listing `entries`
+pub fn is_in_history_panel(x: usize, y: usize, entries: &[&HistoryEntry]) -> bool {
+    let (bx, by, width, height) = history_panel_bounds(history_panel_rows(entries).len());
+    (bx..bx + width).contains(&x) && (by..by + height).contains(&y)
+}
@@ -0,0 +1,418 @@
+//! Color profiles for exported images.
+//!
+//! This module handles:
+//! - `ColorProfile`, the RGB spaces snapshots can be saved in
+//!   (`color-profile srgb|display-p3|adobe-rgb|off`)
+//! - The profile images saved on this thread carry (`AppState` sets it from
+//!   `color-profile` around each command; the snapshot worker keeps it per job)
+//! - Building each space's ICC profile to embed: version 2.4, a display
+//!   profile of three colorants and their tone curves
+//! - Converting canvas pixels, which are sRGB, into the chosen space
+//! - Writing an image tagged with the profile (PNG, JPEG, and WebP; BMP has
+//!   nowhere to put one)
+//!
+//! Without a profile, images are written untagged as before, and viewers
+//! assume sRGB. `srgb` only adds the tag, so color-managed viewers and print
+//! workflows stop guessing; the wider spaces also move each pixel so it shows
+//! the same color in them. Every sRGB color fits in both, so none is clipped.
+
+use std::cell::Cell;
+use std::io::{Cursor, Seek, Write};
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ImageEncoder, ImageFormat, RgbImage};
+
+use crate::snapshot::write_image;
+
+thread_local! {
+    static COLOR_PROFILE: Cell<Option<ColorProfile>> = const { Cell::new(None) };
+}
+
+/// A 3x3 matrix, by rows
+type Matrix = [[f64; 3]; 3];
+
+/// The white point of D65 daylight, which all three spaces use, as xy
+const D65: (f64, f64) = (0.3127, 0.3290);
+/// The ICC profile connection space's white point (D50), as XYZ
+const PCS_WHITE: [f64; 3] = [0.9642, 1.0, 0.8249];
+/// Bradford cone response, for moving colors from D65 to D50 light
+const BRADFORD: Matrix = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+/// Entries in the table an sRGB tone curve is written as
+const CURVE_ENTRIES: usize = 1024;
+
+/// An RGB space exported images can be converted to and tagged with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorProfile {
+    /// The canvas's own space: images are tagged, not converted
+    Srgb,
+    /// The wide-gamut space of recent displays (sRGB's tone curve)
+    DisplayP3,
+    /// The wide-gamut space of print workflows (gamma 2.2)
+    AdobeRgb,
+}
+
+/// How a space's channel values relate to light
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ToneCurve {
+    /// sRGB's: a short straight run from black, then gamma 2.4
+    Srgb,
+    /// A plain power curve
+    Gamma(f64),
+}
+
+impl ToneCurve {
+    /// Light from a channel value, both 0 to 1
+    fn decode(self, v: f64) -> f64 {
+        match self {
+            ToneCurve::Srgb if v <= 0.04045 => v / 12.92,
+            ToneCurve::Srgb => ((v + 0.055) / 1.055).powf(2.4),
+            ToneCurve::Gamma(gamma) => v.powf(gamma),
+        }
+    }
+
+    /// The channel value for an amount of light, both 0 to 1
+    fn encode(self, v: f64) -> f64 {
+        match self {
+            ToneCurve::Srgb if v <= 0.0031308 => v * 12.92,
+            ToneCurve::Srgb => 1.055 * v.powf(1.0 / 2.4) - 0.055,
+            ToneCurve::Gamma(gamma) => v.powf(1.0 / gamma),
+        }
+    }
+}
+
+impl ColorProfile {
+    /// Every profile, in the order `color-profile` lists them
+    pub const ALL: [ColorProfile; 3] = [
+        ColorProfile::Srgb,
+        ColorProfile::DisplayP3,
+        ColorProfile::AdobeRgb,
+    ];
+
+    /// Parse a profile name: `srgb`, `display-p3`, or `adobe-rgb`
+    pub fn parse(s: &str) -> Option<ColorProfile> {
+        ColorProfile::ALL
+            .into_iter()
+            .find(|profile| profile.name() == s)
+    }
+
+    /// The name `parse` reads
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorProfile::Srgb => "srgb",
+            ColorProfile::DisplayP3 => "display-p3",
+            ColorProfile::AdobeRgb => "adobe-rgb",
+        }
+    }
+
+    /// What viewers show as the profile's name
+    pub fn description(self) -> &'static str {
+        match self {
+            ColorProfile::Srgb => "sRGB",
+            ColorProfile::DisplayP3 => "Display P3",
+            ColorProfile::AdobeRgb => "Adobe RGB (1998) compatible",
+        }
+    }
+
+    /// The red, green, and blue primaries, as xy
+    fn primaries(self) -> [(f64, f64); 3] {
+        match self {
+            ColorProfile::Srgb => [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)],
+            ColorProfile::DisplayP3 => [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)],
+            ColorProfile::AdobeRgb => [(0.64, 0.33), (0.21, 0.71), (0.15, 0.06)],
+        }
+    }
+
+    fn tone_curve(self) -> ToneCurve {
+        match self {
+            ColorProfile::Srgb | ColorProfile::DisplayP3 => ToneCurve::Srgb,
+            // 563/256, the value the tag can hold exactly
+            ColorProfile::AdobeRgb => ToneCurve::Gamma(563.0 / 256.0),
+        }
+    }
+
+    /// From linear RGB in this space to XYZ under D65
+    fn to_xyz(self) -> Matrix {
+        let [r, g, b] = self.primaries().map(xy_to_xyz);
+        let primaries = transpose([r, g, b]);
+        let scale = apply(&invert(&primaries), xy_to_xyz(D65));
+        primaries.map(|row| [0, 1, 2].map(|j| row[j] * scale[j]))
+    }
+
+    /// The ICC profile describing this space
+    pub fn icc_profile(self) -> Vec<u8> {
+        let adapt = d65_to_d50();
+        // The colorants are the primaries as seen under D50
+        let colorants = transpose(multiply(&adapt, &self.to_xyz()));
+        let curve = curve_tag(self.tone_curve());
+        let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+            (b"desc", desc_tag(self.description())),
+            (b"cprt", text_tag("No copyright, use freely")),
+            (b"wtpt", xyz_tag(PCS_WHITE)),
+            (b"chad", sf32_tag(&adapt)),
+            (b"rXYZ", xyz_tag(colorants[0])),
+            (b"gXYZ", xyz_tag(colorants[1])),
+            (b"bXYZ", xyz_tag(colorants[2])),
+            (b"rTRC", curve.clone()),
+            (b"gTRC", curve.clone()),
+            (b"bTRC", curve),
+        ];
+
+        // Tag data follows the header and tag table, each 4-byte aligned;
+        // tags with the same data (the tone curves) share it
+        let mut table = Vec::new();
+        let mut data: Vec<u8> = Vec::new();
+        let data_start = 128 + 4 + 12 * tags.len();
+        let mut placed: Vec<(usize, &Vec<u8>)> = Vec::new();
+        for (signature, bytes) in &tags {
+            let offset = match placed.iter().find(|(_, earlier)| *earlier == bytes) {
+                Some(&(offset, _)) => offset,
+                None => {
+                    let offset = data_start + data.len();
+                    data.extend_from_slice(bytes);
+                    data.resize(data.len().next_multiple_of(4), 0);
+                    placed.push((offset, bytes));
+                    offset
+                }
+            };
+            table.extend_from_slice(*signature);
+            table.extend_from_slice(&(offset as u32).to_be_bytes());
+            table.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        }
+
+        let size = data_start + data.len();
+        let mut profile = Vec::with_capacity(size);
+        profile.extend_from_slice(&(size as u32).to_be_bytes());
+        profile.extend_from_slice(&[0; 4]); // No preferred CMM
+        profile.extend_from_slice(&[2, 0x40, 0, 0]); // Version 2.4
+        profile.extend_from_slice(b"mntrRGB XYZ ");
+        // Creation date, fixed so the same canvas saves the same bytes
+        for part in [2024u16, 1, 1, 0, 0, 0] {
+            profile.extend_from_slice(&part.to_be_bytes());
+        }
+        profile.extend_from_slice(b"acsp");
+        profile.extend_from_slice(&[0; 28]); // Platform, flags, device, attributes, intent
+        for v in PCS_WHITE {
+            profile.extend_from_slice(&s15_fixed16(v));
+        }
+        profile.extend_from_slice(&[0; 48]); // Creator, ID, reserved
+        profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+        profile.extend_from_slice(&table);
+        profile.extend_from_slice(&data);
+        profile
+    }
+
+    /// An sRGB color as this space writes it
+    pub fn from_srgb(self, rgb: [u8; 3]) -> [u8; 3] {
+        Conversion::new(self).apply(rgb)
+    }
+
+    /// Convert an image of sRGB pixels into this space
+    pub fn convert(self, img: &mut RgbImage) {
+        if self != ColorProfile::Srgb {
+            let conversion = Conversion::new(self);
+            for pixel in img.pixels_mut() {
+                pixel.0 = conversion.apply(pixel.0);
+            }
+        }
+    }
+}
+
+/// Moving sRGB pixels into a space: decoded to light, mixed into the
+/// space's primaries, and encoded by its tone curve
+struct Conversion {
+    decoded: [f64; 256],
+    matrix: Matrix,
+    curve: ToneCurve,
+}
+
+impl Conversion {
+    fn new(profile: ColorProfile) -> Self {
+        let srgb = ColorProfile::Srgb.tone_curve();
+        Conversion {
+            decoded: std::array::from_fn(|i| srgb.decode(i as f64 / 255.0)),
+            matrix: multiply(&invert(&profile.to_xyz()), &ColorProfile::Srgb.to_xyz()),
+            curve: profile.tone_curve(),
+        }
+    }
+
+    fn apply(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let light = apply(&self.matrix, rgb.map(|c| self.decoded[c as usize]));
+        light.map(|v| (self.curve.encode(v.clamp(0.0, 1.0)) * 255.0).round() as u8)
+    }
+}
+
+/// The profile images saved on this thread carry (None: untagged sRGB)
+pub fn color_profile() -> Option<ColorProfile> {
+    COLOR_PROFILE.with(Cell::get)
+}
+
+/// Run `f` with `profile` as the color profile, restoring the previous one after
+pub fn with_color_profile<R>(profile: Option<ColorProfile>, f: impl FnOnce() -> R) -> R {
+    let previous = COLOR_PROFILE.with(|current| current.replace(profile));
+    let result = f();
+    COLOR_PROFILE.with(|current| current.set(previous));
+    result
+}
+
+/// Encode `img` (sRGB) as `format` into `out`, converted to and tagged with
+/// the thread's color profile, if any
+pub fn write_profiled<W: Write + Seek>(
+    img: &RgbImage,
+    out: &mut W,
+    format: ImageFormat,
+) -> Result<(), String> {
+    let Some(profile) = color_profile() else {
+        return write_image(img, out, format);
+    };
+    let mut img = img.clone();
+    profile.convert(&mut img);
+    let icc = profile.icc_profile();
+    match format {
+        ImageFormat::Png => encode_tagged(PngEncoder::new(out), &img, icc),
+        ImageFormat::Jpeg => encode_tagged(JpegEncoder::new(out), &img, icc),
+        ImageFormat::WebP => encode_tagged(WebPEncoder::new_lossless(out), &img, icc),
+        _ => Err(format!(
+            "{:?} images can't carry a color profile (use .png, .jpg, or .webp, or color-profile off)",
+            format
+        )),
+    }
+}
+
+/// Encode `img` with `encoder`, embedding `icc`
+fn encode_tagged(
+    mut encoder: impl ImageEncoder,
+    img: &RgbImage,
+    icc: Vec<u8>,
+) -> Result<(), String> {
+    encoder.set_icc_profile(icc).map_err(|e| e.to_string())?;
+    let (width, height) = img.dimensions();
+    encoder
+        .write_image(img, width, height, image::ExtendedColorType::Rgb8)
+        .map_err(|e| e.to_string())
+}
+
+/// Save `img` (sRGB) to `path` as `format`, converted to and tagged with the
+/// thread's color profile, if any
+pub fn save_profiled(img: &RgbImage, path: &Path, format: ImageFormat) -> Result<(), String> {
+    // Encoded first, so a format that can't be tagged leaves no file behind
+    let mut bytes = Cursor::new(Vec::new());
+    write_profiled(img, &mut bytes, format)?;
+    std::fs::write(path, bytes.into_inner()).map_err(|e| e.to_string())
+}
+
+/// XYZ (with Y = 1) of a chromaticity
+fn xy_to_xyz((x, y): (f64, f64)) -> [f64; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// The Bradford transform from colors under D65 to how they look under D50
+fn d65_to_d50() -> Matrix {
+    let (from, to) = (
+        apply(&BRADFORD, xy_to_xyz(D65)),
+        apply(&BRADFORD, PCS_WHITE),
+    );
+    let scale = [0, 1, 2].map(|i| {
+        let mut row = [0.0; 3];
+        row[i] = to[i] / from[i];
+        row
+    });
+    multiply(&invert(&BRADFORD), &multiply(&scale, &BRADFORD))
+}
+
+fn apply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn transpose(m: Matrix) -> Matrix {
+    std::array::from_fn(|i| std::array::from_fn(|j| m[j][i]))
+}
+
+/// The inverse of `m` (its primaries are independent, so it has one)
+fn invert(m: &Matrix) -> Matrix {
+    let cofactor = |i: usize, j: usize| {
+        let (r1, r2) = ((i + 1) % 3, (i + 2) % 3);
+        let (c1, c2) = ((j + 1) % 3, (j + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let det: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / det))
+}
+
+/// An ICC s15Fixed16Number
+fn s15_fixed16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+/// The start of a tag's data: its type and four reserved bytes
+fn tag_start(kind: &[u8; 4]) -> Vec<u8> {
+    let mut tag = kind.to_vec();
+    tag.extend_from_slice(&[0; 4]);
+    tag
+}
+
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    let mut tag = tag_start(b"XYZ ");
+    for v in xyz {
+        tag.extend_from_slice(&s15_fixed16(v));
+    }
+    tag
+}
+
+fn sf32_tag(m: &Matrix) -> Vec<u8> {
+    let mut tag = tag_start(b"sf32");
+    for v in m.iter().flatten() {
+        tag.extend_from_slice(&s15_fixed16(*v));
+    }
+    tag
+}
+
+/// A tone curve: a gamma as one u8Fixed8Number, or a table of light levels
+fn curve_tag(curve: ToneCurve) -> Vec<u8> {
+    let mut tag = tag_start(b"curv");
+    match curve {
+        ToneCurve::Gamma(gamma) => {
+            tag.extend_from_slice(&1u32.to_be_bytes());
+            tag.extend_from_slice(&((gamma * 256.0).round() as u16).to_be_bytes());
+        }
+        ToneCurve::Srgb => {
+            tag.extend_from_slice(&(CURVE_ENTRIES as u32).to_be_bytes());
+            for i in 0..CURVE_ENTRIES {
+                let light = curve.decode(i as f64 / (CURVE_ENTRIES - 1) as f64);
+                tag.extend_from_slice(&((light * 65535.0).round() as u16).to_be_bytes());
+            }
+        }
+    }
+    tag
+}
+
+/// ASCII text, NUL-terminated
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = tag_start(b"text");
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+/// A version 2 profile description: ASCII, with empty Unicode and
+/// ScriptCode versions
+fn desc_tag(text: &str) -> Vec<u8> {
+    let mut tag = tag_start(b"desc");
+    tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag.extend_from_slice(&[0; 8]); // Unicode language and length
+    tag.extend_from_slice(&[0; 3]); // ScriptCode code and length
+    tag.extend_from_slice(&[0; 67]);
+    tag
+}
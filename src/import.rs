@@ -0,0 +1,80 @@
+//! Importing images onto the canvas.
+//!
+//! This module handles:
+//! - Decoding an image file (any format the `image` crate reads from its
+//!   contents: PNG, JPEG, BMP, WebP, GIF, ...)
+//! - Pasting it onto the canvas at a position, scaled by nearest neighbour
+//!   and blended by its alpha channel, for `image load`
+//! - Laying an image out as a reference frame, lined up with a snapshot, for
+//!   `compare` and `diff`
+//!
+//! Pasted pixels become canvas pixels like any drawing: they are undoable,
+//! and later drawing covers them.
+
+use image::RgbaImage;
+
+use crate::{canvas_bottom, window_height, window_width, CANVAS_TOP, WHITE};
+
+/// Largest `image load` scale factor
+pub const MAX_IMAGE_SCALE: f64 = 16.0;
+
+/// Whether `scale` is a usable `image load` scale factor (above 0, at most
+/// `MAX_IMAGE_SCALE`)
+pub fn is_valid_image_scale(scale: f64) -> bool {
+    scale > 0.0 && scale <= MAX_IMAGE_SCALE
+}
+
+/// Decode the image file at `path`
+pub fn load_image(path: &str) -> Result<RgbaImage, String> {
+    image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("cannot load {}: {}", path, e))?
+        .decode()
+        .map(|img| img.to_rgba8())
+        .map_err(|e| format!("cannot load {}: {}", path, e))
+}
+
+/// Blend `src` (straight alpha 0-255) over the `0xRRGGBB` color `dst`
+fn blend(dst: u32, [r, g, b, a]: [u8; 4]) -> u32 {
+    let a = u32::from(a);
+    let mix = |src: u8, shift: u32| {
+        let d = (dst >> shift) & 0xFF;
+        (u32::from(src) * a + d * (255 - a) + 127) / 255
+    };
+    (mix(r, 16) << 16) | (mix(g, 8) << 8) | mix(b, 0)
+}
+
+/// Paste `img` with its top-left corner at (x, y), `scale` times its size,
+/// clipped to the canvas
+pub fn paste_image(buffer: &mut [u32], img: &RgbaImage, x: usize, y: usize, scale: f64) {
+    if img.width() == 0 || img.height() == 0 {
+        return;
+    }
+    let scaled = |n: u32| ((f64::from(n) * scale).round() as usize).max(1);
+    let (w, h) = (scaled(img.width()), scaled(img.height()));
+    let width = window_width();
+    let right = x.saturating_add(w).min(width);
+    let bottom = y.saturating_add(h).min(canvas_bottom());
+    // Nearest source pixel for each destination column and row
+    let src = |d: usize, len: u32| ((d as f64 / scale) as u32).min(len - 1);
+    for dy in y.max(CANVAS_TOP)..bottom {
+        let sy = src(dy - y, img.height());
+        for dx in x..right {
+            let pixel = img.get_pixel(src(dx - x, img.width()), sy).0;
+            let slot = &mut buffer[dy * width + dx];
+            *slot = match pixel[3] {
+                0 => *slot,
+                255 => u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]]),
+                _ => blend(*slot, pixel),
+            };
+        }
+    }
+}
+
+/// A window-sized frame holding `img` at the canvas's top-left corner at its
+/// own size on white, so a snapshot of this canvas lines up with it exactly
+pub fn reference_frame(img: &RgbaImage) -> Vec<u32> {
+    let mut frame = vec![WHITE; window_width() * window_height()];
+    paste_image(&mut frame, img, 0, CANVAS_TOP, 1.0);
+    frame
+}
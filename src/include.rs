@@ -0,0 +1,103 @@
+//! Scripts that include other scripts.
+//!
+//! This module handles:
+//! - Reading a script file's command lines (one per line, blank lines and
+//!   `#` comments skipped; recordings are read too, without their times)
+//! - `include <path>` lines, replaced by the lines of the script they name
+//! - Refusing includes that come back round to a script already being read,
+//!   or go deeper than `MAX_INCLUDE_DEPTH`
+//!
+//! Shared setup (a palette, a background, aliases) can then live in one file
+//! that each script includes. A relative path is taken from the directory of
+//! the script that includes it, so a folder of scripts works wherever it is
+//! run from. Each line is passed to a `fill` function before it is looked at,
+//! so `--script` variables work in included files and in the paths they
+//! include (`include ${theme}.txt`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::record::{read_recording, RECORDING_HEADER};
+
+/// Most scripts one chain of includes can pass through, counting the first
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The path an `include <path>` line names (the rest of the line, so it may
+/// hold spaces), if it is one
+pub fn include_path(line: &str) -> Option<&str> {
+    let path = line.strip_prefix("include")?;
+    path.starts_with(char::is_whitespace)
+        .then(|| path.trim())
+        .filter(|path| !path.is_empty())
+}
+
+/// The command lines of the script file at `path`, includes left in
+fn file_lines(path: &Path) -> Result<Vec<String>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    if text.lines().next() == Some(RECORDING_HEADER) {
+        return Ok(read_recording(&path.to_string_lossy())?
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect());
+    }
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Read the script at `path`, each line put through `fill` and each
+/// `include` replaced by the lines of the script it names
+///
+/// An error from `fill` is reported with the file it came from.
+pub fn read_included(
+    path: &str,
+    fill: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<Vec<String>, String> {
+    let mut lines = Vec::new();
+    include_into(Path::new(path), fill, &mut Vec::new(), &mut lines)?;
+    Ok(lines)
+}
+
+/// Append the lines of the script at `path` to `lines`, `reading` being the
+/// scripts that included it (their paths as named, and as found on disk)
+fn include_into(
+    path: &Path,
+    fill: &dyn Fn(&str) -> Result<String, String>,
+    reading: &mut Vec<(PathBuf, PathBuf)>,
+    lines: &mut Vec<String>,
+) -> Result<(), String> {
+    let found =
+        fs::canonicalize(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    if reading.iter().any(|(_, open)| *open == found) {
+        let chain: Vec<String> = reading
+            .iter()
+            .map(|(named, _)| named.display().to_string())
+            .chain([path.display().to_string()])
+            .collect();
+        return Err(format!("include cycle: {}", chain.join(" -> ")));
+    }
+    if reading.len() >= MAX_INCLUDE_DEPTH {
+        return Err(format!(
+            "{}: includes go more than {} deep",
+            path.display(),
+            MAX_INCLUDE_DEPTH
+        ));
+    }
+    reading.push((path.to_path_buf(), found));
+    for line in file_lines(path)? {
+        let line = fill(&line).map_err(|e| format!("{}: {}", path.display(), e))?;
+        match include_path(&line) {
+            Some(included) => {
+                let dir = path.parent().unwrap_or(Path::new(""));
+                include_into(&dir.join(included), fill, reading, lines)?;
+            }
+            None => lines.push(line),
+        }
+    }
+    reading.pop();
+    Ok(())
+}
@@ -0,0 +1,415 @@
+//! Keyboard shortcuts.
+//!
+//! This module handles:
+//! - `Chord`, a key with optional Ctrl and Shift, written like `ctrl+shift+z`
+//! - `Action`, what a shortcut does: pick a tool, color, or brush size, clear,
+//!   snapshot, undo, or redo
+//! - `Keymap`, the chords bound to actions: built-in defaults, overridden by a
+//!   `--keymap` file of `chord = action` lines
+//! - `KeyEdges`, which turns the keys held down each frame into presses
+//!
+//! Shortcuts without Ctrl are letters and digits a focused text input would
+//! want, so the run loop only applies them when no widget has focus (and
+//! keeps their characters from reaching subscribers as `key` events).
+
+use minifb::Key;
+
+use crate::{ToolMode, PALETTE_LEN, TOOLBAR_TOOLS};
+
+/// Key names usable in a chord, besides `a`-`z`, `0`-`9`, and `f1`-`f12`
+const NAMED_KEYS: [(&str, Key); 10] = [
+    ("[", Key::LeftBracket),
+    ("]", Key::RightBracket),
+    ("-", Key::Minus),
+    ("=", Key::Equal),
+    (",", Key::Comma),
+    (".", Key::Period),
+    ("/", Key::Slash),
+    (";", Key::Semicolon),
+    ("space", Key::Space),
+    ("delete", Key::Delete),
+];
+
+const LETTER_KEYS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+const DIGIT_KEYS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+const FUNCTION_KEYS: [Key; 12] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+];
+
+/// Look up a key by its chord name
+pub fn key_from_name(name: &str) -> Option<Key> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.clone().next()) {
+        if c.is_ascii_lowercase() {
+            return Some(LETTER_KEYS[(c as u8 - b'a') as usize]);
+        }
+        if c.is_ascii_digit() {
+            return Some(DIGIT_KEYS[(c as u8 - b'0') as usize]);
+        }
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION_KEYS.get(n.checked_sub(1)?).copied();
+    }
+    NAMED_KEYS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|&(_, key)| key)
+}
+
+/// The chord name of a key, if it can be bound
+pub fn key_name(key: Key) -> Option<String> {
+    if let Some(i) = LETTER_KEYS.iter().position(|&k| k == key) {
+        return Some(((b'a' + i as u8) as char).to_string());
+    }
+    if let Some(i) = DIGIT_KEYS.iter().position(|&k| k == key) {
+        return Some(i.to_string());
+    }
+    if let Some(i) = FUNCTION_KEYS.iter().position(|&k| k == key) {
+        return Some(format!("f{}", i + 1));
+    }
+    NAMED_KEYS
+        .iter()
+        .find(|&&(_, k)| k == key)
+        .map(|(name, _)| name.to_string())
+}
+
+/// A key pressed with or without Ctrl and Shift
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub key: Key,
+}
+
+impl Chord {
+    /// A key on its own
+    pub fn key(key: Key) -> Self {
+        Chord {
+            ctrl: false,
+            shift: false,
+            key,
+        }
+    }
+
+    /// Ctrl and a key
+    pub fn ctrl(key: Key) -> Self {
+        Chord {
+            ctrl: true,
+            ..Chord::key(key)
+        }
+    }
+
+    /// Parse `[ctrl+][shift+]<key>`, e.g. `b`, `[`, `ctrl+shift+z`
+    /// (case-insensitive)
+    pub fn parse(s: &str) -> Option<Self> {
+        let lower = s.trim().to_ascii_lowercase();
+        let mut rest = lower.as_str();
+        let mut chord = Chord::key(Key::Unknown);
+        loop {
+            if let Some(r) = rest.strip_prefix("ctrl+").filter(|r| !r.is_empty()) {
+                chord.ctrl = true;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("shift+").filter(|r| !r.is_empty()) {
+                chord.shift = true;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        chord.key = key_from_name(rest)?;
+        Some(chord)
+    }
+
+    /// The character typing this chord sends, for chords without Ctrl on
+    /// letter, digit, and punctuation keys
+    pub fn typed_char(&self) -> Option<char> {
+        if self.ctrl {
+            return None;
+        }
+        let name = key_name(self.key)?;
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if self.shift => Some(c.to_ascii_uppercase()),
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        write!(
+            f,
+            "{}",
+            key_name(self.key).unwrap_or_else(|| "?".to_string())
+        )
+    }
+}
+
+/// What a shortcut does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Pick a drawing tool
+    Tool(ToolMode),
+    /// Shrink the brush by one
+    SizeDown,
+    /// Grow the brush by one
+    SizeUp,
+    /// Pick a palette color for the edge
+    Color(usize),
+    Clear,
+    /// Save a snapshot to the default path
+    Snapshot,
+    Undo,
+    Redo,
+}
+
+impl Action {
+    /// Parse `tool <name>`, `size down`, `size up`, `color <index>`, `clear`,
+    /// `snapshot`, `undo`, or `redo`
+    pub fn parse(s: &str) -> Option<Self> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words.as_slice() {
+            ["tool", name] => TOOLBAR_TOOLS
+                .iter()
+                .chain(&[ToolMode::Eyedropper])
+                .find(|tool| tool.name() == *name)
+                .map(|&tool| Action::Tool(tool)),
+            ["size", "down"] => Some(Action::SizeDown),
+            ["size", "up"] => Some(Action::SizeUp),
+            ["color", index] => index
+                .parse()
+                .ok()
+                .filter(|&i| i < PALETTE_LEN)
+                .map(Action::Color),
+            ["clear"] => Some(Action::Clear),
+            ["snapshot"] => Some(Action::Snapshot),
+            ["undo"] => Some(Action::Undo),
+            ["redo"] => Some(Action::Redo),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Action::Tool(tool) => write!(f, "tool {}", tool.name()),
+            Action::SizeDown => write!(f, "size down"),
+            Action::SizeUp => write!(f, "size up"),
+            Action::Color(index) => write!(f, "color {}", index),
+            Action::Clear => write!(f, "clear"),
+            Action::Snapshot => write!(f, "snapshot"),
+            Action::Undo => write!(f, "undo"),
+            Action::Redo => write!(f, "redo"),
+        }
+    }
+}
+
+/// The chords bound to actions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Default for Keymap {
+    /// B/L/R/C/O/T pick tools, `[` and `]` change the brush size, 1-9 and 0
+    /// pick the first ten palette colors, Ctrl+N clears, Ctrl+S saves a
+    /// snapshot, Ctrl+Z undoes, and Ctrl+Y or Ctrl+Shift+Z redoes
+    fn default() -> Self {
+        let mut bindings = vec![
+            (Chord::key(Key::B), Action::Tool(ToolMode::Brush)),
+            (Chord::key(Key::L), Action::Tool(ToolMode::Line)),
+            (Chord::key(Key::R), Action::Tool(ToolMode::Rectangle)),
+            (Chord::key(Key::C), Action::Tool(ToolMode::Circle)),
+            (Chord::key(Key::O), Action::Tool(ToolMode::Oval)),
+            (Chord::key(Key::T), Action::Tool(ToolMode::Triangle)),
+            (Chord::key(Key::LeftBracket), Action::SizeDown),
+            (Chord::key(Key::RightBracket), Action::SizeUp),
+            (Chord::ctrl(Key::N), Action::Clear),
+            (Chord::ctrl(Key::S), Action::Snapshot),
+            (Chord::ctrl(Key::Z), Action::Undo),
+            (Chord::ctrl(Key::Y), Action::Redo),
+            (
+                Chord {
+                    shift: true,
+                    ..Chord::ctrl(Key::Z)
+                },
+                Action::Redo,
+            ),
+        ];
+        // 1-9 then 0, in keyboard order
+        for (i, &key) in DIGIT_KEYS[1..].iter().chain(&DIGIT_KEYS[..1]).enumerate() {
+            bindings.push((Chord::key(key), Action::Color(i)));
+        }
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// A keymap with no bindings
+    pub fn empty() -> Self {
+        Keymap {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// The default keymap with a config file's lines applied (see `apply`)
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut keymap = Keymap::default();
+        keymap
+            .apply(&text)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Ok(keymap)
+    }
+
+    /// Apply config lines of `chord = action` (or `chord = none` to unbind),
+    /// skipping blank lines and `#` comments
+    pub fn apply(&mut self, text: &str) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Split at the last `=`, so the `=` key can be bound too
+            let (chord, action) = line
+                .rsplit_once('=')
+                .filter(|(chord, _)| !chord.trim().is_empty())
+                .ok_or_else(|| format!("line {}: expected 'chord = action'", i + 1))?;
+            let chord = Chord::parse(chord)
+                .ok_or_else(|| format!("line {}: unknown key '{}'", i + 1, chord.trim()))?;
+            match action.trim() {
+                "none" => self.unbind(chord),
+                action => self.bind(
+                    chord,
+                    Action::parse(action)
+                        .ok_or_else(|| format!("line {}: unknown action '{}'", i + 1, action))?,
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Bind `chord` to `action`, replacing what it was bound to
+    pub fn bind(&mut self, chord: Chord, action: Action) {
+        self.unbind(chord);
+        self.bindings.push((chord, action));
+    }
+
+    /// Remove `chord`'s binding, if it has one
+    pub fn unbind(&mut self, chord: Chord) {
+        self.bindings.retain(|(bound, _)| *bound != chord);
+    }
+
+    /// The action bound to `chord`
+    pub fn lookup(&self, chord: Chord) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == chord)
+            .map(|&(_, action)| action)
+    }
+
+    /// All bindings, in the order they were made
+    pub fn iter(&self) -> impl Iterator<Item = &(Chord, Action)> {
+        self.bindings.iter()
+    }
+
+    /// The keys some binding uses, each once
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys: Vec<Key> = Vec::new();
+        for (chord, _) in &self.bindings {
+            if !keys.contains(&chord.key) {
+                keys.push(chord.key);
+            }
+        }
+        keys
+    }
+}
+
+/// Turns which keys are held down into which were just pressed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyEdges {
+    keys: Vec<Key>,
+    down: Vec<Key>,
+}
+
+impl KeyEdges {
+    /// Track presses of `keys`
+    pub fn new(keys: Vec<Key>) -> Self {
+        KeyEdges {
+            keys,
+            down: Vec::new(),
+        }
+    }
+
+    /// Poll every tracked key with `is_down`, returning those that are down
+    /// now but weren't at the last update
+    pub fn update(&mut self, is_down: impl Fn(Key) -> bool) -> Vec<Key> {
+        let down: Vec<Key> = self.keys.iter().copied().filter(|&k| is_down(k)).collect();
+        let pressed = down
+            .iter()
+            .copied()
+            .filter(|k| !self.down.contains(k))
+            .collect();
+        self.down = down;
+        pressed
+    }
+}
@@ -0,0 +1,99 @@
+//! Chart legends.
+//!
+//! This module handles:
+//! - `LegendEntry`, a colored swatch and the label beside it
+//! - Sizing the box that holds a legend's entries, one per row
+//! - Drawing the box, swatches, and labels onto the canvas
+//!
+//! A legend is drawn in one command (`legend x,y entry "label" <color> ...`)
+//! so a chart built from `plot` lines can be labelled without working out
+//! where each swatch and label goes. Like any other drawing it becomes canvas
+//! pixels; nothing about it is retained.
+
+use crate::drawing::{draw_line, fill_rectangle};
+use crate::font::{draw_text, text_width, GLYPH_HEIGHT};
+use crate::{canvas_bottom, window_width, CANVAS_TOP, WHITE};
+
+/// Most entries one legend may have
+pub const MAX_LEGEND_ENTRIES: usize = 16;
+/// Longest label, in characters
+pub const MAX_LEGEND_LABEL_LEN: usize = 40;
+/// Pixels between the box's border and its contents
+const LEGEND_PADDING: usize = 5;
+/// Side of a color swatch
+const SWATCH_SIZE: usize = GLYPH_HEIGHT + 2;
+/// Pixels between a swatch and its label
+const SWATCH_GAP: usize = 4;
+/// Pixels between consecutive rows
+const ROW_GAP: usize = 3;
+
+/// One row of a legend
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegendEntry {
+    pub label: String,
+    /// Swatch color as `0xRRGGBB`
+    pub color: u32,
+}
+
+/// Width and height of the box holding `entries`, border included
+pub fn legend_size(entries: &[LegendEntry]) -> (usize, usize) {
+    let widest = entries
+        .iter()
+        .map(|entry| text_width(&entry.label))
+        .max()
+        .unwrap_or(0);
+    let rows = entries.len();
+    (
+        2 * (LEGEND_PADDING + 1) + SWATCH_SIZE + SWATCH_GAP + widest,
+        2 * (LEGEND_PADDING + 1) + rows * SWATCH_SIZE + rows.saturating_sub(1) * ROW_GAP,
+    )
+}
+
+/// Draw a white box with its top-left corner at (x, y), bordered in `color`,
+/// holding a swatch and a label (also in `color`) for each entry
+///
+/// Labels that would leave the canvas are left out.
+pub fn draw_legend(buffer: &mut [u32], x: usize, y: usize, entries: &[LegendEntry], color: u32) {
+    if x >= window_width() || y >= canvas_bottom() {
+        return;
+    }
+    let (width, height) = legend_size(entries);
+    let (right, bottom) = (x + width - 1, y + height - 1);
+    fill_rectangle(buffer, x, y, right, bottom, WHITE);
+    draw_line(buffer, x, y, right, y, color);
+    draw_line(buffer, right, y, right, bottom, color);
+    draw_line(buffer, right, bottom, x, bottom, color);
+    draw_line(buffer, x, bottom, x, y, color);
+
+    let left = x + 1 + LEGEND_PADDING;
+    for (i, entry) in entries.iter().enumerate() {
+        let top = y + 1 + LEGEND_PADDING + i * (SWATCH_SIZE + ROW_GAP);
+        let (swatch_right, swatch_bottom) = (left + SWATCH_SIZE - 1, top + SWATCH_SIZE - 1);
+        fill_rectangle(buffer, left, top, swatch_right, swatch_bottom, entry.color);
+        // Outlined, so a white swatch still shows
+        draw_line(buffer, left, top, swatch_right, top, color);
+        draw_line(
+            buffer,
+            swatch_right,
+            top,
+            swatch_right,
+            swatch_bottom,
+            color,
+        );
+        draw_line(
+            buffer,
+            swatch_right,
+            swatch_bottom,
+            left,
+            swatch_bottom,
+            color,
+        );
+        draw_line(buffer, left, swatch_bottom, left, top, color);
+
+        let label_top = top + (SWATCH_SIZE - GLYPH_HEIGHT) / 2;
+        if label_top >= CANVAS_TOP && label_top + GLYPH_HEIGHT <= canvas_bottom() {
+            let label_left = left + SWATCH_SIZE + SWATCH_GAP;
+            draw_text(buffer, label_left, label_top, &entry.label, color);
+        }
+    }
+}
@@ -3,24 +3,150 @@
 //! This library provides the core functionality for the displai application,
 //! including drawing primitives, UI rendering, and command handling.
 
-use minifb::{Key, MouseButton, MouseMode, Window, WindowOptions};
+use minifb::{InputCallback, Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 use std::io::{self, BufRead, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
-use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+pub mod access;
+pub mod alias;
+pub mod annotate;
+pub mod announce;
+pub mod antialias;
+pub mod autosave;
+pub mod bezier;
+pub mod blend;
+pub mod brush;
+pub mod canvas;
+pub mod capture;
+pub mod clipboard;
+pub mod clock;
+pub mod colors;
 pub mod command;
+pub mod compare;
+pub mod config;
+pub mod dash;
+pub mod debugger;
+pub mod dialog;
+pub mod diff;
 pub mod drawing;
+pub mod events;
+pub mod filters;
+pub mod focus;
+pub mod font;
+pub mod fuzz;
+pub mod gif;
+pub mod gradient;
+pub mod history;
+pub mod icc;
+pub mod import;
+pub mod include;
+pub mod keymap;
+pub mod legend;
+pub mod locks;
+pub mod notify;
+pub mod objects;
+pub mod offscreen;
+pub mod palette;
+pub mod panes;
+pub mod polygon;
+pub mod poster;
+pub mod project;
+pub mod protocol;
+pub mod readback;
+pub mod record;
+pub mod redraw;
+pub mod resize;
+pub mod script;
+pub mod selection;
+pub mod shadow;
+pub mod snap;
+pub mod snapshot;
+pub mod sound;
+pub mod state;
+pub mod stats;
+pub mod strokes;
+pub mod svg;
+pub mod tabs;
+pub mod transform;
 pub mod ui;
+pub mod vars;
+pub mod watch;
 
+pub use access::*;
+pub use alias::*;
+pub use annotate::*;
+pub use announce::*;
+pub use antialias::*;
+pub use autosave::*;
+pub use bezier::*;
+pub use blend::*;
+pub use brush::*;
+pub use canvas::*;
+pub use capture::*;
+pub use clipboard::*;
+pub use clock::*;
+pub use colors::*;
 pub use command::*;
+pub use compare::*;
+pub use config::*;
+pub use dash::*;
+pub use debugger::*;
+pub use dialog::*;
+pub use diff::*;
 pub use drawing::*;
+pub use events::*;
+pub use filters::*;
+pub use focus::*;
+pub use font::*;
+pub use fuzz::*;
+pub use gif::*;
+pub use gradient::*;
+pub use history::*;
+pub use icc::*;
+pub use import::*;
+pub use include::*;
+pub use keymap::*;
+pub use legend::*;
+pub use locks::*;
+pub use notify::*;
+pub use objects::*;
+pub use offscreen::*;
+pub use palette::*;
+pub use panes::*;
+pub use polygon::*;
+pub use poster::*;
+pub use project::*;
+pub use protocol::*;
+pub use readback::*;
+pub use record::*;
+pub use redraw::*;
+pub use resize::*;
+pub use script::*;
+pub use selection::*;
+pub use shadow::*;
+pub use snap::*;
+pub use snapshot::*;
+pub use sound::*;
+pub use state::*;
+pub use stats::*;
+pub use strokes::*;
+pub use svg::*;
+pub use tabs::*;
+pub use transform::*;
 pub use ui::*;
+pub use vars::*;
+pub use watch::*;
 
 // ============================================================================
 // Constants
 // ============================================================================
 
+/// Default window size; the live size is `window_width()`/`window_height()`
 pub const WIDTH: usize = 800;
 pub const HEIGHT: usize = 600;
 pub const WHITE: u32 = 0xFFFFFF;
@@ -54,13 +180,14 @@ pub const BUTTON_MARGIN: usize = 3;
 pub const BOTTOM_TOOLBAR_HEIGHT: usize = 60;
 pub const TOOLBAR_ROW_HEIGHT: usize = 30;
 pub const CANVAS_TOP: usize = TITLE_BAR_HEIGHT;
+/// Canvas bottom at the default size; the live value is `canvas_bottom()`
 pub const CANVAS_BOTTOM: usize = HEIGHT - BOTTOM_TOOLBAR_HEIGHT;
 
 pub const MIN_BRUSH_SIZE: usize = 1;
 pub const MAX_BRUSH_SIZE: usize = 20;
 pub const DEFAULT_BRUSH_SIZE: usize = 1;
 
-pub const SOCKET_PATH: &str = "/tmp/displai.sock";
+pub const SNAPSHOT_PATH: &str = "canvas.png";
 
 // ============================================================================
 // Types
@@ -77,166 +204,1413 @@ pub enum ToolMode {
     Circle,
     Oval,
     Triangle,
+    Bucket,
+    Polygon,
+    Bezier,
+    RoundedRect,
+    Arrow,
+    Select,
+    Eyedropper,
+}
+
+impl ToolMode {
+    /// Short name, matching the protocol verb that draws the same shape
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToolMode::Brush => "brush",
+            ToolMode::Line => "line",
+            ToolMode::Square => "square",
+            ToolMode::Rectangle => "rect",
+            ToolMode::Circle => "circle",
+            ToolMode::Oval => "oval",
+            ToolMode::Triangle => "triangle",
+            ToolMode::Bucket => "bucket",
+            ToolMode::Polygon => "polygon",
+            ToolMode::Bezier => "bezier",
+            ToolMode::RoundedRect => "rrect",
+            ToolMode::Arrow => "arrow",
+            ToolMode::Select => "select",
+            ToolMode::Eyedropper => "eyedropper",
+        }
+    }
 }
 
 // ============================================================================
 // Socket/Stdin Communication
 // ============================================================================
 
-/// Spawn a thread that reads lines from stdin and sends them to the receiver
-fn spawn_stdin_reader() -> Receiver<String> {
-    let (tx, rx) = mpsc::channel();
+/// Where the response to an incoming command line should be written
+enum Reply {
+    Stdout,
+    Socket(UnixStream),
+    Silent, // Lines run on the session's behalf (replay, debug step)
+    /// Commands an alias stands for, whose responses make up the alias's
+    Collect(Sender<CommandResult>),
+}
+
+/// The reply channel for one command line, plus its request ID if it had one
+struct Responder {
+    id: Option<String>,
+    reply: Reply,
+}
+
+impl Responder {
+    fn write(self, response: &str) {
+        let line = frame_response(self.id.as_deref(), response);
+        match self.reply {
+            Reply::Stdout => {
+                println!("{}", line);
+                let _ = io::stdout().flush();
+            }
+            Reply::Socket(mut stream) => {
+                let _ = writeln!(stream, "{}", line);
+            }
+            // Collected results are sent whole by `answer`
+            Reply::Silent | Reply::Collect(_) => {}
+        }
+    }
+
+    /// Deliver a command's result: its output, "ok" if it has none, or its
+    /// error
+    fn answer(self, result: CommandResult) {
+        match &self.reply {
+            Reply::Collect(results) => {
+                let _ = results.send(result);
+            }
+            _ => self.write(&response_line(&result)),
+        }
+    }
+
+    /// A writer for events sent back over this reply channel (None when the
+    /// line gets no reply)
+    fn event_sink(&self) -> Option<Box<dyn Write + Send>> {
+        match &self.reply {
+            Reply::Stdout => Some(Box::new(io::stdout())),
+            Reply::Socket(stream) => stream
+                .try_clone()
+                .ok()
+                .map(|s| Box::new(s) as Box<dyn Write + Send>),
+            Reply::Silent | Reply::Collect(_) => None,
+        }
+    }
+}
+
+/// A command line received from stdin or the socket, with who sent it and
+/// where to reply
+struct Incoming {
+    line: String,
+    idempotency_key: Option<String>,
+    client: ClientId,
+    responder: Responder,
+}
 
+impl Incoming {
+    /// Split off the request ID and pair the line with its sender and reply channel
+    fn new(line: &str, client: ClientId, reply: Reply) -> Self {
+        let (id, rest) = split_request_id(line);
+        let (key, command) = split_idempotency_key(rest);
+        Incoming {
+            line: command.to_string(),
+            idempotency_key: key.map(str::to_string),
+            client,
+            responder: Responder {
+                id: id.map(str::to_string),
+                reply,
+            },
+        }
+    }
+}
+
+/// Spawn a thread that reads lines from stdin and forwards them
+/// The thread finishes when stdin is closed.
+fn spawn_stdin_reader(tx: Sender<Incoming>) -> thread::JoinHandle<()> {
     thread::spawn(move || {
         let stdin = io::stdin();
         let reader = stdin.lock();
 
         for line in reader.lines().map_while(Result::ok) {
-            if tx.send(line).is_err() {
+            if tx
+                .send(Incoming::new(&line, STDIN_CLIENT, Reply::Stdout))
+                .is_err()
+            {
                 break;
             }
         }
-    });
+    })
+}
 
-    rx
+/// Queue the `--script` commands, to run before any others as if sent on
+/// stdin
+fn queue_script(config: &Config, tx: &Sender<Incoming>) {
+    for line in &config.script {
+        let _ = tx.send(Incoming::new(line, STDIN_CLIENT, Reply::Stdout));
+    }
 }
 
-/// A command received from the socket, with an optional stream to write the response back to
-struct SocketCommand {
-    line: String,
-    stream: Option<UnixStream>, // Only first command per connection gets response
+/// Save the canvas to the `--persist` file, if any, for the next start to
+/// restore
+fn persist_canvas(config: &Config, canvas: &[u32]) {
+    if let Some(path) = &config.persist {
+        if let Err(e) = save_canvas_image(canvas, path) {
+            eprintln!("displai: cannot save canvas to {}: {}", path, e);
+        }
+    }
 }
 
-/// Spawn a thread that listens on a Unix socket and sends received commands to the receiver
-/// Supports multi-line mode: all lines in a connection are processed, but only the first gets a response
-fn spawn_unix_socket_listener() -> Receiver<SocketCommand> {
-    let (tx, rx) = mpsc::channel();
+/// Remove the socket file `config` listened on, and any ready file, so they
+/// don't outlive the process
+fn remove_socket(config: &Config) {
+    if let Some(path) = &config.socket {
+        let _ = std::fs::remove_file(path);
+    }
+    if let Some(path) = &config.ready_file {
+        remove_ready_file(path);
+    }
+}
+
+/// Announce `ready` through the ready file and beacon `config` asks for,
+/// once the socket (if any) is listening
+fn announce_ready(config: &Config, ready: &Event) {
+    let line = ready.to_string();
+    if let Some(path) = &config.ready_file {
+        if let Err(e) = write_ready_file(path, &line) {
+            eprintln!("displai: cannot write ready file {}: {}", path, e);
+        }
+    }
+    if let Some(addr) = &config.beacon {
+        if let Err(e) = send_beacon(addr, &line) {
+            eprintln!("displai: cannot send beacon to {}: {}", addr, e);
+        }
+    }
+}
+
+/// Listen on the Unix socket at `path`, and spawn a thread that forwards
+/// received commands
+/// The socket is bound (and its path printed to stderr) before this returns,
+/// so clients that read the path can connect straight away.
+/// Every line of a connection is processed and answered, in order.
+/// `clients` tracks how many connections are currently open. Each connection
+/// gets its own client id, which is sent on `closed` when it ends.
+fn spawn_unix_socket_listener(
+    path: String,
+    tx: Sender<Incoming>,
+    clients: Arc<AtomicUsize>,
+    closed: Sender<ClientId>,
+) {
+    // Bind beside the path and rename the socket over it, so a stale socket
+    // from an earlier run is replaced at once: clients watching the path see
+    // one change, and never a moment with nothing there
+    let partial = format!("{}.{}.tmp", path, std::process::id());
+    let _ = std::fs::remove_file(&partial);
+    let listener = match UnixListener::bind(&partial).and_then(|listener| {
+        std::fs::rename(&partial, &path)?;
+        Ok(listener)
+    }) {
+        Ok(listener) => listener,
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            eprintln!("displai: cannot listen on {}: {}", path, e);
+            return;
+        }
+    };
+    // On stderr, as stdout carries replies to stdin
+    eprintln!("displai: listening on {}", path);
 
     thread::spawn(move || {
-        // Remove stale socket file if it exists
-        let _ = std::fs::remove_file(SOCKET_PATH);
-
-        if let Ok(listener) = UnixListener::bind(SOCKET_PATH) {
-            for stream in listener.incoming().flatten() {
-                let tx = tx.clone();
-                // Handle each connection in its own thread to avoid blocking
-                thread::spawn(move || {
-                    let mut stream_for_response = stream.try_clone().ok();
-                    let reader = io::BufReader::new(stream);
-                    for line in reader.lines().map_while(Result::ok) {
-                        // First command gets the response stream, subsequent commands get None
-                        let response_stream = stream_for_response.take();
-                        if tx
-                            .send(SocketCommand {
-                                line,
-                                stream: response_stream,
-                            })
-                            .is_err()
-                        {
-                            return;
+        let next_client = AtomicU64::new(STDIN_CLIENT + 1);
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            let closed = closed.clone();
+            let clients = Arc::clone(&clients);
+            let client = next_client.fetch_add(1, Ordering::Relaxed);
+            clients.fetch_add(1, Ordering::Relaxed);
+            // Handle each connection in its own thread to avoid blocking
+            thread::spawn(move || {
+                let response_stream = stream.try_clone().ok();
+                let reader = io::BufReader::new(stream);
+                for line in reader.lines().map_while(Result::ok) {
+                    let reply = match response_stream.as_ref().map(UnixStream::try_clone) {
+                        Some(Ok(out)) => Reply::Socket(out),
+                        _ => Reply::Silent,
+                    };
+                    if tx.send(Incoming::new(&line, client, reply)).is_err() {
+                        break;
+                    }
+                }
+                clients.fetch_sub(1, Ordering::Relaxed);
+                let _ = closed.send(client);
+            });
+        }
+    });
+}
+
+// ============================================================================
+// Command Session
+// ============================================================================
+
+/// Keys the run loop routes through focus (typed characters arrive separately),
+/// and whether holding them repeats
+const ROUTED_KEYS: [(Key, KeyInput, KeyRepeat); 11] = [
+    (Key::Tab, KeyInput::Tab, KeyRepeat::No),
+    (Key::Enter, KeyInput::Enter, KeyRepeat::No),
+    (Key::NumPadEnter, KeyInput::Enter, KeyRepeat::No),
+    (Key::Backspace, KeyInput::Backspace, KeyRepeat::Yes),
+    (Key::Escape, KeyInput::Escape, KeyRepeat::No),
+    (Key::Left, KeyInput::Left, KeyRepeat::Yes),
+    (Key::Right, KeyInput::Right, KeyRepeat::Yes),
+    (Key::Up, KeyInput::Up, KeyRepeat::Yes),
+    (Key::Down, KeyInput::Down, KeyRepeat::Yes),
+    (Key::Home, KeyInput::Home, KeyRepeat::No),
+    (Key::End, KeyInput::End, KeyRepeat::No),
+];
+
+/// Open the window at `size`, presenting at most once per `interval` and
+/// sending typed characters to `typed`
+fn open_window(title: &str, size: Dimensions, interval: Duration, typed: &Sender<char>) -> Window {
+    let mut window = Window::new(title, size.width, size.height, WindowOptions::default())
+        .expect("Failed to create window");
+    window.limit_update_rate(Some(interval));
+    window.set_input_callback(Box::new(TypedChars(typed.clone())));
+    window
+}
+
+/// Forwards characters typed into the window to the run loop, for focused widgets
+struct TypedChars(Sender<char>);
+
+impl InputCallback for TypedChars {
+    fn add_char(&mut self, uni_char: u32) {
+        if let Some(c) = char::from_u32(uni_char) {
+            let _ = self.0.send(c);
+        }
+    }
+}
+
+/// Everything protocol commands act on, shared by the windowed and headless loops
+struct Session {
+    state: AppState,
+    history: History,
+    /// None encodes snapshots inline (headless mode has no frame rate to protect)
+    snapshots: Option<SnapshotWorker<Responder>>,
+    capture: Option<FrameCapture>,
+    /// The animated GIF being recorded, if any
+    gif: Option<GifRecorder>,
+    /// Where executed commands are logged, while `record` is on
+    recorder: Option<Recorder>,
+    /// The recording being played back, if any
+    replay: Option<Replay>,
+    /// Who started the replay; replayed commands run as that client
+    replay_client: ClientId,
+    /// Strokes being drawn by `playstrokes`
+    playback: Option<StrokePlayback>,
+    /// The script being stepped through with `debug step`, if any
+    stepper: Option<Stepper>,
+    /// Who loaded the script; commands stepped with F10 run as that client
+    stepper_client: ClientId,
+    /// Canvas regions locked by clients with `lockregion`
+    locks: RegionLocks,
+    /// Canvas regions watched with `watch`
+    watches: Watches,
+    /// The split view opened with `compare`, drawn over the presented frame
+    compare: Option<CompareView>,
+    /// The overlay opened with `diff overlay`, drawn over the presented frame
+    diff_overlay: Option<DiffOverlay>,
+    /// Each client's access (read-only or read-write)
+    access: AccessControl,
+    /// Socket connections that have closed, whose locks (and access granted
+    /// by `auth`) are released
+    closed_tx: Sender<ClientId>,
+    closed_rx: Receiver<ClientId>,
+    /// Results of snapshots taken with Ctrl+S, shown as toasts once encoded
+    shortcut_snapshots_tx: Sender<CommandResult>,
+    shortcut_snapshots_rx: Receiver<CommandResult>,
+    idempotency_keys: IdempotencyCache,
+    commands_executed: u64,
+    stats_visible: bool,
+    /// Whether the history panel is shown (F4)
+    history_visible: bool,
+    /// Whether responses carry their command's execution time (`timing on`)
+    timing: bool,
+    /// Toasts shown by `notify`, drawn over the presented frame only
+    toasts: Toasts,
+    fps_counter: FpsCounter,
+    clients: Arc<AtomicUsize>,
+    /// Connections (or stdout) that sent `subscribe`
+    subscribers: Subscribers,
+    /// First event each subscriber gets, naming this run of displai
+    ready: Event,
+    /// Saves the session now and then, if it is kept (`--session`)
+    autosave: Option<Autosave>,
+    /// The image being marked up (`--annotate`), which Ctrl+S saves a copy of
+    annotation: Option<Annotation>,
+    /// The canvases not shown, and which one is (`canvas new|switch|close`)
+    canvases: Canvases,
+}
+
+impl Session {
+    /// Start a session on a blank canvas, with client access from `config`;
+    /// call after `set_dimensions(config.size)`
+    fn new(config: &Config, snapshots: Option<SnapshotWorker<Responder>>) -> Self {
+        let (closed_tx, closed_rx) = mpsc::channel();
+        let (shortcut_snapshots_tx, shortcut_snapshots_rx) = mpsc::channel();
+        let mut state = AppState::with_canvas(Canvas::with_size(config.size));
+        state.antialias = config.antialias;
+        if let Some(img) = &config.load {
+            paste_image(&mut state.canvas, img, 0, CANVAS_TOP, 1.0);
+        }
+        if config.annotate.is_some() {
+            state.current_tool = ToolMode::Brush;
+            state.edge_color = state.palette.get(ANNOTATE_COLOR);
+            state.brush_size = ANNOTATE_BRUSH_SIZE;
+        }
+        Session {
+            history: History::with_baseline(&state.canvas),
+            state,
+            snapshots,
+            capture: None,
+            gif: None,
+            recorder: None,
+            replay: None,
+            replay_client: STDIN_CLIENT,
+            playback: None,
+            stepper: None,
+            stepper_client: STDIN_CLIENT,
+            locks: RegionLocks::new(),
+            watches: Watches::new(),
+            compare: None,
+            diff_overlay: None,
+            access: AccessControl::new(
+                config.stdin_access,
+                config.socket_access,
+                config.tokens.clone(),
+            ),
+            closed_tx,
+            closed_rx,
+            shortcut_snapshots_tx,
+            shortcut_snapshots_rx,
+            idempotency_keys: IdempotencyCache::default(),
+            commands_executed: 0,
+            stats_visible: false,
+            history_visible: false,
+            timing: false,
+            toasts: Toasts::new(),
+            fps_counter: FpsCounter::new(),
+            clients: Arc::new(AtomicUsize::new(0)),
+            subscribers: Subscribers::new(),
+            ready: Event::Ready {
+                instance: instance_id(),
+                socket: config.socket.clone(),
+            },
+            autosave: None,
+            annotation: config.annotate.clone(),
+            canvases: Canvases::new(),
+        }
+    }
+
+    /// Start autosaving to the session directory, if there is one, first
+    /// restoring what was saved there (unless `--no-restore`)
+    ///
+    /// An image from `--load` or `--persist` takes the place of the saved
+    /// canvas; the saved tool settings still apply.
+    fn start_autosave(&mut self, config: &Config) {
+        let Some(dir) = &config.session else {
+            return;
+        };
+        let mut autosave = Autosave::new(dir, AUTOSAVE_INTERVAL, Instant::now());
+        if config.restore {
+            match read_session(dir) {
+                Ok(Some(saved)) => {
+                    if config.load.is_none() {
+                        paste_image(&mut self.state.canvas, &saved.canvas, 0, CANVAS_TOP, 1.0);
+                        self.history = History::with_baseline(&self.state.canvas);
+                    }
+                    if let Err(e) = apply_session_settings(&mut self.state, &saved.settings) {
+                        eprintln!("displai: cannot restore settings from {}: {}", dir, e);
+                    }
+                    autosave.mark_saved(&self.state);
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("displai: cannot restore session from {}: {}", dir, e),
+            }
+        }
+        self.autosave = Some(autosave);
+    }
+
+    /// Save the session if one is kept and a save is due
+    fn autosave(&mut self, now: Instant) {
+        if let Some(autosave) = &mut self.autosave {
+            if let Err(e) = autosave.offer(&self.state, now) {
+                eprintln!("displai: cannot autosave to {}: {}", autosave.dir(), e);
+            }
+        }
+    }
+
+    /// Save the session one last time, on the way out
+    fn finish_autosave(&mut self) {
+        if let Some(autosave) = self.autosave.take() {
+            let dir = autosave.dir().to_string();
+            if let Err(e) = autosave.finish(&self.state) {
+                eprintln!("displai: cannot autosave to {}: {}", dir, e);
+            }
+        }
+    }
+
+    /// Gather the counters shown by the stats overlay and the `stats` command
+    fn stats(&self) -> SessionStats {
+        SessionStats {
+            pixels_drawn: self.history.pixels_changed(),
+            commands: self.commands_executed,
+            clients: self.clients.load(Ordering::Relaxed),
+            fps: self.fps_counter.fps(),
+            memory_bytes: self.state.canvas.len() * std::mem::size_of::<u32>()
+                + self.history.pixel_bytes(),
+        }
+    }
+
+    /// Parse and apply one command line, replying to its sender
+    ///
+    /// Returns the command if it parsed, so the caller can apply anything that
+    /// belongs to its loop (frame pacing) and schedule a redraw.
+    fn handle(&mut self, incoming: Incoming) -> Option<Command> {
+        // Palette indices and aliases in the line mean this session's
+        let cmd = match self.state.parse(&incoming.line) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                incoming.responder.answer(Err(e));
+                return None;
+            }
+        };
+        match cmd {
+            Command::RunAlias { .. } => self.run_alias(cmd, incoming),
+            _ => self.run(cmd, incoming),
+        }
+    }
+
+    /// Run an alias's commands in turn as if its sender had sent them,
+    /// replying once for them all (see `alias_response`)
+    ///
+    /// Each command is checked and recorded on its own, and their changes
+    /// are one history entry named after the alias; the alias's idempotency
+    /// key covers them all. Returns the last command that ran, or the alias
+    /// if none did.
+    fn run_alias(&mut self, alias: Command, incoming: Incoming) -> Option<Command> {
+        let Command::RunAlias { name, commands, .. } = &alias else {
+            return self.run(alias, incoming);
+        };
+        let key = incoming
+            .idempotency_key
+            .as_deref()
+            .filter(|_| alias.is_mutating());
+        if key.is_some_and(|key| self.idempotency_keys.contains(key)) {
+            incoming.responder.answer(reply(DUPLICATE_RESPONSE));
+            return Some(alias);
+        }
+        let executed = self.commands_executed;
+        // The alias is timed as a whole
+        let timing = std::mem::replace(&mut self.timing, false);
+        let started = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        let mut responses = Vec::new();
+        let mut last = None;
+        self.history.begin_group(name);
+        for cmd in commands {
+            last = self.run(
+                cmd.clone(),
+                Incoming {
+                    line: cmd.to_string(),
+                    idempotency_key: None,
+                    client: incoming.client,
+                    responder: Responder {
+                        id: None,
+                        reply: Reply::Collect(tx.clone()),
+                    },
+                },
+            );
+            // Snapshots encoded on the worker reply later, and go unheard
+            let response = rx.try_recv().unwrap_or(Ok(CommandOutput::Ok));
+            let refused = response.is_err();
+            responses.push(response);
+            if refused {
+                break;
+            }
+        }
+        let _ = self.history.end_group(&self.state.canvas);
+        // The key is used up once any of the alias's commands ran
+        if let Some(key) = key.filter(|_| self.commands_executed > executed) {
+            self.idempotency_keys.insert(key);
+        }
+        self.timing = timing;
+        let response = alias_response(responses);
+        incoming.responder.answer(if timing {
+            timed_response(response, started.elapsed())
+        } else {
+            response
+        });
+        last.or(Some(alias))
+    }
+
+    /// Apply one parsed command, replying to its sender
+    fn run(&mut self, cmd: Command, incoming: Incoming) -> Option<Command> {
+        // Read-only clients are refused before anything is run or recorded
+        if let Err(e) = self.access.check(incoming.client, &cmd) {
+            incoming.responder.answer(failed(e));
+            return Some(cmd);
+        }
+        // Out-of-range arguments are refused with their reason, judged where
+        // the command lands
+        if let Err(e) = validate(&self.state.placed(&cmd)) {
+            incoming.responder.answer(failed(e));
+            return Some(cmd);
+        }
+        // A retried drawing command with a recently seen key must not draw twice
+        let key = incoming
+            .idempotency_key
+            .as_deref()
+            .filter(|_| cmd.is_mutating());
+        if key.is_some_and(|key| self.idempotency_keys.contains(key)) {
+            incoming.responder.answer(reply(DUPLICATE_RESPONSE));
+            return Some(cmd);
+        }
+        // Drawing into another client's locked region is refused (pane
+        // commands are judged where they land)
+        let reach = draw_reach(self.state.brush_size, self.state.shadow.as_ref());
+        let placed = self.state.placed(&cmd);
+        if let Err(e) = self.locks.check(incoming.client, &placed, reach) {
+            incoming.responder.answer(failed(e));
+            return Some(cmd);
+        }
+        // Only commands that get past every refusal use up their key, and
+        // are counted and recorded
+        if let Some(key) = key {
+            self.idempotency_keys.insert(key);
+        }
+        self.commands_executed += 1;
+        self.record(&cmd);
+        // Changes made since the last command (by the mouse) aren't this one's
+        if !self.watches.is_empty() {
+            self.watches.changed(&self.state.canvas);
+        }
+        let started = Instant::now();
+        let response = match &cmd {
+            // Encode snapshots on the worker; the reply is sent when it finishes
+            // (untimed, since encoding happens after this returns)
+            Command::Snapshot(path) if self.snapshots.is_some() => {
+                let default = default_snapshot_path();
+                let path = path.as_deref().unwrap_or(&default);
+                if let Some(snapshots) = &self.snapshots {
+                    with_color_profile(self.state.color_profile, || {
+                        snapshots.request(&self.state.render(), path, incoming.responder)
+                    });
+                }
+                return Some(cmd);
+            }
+            Command::GroupBegin(label) => {
+                self.history.begin_group(label);
+                Ok(CommandOutput::Ok)
+            }
+            Command::GroupEnd => self
+                .history
+                .end_group(&self.state.canvas)
+                .map(|_| CommandOutput::Ok)
+                .map_err(CommandError::Failed),
+            Command::Undo => self
+                .history
+                .undo(&mut self.state.canvas)
+                .map(|_| CommandOutput::Ok)
+                .map_err(CommandError::Failed),
+            Command::Redo => self
+                .history
+                .redo(&mut self.state.canvas)
+                .map(|_| CommandOutput::Ok)
+                .map_err(CommandError::Failed),
+            Command::Stats(Some(visible)) => {
+                self.stats_visible = *visible;
+                Ok(CommandOutput::Ok)
+            }
+            Command::Stats(None) => reply(self.stats().summary()),
+            // The run loop paces frames by the returned command
+            Command::Fps(_) => Ok(CommandOutput::Ok),
+            Command::Timing(on) => {
+                self.timing = *on;
+                Ok(CommandOutput::Ok)
+            }
+            Command::Notify {
+                text,
+                secs,
+                level,
+                beep,
+            } => {
+                self.toasts.push(text, *secs, *level, Instant::now());
+                if *beep {
+                    play_beep(DEFAULT_BEEP_FREQ, DEFAULT_BEEP_MS);
+                }
+                Ok(CommandOutput::Ok)
+            }
+            // Out-of-range tones were refused by `validate`
+            Command::Beep { freq, ms } => {
+                play_beep(*freq, *ms);
+                Ok(CommandOutput::Ok)
+            }
+            Command::Subscribe => {
+                if let Some(sink) = incoming.responder.event_sink() {
+                    self.subscribers.add_greeted(sink, &self.ready);
+                }
+                Ok(CommandOutput::Ok)
+            }
+            Command::Capture(target) => apply_capture_command(&mut self.capture, target.as_deref()),
+            Command::Gif(op) => apply_gif_command(&mut self.gif, op),
+            Command::Record(target) => {
+                apply_record_command(&mut self.recorder, target.as_deref(), Instant::now())
+            }
+            Command::Auth(token) => match self.access.authenticate(incoming.client, token) {
+                Ok(access) => reply(format!("access {}", access.name())),
+                Err(e) => failed(e),
+            },
+            Command::LockRegion { .. } | Command::UnlockRegion(_) => {
+                self.locks.apply(incoming.client, &cmd)
+            }
+            // Who holds each pane depends on the locks and who is asking
+            Command::State => reply(self.state.describe(&self.locks, incoming.client)),
+            Command::Layout => {
+                let mut layout = self.state.layout(&self.locks, incoming.client);
+                // Where the split view's divider is now, dragged or not
+                if let Some(view) = &self.compare {
+                    layout.push_str(&format!(" divider:{}", view.divider()));
+                }
+                reply(layout)
+            }
+            Command::Watch { .. } | Command::Unwatch(_) => {
+                self.watches.apply(&self.state.canvas, &cmd)
+            }
+            Command::Compare(op) => self.apply_compare(op),
+            Command::Diff(op) => self.apply_diff(op),
+            Command::CanvasSize(_) => self.resize_canvas(&cmd),
+            Command::Tab(op) => self.apply_tab(*op),
+            Command::Save(path) => self.save_project(path),
+            Command::Load(path) => self.load_project(path),
+            Command::Resume => match self.replay.as_mut() {
+                Some(replay) if replay.is_paused() => {
+                    replay.resume(Instant::now());
+                    reply("resumed")
+                }
+                _ => failed("no paused replay"),
+            },
+            Command::Replay { path, speed } => match Replay::start(path, *speed, Instant::now()) {
+                Ok(replay) => {
+                    let response = format!("replaying {} commands from {}", replay.len(), path);
+                    self.replay = Some(replay);
+                    self.replay_client = incoming.client;
+                    reply(response)
+                }
+                Err(e) => failed(e),
+            },
+            Command::PlayStrokes { path, speed } => {
+                match StrokePlayback::start(path, *speed, Instant::now()) {
+                    Ok(playback) => {
+                        let response = format!("playing {} strokes from {}", playback.len(), path);
+                        self.playback = Some(playback);
+                        reply(response)
+                    }
+                    Err(e) => failed(e),
+                }
+            }
+            Command::Debug(DebugCommand::Step(path)) => match Stepper::load(path) {
+                Ok(stepper) => {
+                    let response = format!("debugging {} commands from {}", stepper.len(), path);
+                    self.stepper = Some(stepper);
+                    self.stepper_client = incoming.client;
+                    reply(response)
+                }
+                Err(e) => failed(e),
+            },
+            Command::Debug(DebugCommand::Status) => match &self.stepper {
+                Some(stepper) => reply(stepper.status()),
+                None => failed("not debugging"),
+            },
+            Command::Debug(DebugCommand::Stop) => match self.stepper.take() {
+                Some(stepper) => reply(format!(
+                    "stopped at {}/{}",
+                    stepper.position(),
+                    stepper.len()
+                )),
+                None => failed("not debugging"),
+            },
+            // The stepped command replies in place of `step` (and is
+            // returned in its place, unless it didn't parse)
+            Command::Step => match self.next_step() {
+                Some(line) => {
+                    let stepped = self.handle(Incoming {
+                        line,
+                        idempotency_key: None,
+                        client: incoming.client,
+                        responder: incoming.responder,
+                    });
+                    return stepped.or(Some(cmd));
+                }
+                None => failed("not debugging"),
+            },
+            _ => {
+                let response = self.state.run_command(&cmd);
+                // A refused command changed nothing, so leaves redo alone
+                if cmd.is_mutating() && response.is_ok() {
+                    self.history.record(cmd.name(), &self.state.canvas);
+                }
+                response
+            }
+        };
+        let response = if self.timing {
+            timed_response(response, started.elapsed())
+        } else {
+            response
+        };
+        incoming.responder.answer(response);
+        self.check_watches(&cmd);
+        Some(cmd)
+    }
+
+    /// Offer the canvas to the active GIF recording
+    fn write_gif_frame(&mut self, now: Instant) {
+        if let Some(active) = self.gif.as_mut() {
+            active.offer(&self.state.render(), now);
+        }
+    }
+
+    /// Log a command to the active recording, stopping it on a write error
+    ///
+    /// `record` and `replay` themselves are left out, so replaying a
+    /// recording can't start another recording or replay, and so is `auth`,
+    /// so tokens aren't written to disk. `step` is left out too: the command
+    /// it runs is recorded instead.
+    fn record(&mut self, cmd: &Command) {
+        if matches!(
+            cmd,
+            Command::Record(_)
+                | Command::Replay { .. }
+                | Command::Auth(_)
+                | Command::Debug(_)
+                | Command::Step
+        ) {
+            return;
+        }
+        if let Some(active) = self.recorder.as_mut() {
+            if let Err(e) = active.record(cmd, Instant::now()) {
+                eprintln!("displai: recording stopped: {}", e);
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// Crop or resize the canvas, returning the protocol response
+    ///
+    /// Refused inside a group and while a capture, GIF recording, or watch
+    /// depends on the old size. Afterwards this thread lays out against the
+    /// new size, undo can't go back past it, and the split view and diff
+    /// overlay (references the size of the old window) are closed.
+    fn resize_canvas(&mut self, cmd: &Command) -> CommandResult {
+        if let Some(busy) = self.size_change_blocker() {
+            return failed(format!("cannot change the canvas size {}", busy));
+        }
+        let response = self.state.run_command(cmd);
+        if self.state.canvas.dimensions() != dimensions() {
+            set_dimensions(self.state.canvas.dimensions());
+            self.history = History::with_baseline(&self.state.canvas);
+            self.compare = None;
+            self.diff_overlay = None;
+        }
+        response
+    }
+
+    /// Why the canvas can't change size now, if it can't: inside a group,
+    /// while a capture, GIF recording, or watch depends on the size, or
+    /// while other canvases (which keep the size) are open
+    fn size_change_blocker(&self) -> Option<&'static str> {
+        if !self.canvases.is_single() {
+            Some("while several canvases are open")
+        } else {
+            self.canvas_change_blocker()
+        }
+    }
+
+    /// Why another canvas can't be shown now, if it can't: inside a group,
+    /// or while a capture, GIF recording, or watch depends on the pixels
+    fn canvas_change_blocker(&self) -> Option<&'static str> {
+        if self.history.open_group_label().is_some() {
+            Some("inside a group")
+        } else if self.capture.is_some() {
+            Some("while capturing")
+        } else if self.gif.is_some() {
+            Some("while recording a GIF")
+        } else if !self.watches.is_empty() {
+            Some("while regions are watched")
+        } else {
+            None
+        }
+    }
+
+    /// Apply a `canvas new|switch|close` command, returning the protocol
+    /// response (`canvas N`, naming the canvas shown afterwards)
+    ///
+    /// The shown canvas's pixels, undo history, display list, and stroke log
+    /// are put away and another's brought out; the split view and diff
+    /// overlay, which were against the old pixels, are closed.
+    fn apply_tab(&mut self, op: TabCommand) -> CommandResult {
+        match op {
+            TabCommand::New => self.canvases.check_open(),
+            TabCommand::Switch(n) => self.canvases.check(n),
+            TabCommand::Close(n) => self.canvases.check_close(n),
+        }?;
+        if let Some(busy) = self.canvas_change_blocker() {
+            return failed(format!("cannot change canvases {}", busy));
+        }
+        let blank = CanvasTab::blank(&self.state.canvas);
+        let shown = CanvasTab {
+            canvas: std::mem::replace(&mut self.state.canvas, blank.canvas.clone()),
+            history: std::mem::replace(&mut self.history, blank.history.clone()),
+            display_list: std::mem::take(&mut self.state.display_list),
+            strokes: std::mem::take(&mut self.state.strokes),
+        };
+        let next = match op {
+            TabCommand::New => self.canvases.open(shown, blank),
+            TabCommand::Switch(n) => self.canvases.switch(n, shown),
+            TabCommand::Close(n) => self.canvases.close(n, shown),
+        };
+        self.state.canvas = next.canvas;
+        self.history = next.history;
+        self.state.display_list = next.display_list;
+        self.state.strokes = next.strokes;
+        set_active_canvas(self.canvases.active());
+        self.compare = None;
+        self.diff_overlay = None;
+        reply(format!("canvas {}", self.canvases.active()))
+    }
+
+    /// Save the drawing and its undo history to a project file, returning
+    /// the protocol response (refused inside a group, whose changes aren't
+    /// an entry yet)
+    fn save_project(&self, path: &str) -> CommandResult {
+        if self.history.open_group_label().is_some() {
+            return failed("cannot save a project inside a group");
+        }
+        save_project(path, &self.state, &self.history)?;
+        reply(format!("saved {}", path))
+    }
+
+    /// Replace the drawing and undo history with a project file's, returning
+    /// the protocol response
+    ///
+    /// Refused inside a group, and, when the project is another size, at
+    /// the times `canvas resize` is. Nothing changes if the file can't be
+    /// read.
+    fn load_project(&mut self, path: &str) -> CommandResult {
+        let project = read_project(path)?;
+        let resized = project.canvas.dimensions() != self.state.canvas.dimensions();
+        let busy = match self.history.open_group_label() {
+            Some(_) => Some("inside a group"),
+            None if resized => self.size_change_blocker(),
+            None => None,
+        };
+        if let Some(busy) = busy {
+            return failed(format!("cannot load a project {}", busy));
+        }
+        if let Err(e) = apply_project(&mut self.state, &project) {
+            return failed(format!("cannot load {}: {}", path, e));
+        }
+        if resized {
+            set_dimensions(self.state.canvas.dimensions());
+            self.compare = None;
+            self.diff_overlay = None;
+        }
+        self.history = project.history;
+        reply(format!("loaded {}", path))
+    }
+
+    /// Apply a `compare` command, returning the protocol response
+    fn apply_compare(&mut self, op: &CompareCommand) -> CommandResult {
+        match op {
+            CompareCommand::Image(path) => {
+                let img = load_image(path)?;
+                self.compare = Some(CompareView::from_image(&img, path));
+                reply(format!("comparing with {}", path))
+            }
+            CompareCommand::Checkpoint => {
+                self.compare = Some(CompareView::from_canvas(&self.state.canvas, "checkpoint"));
+                reply("comparing with checkpoint")
+            }
+            CompareCommand::Divider(x) => match self.compare.as_mut() {
+                Some(view) => {
+                    view.set_divider(*x);
+                    Ok(CommandOutput::Ok)
+                }
+                None => failed("not comparing"),
+            },
+            CompareCommand::Off => match self.compare.take() {
+                Some(_) => Ok(CommandOutput::Ok),
+                None => failed("not comparing"),
+            },
+        }
+    }
+
+    /// Apply `diff ...`, comparing what a snapshot would save
+    fn apply_diff(&mut self, op: &DiffCommand) -> CommandResult {
+        match op {
+            DiffCommand::Image(path) => {
+                let img = load_image(path)?;
+                reply(diff_frames(&self.state.render(), &reference_frame(&img)).response())
+            }
+            DiffCommand::Overlay(path) => {
+                let img = load_image(path)?;
+                let overlay = DiffOverlay::from_image(&img, path);
+                let response = overlay.summary(&self.state.render()).response();
+                self.diff_overlay = Some(overlay);
+                reply(response)
+            }
+            DiffCommand::OverlayOff => match self.diff_overlay.take() {
+                Some(_) => Ok(CommandOutput::Ok),
+                None => failed("no diff overlay"),
+            },
+        }
+    }
+
+    /// Tell subscribers about watched regions `cmd` changed, pausing any replay
+    fn check_watches(&mut self, cmd: &Command) {
+        if self.watches.is_empty() {
+            return;
+        }
+        for id in self.watches.changed(&self.state.canvas) {
+            self.subscribers.emit(&Event::Watch {
+                id,
+                command: cmd.to_string(),
+            });
+            if let Some(replay) = self.replay.as_mut() {
+                replay.pause(Instant::now());
+            }
+        }
+    }
+
+    /// Take the next command of the script being debugged, unloading the
+    /// script once its last command has been taken
+    fn next_step(&mut self) -> Option<String> {
+        let stepper = self.stepper.as_mut()?;
+        let line = stepper.advance();
+        if stepper.is_finished() {
+            self.stepper = None;
+        }
+        line
+    }
+
+    /// Run the next command of the script being debugged as the client that
+    /// loaded it, with its reply dropped (F10 in the window)
+    fn step_script(&mut self) -> Option<Command> {
+        let line = self.next_step()?;
+        self.handle(Incoming {
+            line,
+            idempotency_key: None,
+            client: self.stepper_client,
+            responder: Responder {
+                id: None,
+                reply: Reply::Silent,
+            },
+        })
+    }
+
+    /// Carry out a keyboard shortcut from the window
+    ///
+    /// Undoing a floating selection puts it back where it was. A snapshot is
+    /// encoded on the snapshot worker, as `snapshot` is, with a toast saying
+    /// where once it's saved.
+    fn run_shortcut(&mut self, action: Action, selection: &mut Selection) {
+        let state = &mut self.state;
+        match action {
+            Action::Tool(tool) => state.current_tool = tool,
+            Action::SizeDown => {
+                state.brush_size = state.brush_size.saturating_sub(1).max(MIN_BRUSH_SIZE)
+            }
+            Action::SizeUp => state.brush_size = (state.brush_size + 1).min(MAX_BRUSH_SIZE),
+            Action::Color(index) => state.edge_color = state.palette.get(index),
+            Action::Clear => {
+                selection.clear();
+                clear_canvas(&mut state.canvas);
+                state.display_list.clear();
+                state.strokes.clear();
+                self.history.record("clear", &state.canvas);
+            }
+            Action::Snapshot => {
+                // An annotation saves its marked-up copy instead, cropped to
+                // the image
+                let default = default_snapshot_path();
+                let frame = state.render();
+                let responder = Responder {
+                    id: None,
+                    reply: Reply::Collect(self.shortcut_snapshots_tx.clone()),
+                };
+                with_color_profile(state.color_profile, || {
+                    match (&self.snapshots, &self.annotation) {
+                        (Some(snapshots), Some(annotation)) => {
+                            let size = (annotation.width, annotation.height);
+                            snapshots.request_cropped(&frame, &annotation.output, size, responder);
+                        }
+                        (Some(snapshots), None) => {
+                            snapshots.request(&frame, &default, responder);
                         }
+                        // Only headless sessions go without a worker, and
+                        // they have no keyboard
+                        (None, Some(annotation)) => responder.answer(snapshot_response(
+                            &annotation.output,
+                            &annotation.save(&frame),
+                        )),
+                        (None, None) => responder.answer(snapshot_response(
+                            &default,
+                            &save_canvas_image(&frame, &default),
+                        )),
                     }
                 });
             }
+            Action::Undo => {
+                let floating = selection.is_floating();
+                selection.cancel(&mut state.canvas);
+                if !floating {
+                    let _ = self.history.undo(&mut state.canvas);
+                }
+            }
+            Action::Redo => {
+                selection.cancel(&mut state.canvas);
+                let _ = self.history.redo(&mut state.canvas);
+            }
         }
-    });
+    }
+
+    /// Run the replayed commands that are due, returning those that parsed
+    ///
+    /// Replayed lines are handled like any other, with their replies dropped.
+    fn advance_replay(&mut self, now: Instant) -> Vec<Command> {
+        // One line at a time, so a watch that pauses the replay stops the rest
+        let mut handled = Vec::new();
+        while let Some(replay) = self.replay.as_mut() {
+            let Some(line) = replay.next_due(now) else {
+                break;
+            };
+            if replay.is_finished() {
+                self.replay = None;
+            }
+            handled.extend(self.handle(Incoming {
+                line,
+                idempotency_key: None,
+                client: self.replay_client,
+                responder: Responder {
+                    id: None,
+                    reply: Reply::Silent,
+                },
+            }));
+        }
+        handled
+    }
+
+    /// Draw the played strokes that are due, returning whether any were
+    ///
+    /// The whole playback becomes one history entry once it finishes.
+    fn advance_playback(&mut self, now: Instant) -> bool {
+        let Some(playback) = self.playback.as_mut() else {
+            return false;
+        };
+        let drew = playback.advance(&mut self.state.canvas, now);
+        if playback.is_finished() {
+            self.playback = None;
+            self.history.record("playstrokes", &self.state.canvas);
+        }
+        drew
+    }
+
+    /// Release the locks and granted access of socket connections that have closed
+    fn release_closed_clients(&mut self) {
+        while let Ok(client) = self.closed_rx.try_recv() {
+            self.locks.release(client);
+            self.access.forget(client);
+        }
+    }
+
+    /// Reply to snapshots the worker has finished, toasting those taken
+    /// with Ctrl+S
+    fn poll_snapshots(&mut self) {
+        let Some(snapshots) = &self.snapshots else {
+            return;
+        };
+        for done in snapshots.poll() {
+            let response = snapshot_response(&done.path, &done.result);
+            for responder in done.tokens {
+                responder.answer(response.clone());
+            }
+        }
+        for result in self.shortcut_snapshots_rx.try_iter() {
+            let level = match result {
+                Ok(_) => NotifyLevel::Info,
+                Err(_) => NotifyLevel::Error,
+            };
+            let text = response_line(&result);
+            self.toasts
+                .push(&text, DEFAULT_NOTIFY_SECS, level, Instant::now());
+        }
+    }
 
-    rx
+    /// Append the canvas to the active capture, stopping it on a write error
+    fn write_capture_frame(&mut self) {
+        if let Some(active) = self.capture.as_mut() {
+            if let Err(e) = active.write_frame(&self.state.render()) {
+                eprintln!("displai: capture stopped: {}", e);
+                self.capture = None;
+            }
+        }
+    }
 }
 
 // ============================================================================
 // Main Application Loop
 // ============================================================================
 
+/// Put back the pixels a brush stroke through `points` (drawn with a brush
+/// reaching `reach` pixels) covered, from a copy of the canvas taken before it
+fn restore_stroke(canvas: &mut [u32], before: &[u32], points: &[(usize, usize)], reach: usize) {
+    let Some(&(x, y)) = points.first() else {
+        return;
+    };
+    let (x1, y1, x2, y2) = points
+        .iter()
+        .fold((x, y, x, y), |(x1, y1, x2, y2), &(x, y)| {
+            (x1.min(x), y1.min(y), x2.max(x), y2.max(y))
+        });
+    let width = window_width();
+    let (left, right) = (x1.saturating_sub(reach), (x2 + reach).min(width - 1));
+    for y in y1.saturating_sub(reach).max(CANVAS_TOP)..=(y2 + reach).min(canvas_bottom() - 1) {
+        let row = y * width;
+        canvas[row + left..=row + right].copy_from_slice(&before[row + left..=row + right]);
+    }
+}
+
+/// Run the application with default options
 pub fn run() {
-    let mut buffer: Vec<u32> = vec![WHITE; WIDTH * HEIGHT];
+    run_with_config(Config::default());
+}
 
-    let mut window = Window::new("displai - v0.1", WIDTH, HEIGHT, WindowOptions::default())
-        .expect("Failed to create window");
+/// Run the application with the given startup options
+pub fn run_with_config(config: Config) {
+    if config.headless {
+        run_headless(config);
+        return;
+    }
 
-    window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+    set_dimensions(config.size);
+    let mut session = Session::new(&config, Some(SnapshotWorker::spawn()));
+    session.start_autosave(&config);
+    let Dimensions {
+        mut width,
+        mut height,
+    } = config.size;
+    let mut interval = frame_interval(config.fps);
+    let (typed_tx, typed_rx) = mpsc::channel();
+    let mut window = open_window(&config.title, config.size, interval, &typed_tx);
 
     let mut is_drawing = false;
     let mut last_pos: Option<(usize, usize)> = None;
     let mut mouse_was_down = false;
     let mut right_mouse_was_down = false;
-    let mut edge_color_index: Option<usize> = Some(0); // Some(index) = color, None = transparent
-    let mut fill_color_index: Option<usize> = None; // None = transparent (no fill)
-    let mut brush_size: usize = DEFAULT_BRUSH_SIZE;
-    let mut current_tool: ToolMode = ToolMode::default();
     let mut drag_start: Option<(usize, usize)> = None;
+    let mut polygon = PolygonBuilder::new();
+    let mut bezier = BezierBuilder::new();
+    let mut selection = Selection::new();
+    let mut shown_canvas = session.canvases.active(); // Canvas the selection is on
+    let ants_start = Instant::now(); // Marching ants advance with time
+    let mut pointer = PointerTracker::new();
+    let mut pressed_object: Option<usize> = None; // Object that took the current press
+    let mut dragging_divider = false; // The press grabbed the split view's divider
+    let mut pressing_history = false; // The press went to the history panel
+    let mut redraw = RedrawTracker::new();
+    let mut stroke_in_progress = false;
+    let mut stroke_points: Vec<(usize, usize)> = Vec::new(); // Mouse positions of the stroke
+    let mut stroke_times: Vec<u64> = Vec::new(); // When each position was reached
+    let mut stroke_color = BLACK; // Color the stroke is drawn in
+    let strokes_epoch = Instant::now(); // Stroke times count from here
+    let mut stroke_before: Option<Vec<u32>> = None; // Canvas before the stroke, with snap on
+    let mut frame: Vec<u32> = vec![WHITE; width * height]; // Canvas plus overlays, as presented
+    let mut key_edges = KeyEdges::new(config.keymap.keys());
 
-    // Start stdin reader thread for command protocol
-    let stdin_rx = spawn_stdin_reader();
-    // Start Unix socket listener thread
-    let socket_rx = spawn_unix_socket_listener();
-
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        // Process any stdin commands (non-blocking)
-        loop {
-            match stdin_rx.try_recv() {
-                Ok(line) => {
-                    if let Some(cmd) = parse_command(&line) {
-                        if let Some(response) = execute_command(
-                            &cmd,
-                            &mut buffer,
-                            &mut edge_color_index,
-                            &mut fill_color_index,
-                            &mut brush_size,
-                        ) {
-                            println!("{}", response);
-                            let _ = io::stdout().flush();
-                        }
-                    }
+    // Start stdin reader and Unix socket listener threads for the command protocol
+    let (command_tx, command_rx) = mpsc::channel();
+    queue_script(&config, &command_tx);
+    if config.stdin {
+        spawn_stdin_reader(command_tx.clone());
+    }
+    if let Some(path) = &config.socket {
+        spawn_unix_socket_listener(
+            path.clone(),
+            command_tx,
+            Arc::clone(&session.clients),
+            session.closed_tx.clone(),
+        );
+    }
+    announce_ready(&config, &session.ready);
+
+    while window.is_open() {
+        // Process any pending commands (non-blocking)
+        while let Ok(incoming) = command_rx.try_recv() {
+            if let Some(cmd) = session.handle(incoming) {
+                if let Command::Fps(fps) = cmd {
+                    interval = frame_interval(fps);
+                    window.limit_update_rate(Some(interval));
                 }
-                Err(TryRecvError::Empty) => break,
-                Err(TryRecvError::Disconnected) => break,
+                redraw.mark_dirty();
             }
         }
-
-        // Process any Unix socket commands (non-blocking)
-        loop {
-            match socket_rx.try_recv() {
-                Ok(socket_cmd) => {
-                    if let Some(cmd) = parse_command(&socket_cmd.line) {
-                        let response = execute_command(
-                            &cmd,
-                            &mut buffer,
-                            &mut edge_color_index,
-                            &mut fill_color_index,
-                            &mut brush_size,
-                        );
-                        // Only send response if we have a stream (first command per connection)
-                        if let Some(mut stream) = socket_cmd.stream {
-                            if let Some(resp) = response {
-                                let _ = writeln!(stream, "{}", resp);
-                            } else {
-                                let _ = writeln!(stream, "ok");
-                            }
-                        }
-                    } else if let Some(mut stream) = socket_cmd.stream {
-                        let _ = writeln!(stream, "error: unknown command");
-                    }
-                }
-                Err(TryRecvError::Empty) => break,
-                Err(TryRecvError::Disconnected) => break,
+        session.release_closed_clients();
+        for cmd in session.advance_replay(Instant::now()) {
+            if let Command::Fps(fps) = cmd {
+                interval = frame_interval(fps);
+                window.limit_update_rate(Some(interval));
             }
+            redraw.mark_dirty();
+        }
+        if session.advance_playback(Instant::now()) {
+            redraw.mark_dirty();
+        }
+        session.poll_snapshots();
+        // Clocks and countdowns change once a second
+        if session
+            .state
+            .objects
+            .tick(Instant::now(), SystemTime::now())
+        {
+            redraw.mark_dirty();
+        }
+        // Toasts disappear when they expire
+        if session.toasts.tick(Instant::now()) {
+            redraw.mark_dirty();
         }
-        draw_title_bar(&mut buffer);
-        draw_bottom_toolbar(
-            &mut buffer,
-            edge_color_index,
-            fill_color_index,
-            brush_size,
-            current_tool,
-        );
 
         let mouse_down = window.get_mouse_down(MouseButton::Left);
         let right_mouse_down = window.get_mouse_down(MouseButton::Right);
         let mouse_clicked = mouse_down && !mouse_was_down;
         let right_mouse_clicked = right_mouse_down && !right_mouse_was_down;
+        let mouse_pos = window.get_mouse_pos(MouseMode::Pass);
+
+        redraw.observe_input(InputSnapshot {
+            mouse_pos,
+            left_down: mouse_down,
+            right_down: right_mouse_down,
+        });
 
-        if let Some((mx, my)) = window.get_mouse_pos(MouseMode::Pass) {
+        // An open dialog is modal: it takes every click, and nothing beneath
+        // it (tools, toolbar, objects) sees the mouse until it is answered
+        let modal = session.state.dialog.is_some();
+        if let (Some(dialog), Some((mx, my)), true) =
+            (&session.state.dialog, mouse_pos, mouse_clicked)
+        {
+            if let Some(answer) = dialog.click(mx as usize, my as usize) {
+                session.state.dialog = None;
+                session.subscribers.emit(&Event::Answered(answer));
+                redraw.mark_dirty();
+            }
+        }
+        if modal {
+            drag_start = None;
+            polygon.cancel();
+            bezier.cancel();
+        }
+        let (mouse_pos, mouse_down, mouse_clicked, right_mouse_clicked) = if modal {
+            (None, false, false, false)
+        } else {
+            (mouse_pos, mouse_down, mouse_clicked, right_mouse_clicked)
+        };
+
+        // Pointer events for retained objects; a press on a widget (button,
+        // slider, checkbox, toggle, or text input), or on any object while someone is subscribed, belongs to the
+        // object rather than the tool
+        let over_object = mouse_pos.and_then(|(mx, my)| {
+            let (x, y) = (mx as usize, my as usize);
+            let in_canvas = x < width && (CANVAS_TOP..canvas_bottom()).contains(&y);
+            in_canvas
+                .then(|| session.state.objects.hit(x, y, &session.state.vars))
+                .flatten()
+        });
+        for event in pointer.update(over_object, mouse_clicked) {
+            session.subscribers.emit(&event);
+        }
+        let focus_before = session.state.objects.focused();
+        let pressed = match over_object {
+            Some(id) if mouse_clicked => {
+                let state = &mut session.state;
+                state.objects.press(id, &mut state.vars)
+            }
+            // Clicking away from every object takes focus from the focused widget
+            None if mouse_clicked => {
+                session.state.objects.set_focus(None);
+                None
+            }
+            _ => None,
+        };
+        if session.state.objects.focused() != focus_before {
+            redraw.mark_dirty();
+        }
+        if let Some(event) = &pressed {
+            session.subscribers.emit(event);
+            redraw.mark_dirty();
+        }
+        if mouse_clicked {
+            let interactive = over_object
+                .and_then(|id| session.state.objects.get(id))
+                .is_some_and(|object| object.kind.is_interactive());
+            if interactive || !session.subscribers.is_empty() {
+                pressed_object = over_object;
+            }
+        } else if !mouse_down {
+            pressed_object = None;
+            if session.state.objects.release() {
+                redraw.mark_dirty();
+            }
+        }
+        // Sliders follow the pointer for as long as they are held
+        if let (Some(id), Some((mx, _))) = (pressed_object, mouse_pos) {
+            let x = mx.max(0.0) as usize;
+            let state = &mut session.state;
+            if let Some(event) = state.objects.drag(id, x, &mut state.vars) {
+                session.subscribers.emit(&event);
+                redraw.mark_dirty();
+            }
+        }
+
+        // The split view's divider is dragged rather than drawn over
+        match (&mut session.compare, mouse_pos) {
+            (Some(view), Some((mx, my))) => {
+                let (x, y) = (mx.max(0.0) as usize, my.max(0.0) as usize);
+                if mouse_clicked && pressed_object.is_none() && view.grabs(x, y) {
+                    dragging_divider = true;
+                }
+                if dragging_divider && mouse_down {
+                    view.set_divider(x);
+                    redraw.mark_dirty();
+                }
+            }
+            (None, _) => dragging_divider = false,
+            _ => {}
+        }
+        if !mouse_down {
+            dragging_divider = false;
+        }
+
+        // A click on the history panel undoes back to the entry under it
+        // (putting a floating selection back first), and draws nothing
+        if let (true, true, Some((mx, my))) = (session.history_visible, mouse_clicked, mouse_pos) {
+            let (x, y) = (mx.max(0.0) as usize, my.max(0.0) as usize);
+            let entries = session.history.entries();
+            pressing_history = pressed_object.is_none() && is_in_history_panel(x, y, &entries);
+            if let Some(keep) = history_panel_entry_at(x, y, &entries).filter(|_| pressing_history)
+            {
+                selection.cancel(&mut session.state.canvas);
+                let _ = session.history.undo_to(keep, &mut session.state.canvas);
+                redraw.mark_dirty();
+            }
+        }
+        if !mouse_down {
+            pressing_history = false;
+        }
+
+        if let Some((mx, my)) = mouse_pos {
             let x = mx as usize;
             let y = my as usize;
 
@@ -244,27 +1618,61 @@ pub fn run() {
                 if is_in_close_button(x, y) {
                     break;
                 }
+                // A floating selection lands on the canvas it was made on
+                if let Some(n) = tab_at(x, y, session.canvases.count()) {
+                    if let Some(label) = selection.commit(&mut session.state.canvas) {
+                        session.history.record(label, &session.state.canvas);
+                    }
+                    if let Err(e) = session.apply_tab(TabCommand::Switch(n)) {
+                        session.toasts.push(
+                            &response_line(&Err(e)),
+                            DEFAULT_NOTIFY_SECS,
+                            NotifyLevel::Error,
+                            Instant::now(),
+                        );
+                    }
+                }
                 if let Some(color_index) = get_clicked_color_index_bottom(x, y) {
-                    edge_color_index = Some(color_index);
+                    session.state.edge_color = session.state.palette.get(color_index);
                 }
                 if is_in_transparent_button(x, y) {
-                    edge_color_index = None; // Transparent edge
+                    session.state.edge_color = None; // Transparent edge
                 }
                 if let Some(tool) = get_clicked_tool(x, y) {
-                    current_tool = tool;
+                    session.state.current_tool = tool;
                 }
-                if is_in_minus_button(x, y) && brush_size > MIN_BRUSH_SIZE {
-                    brush_size -= 1;
+                if is_in_eyedropper_button(x, y) {
+                    session.state.current_tool = ToolMode::Eyedropper;
                 }
-                if is_in_plus_button(x, y) && brush_size < MAX_BRUSH_SIZE {
-                    brush_size += 1;
+                if is_in_brush_shape_button(x, y) {
+                    session.state.brush_shape = session.state.brush_shape.next();
+                }
+                if is_in_minus_button(x, y) && session.state.brush_size > MIN_BRUSH_SIZE {
+                    session.state.brush_size -= 1;
+                }
+                if is_in_plus_button(x, y) && session.state.brush_size < MAX_BRUSH_SIZE {
+                    session.state.brush_size += 1;
                 }
                 if is_in_clear_button(x, y) {
-                    clear_canvas(&mut buffer);
+                    selection.clear();
+                    clear_canvas(&mut session.state.canvas);
+                    session.state.display_list.clear();
+                    session.state.strokes.clear();
+                    session.history.record("clear", &session.state.canvas);
+                }
+                // Undoing a floating selection puts it back where it was
+                if is_in_undo_button(x, y) && !selection.is_floating() {
+                    let _ = session.history.undo(&mut session.state.canvas);
+                }
+                if is_in_undo_button(x, y) || is_in_redo_button(x, y) {
+                    selection.cancel(&mut session.state.canvas);
+                }
+                if is_in_redo_button(x, y) {
+                    let _ = session.history.redo(&mut session.state.canvas);
                 }
                 // Click on fill indicator to toggle fill off
                 if is_in_fill_indicator(x, y) {
-                    fill_color_index = None;
+                    session.state.set_fill(None);
                 }
             }
 
@@ -272,30 +1680,89 @@ pub fn run() {
             if right_mouse_clicked {
                 if let Some(color_index) = get_clicked_color_index_bottom(x, y) {
                     // Toggle fill: if same color, turn off fill; otherwise set it
-                    if fill_color_index == Some(color_index) {
-                        fill_color_index = None;
+                    let color = session.state.palette.get(color_index);
+                    if session.state.fill_color == color {
+                        session.state.set_fill(None);
                     } else {
-                        fill_color_index = Some(color_index);
+                        session.state.set_fill(color);
                     }
                 }
                 if is_in_transparent_button(x, y) {
-                    fill_color_index = None; // Transparent fill
+                    session.state.set_fill(None); // Transparent fill
                 }
             }
 
-            let edge_color = edge_color_index.map(|i| COLOR_PALETTE[i]);
-            let fill_color = fill_color_index.map(|i| COLOR_PALETTE[i]);
+            let edge_color = session.state.edge_color;
 
-            // Freehand drawing only in Brush mode
-            if current_tool == ToolMode::Brush {
-                if mouse_down && x < WIDTH && (CANVAS_TOP..CANVAS_BOTTOM).contains(&y) {
+            // Switching tools abandons a polygon or curve in progress, and
+            // puts down any floating selection
+            if session.state.current_tool != ToolMode::Polygon {
+                polygon.cancel();
+            }
+            if session.state.current_tool != ToolMode::Bezier {
+                bezier.cancel();
+            }
+            if session.state.current_tool != ToolMode::Select {
+                if let Some(label) = selection.commit(&mut session.state.canvas) {
+                    session.history.record(label, &session.state.canvas);
+                }
+                selection.clear();
+            }
+
+            if pressed_object.is_some() || dragging_divider || pressing_history {
+                // The press went to an object (or its subscribers), the
+                // split view's divider, or the history panel
+                is_drawing = false;
+                last_pos = None;
+            } else if session.state.current_tool == ToolMode::Brush {
+                // Freehand drawing only in Brush mode
+                if mouse_down && x < width && (CANVAS_TOP..canvas_bottom()).contains(&y) {
                     if let Some(color) = edge_color {
                         if is_drawing {
                             if let Some((lx, ly)) = last_pos {
-                                draw_brush_line(&mut buffer, lx, ly, x, y, color, brush_size);
+                                let state = &mut session.state;
+                                with_blend_mode(state.blend, || {
+                                    with_antialiasing(state.antialias, || {
+                                        with_brush_shape(state.brush_shape, || {
+                                            draw_brush_line(
+                                                &mut state.canvas,
+                                                lx,
+                                                ly,
+                                                x,
+                                                y,
+                                                color,
+                                                state.brush_size,
+                                            )
+                                        })
+                                    })
+                                });
+                            }
+                            if stroke_points.last() != Some(&(x, y)) {
+                                stroke_points.push((x, y));
+                                stroke_times.push(strokes_epoch.elapsed().as_millis() as u64);
                             }
                         } else {
-                            draw_circle(&mut buffer, x, y, brush_size, color);
+                            // Shape snap may put the stroke's pixels back
+                            stroke_before =
+                                session.state.snap.map(|_| session.state.canvas.to_vec());
+                            stroke_points = vec![(x, y)];
+                            stroke_times = vec![strokes_epoch.elapsed().as_millis() as u64];
+                            stroke_color = color;
+                            let state = &mut session.state;
+                            with_blend_mode(state.blend, || {
+                                with_antialiasing(state.antialias, || {
+                                    with_brush_shape(state.brush_shape, || {
+                                        draw_brush_dot(
+                                            &mut state.canvas,
+                                            x,
+                                            y,
+                                            state.brush_size,
+                                            color,
+                                        )
+                                    })
+                                })
+                            });
+                            stroke_in_progress = true;
                         }
                     }
                     is_drawing = true;
@@ -304,9 +1771,79 @@ pub fn run() {
                     is_drawing = false;
                     last_pos = None;
                 }
+            } else if session.state.current_tool == ToolMode::Bucket {
+                // Bucket: click to flood-fill with the fill color
+                let in_canvas = x < width && (CANVAS_TOP..canvas_bottom()).contains(&y);
+                if mouse_clicked && in_canvas && session.state.fill_color.is_some() {
+                    session.state.execute(&Command::Bucket { x, y });
+                    session
+                        .history
+                        .record(ToolMode::Bucket.name(), &session.state.canvas);
+                }
+                is_drawing = false;
+                last_pos = None;
+            } else if session.state.current_tool == ToolMode::Eyedropper {
+                // Eyedropper: left-click picks the edge color, right-click the fill
+                let in_canvas = x < width && (CANVAS_TOP..canvas_bottom()).contains(&y);
+                if in_canvas {
+                    let color = session.state.canvas[y * width + x] & 0xFFFFFF;
+                    if mouse_clicked {
+                        session.state.edge_color = Some(color);
+                    }
+                    if right_mouse_clicked {
+                        session.state.set_fill(Some(color));
+                    }
+                }
+                is_drawing = false;
+                last_pos = None;
+            } else if session.state.current_tool == ToolMode::Select {
+                // Select: drag to select, drag the selection to move it
+                let in_canvas = x < width && (CANVAS_TOP..canvas_bottom()).contains(&y);
+                if mouse_clicked && in_canvas {
+                    if let Some(label) = selection.press(&mut session.state.canvas, x, y) {
+                        session.history.record(label, &session.state.canvas);
+                    }
+                } else if mouse_down {
+                    selection.drag(x, y);
+                } else if mouse_was_down {
+                    selection.release();
+                }
+                is_drawing = false;
+                last_pos = None;
+            } else if session.state.current_tool == ToolMode::Polygon {
+                // Polygon: click to add vertices, double-click to close
+                let in_canvas = x < width && (CANVAS_TOP..canvas_bottom()).contains(&y);
+                if mouse_clicked && in_canvas {
+                    if let Some(vertices) = polygon.click(x, y, Instant::now()) {
+                        session.state.draw_polygon(&vertices);
+                        session
+                            .history
+                            .record(ToolMode::Polygon.name(), &session.state.canvas);
+                    }
+                }
+                is_drawing = false;
+                last_pos = None;
+            } else if session.state.current_tool == ToolMode::Bezier {
+                // Bezier: drag out the endpoints, then drag the control point
+                let in_canvas = x < width && (CANVAS_TOP..canvas_bottom()).contains(&y);
+                let (cx, cy) = (x.min(width - 1), y.clamp(CANVAS_TOP, canvas_bottom() - 1));
+                if mouse_clicked && in_canvas {
+                    bezier.press(x, y);
+                } else if mouse_down {
+                    bezier.drag(cx, cy);
+                } else if mouse_was_down {
+                    if let Some(controls) = bezier.release(cx, cy) {
+                        session.state.execute(&Command::Bezier(controls));
+                        session
+                            .history
+                            .record(ToolMode::Bezier.name(), &session.state.canvas);
+                    }
+                }
+                is_drawing = false;
+                last_pos = None;
             } else {
                 // Shape tools: click-drag to define shape bounds
-                let in_canvas = x < WIDTH && (CANVAS_TOP..CANVAS_BOTTOM).contains(&y);
+                let in_canvas = x < width && (CANVAS_TOP..canvas_bottom()).contains(&y);
 
                 if mouse_clicked && in_canvas {
                     // Start drag
@@ -315,17 +1852,11 @@ pub fn run() {
                     // Mouse released - draw the shape if we have a valid drag
                     if let Some((start_x, start_y)) = drag_start {
                         if in_canvas {
-                            draw_shape_with_fill(
-                                &mut buffer,
-                                current_tool,
-                                start_x,
-                                start_y,
-                                x,
-                                y,
-                                edge_color,
-                                fill_color,
-                                brush_size,
-                            );
+                            let tool = session.state.current_tool;
+                            session.state.draw_shape(tool, start_x, start_y, x, y);
+                            session
+                                .history
+                                .record(session.state.current_tool.name(), &session.state.canvas);
                         }
                         drag_start = None;
                     }
@@ -339,11 +1870,399 @@ pub fn run() {
             last_pos = None;
         }
 
+        // A brush stroke becomes one history entry once it ends, unless
+        // shape snap recognizes it and draws the clean shape instead
+        if stroke_in_progress && !is_drawing {
+            let snapped = match (session.state.snap, stroke_before.take()) {
+                (Some(tolerance), Some(before)) => recognize(&stroke_points, tolerance)
+                    .filter(|cmd| validate(cmd).is_ok())
+                    .map(|cmd| (cmd, before)),
+                _ => None,
+            };
+            if let Some((cmd, before)) = snapped {
+                let reach = session.state.brush_size + 1;
+                restore_stroke(&mut session.state.canvas, &before, &stroke_points, reach);
+                session.state.execute(&cmd);
+                session.history.record(cmd.name(), &session.state.canvas);
+                session.subscribers.emit(&Event::Snapped(cmd.to_string()));
+            } else {
+                session.state.strokes.push(Stroke {
+                    points: std::mem::take(&mut stroke_points),
+                    times: std::mem::take(&mut stroke_times),
+                    color: stroke_color,
+                    brush_size: session.state.brush_size,
+                });
+                session
+                    .history
+                    .record(ToolMode::Brush.name(), &session.state.canvas);
+            }
+            stroke_in_progress = false;
+        }
+
+        // Keymap shortcuts (not beneath a dialog); those without Ctrl only
+        // while no widget has focus, and their characters aren't typed
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let focused = session.state.objects.focused().is_some();
+        let mut shortcut_chars = Vec::new();
+        for key in key_edges.update(|key| window.is_key_down(key)) {
+            let chord = Chord { ctrl, shift, key };
+            if modal || (focused && !ctrl) {
+                continue;
+            }
+            if let Some(action) = config.keymap.lookup(chord) {
+                shortcut_chars.extend(chord.typed_char());
+                session.run_shortcut(action, &mut selection);
+                redraw.mark_dirty();
+            }
+        }
+
+        // Keys go to the focused widget first; Escape quits if nothing is
+        // focused, and other unused keys reach subscribers as "key <name>"
+        let mut keys: Vec<KeyInput> = typed_rx
+            .try_iter()
+            .filter(|c| !c.is_control() && !ctrl && !shortcut_chars.contains(c))
+            .map(KeyInput::Char)
+            .collect();
+        for (key, input, repeat) in ROUTED_KEYS {
+            if window.is_key_pressed(key, repeat) {
+                keys.push(match input {
+                    KeyInput::Tab if shift => KeyInput::BackTab,
+                    input => input,
+                });
+            }
+        }
+        let mut quit = false;
+        for key in keys {
+            let state = &mut session.state;
+            // An open dialog takes every key; Enter and Escape answer it
+            if let Some(dialog) = &mut state.dialog {
+                if let Some(answer) = dialog.key(key) {
+                    state.dialog = None;
+                    session.subscribers.emit(&Event::Answered(answer));
+                }
+                redraw.mark_dirty();
+                continue;
+            }
+            match route_key(&mut state.objects, &mut state.vars, key) {
+                KeyRoute::Consumed(event) => {
+                    if let Some(event) = event {
+                        session.subscribers.emit(&event);
+                    }
+                    redraw.mark_dirty();
+                }
+                // With a selection, Enter puts it down, Escape drops it, and
+                // Backspace deletes it
+                KeyRoute::Unclaimed if !selection.is_empty() && key == KeyInput::Enter => {
+                    if let Some(label) = selection.commit(&mut state.canvas) {
+                        session.history.record(label, &state.canvas);
+                    }
+                    redraw.mark_dirty();
+                }
+                KeyRoute::Unclaimed if !selection.is_empty() && key == KeyInput::Escape => {
+                    selection.cancel(&mut state.canvas);
+                    redraw.mark_dirty();
+                }
+                KeyRoute::Unclaimed if !selection.is_empty() && key == KeyInput::Backspace => {
+                    if let Some(label) = selection.delete(&mut state.canvas) {
+                        session.history.record(label, &state.canvas);
+                    }
+                    redraw.mark_dirty();
+                }
+                KeyRoute::Unclaimed if key == KeyInput::Escape => quit = true,
+                KeyRoute::Unclaimed => session.subscribers.emit(&Event::Key(key.name())),
+            }
+        }
+        if quit {
+            break;
+        }
+
+        // Ctrl+C/X/V copy, cut, and paste the selection; Delete deletes it.
+        // With the system clipboard, Ctrl+C and Ctrl+X also put the selection
+        // (Ctrl+C with none, the whole canvas) there, and Ctrl+V pastes its
+        // image, which may have been copied elsewhere since: a screenshot to
+        // annotate, say.
+        let ctrl = ctrl && !modal;
+        let canvas = &mut session.state.canvas;
+        let copy_key = ctrl && window.is_key_pressed(Key::C, KeyRepeat::No);
+        let cut_key = ctrl && window.is_key_pressed(Key::X, KeyRepeat::No);
+        let mut edited = None;
+        let copied = if cut_key {
+            edited = selection.cut(canvas);
+            edited.is_some()
+        } else {
+            copy_key && selection.copy(canvas)
+        };
+        if clipboard_supported() && (copy_key || copied) {
+            let result = match selection.clipboard().filter(|_| copied) {
+                Some(clip) => copy_to_clipboard(clip).map(|()| (clip.width, clip.height)),
+                None => copy_canvas(canvas, None),
+            };
+            let level = match result {
+                Ok(_) => NotifyLevel::Info,
+                Err(_) => NotifyLevel::Error,
+            };
+            let text = response_line(&copy_response(&result));
+            session
+                .toasts
+                .push(&text, DEFAULT_NOTIFY_SECS, level, Instant::now());
+        }
+        if !modal && window.is_key_pressed(Key::Delete, KeyRepeat::No) {
+            edited = selection.delete(canvas);
+        }
+        if ctrl && window.is_key_pressed(Key::V, KeyRepeat::No) {
+            // Paste at the pointer, or the canvas's top-left corner
+            let (px, py) = mouse_pos
+                .map(|(mx, my)| (mx as usize, my as usize))
+                .filter(|&(px, py)| px < width && (CANVAS_TOP..canvas_bottom()).contains(&py))
+                .unwrap_or((0, CANVAS_TOP));
+            let latest = match clipboard_supported().then(paste_from_clipboard) {
+                Some(Ok(img)) => Some(image_clip(&img)),
+                // Text or nothing on the system clipboard leaves our own
+                Some(Err(e)) if selection.clipboard().is_none() => {
+                    let text = format!("error: {}", e);
+                    session.toasts.push(
+                        &text,
+                        DEFAULT_NOTIFY_SECS,
+                        NotifyLevel::Error,
+                        Instant::now(),
+                    );
+                    None
+                }
+                _ => None,
+            };
+            let pasted = (latest.is_some() || selection.clipboard().is_some())
+                .then(|| selection.paste_latest(canvas, latest, px, py));
+            if let Some(label) = pasted {
+                edited = label;
+                session.state.current_tool = ToolMode::Select;
+            }
+        }
+        if let Some(label) = edited {
+            session.history.record(label, &session.state.canvas);
+        }
+        // The marching ants move every frame
+        if !selection.is_empty() {
+            redraw.mark_dirty();
+        }
+
+        // F3 toggles the stats overlay
+        if window.is_key_pressed(Key::F3, KeyRepeat::No) {
+            session.stats_visible = !session.stats_visible;
+            redraw.mark_dirty();
+        }
+        // F4 toggles the history panel
+        if window.is_key_pressed(Key::F4, KeyRepeat::No) {
+            session.history_visible = !session.history_visible;
+            redraw.mark_dirty();
+        }
+        // F10 steps the script being debugged
+        if !modal && session.stepper.is_some() && window.is_key_pressed(Key::F10, KeyRepeat::Yes) {
+            if let Some(Command::Fps(fps)) = session.step_script() {
+                interval = frame_interval(fps);
+                window.limit_update_rate(Some(interval));
+            }
+            redraw.mark_dirty();
+        }
+
+        // `canvas crop` and `canvas resize` change the window's size; minifb
+        // can't resize a window, so it is opened again at the new one
+        if dimensions() != (Dimensions { width, height }) {
+            Dimensions { width, height } = dimensions();
+            window = open_window(&config.title, dimensions(), interval, &typed_tx);
+            frame = vec![WHITE; width * height];
+            selection.clear();
+            (is_drawing, stroke_in_progress, drag_start) = (false, false, None);
+            stroke_before = None;
+            redraw.mark_dirty();
+        }
+
+        // Another canvas shown (by a tab or `canvas`): what was being drawn or
+        // selected belonged to the old one
+        if session.canvases.active() != shown_canvas {
+            shown_canvas = session.canvases.active();
+            selection.clear();
+            (is_drawing, stroke_in_progress, drag_start) = (false, false, None);
+            stroke_before = None;
+            redraw.mark_dirty();
+        }
+
         mouse_was_down = mouse_down;
         right_mouse_was_down = right_mouse_down;
 
-        window
-            .update_with_buffer(&buffer, WIDTH, HEIGHT)
-            .expect("Failed to update buffer");
+        // Only push the buffer when something changed (or the heartbeat is due);
+        // otherwise just pump window events
+        let now = Instant::now();
+        session.fps_counter.tick(now);
+        if redraw.should_present(now) {
+            draw_title_bar(&mut session.state.canvas);
+            draw_tab_strip(
+                &mut session.state.canvas,
+                session.canvases.count(),
+                session.canvases.active(),
+            );
+            if let Some(stepper) = &session.stepper {
+                draw_step_status(&mut session.state.canvas, stepper);
+            }
+            let (palette, brush) = (session.state.palette, session.state.brush_shape);
+            with_palette(&palette, || {
+                with_brush_shape(brush, || {
+                    draw_bottom_toolbar(
+                        &mut session.state.canvas,
+                        session.state.edge_color,
+                        session.state.fill_color,
+                        session.state.brush_size,
+                        session.state.current_tool,
+                    )
+                })
+            });
+            // Objects and overlays go on a copy so they never reach the canvas or
+            // history (toasts, the history panel, and the stats overlay stay
+            // out of snapshots too)
+            let overlaid = session.stats_visible
+                || session.history_visible
+                || session.state.has_overlays()
+                || !session.toasts.is_empty()
+                || !polygon.is_empty()
+                || !bezier.is_empty()
+                || !selection.is_empty()
+                || session.compare.is_some()
+                || session.diff_overlay.is_some();
+            let presented = if overlaid {
+                session.state.render_into(&mut frame);
+                if let Some(overlay) = &session.diff_overlay {
+                    overlay.draw(&mut frame);
+                }
+                if let Some(view) = &session.compare {
+                    view.draw(&mut frame);
+                }
+                let cursor = mouse_pos.map(|(mx, my)| (mx as usize, my as usize));
+                let preview_color = session.state.edge_color.unwrap_or(DARK_GRAY);
+                polygon.draw_preview(&mut frame, cursor, preview_color);
+                bezier.draw_preview(&mut frame, preview_color);
+                selection.draw_preview(&mut frame, ants_phase(ants_start.elapsed()));
+                session.toasts.draw(&mut frame);
+                if session.history_visible {
+                    draw_history_panel(&mut frame, &session.history.entries());
+                }
+                if session.stats_visible {
+                    let stats = session.stats();
+                    draw_stats_overlay(&mut frame, &stats);
+                }
+                &frame[..]
+            } else {
+                &session.state.canvas[..]
+            };
+            window
+                .update_with_buffer(presented, width, height)
+                .expect("Failed to update buffer");
+            redraw.presented(now);
+        } else {
+            window.update();
+        }
+
+        session.write_capture_frame();
+        session.write_gif_frame(now);
+        session.autosave(now);
+    }
+
+    if let Some(active) = session.capture.take() {
+        let _ = active.finish();
+    }
+    if let Some(active) = session.recorder.take() {
+        let _ = active.finish();
+    }
+    // Let the encoder write the frames it was still working on
+    if let Some(active) = session.gif.take() {
+        let _ = active.finish();
+    }
+    persist_canvas(&config, &session.state.canvas);
+    session.finish_autosave();
+    remove_socket(&config);
+}
+
+/// Run without a window, serving stdin and the Unix socket (as configured)
+/// against an off-screen canvas
+///
+/// Commands behave as in the windowed app (snapshots are encoded inline).
+/// Returns once stdin is closed and every line read from it has been handled;
+/// without stdin, it serves the socket until the process is killed.
+pub fn run_headless(config: Config) {
+    set_dimensions(config.size);
+    let mut session = Session::new(&config, None);
+    session.start_autosave(&config);
+    let mut interval = frame_interval(config.fps);
+
+    let (command_tx, command_rx) = mpsc::channel();
+    queue_script(&config, &command_tx);
+    let stdin_reader = config.stdin.then(|| spawn_stdin_reader(command_tx.clone()));
+    if let Some(path) = &config.socket {
+        spawn_unix_socket_listener(
+            path.clone(),
+            command_tx,
+            Arc::clone(&session.clients),
+            session.closed_tx.clone(),
+        );
+    }
+    announce_ready(&config, &session.ready);
+
+    loop {
+        // Check before draining, so lines sent just before EOF are still
+        // handled; without stdin, serve the socket until killed (or, with
+        // neither, stop once the script has run)
+        let stdin_closed = match &stdin_reader {
+            Some(reader) => reader.is_finished(),
+            None => config.socket.is_none(),
+        };
+        match command_rx.recv_timeout(interval) {
+            Ok(incoming) => {
+                if let Some(Command::Fps(fps)) = session.handle(incoming) {
+                    interval = frame_interval(fps);
+                }
+                while let Ok(incoming) = command_rx.try_recv() {
+                    if let Some(Command::Fps(fps)) = session.handle(incoming) {
+                        interval = frame_interval(fps);
+                    }
+                }
+            }
+            // A replay or stroke playback in progress keeps the session
+            // alive until it finishes (or the replay is paused by a watch,
+            // with no stdin left to resume it)
+            Err(RecvTimeoutError::Timeout)
+                if stdin_closed
+                    && session.replay.as_ref().is_none_or(Replay::is_paused)
+                    && session.playback.is_none() =>
+            {
+                break
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        session.release_closed_clients();
+        let now = Instant::now();
+        for cmd in session.advance_replay(now) {
+            if let Command::Fps(fps) = cmd {
+                interval = frame_interval(fps);
+            }
+        }
+        session.advance_playback(now);
+        session.fps_counter.tick(now);
+        session.state.objects.tick(now, SystemTime::now());
+        session.write_capture_frame();
+        session.write_gif_frame(now);
+        session.autosave(now);
+    }
+
+    if let Some(active) = session.capture.take() {
+        let _ = active.finish();
+    }
+    if let Some(active) = session.recorder.take() {
+        let _ = active.finish();
+    }
+    if let Some(active) = session.gif.take() {
+        let _ = active.finish();
     }
+    persist_canvas(&config, &session.state.canvas);
+    session.finish_autosave();
+    remove_socket(&config);
 }
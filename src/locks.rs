@@ -0,0 +1,365 @@
+//! Exclusive canvas regions for concurrent clients.
+//!
+//! This module handles:
+//! - `Region`, a rectangle of the canvas, and the region a command draws into
+//! - `RegionLocks`, the locks taken with `lockregion`, and checking drawing
+//!   commands against them
+//! - `Ownership`, who a part of the canvas belongs to as one client sees it,
+//!   reported by `layout` and `state` so clients can pick a free pane
+//!
+//! When several agents share one canvas, each can lock the panel it draws in:
+//! a drawing command from any other client that would touch a locked region is
+//! refused. Locks belong to the connection that took them and are released
+//! when it closes, so a crashed agent can't hold a panel forever; stdin is one
+//! long-lived client. Drawing with the mouse is never blocked.
+
+use std::fmt;
+
+use crate::command::{done, reply, AttributedPoint, Command, CommandOutput, CommandResult};
+use crate::drawing::default_arrow_head;
+use crate::legend::legend_size;
+use crate::shadow::Shadow;
+
+/// Identifies who sent a command: stdin, or one socket connection
+pub type ClientId = u64;
+
+/// The client id of stdin (socket connections are numbered from 1)
+pub const STDIN_CLIENT: ClientId = 0;
+
+/// Most locks held at once, across all clients
+pub const MAX_REGION_LOCKS: usize = 256;
+
+/// A rectangle of the canvas, in window coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// The whole canvas, whatever its size
+    pub const ALL: Region = Region {
+        x: 0,
+        y: 0,
+        width: usize::MAX,
+        height: usize::MAX,
+    };
+
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The rectangle between two corners (inclusive, either order), grown by
+    /// `pad` pixels on every side
+    pub fn around(x1: usize, y1: usize, x2: usize, y2: usize, pad: usize) -> Self {
+        let (left, top) = (
+            x1.min(x2).saturating_sub(pad),
+            y1.min(y2).saturating_sub(pad),
+        );
+        let (right, bottom) = (
+            x1.max(x2).saturating_add(pad),
+            y1.max(y2).saturating_add(pad),
+        );
+        Region {
+            x: left,
+            y: top,
+            width: (right - left).saturating_add(1),
+            height: (bottom - top).saturating_add(1),
+        }
+    }
+
+    /// Whether the two rectangles share at least one pixel
+    pub fn intersects(&self, other: &Region) -> bool {
+        self.x < other.x.saturating_add(other.width)
+            && other.x < self.x.saturating_add(self.width)
+            && self.y < other.y.saturating_add(other.height)
+            && other.y < self.y.saturating_add(self.height)
+    }
+}
+
+impl fmt::Display for Region {
+    /// Format as `x,y,w,h`, the arguments `lockregion` takes
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// How far beyond its geometry a shape drawn with `brush_size` and `shadow`
+/// can paint
+pub fn draw_reach(brush_size: usize, shadow: Option<&Shadow>) -> usize {
+    let shadow = shadow.map_or(0, |s| {
+        s.dx.unsigned_abs()
+            .max(s.dy.unsigned_abs())
+            .saturating_add(s.blur)
+    });
+    brush_size.saturating_add(shadow)
+}
+
+/// The bounding box of some points, grown by `reach`
+fn points_region(points: impl IntoIterator<Item = (usize, usize)>, reach: usize) -> Option<Region> {
+    let mut points = points.into_iter();
+    let (x, y) = points.next()?;
+    let (x1, y1, x2, y2) = points.fold((x, y, x, y), |(x1, y1, x2, y2), (x, y)| {
+        (x1.min(x), y1.min(y), x2.max(x), y2.max(y))
+    });
+    Some(Region::around(x1, y1, x2, y2, reach))
+}
+
+/// `points_region` for points that may carry their own brush size
+fn attributed_region(points: &[AttributedPoint], reach: usize) -> Option<Region> {
+    let reach = points.iter().filter_map(|p| p.size).fold(reach, usize::max);
+    points_region(points.iter().map(|p| (p.x, p.y)), reach)
+}
+
+/// The canvas region a command may change, with shapes grown by `reach` (see
+/// `draw_reach`), or None for commands that don't touch canvas pixels
+///
+/// Commands whose extent isn't known up front (clear, bucket fills, undo and
+/// redo) claim the whole canvas, and images everything right of and below
+/// their corner.
+pub fn command_region(cmd: &Command, reach: usize) -> Option<Region> {
+    if let Some((_, x1, y1, x2, y2)) = cmd.shape_bounds() {
+        return Some(Region::around(x1, y1, x2, y2, reach));
+    }
+    match cmd {
+        Command::Stroke { x1, y1, x2, y2 } | Command::RoundedRect { x1, y1, x2, y2, .. } => {
+            Some(Region::around(*x1, *y1, *x2, *y2, reach))
+        }
+        Command::Dot { x, y } => Some(Region::around(*x, *y, *x, *y, reach)),
+        // A head's back corners stick out past its tip; the head never gets
+        // longer than a default one at the widest brush in reach
+        Command::Arrow {
+            x1,
+            y1,
+            x2,
+            y2,
+            head,
+            ..
+        } => {
+            let head = head.unwrap_or_else(|| default_arrow_head(reach));
+            Some(Region::around(
+                *x1,
+                *y1,
+                *x2,
+                *y2,
+                reach.saturating_add(head),
+            ))
+        }
+        Command::Star {
+            x, y, outer, inner, ..
+        } => {
+            let r = (*outer).max(*inner);
+            Some(Region::around(
+                x.saturating_sub(r),
+                y.saturating_sub(r),
+                x.saturating_add(r),
+                y.saturating_add(r),
+                reach,
+            ))
+        }
+        Command::Arc { x, y, r, .. }
+        | Command::Pie { x, y, r, .. }
+        | Command::Ngon { x, y, r, .. } => Some(Region::around(
+            x.saturating_sub(*r),
+            y.saturating_sub(*r),
+            x.saturating_add(*r),
+            y.saturating_add(*r),
+            reach,
+        )),
+        // A curve stays within the hull of its control points
+        Command::Polygon(points) | Command::Bezier(points) => {
+            points_region(points.iter().copied(), reach)
+        }
+        Command::Polyline(points) | Command::Points(points) => attributed_region(points, reach),
+        Command::Image { x, y, .. } | Command::Paste { x, y } => {
+            Some(Region::new(*x, *y, usize::MAX, usize::MAX))
+        }
+        Command::Legend { x, y, entries } => {
+            let (width, height) = legend_size(entries);
+            Some(Region::new(*x, *y, width, height))
+        }
+        Command::Filter {
+            region: Some((x1, y1, x2, y2)),
+            ..
+        } => Some(Region::around(*x1, *y1, *x2, *y2, 0)),
+        Command::Clear
+        | Command::Canvas(_)
+        | Command::CanvasSize(_)
+        | Command::Tab(_)
+        | Command::Load(_)
+        | Command::Filter { region: None, .. }
+        | Command::Bucket { .. }
+        | Command::Undo
+        | Command::Redo => Some(Region::ALL),
+        _ => None,
+    }
+}
+
+/// A locked region and the client holding it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionLock {
+    pub id: usize,
+    pub owner: ClientId,
+    pub region: Region,
+}
+
+impl RegionLock {
+    /// Who holds the lock, as `client` sees it
+    pub fn ownership(&self, client: ClientId) -> Ownership {
+        if self.owner == client {
+            Ownership::Mine
+        } else {
+            Ownership::Taken(self.owner)
+        }
+    }
+}
+
+/// Who a part of the canvas belongs to, as one client sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ownership {
+    /// No lock touches it
+    Free,
+    /// Only the client's own locks touch it
+    Mine,
+    /// Another client's lock touches it, so drawing there may be refused
+    Taken(ClientId),
+}
+
+impl fmt::Display for Ownership {
+    /// Format as `free`, `mine`, or the holder: `stdin` or `client<N>`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Ownership::Free => write!(f, "free"),
+            Ownership::Mine => write!(f, "mine"),
+            Ownership::Taken(STDIN_CLIENT) => write!(f, "stdin"),
+            Ownership::Taken(owner) => write!(f, "client{}", owner),
+        }
+    }
+}
+
+/// The regions locked by clients
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionLocks {
+    locks: Vec<RegionLock>,
+    next_id: usize,
+}
+
+impl RegionLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.locks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locks.is_empty()
+    }
+
+    /// All locks, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &RegionLock> {
+        self.locks.iter()
+    }
+
+    /// The first lock held by someone other than `client` that overlaps `region`
+    pub fn conflict(&self, client: ClientId, region: &Region) -> Option<&RegionLock> {
+        self.locks
+            .iter()
+            .find(|lock| lock.owner != client && lock.region.intersects(region))
+    }
+
+    /// Who `region` belongs to, as `client` sees it: taken if another
+    /// client's lock overlaps it (the oldest such), else mine if one of its
+    /// own does, else free
+    pub fn ownership(&self, client: ClientId, region: &Region) -> Ownership {
+        if let Some(held) = self.conflict(client, region) {
+            return Ownership::Taken(held.owner);
+        }
+        if self.locks.iter().any(|lock| lock.region.intersects(region)) {
+            Ownership::Mine
+        } else {
+            Ownership::Free
+        }
+    }
+
+    /// Lock `region` for `client`, returning the new lock's id
+    ///
+    /// Fails if another client holds an overlapping lock; a client's own locks
+    /// may overlap.
+    pub fn lock(&mut self, client: ClientId, region: Region) -> Result<usize, String> {
+        if let Some(held) = self.conflict(client, &region) {
+            return Err(format!("region overlaps lock {}", held.id));
+        }
+        if self.locks.len() >= MAX_REGION_LOCKS {
+            return Err(format!("too many locks (max {})", MAX_REGION_LOCKS));
+        }
+        self.next_id += 1;
+        self.locks.push(RegionLock {
+            id: self.next_id,
+            owner: client,
+            region,
+        });
+        Ok(self.next_id)
+    }
+
+    /// Release `client`'s lock `id`
+    pub fn unlock(&mut self, client: ClientId, id: usize) -> Result<(), String> {
+        match self.locks.iter().position(|lock| lock.id == id) {
+            Some(i) if self.locks[i].owner == client => {
+                self.locks.remove(i);
+                Ok(())
+            }
+            Some(_) => Err(format!("lock {} is held by another client", id)),
+            None => Err(format!("no lock {}", id)),
+        }
+    }
+
+    /// Release every lock `client` holds, returning how many there were
+    pub fn release(&mut self, client: ClientId) -> usize {
+        let before = self.locks.len();
+        self.locks.retain(|lock| lock.owner != client);
+        before - self.locks.len()
+    }
+
+    /// Check a command from `client` against other clients' locks, returning
+    /// why it's refused if it would draw into one
+    pub fn check(&self, client: ClientId, cmd: &Command, reach: usize) -> Result<(), String> {
+        let Some(region) = command_region(cmd, reach) else {
+            return Ok(());
+        };
+        match self.conflict(client, &region) {
+            Some(held) => Err(format!(
+                "region is locked by another client (lock {})",
+                held.id
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply `lockregion` or `unlockregion` from `client`, returning the
+    /// protocol response
+    pub fn apply(&mut self, client: ClientId, cmd: &Command) -> CommandResult {
+        match *cmd {
+            Command::LockRegion {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let id = self.lock(client, Region::new(x, y, width, height))?;
+                reply(format!("locked {}", id))
+            }
+            Command::UnlockRegion(Some(id)) => done(self.unlock(client, id)),
+            Command::UnlockRegion(None) => reply(format!("unlocked {}", self.release(client))),
+            _ => Ok(CommandOutput::Ok),
+        }
+    }
+}
@@ -1,3 +1,14 @@
 fn main() {
-    displai::run();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", displai::USAGE);
+        return;
+    }
+    match displai::Config::from_args(args) {
+        Ok(config) => displai::run_with_config(config),
+        Err(e) => {
+            eprintln!("displai: {}", e);
+            std::process::exit(2);
+        }
+    }
 }
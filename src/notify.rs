@@ -0,0 +1,154 @@
+//! Toast notifications over the canvas.
+//!
+//! This module handles:
+//! - `NotifyLevel`, the severity of a toast (info, warn, error) and its colors
+//! - `Toasts`, the banners shown by `notify`, each until it expires
+//! - Drawing the toasts stacked above the bottom of the canvas, newest lowest
+//!
+//! Like the stats overlay, toasts are drawn onto the presented frame, never
+//! into the canvas itself, so they do not show up in snapshots or undo
+//! history.
+
+use std::time::{Duration, Instant};
+
+use crate::drawing::fill_rectangle;
+use crate::font::{draw_text, text_width, GLYPH_ADVANCE, GLYPH_HEIGHT};
+use crate::{canvas_bottom, window_width, BLACK, CANVAS_TOP, DARK_GRAY, RED, WHITE};
+
+/// How long a toast shows when `notify` gives no duration, in seconds
+pub const DEFAULT_NOTIFY_SECS: u64 = 3;
+/// Longest a toast shows, in seconds
+pub const MAX_NOTIFY_SECS: u64 = 60;
+/// Longest toast text, in characters
+pub const MAX_NOTIFY_LEN: usize = 120;
+/// Most toasts shown at once; a new one pushes out the oldest
+pub const MAX_TOASTS: usize = 5;
+/// Background of a warning toast
+pub const WARN_BACKGROUND: u32 = 0xF0C040;
+
+const PADDING: usize = 6;
+const MARGIN: usize = 10;
+const GAP: usize = 4;
+const TOAST_HEIGHT: usize = GLYPH_HEIGHT + 2 * PADDING;
+
+/// How urgent a toast is, which sets its colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotifyLevel {
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl NotifyLevel {
+    /// Parse a level name (`info`, `warn`, or `error`)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "info" => Some(NotifyLevel::Info),
+            "warn" => Some(NotifyLevel::Warn),
+            "error" => Some(NotifyLevel::Error),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotifyLevel::Info => "info",
+            NotifyLevel::Warn => "warn",
+            NotifyLevel::Error => "error",
+        }
+    }
+
+    /// Background and text colors
+    pub fn colors(&self) -> (u32, u32) {
+        match self {
+            NotifyLevel::Info => (DARK_GRAY, WHITE),
+            NotifyLevel::Warn => (WARN_BACKGROUND, BLACK),
+            NotifyLevel::Error => (RED, WHITE),
+        }
+    }
+}
+
+/// One banner and when it goes away
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    pub text: String,
+    pub level: NotifyLevel,
+    pub expires: Instant,
+}
+
+/// The toasts on screen, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Toasts {
+    items: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show `text` from `now` for `secs` seconds (at most `MAX_NOTIFY_SECS`)
+    pub fn push(&mut self, text: &str, secs: u64, level: NotifyLevel, now: Instant) {
+        if self.items.len() == MAX_TOASTS {
+            self.items.remove(0);
+        }
+        self.items.push(Toast {
+            text: text.to_string(),
+            level,
+            expires: now + Duration::from_secs(secs.min(MAX_NOTIFY_SECS)),
+        });
+    }
+
+    /// Drop toasts that have expired by `now`, returning whether any did
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let before = self.items.len();
+        self.items.retain(|toast| toast.expires > now);
+        self.items.len() != before
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// All toasts, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Toast> {
+        self.items.iter()
+    }
+
+    /// Bounds of each toast as (x, y, width, height), oldest first: centered
+    /// and stacked up from the bottom of the canvas, the newest lowest
+    pub fn bounds(&self) -> Vec<(usize, usize, usize, usize)> {
+        let max_width = window_width() - 2 * MARGIN;
+        let mut bottom = canvas_bottom() - MARGIN;
+        let mut bounds: Vec<_> = self
+            .items
+            .iter()
+            .rev()
+            .map(|toast| {
+                let width = (text_width(&toast.text) + 2 * PADDING).min(max_width);
+                let y = bottom.saturating_sub(TOAST_HEIGHT).max(CANVAS_TOP);
+                bottom = y.saturating_sub(GAP);
+                ((window_width() - width) / 2, y, width, TOAST_HEIGHT)
+            })
+            .collect();
+        bounds.reverse();
+        bounds
+    }
+
+    /// Draw the toasts onto a window-sized frame
+    pub fn draw(&self, frame: &mut [u32]) {
+        for (toast, (x, y, w, h)) in self.items.iter().zip(self.bounds()) {
+            let (background, color) = toast.level.colors();
+            fill_rectangle(frame, x, y, x + w - 1, y + h - 1, background);
+            // Text too wide for the window is cut off at the right
+            let room = (w - 2 * PADDING + 1) / GLYPH_ADVANCE;
+            let text: String = toast.text.chars().take(room).collect();
+            draw_text(frame, x + PADDING, y + PADDING, &text, color);
+        }
+    }
+}
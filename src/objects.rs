@@ -0,0 +1,931 @@
+//! Retained objects drawn over the canvas.
+//!
+//! This module handles:
+//! - `Objects`, the ordered set of retained objects, each addressed by an ID
+//! - Text objects, created with `obj text` and edited in place with `obj settext`;
+//!   their text is a template whose `{name}` placeholders show variables
+//! - Clock and countdown objects, refreshed once a second by `tick`
+//! - Buttons, which show a pressed state and name an event sent when clicked
+//! - Sliders, dragged to set a variable between a minimum and maximum
+//! - Checkboxes and toggle switches, clicked to turn a variable `on` or `off`
+//! - Text inputs, which take keyboard focus when clicked, edit a variable as
+//!   the user types, and submit it with Enter
+//! - Keyboard focus: which widget typed keys go to, moved by clicks and Tab
+//! - Blinking, set with `obj blink`: `tick` also toggles blinking objects on and off
+//! - Rendering the objects onto a frame, in creation order
+//! - Finding the object under a point, for pointer events
+//!
+//! Objects are not canvas pixels: they are drawn onto the presented frame and
+//! into snapshots, but never into the canvas itself, so editing or deleting
+//! one simply re-renders the frame without disturbing what is drawn beneath.
+//! Undo history does not cover them.
+
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::clock::{format_clock, format_countdown, unix_seconds};
+use crate::command::{done, reply, CommandResult, ObjectCommand};
+use crate::drawing::{draw_line, fill_rectangle};
+use crate::events::Event;
+use crate::font::{draw_text_scaled, text_width, GLYPH_HEIGHT};
+use crate::vars::Variables;
+use crate::{canvas_bottom, window_width, BLACK, CANVAS_TOP, DARK_GRAY, GRAY, WHITE};
+
+/// Largest text scale (each glyph pixel becomes a `scale` x `scale` block)
+pub const MAX_TEXT_SCALE: usize = 8;
+/// Shortest blink half-period (time shown, then time hidden), in milliseconds
+pub const MIN_BLINK_MS: u64 = 50;
+/// Longest blink half-period, in milliseconds
+pub const MAX_BLINK_MS: u64 = 10_000;
+/// Height of a slider (track and knob), in pixels
+pub const SLIDER_HEIGHT: usize = 16;
+/// Width of a slider's knob, in pixels
+pub const SLIDER_KNOB_WIDTH: usize = 8;
+/// Narrowest slider, in pixels
+pub const MIN_SLIDER_WIDTH: usize = 2 * SLIDER_KNOB_WIDTH;
+
+/// Height of a checkbox or toggle switch (and width of a checkbox), in pixels
+pub const CHECK_SIZE: usize = 12;
+/// Width of a toggle switch, in pixels
+pub const TOGGLE_WIDTH: usize = 2 * CHECK_SIZE;
+/// Space between a checkbox or switch and its label, in pixels
+pub const CHECK_LABEL_GAP: usize = 4;
+
+/// Height of a text input, in pixels
+pub const INPUT_HEIGHT: usize = GLYPH_HEIGHT + 2 * INPUT_PADDING;
+/// Space between a text input's border and its text, in pixels
+pub const INPUT_PADDING: usize = 4;
+/// Narrowest text input, in pixels
+pub const MIN_INPUT_WIDTH: usize = 24;
+/// Most characters a text input holds
+pub const MAX_INPUT_LEN: usize = 128;
+/// Space between a focused widget and the ring drawn around it, in pixels
+pub const FOCUS_RING_GAP: usize = 2;
+
+/// Whether `c` can be typed into a text input (printable ASCII other than `"`,
+/// so the text can be written back with `var set`)
+pub fn is_input_char(c: char) -> bool {
+    (' '..='~').contains(&c) && c != '"'
+}
+
+/// Whether a checkbox's variable text means checked (`on`, `true`, or `1`)
+pub fn is_checked(text: Option<&str>) -> bool {
+    matches!(text.map(str::trim), Some("on" | "true" | "1"))
+}
+
+/// Flip a checkbox's variable between `on` and `off`, returning the change event
+fn flip_check(var: &str, vars: &mut Variables) -> Event {
+    let value = if is_checked(vars.get(var)) {
+        "off"
+    } else {
+        "on"
+    };
+    vars.set(var, value);
+    Event::Changed {
+        name: var.to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// How an on/off widget looks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStyle {
+    /// A square box, ticked when on
+    Checkbox,
+    /// A sliding switch, knob on the right when on
+    Toggle,
+}
+
+impl CheckStyle {
+    /// Protocol verb, also used by `obj list`
+    pub fn name(&self) -> &'static str {
+        match self {
+            CheckStyle::Checkbox => "checkbox",
+            CheckStyle::Toggle => "toggle",
+        }
+    }
+
+    /// Width of the box or switch drawn before the label
+    pub fn indicator_width(&self) -> usize {
+        match self {
+            CheckStyle::Checkbox => CHECK_SIZE,
+            CheckStyle::Toggle => TOGGLE_WIDTH,
+        }
+    }
+
+    /// Width and height of the widget with `label`
+    pub fn size(&self, label: &str) -> (usize, usize) {
+        let label_width = text_width(label);
+        let gap = if label_width > 0 { CHECK_LABEL_GAP } else { 0 };
+        (self.indicator_width() + gap + label_width, CHECK_SIZE)
+    }
+}
+
+/// The value a slider over `min..=max` shows for its variable's text: the
+/// number clamped into range, or `min` if the variable is unset or not a number
+pub fn slider_value(text: Option<&str>, min: i64, max: i64) -> i64 {
+    text.and_then(|t| t.trim().parse::<i64>().ok())
+        .map_or(min, |v| v.clamp(min, max))
+}
+
+/// What a retained object is and how it draws
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectKind {
+    Text {
+        /// Template, expanded with the current variables when drawn
+        text: String,
+        /// Text color as `0xRRGGBB` (None = transparent, drawn as nothing)
+        color: Option<u32>,
+        scale: usize,
+    },
+    /// The current UTC time, in a `format_clock` format
+    Clock {
+        format: String,
+        color: Option<u32>,
+        /// Text as of the last `tick`
+        shown: String,
+    },
+    /// Time left until `deadline`, stopping at zero
+    Countdown {
+        deadline: Instant,
+        color: Option<u32>,
+        /// Text as of the last `tick`
+        shown: String,
+    },
+    /// A `w` x `h` button with a centered label
+    Button {
+        /// Template, expanded with the current variables when drawn
+        label: String,
+        /// Sent to subscribers when the button is clicked
+        event: String,
+        w: usize,
+        h: usize,
+        /// Label color (None = no label drawn)
+        color: Option<u32>,
+        /// Held down by the mouse
+        pressed: bool,
+    },
+    /// A `w` wide slider whose knob shows (and drags) the integer in `var`
+    Slider {
+        w: usize,
+        min: i64,
+        max: i64,
+        /// Variable holding the value
+        var: String,
+        /// Knob color (None = gray)
+        color: Option<u32>,
+    },
+    /// A checkbox or toggle switch showing (and flipping) `var` between `on` and `off`
+    Check {
+        style: CheckStyle,
+        /// Template, expanded with the current variables when drawn
+        label: String,
+        /// Variable holding `on` or `off`
+        var: String,
+        /// Label and check mark color (None = no label; mark drawn black)
+        color: Option<u32>,
+    },
+    /// A `w` wide single-line text field editing the text in `var`
+    Input {
+        w: usize,
+        /// Variable holding the text typed so far
+        var: String,
+        /// Text color (None = black)
+        color: Option<u32>,
+    },
+}
+
+impl ObjectKind {
+    /// Short name used by `obj list`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectKind::Text { .. } => "text",
+            ObjectKind::Clock { .. } => "clock",
+            ObjectKind::Countdown { .. } => "countdown",
+            ObjectKind::Button { .. } => "button",
+            ObjectKind::Slider { .. } => "slider",
+            ObjectKind::Check { style, .. } => style.name(),
+            ObjectKind::Input { .. } => "input",
+        }
+    }
+
+    /// Whether the object takes mouse presses and keyboard focus itself
+    /// (rather than only reporting presses to subscribers)
+    pub fn is_interactive(&self) -> bool {
+        matches!(
+            self,
+            ObjectKind::Button { .. }
+                | ObjectKind::Slider { .. }
+                | ObjectKind::Check { .. }
+                | ObjectKind::Input { .. }
+        )
+    }
+
+    /// Refresh time-driven text, returning whether it changed
+    fn tick(&mut self, now: Instant, wall: SystemTime) -> bool {
+        let (shown, text) = match self {
+            ObjectKind::Text { .. }
+            | ObjectKind::Button { .. }
+            | ObjectKind::Slider { .. }
+            | ObjectKind::Check { .. }
+            | ObjectKind::Input { .. } => return false,
+            ObjectKind::Clock { format, shown, .. } => {
+                (shown, format_clock(format, unix_seconds(wall)))
+            }
+            ObjectKind::Countdown {
+                deadline, shown, ..
+            } => {
+                // Round up, so a 10s countdown shows 00:10 for its first second
+                let left = deadline.saturating_duration_since(now);
+                let secs = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+                (shown, format_countdown(secs))
+            }
+        };
+        if *shown == text {
+            return false;
+        }
+        *shown = text;
+        true
+    }
+}
+
+/// Blinking of an object: shown for `period`, hidden for `period`, repeating
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blink {
+    pub period: Duration,
+    /// When the object was last shown at the start of a period
+    pub since: Instant,
+}
+
+impl Blink {
+    /// Whether a blinking object is shown at `now`
+    pub fn shown_at(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.since).as_millis();
+        (elapsed / self.period.as_millis()).is_multiple_of(2)
+    }
+}
+
+/// A retained object anchored at its top-left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub id: usize,
+    pub x: usize,
+    pub y: usize,
+    pub kind: ObjectKind,
+    /// Blink timing (None = steady)
+    pub blink: Option<Blink>,
+    /// Whether the object is drawn (false during the off half of a blink)
+    pub visible: bool,
+}
+
+impl Object {
+    /// The text the object shows, with templates expanded from `vars`
+    pub fn text(&self, vars: &Variables) -> String {
+        match &self.kind {
+            ObjectKind::Text { text, .. }
+            | ObjectKind::Button { label: text, .. }
+            | ObjectKind::Check { label: text, .. } => vars.expand(text),
+            ObjectKind::Clock { shown, .. } | ObjectKind::Countdown { shown, .. } => shown.clone(),
+            ObjectKind::Slider { min, max, var, .. } => {
+                slider_value(vars.get(var), *min, *max).to_string()
+            }
+            ObjectKind::Input { var, .. } => vars.get(var).unwrap_or("").to_string(),
+        }
+    }
+
+    /// Color and scale the text is drawn with
+    fn style(&self) -> (Option<u32>, usize) {
+        match &self.kind {
+            ObjectKind::Text { color, scale, .. } => (*color, *scale),
+            ObjectKind::Clock { color, .. }
+            | ObjectKind::Countdown { color, .. }
+            | ObjectKind::Button { color, .. }
+            | ObjectKind::Slider { color, .. }
+            | ObjectKind::Check { color, .. }
+            | ObjectKind::Input { color, .. } => (*color, 1),
+        }
+    }
+
+    /// Bounds of what the object draws with `vars`, as (x, y, width, height)
+    pub fn bounds(&self, vars: &Variables) -> (usize, usize, usize, usize) {
+        match self.kind {
+            ObjectKind::Button { w, h, .. } => return (self.x, self.y, w, h),
+            ObjectKind::Slider { w, .. } => return (self.x, self.y, w, SLIDER_HEIGHT),
+            ObjectKind::Input { w, .. } => return (self.x, self.y, w, INPUT_HEIGHT),
+            ObjectKind::Check { style, .. } => {
+                let (w, h) = style.size(&self.text(vars));
+                return (self.x, self.y, w, h);
+            }
+            _ => {}
+        }
+        let scale = self.style().1;
+        (
+            self.x,
+            self.y,
+            text_width(&self.text(vars)) * scale,
+            GLYPH_HEIGHT * scale,
+        )
+    }
+
+    /// The value a slider takes with the pointer at column `x`: its range
+    /// spread evenly across its width, clamped at the ends
+    pub fn slider_value_at(&self, x: usize) -> Option<i64> {
+        let ObjectKind::Slider { w, min, max, .. } = self.kind else {
+            return None;
+        };
+        let offset = x.saturating_sub(self.x).min(w - 1) as i128;
+        let span = i128::from(max) - i128::from(min);
+        // Round to the nearest value
+        let steps = (offset * span * 2 + (w as i128 - 1)) / (2 * (w as i128 - 1));
+        Some((i128::from(min) + steps) as i64)
+    }
+
+    /// Draw the box or switch of a checkbox or toggle at the object's position
+    fn draw_check(&self, frame: &mut [u32], style: CheckStyle, checked: bool) {
+        let (x, y) = (self.x, self.y);
+        let right = x + style.indicator_width() - 1;
+        let bottom = y + CHECK_SIZE - 1;
+        let mark = self.style().0.unwrap_or(BLACK);
+        match style {
+            CheckStyle::Checkbox => {
+                fill_rectangle(frame, x, y, right, bottom, WHITE);
+                if checked {
+                    // A tick: short stroke down, long stroke up
+                    for dx in 0..2 {
+                        draw_line(frame, x + 2 + dx, y + 6, x + 4 + dx, y + 9, mark);
+                        draw_line(frame, x + 4 + dx, y + 9, x + 8 + dx, y + 2, mark);
+                    }
+                }
+            }
+            CheckStyle::Toggle => {
+                let track = if checked { mark } else { GRAY };
+                fill_rectangle(frame, x, y, right, bottom, track);
+                let knob = if checked { right + 1 - CHECK_SIZE } else { x };
+                let knob_right = knob + CHECK_SIZE - 1;
+                fill_rectangle(frame, knob + 1, y + 1, knob_right - 1, bottom - 1, WHITE);
+            }
+        }
+        draw_line(frame, x, y, right, y, DARK_GRAY);
+        draw_line(frame, x, bottom, right, bottom, DARK_GRAY);
+        draw_line(frame, x, y, x, bottom, DARK_GRAY);
+        draw_line(frame, right, y, right, bottom, DARK_GRAY);
+    }
+
+    /// Draw a text input: a white field with its text, scrolled to show the
+    /// end, and a caret after it while `focused`
+    fn draw_input(&self, frame: &mut [u32], w: usize, text: &str, focused: bool) {
+        let (right, bottom) = (self.x + w - 1, self.y + INPUT_HEIGHT - 1);
+        let border = if focused { BLACK } else { DARK_GRAY };
+        fill_rectangle(frame, self.x, self.y, right, bottom, WHITE);
+        draw_line(frame, self.x, self.y, right, self.y, border);
+        draw_line(frame, self.x, bottom, right, bottom, border);
+        draw_line(frame, self.x, self.y, self.x, bottom, border);
+        draw_line(frame, right, self.y, right, bottom, border);
+
+        // Drop leading characters until the text (and caret) fits
+        let room = w.saturating_sub(2 * INPUT_PADDING + 2);
+        let mut shown = text;
+        while text_width(shown) > room {
+            shown = &shown[shown.chars().next().map_or(0, char::len_utf8)..];
+        }
+        let (x, y) = (self.x + INPUT_PADDING, self.y + INPUT_PADDING);
+        let color = self.style().0.unwrap_or(BLACK);
+        draw_text_scaled(frame, x, y, shown, color, 1);
+        if focused {
+            let caret = x + text_width(shown) + 1;
+            draw_line(frame, caret, y - 1, caret, y + GLYPH_HEIGHT, color);
+        }
+    }
+
+    /// Draw a ring just outside the object's bounds, marking keyboard focus
+    fn draw_focus_ring(&self, frame: &mut [u32], vars: &Variables) {
+        let (x, y, w, h) = self.bounds(vars);
+        let (left, top) = (
+            x.saturating_sub(FOCUS_RING_GAP + 1),
+            y.saturating_sub(FOCUS_RING_GAP + 1),
+        );
+        let (right, bottom) = (x + w + FOCUS_RING_GAP, y + h + FOCUS_RING_GAP);
+        draw_line(frame, left, top, right, top, BLACK);
+        draw_line(frame, left, bottom, right, bottom, BLACK);
+        draw_line(frame, left, top, left, bottom, BLACK);
+        draw_line(frame, right, top, right, bottom, BLACK);
+    }
+
+    /// Draw the object onto a window-sized frame (`focused` if it has keyboard
+    /// focus: text inputs show a caret, other widgets a focus ring)
+    fn draw(&self, frame: &mut [u32], vars: &Variables, focused: bool) {
+        let (color, scale) = self.style();
+        if let ObjectKind::Input { w, .. } = self.kind {
+            self.draw_input(frame, w, &self.text(vars), focused);
+            return;
+        }
+        if focused {
+            self.draw_focus_ring(frame, vars);
+        }
+        if let ObjectKind::Slider {
+            w,
+            min,
+            max,
+            ref var,
+            ..
+        } = self.kind
+        {
+            let right = self.x + w - 1;
+            let mid = self.y + SLIDER_HEIGHT / 2;
+            fill_rectangle(frame, self.x, mid - 1, right, mid, DARK_GRAY);
+            // Knob centered on the value's position, kept within the track
+            let value = i128::from(slider_value(vars.get(var), min, max));
+            let span = i128::from(max) - i128::from(min);
+            let center = self.x + ((value - i128::from(min)) * (w as i128 - 1) / span) as usize;
+            let left = center
+                .saturating_sub(SLIDER_KNOB_WIDTH / 2)
+                .clamp(self.x, right + 1 - SLIDER_KNOB_WIDTH);
+            let (knob_right, bottom) = (left + SLIDER_KNOB_WIDTH - 1, self.y + SLIDER_HEIGHT - 1);
+            fill_rectangle(
+                frame,
+                left,
+                self.y,
+                knob_right,
+                bottom,
+                color.unwrap_or(GRAY),
+            );
+            draw_line(frame, left, self.y, knob_right, self.y, BLACK);
+            draw_line(frame, left, bottom, knob_right, bottom, BLACK);
+            draw_line(frame, left, self.y, left, bottom, BLACK);
+            draw_line(frame, knob_right, self.y, knob_right, bottom, BLACK);
+            return;
+        }
+        let text = self.text(vars);
+        let (x, y) = match self.kind {
+            ObjectKind::Check { style, ref var, .. } => {
+                self.draw_check(frame, style, is_checked(vars.get(var)));
+                (
+                    self.x + style.indicator_width() + CHECK_LABEL_GAP,
+                    self.y + (CHECK_SIZE - GLYPH_HEIGHT) / 2,
+                )
+            }
+            ObjectKind::Button { w, h, pressed, .. } => {
+                let (right, bottom) = (self.x + w - 1, self.y + h - 1);
+                let (face, border) = if pressed {
+                    (DARK_GRAY, BLACK)
+                } else {
+                    (GRAY, DARK_GRAY)
+                };
+                fill_rectangle(frame, self.x, self.y, right, bottom, face);
+                draw_line(frame, self.x, self.y, right, self.y, border);
+                draw_line(frame, self.x, bottom, right, bottom, border);
+                draw_line(frame, self.x, self.y, self.x, bottom, border);
+                draw_line(frame, right, self.y, right, bottom, border);
+                // Center the label; pressed buttons nudge it down and right
+                let nudge = usize::from(pressed);
+                (
+                    self.x + w.saturating_sub(text_width(&text)) / 2 + nudge,
+                    self.y + h.saturating_sub(GLYPH_HEIGHT) / 2 + nudge,
+                )
+            }
+            _ => (self.x, self.y),
+        };
+        if let Some(color) = color {
+            draw_text_scaled(frame, x, y, &text, color, scale);
+        }
+    }
+}
+
+/// Retained objects in creation (and drawing) order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Objects {
+    items: Vec<Object>,
+    next_id: usize,
+    /// The widget receiving typed keys
+    focus: Option<usize>,
+}
+
+impl Objects {
+    pub fn new() -> Self {
+        Objects {
+            items: Vec::new(),
+            next_id: 1,
+            focus: None,
+        }
+    }
+
+    /// Add an object, returning its ID (IDs start at 1 and are never reused)
+    pub fn add(&mut self, x: usize, y: usize, kind: ObjectKind) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.items.push(Object {
+            id,
+            x,
+            y,
+            kind,
+            blink: None,
+            visible: true,
+        });
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Object> {
+        self.items.iter().find(|o| o.id == id)
+    }
+
+    /// Replace the text of a text object or the label of a button, checkbox, or toggle
+    pub fn set_text(&mut self, id: usize, new_text: &str) -> Result<(), String> {
+        let object = self
+            .items
+            .iter_mut()
+            .find(|o| o.id == id)
+            .ok_or_else(|| format!("no object {}", id))?;
+        match &mut object.kind {
+            ObjectKind::Text { text, .. }
+            | ObjectKind::Button { label: text, .. }
+            | ObjectKind::Check { label: text, .. } => {
+                *text = new_text.to_string();
+                Ok(())
+            }
+            kind => Err(format!("object {} is a {}, not text", id, kind.name())),
+        }
+    }
+
+    /// Start blinking an object with half-period `period` from `now`, or
+    /// stop blinking (and show it) when `period` is None
+    pub fn set_blink(
+        &mut self,
+        id: usize,
+        period: Option<Duration>,
+        now: Instant,
+    ) -> Result<(), String> {
+        let object = self
+            .items
+            .iter_mut()
+            .find(|o| o.id == id)
+            .ok_or_else(|| format!("no object {}", id))?;
+        object.blink = period.map(|period| Blink { period, since: now });
+        object.visible = true;
+        Ok(())
+    }
+
+    /// Press an object with the mouse, returning the event it sends (buttons
+    /// show their pressed state and send their event name; checkboxes and
+    /// toggles flip their variable and report the change)
+    ///
+    /// Pressing a widget gives it keyboard focus; pressing anything else
+    /// takes focus away.
+    pub fn press(&mut self, id: usize, vars: &mut Variables) -> Option<Event> {
+        let object = self.items.iter_mut().find(|o| o.id == id)?;
+        self.focus = object.kind.is_interactive().then_some(id);
+        match &mut object.kind {
+            ObjectKind::Button { event, pressed, .. } => {
+                *pressed = true;
+                Some(Event::Named(event.clone()))
+            }
+            ObjectKind::Check { var, .. } => Some(flip_check(var, vars)),
+            _ => None,
+        }
+    }
+
+    /// Drag a slider's knob to column `x`, storing the new value in its
+    /// variable; returns the change event if the value changed
+    pub fn drag(&self, id: usize, x: usize, vars: &mut Variables) -> Option<Event> {
+        let object = self.get(id)?;
+        let ObjectKind::Slider { min, max, var, .. } = &object.kind else {
+            return None;
+        };
+        let value = object.slider_value_at(x)?;
+        if vars.get(var).is_some() && slider_value(vars.get(var), *min, *max) == value {
+            return None;
+        }
+        vars.set(var, &value.to_string());
+        Some(Event::Changed {
+            name: var.clone(),
+            value: value.to_string(),
+        })
+    }
+
+    /// Move a slider's value by `delta` steps (clamped to its range), storing
+    /// it in its variable; returns the change event if the value changed
+    pub fn step_slider(&self, id: usize, delta: i128, vars: &mut Variables) -> Option<Event> {
+        let ObjectKind::Slider { min, max, var, .. } = &self.get(id)?.kind else {
+            return None;
+        };
+        let current = slider_value(vars.get(var), *min, *max);
+        let value = (i128::from(current) + delta).clamp(i128::from(*min), i128::from(*max)) as i64;
+        if value == current {
+            return None;
+        }
+        vars.set(var, &value.to_string());
+        Some(Event::Changed {
+            name: var.clone(),
+            value: value.to_string(),
+        })
+    }
+
+    /// The widget with keyboard focus
+    pub fn focused(&self) -> Option<usize> {
+        self.focus
+    }
+
+    /// Give keyboard focus to a widget, or take it away (None, or any object
+    /// that isn't a widget); returns whether focus changed
+    pub fn set_focus(&mut self, id: Option<usize>) -> bool {
+        let focus = id.filter(|&id| self.get(id).is_some_and(|o| o.kind.is_interactive()));
+        let changed = focus != self.focus;
+        self.focus = focus;
+        changed
+    }
+
+    /// Move focus to the next widget in creation order (the previous one when
+    /// `backward`), wrapping around; with nothing focused, Tab starts at the
+    /// first widget and Shift+Tab at the last
+    ///
+    /// Returns the newly focused widget, or None if there are no widgets.
+    pub fn focus_next(&mut self, backward: bool) -> Option<usize> {
+        let widgets: Vec<usize> = self
+            .items
+            .iter()
+            .filter(|o| o.kind.is_interactive())
+            .map(|o| o.id)
+            .collect();
+        let count = widgets.len();
+        let current = self
+            .focus
+            .and_then(|id| widgets.iter().position(|&w| w == id));
+        let next = match (current, backward) {
+            (None, false) => 0,
+            (None, true) => count.checked_sub(1)?,
+            (Some(i), false) => (i + 1) % count,
+            (Some(i), true) => (i + count - 1) % count,
+        };
+        self.focus = widgets.get(next).copied();
+        self.focus
+    }
+
+    /// Activate the focused button, checkbox, or toggle from the keyboard
+    /// (Enter or Space), returning the event a click would send
+    ///
+    /// Buttons don't show a pressed state: there is no key release to end it.
+    pub fn activate(&self, vars: &mut Variables) -> Option<Event> {
+        match &self.get(self.focus?)?.kind {
+            ObjectKind::Button { event, .. } => Some(Event::Named(event.clone())),
+            ObjectKind::Check { var, .. } => Some(flip_check(var, vars)),
+            _ => None,
+        }
+    }
+
+    /// Variable edited by the focused text input
+    fn focused_var(&self) -> Option<&str> {
+        match &self.get(self.focus?)?.kind {
+            ObjectKind::Input { var, .. } => Some(var),
+            _ => None,
+        }
+    }
+
+    /// Type `c` into the focused text input, returning whether its text changed
+    ///
+    /// Characters that can't be typed (see `is_input_char`) and characters past
+    /// `MAX_INPUT_LEN` are ignored.
+    pub fn type_char(&self, c: char, vars: &mut Variables) -> bool {
+        let Some(var) = self.focused_var() else {
+            return false;
+        };
+        let mut text = vars.get(var).unwrap_or("").to_string();
+        if !is_input_char(c) || text.len() >= MAX_INPUT_LEN {
+            return false;
+        }
+        text.push(c);
+        vars.set(var, &text);
+        true
+    }
+
+    /// Delete the last character of the focused text input, returning whether
+    /// its text changed
+    pub fn backspace(&self, vars: &mut Variables) -> bool {
+        let Some(var) = self.focused_var() else {
+            return false;
+        };
+        let mut text = vars.get(var).unwrap_or("").to_string();
+        if text.pop().is_none() {
+            return false;
+        }
+        vars.set(var, &text);
+        true
+    }
+
+    /// Submit the focused text input: returns the submit event carrying its
+    /// text, and clears the field for the next entry
+    pub fn submit(&self, vars: &mut Variables) -> Option<Event> {
+        let var = self.focused_var()?;
+        let value = vars.get(var).unwrap_or("").to_string();
+        vars.set(var, "");
+        Some(Event::Submitted {
+            name: var.to_string(),
+            value,
+        })
+    }
+
+    /// Release every pressed button, returning whether any was pressed
+    pub fn release(&mut self) -> bool {
+        let mut changed = false;
+        for object in &mut self.items {
+            if let ObjectKind::Button { pressed, .. } = &mut object.kind {
+                changed |= *pressed;
+                *pressed = false;
+            }
+        }
+        changed
+    }
+
+    /// Remove an object
+    pub fn remove(&mut self, id: usize) -> Result<(), String> {
+        let index = self
+            .items
+            .iter()
+            .position(|o| o.id == id)
+            .ok_or_else(|| format!("no object {}", id))?;
+        self.items.remove(index);
+        if self.focus == Some(id) {
+            self.focus = None;
+        }
+        Ok(())
+    }
+
+    /// Apply an object command, with `edge_color` as the color of new objects
+    ///
+    /// Returns the output: the new ID for commands that create an object,
+    /// the object list for `obj list`, or why it failed.
+    pub fn apply(&mut self, cmd: &ObjectCommand, edge_color: Option<u32>) -> CommandResult {
+        let result = match cmd {
+            ObjectCommand::Text { x, y, text, scale } => {
+                let kind = ObjectKind::Text {
+                    text: text.clone(),
+                    color: edge_color,
+                    scale: *scale,
+                };
+                return reply(format!("id:{}", self.add(*x, *y, kind)));
+            }
+            ObjectCommand::Clock { x, y, format } => {
+                let mut kind = ObjectKind::Clock {
+                    format: format.clone(),
+                    color: edge_color,
+                    shown: String::new(),
+                };
+                kind.tick(Instant::now(), SystemTime::now());
+                return reply(format!("id:{}", self.add(*x, *y, kind)));
+            }
+            ObjectCommand::Countdown { x, y, secs } => {
+                let now = Instant::now();
+                let mut kind = ObjectKind::Countdown {
+                    deadline: now + Duration::from_secs(*secs),
+                    color: edge_color,
+                    shown: String::new(),
+                };
+                kind.tick(now, SystemTime::now());
+                return reply(format!("id:{}", self.add(*x, *y, kind)));
+            }
+            ObjectCommand::Button {
+                x,
+                y,
+                w,
+                h,
+                label,
+                event,
+            } => {
+                let kind = ObjectKind::Button {
+                    label: label.clone(),
+                    event: event.clone(),
+                    w: *w,
+                    h: *h,
+                    color: edge_color,
+                    pressed: false,
+                };
+                return reply(format!("id:{}", self.add(*x, *y, kind)));
+            }
+            ObjectCommand::Slider {
+                x,
+                y,
+                w,
+                min,
+                max,
+                var,
+            } => {
+                let kind = ObjectKind::Slider {
+                    w: *w,
+                    min: *min,
+                    max: *max,
+                    var: var.clone(),
+                    color: edge_color,
+                };
+                return reply(format!("id:{}", self.add(*x, *y, kind)));
+            }
+            ObjectCommand::Check {
+                x,
+                y,
+                style,
+                label,
+                var,
+            } => {
+                let kind = ObjectKind::Check {
+                    style: *style,
+                    label: label.clone(),
+                    var: var.clone(),
+                    color: edge_color,
+                };
+                return reply(format!("id:{}", self.add(*x, *y, kind)));
+            }
+            ObjectCommand::Input { x, y, w, var } => {
+                let kind = ObjectKind::Input {
+                    w: *w,
+                    var: var.clone(),
+                    color: edge_color,
+                };
+                return reply(format!("id:{}", self.add(*x, *y, kind)));
+            }
+            ObjectCommand::SetText { id, text } => self.set_text(*id, text),
+            ObjectCommand::Blink { id, ms } => {
+                let period = (*ms > 0).then(|| Duration::from_millis(*ms));
+                self.set_blink(*id, period, Instant::now())
+            }
+            ObjectCommand::Delete(id) => self.remove(*id),
+            ObjectCommand::List => return reply(self.list()),
+        };
+        done(result)
+    }
+
+    /// `id:kind` for every object, oldest first (`none` when empty)
+    pub fn list(&self) -> String {
+        if self.items.is_empty() {
+            return "none".to_string();
+        }
+        self.items
+            .iter()
+            .map(|o| format!("{}:{}", o.id, o.kind.name()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Refresh clocks, countdowns, and blinking for the time `now` (monotonic)
+    /// and `wall` (calendar), returning whether anything drawn changed
+    ///
+    /// The run loop calls this every frame; clock text only changes once a
+    /// second, and blinking objects at the end of each half-period.
+    pub fn tick(&mut self, now: Instant, wall: SystemTime) -> bool {
+        let mut changed = false;
+        for object in &mut self.items {
+            changed |= object.kind.tick(now, wall);
+            if let Some(blink) = object.blink {
+                let visible = blink.shown_at(now);
+                changed |= visible != object.visible;
+                object.visible = visible;
+            }
+        }
+        changed
+    }
+
+    /// Draw every object onto a window-sized frame, clipped to the canvas area,
+    /// with text templates expanded from `vars`
+    pub fn draw(&self, frame: &mut [u32], vars: &Variables) {
+        if self.items.is_empty() {
+            return;
+        }
+        let width = window_width();
+        let bottom = canvas_bottom();
+        // Keep the title bar and toolbar rows as they are
+        let top_rows = frame[..CANVAS_TOP * width].to_vec();
+        let bottom_rows = frame[bottom * width..].to_vec();
+        for object in self.items.iter().filter(|o| o.visible) {
+            object.draw(frame, vars, self.focus == Some(object.id));
+        }
+        frame[..CANVAS_TOP * width].copy_from_slice(&top_rows);
+        frame[bottom * width..].copy_from_slice(&bottom_rows);
+    }
+
+    /// ID of the topmost shown object whose bounds contain (x, y)
+    pub fn hit(&self, x: usize, y: usize, vars: &Variables) -> Option<usize> {
+        self.items
+            .iter()
+            .rev()
+            .filter(|o| o.visible)
+            .find(|o| {
+                let (ox, oy, w, h) = o.bounds(vars);
+                (ox..ox + w).contains(&x) && (oy..oy + h).contains(&y)
+            })
+            .map(|o| o.id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// All objects, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Object> {
+        self.items.iter()
+    }
+}
+
+impl Default for Objects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,103 @@
+//! Offscreen rendering with per-operation timing.
+//!
+//! This module handles:
+//! - An `OffscreenRenderer` that executes commands against its own canvas
+//! - Recording how long each command took to rasterize
+//! - Summarizing timings per primitive, to find what dominates a workload
+
+use std::time::{Duration, Instant};
+
+use crate::command::Command;
+use crate::{AppState, Canvas};
+
+/// How long one command took to execute
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpTiming {
+    pub command: Command,
+    pub duration: Duration,
+}
+
+/// Aggregate timing for one kind of command
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpSummary {
+    pub name: &'static str,
+    pub count: usize,
+    pub total: Duration,
+}
+
+/// A canvas plus tool state that executes commands without a window,
+/// timing every command it runs
+#[derive(Debug, Clone)]
+pub struct OffscreenRenderer {
+    state: AppState,
+    timings: Vec<OpTiming>,
+}
+
+impl OffscreenRenderer {
+    /// Create a renderer with a blank canvas and the same defaults as the app
+    pub fn new() -> Self {
+        OffscreenRenderer {
+            state: AppState::new(),
+            timings: Vec::new(),
+        }
+    }
+
+    /// Execute a command, returning its response and how long it took
+    pub fn execute(&mut self, cmd: &Command) -> (Option<String>, Duration) {
+        let start = Instant::now();
+        let response = self.state.execute(cmd);
+        let duration = start.elapsed();
+        self.timings.push(OpTiming {
+            command: cmd.clone(),
+            duration,
+        });
+        (response, duration)
+    }
+
+    /// Execute a list of commands, returning the duration of each
+    pub fn execute_all(&mut self, commands: &[Command]) -> Vec<Duration> {
+        commands.iter().map(|cmd| self.execute(cmd).1).collect()
+    }
+
+    /// Every timing recorded since creation or the last `reset_timings`
+    pub fn timings(&self) -> &[OpTiming] {
+        &self.timings
+    }
+
+    /// Forget recorded timings (the canvas is kept)
+    pub fn reset_timings(&mut self) {
+        self.timings.clear();
+    }
+
+    /// Total time per command kind, most expensive first
+    pub fn summary(&self) -> Vec<OpSummary> {
+        let mut summary: Vec<OpSummary> = Vec::new();
+        for timing in &self.timings {
+            let name = timing.command.name();
+            match summary.iter_mut().find(|s| s.name == name) {
+                Some(entry) => {
+                    entry.count += 1;
+                    entry.total += timing.duration;
+                }
+                None => summary.push(OpSummary {
+                    name,
+                    count: 1,
+                    total: timing.duration,
+                }),
+            }
+        }
+        summary.sort_by_key(|s| std::cmp::Reverse(s.total));
+        summary
+    }
+
+    /// The rendered canvas
+    pub fn canvas(&self) -> &Canvas {
+        &self.state.canvas
+    }
+}
+
+impl Default for OffscreenRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,95 @@
+//! The runtime color palette.
+//!
+//! This module handles:
+//! - `Palette`, the 14 swatch colors, reprogrammed with `palette set`
+//! - The palette that parsing, color formatting, and the bottom toolbar on this
+//!   thread resolve palette indices against
+//!
+//! The palette is part of `AppState`, but indices are read deep inside command
+//! parsing and toolbar layout, so (as with the window size) the owner installs
+//! its palette for the duration of a call with `with_palette`.
+
+use std::cell::Cell;
+
+use crate::COLOR_PALETTE;
+
+/// Number of palette slots
+pub const PALETTE_LEN: usize = COLOR_PALETTE.len();
+
+/// The colors behind palette indices `0-13`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    colors: [u32; PALETTE_LEN],
+}
+
+impl Palette {
+    /// The app's default palette (`COLOR_PALETTE`)
+    pub fn new() -> Self {
+        Palette {
+            colors: COLOR_PALETTE,
+        }
+    }
+
+    pub fn colors(&self) -> &[u32; PALETTE_LEN] {
+        &self.colors
+    }
+
+    pub fn get(&self, index: usize) -> Option<u32> {
+        self.colors.get(index).copied()
+    }
+
+    /// Replace the color in one slot
+    pub fn set(&mut self, index: usize, color: u32) -> Result<(), String> {
+        let slot = self.colors.get_mut(index).ok_or_else(|| {
+            format!(
+                "palette index {} is out of range (0-{})",
+                index,
+                PALETTE_LEN - 1
+            )
+        })?;
+        *slot = color;
+        Ok(())
+    }
+
+    /// Restore the default colors
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Index of the first slot holding `color`
+    pub fn index_of(&self, color: u32) -> Option<usize> {
+        self.colors.iter().position(|&c| c == color)
+    }
+
+    /// The colors as the protocol writes them (`#RRGGBB`, space-separated)
+    pub fn summary(&self) -> String {
+        self.colors
+            .iter()
+            .map(|c| format!("#{:06X}", c))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Palette> = const { Cell::new(Palette { colors: COLOR_PALETTE }) };
+}
+
+/// The palette code on this thread resolves indices against
+pub fn current_palette() -> Palette {
+    CURRENT.with(Cell::get)
+}
+
+/// Run `f` with `palette` as the current palette, restoring the previous one after
+pub fn with_palette<R>(palette: &Palette, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.replace(*palette));
+    let result = f();
+    CURRENT.with(|current| current.set(previous));
+    result
+}
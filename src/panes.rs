@@ -0,0 +1,607 @@
+//! Canvas panes: splitting the drawable area into independent parts.
+//!
+//! This module handles:
+//! - `Panes`, a grid of columns and rows laid over the canvas with a margin
+//!   around and between them (`panes 2x1 [margin]`, `panes off`)
+//! - Where each pane lies, numbered from 1 left to right, then top to bottom
+//! - `clip_to_pane`, which puts back whatever a command drew outside its pane
+//! - `PaneScale`, a pane's data range (`@N scale x0,y0 x1,y1 [axes]`), which
+//!   maps `plot` points in data units to pixels and can draw labelled axes
+//! - `TimeSeries`, samples appended one at a time with `tsplot`, scrolling
+//!   the plot area left once they reach its right edge
+//! - `HeldPane`, the pixels viewers see of a double-buffered pane (`hold`),
+//!   replaced all at once by `flip`
+//!
+//! A drawing command prefixed with `@N` runs in pane N: its coordinates are
+//! taken from the pane's top-left corner, and its pixels stop at the pane's
+//! edge, so two plots can be driven side by side without knowing where the
+//! other one is. Without a layout the whole canvas is pane 1.
+//!
+//! Scales and their ticks use `log10` and `powf`, which the `deterministic`
+//! feature does not replace, so log axes may differ by a pixel per platform.
+
+use std::fmt;
+
+use crate::drawing::draw_line;
+use crate::font::{draw_text, text_width, GLYPH_ADVANCE, GLYPH_HEIGHT};
+use crate::locks::Region;
+use crate::{canvas_bottom, window_width, CANVAS_TOP, WHITE};
+
+/// Most columns or rows a layout may have
+pub const MAX_PANES_PER_SIDE: usize = 8;
+/// Widest margin around and between panes, in pixels
+pub const MAX_PANE_MARGIN: usize = 100;
+
+/// A grid of panes over the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Panes {
+    pub cols: usize,
+    pub rows: usize,
+    /// Pixels left blank around and between panes
+    pub margin: usize,
+}
+
+impl Default for Panes {
+    /// One pane covering the whole canvas
+    fn default() -> Self {
+        Panes {
+            cols: 1,
+            rows: 1,
+            margin: 0,
+        }
+    }
+}
+
+impl fmt::Display for Panes {
+    /// Format as `CxR`, e.g. `2x1`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.cols, self.rows)
+    }
+}
+
+/// The start and end of span `i` of `n` along `length`, with `margin` pixels
+/// before the first, between each, and after the last
+///
+/// Leftover pixels are spread over the spans, which differ by one at most.
+fn span(length: usize, n: usize, margin: usize, i: usize) -> (usize, usize) {
+    let usable = length.saturating_sub(margin * (n + 1));
+    (
+        margin * (i + 1) + usable * i / n,
+        margin * (i + 1) + usable * (i + 1) / n,
+    )
+}
+
+impl Panes {
+    pub fn count(&self) -> usize {
+        self.cols * self.rows
+    }
+
+    /// Check the layout is within limits and leaves every pane at least a
+    /// pixel on the current canvas
+    pub fn check(&self) -> Result<(), String> {
+        let sides = 1..=MAX_PANES_PER_SIDE;
+        if !sides.contains(&self.cols) || !sides.contains(&self.rows) {
+            return Err(format!(
+                "panes {} is outside 1x1-{max}x{max}",
+                self,
+                max = MAX_PANES_PER_SIDE
+            ));
+        }
+        if self.margin > MAX_PANE_MARGIN {
+            return Err(format!(
+                "pane margin {} is outside 0-{}",
+                self.margin, MAX_PANE_MARGIN
+            ));
+        }
+        let fits = |length: usize, n: usize| length >= self.margin * (n + 1) + n;
+        if fits(window_width(), self.cols) && fits(canvas_bottom() - CANVAS_TOP, self.rows) {
+            Ok(())
+        } else {
+            Err(format!(
+                "panes {} with margin {} don't fit the canvas",
+                self, self.margin
+            ))
+        }
+    }
+
+    /// Where pane `index` (from 1) lies, in window coordinates, or None if
+    /// the layout has no such pane
+    pub fn pane(&self, index: usize) -> Option<Region> {
+        if index == 0 || index > self.count() {
+            return None;
+        }
+        let (col, row) = ((index - 1) % self.cols, (index - 1) / self.cols);
+        let (left, right) = span(window_width(), self.cols, self.margin, col);
+        let (top, bottom) = span(canvas_bottom() - CANVAS_TOP, self.rows, self.margin, row);
+        Some(Region::new(
+            left,
+            CANVAS_TOP + top,
+            right - left,
+            bottom - top,
+        ))
+    }
+}
+
+/// Put back the pixels of `canvas` outside `pane` from `before`, both `width`
+/// pixels wide
+pub fn clip_to_pane(canvas: &mut [u32], before: &[u32], width: usize, pane: Region) {
+    let right = pane.x.saturating_add(pane.width).min(width);
+    let bottom = pane.y.saturating_add(pane.height);
+    for (y, (row, saved)) in canvas
+        .chunks_exact_mut(width)
+        .zip(before.chunks_exact(width))
+        .enumerate()
+    {
+        if y < pane.y || y >= bottom {
+            row.copy_from_slice(saved);
+        } else {
+            let left = pane.x.min(width);
+            row[..left].copy_from_slice(&saved[..left]);
+            row[right..].copy_from_slice(&saved[right..]);
+        }
+    }
+}
+
+/// What viewers see of a double-buffered pane (`hold <pane> on`): its pixels
+/// as of the last `flip`, shown over the canvas beneath
+///
+/// Drawing in the pane still lands on the canvas, out of sight, so a chart
+/// can be cleared and redrawn without viewers ever seeing it blank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeldPane {
+    /// Where the pane lies, in window coordinates
+    pub region: Region,
+    /// The pane's pixels as shown, row by row
+    pub pixels: Vec<u32>,
+}
+
+impl HeldPane {
+    /// Hold `region` of `canvas`, `width` pixels wide, as it is now
+    pub fn capture(canvas: &[u32], width: usize, region: Region) -> Self {
+        let pixels = canvas
+            .chunks_exact(width)
+            .skip(region.y)
+            .take(region.height)
+            .flat_map(|row| &row[region.x..region.x + region.width])
+            .copied()
+            .collect();
+        HeldPane { region, pixels }
+    }
+
+    /// Draw the held pixels over `frame`, `width` pixels wide (nothing if the
+    /// pane no longer fits it)
+    pub fn draw(&self, frame: &mut [u32], width: usize) {
+        let region = self.region;
+        let fits = region.x + region.width <= width
+            && (region.y + region.height) * width <= frame.len()
+            && self.pixels.len() == region.width * region.height;
+        if !fits || region.width == 0 {
+            return;
+        }
+        for (row, held) in frame
+            .chunks_exact_mut(width)
+            .skip(region.y)
+            .zip(self.pixels.chunks_exact(region.width))
+        {
+            row[region.x..region.x + region.width].copy_from_slice(held);
+        }
+    }
+}
+
+/// Pixels left of a pane's plot area for the y axis and its labels
+pub const AXIS_LEFT: usize = 40;
+/// Pixels below a pane's plot area for the x axis and its labels
+pub const AXIS_BOTTOM: usize = 14;
+/// Pixels kept clear above and right of a plot area with axes, so the
+/// outermost labels fit
+pub const AXIS_PAD: usize = 8;
+/// Length of an axis tick mark
+const TICK_LENGTH: usize = 3;
+/// Roughly how many ticks an axis gets
+const TARGET_TICKS: f64 = 5.0;
+/// Smallest axis extent (largest absolute value) labelled with a k, M, G, or
+/// T suffix
+const ABBREVIATE_FROM: f64 = 1e4;
+/// Suffixed labels needing more decimals than this are written out in full
+const MAX_ABBREVIATED_DECIMALS: i32 = 2;
+/// Suffixes for abbreviated labels, largest first
+const SUFFIXES: [(f64, &str); 4] = [(1e12, "T"), (1e9, "G"), (1e6, "M"), (1e3, "k")];
+/// Axis extents at or beyond this, or below `SCIENTIFIC_BELOW`, get
+/// scientific labels
+const SCIENTIFIC_FROM: f64 = 1e15;
+const SCIENTIFIC_BELOW: f64 = 1e-3;
+
+/// A pane's data range: `(x0, y0)` lands on the bottom-left corner of its
+/// plot area and `(x1, y1)` on the top-right, so y grows upward as on a chart
+///
+/// Either axis may be logarithmic, spacing each power of ten equally.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaneScale {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+    /// Map x logarithmically
+    pub log_x: bool,
+    /// Map y logarithmically
+    pub log_y: bool,
+    /// Leave room for, and draw, labelled axes
+    pub axes: bool,
+}
+
+impl fmt::Display for PaneScale {
+    /// Format as `scale` arguments, e.g. `0,-1 10,1 axes` or `1,0 1000,1 logx`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{} {},{}", self.x0, self.y0, self.x1, self.y1)?;
+        if self.log_x {
+            write!(f, " logx")?;
+        }
+        if self.log_y {
+            write!(f, " logy")?;
+        }
+        if self.axes {
+            write!(f, " axes")?;
+        }
+        Ok(())
+    }
+}
+
+impl PaneScale {
+    /// Check the range is made of numbers, isn't empty on either axis, and
+    /// is above zero on a log axis
+    pub fn check(&self) -> Result<(), String> {
+        let bounds = [self.x0, self.y0, self.x1, self.y1];
+        if !bounds.iter().all(|v| v.is_finite()) {
+            Err("scale bounds must be numbers".to_string())
+        } else if self.x0 == self.x1 || self.y0 == self.y1 {
+            Err(format!("scale {} is empty on an axis", self))
+        } else if self.log_x && self.x0.min(self.x1) <= 0.0 {
+            Err(format!(
+                "scale {} has a log x axis reaching 0 or below",
+                self
+            ))
+        } else if self.log_y && self.y0.min(self.y1) <= 0.0 {
+            Err(format!(
+                "scale {} has a log y axis reaching 0 or below",
+                self
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Where points are plotted within `pane`: all of it, or what the axes
+    /// leave (None if they leave nothing)
+    pub fn plot_area(&self, pane: Region) -> Option<Region> {
+        if !self.axes {
+            return Some(pane);
+        }
+        let width = pane.width.checked_sub(AXIS_LEFT + AXIS_PAD)?;
+        let height = pane.height.checked_sub(AXIS_BOTTOM + AXIS_PAD)?;
+        (width > 0 && height > 0)
+            .then(|| Region::new(pane.x + AXIS_LEFT, pane.y + AXIS_PAD, width, height))
+    }
+
+    /// Where data point (x, y) lands in `area`, in (fractional) window
+    /// coordinates; NaN on a log axis for values of 0 or below
+    pub fn to_pixel(&self, area: Region, x: f64, y: f64) -> (f64, f64) {
+        let right = (area.width - 1) as f64;
+        let down = (area.height - 1) as f64;
+        let (x, x0, x1) = (
+            along(x, self.log_x),
+            along(self.x0, self.log_x),
+            along(self.x1, self.log_x),
+        );
+        let (y, y0, y1) = (
+            along(y, self.log_y),
+            along(self.y0, self.log_y),
+            along(self.y1, self.log_y),
+        );
+        (
+            area.x as f64 + (x - x0) / (x1 - x0) * right,
+            area.y as f64 + down - (y - y0) / (y1 - y0) * down,
+        )
+    }
+
+    /// The ticks along the x axis
+    pub fn x_ticks(&self) -> Vec<(f64, String)> {
+        if self.log_x {
+            log_ticks(self.x0, self.x1)
+        } else {
+            ticks(self.x0, self.x1)
+        }
+    }
+
+    /// The ticks along the y axis
+    pub fn y_ticks(&self) -> Vec<(f64, String)> {
+        if self.log_y {
+            log_ticks(self.y0, self.y1)
+        } else {
+            ticks(self.y0, self.y1)
+        }
+    }
+
+    /// The data points as runs of pixels inside `area`: each run is a
+    /// polyline, broken wherever the data leaves the area or isn't a number
+    pub fn plot(&self, area: Region, points: &[(f64, f64)]) -> Vec<Vec<(usize, usize)>> {
+        let pixels: Vec<Option<(f64, f64)>> = points
+            .iter()
+            .map(|&(x, y)| {
+                let (px, py) = self.to_pixel(area, x, y);
+                (px.is_finite() && py.is_finite()).then_some((px, py))
+            })
+            .collect();
+        // A single point is plotted as a segment of no length
+        let segments: Vec<_> = if pixels.len() == 1 {
+            vec![(pixels[0], pixels[0])]
+        } else {
+            pixels.windows(2).map(|w| (w[0], w[1])).collect()
+        };
+        let mut runs: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut joined = false;
+        for segment in segments {
+            let clipped = match segment {
+                (Some(a), Some(b)) => clip_segment(area, a, b),
+                _ => None,
+            };
+            let Some((a, b)) = clipped else {
+                joined = false;
+                continue;
+            };
+            let round = |(x, y): (f64, f64)| (x.round() as usize, y.round() as usize);
+            match runs.last_mut() {
+                Some(run) if joined && run.last() == Some(&round(a)) => run.push(round(b)),
+                _ => runs.push(vec![round(a), round(b)]),
+            }
+            // The next segment continues this run only if this one wasn't cut short
+            joined = segment.1 == Some(b);
+        }
+        runs
+    }
+
+    /// Draw the axes along the left and bottom of `area` in `color`, with a
+    /// labelled tick at each round value in range
+    pub fn draw_axes(&self, buffer: &mut [u32], area: Region, color: u32) {
+        let (left, top) = (area.x - 1, area.y);
+        let (right, bottom) = (area.x + area.width - 1, area.y + area.height);
+        draw_line(buffer, left, top, left, bottom, color);
+        draw_line(buffer, left, bottom, right, bottom, color);
+        // Every tick is marked; labels that would crowd the previous one
+        // are skipped
+        let mut free_x = 0;
+        for (value, label) in self.x_ticks() {
+            let x = self.to_pixel(area, value, self.y0).0.round() as usize;
+            draw_line(buffer, x, bottom, x, bottom + TICK_LENGTH, color);
+            let label_x = x.saturating_sub(text_width(&label) / 2);
+            if label_x >= free_x {
+                draw_text(buffer, label_x, bottom + TICK_LENGTH + 2, &label, color);
+                free_x = label_x + text_width(&label) + GLYPH_ADVANCE;
+            }
+        }
+        let mut last_y: Option<usize> = None;
+        for (value, label) in self.y_ticks() {
+            let y = self.to_pixel(area, self.x0, value).1.round() as usize;
+            draw_line(buffer, left - TICK_LENGTH, y, left, y, color);
+            if last_y.is_some_and(|last| last.abs_diff(y) <= GLYPH_HEIGHT + 1) {
+                continue;
+            }
+            let label_x = left.saturating_sub(TICK_LENGTH + 2 + text_width(&label));
+            draw_text(
+                buffer,
+                label_x,
+                y.saturating_sub(GLYPH_HEIGHT / 2),
+                &label,
+                color,
+            );
+            last_y = Some(y);
+        }
+    }
+}
+
+/// A scrolling time series in a pane, fed a sample at a time by `tsplot`
+///
+/// The pane's x range is how many samples fit across its plot area. Each
+/// sample is drawn a step right of the last, joined to it by a line; once
+/// they reach the right edge, each new sample first moves the plot area's
+/// pixels a step left.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeSeries {
+    /// Samples appended so far
+    pub count: usize,
+    /// Pixel row of the last sample (None if it wasn't plottable, such as 0
+    /// on a log axis)
+    pub last: Option<f64>,
+}
+
+impl TimeSeries {
+    /// Append `value` to the series drawn with `scale` in `area`, scrolling
+    /// `buffer` if the series is full, and return the line joining it to the
+    /// previous sample (a single point for the first), clipped to the area
+    pub fn append(
+        &mut self,
+        buffer: &mut [u32],
+        scale: &PaneScale,
+        area: Region,
+        value: f64,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let step = sample_step(scale, area);
+        let last_slot = (area.width - 1) / step;
+        let slot = if self.count > last_slot {
+            scroll_left(buffer, area, step);
+            last_slot
+        } else {
+            self.count
+        };
+        let x = (area.x + slot * step) as f64;
+        let y = scale.to_pixel(area, scale.x0, value).1;
+        let previous = if self.count == 0 { None } else { self.last };
+        self.count += 1;
+        self.last = y.is_finite().then_some(y);
+        let y = self.last?;
+        let from = previous.map_or((x, y), |py| (x - step as f64, py));
+        let (a, b) = clip_segment(area, from, (x, y))?;
+        let round = |(x, y): (f64, f64)| (x.round() as usize, y.round() as usize);
+        Some((round(a), round(b)))
+    }
+}
+
+/// Pixels between consecutive samples of a time series in `area`
+fn sample_step(scale: &PaneScale, area: Region) -> usize {
+    let samples = (scale.x1 - scale.x0).abs();
+    let step = ((area.width - 1) as f64 / samples).round() as usize;
+    step.clamp(1, (area.width - 1).max(1))
+}
+
+/// Move the pixels of `area` left by `by`, whitening the columns this
+/// opens at its right edge
+pub fn scroll_left(buffer: &mut [u32], area: Region, by: usize) {
+    let width = window_width();
+    let by = by.min(area.width);
+    for y in area.y..area.y + area.height {
+        let row = &mut buffer[y * width + area.x..y * width + area.x + area.width];
+        row.copy_within(by.., 0);
+        row[area.width - by..].fill(WHITE);
+    }
+}
+
+/// The part of the segment from `a` to `b` inside `area` (Liang-Barsky), or
+/// None if it misses the area
+fn clip_segment(area: Region, a: (f64, f64), b: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+    let (x_min, y_min) = (area.x as f64, area.y as f64);
+    let (x_max, y_max) = (
+        x_min + (area.width - 1) as f64,
+        y_min + (area.height - 1) as f64,
+    );
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [
+        (-dx, a.0 - x_min),
+        (dx, x_max - a.0),
+        (-dy, a.1 - y_min),
+        (dy, y_max - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let at = |t: f64| {
+        if t == 0.0 {
+            a
+        } else if t == 1.0 {
+            b
+        } else {
+            (a.0 + t * dx, a.1 + t * dy)
+        }
+    };
+    Some((at(t0), at(t1)))
+}
+
+/// Round values between `from` and `to` (either order) one "nice" step
+/// apart (1, 2, or 5 times a power of ten), with their labels
+///
+/// Labels show as many decimals as the step needs. Axes reaching 10,000 or
+/// more are labelled with k/M/G/T suffixes (`1.2k`, `3.4M`), and very large
+/// or very small ones in scientific notation (`2e15`, `5e-5`).
+pub fn ticks(from: f64, to: f64) -> Vec<(f64, String)> {
+    let (low, high) = (from.min(to), from.max(to));
+    let raw = (high - low) / TARGET_TICKS;
+    if !(raw.is_finite() && raw > 0.0) {
+        return Vec::new();
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= raw)
+        .unwrap_or(10.0 * magnitude);
+    // Ranges near the limits of f64 can't be stepped through
+    if !(step > 0.0 && step.is_finite()) {
+        return Vec::new();
+    }
+    let extent = low.abs().max(high.abs());
+    let first = (low / step).ceil() as i64;
+    let last = (high / step + 1e-9).floor() as i64;
+    (first..=last)
+        .map(|i| {
+            // Keep "-0" off the axis
+            let value = if i == 0 { 0.0 } else { i as f64 * step };
+            (value, tick_label(value, step, extent))
+        })
+        .collect()
+}
+
+/// Powers of ten between `from` and `to` (either order, both above zero),
+/// with their labels
+///
+/// Ranges of two decades or less also get ticks at 2 and 5 times each power;
+/// wider ones skip powers to keep to about `TARGET_TICKS`.
+pub fn log_ticks(from: f64, to: f64) -> Vec<(f64, String)> {
+    let (low, high) = (from.min(to), from.max(to));
+    if !(low > 0.0 && high.is_finite()) {
+        return Vec::new();
+    }
+    let (first, last) = (low.log10().floor() as i32, high.log10().ceil() as i32);
+    let decades = last - first;
+    let multiples: &[f64] = if decades <= 2 {
+        &[1.0, 2.0, 5.0]
+    } else {
+        &[1.0]
+    };
+    let every = ((decades as f64 / TARGET_TICKS).ceil() as i32).max(1);
+    // Powers of ten aren't exact in binary; allow for rounding at the ends
+    let (low, high) = (low * (1.0 - 1e-9), high * (1.0 + 1e-9));
+    (first..=last)
+        .filter(|exponent| exponent.rem_euclid(every) == 0)
+        .flat_map(|exponent| multiples.iter().map(move |m| m * 10f64.powi(exponent)))
+        .filter(|value| value.is_finite() && (low..=high).contains(value))
+        .map(|value| (value, tick_label(value, value, value)))
+        .collect()
+}
+
+/// A value's position along an axis: itself, or its logarithm on a log axis
+/// (NaN for values of 0 or below)
+fn along(value: f64, log: bool) -> f64 {
+    match log {
+        false => value,
+        true if value > 0.0 => value.log10(),
+        true => f64::NAN,
+    }
+}
+
+/// The label of the tick at `value` on an axis stepping by `step`, whose
+/// largest absolute value is `extent` (see `ticks`)
+fn tick_label(value: f64, step: f64, extent: f64) -> String {
+    // Decimals that tell ticks `step` apart, for values in units of `unit`
+    let decimals = |unit: f64| -(step / unit).log10().floor() as i32;
+    if !(SCIENTIFIC_BELOW..SCIENTIFIC_FROM).contains(&extent) {
+        if value == 0.0 {
+            return "0".to_string();
+        }
+        let exponent = value.abs().log10().floor();
+        let digits = decimals(10f64.powf(exponent)).clamp(0, 6) as usize;
+        return format!("{:.*e}", digits, value);
+    }
+    if extent >= ABBREVIATE_FROM {
+        let (unit, suffix) = SUFFIXES
+            .into_iter()
+            .find(|&(unit, _)| extent >= unit)
+            .expect("extent is at least the smallest unit");
+        let digits = decimals(unit).max(0);
+        if value == 0.0 {
+            return "0".to_string();
+        }
+        if digits <= MAX_ABBREVIATED_DECIMALS {
+            return format!("{:.*}{}", digits as usize, value / unit, suffix);
+        }
+    }
+    format!("{:.*}", decimals(1.0).max(0) as usize, value)
+}
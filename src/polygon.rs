@@ -0,0 +1,85 @@
+//! Polygon tool input.
+//!
+//! This module handles:
+//! - Collecting polygon vertices from clicks on the canvas
+//! - Closing the polygon on a double-click
+//! - Previewing the edges placed so far, plus the edge to the cursor
+//!
+//! The preview is drawn onto the presented frame only; the polygon reaches the
+//! canvas (and history) once it is closed.
+
+use std::time::{Duration, Instant};
+
+use crate::drawing::draw_line;
+
+/// Longest gap between two clicks that still counts as a double-click
+pub const DOUBLE_CLICK_MS: u64 = 400;
+/// How far (on either axis) the second click of a double-click may stray
+pub const DOUBLE_CLICK_DISTANCE: usize = 4;
+
+/// Vertices of a polygon being placed with the polygon tool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolygonBuilder {
+    vertices: Vec<(usize, usize)>,
+    /// Time of the click that placed the last vertex
+    last_click: Option<Instant>,
+}
+
+impl PolygonBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a click at (x, y), returning the finished polygon's vertices
+    /// when the click closes it
+    ///
+    /// A click close to the last vertex, soon after placing it, is a
+    /// double-click and closes the polygon. With fewer than three vertices the
+    /// double-click is ignored and placing continues.
+    pub fn click(&mut self, x: usize, y: usize, now: Instant) -> Option<Vec<(usize, usize)>> {
+        let double = match (self.last_click, self.vertices.last()) {
+            (Some(at), Some(&(lx, ly))) => {
+                now.saturating_duration_since(at) <= Duration::from_millis(DOUBLE_CLICK_MS)
+                    && lx.abs_diff(x) <= DOUBLE_CLICK_DISTANCE
+                    && ly.abs_diff(y) <= DOUBLE_CLICK_DISTANCE
+            }
+            _ => false,
+        };
+        if double {
+            self.last_click = None;
+            if self.vertices.len() < 3 {
+                return None;
+            }
+            return Some(std::mem::take(&mut self.vertices));
+        }
+        self.vertices.push((x, y));
+        self.last_click = Some(now);
+        None
+    }
+
+    /// Vertices placed so far
+    pub fn vertices(&self) -> &[(usize, usize)] {
+        &self.vertices
+    }
+
+    /// Abandon the polygon in progress
+    pub fn cancel(&mut self) {
+        self.vertices.clear();
+        self.last_click = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    /// Draw the edges placed so far, and one from the last vertex to `cursor`,
+    /// as 1px lines
+    pub fn draw_preview(&self, frame: &mut [u32], cursor: Option<(usize, usize)>, color: u32) {
+        for pair in self.vertices.windows(2) {
+            draw_line(frame, pair[0].0, pair[0].1, pair[1].0, pair[1].1, color);
+        }
+        if let (Some(&(x, y)), Some((cx, cy))) = (self.vertices.last(), cursor) {
+            draw_line(frame, x, y, cx, cy, color);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Poster export: the canvas cut into overlapping tiles to print one a sheet.
+//!
+//! This module handles:
+//! - Laying out a `cols`x`rows` grid of equal tiles over the canvas, each
+//!   overlapping its neighbors by about `TILE_OVERLAP` pixels
+//! - Alignment marks where each seam runs, drawn on the tiles either side of it
+//! - Writing the tiles as PNGs named `tile-<row>-<col>.png` (`export tiles`)
+//!
+//! A seam is the middle of an overlap. Both tiles that share it get short
+//! ticks at its ends, at the same canvas position, so printed sheets line up
+//! by laying one tick over the other (or cutting one sheet along them). A
+//! tick is black, or white over dark pixels, so it shows on any drawing.
+
+use std::path::Path;
+
+use image::{ImageBuffer, Rgb, RgbImage};
+
+use crate::icc::save_profiled;
+use crate::{canvas_bottom, window_width, CANVAS_TOP};
+
+/// Most tiles along either side of the grid
+pub const MAX_TILE_GRID: usize = 16;
+/// Pixels each tile shares with the next, across and down
+pub const TILE_OVERLAP: usize = 24;
+/// Length of an alignment tick
+pub const MARK_LENGTH: usize = 12;
+
+/// Where the tiles of a grid sit along one side of the canvas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileSpan {
+    /// Pixels each tile covers
+    pub size: usize,
+    /// Where each tile starts, from the canvas edge
+    pub starts: Vec<usize>,
+}
+
+impl TileSpan {
+    /// Cut `length` pixels into `count` equal tiles overlapping by up to
+    /// `TILE_OVERLAP` (less when the tiles would be too small to share that
+    /// much), spread evenly from one edge to the other
+    pub fn new(length: usize, count: usize) -> TileSpan {
+        let overlap = TILE_OVERLAP.min(length / count / 2);
+        let size = (length + (count - 1) * overlap).div_ceil(count);
+        let starts = (0..count)
+            .map(|i| (i * (length - size)).checked_div(count - 1).unwrap_or(0))
+            .collect();
+        TileSpan { size, starts }
+    }
+
+    /// Where the seam between tile `i` and the next runs: the middle of the
+    /// pixels they share
+    pub fn seam(&self, i: usize) -> usize {
+        (self.starts[i + 1] + self.starts[i] + self.size) / 2
+    }
+}
+
+/// Check a grid is 1 to `MAX_TILE_GRID` tiles each way
+pub fn check_tile_grid(cols: usize, rows: usize) -> Result<(), String> {
+    if !(1..=MAX_TILE_GRID).contains(&cols) || !(1..=MAX_TILE_GRID).contains(&rows) {
+        return Err(format!(
+            "tile grid {}x{} is outside 1x1-{max}x{max}",
+            cols,
+            rows,
+            max = MAX_TILE_GRID
+        ));
+    }
+    Ok(())
+}
+
+/// File name of the tile in `row` and `col`, counting from 1
+pub fn tile_name(row: usize, col: usize) -> String {
+    format!("tile-{}-{}.png", row, col)
+}
+
+/// The tile at `row` and `col` (counting from 0) of the canvas portion of
+/// `buffer`, with its alignment marks
+pub fn tile_image(
+    buffer: &[u32],
+    across: &TileSpan,
+    down: &TileSpan,
+    row: usize,
+    col: usize,
+) -> RgbImage {
+    let (left, top) = (across.starts[col], CANVAS_TOP + down.starts[row]);
+    let mut img: RgbImage = ImageBuffer::from_fn(across.size as u32, down.size as u32, |x, y| {
+        let pixel = buffer[(top + y as usize) * window_width() + left + x as usize];
+        Rgb([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])
+    });
+
+    // Seams with the tiles either side, in this tile's own coordinates
+    let seams = |span: &TileSpan, i: usize, start: usize| {
+        let before = (i > 0).then(|| span.seam(i - 1) - start);
+        let after = (i + 1 < span.starts.len()).then(|| span.seam(i) - start);
+        before.into_iter().chain(after)
+    };
+    let (width, height) = (across.size, down.size);
+    let length = MARK_LENGTH.min(width / 2).min(height / 2);
+    for x in seams(across, col, left) {
+        for y in (0..length).chain(height - length..height) {
+            mark(&mut img, x, y);
+        }
+    }
+    for y in seams(down, row, down.starts[row]) {
+        for x in (0..length).chain(width - length..width) {
+            mark(&mut img, x, y);
+        }
+    }
+    img
+}
+
+/// Turn one pixel into part of a mark: black, or white over a dark pixel
+fn mark(img: &mut RgbImage, x: usize, y: usize) {
+    let pixel = img.get_pixel_mut(x as u32, y as u32);
+    let [r, g, b] = pixel.0.map(u32::from);
+    let luma = (299 * r + 587 * g + 114 * b) / 1000;
+    pixel.0 = if luma < 128 { [255; 3] } else { [0; 3] };
+}
+
+/// Write the canvas portion of `buffer` to `dir` as a `cols`x`rows` grid of
+/// tiles, returning how many were written
+///
+/// Fails without writing if the grid is out of range or `dir` doesn't exist.
+pub fn export_tiles(buffer: &[u32], dir: &str, cols: usize, rows: usize) -> Result<usize, String> {
+    check_tile_grid(cols, rows)?;
+    if !Path::new(dir).is_dir() {
+        return Err(format!("directory {} does not exist", dir));
+    }
+    let across = TileSpan::new(window_width(), cols);
+    let down = TileSpan::new(canvas_bottom() - CANVAS_TOP, rows);
+    for row in 0..rows {
+        for col in 0..cols {
+            let path = Path::new(dir).join(tile_name(row + 1, col + 1));
+            let tile = tile_image(buffer, &across, &down, row, col);
+            save_profiled(&tile, &path, image::ImageFormat::Png)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+    }
+    Ok(cols * rows)
+}
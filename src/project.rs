@@ -0,0 +1,218 @@
+//! Project files: a drawing saved whole, to be reopened later with undo intact.
+//!
+//! This module handles:
+//! - `save <path.displai>`: the canvas size and pixels, the palette and tool
+//!   settings, and the undo history, written to one file
+//! - `load <path.displai>`: reading such a file back and putting it in place
+//!   of the current drawing
+//!
+//! A project file is `DISPLAI` and a NUL, a little-endian u32 format version,
+//! then chunks: a 4-byte name, a u32 length, and that many bytes of data.
+//! `SIZE` holds the window width and height, `CNVS` the canvas rows (u32
+//! pixels, deflated), `TOOL` the settings as the lines autosave writes, and
+//! `HIST` the undo and redo entries (deflated). Readers skip chunks they
+//! don't know, so later versions can add some (layers, say) and still be read.
+//! Retained objects, variables, aliases, and panes aren't saved; loading
+//! leaves them as they are.
+
+use std::path::Path;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+use crate::autosave::{apply_session_settings, session_settings};
+use crate::canvas::{with_dimensions, Canvas, Dimensions};
+use crate::history::History;
+use crate::palette::Palette;
+use crate::panes::Panes;
+use crate::state::AppState;
+use crate::CANVAS_TOP;
+
+/// The extension project files are saved with
+pub const PROJECT_EXTENSION: &str = "displai";
+/// The first bytes of every project file
+const MAGIC: &[u8; 8] = b"DISPLAI\0";
+/// The format version written; files from later versions are refused
+const VERSION: u32 = 1;
+/// How hard pixels are squeezed (miniz levels run 0-10)
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// Refuse paths that don't end in `.displai` (case-insensitive), so a
+/// project can't be saved over an image by mistake
+pub fn check_project_path(path: &str) -> Result<(), String> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    if ext.eq_ignore_ascii_case(PROJECT_EXTENSION) {
+        Ok(())
+    } else {
+        Err(format!(
+            "project file '{}' must end in .{}",
+            path, PROJECT_EXTENSION
+        ))
+    }
+}
+
+/// A project read back from its file
+#[derive(Debug, Clone)]
+pub struct Project {
+    /// The canvas, at the size it was saved at (title bar and toolbar blank)
+    pub canvas: Canvas,
+    /// Lines for `apply_session_settings`
+    pub settings: Vec<String>,
+    /// Undo and redo, for `canvas`; lays out against the canvas's size
+    pub history: History,
+}
+
+/// Write the canvas and settings of `state` and `history` to `path`
+///
+/// The file is written beside itself and renamed into place, so a failed
+/// save leaves the last one whole.
+pub fn save_project(path: &str, state: &AppState, history: &History) -> Result<(), String> {
+    check_project_path(path)?;
+    let dims = state.canvas.dimensions();
+    let rows = &state.canvas[CANVAS_TOP * dims.width..dims.canvas_bottom() * dims.width];
+    let pixels: Vec<u8> = rows.iter().flat_map(|pixel| pixel.to_le_bytes()).collect();
+    let settings = session_settings(state).join("\n");
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    let mut size = (dims.width as u32).to_le_bytes().to_vec();
+    size.extend_from_slice(&(dims.height as u32).to_le_bytes());
+    for (name, data) in [
+        (b"SIZE", size),
+        (b"CNVS", compress_to_vec_zlib(&pixels, COMPRESSION_LEVEL)),
+        (b"TOOL", settings.into_bytes()),
+        (
+            b"HIST",
+            compress_to_vec_zlib(&history.to_bytes(), COMPRESSION_LEVEL),
+        ),
+    ] {
+        out.extend_from_slice(name);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&data);
+    }
+
+    let partial = format!("{}.new", path);
+    std::fs::write(&partial, out).map_err(|e| format!("cannot write {}: {}", path, e))?;
+    std::fs::rename(&partial, path).map_err(|e| format!("cannot write {}: {}", path, e))
+}
+
+/// Read the project saved at `path`
+pub fn read_project(path: &str) -> Result<Project, String> {
+    check_project_path(path)?;
+    let bytes = std::fs::read(path).map_err(|e| format!("cannot load {}: {}", path, e))?;
+    parse_project(&bytes).map_err(|e| format!("cannot load {}: {}", path, e))
+}
+
+/// A chunk's name and data
+type Chunk<'a> = (&'a [u8], &'a [u8]);
+
+/// The chunks of a project file, in order, after checking its header
+fn chunks(bytes: &[u8]) -> Result<Vec<Chunk<'_>>, String> {
+    let header = MAGIC.len() + 4;
+    if bytes.len() < header || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a displai project".to_string());
+    }
+    let version = u32::from_le_bytes(bytes[MAGIC.len()..header].try_into().expect("4 bytes"));
+    if version > VERSION {
+        return Err(format!(
+            "project format {} is newer than this displai reads ({})",
+            version, VERSION
+        ));
+    }
+    let mut rest = &bytes[header..];
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        if rest.len() < 8 {
+            return Err("project is cut short".to_string());
+        }
+        let len = u32::from_le_bytes(rest[4..8].try_into().expect("4 bytes")) as usize;
+        let data = rest.get(8..8 + len).ok_or("project is cut short")?;
+        chunks.push((&rest[..4], data));
+        rest = &rest[8 + len..];
+    }
+    Ok(chunks)
+}
+
+fn parse_project(bytes: &[u8]) -> Result<Project, String> {
+    let chunks = chunks(bytes)?;
+    let chunk = |name: &[u8; 4]| {
+        chunks
+            .iter()
+            .find(|(found, _)| found == name)
+            .map(|(_, data)| *data)
+    };
+    let inflate = |data: &[u8]| {
+        decompress_to_vec_zlib(data).map_err(|_| "project data is damaged".to_string())
+    };
+
+    let size = chunk(b"SIZE").ok_or("project has no canvas size")?;
+    let [width, height] = [0, 4].map(|at| {
+        size.get(at..at + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")) as usize)
+    });
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err("project canvas size is damaged".to_string());
+    };
+    let dims = Dimensions::new(width, height)?;
+
+    let pixels = inflate(chunk(b"CNVS").ok_or("project has no canvas")?)?;
+    let mut canvas = Canvas::with_size(dims);
+    let rows = &mut canvas[CANVAS_TOP * width..dims.canvas_bottom() * width];
+    if pixels.len() != rows.len() * 4 {
+        return Err("project canvas doesn't match its size".to_string());
+    }
+    for (pixel, bytes) in rows.iter_mut().zip(pixels.chunks_exact(4)) {
+        *pixel = u32::from_le_bytes(bytes.try_into().expect("4 bytes"));
+    }
+
+    let settings = match chunk(b"TOOL") {
+        Some(text) => std::str::from_utf8(text)
+            .map_err(|_| "project settings are not UTF-8".to_string())?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let history = match chunk(b"HIST") {
+        Some(data) => {
+            let data = inflate(data)?;
+            with_dimensions(dims, || History::from_bytes(&data, &canvas))?
+        }
+        None => with_dimensions(dims, || History::with_baseline(&canvas)),
+    };
+    Ok(Project {
+        canvas,
+        settings,
+        history,
+    })
+}
+
+/// Put the canvas and settings of `project` in place of those of `state`
+///
+/// The palette and tools are set from the project alone, and the display
+/// list, stroke log, and pane data start over; a pane layout that doesn't
+/// fit the project's canvas goes back to one pane. Fails, leaving `state`
+/// as it was, if a setting is refused.
+pub fn apply_project(state: &mut AppState, project: &Project) -> Result<(), String> {
+    let mut opened = state.clone();
+    opened.canvas = project.canvas.clone();
+    opened.palette = Palette::new();
+    opened.display_list.clear();
+    opened.strokes.clear();
+    opened.scales.clear();
+    opened.series.clear();
+    opened.held.clear();
+    if with_dimensions(opened.canvas.dimensions(), || opened.panes.check()).is_err() {
+        opened.panes = Panes::default();
+    }
+    apply_session_settings(&mut opened, &project.settings)?;
+    *state = opened;
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! Request framing for the line protocol.
+//!
+//! This module handles:
+//! - Splitting an optional request ID (`#42 line 0,0 10,10`) off a command line
+//! - Tagging responses with the same ID (`#42 ok`)
+//! - Idempotency keys (`!key rect 0,0 9,9`) that make retried drawing commands run at most once
+//! - Appending execution time to responses while `timing on` is set (`ok 1250us`)
+//!
+//! Tagged lines always get a response, even on a multi-line socket connection
+//! or stdin, so pipelined clients can match replies to requests and notice
+//! when one is missing. Untagged lines behave exactly as before.
+//!
+//! Both prefixes are optional and may be combined, ID first: `#42 !k7 dot 5,5`.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use crate::command::{failed, reply, CommandResult};
+
+/// Longest request ID or idempotency key accepted (longer tags are treated as part of the command)
+pub const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// How many recent idempotency keys are remembered
+pub const IDEMPOTENCY_CAPACITY: usize = 4096;
+
+/// Response sent instead of re-running a command whose key was already seen
+pub const DUPLICATE_RESPONSE: &str = "ok duplicate";
+
+fn is_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Split a line into its request ID (without the `#`) and the command text
+///
+/// Lines without a well-formed `#id` prefix are returned unchanged with no ID.
+pub fn split_request_id(line: &str) -> (Option<&str>, &str) {
+    split_tag(line, '#')
+}
+
+/// Split a line into its idempotency key (without the `!`) and the command text
+///
+/// Call after `split_request_id`. Lines without a well-formed `!key` prefix are
+/// returned unchanged with no key.
+pub fn split_idempotency_key(line: &str) -> (Option<&str>, &str) {
+    split_tag(line, '!')
+}
+
+fn split_tag(line: &str, marker: char) -> (Option<&str>, &str) {
+    let trimmed = line.trim_start();
+    if let Some(rest) = trimmed.strip_prefix(marker) {
+        let (id, command) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if is_request_id(id) {
+            return (Some(id), command.trim_start());
+        }
+    }
+    (None, line)
+}
+
+/// Format a response line, tagged with the request ID if there is one
+pub fn frame_response(id: Option<&str>, response: &str) -> String {
+    match id {
+        Some(id) => format!("#{} {}", id, response),
+        None => response.to_string(),
+    }
+}
+
+/// A result with the time its command took appended in microseconds
+///
+/// Commands without output are timed as `ok`, so every command gets a reply;
+/// errors stay errors.
+pub fn timed_response(result: CommandResult, elapsed: Duration) -> CommandResult {
+    let us = elapsed.as_micros();
+    match result {
+        Ok(output) => reply(format!("{} {}us", output, us)),
+        Err(e) => failed(format!("{} {}us", e, us)),
+    }
+}
+
+/// Remembers recently used idempotency keys, forgetting the oldest beyond a capacity
+#[derive(Debug, Clone)]
+pub struct IdempotencyCache {
+    capacity: usize,
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+}
+
+impl IdempotencyCache {
+    /// Create a cache remembering up to `capacity` keys (at least one)
+    pub fn new(capacity: usize) -> Self {
+        IdempotencyCache {
+            capacity: capacity.max(1),
+            order: VecDeque::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Record a key, returning true if it is new (the command should run)
+    /// and false if it was seen recently (the command is a duplicate)
+    pub fn insert(&mut self, key: &str) -> bool {
+        if self.seen.contains(key) {
+            return false;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.to_string());
+        self.seen.insert(key.to_string());
+        true
+    }
+
+    /// Whether a key was seen recently
+    pub fn contains(&self, key: &str) -> bool {
+        self.seen.contains(key)
+    }
+
+    /// Number of keys currently remembered
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::new(IDEMPOTENCY_CAPACITY)
+    }
+}
@@ -0,0 +1,93 @@
+//! Reading canvas pixels back over the protocol.
+//!
+//! This module handles:
+//! - `getpixel x,y`, which replies with one pixel's color as `#RRGGBB`
+//! - `pick x,y`, which replies with one pixel's palette index, or its
+//!   `#RRGGBB` and the closest palette index (the eyedropper's lookup)
+//! - `getregion x1,y1 x2,y2`, which replies with a rectangle of pixels as
+//!   `<w>x<h> <base64>`: the rows, top to bottom, run-length encoded
+//! - The run-length encoding: 4-byte runs of (count 1-255, red, green, blue),
+//!   which may continue from one row into the next
+//!
+//! Both read the canvas itself, so retained objects and dialogs (which are
+//! only drawn over it) are not included.
+
+use crate::colors::{nearest_palette_color, palette_index};
+use crate::command::{check_point, clip_region, CommandOutput, CommandResult};
+use crate::snapshot::base64_encode;
+use crate::window_width;
+
+/// Longest run in one run-length record
+pub const MAX_RUN: usize = 255;
+
+/// Run-length encode pixels as (count, red, green, blue) records
+pub fn rle_encode(pixels: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut rest = pixels;
+    while let Some(&color) = rest.first() {
+        let run = rest
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|&&p| p == color)
+            .count();
+        let [_, r, g, b] = color.to_be_bytes();
+        out.extend([run as u8, r, g, b]);
+        rest = &rest[run..];
+    }
+    out
+}
+
+/// Decode `rle_encode` output back into pixels (None if it is malformed)
+pub fn rle_decode(bytes: &[u8]) -> Option<Vec<u32>> {
+    if !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut pixels = Vec::new();
+    for record in bytes.chunks(4) {
+        let [run, r, g, b] = record else {
+            return None;
+        };
+        if *run == 0 {
+            return None;
+        }
+        let color = u32::from_be_bytes([0, *r, *g, *b]);
+        pixels.extend(std::iter::repeat_n(color, usize::from(*run)));
+    }
+    Some(pixels)
+}
+
+/// The reply to `getpixel`: the color at (x, y) as `#RRGGBB`
+pub fn pixel_response(buffer: &[u32], x: usize, y: usize) -> CommandResult {
+    check_point(x, y)?;
+    let color = buffer[y * window_width() + x] & 0xFFFFFF;
+    Ok(CommandOutput::Text(format!("#{:06X}", color)))
+}
+
+/// The reply to `pick`: the palette index of the color at (x, y), or if it
+/// isn't a palette color, `#RRGGBB closest:<index>`
+///
+/// The first word is always a valid `edge`/`fill` argument for the color.
+pub fn pick_response(buffer: &[u32], x: usize, y: usize) -> CommandResult {
+    check_point(x, y)?;
+    let color = buffer[y * window_width() + x] & 0xFFFFFF;
+    Ok(CommandOutput::Text(match palette_index(color) {
+        Some(i) => i.to_string(),
+        None => format!("#{:06X} closest:{}", color, nearest_palette_color(color)),
+    }))
+}
+
+/// The reply to `getregion`: `<w>x<h> <base64 RLE>` for the rectangle between
+/// two corners (inclusive, either order), clipped to the canvas
+pub fn region_response(buffer: &[u32], p1: (usize, usize), p2: (usize, usize)) -> CommandResult {
+    let (left, top, right, bottom) = clip_region(p1, p2)?;
+    let width = window_width();
+    let pixels: Vec<u32> = (top..=bottom)
+        .flat_map(|y| buffer[y * width + left..=y * width + right].iter().copied())
+        .collect();
+    Ok(CommandOutput::Text(format!(
+        "{}x{} {}",
+        right - left + 1,
+        bottom - top + 1,
+        base64_encode(&rle_encode(&pixels))
+    )))
+}
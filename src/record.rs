@@ -0,0 +1,227 @@
+//! Command recording and replay.
+//!
+//! This module handles:
+//! - `Recorder`, appending each executed command with its time offset to a file
+//! - `read_recording`, decoding a recording back into timed command lines
+//! - `Replay`, handing a recording's lines back out as their (scaled) times come
+//!   due, and pausing for watches
+//!
+//! File format: the header `# displai recording`, then one command per line as
+//! `<ms> <command>`, where `ms` is milliseconds since recording started and
+//! `command` is the command in protocol syntax. Recordings are plain text, so
+//! they can be trimmed or edited by hand before replaying.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+use crate::command::{failed, reply, Command, CommandResult};
+
+/// First line of every recording
+pub const RECORDING_HEADER: &str = "# displai recording";
+/// Slowest and fastest replay speeds (1 = as recorded)
+pub const MIN_REPLAY_SPEED: f64 = 0.1;
+pub const MAX_REPLAY_SPEED: f64 = 100.0;
+
+/// Whether `speed` is a usable replay speed
+pub fn is_valid_replay_speed(speed: f64) -> bool {
+    (MIN_REPLAY_SPEED..=MAX_REPLAY_SPEED).contains(&speed)
+}
+
+/// Check a speed for `verb` (`replay` or `playstrokes`)
+pub fn check_replay_speed(verb: &str, speed: f64) -> Result<(), String> {
+    if is_valid_replay_speed(speed) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} speed {} is outside {}-{}",
+            verb, speed, MIN_REPLAY_SPEED, MAX_REPLAY_SPEED
+        ))
+    }
+}
+
+/// An open recording receiving executed commands
+pub struct Recorder {
+    path: String,
+    writer: BufWriter<File>,
+    started: Instant,
+    commands: u64,
+}
+
+impl Recorder {
+    /// Create (or truncate) a recording at `path`, timed from `now`
+    pub fn start(path: &str, now: Instant) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", RECORDING_HEADER).map_err(|e| e.to_string())?;
+        Ok(Recorder {
+            path: path.to_string(),
+            writer,
+            started: now,
+            commands: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of commands written so far
+    pub fn commands(&self) -> u64 {
+        self.commands
+    }
+
+    /// Append a command executed at `now`
+    pub fn record(&mut self, cmd: &Command, now: Instant) -> Result<(), String> {
+        let ms = now.saturating_duration_since(self.started).as_millis();
+        writeln!(self.writer, "{} {}", ms, cmd).map_err(|e| e.to_string())?;
+        self.commands += 1;
+        Ok(())
+    }
+
+    /// Flush and close the recording, returning how many commands it holds
+    pub fn finish(mut self) -> Result<u64, String> {
+        self.writer.flush().map_err(|e| e.to_string())?;
+        Ok(self.commands)
+    }
+}
+
+/// Apply a `record` command: start (restarting any active recording) or stop,
+/// returning the protocol response
+pub fn apply_record_command(
+    recorder: &mut Option<Recorder>,
+    target: Option<&str>,
+    now: Instant,
+) -> CommandResult {
+    let finished = recorder.take().map(|active| {
+        let path = active.path().to_string();
+        (path, active.finish())
+    });
+    match target {
+        Some(path) => match Recorder::start(path, now) {
+            Ok(started) => {
+                *recorder = Some(started);
+                reply(format!("recording {}", path))
+            }
+            Err(e) => failed(e),
+        },
+        None => match finished {
+            Some((path, Ok(commands))) => {
+                reply(format!("recorded {} commands to {}", commands, path))
+            }
+            Some((_, Err(e))) => failed(e),
+            None => failed("not recording"),
+        },
+    }
+}
+
+/// Read a recording as (milliseconds, command line) pairs, in file order
+///
+/// Blank lines and `#` comments are skipped; command lines are not parsed here,
+/// so a line this build doesn't understand fails when it is replayed, not read.
+pub fn read_recording(path: &str) -> Result<Vec<(u64, String)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let mut lines = text.lines();
+    if lines.next() != Some(RECORDING_HEADER) {
+        return Err(format!("{} is not a displai recording", path));
+    }
+    let mut entries = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = line
+            .split_once(' ')
+            .and_then(|(ms, cmd)| Some((ms.parse().ok()?, cmd.trim().to_string())))
+            .filter(|(_, cmd)| !cmd.is_empty());
+        // The header is line 1
+        entries.push(
+            entry.ok_or_else(|| format!("{} line {}: expected <ms> <command>", path, i + 2))?,
+        );
+    }
+    Ok(entries)
+}
+
+/// A recording being played back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    path: String,
+    entries: Vec<(u64, String)>,
+    next: usize,
+    started: Instant,
+    speed: f64,
+    paused_at: Option<Instant>,
+}
+
+impl Replay {
+    /// Load the recording at `path` to play from `now` at `speed` times its
+    /// recorded pace (which must be in range)
+    pub fn start(path: &str, speed: f64, now: Instant) -> Result<Self, String> {
+        check_replay_speed("replay", speed)?;
+        Ok(Replay {
+            path: path.to_string(),
+            entries: read_recording(path)?,
+            next: 0,
+            started: now,
+            speed,
+            paused_at: None,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of commands in the recording
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether every command has been handed out
+    pub fn is_finished(&self) -> bool {
+        self.next == self.entries.len()
+    }
+
+    /// Whether playback is paused (by a watch)
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Stop handing out lines from `now` until `resume`
+    pub fn pause(&mut self, now: Instant) {
+        self.paused_at.get_or_insert(now);
+    }
+
+    /// Continue from where `pause` stopped, keeping the recorded gaps between
+    /// the remaining lines
+    pub fn resume(&mut self, now: Instant) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.started += now.saturating_duration_since(paused_at);
+        }
+    }
+
+    /// The next command line, if its time has come by `now` and playback
+    /// isn't paused
+    pub fn next_due(&mut self, now: Instant) -> Option<String> {
+        if self.is_paused() {
+            return None;
+        }
+        let elapsed = now.saturating_duration_since(self.started).as_secs_f64() * 1000.0;
+        let (ms, line) = self.entries.get(self.next)?;
+        if *ms as f64 / self.speed > elapsed {
+            return None;
+        }
+        self.next += 1;
+        Some(line.clone())
+    }
+
+    /// The command lines whose time has come by `now`, in order
+    pub fn due(&mut self, now: Instant) -> Vec<String> {
+        std::iter::from_fn(|| self.next_due(now)).collect()
+    }
+}
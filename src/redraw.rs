@@ -0,0 +1,78 @@
+//! Change-driven presentation for the displai application.
+//!
+//! This module handles:
+//! - Tracking whether the frame buffer or input state changed since the last present
+//! - A low-rate heartbeat so the window is still refreshed while idle
+//!
+//! Idle frames only pump window events (`Window::update`) instead of pushing
+//! the full buffer, which matters when many instances share one host.
+
+use std::time::{Duration, Instant};
+
+/// Longest time between presents while nothing changes
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Mouse state sampled once per frame, used to detect input changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputSnapshot {
+    pub mouse_pos: Option<(f32, f32)>,
+    pub left_down: bool,
+    pub right_down: bool,
+}
+
+/// Decides whether a frame needs to be pushed to the window
+#[derive(Debug, Clone)]
+pub struct RedrawTracker {
+    dirty: bool,
+    last_present: Option<Instant>,
+    last_input: Option<InputSnapshot>,
+}
+
+impl RedrawTracker {
+    /// Create a tracker whose first frame is always presented
+    pub fn new() -> Self {
+        RedrawTracker {
+            dirty: true,
+            last_present: None,
+            last_input: None,
+        }
+    }
+
+    /// Record that the buffer changed and must be presented
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Whether a change is waiting to be presented
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Record this frame's input, marking the tracker dirty if it differs from the last frame
+    pub fn observe_input(&mut self, input: InputSnapshot) {
+        if self.last_input != Some(input) {
+            self.dirty = true;
+        }
+        self.last_input = Some(input);
+    }
+
+    /// Whether the frame should be presented at `now` (dirty or heartbeat due)
+    pub fn should_present(&self, now: Instant) -> bool {
+        match self.last_present {
+            None => true,
+            Some(last) => self.dirty || now.duration_since(last) >= HEARTBEAT_INTERVAL,
+        }
+    }
+
+    /// Record that the frame was presented at `now`
+    pub fn presented(&mut self, now: Instant) {
+        self.dirty = false;
+        self.last_present = Some(now);
+    }
+}
+
+impl Default for RedrawTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,210 @@
+//! Changing the canvas size: cropping to a rectangle or resizing around an
+//! anchor.
+//!
+//! This module handles:
+//! - `CanvasSize`, set out by `canvas crop x1,y1 x2,y2` and `canvas resize
+//!   WxH [anchor]`
+//! - Working out the new window size and where the picture lands in it
+//! - Copying the picture into a canvas of the new size
+//!
+//! A resize's `WxH` is the window size, as with `--size`; the canvas is that
+//! less the title bar and toolbar. A crop makes the canvas exactly the
+//! rectangle, so the window grows by the bars around it. Either way the
+//! window must stay within the sizes `--size` accepts, so a crop narrower
+//! than `MIN_WIDTH` is refused. New area is white; the picture is not scaled
+//! (that is `canvas scale`).
+
+use std::fmt;
+
+use crate::canvas::{Canvas, Dimensions};
+use crate::{BOTTOM_TOOLBAR_HEIGHT, CANVAS_TOP, TITLE_BAR_HEIGHT};
+
+/// Which point of the picture stays put when the canvas is resized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Anchor {
+    /// Top-left corner (the default)
+    #[default]
+    NorthWest,
+    North,
+    NorthEast,
+    West,
+    Center,
+    East,
+    SouthWest,
+    South,
+    SouthEast,
+}
+
+impl Anchor {
+    /// Every anchor, by its compass name
+    pub const ALL: [(&'static str, Anchor); 9] = [
+        ("nw", Anchor::NorthWest),
+        ("n", Anchor::North),
+        ("ne", Anchor::NorthEast),
+        ("w", Anchor::West),
+        ("center", Anchor::Center),
+        ("e", Anchor::East),
+        ("sw", Anchor::SouthWest),
+        ("s", Anchor::South),
+        ("se", Anchor::SouthEast),
+    ];
+
+    /// Parse a compass name: `nw`, `n`, `ne`, `w`, `center`, `e`, `sw`, `s`, or `se`
+    pub fn parse(s: &str) -> Option<Anchor> {
+        Anchor::ALL
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, anchor)| *anchor)
+    }
+
+    /// The compass name `parse` reads
+    pub fn name(self) -> &'static str {
+        Anchor::ALL
+            .iter()
+            .find(|(_, anchor)| *anchor == self)
+            .map(|(name, _)| *name)
+            .expect("every anchor has a name")
+    }
+
+    /// How far across and down the picture moves when the canvas grows by
+    /// `dx` and `dy` pixels (negative when it shrinks)
+    fn offset(self, dx: isize, dy: isize) -> (isize, isize) {
+        // Halves of the change each way: none, half, or all of it
+        let (across, down) = match self {
+            Anchor::NorthWest => (0, 0),
+            Anchor::North => (1, 0),
+            Anchor::NorthEast => (2, 0),
+            Anchor::West => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::East => (2, 1),
+            Anchor::SouthWest => (0, 2),
+            Anchor::South => (1, 2),
+            Anchor::SouthEast => (2, 2),
+        };
+        (dx * across / 2, dy * down / 2)
+    }
+}
+
+/// A change to the size of the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanvasSize {
+    /// Keep only the rectangle between two corners (inclusive, either order)
+    Crop {
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+    },
+    /// Make the window `width` x `height`, keeping the picture at `anchor`
+    Resize {
+        width: usize,
+        height: usize,
+        anchor: Anchor,
+    },
+}
+
+impl CanvasSize {
+    /// Parse the arguments of `canvas crop ...` or `canvas resize ...`
+    pub fn parse(args: &[&str]) -> Option<CanvasSize> {
+        let point = |s: &str| -> Option<(usize, usize)> {
+            let (x, y) = s.split_once(',')?;
+            Some((x.parse().ok()?, y.parse().ok()?))
+        };
+        match *args {
+            ["crop", from, to] => {
+                let ((x1, y1), (x2, y2)) = (point(from)?, point(to)?);
+                Some(CanvasSize::Crop { x1, y1, x2, y2 })
+            }
+            ["resize", size] | ["resize", size, _] => {
+                let (width, height) = size.split_once('x')?;
+                let anchor = match args.get(2) {
+                    None => Anchor::default(),
+                    Some(name) => Anchor::parse(name)?,
+                };
+                Some(CanvasSize::Resize {
+                    width: width.parse().ok()?,
+                    height: height.parse().ok()?,
+                    anchor,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The window size this makes from one of size `current`, and how far
+    /// across and down the picture moves in it
+    ///
+    /// Fails if a crop misses the canvas or the new window is outside the
+    /// sizes `Dimensions::new` accepts.
+    pub fn layout(&self, current: Dimensions) -> Result<(Dimensions, (isize, isize)), String> {
+        match *self {
+            CanvasSize::Crop { x1, y1, x2, y2 } => {
+                let left = x1.min(x2);
+                let top = y1.min(y2).max(CANVAS_TOP);
+                let right = x1.max(x2).min(current.width - 1);
+                let bottom = y1.max(y2).min(current.canvas_bottom() - 1);
+                if left > right || top > bottom {
+                    return Err(format!(
+                        "region {},{} {},{} is outside the canvas",
+                        x1, y1, x2, y2
+                    ));
+                }
+                let height = bottom - top + 1 + TITLE_BAR_HEIGHT + BOTTOM_TOOLBAR_HEIGHT;
+                let dims = Dimensions::new(right - left + 1, height)
+                    .map_err(|e| format!("cropped window {}", e))?;
+                Ok((dims, (-(left as isize), -((top - CANVAS_TOP) as isize))))
+            }
+            CanvasSize::Resize {
+                width,
+                height,
+                anchor,
+            } => {
+                let dims = Dimensions::new(width, height)?;
+                let dx = dims.width as isize - current.width as isize;
+                let dy = dims.canvas_height() as isize - current.canvas_height() as isize;
+                Ok((dims, anchor.offset(dx, dy)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for CanvasSize {
+    /// Format as `canvas`'s arguments, e.g. `resize 1024x768 center`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CanvasSize::Crop { x1, y1, x2, y2 } => write!(f, "crop {},{} {},{}", x1, y1, x2, y2),
+            CanvasSize::Resize {
+                width,
+                height,
+                anchor: Anchor::NorthWest,
+            } => write!(f, "resize {}x{}", width, height),
+            CanvasSize::Resize {
+                width,
+                height,
+                anchor,
+            } => write!(f, "resize {}x{} {}", width, height, anchor.name()),
+        }
+    }
+}
+
+/// A white canvas of size `dims` with the picture on `canvas` moved `dx`
+/// across and `dy` down in it, cut off where it doesn't fit
+pub fn resized_canvas(canvas: &Canvas, dims: Dimensions, (dx, dy): (isize, isize)) -> Canvas {
+    let old = canvas.dimensions();
+    let mut resized = Canvas::with_size(dims);
+    // Source columns and rows (canvas-relative) that land inside the new canvas
+    let span = |shift: isize, from: usize, to: usize| {
+        let start = (-shift).max(0) as usize;
+        let end = (to as isize - shift).clamp(0, from as isize) as usize;
+        start..end.max(start)
+    };
+    let columns = span(dx, old.width, dims.width);
+    for y in span(dy, old.canvas_height(), dims.canvas_height()) {
+        let from = (CANVAS_TOP + y) * old.width;
+        let to = (CANVAS_TOP + (y as isize + dy) as usize) * dims.width;
+        let x = (columns.start as isize + dx) as usize;
+        resized[to + x..to + x + columns.len()]
+            .copy_from_slice(&canvas[from + columns.start..from + columns.end]);
+    }
+    resized
+}
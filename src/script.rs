@@ -0,0 +1,83 @@
+//! Template scripts run at startup.
+//!
+//! This module handles:
+//! - `--define KEY=VALUE` definitions
+//! - Putting values in for `${NAME}` in a script's lines: the `--define`d
+//!   value, else the environment variable
+//! - Reading the `--script` file (and the scripts it includes) with its
+//!   variables put in
+//!
+//! One script can then draw different data or titles each run
+//! (`displai --script chart.txt --define title=Sales`). A `${NAME}` that is
+//! neither defined nor set in the environment stops displai starting, so a
+//! misspelt name is caught rather than drawn. Any other `$` (an alias's
+//! `$1`, a `${` not followed by a name and `}`) is left as it is.
+
+use crate::include::read_included;
+
+/// Whether `name` can be `--define`d and used as `${name}`: a letter or `_`,
+/// then letters, digits, or `_`, as environment variables are named
+pub fn is_valid_define_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse a `--define` value, `KEY=VALUE` (the value may be empty or hold
+/// `=`s)
+pub fn parse_define(define: &str) -> Option<(String, String)> {
+    let (name, value) = define.split_once('=')?;
+    is_valid_define_name(name).then(|| (name.to_string(), value.to_string()))
+}
+
+/// The value of variable `name`: its last `--define`, else the environment
+/// variable
+pub fn script_variable(defines: &[(String, String)], name: &str) -> Option<String> {
+    defines
+        .iter()
+        .rev()
+        .find(|(defined, _)| defined == name)
+        .map(|(_, value)| value.clone())
+        .or_else(|| std::env::var(name).ok())
+}
+
+/// `line` with each `${NAME}` replaced by `lookup(NAME)`, or the first name
+/// `lookup` has no value for
+///
+/// Values are put in as they are: a `${` in one is not looked up again.
+pub fn interpolate(line: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let name = after.find('}').map(|end| &after[..end]);
+        out.push_str(&rest[..start]);
+        match name.filter(|name| is_valid_define_name(name)) {
+            Some(name) => {
+                out.push_str(&lookup(name).ok_or_else(|| name.to_string())?);
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                out.push_str("${");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Read the script at `path` (see `read_included`), putting in its
+/// variables from `defines` and the environment
+pub fn read_template_script(
+    path: &str,
+    defines: &[(String, String)],
+) -> Result<Vec<String>, String> {
+    read_included(path, &|line| {
+        interpolate(line, |name| script_variable(defines, name)).map_err(|name| {
+            format!(
+                "${{{}}} is not set (pass --define {}=VALUE or set it in the environment)",
+                name, name
+            )
+        })
+    })
+}
@@ -0,0 +1,406 @@
+//! Selection tool input.
+//!
+//! This module handles:
+//! - Dragging out a rectangular selection on the canvas
+//! - Lifting the selected pixels into a floating selection that follows the
+//!   pointer, and stamping it back down
+//! - Copy, cut, paste, and delete through a clipboard of pixels
+//! - Previewing the selection with a marching-ants border
+//!
+//! A floating selection is drawn onto the presented frame only: lifting it
+//! leaves white behind on the canvas, and its pixels reach the canvas (and
+//! history, as one entry for the whole move or paste) when it is committed.
+//! Until then protocol commands and snapshots see the canvas beneath it.
+
+use std::time::Duration;
+
+use crate::locks::Region;
+use crate::{canvas_bottom, window_width, BLACK, CANVAS_TOP, WHITE};
+
+/// Length of each dash of the marching-ants border, in pixels
+pub const ANTS_DASH: usize = 4;
+/// Time for the ants to march one pixel
+pub const ANTS_STEP_MS: u128 = 80;
+
+/// Offset of the marching-ants pattern after `elapsed` time
+pub fn ants_phase(elapsed: Duration) -> usize {
+    (elapsed.as_millis() / ANTS_STEP_MS) as usize % (2 * ANTS_DASH)
+}
+
+/// Clamp a point to the canvas area
+fn clamp_to_canvas(x: usize, y: usize) -> (usize, usize) {
+    (
+        x.min(window_width() - 1),
+        y.clamp(CANVAS_TOP, canvas_bottom() - 1),
+    )
+}
+
+/// A rectangle of pixels taken from the canvas
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub width: usize,
+    pub height: usize,
+    /// Rows top to bottom, as `0xRRGGBB`
+    pub pixels: Vec<u32>,
+}
+
+impl Clip {
+    /// Copy the pixels of `region` (which must lie on the canvas) from a
+    /// window-sized buffer
+    pub fn copy(buffer: &[u32], region: Region) -> Self {
+        let width = window_width();
+        let mut pixels = Vec::with_capacity(region.width * region.height);
+        for y in region.y..region.y + region.height {
+            let row = y * width + region.x;
+            pixels.extend_from_slice(&buffer[row..row + region.width]);
+        }
+        Clip {
+            width: region.width,
+            height: region.height,
+            pixels,
+        }
+    }
+
+    /// Draw the clip with its top-left corner at (x, y), clipped to the canvas
+    pub fn stamp(&self, buffer: &mut [u32], x: isize, y: isize) {
+        let width = window_width();
+        for row in 0..self.height {
+            let by = y + row as isize;
+            if by < CANVAS_TOP as isize || by >= canvas_bottom() as isize {
+                continue;
+            }
+            for col in 0..self.width {
+                let bx = x + col as isize;
+                if bx < 0 || bx >= width as isize {
+                    continue;
+                }
+                buffer[by as usize * width + bx as usize] = self.pixels[row * self.width + col];
+            }
+        }
+    }
+}
+
+/// Fill `region` (on the canvas) with white
+fn erase(buffer: &mut [u32], region: Region) {
+    let width = window_width();
+    for y in region.y..region.y + region.height {
+        let row = y * width + region.x;
+        buffer[row..row + region.width].fill(WHITE);
+    }
+}
+
+/// Draw a marching-ants border just inside the rectangle from (left, top) to
+/// (right, bottom), inclusive, clipped to the canvas
+fn draw_ants(
+    frame: &mut [u32],
+    left: isize,
+    top: isize,
+    right: isize,
+    bottom: isize,
+    phase: usize,
+) {
+    let width = window_width() as isize;
+    let mut plot = |x: isize, y: isize, i: isize| {
+        if x < 0 || x >= width || y < CANVAS_TOP as isize || y >= canvas_bottom() as isize {
+            return;
+        }
+        let step = (i.unsigned_abs() + phase) / ANTS_DASH;
+        frame[(y * width + x) as usize] = if step.is_multiple_of(2) { BLACK } else { WHITE };
+    };
+    // Walk the border clockwise so the dashes march around it
+    for x in left..=right {
+        plot(x, top, x - left);
+        plot(x, bottom, right - x);
+    }
+    for y in top..=bottom {
+        plot(right, y, y - top);
+        plot(left, y, bottom - y);
+    }
+}
+
+/// Where the selection tool is
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    /// Nothing selected
+    Idle,
+    /// A selection being dragged out, corner to corner
+    Dragging {
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+    /// A rectangle selected, its pixels still on the canvas
+    Selected(Region),
+    /// Pixels lifted off the canvas (or pasted), drawn at (x, y)
+    Floating {
+        clip: Clip,
+        x: isize,
+        y: isize,
+        /// Where the pixels were lifted from (None for a paste)
+        origin: Option<Region>,
+        /// Pointer offset from the top-left corner while being dragged
+        grab: Option<(isize, isize)>,
+    },
+}
+
+/// The selection tool's state and clipboard
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    mode: Mode,
+    clipboard: Option<Clip>,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection {
+            mode: Mode::Idle,
+            clipboard: None,
+        }
+    }
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether nothing is selected, being selected, or floating
+    pub fn is_empty(&self) -> bool {
+        self.mode == Mode::Idle
+    }
+
+    /// Whether a lifted or pasted selection is waiting to be committed
+    pub fn is_floating(&self) -> bool {
+        matches!(self.mode, Mode::Floating { .. })
+    }
+
+    /// The clipboard's contents, if anything was copied
+    pub fn clipboard(&self) -> Option<&Clip> {
+        self.clipboard.as_ref()
+    }
+
+    /// The selected rectangle: being dragged, selected, or floating (where it
+    /// is now, which may hang off the canvas)
+    pub fn bounds(&self) -> Option<(isize, isize, usize, usize)> {
+        match &self.mode {
+            Mode::Idle => None,
+            Mode::Dragging { start, end } => {
+                let r = Region::around(start.0, start.1, end.0, end.1, 0);
+                Some((r.x as isize, r.y as isize, r.width, r.height))
+            }
+            Mode::Selected(r) => Some((r.x as isize, r.y as isize, r.width, r.height)),
+            Mode::Floating { clip, x, y, .. } => Some((*x, *y, clip.width, clip.height)),
+        }
+    }
+
+    /// Whether (x, y) is inside the selected or floating rectangle
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        match &self.mode {
+            Mode::Selected(_) | Mode::Floating { .. } => {
+                let (left, top, width, height) = self.bounds().expect("a selection");
+                let (x, y) = (x as isize, y as isize);
+                x >= left && x < left + width as isize && y >= top && y < top + height as isize
+            }
+            _ => false,
+        }
+    }
+
+    /// Handle a press of the pointer at (x, y) on the canvas
+    ///
+    /// A press inside the selection picks it up to move it (lifting it off the
+    /// canvas if it isn't floating yet); a press anywhere else commits any
+    /// floating selection and starts a new one. Returns the history label if
+    /// the canvas changed.
+    pub fn press(&mut self, buffer: &mut [u32], x: usize, y: usize) -> Option<&'static str> {
+        if self.contains(x, y) {
+            if let Mode::Selected(region) = self.mode {
+                self.mode = Mode::Floating {
+                    clip: Clip::copy(buffer, region),
+                    x: region.x as isize,
+                    y: region.y as isize,
+                    origin: Some(region),
+                    grab: None,
+                };
+                erase(buffer, region);
+            }
+            if let Mode::Floating {
+                x: fx, y: fy, grab, ..
+            } = &mut self.mode
+            {
+                *grab = Some((x as isize - *fx, y as isize - *fy));
+            }
+            return None;
+        }
+        let committed = self.commit(buffer);
+        let start = clamp_to_canvas(x, y);
+        self.mode = Mode::Dragging { start, end: start };
+        committed
+    }
+
+    /// Follow the pointer, held down at (x, y)
+    pub fn drag(&mut self, x: usize, y: usize) {
+        match &mut self.mode {
+            Mode::Dragging { end, .. } => *end = clamp_to_canvas(x, y),
+            Mode::Floating {
+                x: fx,
+                y: fy,
+                grab: Some((gx, gy)),
+                ..
+            } => {
+                *fx = x as isize - *gx;
+                *fy = y as isize - *gy;
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle the pointer being released: a dragged-out rectangle becomes the
+    /// selection (a click without a drag selects nothing), and a floating
+    /// selection is put down (still floating) where it is
+    pub fn release(&mut self) {
+        match &mut self.mode {
+            Mode::Dragging { start, end } => {
+                let (start, end) = (*start, *end);
+                self.mode = if start.0 != end.0 && start.1 != end.1 {
+                    Mode::Selected(Region::around(start.0, start.1, end.0, end.1, 0))
+                } else {
+                    Mode::Idle
+                };
+            }
+            Mode::Floating { grab, .. } => *grab = None,
+            _ => {}
+        }
+    }
+
+    /// Stamp a floating selection onto the canvas and deselect
+    ///
+    /// Returns the history label ("move" or "paste") if the canvas changed.
+    pub fn commit(&mut self, buffer: &mut [u32]) -> Option<&'static str> {
+        match std::mem::replace(&mut self.mode, Mode::Idle) {
+            Mode::Floating {
+                clip, x, y, origin, ..
+            } => {
+                clip.stamp(buffer, x, y);
+                Some(if origin.is_some() { "move" } else { "paste" })
+            }
+            _ => None,
+        }
+    }
+
+    /// Deselect without changing the canvas: a lifted selection goes back
+    /// where it came from, and a paste is dropped
+    pub fn cancel(&mut self, buffer: &mut [u32]) {
+        if let Mode::Floating {
+            clip,
+            origin: Some(origin),
+            ..
+        } = std::mem::replace(&mut self.mode, Mode::Idle)
+        {
+            clip.stamp(buffer, origin.x as isize, origin.y as isize);
+        }
+    }
+
+    /// Forget the selection, leaving the canvas as it is (for when the canvas
+    /// is about to be replaced, e.g. cleared)
+    pub fn clear(&mut self) {
+        self.mode = Mode::Idle;
+    }
+
+    /// Copy the selected (or floating) pixels to the clipboard, returning
+    /// whether anything was selected
+    pub fn copy(&mut self, buffer: &[u32]) -> bool {
+        match &self.mode {
+            Mode::Selected(region) => self.clipboard = Some(Clip::copy(buffer, *region)),
+            Mode::Floating { clip, .. } => self.clipboard = Some(clip.clone()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Erase the selected pixels (or drop the floating ones) and deselect
+    ///
+    /// Returns the history label ("delete") if the canvas changed.
+    pub fn delete(&mut self, buffer: &mut [u32]) -> Option<&'static str> {
+        match std::mem::replace(&mut self.mode, Mode::Idle) {
+            Mode::Selected(region) => {
+                erase(buffer, region);
+                Some("delete")
+            }
+            // The lifted pixels were already erased from the canvas
+            Mode::Floating {
+                origin: Some(_), ..
+            } => Some("delete"),
+            _ => None,
+        }
+    }
+
+    /// Copy the selection to the clipboard, then delete it
+    ///
+    /// Returns the history label ("cut") if the canvas changed.
+    pub fn cut(&mut self, buffer: &mut [u32]) -> Option<&'static str> {
+        if !self.copy(buffer) {
+            return None;
+        }
+        self.delete(buffer).map(|_| "cut")
+    }
+
+    /// Float the clipboard's pixels with their top-left corner at (x, y),
+    /// committing any floating selection first
+    ///
+    /// Returns the history label if committing changed the canvas.
+    pub fn paste(&mut self, buffer: &mut [u32], x: usize, y: usize) -> Option<&'static str> {
+        let clip = self.clipboard.clone()?;
+        self.paste_clip(buffer, clip, x, y)
+    }
+
+    /// Float `latest` if given (the system clipboard's image, which may have
+    /// been copied elsewhere since, a screenshot say), or else the
+    /// clipboard's pixels, with the top-left corner at (x, y)
+    ///
+    /// Returns the history label if committing changed the canvas.
+    pub fn paste_latest(
+        &mut self,
+        buffer: &mut [u32],
+        latest: Option<Clip>,
+        x: usize,
+        y: usize,
+    ) -> Option<&'static str> {
+        match latest {
+            Some(clip) => self.paste_clip(buffer, clip, x, y),
+            None => self.paste(buffer, x, y),
+        }
+    }
+
+    /// Float `clip` (from outside the clipboard, e.g. the system clipboard)
+    /// with its top-left corner at (x, y), as `paste` does
+    pub fn paste_clip(
+        &mut self,
+        buffer: &mut [u32],
+        clip: Clip,
+        x: usize,
+        y: usize,
+    ) -> Option<&'static str> {
+        let committed = self.commit(buffer);
+        let (x, y) = clamp_to_canvas(x, y);
+        self.mode = Mode::Floating {
+            clip,
+            x: x as isize,
+            y: y as isize,
+            origin: None,
+            grab: None,
+        };
+        committed
+    }
+
+    /// Draw the selection onto a presented frame: floating pixels, and a
+    /// marching-ants border offset by `phase` (see `ants_phase`)
+    pub fn draw_preview(&self, frame: &mut [u32], phase: usize) {
+        if let Mode::Floating { clip, x, y, .. } = &self.mode {
+            clip.stamp(frame, *x, *y);
+        }
+        if let Some((left, top, width, height)) = self.bounds() {
+            let right = left + width as isize - 1;
+            let bottom = top + height as isize - 1;
+            draw_ants(frame, left, top, right, bottom, phase);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Drop shadows for shapes.
+//!
+//! This module handles:
+//! - The `Shadow` settings set by `shadow on dx,dy [color] [blur]`
+//! - Drawing a shape's shadow: its coverage offset, optionally box-blurred,
+//!   and blended onto the canvas beneath where the shape will be drawn
+//!
+//! The shadow is computed only over the shape's bounding box (grown by the
+//! blur radius), so small shapes on a large canvas stay cheap.
+
+use crate::blend::{blend, with_blend_mode, BlendMode};
+use crate::dash::{stroke_style, with_stroke_style};
+use crate::{canvas_bottom, window_width, CANVAS_TOP, DARK_GRAY};
+
+/// Default shadow color when `shadow on` gives none
+pub const DEFAULT_SHADOW_COLOR: u32 = DARK_GRAY;
+/// Largest shadow offset on either axis, in pixels
+pub const MAX_SHADOW_OFFSET: isize = 100;
+/// Largest blur radius, in pixels
+pub const MAX_SHADOW_BLUR: usize = 20;
+
+/// Offset, color, and softness of the shadow drawn beneath shapes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    pub dx: isize,
+    pub dy: isize,
+    /// Shadow color as `0xRRGGBB`
+    pub color: u32,
+    /// Box blur radius (0 = hard-edged)
+    pub blur: usize,
+}
+
+/// One pass of a box blur along rows (`horizontal`) or columns of a `w` x `h` grid
+fn box_blur(values: &[f32], w: usize, h: usize, radius: usize, horizontal: bool) -> Vec<f32> {
+    let mut out = vec![0.0; values.len()];
+    let (lines, len) = if horizontal { (h, w) } else { (w, h) };
+    let index = |line: usize, i: usize| {
+        if horizontal {
+            line * w + i
+        } else {
+            i * w + line
+        }
+    };
+    let span = (2 * radius + 1) as f32;
+    for line in 0..lines {
+        // Running sum over the window i-radius..=i+radius (outside counts as 0)
+        let mut sum: f32 = (0..=radius.min(len - 1))
+            .map(|i| values[index(line, i)])
+            .sum();
+        for i in 0..len {
+            out[index(line, i)] = sum / span;
+            if i + radius + 1 < len {
+                sum += values[index(line, i + radius + 1)];
+            }
+            if i >= radius {
+                sum -= values[index(line, i - radius)];
+            }
+        }
+    }
+    out
+}
+
+/// Draw the shadow of a shape onto the canvas area of `buffer`
+///
+/// `draw_mask` must draw the shape into the zeroed scratch buffer it is given
+/// (any non-zero color); every pixel it sets casts shadow.
+pub fn draw_shadow(buffer: &mut [u32], shadow: &Shadow, draw_mask: impl FnOnce(&mut [u32])) {
+    let width = window_width();
+    let bottom = canvas_bottom();
+    let mut mask = vec![0u32; buffer.len()];
+    // Blending with the empty mask could leave no mark; a dashed shadow
+    // starts its pattern where the shape's will
+    with_blend_mode(BlendMode::Normal, || {
+        with_stroke_style(stroke_style(), || draw_mask(&mut mask))
+    });
+
+    // Bounding box of the shape's coverage
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for y in CANVAS_TOP..bottom {
+        let row = &mask[y * width..(y + 1) * width];
+        if let (Some(first), Some(last)) = (
+            row.iter().position(|&p| p != 0),
+            row.iter().rposition(|&p| p != 0),
+        ) {
+            bounds = Some(match bounds {
+                None => (first, y, last, y),
+                Some((x0, y0, x1, _)) => (x0.min(first), y0, x1.max(last), y),
+            });
+        }
+    }
+    let Some((x0, y0, x1, y1)) = bounds else {
+        return;
+    };
+
+    // Work area: the coverage box grown by the blur radius
+    let pad = shadow.blur;
+    let (ax, ay) = (x0 as isize - pad as isize, y0 as isize - pad as isize);
+    let (w, h) = (x1 - x0 + 1 + 2 * pad, y1 - y0 + 1 + 2 * pad);
+    let mut coverage = vec![0.0f32; w * h];
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            if mask[y * width + x] != 0 {
+                coverage[(y - y0 + pad) * w + (x - x0 + pad)] = 1.0;
+            }
+        }
+    }
+    if pad > 0 {
+        coverage = box_blur(&coverage, w, h, pad, true);
+        coverage = box_blur(&coverage, w, h, pad, false);
+    }
+
+    // Blend the offset coverage onto the canvas, clipped to the drawable area
+    for cy in 0..h {
+        let y = ay + cy as isize + shadow.dy;
+        if y < CANVAS_TOP as isize || y >= bottom as isize {
+            continue;
+        }
+        for cx in 0..w {
+            let x = ax + cx as isize + shadow.dx;
+            if x < 0 || x >= width as isize {
+                continue;
+            }
+            let alpha = coverage[cy * w + cx];
+            if alpha > 0.0 {
+                let i = y as usize * width + x as usize;
+                buffer[i] = blend(buffer[i], shadow.color, alpha.min(1.0));
+            }
+        }
+    }
+}
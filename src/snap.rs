@@ -0,0 +1,142 @@
+//! Shape snap: recognizing freehand strokes as clean shapes.
+//!
+//! This module handles:
+//! - `recognize`, which fits the points of a brush stroke to a line, circle,
+//!   or rectangle within a tolerance, giving the command that draws it
+//!
+//! With `snap on`, the run loop checks each freehand stroke when the mouse is
+//! released: a recognized stroke is erased and the shape drawn in its place
+//! (one history entry, named after the shape), and subscribers get the
+//! command as a `snap <command>` event. The tolerance is a fraction of the
+//! stroke's size, so small and large sketches snap alike.
+
+use crate::command::Command;
+
+/// Tolerance used by `snap on` without one
+pub const DEFAULT_SNAP_TOLERANCE: f64 = 0.15;
+/// Loosest tolerance `snap on` accepts
+pub const MAX_SNAP_TOLERANCE: f64 = 0.5;
+/// Smallest stroke (its length for a line, bounding box for a closed shape),
+/// in pixels, that is recognized
+pub const MIN_SNAP_EXTENT: f64 = 10.0;
+/// Spacing of the points a stroke is resampled to, in pixels
+const SAMPLE_SPACING: f64 = 2.0;
+
+type Point = (f64, f64);
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Points every `SAMPLE_SPACING` pixels along the stroke, so fast strokes
+/// (few, far-apart mouse positions) are judged by their whole path
+fn resample(points: &[(usize, usize)]) -> Vec<Point> {
+    let mut samples = Vec::new();
+    for pair in points.windows(2) {
+        let a = (pair[0].0 as f64, pair[0].1 as f64);
+        let b = (pair[1].0 as f64, pair[1].1 as f64);
+        let steps = (distance(a, b) / SAMPLE_SPACING).ceil().max(1.0) as usize;
+        for i in 0..steps {
+            let t = i as f64 / steps as f64;
+            samples.push((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t));
+        }
+    }
+    if let Some(&(x, y)) = points.last() {
+        samples.push((x as f64, y as f64));
+    }
+    samples
+}
+
+/// Distance from `p` to the segment from `a` to `b`
+fn segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    if len2 == 0.0 {
+        return distance(p, a);
+    }
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len2).clamp(0.0, 1.0);
+    distance(p, (a.0 + dx * t, a.1 + dy * t))
+}
+
+fn round(v: f64) -> usize {
+    v.round().max(0.0) as usize
+}
+
+/// An open stroke that stays close to the segment between its ends
+fn as_line(samples: &[Point], tolerance: f64) -> Option<Command> {
+    let (a, b) = (*samples.first()?, *samples.last()?);
+    let len = distance(a, b);
+    let path: f64 = samples.windows(2).map(|w| distance(w[0], w[1])).sum();
+    let straight = samples
+        .iter()
+        .all(|&p| segment_distance(p, a, b) <= tolerance * len);
+    (len >= MIN_SNAP_EXTENT && path <= len * (1.0 + tolerance) && straight).then(|| Command::Line {
+        x1: round(a.0),
+        y1: round(a.1),
+        x2: round(b.0),
+        y2: round(b.1),
+    })
+}
+
+/// A closed stroke whose points are all about the same distance from the
+/// middle of its bounding box
+fn as_circle(samples: &[Point], bounds: (f64, f64, f64, f64), tolerance: f64) -> Option<Command> {
+    let (x1, y1, x2, y2) = bounds;
+    let center = ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+    let radii: Vec<f64> = samples.iter().map(|&p| distance(p, center)).collect();
+    let r = radii.iter().sum::<f64>() / radii.len() as f64;
+    radii
+        .iter()
+        .all(|&d| (d - r).abs() <= tolerance * r)
+        .then(|| Command::Circle {
+            x: round(center.0),
+            y: round(center.1),
+            r: round(r).max(1),
+        })
+}
+
+/// A closed stroke whose points all lie near an edge of its bounding box
+fn as_rect(samples: &[Point], bounds: (f64, f64, f64, f64), tolerance: f64) -> Option<Command> {
+    let (x1, y1, x2, y2) = bounds;
+    // Half the tolerance of the other shapes, or circles would pass too
+    let slack = tolerance * (x2 - x1).min(y2 - y1) / 2.0;
+    samples
+        .iter()
+        .all(|&(x, y)| {
+            let edge = (x - x1).min(x2 - x).min(y - y1).min(y2 - y);
+            edge <= slack
+        })
+        .then(|| Command::Rect {
+            x1: round(x1),
+            y1: round(y1),
+            x2: round(x2),
+            y2: round(y2),
+        })
+}
+
+/// Recognize a freehand stroke (its mouse positions, in order) as a line,
+/// circle, or rectangle, returning the command that draws the clean shape
+///
+/// `tolerance` is how far (as a fraction of the stroke's size) the stroke may
+/// stray from the shape. A stroke counts as closed when its ends are within
+/// twice that of its bounding box's diagonal.
+pub fn recognize(points: &[(usize, usize)], tolerance: f64) -> Option<Command> {
+    let samples = resample(points);
+    if samples.len() < 2 {
+        return None;
+    }
+    let bounds = samples.iter().fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(x1, y1, x2, y2), &(x, y)| (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+    );
+    let (x1, y1, x2, y2) = bounds;
+    let diagonal = distance((x1, y1), (x2, y2));
+    let gap = distance(samples[0], samples[samples.len() - 1]);
+    if gap > 2.0 * tolerance * diagonal {
+        return as_line(&samples, tolerance);
+    }
+    if (x2 - x1).min(y2 - y1) < MIN_SNAP_EXTENT {
+        return None;
+    }
+    as_circle(&samples, bounds, tolerance).or_else(|| as_rect(&samples, bounds, tolerance))
+}
@@ -0,0 +1,310 @@
+//! Background snapshot encoding for the displai application.
+//!
+//! This module handles:
+//! - A `SnapshotWorker` thread that encodes images off the UI thread
+//! - Coalescing requests for the same path that arrive before encoding starts
+//! - Completion results carrying every requester's token, so each can be answered
+//! - Choosing the image format from the snapshot path's extension, and
+//!   encoding it (AVIF, with the `avif` feature, at the encoder's fastest
+//!   speed, so snapshots taken many times a second keep up)
+//! - `snapshot base64` replies, which carry the PNG itself instead of a path
+//!
+//! The UI thread copies the frame and returns immediately; it polls for
+//! completions each frame and replies to whoever asked for the snapshot.
+
+use std::collections::VecDeque;
+use std::io::{Seek, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::canvas::{dimensions, with_dimensions, Dimensions};
+use crate::command::{
+    encode_canvas_png, save_canvas_image, save_region_image, CommandError, CommandOutput,
+    CommandResult,
+};
+use crate::icc::{color_profile, with_color_profile, ColorProfile};
+use crate::CANVAS_TOP;
+
+#[cfg(feature = "avif")]
+use image::codecs::avif::AvifEncoder;
+#[cfg(feature = "avif")]
+use image::{ExtendedColorType, ImageEncoder};
+use image::{ImageFormat, RgbImage};
+
+/// Snapshot file extensions (lowercase) and the formats they are written in
+/// (`avif` only with the `avif` feature)
+pub const SNAPSHOT_FORMATS: &[(&str, ImageFormat)] = &[
+    ("png", ImageFormat::Png),
+    ("jpg", ImageFormat::Jpeg),
+    ("jpeg", ImageFormat::Jpeg),
+    ("bmp", ImageFormat::Bmp),
+    ("webp", ImageFormat::WebP),
+    #[cfg(feature = "avif")]
+    ("avif", ImageFormat::Avif),
+];
+
+/// The snapshot extensions, as errors list them
+#[cfg(not(feature = "avif"))]
+const SNAPSHOT_EXTENSIONS: &str = ".png, .jpg, .bmp, or .webp";
+#[cfg(feature = "avif")]
+const SNAPSHOT_EXTENSIONS: &str = ".png, .jpg, .bmp, .webp, or .avif";
+
+/// AVIF encoder speed, 1 (smallest files) to 10 (fastest)
+#[cfg(feature = "avif")]
+pub const AVIF_SPEED: u8 = 10;
+/// AVIF quality, 1-100
+#[cfg(feature = "avif")]
+pub const AVIF_QUALITY: u8 = 80;
+
+/// The image format a snapshot to `path` is written in, from its extension
+/// (case-insensitive)
+pub fn snapshot_format(path: &str) -> Result<ImageFormat, String> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    SNAPSHOT_FORMATS
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|&(_, format)| format)
+        .ok_or_else(|| {
+            format!(
+                "unsupported snapshot format '{}' (use {})",
+                path, SNAPSHOT_EXTENSIONS
+            )
+        })
+}
+
+/// Encode `img` as `format` into `out`, untagged
+///
+/// WebP is lossless; AVIF is lossy, at `AVIF_QUALITY` and `AVIF_SPEED`.
+pub fn write_image<W: Write + Seek>(
+    img: &RgbImage,
+    out: &mut W,
+    format: ImageFormat,
+) -> Result<(), String> {
+    #[cfg(feature = "avif")]
+    if format == ImageFormat::Avif {
+        let encoder = AvifEncoder::new_with_speed_quality(out, AVIF_SPEED, AVIF_QUALITY);
+        return encoder
+            .write_image(img, img.width(), img.height(), ExtendedColorType::Rgb8)
+            .map_err(|e| e.to_string());
+    }
+    img.write_to(out, format).map_err(|e| e.to_string())
+}
+
+/// A queued snapshot waiting for the worker
+struct SnapshotJob<T> {
+    path: String,
+    dims: Dimensions,
+    /// The color profile in effect when it was requested
+    profile: Option<ColorProfile>,
+    pixels: Vec<u32>,
+    /// Only this width and height of the canvas, from its top-left corner
+    crop: Option<(usize, usize)>,
+    tokens: Vec<T>,
+}
+
+/// Save the canvas portion of `pixels` to `path`, cropped if `crop` says so
+fn save_job(pixels: &[u32], path: &str, crop: Option<(usize, usize)>) -> Result<(), String> {
+    match crop {
+        Some((width, height)) => save_region_image(
+            pixels,
+            (0, CANVAS_TOP),
+            (width - 1, CANVAS_TOP + height - 1),
+            path,
+        ),
+        None => save_canvas_image(pixels, path),
+    }
+}
+
+/// Queue shared between the UI thread and the worker
+struct SnapshotQueue<T> {
+    jobs: VecDeque<SnapshotJob<T>>,
+    shutdown: bool,
+}
+
+/// The outcome of one encoded snapshot, with the tokens of every request it satisfied
+#[derive(Debug)]
+pub struct SnapshotDone<T> {
+    pub path: String,
+    pub result: Result<(), String>,
+    pub tokens: Vec<T>,
+}
+
+/// Encodes snapshots on a background thread
+///
+/// `T` is an opaque token handed back on completion (e.g. where to send the reply).
+pub struct SnapshotWorker<T> {
+    queue: Arc<(Mutex<SnapshotQueue<T>>, Condvar)>,
+    done: Receiver<SnapshotDone<T>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> SnapshotWorker<T> {
+    /// Start the worker thread
+    pub fn spawn() -> Self {
+        let queue = Arc::new((
+            Mutex::new(SnapshotQueue {
+                jobs: VecDeque::new(),
+                shutdown: false,
+            }),
+            Condvar::new(),
+        ));
+        let (done_tx, done) = mpsc::channel();
+
+        let worker_queue = Arc::clone(&queue);
+        let handle = thread::spawn(move || {
+            let (lock, ready) = &*worker_queue;
+            loop {
+                let job = {
+                    let mut state = lock.lock().unwrap();
+                    loop {
+                        if let Some(job) = state.jobs.pop_front() {
+                            break job;
+                        }
+                        if state.shutdown {
+                            return;
+                        }
+                        state = ready.wait(state).unwrap();
+                    }
+                };
+                let result = with_color_profile(job.profile, || {
+                    with_dimensions(job.dims, || save_job(&job.pixels, &job.path, job.crop))
+                });
+                let done = SnapshotDone {
+                    path: job.path,
+                    result,
+                    tokens: job.tokens,
+                };
+                if done_tx.send(done).is_err() {
+                    return;
+                }
+            }
+        });
+
+        SnapshotWorker {
+            queue,
+            done,
+            handle: Some(handle),
+        }
+    }
+
+    /// Queue a snapshot of `pixels` (sized for this thread's `dimensions()`) to
+    /// `path`, tagged with this thread's `color_profile()`
+    ///
+    /// If a request for the same path is still waiting, it is replaced by this
+    /// newer frame and `token` joins it instead of adding another encode.
+    /// Returns true if the request was coalesced.
+    pub fn request(&self, pixels: &[u32], path: &str, token: T) -> bool {
+        self.queue_job(pixels, path, None, token)
+    }
+
+    /// `request`, saving only the `width` x `height` corner of the canvas at
+    /// its top-left (an annotated image smaller than the window)
+    pub fn request_cropped(
+        &self,
+        pixels: &[u32],
+        path: &str,
+        (width, height): (usize, usize),
+        token: T,
+    ) -> bool {
+        self.queue_job(pixels, path, Some((width, height)), token)
+    }
+
+    fn queue_job(
+        &self,
+        pixels: &[u32],
+        path: &str,
+        crop: Option<(usize, usize)>,
+        token: T,
+    ) -> bool {
+        let (lock, ready) = &*self.queue;
+        let mut state = lock.lock().unwrap();
+        if let Some(job) = state.jobs.iter_mut().find(|job| job.path == path) {
+            job.dims = dimensions();
+            job.profile = color_profile();
+            job.pixels.clear();
+            job.pixels.extend_from_slice(pixels);
+            job.crop = crop;
+            job.tokens.push(token);
+            return true;
+        }
+        state.jobs.push_back(SnapshotJob {
+            path: path.to_string(),
+            dims: dimensions(),
+            profile: color_profile(),
+            pixels: pixels.to_vec(),
+            crop,
+            tokens: vec![token],
+        });
+        ready.notify_one();
+        false
+    }
+
+    /// Collect finished snapshots without blocking
+    pub fn poll(&self) -> Vec<SnapshotDone<T>> {
+        self.done.try_iter().collect()
+    }
+
+    /// Wait for the next finished snapshot (None if the worker has stopped)
+    pub fn wait(&self) -> Option<SnapshotDone<T>> {
+        self.done.recv().ok()
+    }
+}
+
+impl<T> Drop for SnapshotWorker<T> {
+    /// Finish any queued snapshots, then stop the worker
+    fn drop(&mut self) {
+        let (lock, ready) = &*self.queue;
+        if let Ok(mut state) = lock.lock() {
+            state.shutdown = true;
+        }
+        ready.notify_one();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Response line for a finished snapshot, matching the synchronous `snapshot` reply
+pub fn snapshot_response(path: &str, result: &Result<(), String>) -> CommandResult {
+    match result {
+        Ok(()) => Ok(CommandOutput::Text(format!("saved {}", path))),
+        Err(e) => Err(CommandError::Failed(e.clone())),
+    }
+}
+
+/// Base64 (standard alphabet, padded) of `bytes`
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// The reply to `snapshot base64`: the canvas PNG as one base64 line
+///
+/// Base64 never contains `:`, so the reply can't be mistaken for an `error:` line.
+pub fn base64_snapshot_response(buffer: &[u32]) -> CommandResult {
+    Ok(CommandOutput::Text(base64_encode(&encode_canvas_png(
+        buffer,
+    )?)))
+}
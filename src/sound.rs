@@ -0,0 +1,52 @@
+//! Sound cues for alerts.
+//!
+//! This module handles:
+//! - The limits and defaults of `beep [freq] [ms]`
+//! - Playing a sine tone on the default audio output, off the UI thread
+//!
+//! Audio is behind the `sound` feature. Without it, or when no output device
+//! can be opened, beeps are silently skipped: a display that can't make noise
+//! should still show the alert.
+
+/// Pitch of a beep given no frequency, in Hz
+pub const DEFAULT_BEEP_FREQ: u32 = 880;
+/// Length of a beep given no duration, in milliseconds
+pub const DEFAULT_BEEP_MS: u64 = 200;
+/// Lowest and highest beep pitches, in Hz (roughly the range of hearing)
+pub const MIN_BEEP_FREQ: u32 = 20;
+pub const MAX_BEEP_FREQ: u32 = 20_000;
+/// Longest beep, in milliseconds
+pub const MAX_BEEP_MS: u64 = 5000;
+
+/// Whether this build can play sound (the `sound` feature)
+pub fn sound_supported() -> bool {
+    cfg!(feature = "sound")
+}
+
+/// Play a `freq` Hz tone for `ms` milliseconds without blocking the caller
+///
+/// Does nothing without the `sound` feature or an audio device.
+pub fn play_beep(freq: u32, ms: u64) {
+    #[cfg(feature = "sound")]
+    std::thread::spawn(move || {
+        // The stream must outlive the tone, so the thread holds it until done
+        let _ = play_tone(freq, ms);
+    });
+    #[cfg(not(feature = "sound"))]
+    let _ = (freq, ms);
+}
+
+#[cfg(feature = "sound")]
+fn play_tone(freq: u32, ms: u64) -> Result<(), String> {
+    use rodio::source::{SineWave, Source};
+    use std::time::Duration;
+
+    let duration = Duration::from_millis(ms);
+    let (_stream, handle) = rodio::OutputStream::try_default().map_err(|e| e.to_string())?;
+    let tone = SineWave::new(freq as f32)
+        .take_duration(duration)
+        .amplify(0.2);
+    handle.play_raw(tone).map_err(|e| e.to_string())?;
+    std::thread::sleep(duration);
+    Ok(())
+}
@@ -0,0 +1,1003 @@
+//! Application state for the displai application.
+//!
+//! This module handles:
+//! - The `AppState` type that owns the canvas and the current tool settings
+//! - Executing protocol commands against that state
+//! - Drawing shapes with the current drop shadow, if one is set
+//! - Drawing with the current anti-aliasing, blend mode, stroke style, and
+//!   brush shape
+//! - Filling shapes with the current gradient, if one is set
+//! - The retained objects drawn over the canvas, and rendering both together
+//! - Variables that templated object text is expanded with
+//! - Aliases, which lines are parsed with, and running the commands one
+//!   stands for
+//! - The palette that color indices resolve against
+//! - The modal dialog, drawn over everything else while it is open
+//! - The display list of drawn shapes, exported with `export svg`
+//! - Poster tiles of the canvas as viewers see it (`export tiles`)
+//! - Saving snapshots and tiles in the current color profile (`color-profile`)
+//! - Cropping and resizing the canvas (`canvas crop`, `canvas resize`)
+//! - The pane layout, and running `@N` commands inside a pane
+//! - Reporting the settings (`state`) and panes (`layout`), with who holds
+//!   each pane by the session's region locks
+//! - Pane data ranges (`scale`), their axes, and `plot` lines drawn in them
+//! - Scrolling time series appended to panes with `tsplot`
+//! - Double-buffered panes (`hold`), shown as of their last `flip`
+//!
+//! `AppState` is everything a command needs, so the library can be embedded
+//! without a window: create a state, feed it commands, read the canvas.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use crate::alias::{alias_lines, alias_params, alias_response, with_aliases, Aliases};
+use crate::antialias::draw_covered;
+use crate::antialias::with_antialiasing;
+use crate::blend::{with_blend_mode, BlendMode};
+use crate::brush::{with_brush_shape, BrushShape};
+use crate::canvas::with_dimensions;
+use crate::command::{
+    done, execute_command, failed, parse_command_line, reply, response_text, save_canvas_image,
+    save_region_image, settings_summary, validate, AliasCommand, AttributedPoint, Command,
+    CommandError, CommandOutput, CommandResult, DialogCommand, ObjectCommand, PaletteCommand,
+    VarCommand,
+};
+use crate::dash::{with_stroke_style, StrokeStyle};
+use crate::dialog::Dialog;
+use crate::drawing::{
+    draw_arc_with_fill, draw_arrow, draw_bezier, draw_brush_line, draw_polygon_with_fill,
+    draw_rounded_rect_with_fill, draw_shape_with_fill, fill_polygon, fill_rounded_rect, fill_shape,
+    ArcShape, Arrow, RoundedRect,
+};
+use crate::gradient::Gradient;
+use crate::icc::{with_color_profile, ColorProfile};
+use crate::locks::{ClientId, Ownership, Region, RegionLocks, STDIN_CLIENT};
+use crate::objects::{is_checked, slider_value, Objects};
+use crate::palette::{with_palette, Palette};
+use crate::panes::{clip_to_pane, HeldPane, PaneScale, Panes, TimeSeries};
+use crate::poster::export_tiles;
+use crate::resize::{resized_canvas, CanvasSize};
+use crate::shadow::{draw_shadow, Shadow};
+use crate::snapshot::{base64_snapshot_response, snapshot_response};
+use crate::strokes::{save_strokes, StrokeLog};
+use crate::svg::{save_svg, DisplayList};
+use crate::tabs::default_snapshot_path;
+use crate::vars::Variables;
+use crate::{Canvas, ToolMode, COLOR_PALETTE, DARK_GRAY, DEFAULT_BRUSH_SIZE, WHITE};
+
+/// A canvas plus the tool settings commands and mouse input draw with
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppState {
+    pub canvas: Canvas,
+    /// Edge color as `0xRRGGBB` (None = transparent)
+    pub edge_color: Option<u32>,
+    /// Fill color as `0xRRGGBB` (None = no fill)
+    pub fill_color: Option<u32>,
+    /// Gradient filling shapes in place of the fill color (None = flat)
+    pub gradient: Option<Gradient>,
+    pub brush_size: usize,
+    pub current_tool: ToolMode,
+    /// Shadow drawn beneath each shape (None = no shadow)
+    pub shadow: Option<Shadow>,
+    /// Tolerance of shape snap for freehand strokes (None = off)
+    pub snap: Option<f64>,
+    /// Whether shapes are drawn with anti-aliased edges
+    pub antialias: bool,
+    /// How drawing mixes with the canvas beneath
+    pub blend: BlendMode,
+    /// Whether outlines are solid, dashed, or dotted
+    pub stroke_style: StrokeStyle,
+    /// What each stamp of the brush covers
+    pub brush_shape: BrushShape,
+    /// Profile snapshots and exports are converted to and tagged with (None =
+    /// untagged)
+    pub color_profile: Option<ColorProfile>,
+    /// Panes the canvas is split into, for `@N` commands
+    pub panes: Panes,
+    /// Data ranges set with `scale`, by pane number
+    pub scales: BTreeMap<usize, PaneScale>,
+    /// Time series appended with `tsplot`, by pane number
+    pub series: BTreeMap<usize, TimeSeries>,
+    /// What viewers see of panes held with `hold`, by pane number
+    pub held: BTreeMap<usize, HeldPane>,
+    /// Retained objects drawn over the canvas
+    pub objects: Objects,
+    /// Values substituted into `{name}` placeholders in object text
+    pub vars: Variables,
+    /// Short verbs standing for runs of commands, defined with `alias`
+    pub aliases: Aliases,
+    /// Colors behind palette indices (`color 3`, `edge 3`, the toolbar swatches)
+    pub palette: Palette,
+    /// The open modal dialog, which takes all input until it is answered
+    pub dialog: Option<Dialog>,
+    /// Shapes drawn so far, kept for vector export
+    pub display_list: DisplayList,
+    /// Freehand strokes drawn so far, kept for `export strokes`
+    pub strokes: StrokeLog,
+}
+
+impl AppState {
+    /// Create a blank 800x600 canvas with the app's default tools (black edge, no fill)
+    pub fn new() -> Self {
+        Self::with_canvas(Canvas::default())
+    }
+
+    /// Start from an existing canvas (of any size) with the app's default tools
+    pub fn with_canvas(canvas: Canvas) -> Self {
+        AppState {
+            canvas,
+            edge_color: Some(COLOR_PALETTE[0]),
+            fill_color: None,
+            gradient: None,
+            brush_size: DEFAULT_BRUSH_SIZE,
+            current_tool: ToolMode::default(),
+            shadow: None,
+            snap: None,
+            antialias: false,
+            blend: BlendMode::Normal,
+            stroke_style: StrokeStyle::Solid,
+            brush_shape: BrushShape::Circle,
+            color_profile: None,
+            panes: Panes::default(),
+            scales: BTreeMap::new(),
+            series: BTreeMap::new(),
+            held: BTreeMap::new(),
+            objects: Objects::new(),
+            vars: Variables::new(),
+            aliases: Aliases::new(),
+            palette: Palette::new(),
+            dialog: None,
+            display_list: DisplayList::new(),
+            strokes: StrokeLog::new(),
+        }
+    }
+
+    /// Parse, check, and execute one command line, as a structured result:
+    /// the output (`CommandOutput::Ok` for commands with none), or why the
+    /// line isn't a command, was refused by `validate`, or failed
+    ///
+    /// Ranges are checked against the canvas's own size, not the thread's.
+    pub fn run(&mut self, line: &str) -> CommandResult {
+        let cmd = with_dimensions(self.canvas.dimensions(), || {
+            let cmd = self.parse(line)?;
+            validate(&self.placed(&cmd))?;
+            Ok::<_, CommandError>(cmd)
+        })?;
+        self.run_command(&cmd)
+    }
+
+    /// Parse a command line with this state's palette and aliases
+    pub fn parse(&self, line: &str) -> Result<Command, CommandError> {
+        with_palette(&self.palette, || {
+            with_aliases(&self.aliases, || parse_command_line(line))
+        })
+    }
+
+    /// Execute a command, answering as the protocol does: None for `ok`, or
+    /// the output or `error: <reason>` line (see `run_command`)
+    pub fn execute(&mut self, cmd: &Command) -> Option<String> {
+        response_text(&self.run_command(cmd))
+    }
+
+    /// Execute a command against the canvas and tool settings, laid out for
+    /// the canvas's own size, returning its output or why it failed
+    ///
+    /// The command isn't checked first; `validate` it (as placed) to refuse
+    /// out-of-range arguments.
+    pub fn run_command(&mut self, cmd: &Command) -> CommandResult {
+        match cmd {
+            Command::Shadow(shadow) => {
+                self.shadow = *shadow;
+                return Ok(CommandOutput::Ok);
+            }
+            Command::Snap(tolerance) => {
+                self.snap = *tolerance;
+                return Ok(CommandOutput::Ok);
+            }
+            Command::Antialias(on) => {
+                self.antialias = *on;
+                return Ok(CommandOutput::Ok);
+            }
+            Command::Blend(mode) => {
+                self.blend = *mode;
+                return Ok(CommandOutput::Ok);
+            }
+            Command::ColorProfile(profile) => {
+                self.color_profile = *profile;
+                return Ok(CommandOutput::Ok);
+            }
+            Command::StrokeStyle(style) => {
+                self.stroke_style = *style;
+                return Ok(CommandOutput::Ok);
+            }
+            Command::BrushShape(shape) => {
+                self.brush_shape = *shape;
+                return Ok(CommandOutput::Ok);
+            }
+            Command::FillGradient(gradient) => {
+                self.gradient = *gradient;
+                // Its start color stands in wherever one color is needed
+                // (bucket fills, SVG export, the toolbar)
+                if let Some(gradient) = gradient {
+                    self.fill_color = Some(gradient.from);
+                }
+                return Ok(CommandOutput::Ok);
+            }
+            // A flat fill replaces the gradient
+            Command::Fill(_) => self.gradient = None,
+            Command::Panes(panes) => {
+                let fits = with_dimensions(self.canvas.dimensions(), || panes.check());
+                return match fits {
+                    Ok(()) => {
+                        // Scales belong to the panes they were set for
+                        self.panes = *panes;
+                        self.scales.clear();
+                        self.series.clear();
+                        self.held.clear();
+                        Ok(CommandOutput::Ok)
+                    }
+                    Err(e) => failed(e),
+                };
+            }
+            Command::CanvasSize(change) => return done(self.resize(change)),
+            // Run alone there are no region locks (a session adds its own)
+            Command::State => return reply(self.describe(&RegionLocks::new(), STDIN_CLIENT)),
+            Command::Layout => return reply(self.layout(&RegionLocks::new(), STDIN_CLIENT)),
+            Command::InPane(index, cmd) => return self.execute_in_pane(*index, cmd),
+            Command::Scale(_) | Command::Plot(_) => return self.execute_in_pane(1, cmd),
+            Command::TsPlot { pane, value } => return self.tsplot(*pane, *value),
+            Command::Hold { pane, on } => return self.hold(*pane, *on),
+            Command::Flip(pane) => return self.flip(*pane),
+            // A cleared canvas starts its time series over
+            Command::Clear => self.series.clear(),
+            Command::Object(op) => {
+                // A new widget starts its variable at a value it can show
+                match op {
+                    ObjectCommand::Slider { min, max, var, .. } => {
+                        let value = slider_value(self.vars.get(var), *min, *max);
+                        self.vars.set(var, &value.to_string());
+                    }
+                    ObjectCommand::Check { var, .. } => {
+                        let on = is_checked(self.vars.get(var));
+                        self.vars.set(var, if on { "on" } else { "off" });
+                    }
+                    ObjectCommand::Input { var, .. } if self.vars.get(var).is_none() => {
+                        self.vars.set(var, "");
+                    }
+                    _ => {}
+                }
+                return self.objects.apply(op, self.edge_color);
+            }
+            Command::Var(VarCommand::Set { name, value }) => {
+                self.vars.set(name, value);
+                return Ok(CommandOutput::Ok);
+            }
+            Command::Var(VarCommand::Get(name)) => {
+                return match self.vars.get(name) {
+                    Some(value) => reply(value),
+                    None => failed(format!("no variable {}", name)),
+                };
+            }
+            Command::Alias(op) => return self.apply_alias(op, cmd),
+            // Each command runs as if sent in turn, stopping at an error
+            Command::RunAlias { commands, .. } => {
+                let mut responses = Vec::new();
+                for cmd in commands {
+                    let response = self.run_command(cmd);
+                    let failed = response.is_err();
+                    responses.push(response);
+                    if failed {
+                        break;
+                    }
+                }
+                return alias_response(responses);
+            }
+            Command::Palette(PaletteCommand::Set { index, color }) => {
+                return done(self.palette.set(*index, *color));
+            }
+            Command::Palette(PaletteCommand::Get) => return reply(self.palette.summary()),
+            Command::Palette(PaletteCommand::Reset) => {
+                self.palette.reset();
+                return Ok(CommandOutput::Ok);
+            }
+            Command::Dialog(DialogCommand::Open { kind, message }) => {
+                if self.dialog.is_some() {
+                    return failed("a dialog is already open".to_string());
+                }
+                self.dialog = Some(Dialog::new(*kind, message));
+                return Ok(CommandOutput::Ok);
+            }
+            Command::Dialog(DialogCommand::Close) => {
+                return match self.dialog.take() {
+                    Some(_) => Ok(CommandOutput::Ok),
+                    None => failed("no dialog is open".to_string()),
+                };
+            }
+            // Snapshots show objects (and any dialog) as they appear on screen
+            Command::Snapshot(path) if self.has_overlays() => {
+                let default = default_snapshot_path();
+                let path = path.as_deref().unwrap_or(&default);
+                let frame = self.render();
+                let result = self.exporting(|| save_canvas_image(&frame, path));
+                return snapshot_response(path, &result);
+            }
+            Command::SnapshotRegion {
+                x1,
+                y1,
+                x2,
+                y2,
+                path,
+            } if self.has_overlays() => {
+                let default = default_snapshot_path();
+                let path = path.as_deref().unwrap_or(&default);
+                let frame = self.render();
+                let result =
+                    self.exporting(|| save_region_image(&frame, (*x1, *y1), (*x2, *y2), path));
+                return snapshot_response(path, &result);
+            }
+            Command::ExportSvg(path) => {
+                let result = with_dimensions(self.canvas.dimensions(), || {
+                    save_svg(&self.display_list, path)
+                });
+                return snapshot_response(path, &result);
+            }
+            Command::ExportStrokes(path) => {
+                let result = with_dimensions(self.canvas.dimensions(), || {
+                    save_strokes(&self.strokes, path)
+                });
+                return snapshot_response(path, &result);
+            }
+            // Tiles show objects (and any dialog) too, as printed from the screen
+            Command::ExportTiles { dir, cols, rows } => {
+                let frame = self.render();
+                let result = self.exporting(|| export_tiles(&frame, dir, *cols, *rows));
+                return match result {
+                    Ok(count) => reply(format!("saved {} tiles to {}", count, dir)),
+                    Err(e) => failed(e),
+                };
+            }
+            Command::SnapshotBase64 if self.has_overlays() => {
+                let frame = self.render();
+                return self.exporting(|| base64_snapshot_response(&frame));
+            }
+            _ => {}
+        }
+        let palette = self.palette;
+        let (antialias, blend) = (self.antialias, self.blend);
+        let (style, brush) = (self.stroke_style, self.brush_shape);
+        let profile = self.color_profile;
+        with_palette(&palette, || {
+            with_blend_mode(blend, || {
+                with_antialiasing(antialias, || {
+                    with_stroke_style(style, || {
+                        with_brush_shape(brush, || {
+                            with_color_profile(profile, || {
+                                with_dimensions(self.canvas.dimensions(), || {
+                                    if let Some((tool, x1, y1, x2, y2)) = cmd.shape_bounds() {
+                                        self.draw_shape_shadow(tool, x1, y1, x2, y2);
+                                    }
+                                    if let Some(points) = cmd.polygon_vertices() {
+                                        self.draw_polygon_shadow(&points);
+                                    }
+                                    if let Some(arc) = cmd.arc_shape() {
+                                        self.draw_arc_shadow(&arc);
+                                    }
+                                    if let Command::Bezier(controls) = cmd {
+                                        self.draw_bezier_shadow(controls);
+                                    }
+                                    if let Some(rect) = cmd.rounded_rect() {
+                                        self.draw_rounded_rect_shadow(&rect);
+                                    }
+                                    if let Some(arrow) = cmd.arrow(self.brush_size) {
+                                        self.draw_arrow_shadow(&arrow);
+                                    }
+                                    self.record(cmd);
+                                    // A gradient fill leaves only the edge to draw
+                                    let mut no_fill = None;
+                                    let fill_color = match self.draw_gradient_fill(cmd) {
+                                        true => &mut no_fill,
+                                        false => &mut self.fill_color,
+                                    };
+                                    execute_command(
+                                        cmd,
+                                        &mut self.canvas,
+                                        &mut self.edge_color,
+                                        fill_color,
+                                        &mut self.brush_size,
+                                    )
+                                })
+                            })
+                        })
+                    })
+                })
+            })
+        })
+    }
+
+    /// Run `f`, which writes the canvas out, with this state's canvas size
+    /// and color profile
+    fn exporting<R>(&self, f: impl FnOnce() -> R) -> R {
+        with_color_profile(self.color_profile, || {
+            with_dimensions(self.canvas.dimensions(), f)
+        })
+    }
+
+    /// Apply `alias` or `unalias` (`cmd`), returning the protocol response
+    ///
+    /// A definition's commands must each parse, with the aliases defined so
+    /// far, so typos are caught when the alias is made rather than used;
+    /// those with arguments (`$1`) in them are checked when it is used.
+    fn apply_alias(&mut self, op: &AliasCommand, cmd: &Command) -> CommandResult {
+        match op {
+            AliasCommand::Define { name, body } => {
+                if let Err(e) = validate(cmd) {
+                    return failed(e);
+                }
+                for line in alias_lines(body) {
+                    if line.split_whitespace().next() == Some(name) {
+                        return failed(format!("alias {} can't use itself", name));
+                    }
+                    if alias_params(line) > 0 {
+                        continue;
+                    }
+                    if let Err(e) = self.parse(line) {
+                        return failed(format!("alias {}: {}", name, e));
+                    }
+                }
+                done(self.aliases.define(name, body))
+            }
+            AliasCommand::Get(name) => match self.aliases.get(name) {
+                Some(body) => reply(format!("\"{}\"", body)),
+                None => failed(format!("no alias {}", name)),
+            },
+            AliasCommand::Remove(name) => {
+                if self.aliases.remove(name) {
+                    Ok(CommandOutput::Ok)
+                } else {
+                    failed(format!("no alias {}", name))
+                }
+            }
+        }
+    }
+
+    /// The `state` response for `client`: the tool settings, then, once the
+    /// canvas is split into panes or a region is locked, which panes are
+    /// free, `client`'s own, or taken by another client's lock, and how many
+    /// locks are held
+    pub fn describe(&self, locks: &RegionLocks, client: ClientId) -> String {
+        let settings = with_palette(&self.palette, || {
+            settings_summary(&self.edge_color, &self.fill_color, self.brush_size)
+        });
+        if self.panes == Panes::default() && locks.is_empty() {
+            return settings;
+        }
+        let (mut free, mut mine, mut taken) = (Vec::new(), Vec::new(), Vec::new());
+        for index in 1..=self.panes.count() {
+            let Some(pane) = self.pane(index) else {
+                continue;
+            };
+            match locks.ownership(client, &pane) {
+                Ownership::Free => free.push(index),
+                Ownership::Mine => mine.push(index),
+                Ownership::Taken(_) => taken.push(index),
+            }
+        }
+        let list = |panes: Vec<usize>| {
+            if panes.is_empty() {
+                "-".to_string()
+            } else {
+                let names: Vec<String> = panes.iter().map(usize::to_string).collect();
+                names.join(",")
+            }
+        };
+        format!(
+            "{} panes:{} free:{} mine:{} taken:{} locks:{}",
+            settings,
+            self.panes,
+            list(free),
+            list(mine),
+            list(taken),
+            locks.len()
+        )
+    }
+
+    /// The `layout` response for `client`: the pane grid and margin, where
+    /// each pane lies (as `lockregion` takes it) and who holds it, and every
+    /// region lock and who holds it
+    ///
+    /// e.g. `panes:2x1 margin:0 pane1:0,24,400,500:free
+    /// pane2:400,24,400,500:client2 lock1:400,24,400,500:client2`
+    pub fn layout(&self, locks: &RegionLocks, client: ClientId) -> String {
+        let mut fields = vec![format!("panes:{} margin:{}", self.panes, self.panes.margin)];
+        for index in 1..=self.panes.count() {
+            if let Some(pane) = self.pane(index) {
+                let owner = locks.ownership(client, &pane);
+                fields.push(format!("pane{}:{}:{}", index, pane, owner));
+            }
+        }
+        fields.extend(
+            locks
+                .iter()
+                .map(|lock| format!("lock{}:{}:{}", lock.id, lock.region, lock.ownership(client))),
+        );
+        fields.join(" ")
+    }
+
+    /// Run a drawing command in pane `index`, its coordinates taken from the
+    /// pane's top-left corner and its pixels kept inside the pane
+    ///
+    /// `clear` whitens just the pane (redrawing its axes, if it has them),
+    /// leaving the display list and stroke log.
+    fn execute_in_pane(&mut self, index: usize, cmd: &Command) -> CommandResult {
+        let Some(pane) = self.pane(index) else {
+            return failed(format!("no pane {} in a {} layout", index, self.panes));
+        };
+        if !cmd.runs_in_pane() {
+            return failed(format!("@{} only prefixes drawing commands", index));
+        }
+        let before = self.canvas.pixels().to_vec();
+        let response = match cmd {
+            Command::Clear => {
+                self.canvas.fill(WHITE);
+                self.series.remove(&index);
+                self.draw_axes(index);
+                Ok(CommandOutput::Ok)
+            }
+            Command::Scale(scale) => self.set_scale(index, *scale),
+            Command::Plot(points) => match self.plot_runs(index, points) {
+                Some(runs) => {
+                    for run in runs {
+                        let _ = self.run_command(&Command::Polyline(attributed(&run)));
+                    }
+                    Ok(CommandOutput::Ok)
+                }
+                None => failed(format!("pane {} has no scale", index)),
+            },
+            _ => self.run_command(&cmd.translated(pane.x, pane.y)),
+        };
+        let width = self.canvas.width();
+        clip_to_pane(&mut self.canvas, &before, width, pane);
+        response
+    }
+
+    /// Set or drop pane `index`'s data range, drawing its axes if it has them
+    fn set_scale(&mut self, index: usize, scale: Option<PaneScale>) -> CommandResult {
+        let Some(scale) = scale else {
+            self.scales.remove(&index);
+            self.series.remove(&index);
+            return Ok(CommandOutput::Ok);
+        };
+        if let Err(e) = scale.check() {
+            return failed(e);
+        }
+        let Some(pane) = self.pane(index) else {
+            return failed(format!("no pane {} in a {} layout", index, self.panes));
+        };
+        if scale.plot_area(pane).is_none() {
+            return failed(format!("pane {} is too small for axes", index));
+        }
+        self.scales.insert(index, scale);
+        // A new range starts the pane's time series over
+        self.series.remove(&index);
+        self.draw_axes(index);
+        Ok(CommandOutput::Ok)
+    }
+
+    /// Append `value` to pane `index`'s time series, in the edge color and
+    /// brush size, keeping its line inside the plot area
+    ///
+    /// Drawn pixels only, since scrolling moves them: nothing is added to
+    /// the display list.
+    fn tsplot(&mut self, index: usize, value: f64) -> CommandResult {
+        let Some(area) = self.tsplot_area(index) else {
+            return match self.pane(index) {
+                Some(_) => failed(format!("pane {} has no scale", index)),
+                None => failed(format!("no pane {} in a {} layout", index, self.panes)),
+            };
+        };
+        let scale = self.scales[&index];
+        let before = self.canvas.pixels().to_vec();
+        let (color, size) = (self.edge_color, self.brush_size);
+        let series = self.series.entry(index).or_default();
+        with_blend_mode(self.blend, || {
+            with_antialiasing(self.antialias, || {
+                with_dimensions(self.canvas.dimensions(), || {
+                    let segment = series.append(&mut self.canvas, &scale, area, value);
+                    if let (Some((a, b)), Some(color)) = (segment, color) {
+                        draw_covered(&mut self.canvas, color, |buffer| {
+                            draw_brush_line(buffer, a.0, a.1, b.0, b.1, color, size)
+                        });
+                    }
+                })
+            })
+        });
+        let width = self.canvas.width();
+        clip_to_pane(&mut self.canvas, &before, width, area);
+        Ok(CommandOutput::Ok)
+    }
+
+    /// Start or stop double-buffering pane `index`: while held, viewers see
+    /// it as it was when held (or last flipped), whatever is drawn in it
+    ///
+    /// Stopping shows the pane as drawn; holding a held pane changes nothing.
+    fn hold(&mut self, index: usize, on: bool) -> CommandResult {
+        if !on {
+            self.held.remove(&index);
+            return Ok(CommandOutput::Ok);
+        }
+        let Some(pane) = self.pane(index) else {
+            return failed(format!("no pane {} in a {} layout", index, self.panes));
+        };
+        let width = self.canvas.width();
+        self.held
+            .entry(index)
+            .or_insert_with(|| HeldPane::capture(&self.canvas, width, pane));
+        Ok(CommandOutput::Ok)
+    }
+
+    /// Show everything drawn in held pane `index` since it was held or last
+    /// flipped, all at once, and keep holding it
+    fn flip(&mut self, index: usize) -> CommandResult {
+        let Some(held) = self.held.get_mut(&index) else {
+            return failed(format!("pane {} isn't held", index));
+        };
+        *held = HeldPane::capture(&self.canvas, self.canvas.width(), held.region);
+        Ok(CommandOutput::Ok)
+    }
+
+    /// Where pane `index`'s time series is drawn: its scale's plot area (None
+    /// if there is no such pane or it has no scale)
+    fn tsplot_area(&self, index: usize) -> Option<Region> {
+        self.scales.get(&index)?.plot_area(self.pane(index)?)
+    }
+
+    /// Draw pane `index`'s axes, if its scale has them
+    fn draw_axes(&mut self, index: usize) {
+        let Some(scale) = self.scales.get(&index).copied().filter(|s| s.axes) else {
+            return;
+        };
+        if let Some(area) = self.pane(index).and_then(|pane| scale.plot_area(pane)) {
+            let dimensions = self.canvas.dimensions();
+            with_dimensions(dimensions, || {
+                scale.draw_axes(&mut self.canvas, area, DARK_GRAY)
+            });
+        }
+    }
+
+    /// Where data points plotted in pane `index` land, as runs of window
+    /// coordinates (see `PaneScale::plot`), or None if the pane has no scale
+    pub fn plot_runs(
+        &self,
+        index: usize,
+        points: &[(f64, f64)],
+    ) -> Option<Vec<Vec<(usize, usize)>>> {
+        let scale = self.scales.get(&index)?;
+        let area = scale.plot_area(self.pane(index)?)?;
+        Some(scale.plot(area, points))
+    }
+
+    /// Where pane `index` lies on this canvas (None if there is no such pane)
+    pub fn pane(&self, index: usize) -> Option<Region> {
+        with_dimensions(self.canvas.dimensions(), || self.panes.pane(index))
+    }
+
+    /// The command as it lands on the canvas: a pane command moved to its
+    /// pane, a plot as the lines it draws, a time series sample as the area
+    /// it scrolls, any other as it is
+    pub fn placed<'a>(&self, cmd: &'a Command) -> Cow<'a, Command> {
+        let landed = match cmd {
+            Command::InPane(index, inner) => self.placed_in_pane(*index, inner),
+            Command::Scale(_) | Command::Plot(_) => self.placed_in_pane(1, cmd),
+            // A sample can scroll the whole plot area
+            Command::TsPlot { pane, .. } => self.tsplot_area(*pane).map(|area| Command::Rect {
+                x1: area.x,
+                y1: area.y,
+                x2: area.x + area.width - 1,
+                y2: area.y + area.height - 1,
+            }),
+            _ => None,
+        };
+        landed.map_or(Cow::Borrowed(cmd), Cow::Owned)
+    }
+
+    /// `cmd` as it lands when run in pane `index` (None if it draws nothing
+    /// there)
+    fn placed_in_pane(&self, index: usize, cmd: &Command) -> Option<Command> {
+        // Left as is, so it's refused for not running in a pane
+        if !cmd.runs_in_pane() {
+            return None;
+        }
+        let pane = self.pane(index)?;
+        match cmd {
+            Command::Plot(points) => Some(Command::Polyline(attributed(
+                &self.plot_runs(index, points)?.concat(),
+            ))),
+            // Axes run along the pane's edges
+            Command::Scale(Some(PaneScale { axes: true, .. })) => Some(Command::Rect {
+                x1: pane.x,
+                y1: pane.y,
+                x2: pane.x + pane.width - 1,
+                y2: pane.y + pane.height - 1,
+            }),
+            Command::Scale(_) => None,
+            _ => Some(cmd.translated(pane.x, pane.y)),
+        }
+    }
+
+    /// Add a shape command to the display list (or empty it and the stroke
+    /// log, for `clear`)
+    fn record(&mut self, cmd: &Command) {
+        let (edge, fill, size) = (self.edge_color, self.fill_color, self.brush_size);
+        if let Some((tool, x1, y1, x2, y2)) = cmd.shape_bounds() {
+            self.display_list
+                .record_drag(tool, x1, y1, x2, y2, edge, fill, size);
+        }
+        match cmd {
+            Command::Polygon(_) | Command::Ngon { .. } | Command::Star { .. } => {
+                let points = cmd.polygon_vertices().expect("polygon command");
+                self.display_list.record_polygon(&points, edge, fill, size);
+            }
+            Command::Arc { .. } | Command::Pie { .. } => {
+                let arc = cmd.arc_shape().expect("arc command");
+                self.display_list.record_arc(arc, edge, fill, size);
+            }
+            Command::Polyline(points) => self.display_list.record_polyline(points, edge, size),
+            Command::Bezier(controls) => self.display_list.record_bezier(controls, edge, size),
+            Command::RoundedRect { .. } => {
+                let rect = cmd.rounded_rect().expect("rounded rect command");
+                self.display_list
+                    .record_rounded_rect(rect, edge, fill, size);
+            }
+            Command::Arrow { .. } => {
+                let arrow = cmd.arrow(size).expect("arrow command");
+                self.display_list.record_arrow(&arrow, edge, size);
+            }
+            Command::Clear => {
+                self.display_list.clear();
+                self.strokes.clear();
+            }
+            _ => {}
+        }
+    }
+
+    /// Draw a shape (as dragged from one corner to the other) with the current
+    /// colors, brush size, and shadow
+    pub fn draw_shape(&mut self, tool: ToolMode, x1: usize, y1: usize, x2: usize, y2: usize) {
+        let (antialias, blend) = (self.antialias, self.blend);
+        let (style, brush) = (self.stroke_style, self.brush_shape);
+        with_blend_mode(blend, || {
+            with_antialiasing(antialias, || {
+                with_stroke_style(style, || {
+                    with_brush_shape(brush, || {
+                        with_dimensions(self.canvas.dimensions(), || {
+                            self.draw_shape_shadow(tool, x1, y1, x2, y2);
+                            let (edge, fill) = (self.edge_color, self.fill_color);
+                            self.display_list.record_drag(
+                                tool,
+                                x1,
+                                y1,
+                                x2,
+                                y2,
+                                edge,
+                                fill,
+                                self.brush_size,
+                            );
+                            let fill = match self.gradient {
+                                Some(gradient) if fill.is_some() => {
+                                    fill_shape(&mut self.canvas, tool, x1, y1, x2, y2, gradient);
+                                    None
+                                }
+                                _ => fill,
+                            };
+                            draw_shape_with_fill(
+                                &mut self.canvas,
+                                tool,
+                                x1,
+                                y1,
+                                x2,
+                                y2,
+                                edge,
+                                fill,
+                                self.brush_size,
+                            );
+                        })
+                    })
+                })
+            })
+        })
+    }
+
+    /// Draw a closed polygon with the current colors, brush size, and shadow
+    pub fn draw_polygon(&mut self, points: &[(usize, usize)]) {
+        let (antialias, blend) = (self.antialias, self.blend);
+        let (style, brush) = (self.stroke_style, self.brush_shape);
+        with_blend_mode(blend, || {
+            with_antialiasing(antialias, || {
+                with_stroke_style(style, || {
+                    with_brush_shape(brush, || {
+                        with_dimensions(self.canvas.dimensions(), || {
+                            self.draw_polygon_shadow(points);
+                            let (edge, fill) = (self.edge_color, self.fill_color);
+                            self.display_list
+                                .record_polygon(points, edge, fill, self.brush_size);
+                            let fill = match self.gradient {
+                                Some(gradient) if fill.is_some() => {
+                                    fill_polygon(&mut self.canvas, points, gradient);
+                                    None
+                                }
+                                _ => fill,
+                            };
+                            draw_polygon_with_fill(
+                                &mut self.canvas,
+                                points,
+                                edge,
+                                fill,
+                                self.brush_size,
+                            );
+                        })
+                    })
+                })
+            })
+        })
+    }
+
+    /// Fill the shape `cmd` draws with the current gradient, if there is one
+    /// and a fill; true if it did, so the flat fill is left out
+    fn draw_gradient_fill(&mut self, cmd: &Command) -> bool {
+        let Some(gradient) = self.gradient.filter(|_| self.fill_color.is_some()) else {
+            return false;
+        };
+        let canvas = &mut self.canvas;
+        if let Some((tool, x1, y1, x2, y2)) = cmd.shape_bounds() {
+            fill_shape(canvas, tool, x1, y1, x2, y2, gradient);
+        } else if let Some(points) = cmd.polygon_vertices() {
+            fill_polygon(canvas, &points, gradient);
+        } else if let Some(arc) = cmd.arc_shape() {
+            fill_polygon(canvas, &arc.outline(), gradient);
+        } else if let Some(rect) = cmd.rounded_rect() {
+            fill_rounded_rect(canvas, &rect, gradient);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Set the fill color (None = no fill), as the toolbar does, dropping
+    /// any gradient
+    pub fn set_fill(&mut self, color: Option<u32>) {
+        self.fill_color = color;
+        self.gradient = None;
+    }
+
+    /// Draw the current shadow (if any) of a shape about to be drawn
+    fn draw_shape_shadow(&mut self, tool: ToolMode, x1: usize, y1: usize, x2: usize, y2: usize) {
+        self.cast_shadow(|mask, edge, fill, brush_size| {
+            draw_shape_with_fill(mask, tool, x1, y1, x2, y2, edge, fill, brush_size);
+        });
+    }
+
+    /// Draw the current shadow (if any) of a polygon about to be drawn
+    fn draw_polygon_shadow(&mut self, points: &[(usize, usize)]) {
+        self.cast_shadow(|mask, edge, fill, brush_size| {
+            draw_polygon_with_fill(mask, points, edge, fill, brush_size);
+        });
+    }
+
+    /// Draw the current shadow (if any) of an arc or pie slice about to be drawn
+    fn draw_arc_shadow(&mut self, arc: &ArcShape) {
+        self.cast_shadow(|mask, edge, fill, brush_size| {
+            draw_arc_with_fill(mask, arc, edge, fill, brush_size);
+        });
+    }
+
+    /// Draw the current shadow (if any) of a rounded rectangle about to be drawn
+    fn draw_rounded_rect_shadow(&mut self, rect: &RoundedRect) {
+        self.cast_shadow(|mask, edge, fill, brush_size| {
+            draw_rounded_rect_with_fill(mask, rect, edge, fill, brush_size);
+        });
+    }
+
+    /// Draw the current shadow (if any) of an arrow about to be drawn
+    fn draw_arrow_shadow(&mut self, arrow: &Arrow) {
+        self.cast_shadow(|mask, edge, _, brush_size| {
+            if let Some(edge) = edge {
+                draw_arrow(mask, arrow, edge, brush_size);
+            }
+        });
+    }
+
+    /// Draw the current shadow (if any) of a Bezier curve about to be drawn
+    fn draw_bezier_shadow(&mut self, controls: &[(usize, usize)]) {
+        self.cast_shadow(|mask, edge, _, brush_size| {
+            if let Some(edge) = edge {
+                draw_bezier(mask, controls, edge, brush_size);
+            }
+        });
+    }
+
+    /// Draw the current shadow (if any) of whatever `draw` draws, given the
+    /// edge and fill colors and brush size it should draw with
+    fn cast_shadow(&mut self, draw: impl FnOnce(&mut [u32], Option<u32>, Option<u32>, usize)) {
+        let Some(shadow) = self.shadow else {
+            return;
+        };
+        let (edge, fill) = (self.edge_color, self.fill_color);
+        let brush_size = self.brush_size;
+        draw_shadow(&mut self.canvas, &shadow, |mask| {
+            // Any non-zero color marks coverage in the mask
+            draw(mask, edge.map(|_| 1), fill.map(|_| 1), brush_size);
+        });
+    }
+
+    /// Crop or resize the canvas, keeping the panes if they still fit
+    ///
+    /// Pane scales, time series, and held views start over, as with a new
+    /// `panes` layout. Objects, the display list, and the stroke log keep
+    /// their window coordinates.
+    fn resize(&mut self, change: &CanvasSize) -> Result<(), String> {
+        let (dims, offset) = change.layout(self.canvas.dimensions())?;
+        with_dimensions(dims, || self.panes.check())?;
+        self.canvas = resized_canvas(&self.canvas, dims, offset);
+        self.scales.clear();
+        self.series.clear();
+        self.held.clear();
+        Ok(())
+    }
+
+    /// Whether anything (held panes, objects, or a dialog) is drawn over the
+    /// canvas
+    pub fn has_overlays(&self) -> bool {
+        !self.held.is_empty() || !self.objects.is_empty() || self.dialog.is_some()
+    }
+
+    /// The canvas as viewers see it, with held panes as last flipped and the
+    /// retained objects and any dialog drawn over it (the canvas itself when
+    /// there are none)
+    pub fn render(&self) -> Cow<'_, Canvas> {
+        if !self.has_overlays() {
+            return Cow::Borrowed(&self.canvas);
+        }
+        let mut frame = self.canvas.clone();
+        self.draw_overlays(&mut frame);
+        Cow::Owned(frame)
+    }
+
+    /// Copy the canvas into a window-sized `frame` and draw the held panes,
+    /// objects, and any dialog over it
+    pub fn render_into(&self, frame: &mut [u32]) {
+        frame.copy_from_slice(&self.canvas);
+        self.draw_overlays(frame);
+    }
+
+    fn draw_overlays(&self, frame: &mut [u32]) {
+        let width = self.canvas.width();
+        for held in self.held.values() {
+            held.draw(frame, width);
+        }
+        with_dimensions(self.canvas.dimensions(), || {
+            self.objects.draw(frame, &self.vars);
+            if let Some(dialog) = &self.dialog {
+                dialog.draw(frame);
+            }
+        });
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Points drawn with the current edge color and brush size
+fn attributed(points: &[(usize, usize)]) -> Vec<AttributedPoint> {
+    points
+        .iter()
+        .map(|&(x, y)| AttributedPoint {
+            x,
+            y,
+            color: None,
+            size: None,
+        })
+        .collect()
+}
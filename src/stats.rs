@@ -0,0 +1,136 @@
+//! Session statistics and the stats overlay.
+//!
+//! This module handles:
+//! - `SessionStats`, a snapshot of live counters (pixels, commands, clients, FPS, memory)
+//! - `FpsCounter`, measuring the loop rate over one-second windows
+//! - Drawing the stats as an overlay in the top-right corner of the canvas
+//!
+//! The overlay is drawn onto the presented frame, never into the canvas
+//! itself, so it does not show up in snapshots or undo history.
+
+use std::time::{Duration, Instant};
+
+use crate::font::{draw_text, text_width, GLYPH_HEIGHT};
+use crate::{window_height, window_width, BLACK, CANVAS_TOP, DARK_GRAY};
+
+/// Background of the overlay box
+pub const STATS_BACKGROUND: u32 = 0xF4F4F4;
+
+const PADDING: usize = 4;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 3;
+
+/// Live counters shown by the overlay and the `stats` command
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionStats {
+    /// Canvas pixels changed by recorded operations this session
+    pub pixels_drawn: u64,
+    /// Commands executed from stdin and the socket
+    pub commands: u64,
+    /// Currently connected socket clients
+    pub clients: usize,
+    /// Main loop frames per second
+    pub fps: f32,
+    /// Bytes held by the canvas and undo history
+    pub memory_bytes: usize,
+}
+
+impl SessionStats {
+    /// One line of text per statistic, as drawn in the overlay
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("PIXELS {}", self.pixels_drawn),
+            format!("COMMANDS {}", self.commands),
+            format!("CLIENTS {}", self.clients),
+            format!("FPS {:.1}", self.fps),
+            format!("MEM {} KB", self.memory_bytes / 1024),
+        ]
+    }
+
+    /// Single-line summary returned by the `stats` command
+    pub fn summary(&self) -> String {
+        format!(
+            "pixels:{} commands:{} clients:{} fps:{:.1} mem:{}KB",
+            self.pixels_drawn,
+            self.commands,
+            self.clients,
+            self.fps,
+            self.memory_bytes / 1024
+        )
+    }
+}
+
+/// Measures frames per second over consecutive one-second windows
+#[derive(Debug, Clone)]
+pub struct FpsCounter {
+    window_start: Option<Instant>,
+    frames: u32,
+    fps: f32,
+}
+
+impl FpsCounter {
+    pub fn new() -> Self {
+        FpsCounter {
+            window_start: None,
+            frames: 0,
+            fps: 0.0,
+        }
+    }
+
+    /// Count a frame at `now`, returning the latest completed measurement
+    pub fn tick(&mut self, now: Instant) -> f32 {
+        let start = *self.window_start.get_or_insert(now);
+        self.frames += 1;
+        let elapsed = now.duration_since(start);
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = self.frames as f32 / elapsed.as_secs_f32();
+            self.frames = 0;
+            self.window_start = Some(now);
+        }
+        self.fps
+    }
+
+    /// The latest completed measurement
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+}
+
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bounds of the overlay box as (x, y, width, height)
+pub fn stats_overlay_bounds(stats: &SessionStats) -> (usize, usize, usize, usize) {
+    let lines = stats.lines();
+    let text = lines.iter().map(|l| text_width(l)).max().unwrap_or(0);
+    let width = text + PADDING * 2;
+    let height = lines.len() * LINE_HEIGHT - 3 + PADDING * 2;
+    (
+        window_width() - width - PADDING,
+        CANVAS_TOP + PADDING,
+        width,
+        height,
+    )
+}
+
+/// Draw the stats overlay into a frame buffer
+pub fn draw_stats_overlay(buffer: &mut [u32], stats: &SessionStats) {
+    let (bx, by, width, height) = stats_overlay_bounds(stats);
+    for y in by..(by + height).min(window_height()) {
+        for x in bx..(bx + width).min(window_width()) {
+            let edge = y == by || y == by + height - 1 || x == bx || x == bx + width - 1;
+            buffer[y * window_width() + x] = if edge { DARK_GRAY } else { STATS_BACKGROUND };
+        }
+    }
+    for (i, line) in stats.lines().iter().enumerate() {
+        draw_text(
+            buffer,
+            bx + PADDING,
+            by + PADDING + i * LINE_HEIGHT,
+            line,
+            BLACK,
+        );
+    }
+}
@@ -0,0 +1,544 @@
+//! Freehand stroke log, export as simplified point lists, and playback.
+//!
+//! This module handles:
+//! - `StrokeLog`, a record of every freehand brush stroke drawn with the
+//!   mouse: its points (with the time each was reached), color, and brush size
+//! - `simplify`, Douglas-Peucker reduction of a stroke's points
+//! - Writing the log as JSON (`export strokes <path>`), and reading it back
+//! - `StrokePlayback`, drawing read strokes point by point as their (scaled)
+//!   times come due (`playstrokes <path> [speed]`)
+//!
+//! Strokes are the raw mouse positions of a drag, which are dense and jittery;
+//! exporting keeps only the points needed to stay within `SIMPLIFY_EPSILON`
+//! of the original path, so handwriting and sketches can be fed to other
+//! tools (or drawn back with `polyline`). Each point keeps its time, so
+//! playback shows the strokes being drawn as they were; points without one
+//! (from other tools) are paced at `UNTIMED_PACE`. Like the display list,
+//! `clear` empties the log and undo leaves it alone; strokes that shape snap
+//! turned into shapes are recorded as those shapes instead.
+
+use std::fmt::Write as _;
+use std::time::Instant;
+
+use crate::blend::hex_color;
+use crate::colors::parse_color_value;
+use crate::drawing::{draw_brush_dot, draw_brush_line};
+use crate::record::{check_replay_speed, MAX_REPLAY_SPEED, MIN_REPLAY_SPEED};
+use crate::{canvas_bottom, window_width, CANVAS_TOP, MAX_BRUSH_SIZE, MIN_BRUSH_SIZE};
+
+/// Most strokes kept; the oldest are dropped beyond this
+pub const MAX_STROKES: usize = 10_000;
+/// How far, in pixels, a simplified stroke may stray from the points drawn
+pub const SIMPLIFY_EPSILON: f64 = 1.0;
+/// Pixels per millisecond points without a time are drawn at
+pub const UNTIMED_PACE: f64 = 0.5;
+/// Longest pause between strokes in playback, in milliseconds (at speed 1);
+/// longer ones are shortened to this
+pub const MAX_STROKE_GAP_MS: u64 = 1000;
+
+/// One freehand stroke, in window coordinates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stroke {
+    /// Mouse positions, in the order they were drawn
+    pub points: Vec<(usize, usize)>,
+    /// When each point was reached, in milliseconds (from any fixed start)
+    pub times: Vec<u64>,
+    pub color: u32,
+    pub brush_size: usize,
+}
+
+/// Distance from `p` to the line through `a` and `b` (or to `a`, if they
+/// are the same point)
+fn line_distance(p: (usize, usize), a: (usize, usize), b: (usize, usize)) -> f64 {
+    let (px, py) = (p.0 as f64, p.1 as f64);
+    let (ax, ay) = (a.0 as f64, a.1 as f64);
+    let (dx, dy) = (b.0 as f64 - ax, b.1 as f64 - ay);
+    let len = dx.hypot(dy);
+    if len == 0.0 {
+        return (px - ax).hypot(py - ay);
+    }
+    ((px - ax) * dy - (py - ay) * dx).abs() / len
+}
+
+/// The points of `points` that Douglas-Peucker keeps at `epsilon` pixels:
+/// the ends, and recursively the point farthest from the chord between kept
+/// points while it is more than `epsilon` away
+pub fn simplify(points: &[(usize, usize)], epsilon: f64) -> Vec<(usize, usize)> {
+    points
+        .iter()
+        .zip(simplify_mask(points, epsilon))
+        .filter_map(|(&point, keep)| keep.then_some(point))
+        .collect()
+}
+
+/// Which of `points` `simplify` keeps
+fn simplify_mask(points: &[(usize, usize)], epsilon: f64) -> Vec<bool> {
+    if points.len() < 3 {
+        return vec![true; points.len()];
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    // Spans still to check, by index of their ends (a stack, not recursion,
+    // so long strokes can't overflow)
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, line_distance(points[i], points[first], points[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest {
+            if distance > epsilon {
+                keep[i] = true;
+                spans.push((first, i));
+                spans.push((i, last));
+            }
+        }
+    }
+    keep
+}
+
+/// Freehand strokes drawn so far, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrokeLog {
+    strokes: Vec<Stroke>,
+}
+
+impl StrokeLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a stroke (one without points, or without a time for each, is
+    /// ignored)
+    pub fn push(&mut self, stroke: Stroke) {
+        if stroke.points.is_empty() || stroke.times.len() != stroke.points.len() {
+            return;
+        }
+        if self.strokes.len() == MAX_STROKES {
+            self.strokes.remove(0);
+        }
+        self.strokes.push(stroke);
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.strokes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+    }
+
+    /// All recorded strokes, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Stroke> {
+        self.strokes.iter()
+    }
+
+    /// The strokes as a JSON document, each simplified at `epsilon` pixels
+    ///
+    /// Points are `[x, y, ms]`: window coordinates, as commands take them, and
+    /// milliseconds since the first stroke began. The canvas they were drawn
+    /// on spans `top` to `top + height`.
+    pub fn to_json(&self, epsilon: f64) -> String {
+        let start = self.strokes.first().map_or(0, |s| s.times[0]);
+        let (width, top) = (window_width(), CANVAS_TOP);
+        let height = canvas_bottom() - top;
+        let mut json = format!(
+            "{{\"width\":{},\"height\":{},\"top\":{},\"strokes\":[",
+            width, height, top
+        );
+        for (i, stroke) in self.strokes.iter().enumerate() {
+            let points = stroke
+                .points
+                .iter()
+                .zip(&stroke.times)
+                .zip(simplify_mask(&stroke.points, epsilon))
+                .filter(|&(_, keep)| keep)
+                .map(|(((x, y), ms), _)| format!("[{},{},{}]", x, y, ms.saturating_sub(start)))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = write!(
+                json,
+                "{}\n{{\"color\":\"{}\",\"size\":{},\"points\":[{}]}}",
+                if i == 0 { "" } else { "," },
+                hex_color(stroke.color),
+                stroke.brush_size,
+                points
+            );
+        }
+        json.push_str("\n]}\n");
+        json
+    }
+}
+
+/// Write the stroke log to `path` as JSON, simplified at `SIMPLIFY_EPSILON`
+pub fn save_strokes(log: &StrokeLog, path: &str) -> Result<(), String> {
+    std::fs::write(path, log.to_json(SIMPLIFY_EPSILON))
+        .map_err(|e| format!("cannot write {}: {}", path, e))
+}
+
+/// A parsed JSON value (as much of JSON as stroke files use)
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// The value of `key`, if this is an object holding it
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Recursive-descent reader of one JSON document
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self) -> String {
+        format!("invalid JSON at byte {}", self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `byte` (after any whitespace), or fail
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) != Some(&byte) {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Consume `byte` (after any whitespace) if it is next
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.bytes.get(self.pos) == Some(&byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        fields.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Object(fields))
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Array(items))
+            }
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => {
+                for (word, value) in [
+                    ("true", Json::Bool(true)),
+                    ("false", Json::Bool(false)),
+                    ("null", Json::Null),
+                ] {
+                    if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error())
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.bytes.get(self.pos) != Some(&b'"') {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let rest = std::str::from_utf8(&self.bytes[self.pos..]).map_err(|_| self.error())?;
+            let mut chars = rest.chars();
+            let c = chars.next().ok_or_else(|| self.error())?;
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escaped = match self.bytes.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'u') => {
+                            let hex = rest.get(2..6).ok_or_else(|| self.error())?;
+                            let code = u32::from_str_radix(hex, 16).map_err(|_| self.error())?;
+                            self.pos += 4;
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error()),
+                    };
+                    self.pos += 1;
+                    s.push(escaped);
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| {
+                self.pos = start;
+                self.error()
+            })
+    }
+}
+
+/// Parse a whole JSON document
+fn parse_json(text: &str) -> Result<Json, String> {
+    let mut parser = JsonParser {
+        bytes: text.as_bytes(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != text.len() {
+        return Err(parser.error());
+    }
+    Ok(value)
+}
+
+/// A JSON number as a coordinate or time (a whole number, at least 0)
+fn whole_number(value: &Json) -> Option<u64> {
+    match value {
+        Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => {
+            Some(*n as u64)
+        }
+        _ => None,
+    }
+}
+
+/// One stroke of a stroke file
+///
+/// Points are `[x, y]` or `[x, y, ms]`; a point without a time is reached
+/// `UNTIMED_PACE` after the one before it (the first, `MAX_STROKE_GAP_MS`
+/// after `previous_end`). `color` (default black) and `size` (default 1) are
+/// optional.
+fn parse_stroke(value: &Json, previous_end: Option<u64>) -> Result<Stroke, String> {
+    let points = match value.get("points") {
+        Some(Json::Array(points)) => points,
+        _ => return Err("expected \"points\": [[x, y, ms], ...]".to_string()),
+    };
+    let color = match value.get("color") {
+        None => crate::BLACK,
+        Some(Json::Str(s)) => parse_color_value(s).ok_or(format!("unknown color '{}'", s))?,
+        Some(_) => return Err("\"color\" should be a string".to_string()),
+    };
+    let brush_size = match value.get("size") {
+        None => MIN_BRUSH_SIZE,
+        Some(size) => whole_number(size)
+            .map(|size| (size as usize).clamp(MIN_BRUSH_SIZE, MAX_BRUSH_SIZE))
+            .ok_or("\"size\" should be a whole number")?,
+    };
+    let mut stroke = Stroke {
+        points: Vec::new(),
+        times: Vec::new(),
+        color,
+        brush_size,
+    };
+    for (i, point) in points.iter().enumerate() {
+        let numbers = match point {
+            Json::Array(numbers) if matches!(numbers.len(), 2 | 3) => {
+                numbers.iter().map(whole_number).collect::<Option<Vec<_>>>()
+            }
+            _ => None,
+        }
+        .ok_or(format!("point {}: expected [x, y] or [x, y, ms]", i + 1))?;
+        let (x, y) = (numbers[0] as usize, numbers[1] as usize);
+        let previous = stroke.points.last().zip(stroke.times.last());
+        let time = match (numbers.get(2), previous) {
+            (Some(&ms), _) => ms,
+            (None, Some((&(px, py), &pt))) => {
+                let distance = (x as f64 - px as f64).hypot(y as f64 - py as f64);
+                pt + (distance / UNTIMED_PACE).round() as u64
+            }
+            (None, None) => previous_end.map_or(0, |end| end + MAX_STROKE_GAP_MS),
+        };
+        // Time never runs backwards within a stroke
+        let time = stroke.times.last().map_or(time, |&pt| time.max(pt));
+        stroke.points.push((x, y));
+        stroke.times.push(time);
+    }
+    Ok(stroke)
+}
+
+/// Read the strokes of a JSON stroke file (as `export strokes` writes),
+/// skipping any without points
+pub fn read_strokes(path: &str) -> Result<Vec<Stroke>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let json = parse_json(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let Some(Json::Array(items)) = json.get("strokes") else {
+        return Err(format!("{}: expected {{\"strokes\": [...]}}", path));
+    };
+    let mut strokes: Vec<Stroke> = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let previous_end = strokes.last().and_then(|s| s.times.last().copied());
+        let stroke = parse_stroke(item, previous_end)
+            .map_err(|e| format!("{}: stroke {}: {}", path, i + 1, e))?;
+        if !stroke.points.is_empty() {
+            strokes.push(stroke);
+        }
+    }
+    Ok(strokes)
+}
+
+/// Strokes being drawn point by point, as their (scaled) times come due
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokePlayback {
+    path: String,
+    strokes: Vec<Stroke>,
+    /// Next point to draw, by stroke and point index
+    next: (usize, usize),
+    started: Instant,
+    speed: f64,
+}
+
+impl StrokePlayback {
+    /// Load the stroke file at `path` to play from `now` at `speed` times
+    /// its recorded pace (which must be in range)
+    pub fn start(path: &str, speed: f64, now: Instant) -> Result<Self, String> {
+        check_replay_speed("playstrokes", speed)?;
+        Ok(Self::new(path, read_strokes(path)?, speed, now))
+    }
+
+    /// Play `strokes` from `now` at `speed` times their recorded pace
+    ///
+    /// Times are made relative to the first point, and pauses between strokes
+    /// longer than `MAX_STROKE_GAP_MS` are shortened to it.
+    pub fn new(path: &str, mut strokes: Vec<Stroke>, speed: f64, now: Instant) -> Self {
+        let mut shift = strokes.first().map_or(0, |s| s.times[0]);
+        let mut previous_end: Option<u64> = None;
+        for stroke in &mut strokes {
+            if let Some(end) = previous_end {
+                let gap = stroke.times[0].saturating_sub(end + shift);
+                shift += gap.saturating_sub(MAX_STROKE_GAP_MS);
+            }
+            for time in &mut stroke.times {
+                *time = time.saturating_sub(shift);
+            }
+            previous_end = stroke.times.last().copied();
+        }
+        StrokePlayback {
+            path: path.to_string(),
+            strokes,
+            next: (0, 0),
+            started: now,
+            speed: speed.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Number of strokes
+    pub fn len(&self) -> usize {
+        self.strokes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+    }
+
+    /// Whether every point has been drawn
+    pub fn is_finished(&self) -> bool {
+        self.next.0 == self.strokes.len()
+    }
+
+    /// Draw onto `canvas` the points whose time has come by `now`, each
+    /// joined to the one before it in its stroke; returns whether anything
+    /// was drawn
+    pub fn advance(&mut self, canvas: &mut [u32], now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.started).as_secs_f64() * 1000.0;
+        let mut drew = false;
+        while let Some(stroke) = self.strokes.get(self.next.0) {
+            let i = self.next.1;
+            if stroke.times[i] as f64 / self.speed > elapsed {
+                break;
+            }
+            let (x, y) = stroke.points[i];
+            match i.checked_sub(1).map(|p| stroke.points[p]) {
+                Some((px, py)) => {
+                    draw_brush_line(canvas, px, py, x, y, stroke.color, stroke.brush_size)
+                }
+                None => draw_brush_dot(canvas, x, y, stroke.brush_size, stroke.color),
+            }
+            drew = true;
+            self.next = if i + 1 == stroke.points.len() {
+                (self.next.0 + 1, 0)
+            } else {
+                (self.next.0, i + 1)
+            };
+        }
+        drew
+    }
+}
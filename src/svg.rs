@@ -0,0 +1,489 @@
+//! Display list of drawn shapes, and SVG export.
+//!
+//! This module handles:
+//! - `DisplayList`, a record of every shape drawn (by command or with the mouse)
+//!   with the colors and brush size it was drawn with
+//! - Turning a shape tool's drag bounds into the geometry the pixel code draws
+//! - Writing the display list as an SVG document (`export svg <path>`)
+//!
+//! The pixel buffer stays the source of truth for what is on screen; the
+//! display list is kept alongside it so shapes can be exported at any
+//! resolution. Freehand strokes, dots, bucket fills, and pasted images have no
+//! vector form and are not recorded. `clear` empties the list; undo and redo
+//! only restore pixels, so undone shapes stay in it.
+
+use std::fmt::Write as _;
+
+use crate::blend::{alpha, is_opaque};
+use crate::command::AttributedPoint;
+use crate::drawing::{default_arrow_head, ArcShape, Arrow, RoundedRect, DRAG_CORNER_RADIUS};
+use crate::{canvas_bottom, window_width, ToolMode, CANVAS_TOP, WHITE};
+
+/// Most shapes kept; the oldest are dropped beyond this
+pub const MAX_DISPLAY_ITEMS: usize = 100_000;
+
+/// Geometry of one recorded shape, in window coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Line {
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+    },
+    /// Rectangles and squares, by top-left corner and size
+    Rect {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// Circles and ovals, by center and radii
+    Ellipse { cx: f64, cy: f64, rx: f64, ry: f64 },
+    /// Closed shapes: triangles and polygons
+    Polygon(Vec<(usize, usize)>),
+    /// Open connected segments
+    Polyline(Vec<(usize, usize)>),
+    /// Arcs and pie slices
+    Arc(ArcShape),
+    /// Quadratic (three control points) or cubic (four) Bezier curves
+    Bezier(Vec<(usize, usize)>),
+    /// Rectangles with rounded corners
+    RoundedRect(RoundedRect),
+}
+
+impl Shape {
+    /// The shape a tool draws when dragged from (x1, y1) to (x2, y2), as in
+    /// `draw_shape_with_fill` (None for tools that don't draw shapes)
+    pub fn from_drag(tool: ToolMode, x1: usize, y1: usize, x2: usize, y2: usize) -> Option<Self> {
+        let (left, right) = (x1.min(x2), x1.max(x2));
+        let (top, bottom) = (y1.min(y2), y1.max(y2));
+        let (width, height) = (right - left, bottom - top);
+        Some(match tool {
+            ToolMode::Line => Shape::Line { x1, y1, x2, y2 },
+            ToolMode::Rectangle => Shape::Rect {
+                x: left,
+                y: top,
+                width,
+                height,
+            },
+            ToolMode::RoundedRect => {
+                Shape::RoundedRect(RoundedRect::new(x1, y1, x2, y2, DRAG_CORNER_RADIUS))
+            }
+            // Squares and circles use the largest square that fits the drag
+            ToolMode::Square => Shape::Rect {
+                x: left,
+                y: top,
+                width: width.min(height),
+                height: width.min(height),
+            },
+            ToolMode::Circle => {
+                let radius = width.min(height) as f64 / 2.0;
+                Shape::Ellipse {
+                    cx: left as f64 + radius,
+                    cy: top as f64 + radius,
+                    rx: radius,
+                    ry: radius,
+                }
+            }
+            ToolMode::Oval => Shape::Ellipse {
+                cx: left as f64 + width as f64 / 2.0,
+                cy: top as f64 + height as f64 / 2.0,
+                rx: width as f64 / 2.0,
+                ry: height as f64 / 2.0,
+            },
+            // Dragging upward points the triangle up
+            ToolMode::Triangle => {
+                let mid_x = left + width / 2;
+                if y2 < y1 {
+                    Shape::Polygon(vec![(mid_x, top), (right, bottom), (left, bottom)])
+                } else {
+                    Shape::Polygon(vec![(left, top), (right, top), (mid_x, bottom)])
+                }
+            }
+            // Arrows are recorded as several shapes (see `record_arrow`)
+            ToolMode::Brush
+            | ToolMode::Arrow
+            | ToolMode::Bucket
+            | ToolMode::Polygon
+            | ToolMode::Bezier
+            | ToolMode::Select
+            | ToolMode::Eyedropper => return None,
+        })
+    }
+}
+
+/// A recorded shape and how it was drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct Primitive {
+    pub shape: Shape,
+    /// Edge color (None = transparent)
+    pub edge: Option<u32>,
+    /// Fill color (None = no fill)
+    pub fill: Option<u32>,
+    pub brush_size: usize,
+}
+
+impl Primitive {
+    /// Width of the stroke the brush paints: a brush of size `n` stamps
+    /// circles of radius `n - 1`
+    pub fn stroke_width(&self) -> usize {
+        if self.brush_size <= 1 {
+            1
+        } else {
+            self.brush_size.saturating_mul(2) - 1
+        }
+    }
+}
+
+/// Shapes drawn so far, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayList {
+    items: Vec<Primitive>,
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a shape, unless it was drawn with neither edge nor fill
+    pub fn push(&mut self, primitive: Primitive) {
+        if primitive.edge.is_none() && primitive.fill.is_none() {
+            return;
+        }
+        if self.items.len() == MAX_DISPLAY_ITEMS {
+            self.items.remove(0);
+        }
+        self.items.push(primitive);
+    }
+
+    /// Record a shape tool's drag (see `Shape::from_drag`)
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_drag(
+        &mut self,
+        tool: ToolMode,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        edge: Option<u32>,
+        fill: Option<u32>,
+        brush_size: usize,
+    ) {
+        if tool == ToolMode::Arrow {
+            let arrow = Arrow {
+                x1,
+                y1,
+                x2,
+                y2,
+                head: default_arrow_head(brush_size),
+                both: false,
+            };
+            self.record_arrow(&arrow, edge, brush_size);
+        } else if let Some(shape) = Shape::from_drag(tool, x1, y1, x2, y2) {
+            // Lines are never filled
+            let fill = fill.filter(|_| tool != ToolMode::Line);
+            self.push(Primitive {
+                shape,
+                edge,
+                fill,
+                brush_size,
+            });
+        }
+    }
+
+    /// Record a closed polygon
+    pub fn record_polygon(
+        &mut self,
+        points: &[(usize, usize)],
+        edge: Option<u32>,
+        fill: Option<u32>,
+        brush_size: usize,
+    ) {
+        self.push(Primitive {
+            shape: Shape::Polygon(points.to_vec()),
+            edge,
+            fill,
+            brush_size,
+        });
+    }
+
+    /// Record an arc or pie slice
+    pub fn record_arc(
+        &mut self,
+        arc: ArcShape,
+        edge: Option<u32>,
+        fill: Option<u32>,
+        brush_size: usize,
+    ) {
+        self.push(Primitive {
+            shape: Shape::Arc(arc),
+            edge,
+            fill,
+            brush_size,
+        });
+    }
+
+    /// Record a rounded rectangle
+    pub fn record_rounded_rect(
+        &mut self,
+        rect: RoundedRect,
+        edge: Option<u32>,
+        fill: Option<u32>,
+        brush_size: usize,
+    ) {
+        self.push(Primitive {
+            shape: Shape::RoundedRect(rect),
+            edge,
+            fill,
+            brush_size,
+        });
+    }
+
+    /// Record an arrow as its shaft (a line) and heads (polygons filled with
+    /// the edge color); arrows without an edge color draw nothing
+    pub fn record_arrow(&mut self, arrow: &Arrow, edge: Option<u32>, brush_size: usize) {
+        if edge.is_none() {
+            return;
+        }
+        let ((x1, y1), (x2, y2)) = arrow.shaft();
+        self.push(Primitive {
+            shape: Shape::Line { x1, y1, x2, y2 },
+            edge,
+            fill: None,
+            brush_size,
+        });
+        for head in arrow.heads() {
+            self.push(Primitive {
+                shape: Shape::Polygon(head.to_vec()),
+                edge,
+                fill: edge,
+                brush_size: 1,
+            });
+        }
+    }
+
+    /// Record a Bezier curve, which is never filled
+    pub fn record_bezier(
+        &mut self,
+        controls: &[(usize, usize)],
+        edge: Option<u32>,
+        brush_size: usize,
+    ) {
+        self.push(Primitive {
+            shape: Shape::Bezier(controls.to_vec()),
+            edge,
+            fill: None,
+            brush_size,
+        });
+    }
+
+    /// Record a polyline; one with per-point colors or sizes is recorded as
+    /// separate lines, each drawn with its end point's attributes
+    pub fn record_polyline(
+        &mut self,
+        points: &[AttributedPoint],
+        edge: Option<u32>,
+        brush_size: usize,
+    ) {
+        if points.iter().all(|p| p.color.is_none() && p.size.is_none()) {
+            self.push(Primitive {
+                shape: Shape::Polyline(points.iter().map(|p| (p.x, p.y)).collect()),
+                edge,
+                fill: None,
+                brush_size,
+            });
+            return;
+        }
+        for pair in points.windows(2) {
+            self.push(Primitive {
+                shape: Shape::Line {
+                    x1: pair[0].x,
+                    y1: pair[0].y,
+                    x2: pair[1].x,
+                    y2: pair[1].y,
+                },
+                edge: pair[1].color.or(edge),
+                fill: None,
+                brush_size: pair[1].size.unwrap_or(brush_size),
+            });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// All recorded shapes, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Primitive> {
+        self.items.iter()
+    }
+
+    /// The shapes as an SVG document the size of the canvas area, in window
+    /// coordinates (the viewBox starts at the top of the canvas)
+    pub fn to_svg(&self) -> String {
+        let (width, top) = (window_width(), CANVAS_TOP);
+        let height = canvas_bottom() - top;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 {top} {w} {h}\">\n",
+            w = width,
+            h = height,
+            top = top
+        );
+        let _ = writeln!(
+            svg,
+            "<rect x=\"0\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            top,
+            width,
+            height,
+            hex(WHITE)
+        );
+        for primitive in &self.items {
+            svg.push_str(&element(primitive));
+            svg.push('\n');
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// A color as SVG writes it (`#RRGGBB`), its alpha going in an opacity
+/// attribute (see `opacity`)
+fn hex(color: u32) -> String {
+    format!("#{:06X}", color & 0xFFFFFF)
+}
+
+/// ` <attribute>="<opacity>"` for a translucent color, or nothing
+fn opacity(attribute: &str, color: u32) -> String {
+    if is_opaque(color) {
+        return String::new();
+    }
+    let opacity = (alpha(color) as f64 / 255.0 * 1000.0).round() / 1000.0;
+    format!(" {}=\"{}\"", attribute, opacity)
+}
+
+/// `x,y x,y ...` for a `points` attribute
+fn point_list(points: &[(usize, usize)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{},{}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A coordinate rounded to hundredths, as path data writes it
+fn coord(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+/// Path data for an arc, closed through the center for a pie slice
+///
+/// SVG can't draw a full turn as one arc (its ends would coincide), so the
+/// curve is written as two halves. An open arc's fill is closed by its chord,
+/// as on the canvas.
+fn arc_path(arc: &ArcShape) -> String {
+    let sweep = arc.sweep();
+    let r = coord(arc.r as f64);
+    let clockwise = u8::from(sweep > 0.0);
+    let (x, y) = arc.point_at(arc.start);
+    let mut d = if arc.pie {
+        format!("M {} {} L {} {}", arc.cx, arc.cy, coord(x), coord(y))
+    } else {
+        format!("M {} {}", coord(x), coord(y))
+    };
+    let halves = if sweep.abs() > 180.0 { 2 } else { 1 };
+    for i in 1..=halves {
+        let (x, y) = arc.point_at(arc.start + sweep * i as f64 / halves as f64);
+        let _ = write!(
+            d,
+            " A {} {} 0 0 {} {} {}",
+            r,
+            r,
+            clockwise,
+            coord(x),
+            coord(y)
+        );
+    }
+    if arc.pie {
+        d.push_str(" Z");
+    }
+    d
+}
+
+/// Path data for a Bezier curve: `M` to the start, then `Q` (quadratic) or
+/// `C` (cubic) through the remaining control points
+fn bezier_path(controls: &[(usize, usize)]) -> String {
+    let Some(((x, y), rest)) = controls.split_first() else {
+        return String::new();
+    };
+    let command = if rest.len() == 2 { 'Q' } else { 'C' };
+    let rest: Vec<String> = rest.iter().map(|(x, y)| format!("{} {}", x, y)).collect();
+    format!("M {} {} {} {}", x, y, command, rest.join(" "))
+}
+
+/// One SVG element for a recorded shape
+fn element(primitive: &Primitive) -> String {
+    let geometry = match &primitive.shape {
+        Shape::Line { x1, y1, x2, y2 } => {
+            format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"",
+                x1, y1, x2, y2
+            )
+        }
+        Shape::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+            x, y, width, height
+        ),
+        Shape::Ellipse { cx, cy, rx, ry } => format!(
+            "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"",
+            cx, cy, rx, ry
+        ),
+        Shape::Polygon(points) => format!("<polygon points=\"{}\"", point_list(points)),
+        Shape::Polyline(points) => format!("<polyline points=\"{}\"", point_list(points)),
+        Shape::Arc(arc) => format!("<path d=\"{}\"", arc_path(arc)),
+        Shape::Bezier(controls) => format!("<path d=\"{}\"", bezier_path(controls)),
+        Shape::RoundedRect(rect) => format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"",
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            rect.r
+        ),
+    };
+    let fill = match primitive.fill {
+        Some(fill) => format!(" fill=\"{}\"{}", hex(fill), opacity("fill-opacity", fill)),
+        None => " fill=\"none\"".to_string(),
+    };
+    let stroke = match primitive.edge {
+        // Brush strokes are stamped circles, so their ends and corners are round
+        Some(edge) => format!(
+            " stroke=\"{}\"{} stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"",
+            hex(edge),
+            opacity("stroke-opacity", edge),
+            primitive.stroke_width()
+        ),
+        None => String::new(),
+    };
+    format!("{}{}{}/>", geometry, fill, stroke)
+}
+
+/// Write the display list to `path` as SVG
+pub fn save_svg(list: &DisplayList, path: &str) -> Result<(), String> {
+    std::fs::write(path, list.to_svg()).map_err(|e| format!("cannot write {}: {}", path, e))
+}
@@ -0,0 +1,283 @@
+//! Several canvases in one window, one shown at a time.
+//!
+//! This module handles:
+//! - `TabCommand`, set out by `canvas new`, `canvas switch N`, and
+//!   `canvas close N`
+//! - `Canvases`: the canvases put away while another is shown, and which one
+//!   is shown (numbered from 1, left to right in the tab strip)
+//! - Which canvas this thread is showing, for default snapshot names:
+//!   `canvas.png` for the first, `canvas-N.png` for the others
+//! - The tab strip drawn in the title bar, and clicks on it
+//!
+//! Each canvas keeps its own pixels, undo history, display list, and stroke
+//! log; tools, the palette, panes, and objects belong to the window and stay
+//! as they are when another canvas is shown. Every canvas has the window's
+//! size, so the size can't change while more than one is open.
+
+use std::cell::Cell;
+use std::fmt;
+
+use crate::canvas::Canvas;
+use crate::font::{draw_text, text_width, GLYPH_HEIGHT};
+use crate::history::History;
+use crate::strokes::StrokeLog;
+use crate::svg::DisplayList;
+use crate::{window_width, BLACK, BUTTON_MARGIN, BUTTON_SIZE, DARK_GRAY, TITLE_BAR_HEIGHT, WHITE};
+
+/// Most canvases open at once
+pub const MAX_CANVASES: usize = 9;
+/// Width of a tab in the title bar's tab strip
+pub const TAB_WIDTH: usize = 20;
+/// Gap between tabs
+const TAB_GAP: usize = 2;
+
+/// An operation on the set of canvases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabCommand {
+    /// Open a blank canvas after the others and show it
+    New,
+    /// Show canvas N
+    Switch(usize),
+    /// Close canvas N, showing its neighbour if it was shown
+    Close(usize),
+}
+
+impl TabCommand {
+    /// Parse the arguments of `canvas new`, `canvas switch N`, or `canvas close N`
+    pub fn parse(args: &[&str]) -> Option<TabCommand> {
+        match *args {
+            ["new"] => Some(TabCommand::New),
+            ["switch", n] => Some(TabCommand::Switch(n.parse().ok()?)),
+            ["close", n] => Some(TabCommand::Close(n.parse().ok()?)),
+            _ => None,
+        }
+    }
+
+    /// Check the canvas number is one that could be open
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            TabCommand::New => Ok(()),
+            TabCommand::Switch(n) | TabCommand::Close(n) => {
+                if (1..=MAX_CANVASES).contains(&n) {
+                    Ok(())
+                } else {
+                    Err(format!("canvas {} is outside 1-{}", n, MAX_CANVASES))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for TabCommand {
+    /// Format as `canvas`'s arguments, e.g. `switch 2`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TabCommand::New => write!(f, "new"),
+            TabCommand::Switch(n) => write!(f, "switch {}", n),
+            TabCommand::Close(n) => write!(f, "close {}", n),
+        }
+    }
+}
+
+/// What belongs to one canvas rather than the window
+#[derive(Debug, Clone)]
+pub struct CanvasTab {
+    pub canvas: Canvas,
+    pub history: History,
+    pub display_list: DisplayList,
+    pub strokes: StrokeLog,
+}
+
+impl CanvasTab {
+    /// A white canvas the size of `like`, with nothing to undo
+    pub fn blank(like: &Canvas) -> Self {
+        let canvas = Canvas::with_size(like.dimensions());
+        CanvasTab {
+            history: History::with_baseline(&canvas),
+            canvas,
+            display_list: DisplayList::default(),
+            strokes: StrokeLog::default(),
+        }
+    }
+}
+
+/// The open canvases: the one shown lives with the session, and the rest are
+/// put away here
+///
+/// Each operation takes the shown canvas and returns the one to show next;
+/// the canvas numbers must be checked first (`check`, `check_open`,
+/// `check_close`).
+#[derive(Debug, Clone)]
+pub struct Canvases {
+    /// One slot per open canvas; the shown canvas's slot is empty
+    tabs: Vec<Option<CanvasTab>>,
+    /// Index of the shown canvas in `tabs`
+    active: usize,
+}
+
+impl Default for Canvases {
+    fn default() -> Self {
+        Canvases {
+            tabs: vec![None],
+            active: 0,
+        }
+    }
+}
+
+impl Canvases {
+    /// Just the one canvas, shown
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of open canvases
+    pub fn count(&self) -> usize {
+        self.tabs.len()
+    }
+
+    /// Whether only the shown canvas is open (there is always one)
+    pub fn is_single(&self) -> bool {
+        self.tabs.len() == 1
+    }
+
+    /// Number of the shown canvas, from 1
+    pub fn active(&self) -> usize {
+        self.active + 1
+    }
+
+    /// Check canvas `n` is open
+    pub fn check(&self, n: usize) -> Result<(), String> {
+        if (1..=self.count()).contains(&n) {
+            Ok(())
+        } else if self.is_single() {
+            Err(format!("no canvas {} (only 1 is open)", n))
+        } else {
+            Err(format!("no canvas {} (1-{} are open)", n, self.count()))
+        }
+    }
+
+    /// Check another canvas can be opened
+    pub fn check_open(&self) -> Result<(), String> {
+        if self.count() < MAX_CANVASES {
+            Ok(())
+        } else {
+            Err(format!("{} canvases are already open", MAX_CANVASES))
+        }
+    }
+
+    /// Check canvas `n` can be closed: it is open, and not the last one
+    pub fn check_close(&self, n: usize) -> Result<(), String> {
+        self.check(n)?;
+        if self.is_single() {
+            return Err("the last canvas can't be closed".to_string());
+        }
+        Ok(())
+    }
+
+    /// Put `shown` away and open `blank` after the others, to show next
+    pub fn open(&mut self, shown: CanvasTab, blank: CanvasTab) -> CanvasTab {
+        self.tabs[self.active] = Some(shown);
+        self.tabs.push(None);
+        self.active = self.tabs.len() - 1;
+        blank
+    }
+
+    /// Put `shown` away and take out canvas `n` to show
+    pub fn switch(&mut self, n: usize, shown: CanvasTab) -> CanvasTab {
+        if n - 1 == self.active {
+            return shown;
+        }
+        self.tabs[self.active] = Some(shown);
+        self.active = n - 1;
+        self.tabs[self.active]
+            .take()
+            .expect("a canvas put away is in its slot")
+    }
+
+    /// Close canvas `n`, returning what to show: `shown` if another canvas
+    /// was closed, or the one after it (the one before, if it was last)
+    pub fn close(&mut self, n: usize, shown: CanvasTab) -> CanvasTab {
+        let index = n - 1;
+        self.tabs.remove(index);
+        if index != self.active {
+            if index < self.active {
+                self.active -= 1;
+            }
+            return shown;
+        }
+        self.active = index.min(self.tabs.len() - 1);
+        self.tabs[self.active]
+            .take()
+            .expect("a canvas put away is in its slot")
+    }
+}
+
+thread_local! {
+    static ACTIVE: Cell<usize> = const { Cell::new(1) };
+}
+
+/// Number of the canvas this thread is showing (1 unless told otherwise)
+pub fn active_canvas() -> usize {
+    ACTIVE.with(Cell::get)
+}
+
+/// Set the number of the canvas this thread is showing
+pub fn set_active_canvas(n: usize) {
+    ACTIVE.with(|active| active.set(n));
+}
+
+/// Where `snapshot` without a path saves: `canvas.png` for the first canvas,
+/// `canvas-N.png` for canvas N
+pub fn default_snapshot_path() -> String {
+    match active_canvas() {
+        1 => crate::SNAPSHOT_PATH.to_string(),
+        n => format!("canvas-{}.png", n),
+    }
+}
+
+/// Left edge of tab `n` (from 1) of `count`, the strip ending left of the
+/// close button
+fn tab_x(n: usize, count: usize) -> usize {
+    let strip = count * (TAB_WIDTH + TAB_GAP);
+    let end = window_width().saturating_sub(BUTTON_SIZE + 2 * BUTTON_MARGIN);
+    end.saturating_sub(strip) + (n - 1) * (TAB_WIDTH + TAB_GAP)
+}
+
+/// Draw a tab for each of `count` canvases, `active` raised in white, in the
+/// title bar (nothing with a single canvas)
+pub fn draw_tab_strip(buffer: &mut [u32], count: usize, active: usize) {
+    if count < 2 {
+        return;
+    }
+    let width = window_width();
+    for n in 1..=count {
+        let left = tab_x(n, count);
+        let (fill, top) = if n == active {
+            (WHITE, BUTTON_MARGIN)
+        } else {
+            (DARK_GRAY, BUTTON_MARGIN + 2)
+        };
+        for y in top..TITLE_BAR_HEIGHT - 1 {
+            for x in left..(left + TAB_WIDTH).min(width) {
+                let edge = x == left || x == left + TAB_WIDTH - 1 || y == top;
+                buffer[y * width + x] = if edge { BLACK } else { fill };
+            }
+        }
+        let label = n.to_string();
+        let x = left + (TAB_WIDTH - text_width(&label)) / 2;
+        let y = top + (TITLE_BAR_HEIGHT - 1 - top - GLYPH_HEIGHT) / 2;
+        let color = if n == active { BLACK } else { WHITE };
+        draw_text(buffer, x, y, &label, color);
+    }
+}
+
+/// The canvas whose tab is at (x, y), with `count` open
+pub fn tab_at(x: usize, y: usize, count: usize) -> Option<usize> {
+    if count < 2 || !(BUTTON_MARGIN..TITLE_BAR_HEIGHT - 1).contains(&y) {
+        return None;
+    }
+    (1..=count).find(|&n| {
+        let left = tab_x(n, count);
+        (left..left + TAB_WIDTH).contains(&x)
+    })
+}
@@ -0,0 +1,211 @@
+//! Whole-canvas transforms: flipping, rotating, and scaling what is drawn.
+//!
+//! This module handles:
+//! - `CanvasTransform`, set out by `canvas flip h|v`, `canvas rotate
+//!   90|180|270`, and `canvas scale WxH [nearest|bilinear]`
+//! - Applying one to the drawable area of a buffer, in place
+//!
+//! The canvas keeps its size, so a quarter turn of a canvas wider than it is
+//! tall turns about the canvas's center: the left and right ends of the
+//! picture are cut off and white fills the strips above and below it.
+//! Scaling puts the whole picture, resized to `WxH`, at the canvas's top-left
+//! corner, with white beyond it and anything larger than the canvas cut off.
+
+use std::fmt;
+
+use crate::canvas::{canvas_bottom, window_width, MAX_DIMENSION};
+use crate::{CANVAS_TOP, WHITE};
+
+/// Which way `canvas flip` mirrors the picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlipAxis {
+    /// Left to right (`h`)
+    Horizontal,
+    /// Top to bottom (`v`)
+    Vertical,
+}
+
+/// How `canvas scale` picks the color of each resized pixel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resample {
+    /// The source pixel nearest its center (the default): hard edges and
+    /// no new colors
+    #[default]
+    Nearest,
+    /// A blend of the four source pixels around its center: smooth, but
+    /// edges soften
+    Bilinear,
+}
+
+/// A change to everything drawn on the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanvasTransform {
+    Flip(FlipAxis),
+    /// A turn clockwise by 90, 180, or 270 degrees
+    Rotate(u16),
+    /// Resize the picture to `width` x `height` pixels
+    Scale {
+        width: usize,
+        height: usize,
+        resample: Resample,
+    },
+}
+
+impl CanvasTransform {
+    /// Parse the arguments of `canvas ...`
+    pub fn parse(args: &[&str]) -> Option<CanvasTransform> {
+        match *args {
+            ["flip", "h"] => Some(CanvasTransform::Flip(FlipAxis::Horizontal)),
+            ["flip", "v"] => Some(CanvasTransform::Flip(FlipAxis::Vertical)),
+            ["rotate", degrees] => degrees.parse().ok().map(CanvasTransform::Rotate),
+            ["scale", size] | ["scale", size, _] => {
+                let (width, height) = size.split_once('x')?;
+                let resample = match args.get(2) {
+                    None | Some(&"nearest") => Resample::Nearest,
+                    Some(&"bilinear") => Resample::Bilinear,
+                    Some(_) => return None,
+                };
+                Some(CanvasTransform::Scale {
+                    width: width.parse().ok()?,
+                    height: height.parse().ok()?,
+                    resample,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Check a rotation is a quarter, half, or three-quarter turn and a
+    /// scale's size is 1 to `MAX_DIMENSION` pixels each way
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            CanvasTransform::Flip(_) => Ok(()),
+            CanvasTransform::Rotate(90 | 180 | 270) => Ok(()),
+            CanvasTransform::Rotate(degrees) => Err(format!(
+                "rotation {} is not 90, 180, or 270 degrees",
+                degrees
+            )),
+            CanvasTransform::Scale { width, height, .. } => {
+                let sides = 1..=MAX_DIMENSION;
+                if sides.contains(&width) && sides.contains(&height) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "scale {}x{} is outside 1x1-{max}x{max}",
+                        width,
+                        height,
+                        max = MAX_DIMENSION
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for CanvasTransform {
+    /// Format as the `canvas` command's arguments, e.g. `rotate 90`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CanvasTransform::Flip(FlipAxis::Horizontal) => write!(f, "flip h"),
+            CanvasTransform::Flip(FlipAxis::Vertical) => write!(f, "flip v"),
+            CanvasTransform::Rotate(degrees) => write!(f, "rotate {}", degrees),
+            CanvasTransform::Scale {
+                width,
+                height,
+                resample,
+            } => {
+                write!(f, "scale {}x{}", width, height)?;
+                match resample {
+                    Resample::Nearest => Ok(()),
+                    Resample::Bilinear => write!(f, " bilinear"),
+                }
+            }
+        }
+    }
+}
+
+/// Apply `transform` to the drawable area of `buffer` (the title bar and
+/// toolbar rows are left alone)
+pub fn transform_canvas(buffer: &mut [u32], transform: &CanvasTransform) {
+    let width = window_width();
+    let area = &mut buffer[CANVAS_TOP * width..canvas_bottom() * width];
+    let height = area.len() / width;
+    let source = area.to_vec();
+    let at = |x: isize, y: isize| {
+        let inside = (0..width as isize).contains(&x) && (0..height as isize).contains(&y);
+        match inside {
+            true => source[y as usize * width + x as usize],
+            false => WHITE,
+        }
+    };
+    // Twice the center's coordinates, so half pixels stay whole numbers
+    let (cx2, cy2) = (width as isize - 1, height as isize - 1);
+    for (y, row) in area.chunks_exact_mut(width).enumerate() {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let (x, y) = (x as isize, y as isize);
+            *pixel = match *transform {
+                CanvasTransform::Flip(FlipAxis::Horizontal) => at(cx2 - x, y),
+                CanvasTransform::Flip(FlipAxis::Vertical) => at(x, cy2 - y),
+                CanvasTransform::Rotate(180) => at(cx2 - x, cy2 - y),
+                // Turned clockwise, each pixel comes from a quarter turn back
+                CanvasTransform::Rotate(90) => at(
+                    (cx2 + 2 * y - cy2).div_euclid(2),
+                    (cy2 - 2 * x + cx2).div_euclid(2),
+                ),
+                CanvasTransform::Rotate(270) => at(
+                    (cx2 - 2 * y + cy2).div_euclid(2),
+                    (cy2 + 2 * x - cx2).div_euclid(2),
+                ),
+                CanvasTransform::Rotate(_) => at(x, y),
+                CanvasTransform::Scale {
+                    width: to_width,
+                    height: to_height,
+                    resample,
+                } => {
+                    let (x, y) = (x as usize, y as usize);
+                    if x >= to_width || y >= to_height {
+                        WHITE
+                    } else {
+                        match resample {
+                            Resample::Nearest => at(
+                                ((2 * x + 1) * width / (2 * to_width)) as isize,
+                                ((2 * y + 1) * height / (2 * to_height)) as isize,
+                            ),
+                            Resample::Bilinear => bilinear(
+                                &source,
+                                width,
+                                height,
+                                (x as f64 + 0.5) * width as f64 / to_width as f64 - 0.5,
+                                (y as f64 + 0.5) * height as f64 / to_height as f64 - 0.5,
+                            ),
+                        }
+                    }
+                }
+            };
+        }
+    }
+}
+
+/// The color at (x, y) between the pixels of a `width` x `height` image,
+/// each byte blended from the four pixels around it (edges held)
+fn bilinear(pixels: &[u32], width: usize, height: usize, x: f64, y: f64) -> u32 {
+    let x = x.clamp(0.0, (width - 1) as f64);
+    let y = y.clamp(0.0, (height - 1) as f64);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let corners = [
+        (pixels[y0 * width + x0], (1.0 - fx) * (1.0 - fy)),
+        (pixels[y0 * width + x1], fx * (1.0 - fy)),
+        (pixels[y1 * width + x0], (1.0 - fx) * fy),
+        (pixels[y1 * width + x1], fx * fy),
+    ];
+    (0..4).fold(0, |color, byte| {
+        let shift = byte * 8;
+        let value: f64 = corners
+            .iter()
+            .map(|&(pixel, weight)| ((pixel >> shift) & 0xFF) as f64 * weight)
+            .sum();
+        color | ((value.round() as u32).min(255) << shift)
+    })
+}
@@ -6,25 +6,51 @@
 //! - Icon rendering for tools
 //! - Hit detection for clickable UI elements
 
+use crate::blend::composite;
+use crate::brush::{brush_shape, spray_offsets, BrushShape};
+use crate::drawing::{bezier_points, RoundedRect};
+use crate::palette::{current_palette, PALETTE_LEN};
 use crate::{
-    ToolMode, BLACK, BUTTON_MARGIN, BUTTON_SIZE, CANVAS_BOTTOM, COLOR_PALETTE, DARK_GRAY, GRAY,
-    HEIGHT, TITLE_BAR_HEIGHT, TOOLBAR_ROW_HEIGHT, WHITE, WIDTH,
+    canvas_bottom, window_height, window_width, ToolMode, BLACK, BUTTON_MARGIN, BUTTON_SIZE,
+    DARK_GRAY, GRAY, TITLE_BAR_HEIGHT, TOOLBAR_ROW_HEIGHT, WHITE,
 };
 
+/// Tool buttons on the toolbar's second row, left to right
+pub const TOOLBAR_TOOLS: [ToolMode; 13] = [
+    ToolMode::Brush,
+    ToolMode::Line,
+    ToolMode::Square,
+    ToolMode::Rectangle,
+    ToolMode::Circle,
+    ToolMode::Oval,
+    ToolMode::Triangle,
+    ToolMode::Bucket,
+    ToolMode::Polygon,
+    ToolMode::Bezier,
+    ToolMode::RoundedRect,
+    ToolMode::Arrow,
+    ToolMode::Select,
+];
+
+/// Left edge of the size display (right of the tool buttons)
+fn size_display_x() -> usize {
+    BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN
+}
+
 /// Draw the title bar with close button
 pub fn draw_title_bar(buffer: &mut [u32]) {
     for y in 0..TITLE_BAR_HEIGHT {
-        for x in 0..WIDTH {
-            buffer[y * WIDTH + x] = GRAY;
+        for x in 0..window_width() {
+            buffer[y * window_width() + x] = GRAY;
         }
     }
 
-    for x in 0..WIDTH {
-        buffer[(TITLE_BAR_HEIGHT - 1) * WIDTH + x] = DARK_GRAY;
+    for x in 0..window_width() {
+        buffer[(TITLE_BAR_HEIGHT - 1) * window_width() + x] = DARK_GRAY;
     }
 
     // Draw close button
-    let close_x = WIDTH - BUTTON_SIZE - BUTTON_MARGIN;
+    let close_x = window_width() - BUTTON_SIZE - BUTTON_MARGIN;
     let close_y = BUTTON_MARGIN;
     draw_button(buffer, close_x, close_y, crate::RED);
     draw_x(buffer, close_x, close_y);
@@ -34,8 +60,8 @@ pub fn draw_title_bar(buffer: &mut [u32]) {
 pub fn draw_button(buffer: &mut [u32], bx: usize, by: usize, color: u32) {
     for y in by..by + BUTTON_SIZE {
         for x in bx..bx + BUTTON_SIZE {
-            if x < WIDTH && y < HEIGHT {
-                buffer[y * WIDTH + x] = color;
+            if x < window_width() && y < window_height() {
+                buffer[y * window_width() + x] = color;
             }
         }
     }
@@ -44,15 +70,15 @@ pub fn draw_button(buffer: &mut [u32], bx: usize, by: usize, color: u32) {
 /// Draw a border around a button
 pub fn draw_button_border(buffer: &mut [u32], bx: usize, by: usize, color: u32) {
     for x in bx..bx + BUTTON_SIZE {
-        if x < WIDTH {
-            buffer[by * WIDTH + x] = color;
-            buffer[(by + BUTTON_SIZE - 1) * WIDTH + x] = color;
+        if x < window_width() {
+            buffer[by * window_width() + x] = color;
+            buffer[(by + BUTTON_SIZE - 1) * window_width() + x] = color;
         }
     }
     for y in by..by + BUTTON_SIZE {
-        if y < HEIGHT {
-            buffer[y * WIDTH + bx] = color;
-            buffer[y * WIDTH + bx + BUTTON_SIZE - 1] = color;
+        if y < window_height() {
+            buffer[y * window_width() + bx] = color;
+            buffer[y * window_width() + bx + BUTTON_SIZE - 1] = color;
         }
     }
 }
@@ -61,15 +87,15 @@ pub fn draw_button_border(buffer: &mut [u32], bx: usize, by: usize, color: u32)
 pub fn draw_button_inner_border(buffer: &mut [u32], bx: usize, by: usize, color: u32) {
     // Draw a border 1 pixel inside the button
     for x in (bx + 1)..(bx + BUTTON_SIZE - 1) {
-        if x < WIDTH {
-            buffer[(by + 1) * WIDTH + x] = color;
-            buffer[(by + BUTTON_SIZE - 2) * WIDTH + x] = color;
+        if x < window_width() {
+            buffer[(by + 1) * window_width() + x] = color;
+            buffer[(by + BUTTON_SIZE - 2) * window_width() + x] = color;
         }
     }
     for y in (by + 1)..(by + BUTTON_SIZE - 1) {
-        if y < HEIGHT {
-            buffer[y * WIDTH + bx + 1] = color;
-            buffer[y * WIDTH + bx + BUTTON_SIZE - 2] = color;
+        if y < window_height() {
+            buffer[y * window_width() + bx + 1] = color;
+            buffer[y * window_width() + bx + BUTTON_SIZE - 2] = color;
         }
     }
 }
@@ -87,9 +113,9 @@ pub fn draw_transparent_button(
         for dx in 0..BUTTON_SIZE {
             let px = bx + dx;
             let py = by + dy;
-            if px < WIDTH && py < HEIGHT {
+            if px < window_width() && py < window_height() {
                 let checker = ((dx / 4) + (dy / 4)) % 2 == 0;
-                buffer[py * WIDTH + px] = if checker { WHITE } else { GRAY };
+                buffer[py * window_width() + px] = if checker { WHITE } else { GRAY };
             }
         }
     }
@@ -109,42 +135,114 @@ pub fn draw_transparent_button(
 
 /// Check if click is on transparent button
 pub fn is_in_transparent_button(x: usize, y: usize) -> bool {
-    let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
+    let row1_y = canvas_bottom() + BUTTON_MARGIN;
     let transparent_x = BUTTON_MARGIN + 14 * (BUTTON_SIZE + BUTTON_MARGIN);
     x >= transparent_x && x < transparent_x + BUTTON_SIZE && y >= row1_y && y < row1_y + BUTTON_SIZE
 }
 
+/// Left edge of the eyedropper button (right of the edge/fill indicator, which
+/// is 28 pixels wide)
+fn eyedropper_x() -> usize {
+    BUTTON_MARGIN + 15 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN + 28 + BUTTON_MARGIN * 2
+}
+
+/// Check if click is on the eyedropper button
+pub fn is_in_eyedropper_button(x: usize, y: usize) -> bool {
+    let row1_y = canvas_bottom() + BUTTON_MARGIN;
+    let eyedropper_x = eyedropper_x();
+    x >= eyedropper_x && x < eyedropper_x + BUTTON_SIZE && y >= row1_y && y < row1_y + BUTTON_SIZE
+}
+
+/// Left edge of the brush shape button (right of the eyedropper)
+fn brush_shape_x() -> usize {
+    eyedropper_x() + BUTTON_SIZE + BUTTON_MARGIN * 2
+}
+
+/// Check if click is on the brush shape button
+pub fn is_in_brush_shape_button(x: usize, y: usize) -> bool {
+    let row1_y = canvas_bottom() + BUTTON_MARGIN;
+    let brush_x = brush_shape_x();
+    x >= brush_x && x < brush_x + BUTTON_SIZE && y >= row1_y && y < row1_y + BUTTON_SIZE
+}
+
+/// Draw one stamp of a brush `shape` in the middle of the button at (bx, by)
+pub fn draw_brush_shape_icon(buffer: &mut [u32], bx: usize, by: usize, shape: BrushShape) {
+    let (cx, cy) = (
+        (bx + BUTTON_SIZE / 2) as isize,
+        (by + BUTTON_SIZE / 2) as isize,
+    );
+    let r: isize = 6;
+    let mut plot = |dx: isize, dy: isize| {
+        let (x, y) = (cx + dx, cy + dy);
+        if x >= 0 && y >= 0 && (x as usize) < window_width() && (y as usize) < window_height() {
+            buffer[y as usize * window_width() + x as usize] = BLACK;
+        }
+    };
+    match shape {
+        BrushShape::Circle => {
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    if dx * dx + dy * dy <= r * r {
+                        plot(dx, dy);
+                    }
+                }
+            }
+        }
+        BrushShape::Square => {
+            for dy in -r + 1..r {
+                for dx in -r + 1..r {
+                    plot(dx, dy);
+                }
+            }
+        }
+        BrushShape::Slash => {
+            for d in -r..=r {
+                plot(d, -d);
+                plot(d + 1, -d);
+            }
+        }
+        BrushShape::Spray => {
+            // Denser than a real stamp, so the icon reads as a spray
+            for seed in 0..4 {
+                for (dx, dy) in spray_offsets(seed, 0, r as usize) {
+                    plot(dx, dy);
+                }
+            }
+        }
+    }
+}
+
 /// Draw edge/fill color indicator showing current colors
 pub fn draw_edge_fill_indicator(
     buffer: &mut [u32],
     x: usize,
     y: usize,
-    edge_color_index: Option<usize>,
-    fill_color_index: Option<usize>,
+    edge_color: Option<u32>,
+    fill_color: Option<u32>,
 ) {
     let size = 20;
     let offset = 8;
 
     // Draw fill color square (behind, offset)
-    if let Some(fill_idx) = fill_color_index {
-        let fill_color = COLOR_PALETTE[fill_idx];
+    if let Some(fill_color) = fill_color {
         for dy in 0..size {
             for dx in 0..size {
                 let px = x + offset + dx;
                 let py = y + offset + dy;
-                if px < WIDTH && py < HEIGHT {
-                    buffer[py * WIDTH + px] = fill_color;
+                if px < window_width() && py < window_height() {
+                    let i = py * window_width() + px;
+                    buffer[i] = composite(buffer[i], fill_color, 1.0);
                 }
             }
         }
         // Border for fill square
         for dx in 0..size {
-            buffer[(y + offset) * WIDTH + x + offset + dx] = DARK_GRAY;
-            buffer[(y + offset + size - 1) * WIDTH + x + offset + dx] = DARK_GRAY;
+            buffer[(y + offset) * window_width() + x + offset + dx] = DARK_GRAY;
+            buffer[(y + offset + size - 1) * window_width() + x + offset + dx] = DARK_GRAY;
         }
         for dy in 0..size {
-            buffer[(y + offset + dy) * WIDTH + x + offset] = DARK_GRAY;
-            buffer[(y + offset + dy) * WIDTH + x + offset + size - 1] = DARK_GRAY;
+            buffer[(y + offset + dy) * window_width() + x + offset] = DARK_GRAY;
+            buffer[(y + offset + dy) * window_width() + x + offset + size - 1] = DARK_GRAY;
         }
     } else {
         // Draw "no fill" indicator (checkerboard for transparent)
@@ -152,32 +250,32 @@ pub fn draw_edge_fill_indicator(
             for dx in 0..size {
                 let px = x + offset + dx;
                 let py = y + offset + dy;
-                if px < WIDTH && py < HEIGHT {
+                if px < window_width() && py < window_height() {
                     let checker = ((dx / 4) + (dy / 4)) % 2 == 0;
-                    buffer[py * WIDTH + px] = if checker { WHITE } else { GRAY };
+                    buffer[py * window_width() + px] = if checker { WHITE } else { GRAY };
                 }
             }
         }
         // Border
         for dx in 0..size {
-            buffer[(y + offset) * WIDTH + x + offset + dx] = DARK_GRAY;
-            buffer[(y + offset + size - 1) * WIDTH + x + offset + dx] = DARK_GRAY;
+            buffer[(y + offset) * window_width() + x + offset + dx] = DARK_GRAY;
+            buffer[(y + offset + size - 1) * window_width() + x + offset + dx] = DARK_GRAY;
         }
         for dy in 0..size {
-            buffer[(y + offset + dy) * WIDTH + x + offset] = DARK_GRAY;
-            buffer[(y + offset + dy) * WIDTH + x + offset + size - 1] = DARK_GRAY;
+            buffer[(y + offset + dy) * window_width() + x + offset] = DARK_GRAY;
+            buffer[(y + offset + dy) * window_width() + x + offset + size - 1] = DARK_GRAY;
         }
     }
 
     // Draw edge color square (front, at origin)
-    if let Some(edge_idx) = edge_color_index {
-        let edge_color = COLOR_PALETTE[edge_idx];
+    if let Some(edge_color) = edge_color {
         for dy in 0..size {
             for dx in 0..size {
                 let px = x + dx;
                 let py = y + dy;
-                if px < WIDTH && py < HEIGHT {
-                    buffer[py * WIDTH + px] = edge_color;
+                if px < window_width() && py < window_height() {
+                    let i = py * window_width() + px;
+                    buffer[i] = composite(buffer[i], edge_color, 1.0);
                 }
             }
         }
@@ -188,12 +286,12 @@ pub fn draw_edge_fill_indicator(
             WHITE
         };
         for dx in 0..size {
-            buffer[y * WIDTH + x + dx] = border_color;
-            buffer[(y + size - 1) * WIDTH + x + dx] = border_color;
+            buffer[y * window_width() + x + dx] = border_color;
+            buffer[(y + size - 1) * window_width() + x + dx] = border_color;
         }
         for dy in 0..size {
-            buffer[(y + dy) * WIDTH + x] = border_color;
-            buffer[(y + dy) * WIDTH + x + size - 1] = border_color;
+            buffer[(y + dy) * window_width() + x] = border_color;
+            buffer[(y + dy) * window_width() + x + size - 1] = border_color;
         }
     } else {
         // Draw checkerboard for transparent edge
@@ -201,27 +299,27 @@ pub fn draw_edge_fill_indicator(
             for dx in 0..size {
                 let px = x + dx;
                 let py = y + dy;
-                if px < WIDTH && py < HEIGHT {
+                if px < window_width() && py < window_height() {
                     let checker = ((dx / 4) + (dy / 4)) % 2 == 0;
-                    buffer[py * WIDTH + px] = if checker { WHITE } else { GRAY };
+                    buffer[py * window_width() + px] = if checker { WHITE } else { GRAY };
                 }
             }
         }
         // Border
         for dx in 0..size {
-            buffer[y * WIDTH + x + dx] = DARK_GRAY;
-            buffer[(y + size - 1) * WIDTH + x + dx] = DARK_GRAY;
+            buffer[y * window_width() + x + dx] = DARK_GRAY;
+            buffer[(y + size - 1) * window_width() + x + dx] = DARK_GRAY;
         }
         for dy in 0..size {
-            buffer[(y + dy) * WIDTH + x] = DARK_GRAY;
-            buffer[(y + dy) * WIDTH + x + size - 1] = DARK_GRAY;
+            buffer[(y + dy) * window_width() + x] = DARK_GRAY;
+            buffer[(y + dy) * window_width() + x + size - 1] = DARK_GRAY;
         }
     }
 }
 
 /// Check if click is on the fill indicator (to clear fill)
 pub fn is_in_fill_indicator(x: usize, y: usize) -> bool {
-    let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
+    let row1_y = canvas_bottom() + BUTTON_MARGIN;
     let transparent_x = BUTTON_MARGIN + 14 * (BUTTON_SIZE + BUTTON_MARGIN);
     let indicator_x = transparent_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
     let offset = 8;
@@ -246,18 +344,18 @@ pub fn draw_x(buffer: &mut [u32], bx: usize, by: usize) {
         let x2 = bx + end - 1 - i;
         let y2 = by + start + i;
 
-        if x1 < WIDTH && y1 < HEIGHT {
-            buffer[y1 * WIDTH + x1] = WHITE;
+        if x1 < window_width() && y1 < window_height() {
+            buffer[y1 * window_width() + x1] = WHITE;
         }
-        if x2 < WIDTH && y2 < HEIGHT {
-            buffer[y2 * WIDTH + x2] = WHITE;
+        if x2 < window_width() && y2 < window_height() {
+            buffer[y2 * window_width() + x2] = WHITE;
         }
     }
 }
 
 /// Check if coordinates are within the close button
 pub fn is_in_close_button(x: usize, y: usize) -> bool {
-    let bx = WIDTH - BUTTON_SIZE - BUTTON_MARGIN;
+    let bx = window_width() - BUTTON_SIZE - BUTTON_MARGIN;
     let by = BUTTON_MARGIN;
     x >= bx && x < bx + BUTTON_SIZE && y >= by && y < by + BUTTON_SIZE
 }
@@ -277,37 +375,40 @@ pub fn get_clicked_color_index(x: usize, y: usize) -> Option<usize> {
     None
 }
 
-/// Draw the bottom toolbar with color palette and tool buttons
+/// Draw the bottom toolbar with the current color palette and tool buttons
+///
+/// The palette and brush shape button show the thread's current palette and
+/// brush shape (see `with_palette`, `with_brush_shape`).
 pub fn draw_bottom_toolbar(
     buffer: &mut [u32],
-    edge_color_index: Option<usize>,
-    fill_color_index: Option<usize>,
+    edge_color: Option<u32>,
+    fill_color: Option<u32>,
     brush_size: usize,
     current_tool: ToolMode,
 ) {
-    let toolbar_top = CANVAS_BOTTOM;
+    let toolbar_top = canvas_bottom();
 
     // Fill toolbar background with gray
-    for y in toolbar_top..HEIGHT {
-        for x in 0..WIDTH {
-            buffer[y * WIDTH + x] = GRAY;
+    for y in toolbar_top..window_height() {
+        for x in 0..window_width() {
+            buffer[y * window_width() + x] = GRAY;
         }
     }
 
     // Draw top border
-    for x in 0..WIDTH {
-        buffer[toolbar_top * WIDTH + x] = DARK_GRAY;
+    for x in 0..window_width() {
+        buffer[toolbar_top * window_width() + x] = DARK_GRAY;
     }
 
     // Row 1: 14 color buttons + transparent button + edge/fill indicator
     let row1_y = toolbar_top + BUTTON_MARGIN;
-    for (i, &color) in COLOR_PALETTE.iter().enumerate() {
+    for (i, &color) in current_palette().colors().iter().enumerate() {
         let bx = BUTTON_MARGIN + i * (BUTTON_SIZE + BUTTON_MARGIN);
         draw_button(buffer, bx, row1_y, color);
 
         // Draw border: white/blue for edge selection, green for fill selection
-        let is_edge = edge_color_index == Some(i);
-        let is_fill = fill_color_index == Some(i);
+        let is_edge = edge_color == Some(color);
+        let is_fill = fill_color == Some(color);
 
         if is_edge && is_fill {
             // Both edge and fill: white outer, green inner
@@ -330,35 +431,37 @@ pub fn draw_bottom_toolbar(
         buffer,
         transparent_x,
         row1_y,
-        edge_color_index.is_none(),
-        fill_color_index.is_none(),
+        edge_color.is_none(),
+        fill_color.is_none(),
     );
 
     // Edge/Fill indicator (after transparent button)
     let indicator_x = transparent_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
-    draw_edge_fill_indicator(
-        buffer,
-        indicator_x,
-        row1_y,
-        edge_color_index,
-        fill_color_index,
-    );
+    draw_edge_fill_indicator(buffer, indicator_x, row1_y, edge_color, fill_color);
+
+    // Eyedropper (after the indicator, next to the colors it picks)
+    let eyedropper_x = eyedropper_x();
+    draw_button(buffer, eyedropper_x, row1_y, GRAY);
+    draw_tool_icon(buffer, eyedropper_x, row1_y, ToolMode::Eyedropper);
+    let border = if current_tool == ToolMode::Eyedropper {
+        0x4040E0 // Blue border, as for the selected tool
+    } else {
+        DARK_GRAY
+    };
+    draw_button_border(buffer, eyedropper_x, row1_y, border);
+
+    // Brush shape (after the eyedropper): the current stamp; clicking it
+    // moves on to the next shape
+    let brush_x = brush_shape_x();
+    draw_button(buffer, brush_x, row1_y, WHITE);
+    draw_brush_shape_icon(buffer, brush_x, row1_y, brush_shape());
+    draw_button_border(buffer, brush_x, row1_y, DARK_GRAY);
 
     // Row 2: Tool buttons + Size display + [-] [+] buttons
     let row2_y = toolbar_top + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
 
-    // Tool buttons: [Brush] [Line] [Sq] [Rect] [Circ] [Oval] [Tri]
-    let tools = [
-        ToolMode::Brush,
-        ToolMode::Line,
-        ToolMode::Square,
-        ToolMode::Rectangle,
-        ToolMode::Circle,
-        ToolMode::Oval,
-        ToolMode::Triangle,
-    ];
-
-    for (i, &tool) in tools.iter().enumerate() {
+    // Tool buttons: [Brush] [Line] [Sq] [Rect] [Circ] [Oval] [Tri] [Bucket] [Poly] [Select]
+    for (i, &tool) in TOOLBAR_TOOLS.iter().enumerate() {
         let bx = BUTTON_MARGIN + i * (BUTTON_SIZE + BUTTON_MARGIN);
         draw_button(buffer, bx, row2_y, GRAY);
         draw_tool_icon(buffer, bx, row2_y, tool);
@@ -372,7 +475,7 @@ pub fn draw_bottom_toolbar(
     }
 
     // Size display (after tool buttons)
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    let size_display_x = size_display_x();
     draw_size_display(buffer, size_display_x, row2_y, brush_size);
 
     // Minus button
@@ -389,6 +492,15 @@ pub fn draw_bottom_toolbar(
     let clear_x = plus_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
     draw_button(buffer, clear_x, row2_y, 0xC04040); // Reddish color
     draw_clear_icon(buffer, clear_x, row2_y);
+
+    // Undo and redo buttons
+    let undo_x = clear_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
+    draw_button(buffer, undo_x, row2_y, DARK_GRAY);
+    draw_arrow_icon(buffer, undo_x, row2_y, true);
+
+    let redo_x = undo_x + BUTTON_SIZE + BUTTON_MARGIN;
+    draw_button(buffer, redo_x, row2_y, DARK_GRAY);
+    draw_arrow_icon(buffer, redo_x, row2_y, false);
 }
 
 /// Draw an icon representing a tool
@@ -407,10 +519,10 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
             for i in 0..6 {
                 let x = start_x + i;
                 let y = end_y - i;
-                if x < WIDTH && y < HEIGHT {
-                    buffer[y * WIDTH + x] = BLACK;
+                if x < window_width() && y < window_height() {
+                    buffer[y * window_width() + x] = BLACK;
                     if y > 0 {
-                        buffer[(y - 1) * WIDTH + x] = BLACK;
+                        buffer[(y - 1) * window_width() + x] = BLACK;
                     }
                 }
             }
@@ -420,8 +532,8 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
             for i in 0..(end_x - start_x) {
                 let x = start_x + i;
                 let y = start_y + i;
-                if x < WIDTH && y < HEIGHT {
-                    buffer[y * WIDTH + x] = BLACK;
+                if x < window_width() && y < window_height() {
+                    buffer[y * window_width() + x] = BLACK;
                 }
             }
         }
@@ -429,10 +541,10 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
             // Square outline
             let size = end_x - start_x;
             for i in 0..size {
-                buffer[start_y * WIDTH + start_x + i] = BLACK; // top
-                buffer[end_y * WIDTH + start_x + i] = BLACK; // bottom
-                buffer[(start_y + i) * WIDTH + start_x] = BLACK; // left
-                buffer[(start_y + i) * WIDTH + end_x] = BLACK; // right
+                buffer[start_y * window_width() + start_x + i] = BLACK; // top
+                buffer[end_y * window_width() + start_x + i] = BLACK; // bottom
+                buffer[(start_y + i) * window_width() + start_x] = BLACK; // left
+                buffer[(start_y + i) * window_width() + end_x] = BLACK; // right
             }
         }
         ToolMode::Rectangle => {
@@ -440,12 +552,12 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
             let rect_start_y = start_y + 3;
             let rect_end_y = end_y - 3;
             for x in start_x..=end_x {
-                buffer[rect_start_y * WIDTH + x] = BLACK; // top
-                buffer[rect_end_y * WIDTH + x] = BLACK; // bottom
+                buffer[rect_start_y * window_width() + x] = BLACK; // top
+                buffer[rect_end_y * window_width() + x] = BLACK; // bottom
             }
             for y in rect_start_y..=rect_end_y {
-                buffer[y * WIDTH + start_x] = BLACK; // left
-                buffer[y * WIDTH + end_x] = BLACK; // right
+                buffer[y * window_width() + start_x] = BLACK; // left
+                buffer[y * window_width() + end_x] = BLACK; // right
             }
         }
         ToolMode::Circle => {
@@ -457,8 +569,12 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
                 let theta = (angle as f64) * std::f64::consts::PI * 2.0 / 32.0;
                 let x = cx as f64 + (radius as f64) * theta.cos();
                 let y = cy as f64 + (radius as f64) * theta.sin();
-                if x >= 0.0 && (x as usize) < WIDTH && y >= 0.0 && (y as usize) < HEIGHT {
-                    buffer[(y as usize) * WIDTH + (x as usize)] = BLACK;
+                if x >= 0.0
+                    && (x as usize) < window_width()
+                    && y >= 0.0
+                    && (y as usize) < window_height()
+                {
+                    buffer[(y as usize) * window_width() + (x as usize)] = BLACK;
                 }
             }
         }
@@ -472,8 +588,12 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
                 let theta = (angle as f64) * std::f64::consts::PI * 2.0 / 32.0;
                 let x = cx as f64 + (rx as f64) * theta.cos();
                 let y = cy as f64 + (ry as f64) * theta.sin();
-                if x >= 0.0 && (x as usize) < WIDTH && y >= 0.0 && (y as usize) < HEIGHT {
-                    buffer[(y as usize) * WIDTH + (x as usize)] = BLACK;
+                if x >= 0.0
+                    && (x as usize) < window_width()
+                    && y >= 0.0
+                    && (y as usize) < window_height()
+                {
+                    buffer[(y as usize) * window_width() + (x as usize)] = BLACK;
                 }
             }
         }
@@ -490,8 +610,8 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
                 let x = apex_x as isize
                     - (i as isize * (apex_x - left_x) as isize / (base_y - apex_y) as isize);
                 let y = apex_y + i;
-                if x >= 0 && (x as usize) < WIDTH && y < HEIGHT {
-                    buffer[y * WIDTH + x as usize] = BLACK;
+                if x >= 0 && (x as usize) < window_width() && y < window_height() {
+                    buffer[y * window_width() + x as usize] = BLACK;
                 }
             }
             // Right edge
@@ -499,13 +619,113 @@ pub fn draw_tool_icon(buffer: &mut [u32], bx: usize, by: usize, tool: ToolMode)
                 let x = apex_x as isize
                     + (i as isize * (right_x - apex_x) as isize / (base_y - apex_y) as isize);
                 let y = apex_y + i;
-                if x >= 0 && (x as usize) < WIDTH && y < HEIGHT {
-                    buffer[y * WIDTH + x as usize] = BLACK;
+                if x >= 0 && (x as usize) < window_width() && y < window_height() {
+                    buffer[y * window_width() + x as usize] = BLACK;
                 }
             }
             // Base
             for x in left_x..=right_x {
-                buffer[base_y * WIDTH + x] = BLACK;
+                buffer[base_y * window_width() + x] = BLACK;
+            }
+        }
+        ToolMode::Bucket => {
+            // Pail with a handle, and paint dripping from its right rim
+            let rim_y = mid_y - 3;
+            let (left, right) = (start_x + 1, end_x - 4);
+            for x in left..=right {
+                buffer[rim_y * window_width() + x] = BLACK;
+                buffer[end_y * window_width() + x] = BLACK;
+            }
+            for y in rim_y..=end_y {
+                buffer[y * window_width() + left] = BLACK;
+                buffer[y * window_width() + right] = BLACK;
+            }
+            for i in 0..=(rim_y - start_y) {
+                buffer[(rim_y - i) * window_width() + left + i] = BLACK;
+                buffer[(rim_y - i) * window_width() + right - i] = BLACK;
+            }
+            for y in rim_y..=mid_y + 2 {
+                buffer[y * window_width() + end_x - 1] = BLACK;
+            }
+        }
+        ToolMode::Polygon => {
+            // Irregular pentagon outline
+            let corners = [
+                (mid_x, start_y),
+                (end_x, mid_y - 2),
+                (end_x - 3, end_y),
+                (start_x + 2, end_y),
+                (start_x, mid_y),
+            ];
+            for (i, &(x0, y0)) in corners.iter().enumerate() {
+                let (x1, y1) = corners[(i + 1) % corners.len()];
+                let steps = x0.abs_diff(x1).max(y0.abs_diff(y1));
+                for s in 0..=steps {
+                    let x = x0 as isize + (x1 as isize - x0 as isize) * s as isize / steps as isize;
+                    let y = y0 as isize + (y1 as isize - y0 as isize) * s as isize / steps as isize;
+                    buffer[y as usize * window_width() + x as usize] = BLACK;
+                }
+            }
+        }
+        ToolMode::Bezier => {
+            // S-curve between two endpoint dots
+            let (left, right) = (start_x + 1, end_x - 1);
+            for (x, y) in bezier_points(&[
+                (left, end_y - 1),
+                (start_x, start_y - 6),
+                (end_x, end_y + 6),
+                (right, start_y + 1),
+            ]) {
+                buffer[y * window_width() + x] = BLACK;
+            }
+            for (x, y) in [(left, end_y - 1), (right, start_y + 1)] {
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    buffer[(y + dy - 1) * window_width() + x + dx - 1] = BLACK;
+                }
+            }
+        }
+        ToolMode::RoundedRect => {
+            // Rectangle outline with rounded corners
+            let rect = RoundedRect::new(start_x, start_y + 2, end_x, end_y - 2, 4);
+            for y in rect.top..=rect.bottom {
+                for (left, right) in rect.outline_runs(y).into_iter().flatten() {
+                    for x in left..=right {
+                        buffer[y * window_width() + x] = BLACK;
+                    }
+                }
+            }
+        }
+        ToolMode::Arrow => {
+            // Diagonal shaft ending in a solid head at the top-right
+            for i in 0..=(end_x - start_x) {
+                buffer[(end_y - i) * window_width() + start_x + i] = BLACK;
+            }
+            for dy in 0..=6 {
+                for dx in 0..=(6 - dy) {
+                    buffer[(start_y + dy) * window_width() + end_x - dx] = BLACK;
+                }
+            }
+        }
+        ToolMode::Select => {
+            // Dashed square outline (marching ants)
+            for i in (0..=(end_x - start_x)).filter(|i| (i / 2) % 2 == 0) {
+                buffer[start_y * window_width() + start_x + i] = BLACK; // top
+                buffer[end_y * window_width() + start_x + i] = BLACK; // bottom
+                buffer[(start_y + i) * window_width() + start_x] = BLACK; // left
+                buffer[(start_y + i) * window_width() + end_x] = BLACK; // right
+            }
+        }
+        ToolMode::Eyedropper => {
+            // Pipette: a diagonal tube from the bottom-left, with a bulb at
+            // the top-right
+            for i in 0..=(end_x - start_x - 4) {
+                buffer[(end_y - i) * window_width() + start_x + i] = BLACK;
+                buffer[(end_y - i) * window_width() + start_x + i + 1] = BLACK;
+            }
+            for y in start_y..start_y + 5 {
+                for x in end_x - 4..=end_x {
+                    buffer[y * window_width() + x] = BLACK;
+                }
             }
         }
     }
@@ -519,8 +739,8 @@ pub fn draw_minus_icon(buffer: &mut [u32], bx: usize, by: usize) {
     let mid_y = by + BUTTON_SIZE / 2;
 
     for x in start_x..end_x {
-        if x < WIDTH && mid_y < HEIGHT {
-            buffer[mid_y * WIDTH + x] = WHITE;
+        if x < window_width() && mid_y < window_height() {
+            buffer[mid_y * window_width() + x] = WHITE;
         }
     }
 }
@@ -537,14 +757,14 @@ pub fn draw_plus_icon(buffer: &mut [u32], bx: usize, by: usize) {
 
     // Horizontal line
     for x in start_x..end_x {
-        if x < WIDTH && mid_y < HEIGHT {
-            buffer[mid_y * WIDTH + x] = WHITE;
+        if x < window_width() && mid_y < window_height() {
+            buffer[mid_y * window_width() + x] = WHITE;
         }
     }
     // Vertical line
     for y in start_y..end_y {
-        if mid_x < WIDTH && y < HEIGHT {
-            buffer[y * WIDTH + mid_x] = WHITE;
+        if mid_x < window_width() && y < window_height() {
+            buffer[y * window_width() + mid_x] = WHITE;
         }
     }
 }
@@ -560,15 +780,40 @@ pub fn draw_clear_icon(buffer: &mut [u32], bx: usize, by: usize) {
         // Top-left to bottom-right diagonal
         let x1 = bx + start + i;
         let y1 = by + start + i;
-        if x1 < WIDTH && y1 < HEIGHT {
-            buffer[y1 * WIDTH + x1] = WHITE;
+        if x1 < window_width() && y1 < window_height() {
+            buffer[y1 * window_width() + x1] = WHITE;
         }
 
         // Top-right to bottom-left diagonal
         let x2 = bx + end - 1 - i;
         let y2 = by + start + i;
-        if x2 < WIDTH && y2 < HEIGHT {
-            buffer[y2 * WIDTH + x2] = WHITE;
+        if x2 < window_width() && y2 < window_height() {
+            buffer[y2 * window_width() + x2] = WHITE;
+        }
+    }
+}
+
+/// Draw a horizontal arrow icon, pointing left (undo) or right (redo)
+pub fn draw_arrow_icon(buffer: &mut [u32], bx: usize, by: usize, left: bool) {
+    let padding = 6;
+    let start_x = bx + padding;
+    let end_x = bx + BUTTON_SIZE - padding;
+    let mid_y = by + BUTTON_SIZE / 2;
+
+    // Shaft
+    for x in start_x..end_x {
+        if x < window_width() && mid_y < window_height() {
+            buffer[mid_y * window_width() + x] = WHITE;
+        }
+    }
+    // Head: two diagonals from the tip
+    let tip_x = if left { start_x } else { end_x - 1 };
+    for i in 1..5 {
+        let x = if left { tip_x + i } else { tip_x - i };
+        for y in [mid_y - i, mid_y + i] {
+            if x < window_width() && y < window_height() {
+                buffer[y * window_width() + x] = WHITE;
+            }
         }
     }
 }
@@ -582,23 +827,23 @@ pub fn draw_size_display(buffer: &mut [u32], x: usize, y: usize, size: usize) {
     // Fill background
     for dy in 0..height {
         for dx in 0..width {
-            if x + dx < WIDTH && y + dy < HEIGHT {
-                buffer[(y + dy) * WIDTH + (x + dx)] = WHITE;
+            if x + dx < window_width() && y + dy < window_height() {
+                buffer[(y + dy) * window_width() + (x + dx)] = WHITE;
             }
         }
     }
 
     // Draw border
     for dx in 0..width {
-        if x + dx < WIDTH {
-            buffer[y * WIDTH + (x + dx)] = DARK_GRAY;
-            buffer[(y + height - 1) * WIDTH + (x + dx)] = DARK_GRAY;
+        if x + dx < window_width() {
+            buffer[y * window_width() + (x + dx)] = DARK_GRAY;
+            buffer[(y + height - 1) * window_width() + (x + dx)] = DARK_GRAY;
         }
     }
     for dy in 0..height {
-        if y + dy < HEIGHT {
-            buffer[(y + dy) * WIDTH + x] = DARK_GRAY;
-            buffer[(y + dy) * WIDTH + (x + width - 1)] = DARK_GRAY;
+        if y + dy < window_height() {
+            buffer[(y + dy) * window_width() + x] = DARK_GRAY;
+            buffer[(y + dy) * window_width() + (x + width - 1)] = DARK_GRAY;
         }
     }
 
@@ -634,11 +879,11 @@ pub fn draw_number(buffer: &mut [u32], x: usize, y: usize, num: usize) {
                     if (bits >> (4 - col)) & 1 == 1 {
                         let px = x + offset + col;
                         let py = y + row * 2; // Scale up vertically
-                        if px < WIDTH && py < HEIGHT {
-                            buffer[py * WIDTH + px] = BLACK;
+                        if px < window_width() && py < window_height() {
+                            buffer[py * window_width() + px] = BLACK;
                         }
-                        if px < WIDTH && py + 1 < HEIGHT {
-                            buffer[(py + 1) * WIDTH + px] = BLACK;
+                        if px < window_width() && py + 1 < window_height() {
+                            buffer[(py + 1) * window_width() + px] = BLACK;
                         }
                     }
                 }
@@ -650,11 +895,11 @@ pub fn draw_number(buffer: &mut [u32], x: usize, y: usize, num: usize) {
 
 /// Get color index from bottom toolbar color palette
 pub fn get_clicked_color_index_bottom(x: usize, y: usize) -> Option<usize> {
-    let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
+    let row1_y = canvas_bottom() + BUTTON_MARGIN;
     if y < row1_y || y >= row1_y + BUTTON_SIZE {
         return None;
     }
-    for i in 0..COLOR_PALETTE.len() {
+    for i in 0..PALETTE_LEN {
         let bx = BUTTON_MARGIN + i * (BUTTON_SIZE + BUTTON_MARGIN);
         if x >= bx && x < bx + BUTTON_SIZE {
             return Some(i);
@@ -665,22 +910,12 @@ pub fn get_clicked_color_index_bottom(x: usize, y: usize) -> Option<usize> {
 
 /// Returns which tool button was clicked, if any
 pub fn get_clicked_tool(x: usize, y: usize) -> Option<ToolMode> {
-    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let row2_y = canvas_bottom() + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
     if y < row2_y || y >= row2_y + BUTTON_SIZE {
         return None;
     }
 
-    let tools = [
-        ToolMode::Brush,
-        ToolMode::Line,
-        ToolMode::Square,
-        ToolMode::Rectangle,
-        ToolMode::Circle,
-        ToolMode::Oval,
-        ToolMode::Triangle,
-    ];
-
-    for (i, &tool) in tools.iter().enumerate() {
+    for (i, &tool) in TOOLBAR_TOOLS.iter().enumerate() {
         let bx = BUTTON_MARGIN + i * (BUTTON_SIZE + BUTTON_MARGIN);
         if x >= bx && x < bx + BUTTON_SIZE {
             return Some(tool);
@@ -691,27 +926,46 @@ pub fn get_clicked_tool(x: usize, y: usize) -> Option<ToolMode> {
 
 /// Check if coordinates are within the minus button
 pub fn is_in_minus_button(x: usize, y: usize) -> bool {
-    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
-    let minus_x = size_display_x + 44 + BUTTON_MARGIN;
+    let row2_y = canvas_bottom() + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let minus_x = size_display_x() + 44 + BUTTON_MARGIN;
     x >= minus_x && x < minus_x + BUTTON_SIZE && y >= row2_y && y < row2_y + BUTTON_SIZE
 }
 
 /// Check if coordinates are within the plus button
 pub fn is_in_plus_button(x: usize, y: usize) -> bool {
-    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
-    let minus_x = size_display_x + 44 + BUTTON_MARGIN;
+    let row2_y = canvas_bottom() + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let minus_x = size_display_x() + 44 + BUTTON_MARGIN;
     let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
     x >= plus_x && x < plus_x + BUTTON_SIZE && y >= row2_y && y < row2_y + BUTTON_SIZE
 }
 
 /// Check if coordinates are within the clear button
 pub fn is_in_clear_button(x: usize, y: usize) -> bool {
-    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
-    let minus_x = size_display_x + 44 + BUTTON_MARGIN;
+    let row2_y = canvas_bottom() + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let minus_x = size_display_x() + 44 + BUTTON_MARGIN;
     let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
     let clear_x = plus_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
     x >= clear_x && x < clear_x + BUTTON_SIZE && y >= row2_y && y < row2_y + BUTTON_SIZE
 }
+
+/// Check if coordinates are within the undo button
+pub fn is_in_undo_button(x: usize, y: usize) -> bool {
+    let row2_y = canvas_bottom() + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let undo_x = undo_button_x();
+    x >= undo_x && x < undo_x + BUTTON_SIZE && y >= row2_y && y < row2_y + BUTTON_SIZE
+}
+
+/// Check if coordinates are within the redo button
+pub fn is_in_redo_button(x: usize, y: usize) -> bool {
+    let row2_y = canvas_bottom() + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let redo_x = undo_button_x() + BUTTON_SIZE + BUTTON_MARGIN;
+    x >= redo_x && x < redo_x + BUTTON_SIZE && y >= row2_y && y < row2_y + BUTTON_SIZE
+}
+
+/// Left edge of the undo button (right of the clear button)
+fn undo_button_x() -> usize {
+    let minus_x = size_display_x() + 44 + BUTTON_MARGIN;
+    let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
+    let clear_x = plus_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
+    clear_x + BUTTON_SIZE + BUTTON_MARGIN * 2
+}
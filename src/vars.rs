@@ -0,0 +1,79 @@
+//! Named variables and templated text.
+//!
+//! This module handles:
+//! - `Variables`, the string values set with `var set <name> <value>`
+//! - Expanding `{name}` placeholders in text templates
+//!
+//! Text objects keep their template and are expanded every time they are
+//! drawn, so setting a variable refreshes every object that mentions it
+//! without the client resending any text.
+
+use std::collections::BTreeMap;
+
+/// Longest variable name
+pub const MAX_VAR_NAME_LEN: usize = 32;
+
+/// Whether `name` is a valid variable name (letters, digits, `_`, `-`)
+pub fn is_valid_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_VAR_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Variable values by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variables {
+    values: BTreeMap<String, String>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.values.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Replace each `{name}` in `template` with the variable's value
+    ///
+    /// Placeholders naming unset variables, and braces that don't form a
+    /// placeholder, are kept as written so typos stay visible.
+    pub fn expand(&self, template: &str) -> String {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            match after.find('}') {
+                Some(close) if is_valid_var_name(&after[..close]) => {
+                    match self.get(&after[..close]) {
+                        Some(value) => out.push_str(value),
+                        None => out.push_str(&rest[open..open + close + 2]),
+                    }
+                    rest = &after[close + 1..];
+                }
+                _ => {
+                    out.push('{');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
@@ -0,0 +1,142 @@
+//! Breakpoints on canvas regions.
+//!
+//! This module handles:
+//! - `Watches`, the regions added with `watch`, each with the pixels it held
+//!   after the last command
+//! - Finding which watched regions a command changed
+//!
+//! When a command changes a watched pixel, subscribers get a `watch <id>
+//! <command>` event and any replay in progress pauses until `resume`, which
+//! pinpoints the command in a large script that touched an unexpected area.
+//! Only protocol commands are checked; drawing with the mouse is not.
+
+use crate::command::{check_rect, done, reply, Command, CommandOutput, CommandResult};
+use crate::locks::Region;
+use crate::window_width;
+
+/// Most watches at once
+pub const MAX_WATCHES: usize = 64;
+
+/// A watched region and the pixels it held
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    pub id: usize,
+    pub region: Region,
+    /// Rows top to bottom, as `0xRRGGBB`
+    pixels: Vec<u32>,
+}
+
+impl Watch {
+    /// Compare the region with the stored pixels, storing its current ones;
+    /// returns whether any differed
+    fn update(&mut self, buffer: &[u32]) -> bool {
+        let width = window_width();
+        let Region { x, y, .. } = self.region;
+        let mut changed = false;
+        for (row, stored) in self.pixels.chunks_mut(self.region.width).enumerate() {
+            let start = (y + row) * width + x;
+            let current = &buffer[start..start + stored.len()];
+            if stored != current {
+                stored.copy_from_slice(current);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// The watched regions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Watches {
+    watches: Vec<Watch>,
+    next_id: usize,
+}
+
+impl Watches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// All watches, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Watch> {
+        self.watches.iter()
+    }
+
+    /// Watch `region` of a window-sized buffer, returning the new watch's id;
+    /// an empty region or one off the canvas is an error
+    pub fn add(&mut self, buffer: &[u32], region: Region) -> Result<usize, String> {
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = region;
+        check_rect("watch region", x, y, width, height)?;
+        if self.watches.len() >= MAX_WATCHES {
+            return Err(format!("too many watches (max {})", MAX_WATCHES));
+        }
+        self.next_id += 1;
+        let mut watch = Watch {
+            id: self.next_id,
+            region,
+            pixels: vec![0; region.width * region.height],
+        };
+        watch.update(buffer);
+        self.watches.push(watch);
+        Ok(self.next_id)
+    }
+
+    /// Stop watching `id`
+    pub fn remove(&mut self, id: usize) -> Result<(), String> {
+        let i = self
+            .watches
+            .iter()
+            .position(|watch| watch.id == id)
+            .ok_or_else(|| format!("no watch {}", id))?;
+        self.watches.remove(i);
+        Ok(())
+    }
+
+    /// Stop watching everything, returning how many watches there were
+    pub fn clear(&mut self) -> usize {
+        std::mem::take(&mut self.watches).len()
+    }
+
+    /// The ids of the watches whose pixels changed since the last check,
+    /// taking the current pixels as the new baseline
+    ///
+    /// Checking before a command as well as after keeps changes made some
+    /// other way (the mouse) from being blamed on it.
+    pub fn changed(&mut self, buffer: &[u32]) -> Vec<usize> {
+        self.watches
+            .iter_mut()
+            .filter_map(|watch| watch.update(buffer).then_some(watch.id))
+            .collect()
+    }
+
+    /// Apply `watch` or `unwatch`, returning the protocol response
+    pub fn apply(&mut self, buffer: &[u32], cmd: &Command) -> CommandResult {
+        match *cmd {
+            Command::Watch {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let id = self.add(buffer, Region::new(x, y, width, height))?;
+                reply(format!("watching {}", id))
+            }
+            Command::Unwatch(Some(id)) => done(self.remove(id)),
+            Command::Unwatch(None) => reply(format!("unwatched {}", self.clear())),
+            _ => Ok(CommandOutput::Ok),
+        }
+    }
+}
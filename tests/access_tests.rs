@@ -0,0 +1,116 @@
+use displai::*;
+
+fn cmd(line: &str) -> Command {
+    parse_command(line).expect("command parses")
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_auth() {
+    let auth = Command::Auth("s3cret".to_string());
+    assert_eq!(parse_command("auth s3cret"), Some(auth.clone()));
+    assert_eq!(parse_command(&auth.to_string()), Some(auth.clone()));
+    assert_eq!(parse_command("auth"), None);
+    assert_eq!(parse_command("auth a b"), None);
+    assert!(!auth.is_mutating());
+}
+
+#[test]
+fn test_parse_access() {
+    for access in [Access::ReadOnly, Access::ReadWrite] {
+        assert_eq!(Access::parse(access.name()), Some(access));
+    }
+    assert_eq!(Access::parse("readonly"), None);
+}
+
+// ===================
+// Permission Tests
+// ===================
+
+#[test]
+fn test_read_only_allows_observing() {
+    for line in [
+        "state",
+        "layout",
+        "stats",
+        "subscribe",
+        "snapshot",
+        "snapshot base64",
+        "snapshot region 0,30 10,40",
+        "getpixel 10,40",
+        "pick 10,40",
+        "getregion 0,30 10,40",
+        "auth token",
+    ] {
+        assert!(Access::ReadOnly.allows(&cmd(line)), "{}", line);
+    }
+}
+
+#[test]
+fn test_read_only_refuses_changes() {
+    for line in [
+        "dot 10,40",
+        "clear",
+        "undo",
+        "size 3",
+        "edge 2",
+        "stats on",
+        "timing on",
+        "snapshot /tmp/out.png",
+        "snapshot region 0,30 10,40 /tmp/out.png",
+        "export svg /tmp/out.svg",
+        "record start /tmp/out.txt",
+        "lockregion 0,30,10,10",
+    ] {
+        assert!(!Access::ReadOnly.allows(&cmd(line)), "{}", line);
+        assert!(Access::ReadWrite.allows(&cmd(line)), "{}", line);
+    }
+}
+
+// ===================
+// Access Control Tests
+// ===================
+
+#[test]
+fn test_default_access_by_transport() {
+    let control = AccessControl::new(Access::ReadWrite, Access::ReadOnly, Vec::new());
+    assert_eq!(control.access(STDIN_CLIENT), Access::ReadWrite);
+    assert_eq!(control.access(1), Access::ReadOnly);
+    assert!(control.check(STDIN_CLIENT, &cmd("clear")).is_ok());
+    assert_eq!(
+        control.check(1, &cmd("clear")),
+        Err("clear is not allowed for read-only clients".to_string())
+    );
+}
+
+#[test]
+fn test_auth_grants_access_per_client() {
+    let tokens = vec![
+        ("writer".to_string(), Access::ReadWrite),
+        ("viewer".to_string(), Access::ReadOnly),
+    ];
+    let mut control = AccessControl::new(Access::ReadWrite, Access::ReadOnly, tokens);
+
+    assert_eq!(control.authenticate(1, "writer"), Ok(Access::ReadWrite));
+    assert_eq!(control.access(1), Access::ReadWrite);
+    // Other connections keep the socket default
+    assert_eq!(control.access(2), Access::ReadOnly);
+    // A token can lower access too
+    assert_eq!(
+        control.authenticate(STDIN_CLIENT, "viewer"),
+        Ok(Access::ReadOnly)
+    );
+    assert_eq!(control.access(STDIN_CLIENT), Access::ReadOnly);
+
+    assert_eq!(
+        control.authenticate(2, "guess"),
+        Err("invalid token".to_string())
+    );
+    assert_eq!(control.access(2), Access::ReadOnly);
+
+    control.forget(1);
+    assert_eq!(control.access(1), Access::ReadOnly);
+}
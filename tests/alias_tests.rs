@@ -0,0 +1,455 @@
+use displai::*;
+
+fn run(state: &mut AppState, line: &str) -> Result<CommandOutput, CommandError> {
+    state.run(line)
+}
+
+fn failed(reason: &str) -> Result<CommandOutput, CommandError> {
+    Err(CommandError::Failed(reason.to_string()))
+}
+
+fn define(name: &str, body: &str) -> Command {
+    Command::Alias(AliasCommand::Define {
+        name: name.to_string(),
+        body: body.to_string(),
+    })
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_alias() {
+    let cmd = define("rl", "rect 10,40 100,100; line 10,40 100,100");
+    assert_eq!(
+        parse_command(r#"alias rl "rect 10,40 100,100; line 10,40 100,100""#),
+        Some(cmd.clone())
+    );
+    assert_eq!(
+        cmd.to_string(),
+        r#"alias rl "rect 10,40 100,100; line 10,40 100,100""#
+    );
+    assert_eq!(cmd.name(), "alias");
+    assert!(validate(&cmd).is_ok());
+
+    let get = Command::Alias(AliasCommand::Get("rl".to_string()));
+    assert_eq!(parse_command("alias rl"), Some(get.clone()));
+    assert_eq!(get.to_string(), "alias rl");
+
+    let remove = Command::Alias(AliasCommand::Remove("rl".to_string()));
+    assert_eq!(parse_command("unalias rl"), Some(remove.clone()));
+    assert_eq!(remove.to_string(), "unalias rl");
+    assert_eq!(remove.name(), "unalias");
+
+    // Invalid formats
+    assert_eq!(parse_command("alias"), None);
+    assert_eq!(parse_command("alias rl rect 10,40 100,100"), None);
+    assert_eq!(parse_command(r#"alias rl "rect 10,40 100,100"#), None);
+    assert_eq!(parse_command(r#"alias rl "dot 1,1" extra"#), None);
+    assert_eq!(parse_command(r#"alias 9lives "dot 1,1""#), None);
+    assert_eq!(parse_command(r#"alias r.l "dot 1,1""#), None);
+    assert_eq!(parse_command("unalias"), None);
+    assert_eq!(parse_command("unalias a b"), None);
+}
+
+#[test]
+fn test_validate_alias() {
+    assert_eq!(
+        validate(&define("rect", "dot 1,1")),
+        Err("'rect' is already a command".to_string())
+    );
+    assert_eq!(
+        validate(&define("rl", " ; ")),
+        Err("alias body has no commands".to_string())
+    );
+    assert!(validate(&define("rl", &"dot 1,1;".repeat(MAX_ALIAS_LEN))).is_err());
+    assert_eq!(
+        validate(&Command::Alias(AliasCommand::Remove("state".to_string()))),
+        Err("'state' is already a command".to_string())
+    );
+}
+
+#[test]
+fn test_alias_lines() {
+    let lines: Vec<_> = alias_lines(" dot 1,1 ;; line 1,1 2,2; ").collect();
+    assert_eq!(lines, ["dot 1,1", "line 1,1 2,2"]);
+}
+
+#[test]
+fn test_alias_names() {
+    assert!(is_valid_alias_name("rl"));
+    assert!(is_valid_alias_name("draw-box_2"));
+    assert!(!is_valid_alias_name(""));
+    assert!(!is_valid_alias_name("2box"));
+    assert!(!is_valid_alias_name("@1"));
+    assert!(!is_valid_alias_name(&"a".repeat(MAX_ALIAS_NAME_LEN + 1)));
+}
+
+#[test]
+fn test_alias_use_parses_only_where_defined() {
+    assert_eq!(parse_command("rl"), None);
+    assert_eq!(
+        parse_command_line("rl"),
+        Err(CommandError::Unknown("rl".to_string()))
+    );
+    let mut aliases = Aliases::new();
+    aliases.define("rl", "dot 50,50; line 1,1 2,2").unwrap();
+    with_aliases(&aliases, || {
+        assert_eq!(
+            parse_command("rl"),
+            Some(Command::RunAlias {
+                name: "rl".to_string(),
+                args: vec![],
+                commands: vec![
+                    Command::Dot { x: 50, y: 50 },
+                    Command::Line {
+                        x1: 1,
+                        y1: 1,
+                        x2: 2,
+                        y2: 2
+                    },
+                ],
+            })
+        );
+        assert_eq!(parse_command("rl").unwrap().to_string(), "rl");
+        // No arguments
+        assert_eq!(
+            parse_command_line("rl 5"),
+            Err(CommandError::Failed(
+                "alias 'rl' takes no arguments, and its commands must parse".to_string()
+            ))
+        );
+    });
+    assert_eq!(parse_command("rl"), None);
+}
+
+// ===================
+// Expansion Tests
+// ===================
+
+#[test]
+fn test_nested_aliases_flatten() {
+    let mut aliases = Aliases::new();
+    aliases.define("a", "dot 1,1").unwrap();
+    aliases.define("b", "a; dot 2,2; a").unwrap();
+    let Some(Command::RunAlias { commands, .. }) = with_aliases(&aliases, || parse_command("b"))
+    else {
+        panic!("b is an alias");
+    };
+    assert_eq!(
+        commands,
+        [
+            Command::Dot { x: 1, y: 1 },
+            Command::Dot { x: 2, y: 2 },
+            Command::Dot { x: 1, y: 1 },
+        ]
+    );
+}
+
+#[test]
+fn test_aliases_naming_each_other_dont_loop() {
+    let mut aliases = Aliases::new();
+    aliases.define("ping", "pong").unwrap();
+    aliases.define("pong", "ping").unwrap();
+    with_aliases(&aliases, || assert_eq!(parse_command("ping"), None));
+}
+
+#[test]
+fn test_alias_expanding_to_too_many_commands() {
+    let mut aliases = Aliases::new();
+    aliases.define("a", &"dot 1,1; ".repeat(8)).unwrap();
+    aliases.define("b", &"a; ".repeat(8)).unwrap();
+    aliases.define("c", &"b; ".repeat(8)).unwrap();
+    with_aliases(&aliases, || {
+        assert!(parse_command("b").is_some());
+        assert_eq!(parse_command("c"), None);
+    });
+}
+
+#[test]
+fn test_too_many_aliases() {
+    let mut aliases = Aliases::new();
+    for i in 0..MAX_ALIASES {
+        aliases.define(&format!("a{}", i), "clear").unwrap();
+    }
+    assert_eq!(
+        aliases.define("one-more", "clear"),
+        Err(format!("too many aliases (max {})", MAX_ALIASES))
+    );
+    // Redefining one is fine
+    assert!(aliases.define("a0", "dot 1,1").is_ok());
+    assert!(aliases.remove("a0"));
+    assert!(!aliases.remove("a0"));
+    assert_eq!(aliases.len(), MAX_ALIASES - 1);
+}
+
+#[test]
+fn test_alias_response() {
+    let ok = || Ok(CommandOutput::Ok);
+    assert_eq!(alias_response([ok(), ok()]), ok());
+    assert_eq!(
+        alias_response([ok(), reply("a"), reply("b")]),
+        reply("a; b")
+    );
+    assert_eq!(alias_response([reply("a"), failed("no")]), failed("no"));
+}
+
+// ===================
+// AppState Tests
+// ===================
+
+#[test]
+fn test_alias_draws_its_commands() {
+    let mut state = AppState::new();
+    let mut expected = AppState::new();
+    assert_eq!(
+        run(
+            &mut state,
+            r#"alias rl "rect 10,40 100,100; line 10,40 100,100""#
+        ),
+        Ok(CommandOutput::Ok)
+    );
+    assert_eq!(run(&mut state, "rl"), Ok(CommandOutput::Ok));
+    run(&mut expected, "rect 10,40 100,100").unwrap();
+    run(&mut expected, "line 10,40 100,100").unwrap();
+    assert_eq!(state.canvas, expected.canvas);
+    assert_eq!(state.display_list, expected.display_list);
+}
+
+#[test]
+fn test_alias_uses_settings_of_the_moment() {
+    let mut state = AppState::new();
+    run(&mut state, r#"alias spot "color 2; dot 100,100""#).unwrap();
+    run(&mut state, "palette set 2 #123456").unwrap();
+    run(&mut state, "spot").unwrap();
+    assert_eq!(state.canvas[100 * WIDTH + 100], 0x123456);
+}
+
+#[test]
+fn test_alias_outputs_and_errors() {
+    let mut state = AppState::new();
+    run(&mut state, r#"alias info "size 4; state; var get x""#).unwrap();
+    assert_eq!(run(&mut state, "info"), failed("no variable x"));
+    // Commands before the error still ran
+    assert_eq!(state.brush_size, 4);
+    run(&mut state, "var set x 7").unwrap();
+    assert_eq!(
+        run(&mut state, "info"),
+        Ok(CommandOutput::Text(
+            "edge:0 fill:none size:4; 7".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_alias_get_and_unalias() {
+    let mut state = AppState::new();
+    assert_eq!(run(&mut state, "alias rl"), failed("no alias rl"));
+    assert_eq!(run(&mut state, "unalias rl"), failed("no alias rl"));
+    run(&mut state, r#"alias rl "dot 1,40; dot 2,40""#).unwrap();
+    assert_eq!(
+        run(&mut state, "alias rl"),
+        Ok(CommandOutput::Text(r#""dot 1,40; dot 2,40""#.to_string()))
+    );
+    run(&mut state, r#"alias rl "clear""#).unwrap();
+    assert_eq!(
+        run(&mut state, "alias rl"),
+        Ok(CommandOutput::Text(r#""clear""#.to_string()))
+    );
+    assert_eq!(run(&mut state, "unalias rl"), Ok(CommandOutput::Ok));
+    assert_eq!(
+        run(&mut state, "rl"),
+        Err(CommandError::Unknown("rl".to_string()))
+    );
+}
+
+#[test]
+fn test_alias_definition_is_checked() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, r#"alias rl "rect 10,40; line 1,1 2,2""#),
+        failed("alias rl: invalid arguments for 'rect'")
+    );
+    assert_eq!(
+        run(&mut state, r#"alias rl "dot 1,1; bogus""#),
+        failed("alias rl: unknown command 'bogus'")
+    );
+    assert_eq!(
+        run(&mut state, r#"alias rl "dot 1,1; rl""#),
+        failed("alias rl can't use itself")
+    );
+    assert_eq!(
+        run(&mut state, r#"alias clear "dot 1,1""#),
+        failed("'clear' is already a command")
+    );
+    assert!(state.aliases.is_empty());
+    // An alias may use one defined before it
+    run(&mut state, r#"alias one "dot 1,40""#).unwrap();
+    assert_eq!(
+        run(&mut state, r#"alias two "one; one""#),
+        Ok(CommandOutput::Ok)
+    );
+}
+
+#[test]
+fn test_alias_is_not_a_pane_command() {
+    let mut state = AppState::new();
+    run(&mut state, r#"alias rl "dot 1,1""#).unwrap();
+    assert_eq!(
+        run(&mut state, "@1 rl"),
+        failed("@1 only prefixes drawing commands")
+    );
+    // Pane commands may be in an alias
+    run(&mut state, "panes 2x1").unwrap();
+    run(&mut state, r#"alias right "@2 dot 0,0""#).unwrap();
+    run(&mut state, "right").unwrap();
+    let pane = state.pane(2).unwrap();
+    assert_eq!(state.canvas[pane.y * WIDTH + pane.x], BLACK);
+}
+
+// ===================
+// Argument Tests
+// ===================
+
+#[test]
+fn test_alias_params() {
+    assert_eq!(alias_params("clear"), 0);
+    assert_eq!(alias_params("rect $1,$2 $3,$4"), 4);
+    assert_eq!(alias_params("dot $2,40"), 2);
+    assert_eq!(alias_params("dot $0,40; dot $x,40"), 0);
+    // One digit only: `$12` is `$1` then `2`
+    assert_eq!(alias_params("dot $12,40"), 1);
+}
+
+#[test]
+fn test_alias_args() {
+    assert_eq!(alias_args(""), Some(vec![]));
+    assert_eq!(alias_args("  10 40\t100 "), Some(vec!["10", "40", "100"]));
+    assert_eq!(
+        alias_args(r#"10 "hello world" 3"#),
+        Some(vec!["10", r#""hello world""#, "3"])
+    );
+    assert_eq!(alias_args(r#"10 "open"#), None);
+    assert_eq!(alias_args(r#""a"b"#), None);
+}
+
+#[test]
+fn test_substitute_args() {
+    assert_eq!(
+        substitute_args("rect $1,$2 $3,$4", &["10", "40", "100", "90"]),
+        "rect 10,40 100,90"
+    );
+    assert_eq!(substitute_args("dot $12,40", &["7"]), "dot 72,40");
+    // Missing arguments and other dollars stay as written
+    assert_eq!(substitute_args("a $2 $ $x $", &["1"]), "a $2 $ $x $");
+    // Arguments aren't substituted again
+    assert_eq!(substitute_args("$1 $2", &["$2", "b"]), "$2 b");
+}
+
+#[test]
+fn test_alias_use_with_arguments() {
+    let mut aliases = Aliases::new();
+    aliases.define("box", "rect $1,$2 $3,$4").unwrap();
+    with_aliases(&aliases, || {
+        let cmd = parse_command("box 10 40 100 90").unwrap();
+        assert_eq!(
+            cmd,
+            Command::RunAlias {
+                name: "box".to_string(),
+                args: ["10", "40", "100", "90"].map(String::from).to_vec(),
+                commands: vec![Command::Rect {
+                    x1: 10,
+                    y1: 40,
+                    x2: 100,
+                    y2: 90
+                }],
+            }
+        );
+        assert_eq!(cmd.to_string(), "box 10 40 100 90");
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+        // Too few, too many, or ones that don't make a command
+        for line in ["box", "box 10 40 100", "box 10 40 100 90 5", "box a b c d"] {
+            assert_eq!(
+                parse_command_line(line),
+                Err(CommandError::Failed(
+                    "alias 'box' takes 4 arguments, and its commands must parse".to_string()
+                )),
+                "{}",
+                line
+            );
+        }
+    });
+}
+
+#[test]
+fn test_alias_arguments_pass_to_nested_aliases() {
+    let mut aliases = Aliases::new();
+    aliases.define("box", "rect $1,$2 $3,$4").unwrap();
+    aliases.define("sq", "box $1 $2 $3 $3").unwrap();
+    let cmd = with_aliases(&aliases, || parse_command("sq 40 50 90")).unwrap();
+    let Command::RunAlias { commands, .. } = cmd else {
+        panic!("sq is an alias");
+    };
+    assert_eq!(
+        commands,
+        [Command::Rect {
+            x1: 40,
+            y1: 50,
+            x2: 90,
+            y2: 90
+        }]
+    );
+}
+
+#[test]
+fn test_alias_draws_with_arguments() {
+    let mut state = AppState::new();
+    run(
+        &mut state,
+        r#"alias label "rect $1,$2 $3,$4; obj text $1,$2 $5""#,
+    )
+    .unwrap();
+    assert_eq!(
+        run(&mut state, r#"label 10 40 100 90 "Total: 5""#),
+        Ok(CommandOutput::Text("id:1".to_string()))
+    );
+    let mut expected = AppState::new();
+    run(&mut expected, "rect 10,40 100,90").unwrap();
+    run(&mut expected, r#"obj text 10,40 "Total: 5""#).unwrap();
+    assert_eq!(state.render(), expected.render());
+}
+
+#[test]
+fn test_alias_arguments_cant_add_commands() {
+    let mut state = AppState::new();
+    run(&mut state, r#"alias remember "var set note $1""#).unwrap();
+    run(&mut state, "dot 100,100").unwrap();
+    run(&mut state, r#"remember "a; clear""#).unwrap();
+    assert_eq!(
+        run(&mut state, "var get note"),
+        Ok(CommandOutput::Text("a; clear".to_string()))
+    );
+    assert_eq!(state.canvas[100 * WIDTH + 100], BLACK);
+}
+
+#[test]
+fn test_alias_lines_with_arguments_are_checked_when_used() {
+    let mut state = AppState::new();
+    // `size $1` can't be checked until there is a size
+    assert_eq!(
+        run(&mut state, r#"alias pen "size $1; dot 100,100""#),
+        Ok(CommandOutput::Ok)
+    );
+    assert_eq!(
+        run(&mut state, "pen 99"),
+        failed("alias 'pen' takes 1 argument, and its commands must parse")
+    );
+    assert_eq!(run(&mut state, "pen 5"), Ok(CommandOutput::Ok));
+    assert_eq!(state.brush_size, 5);
+    // Lines without arguments are still checked when it is defined
+    assert_eq!(
+        run(&mut state, r#"alias pen "size $1; dot 100""#),
+        failed("alias pen: invalid arguments for 'dot'")
+    );
+}
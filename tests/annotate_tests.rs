@@ -0,0 +1,135 @@
+mod common;
+
+use common::scratch;
+use displai::*;
+use image::{Rgba, RgbaImage};
+
+fn annotation(width: usize, height: usize) -> Annotation {
+    Annotation {
+        output: "shot-annotated.png".to_string(),
+        width,
+        height,
+    }
+}
+
+// ===================
+// Output Path Tests
+// ===================
+
+#[test]
+fn test_annotated_path() {
+    assert_eq!(annotated_path("shot.png"), "shot-annotated.png");
+    assert_eq!(
+        annotated_path("/tmp/screens/bug 12.JPG"),
+        "/tmp/screens/bug 12-annotated.JPG"
+    );
+    assert_eq!(
+        annotated_path("/tmp/archive.tar.webp"),
+        "/tmp/archive.tar-annotated.webp"
+    );
+    // Formats snapshots can't write are saved as PNG
+    assert_eq!(annotated_path("cat.gif"), "cat-annotated.png");
+    assert_eq!(annotated_path("screenshot"), "screenshot-annotated.png");
+}
+
+#[test]
+fn test_annotation_new() {
+    let img = RgbaImage::new(640, 360);
+    assert_eq!(
+        Annotation::new("/tmp/shot.png", &img),
+        Annotation {
+            output: "/tmp/shot-annotated.png".to_string(),
+            width: 640,
+            height: 360,
+        }
+    );
+}
+
+// ===================
+// Window Size Tests
+// ===================
+
+#[test]
+fn test_window_fits_the_image() {
+    let dims = annotation(1280, 720).window_size().unwrap();
+    assert_eq!((dims.width, dims.canvas_height()), (1280, 720));
+}
+
+#[test]
+fn test_small_images_get_the_smallest_window() {
+    let dims = annotation(100, 20).window_size().unwrap();
+    assert_eq!(dims, Dimensions::new(MIN_WIDTH, MIN_HEIGHT).unwrap());
+}
+
+#[test]
+fn test_unsuitable_images_are_refused() {
+    let err = annotation(MAX_DIMENSION + 1, 100)
+        .window_size()
+        .unwrap_err();
+    assert!(
+        err.starts_with(&format!(
+            "image {}x100 is too large to annotate",
+            MAX_DIMENSION + 1
+        )),
+        "{}",
+        err
+    );
+    assert!(annotation(1000, MAX_DIMENSION).window_size().is_err());
+    assert!(annotation(0, 0).window_size().is_err());
+}
+
+// ===================
+// Saving Tests
+// ===================
+
+#[test]
+fn test_save_crops_to_the_image() {
+    let dir = scratch("save");
+    let annotation = Annotation {
+        output: dir.join("shot-annotated.png").display().to_string(),
+        width: 300,
+        height: 100,
+    };
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    buffer[(CANVAS_TOP + 99) * WIDTH + 299] = RED;
+    buffer[(CANVAS_TOP + 100) * WIDTH + 300] = BLUE;
+    annotation.save(&buffer).unwrap();
+    let img = image::open(&annotation.output).unwrap().to_rgb8();
+    assert_eq!(img.dimensions(), (300, 100));
+    assert_eq!(img.get_pixel(299, 99).0, [0xE0, 0x40, 0x40]);
+    assert_eq!(img.get_pixel(0, 0).0, [0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_save_reports_a_missing_directory() {
+    let annotation = Annotation {
+        output: "/nonexistent/shot-annotated.png".to_string(),
+        width: 10,
+        height: 10,
+    };
+    let buffer = vec![WHITE; WIDTH * HEIGHT];
+    assert_eq!(
+        annotation.save(&buffer),
+        Err("directory /nonexistent does not exist".to_string())
+    );
+}
+
+#[test]
+fn test_annotated_copy_round_trips_the_image() {
+    let dir = scratch("round_trip");
+    let path = dir.join("shot.png");
+    let img = RgbaImage::from_fn(600, 250, |x, y| Rgba([x as u8, y as u8, 7, 255]));
+    img.save(&path).unwrap();
+
+    let annotation = Annotation::new(&path.display().to_string(), &img);
+    let dims = annotation.window_size().unwrap();
+    let mut canvas = Canvas::with_size(dims);
+    with_dimensions(dims, || {
+        paste_image(&mut canvas, &img, 0, CANVAS_TOP, 1.0);
+        annotation.save(&canvas).unwrap();
+    });
+    let saved = image::open(dir.join("shot-annotated.png"))
+        .unwrap()
+        .to_rgba8();
+    assert!(saved == img);
+}
@@ -0,0 +1,238 @@
+mod common;
+
+use common::{pixel, run};
+use displai::*;
+
+/// Whether a pixel is neither of the two colors it was drawn with
+fn is_blended(color: u32) -> bool {
+    color != BLACK && color != WHITE
+}
+
+/// The canvas after running `lines`, with anti-aliasing on or off
+fn drawn(aa: bool, lines: &[&str]) -> AppState {
+    let mut state = AppState::new();
+    run(&mut state, if aa { "aa on" } else { "aa off" });
+    for line in lines {
+        run(&mut state, line);
+    }
+    state
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_aa() {
+    assert_eq!(parse_command("aa on"), Some(Command::Antialias(true)));
+    assert_eq!(parse_command("aa off"), Some(Command::Antialias(false)));
+    for cmd in [Command::Antialias(true), Command::Antialias(false)] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert_eq!(cmd.name(), "aa");
+        assert!(!cmd.is_mutating());
+        assert!(validate(&cmd).is_ok());
+    }
+
+    // Invalid formats
+    assert_eq!(parse_command("aa"), None);
+    assert_eq!(parse_command("aa yes"), None);
+    assert_eq!(parse_command("aa on off"), None);
+}
+
+#[test]
+fn test_aa_sets_state() {
+    let mut state = AppState::new();
+    assert!(!state.antialias);
+    assert_eq!(run(&mut state, "aa on"), None);
+    assert!(state.antialias);
+    assert_eq!(run(&mut state, "aa off"), None);
+    assert!(!state.antialias);
+}
+
+#[test]
+fn test_with_antialiasing_restores_setting() {
+    assert!(!antialiasing());
+    with_antialiasing(true, || {
+        assert!(antialiasing());
+        with_antialiasing(false, || assert!(!antialiasing()));
+        assert!(antialiasing());
+    });
+    assert!(!antialiasing());
+}
+
+#[test]
+fn test_blend() {
+    assert_eq!(blend(WHITE, BLACK, 0.5), 0x808080);
+    assert_eq!(blend(0x000000, 0xFF0000, 1.0), 0xFF0000);
+    assert_eq!(blend(0x123456, 0xFF0000, 0.0), 0x123456);
+}
+
+// ===================
+// Line Tests
+// ===================
+
+#[test]
+fn test_slanted_line_is_soft_only_with_aa() {
+    let hard = drawn(false, &["line 100,100 300,180"]);
+    assert!(!hard.canvas.iter().any(|&p| is_blended(p)));
+
+    let soft = drawn(true, &["line 100,100 300,180"]);
+    let blended = soft.canvas.iter().filter(|&&p| is_blended(p)).count();
+    assert!(blended > 100, "{} blended pixels", blended);
+    // Ends and the middle stay fully inked
+    assert_eq!(pixel(&soft, 100, 100), BLACK);
+    assert_eq!(pixel(&soft, 200, 140), BLACK);
+    assert_eq!(pixel(&soft, 300, 180), BLACK);
+}
+
+#[test]
+fn test_straight_lines_match_hard_lines() {
+    let lines = ["line 100,100 300,100", "line 150,80 150,300"];
+    assert_eq!(drawn(true, &lines).canvas, drawn(false, &lines).canvas);
+
+    // A wide brush's sides are exact too; only its round ends are smoother
+    let lines = ["size 4", "line 100,100 300,100", "line 150,80 150,300"];
+    let (soft, hard) = (drawn(true, &lines), drawn(false, &lines));
+    for i in 90..=110 {
+        assert_eq!(pixel(&soft, 200, i), pixel(&hard, 200, i));
+        assert_eq!(pixel(&soft, i + 50, 200), pixel(&hard, i + 50, 200));
+    }
+    assert_ne!(soft.canvas, hard.canvas);
+}
+
+#[test]
+fn test_overlapping_pieces_blend_once() {
+    let mut once = vec![WHITE; WIDTH * HEIGHT];
+    let mut twice = once.clone();
+    with_antialiasing(true, || {
+        draw_covered(&mut once, BLACK, |_| {
+            cover_capsule(100.0, 100.0, 300.0, 180.0, 0.5)
+        });
+        draw_covered(&mut twice, BLACK, |_| {
+            cover_capsule(100.0, 100.0, 300.0, 180.0, 0.5);
+            cover_capsule(100.0, 100.0, 300.0, 180.0, 0.5);
+        });
+    });
+    assert_eq!(once, twice);
+
+    // Separate shapes each blend in, darkening the edges they share
+    with_antialiasing(true, || {
+        draw_line(&mut twice, 100, 100, 300, 180, BLACK);
+    });
+    assert_ne!(once, twice);
+}
+
+#[test]
+fn test_covered_drawing_is_hard_when_off() {
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    // Coverage goes nowhere without anti-aliasing...
+    draw_covered(&mut buffer, BLACK, |_| cover_disc(100.0, 100.0, 5.0));
+    assert!(buffer.iter().all(|&p| p == WHITE));
+    // ...and the pieces draw as usual
+    draw_covered(&mut buffer, BLACK, |buffer| {
+        draw_circle(buffer, 100, 100, 5, BLACK)
+    });
+    assert_eq!(buffer[100 * WIDTH + 104], BLACK);
+    assert!(!buffer.iter().any(|&p| is_blended(p)));
+}
+
+// ===================
+// Shape Tests
+// ===================
+
+#[test]
+fn test_circle_outline_and_fill_are_soft() {
+    let state = drawn(true, &["fill #FF0000", "size 2", "circle 300,300 80"]);
+    assert_eq!(pixel(&state, 300, 300), 0xFF0000, "Center");
+    assert_eq!(pixel(&state, 300, 300 - 80), BLACK, "On the outline");
+    assert_eq!(pixel(&state, 300, 150), WHITE, "Well outside");
+    let blended = (200..400)
+        .flat_map(|y| (200..400).map(move |x| (x, y)))
+        .filter(|&(x, y)| is_blended(pixel(&state, x, y)) && pixel(&state, x, y) != 0xFF0000)
+        .count();
+    assert!(blended > 50, "{} blended pixels", blended);
+}
+
+#[test]
+fn test_axis_aligned_fills_stay_crisp() {
+    let lines = [
+        "edge none",
+        "fill #0000FF",
+        "rect 100,100 200,180",
+        "polygon 300,100 400,100 400,200 300,200",
+    ];
+    assert_eq!(drawn(true, &lines).canvas, drawn(false, &lines).canvas);
+}
+
+#[test]
+fn test_rounded_rect_fill_softens_only_corners() {
+    let state = drawn(
+        true,
+        &["edge none", "fill #000000", "rrect 100,100 300,200 20"],
+    );
+    // Straight sides are exact
+    assert_eq!(pixel(&state, 200, 100), BLACK);
+    assert_eq!(pixel(&state, 200, 99), WHITE);
+    assert_eq!(pixel(&state, 100, 150), BLACK);
+    assert_eq!(pixel(&state, 99, 150), WHITE);
+    // Corners are not
+    let corner = (100..120)
+        .flat_map(|y| (100..120).map(move |x| (x, y)))
+        .filter(|&(x, y)| is_blended(pixel(&state, x, y)))
+        .count();
+    assert!(corner > 5, "{} blended corner pixels", corner);
+}
+
+#[test]
+fn test_slanted_polygon_fill_is_soft() {
+    let state = drawn(
+        true,
+        &[
+            "edge none",
+            "fill #000000",
+            "polygon 100,100 300,150 150,300",
+        ],
+    );
+    assert_eq!(pixel(&state, 180, 180), BLACK);
+    assert!(state.canvas.iter().any(|&p| is_blended(p)));
+}
+
+#[test]
+fn test_mouse_shapes_use_aa() {
+    let mut state = AppState::new();
+    run(&mut state, "aa on");
+    state.draw_shape(ToolMode::Circle, 100, 100, 200, 200);
+    assert!(state.canvas.iter().any(|&p| is_blended(p)));
+}
+
+#[test]
+fn test_aa_shapes_near_edges_do_not_panic() {
+    let mut state = AppState::new();
+    for line in [
+        "aa on",
+        "size 20",
+        "line 0,30 799,539",
+        "circle 0,30 400",
+        "oval 799,539 300,20",
+        "fill #FF0000",
+        "triangle 0,30 799,539",
+        "rrect 0,30 799,539 100",
+        "arrow 0,30 799,539 60 both",
+        "polygon 0,30 799,30 400,539",
+        "star 400,300 1000 5 7",
+    ] {
+        run(&mut state, line);
+    }
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    with_antialiasing(true, || {
+        draw_brush_line(&mut buffer, 0, 0, usize::MAX, usize::MAX, BLACK, 5);
+        draw_circle(&mut buffer, usize::MAX, 100, 5, BLACK);
+        fill_polygon(
+            &mut buffer,
+            &[(0, 0), (usize::MAX, 40), (10, usize::MAX)],
+            BLACK,
+        );
+        fill_oval(&mut buffer, 0, 0, usize::MAX, usize::MAX, BLACK);
+        fill_triangle(&mut buffer, 0, 0, usize::MAX, usize::MAX, BLACK);
+    });
+}
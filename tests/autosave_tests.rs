@@ -0,0 +1,215 @@
+mod common;
+
+use common::{run, scratch};
+use displai::*;
+use std::time::{Duration, Instant};
+
+/// Wait for `autosave`'s save in progress to be written
+fn settle(autosave: &Autosave) {
+    while autosave.is_writing() {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn lines(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+// ===================
+// Settings Tests
+// ===================
+
+#[test]
+fn test_default_session_settings() {
+    assert_eq!(
+        session_settings(&AppState::new()),
+        lines(&[
+            "edge 0",
+            "fill none",
+            "fill-gradient off",
+            "size 1",
+            "stroke-style solid",
+            "brush shape circle",
+            "shadow off",
+            "snap off",
+            "aa off",
+            "blend normal",
+            "tool brush",
+        ])
+    );
+}
+
+#[test]
+fn test_session_settings_round_trip() {
+    let mut state = AppState::new();
+    for line in [
+        "palette set 3 #123456",
+        "edge 3",
+        "fill #FF8000",
+        "fill-gradient linear 1 2 45",
+        "size 6",
+        "stroke-style dashed",
+        "brush shape square",
+        "shadow on 3,4 #000000 2",
+        "snap on 12",
+        "aa on",
+        "blend multiply",
+    ] {
+        assert_eq!(run(&mut state, line), None, "{}", line);
+    }
+    state.current_tool = ToolMode::RoundedRect;
+    let settings = session_settings(&state);
+    assert_eq!(settings[0], "palette set 3 #123456");
+    assert!(settings.contains(&"edge 3".to_string()));
+    assert_eq!(settings.last().unwrap(), "tool rrect");
+
+    let mut restored = AppState::new();
+    apply_session_settings(&mut restored, &settings).unwrap();
+    assert_eq!(restored.palette, state.palette);
+    assert_eq!(restored.edge_color, Some(0x123456));
+    assert_eq!(restored.fill_color, state.fill_color);
+    assert_eq!(restored.gradient, state.gradient);
+    assert_eq!(restored.brush_size, 6);
+    assert_eq!(restored.stroke_style, state.stroke_style);
+    assert_eq!(restored.brush_shape, state.brush_shape);
+    assert_eq!(restored.shadow, state.shadow);
+    assert_eq!(restored.snap, Some(12.0));
+    assert!(restored.antialias);
+    assert_eq!(restored.blend, state.blend);
+    assert_eq!(restored.current_tool, ToolMode::RoundedRect);
+    assert_eq!(session_settings(&restored), settings);
+}
+
+#[test]
+fn test_session_settings_errors() {
+    let mut state = AppState::new();
+    assert_eq!(
+        apply_session_settings(&mut state, &lines(&["size 4", "bogus 1"])),
+        Err("line 2: bogus 1: unknown command 'bogus'".to_string())
+    );
+    // Lines before the bad one stay applied
+    assert_eq!(state.brush_size, 4);
+
+    // Only settings are applied: a session file can't draw
+    let before = state.canvas.clone();
+    assert_eq!(
+        apply_session_settings(&mut state, &lines(&["rect 10,40 50,80"])),
+        Err("line 1: rect 10,40 50,80: not a tool setting".to_string())
+    );
+    assert!(state.canvas == before);
+}
+
+// ===================
+// Saving and Reading Tests
+// ===================
+
+#[test]
+fn test_write_and_read_session() {
+    let dir = scratch("round_trip");
+    let dir_name = dir.display().to_string();
+    assert_eq!(read_session(&dir_name), Ok(None));
+
+    let mut state = AppState::new();
+    run(&mut state, "fill 2");
+    run(&mut state, "rect 100,100 200,200");
+    let settings = session_settings(&state);
+    write_session(
+        &dir_name,
+        &state.canvas,
+        state.canvas.dimensions(),
+        &settings,
+    )
+    .unwrap();
+    let saved = read_session(&dir_name).unwrap().unwrap();
+    assert_eq!(saved.settings, settings);
+    assert_eq!(saved.canvas.dimensions(), (800, 510));
+    // Canvas rows start at CANVAS_TOP, so window y=150 is image row 120
+    assert_eq!(saved.canvas.get_pixel(150, 120).0, [0xE0, 0x40, 0x40, 0xFF]);
+    // Nothing is left behind from writing beside the files
+    let mut names: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec![SESSION_CANVAS, SESSION_SETTINGS]);
+
+    // A canvas without settings restores with the default tools
+    std::fs::remove_file(dir.join(SESSION_SETTINGS)).unwrap();
+    assert!(read_session(&dir_name)
+        .unwrap()
+        .unwrap()
+        .settings
+        .is_empty());
+}
+
+#[test]
+fn test_unreadable_session_fails() {
+    let dir = scratch("unreadable");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(SESSION_CANVAS), "not a png").unwrap();
+    let err = read_session(&dir.display().to_string()).unwrap_err();
+    assert!(err.starts_with("cannot load "), "{}", err);
+}
+
+// ===================
+// Autosave Tests
+// ===================
+
+#[test]
+fn test_autosave_waits_for_the_interval() {
+    let dir = scratch("interval");
+    let dir_name = dir.display().to_string();
+    let start = Instant::now();
+    let mut autosave = Autosave::new(&dir_name, Duration::from_secs(30), start);
+    let mut state = AppState::new();
+    run(&mut state, "dot 100,100");
+
+    assert_eq!(
+        autosave.offer(&state, start + Duration::from_secs(29)),
+        Ok(false)
+    );
+    assert!(!dir.join(SESSION_CANVAS).exists());
+    assert_eq!(
+        autosave.offer(&state, start + Duration::from_secs(30)),
+        Ok(true)
+    );
+    settle(&autosave);
+    // Nothing changed since
+    assert_eq!(
+        autosave.offer(&state, start + Duration::from_secs(60)),
+        Ok(false)
+    );
+    run(&mut state, "size 3");
+    assert_eq!(
+        autosave.offer(&state, start + Duration::from_secs(75)),
+        Ok(false)
+    );
+    assert_eq!(
+        autosave.offer(&state, start + Duration::from_secs(90)),
+        Ok(true)
+    );
+    autosave.finish(&state).unwrap();
+
+    let saved = read_session(&dir_name).unwrap().unwrap();
+    assert!(saved.settings.contains(&"size 3".to_string()));
+    assert_eq!(saved.canvas.get_pixel(100, 70).0, [0, 0, 0, 0xFF]);
+}
+
+#[test]
+fn test_autosave_finish_saves_changes() {
+    let dir = scratch("finish");
+    let dir_name = dir.display().to_string();
+    let mut state = AppState::new();
+    let mut autosave = Autosave::new(&dir_name, AUTOSAVE_INTERVAL, Instant::now());
+    autosave.mark_saved(&state);
+    // An unchanged session isn't written
+    autosave.finish(&state).unwrap();
+    assert!(!dir.join(SESSION_CANVAS).exists());
+
+    let mut autosave = Autosave::new(&dir_name, AUTOSAVE_INTERVAL, Instant::now());
+    autosave.mark_saved(&state);
+    run(&mut state, "edge 4");
+    autosave.finish(&state).unwrap();
+    let saved = read_session(&dir_name).unwrap().unwrap();
+    assert!(saved.settings.contains(&"edge 4".to_string()));
+}
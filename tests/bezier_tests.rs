@@ -0,0 +1,205 @@
+mod common;
+
+use common::run;
+use displai::*;
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_bezier() {
+    let quadratic = Command::Bezier(vec![(100, 300), (200, 100), (300, 300)]);
+    assert_eq!(
+        parse_command("bezier 100,300 200,100 300,300"),
+        Some(quadratic.clone())
+    );
+    let cubic = Command::Bezier(vec![(100, 300), (150, 100), (250, 400), (300, 200)]);
+    assert_eq!(
+        parse_command("bezier 100,300 150,100 250,400 300,200"),
+        Some(cubic.clone())
+    );
+    assert_eq!(cubic.to_string(), "bezier 100,300 150,100 250,400 300,200");
+    for cmd in [quadratic, cubic] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert_eq!(cmd.name(), "bezier");
+        assert!(cmd.is_mutating());
+        assert!(validate(&cmd).is_ok());
+    }
+
+    // Invalid formats
+    assert_eq!(parse_command("bezier"), None);
+    assert_eq!(parse_command("bezier 100,300 300,300"), None);
+    assert_eq!(parse_command("bezier 1,40 2,40 3,40 4,40 5,40"), None);
+    assert_eq!(parse_command("bezier 1,40 2,40 3"), None);
+}
+
+#[test]
+fn test_validate_bezier() {
+    // Every control point must be on the canvas, even though the curve
+    // never reaches a bent one
+    assert!(validate(&Command::Bezier(vec![(100, 300), (200, 5), (300, 300)])).is_err());
+    assert!(validate(&Command::Bezier(vec![(100, 300), (200, 50), (300, 300)])).is_ok());
+}
+
+// ===================
+// Curve Tests
+// ===================
+
+#[test]
+fn test_bezier_points_run_end_to_end() {
+    let points = bezier_points(&[(100, 300), (200, 100), (300, 300)]);
+    assert_eq!(points.first(), Some(&(100, 300)));
+    assert_eq!(points.last(), Some(&(300, 300)));
+    // Halfway along, a quadratic is halfway to its control point
+    assert!(points.contains(&(200, 200)));
+    // Neighboring points touch, so the stroke has no gaps
+    for pair in points.windows(2) {
+        assert!(pair[0] != pair[1]);
+        assert!(pair[0].0.abs_diff(pair[1].0) <= 2 && pair[0].1.abs_diff(pair[1].1) <= 2);
+    }
+}
+
+#[test]
+fn test_bezier_points_cubic_is_symmetric() {
+    let points = bezier_points(&[(100, 300), (100, 100), (300, 100), (300, 300)]);
+    assert_eq!(points.first(), Some(&(100, 300)));
+    assert_eq!(points.last(), Some(&(300, 300)));
+    // The peak is three quarters of the way to the control points
+    assert!(points.contains(&(200, 150)));
+    assert!(points.iter().all(|&(_, y)| y >= 150));
+}
+
+#[test]
+fn test_bezier_points_other_counts_pass_through() {
+    assert_eq!(
+        bezier_points(&[(10, 40), (20, 50)]),
+        vec![(10, 40), (20, 50)]
+    );
+    assert_eq!(bezier_points(&[(10, 40); 3]), vec![(10, 40)]);
+}
+
+#[test]
+fn test_bezier_draws_with_brush() {
+    let mut state = AppState::new();
+    run(&mut state, "edge #FF0000");
+    run(&mut state, "size 5");
+    run(&mut state, "bezier 100,300 200,100 300,300");
+    assert_eq!(state.canvas[200 * WIDTH + 200], 0xFF0000);
+    // The brush widens the stroke either side of the curve
+    assert_eq!(state.canvas[202 * WIDTH + 200], 0xFF0000);
+    // Nothing along the straight chord between the ends
+    assert_eq!(state.canvas[300 * WIDTH + 200], WHITE);
+    assert_eq!(state.canvas[100 * WIDTH + 200], WHITE, "Control point");
+}
+
+// ===================
+// SVG Tests
+// ===================
+
+#[test]
+fn test_to_svg_bezier() {
+    let mut state = AppState::new();
+    run(&mut state, "bezier 100,300 200,100 300,300");
+    run(&mut state, "bezier 100,300 150,100 250,400 300,200");
+    let svg = state.display_list.to_svg();
+    assert!(svg.contains("<path d=\"M 100 300 Q 200 100 300 300\" fill=\"none\""));
+    assert!(svg.contains("<path d=\"M 100 300 C 150 100 250 400 300 200\" fill=\"none\""));
+}
+
+// ===================
+// Bezier Tool Tests
+// ===================
+
+#[test]
+fn test_drag_then_bend_finishes_curve() {
+    let mut bezier = BezierBuilder::new();
+    bezier.press(100, 300);
+    bezier.drag(200, 300);
+    assert_eq!(bezier.release(300, 300), None);
+    assert!(!bezier.is_empty());
+
+    // The second drag moves the control point, and letting go finishes
+    bezier.press(200, 300);
+    bezier.drag(200, 150);
+    assert_eq!(
+        bezier.release(200, 100),
+        Some(vec![(100, 300), (200, 100), (300, 300)])
+    );
+    assert!(bezier.is_empty());
+}
+
+#[test]
+fn test_click_places_control_point() {
+    let mut bezier = BezierBuilder::new();
+    bezier.press(100, 300);
+    bezier.release(300, 300);
+    bezier.press(250, 200);
+    assert_eq!(
+        bezier.release(250, 200),
+        Some(vec![(100, 300), (250, 200), (300, 300)])
+    );
+}
+
+#[test]
+fn test_short_drag_places_nothing() {
+    let mut bezier = BezierBuilder::new();
+    bezier.press(100, 300);
+    assert_eq!(bezier.release(100 + MIN_BEZIER_DRAG - 1, 300), None);
+    assert!(bezier.is_empty());
+}
+
+#[test]
+fn test_cancel_discards_curve() {
+    let mut bezier = BezierBuilder::new();
+    bezier.press(100, 300);
+    bezier.release(300, 300);
+    bezier.cancel();
+    assert!(bezier.is_empty());
+    // The next press starts a new curve rather than bending the old one
+    bezier.press(150, 200);
+    assert_eq!(bezier.release(150, 200), None);
+    assert!(bezier.is_empty());
+}
+
+#[test]
+fn test_preview_draws_curve_and_handles() {
+    let mut bezier = BezierBuilder::new();
+    bezier.press(100, 300);
+    bezier.drag(300, 300);
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    bezier.draw_preview(&mut frame, BLACK);
+    assert_eq!(frame[300 * WIDTH + 200], BLACK, "Straight while dragging");
+
+    bezier.release(300, 300);
+    bezier.press(200, 100);
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    bezier.draw_preview(&mut frame, BLACK);
+    assert_eq!(frame[200 * WIDTH + 200], BLACK, "Curve");
+    assert_eq!(frame[200 * WIDTH + 150], BLACK, "Handle to the start");
+    assert_eq!(frame[300 * WIDTH + 200], WHITE, "No longer straight");
+}
+
+#[test]
+fn test_bezier_tool_button() {
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    draw_bottom_toolbar(&mut buffer, Some(BLACK), None, 1, ToolMode::Bezier);
+
+    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let index = TOOLBAR_TOOLS
+        .iter()
+        .position(|&tool| tool == ToolMode::Bezier)
+        .unwrap();
+    let bezier_x = BUTTON_MARGIN + index * (BUTTON_SIZE + BUTTON_MARGIN);
+    assert_eq!(
+        get_clicked_tool(bezier_x + BUTTON_SIZE / 2, row2_y + BUTTON_SIZE / 2),
+        Some(ToolMode::Bezier)
+    );
+    assert_eq!(buffer[row2_y * WIDTH + bezier_x], 0x4040E0);
+    let icon_pixels = (row2_y + 3..row2_y + BUTTON_SIZE - 2)
+        .flat_map(|y| (bezier_x + 3..bezier_x + BUTTON_SIZE - 2).map(move |x| (x, y)))
+        .filter(|&(x, y)| buffer[y * WIDTH + x] == BLACK)
+        .count();
+    assert!(icon_pixels > 10);
+    assert_eq!(ToolMode::Bezier.name(), "bezier");
+}
@@ -0,0 +1,332 @@
+mod common;
+
+use common::{drawn, pixel, run};
+use displai::*;
+
+/// Half-opaque black (`#00000080`) over white
+const HALF_GRAY: u32 = 0x7F7F7F;
+
+// ===================
+// Color Tests
+// ===================
+
+#[test]
+fn test_parse_rgba_colors() {
+    let half_red = with_alpha(0xFF0000, 0x80);
+    assert_eq!(
+        parse_command("edge #FF000080"),
+        Some(Command::Edge(Some(half_red)))
+    );
+    assert_eq!(
+        parse_command("fill #FF000080"),
+        Some(Command::Fill(Some(half_red)))
+    );
+    for line in ["edge #FF000080", "fill #12345600"] {
+        let cmd = parse_command(line).unwrap();
+        assert_eq!(cmd.to_string(), line);
+        assert!(validate(&cmd).is_ok());
+    }
+    // Fully opaque is the plain color
+    assert_eq!(
+        parse_command("edge #123456FF").unwrap().to_string(),
+        "edge #123456"
+    );
+    // Point colors take alpha too
+    assert_eq!(
+        parse_command("polyline 1,40 5,50:#00FF0040")
+            .unwrap()
+            .to_string(),
+        "polyline 1,40 5,50:#00FF0040"
+    );
+
+    // Invalid formats
+    assert_eq!(parse_command("edge #FF00008"), None);
+    assert_eq!(parse_command("edge #FF00008G"), None);
+    assert_eq!(parse_command("edge #+F000080"), None);
+    assert_eq!(parse_command("edge #FF0000800"), None);
+}
+
+#[test]
+fn test_alpha_helpers() {
+    assert_eq!(alpha(0x123456), 255);
+    assert!(is_opaque(0x123456));
+    let color = with_alpha(0x123456, 0x40);
+    assert_eq!(alpha(color), 0x40);
+    assert!(!is_opaque(color));
+    assert_eq!(color & 0xFFFFFF, 0x123456);
+    assert_eq!(hex_color(color), "#12345640");
+    assert_eq!(hex_color(0x123456), "#123456");
+    assert_eq!(with_alpha(color, 255), 0x123456);
+}
+
+#[test]
+fn test_opaque_only_colors_reject_alpha() {
+    assert_eq!(
+        validate(&parse_command("palette set 3 #FF000080").unwrap()),
+        Err("color #FF000080 must be opaque".to_string())
+    );
+    assert_eq!(
+        validate(&parse_command("shadow on 4,4 #00000080").unwrap()),
+        Err("shadow color #00000080 must be opaque".to_string())
+    );
+}
+
+#[test]
+fn test_state_reports_translucent_colors() {
+    let mut state = drawn(&["edge #00000080", "fill none"]);
+    assert_eq!(
+        run(&mut state, "state"),
+        Some("edge:#00000080 fill:none size:1".to_string())
+    );
+}
+
+// ===================
+// Compositing Tests
+// ===================
+
+#[test]
+fn test_translucent_fill_shows_canvas_through() {
+    let state = drawn(&[
+        "edge none",
+        "fill #000000",
+        "rect 100,100 150,150",
+        "fill #FFFFFF80",
+        "rect 125,100 200,150",
+    ]);
+    // White over black and over white
+    assert_eq!(pixel(&state, 130, 120), 0x808080);
+    assert_eq!(pixel(&state, 180, 120), WHITE);
+    assert_eq!(pixel(&state, 110, 120), BLACK);
+}
+
+#[test]
+fn test_overlapping_dots_composite_once() {
+    let state = drawn(&["edge #00000080", "size 5", "line 100,100 300,100"]);
+    for y in 97..=103 {
+        assert_eq!(pixel(&state, 200, y), HALF_GRAY, "row {}", y);
+    }
+    assert_eq!(pixel(&state, 200, 110), WHITE);
+}
+
+#[test]
+fn test_polyline_joins_composite_once() {
+    let state = drawn(&[
+        "edge #00000080",
+        "size 5",
+        "polyline 100,200 200,200 200,300",
+    ]);
+    assert_eq!(pixel(&state, 200, 200), HALF_GRAY);
+    assert_eq!(pixel(&state, 150, 200), HALF_GRAY);
+}
+
+#[test]
+fn test_edge_composites_over_fill() {
+    let state = drawn(&[
+        "edge #00000080",
+        "fill #00000080",
+        "size 3",
+        "rect 100,100 200,200",
+    ]);
+    assert_eq!(pixel(&state, 150, 150), HALF_GRAY);
+    // Edge over fill: half of the way again
+    assert_eq!(pixel(&state, 100, 150), 0x3F3F3F);
+}
+
+#[test]
+fn test_antialiased_translucent_shapes_never_exceed_their_alpha() {
+    let state = drawn(&[
+        "aa on",
+        "edge #00000080",
+        "size 6",
+        "circle 300,300 80",
+        "line 100,100 400,250",
+    ]);
+    // Where the line crosses the circle, two shapes overlap
+    let darkest = state
+        .canvas
+        .iter()
+        .filter(|&&p| p != WHITE)
+        .map(|&p| p & 0xFF)
+        .min()
+        .unwrap();
+    assert!(darkest >= 0x3F, "darkest channel {:X}", darkest);
+    let single = (CANVAS_TOP..540)
+        .flat_map(|y| (0..120).map(move |x| (x, y)))
+        .map(|(x, y)| pixel(&state, x, y) & 0xFF)
+        .min()
+        .unwrap();
+    assert_eq!(single, 0x7F);
+}
+
+#[test]
+fn test_translucent_bucket_fill() {
+    let mut state = drawn(&["fill #0000FF80"]);
+    assert_eq!(run(&mut state, "bucket 400,300"), None);
+    assert_eq!(pixel(&state, 400, 300), 0x7F7FFF);
+    assert_eq!(pixel(&state, 10, 40), 0x7F7FFF);
+    // Again: deeper still
+    run(&mut state, "bucket 400,300");
+    assert_eq!(pixel(&state, 400, 300), 0x3F3FFF);
+}
+
+#[test]
+fn test_translucent_text() {
+    let state = drawn(&["edge #00000080", "legend 100,100 entry \"ab\" #FFFFFF"]);
+    assert_eq!(pixel(&state, 110, 100), HALF_GRAY);
+}
+
+// ===================
+// Blend Mode Tests
+// ===================
+
+#[test]
+fn test_parse_blend() {
+    for mode in BlendMode::ALL {
+        let cmd = Command::Blend(mode);
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert_eq!(cmd.name(), "blend");
+        assert!(!cmd.is_mutating());
+        assert!(validate(&cmd).is_ok());
+    }
+    assert_eq!(
+        parse_command("blend multiply"),
+        Some(Command::Blend(BlendMode::Multiply))
+    );
+    assert_eq!(
+        Command::Blend(BlendMode::Screen).to_string(),
+        "blend screen"
+    );
+
+    // Invalid formats
+    assert_eq!(parse_command("blend"), None);
+    assert_eq!(parse_command("blend darken"), None);
+    assert_eq!(parse_command("blend multiply screen"), None);
+}
+
+#[test]
+fn test_blend_sets_state() {
+    let mut state = AppState::new();
+    assert_eq!(state.blend, BlendMode::Normal);
+    assert_eq!(run(&mut state, "blend overlay"), None);
+    assert_eq!(state.blend, BlendMode::Overlay);
+    run(&mut state, "blend normal");
+    assert_eq!(state.blend, BlendMode::Normal);
+}
+
+#[test]
+fn test_with_blend_mode_restores_mode() {
+    assert_eq!(blend_mode(), BlendMode::Normal);
+    with_blend_mode(BlendMode::Screen, || {
+        assert_eq!(blend_mode(), BlendMode::Screen);
+        with_blend_mode(BlendMode::Multiply, || {
+            assert_eq!(blend_mode(), BlendMode::Multiply)
+        });
+        assert_eq!(blend_mode(), BlendMode::Screen);
+    });
+    assert_eq!(blend_mode(), BlendMode::Normal);
+}
+
+#[test]
+fn test_composite_by_mode() {
+    assert_eq!(composite(0x123456, 0xABCDEF, 1.0), 0xABCDEF);
+    assert_eq!(composite(WHITE, with_alpha(BLACK, 0x80), 1.0), HALF_GRAY);
+    assert_eq!(composite(WHITE, BLACK, 0.5), 0x808080);
+    with_blend_mode(BlendMode::Multiply, || {
+        assert_eq!(composite(0xFF8000, 0x80FF00, 1.0), 0x808000);
+        assert_eq!(composite(WHITE, 0x123456, 1.0), 0x123456);
+    });
+    with_blend_mode(BlendMode::Screen, || {
+        assert_eq!(composite(BLACK, 0x123456, 1.0), 0x123456);
+        assert_eq!(composite(0xFF0000, 0x0000FF, 1.0), 0xFF00FF);
+    });
+    with_blend_mode(BlendMode::Overlay, || {
+        // Darkens the dark, lightens the light
+        assert_eq!(composite(0x404040, WHITE, 1.0), 0x808080);
+        assert_eq!(composite(0xC0C0C0, BLACK, 1.0), 0x818181);
+        assert_eq!(composite(WHITE, 0x123456, 1.0), WHITE);
+    });
+}
+
+#[test]
+fn test_multiply_darkens_overlaps() {
+    let state = drawn(&[
+        "edge none",
+        "fill #FF0000",
+        "rect 100,100 200,200",
+        "blend multiply",
+        "fill #00FF00",
+        "rect 150,100 250,200",
+    ]);
+    assert_eq!(pixel(&state, 120, 150), 0xFF0000);
+    assert_eq!(pixel(&state, 170, 150), BLACK);
+    // Over white, multiply is the color itself
+    assert_eq!(pixel(&state, 230, 150), 0x00FF00);
+}
+
+#[test]
+fn test_screen_lightens() {
+    let state = drawn(&[
+        "edge none",
+        "fill #000080",
+        "rect 100,100 200,200",
+        "blend screen",
+        "fill #800000",
+        "rect 150,100 250,200",
+    ]);
+    assert_eq!(pixel(&state, 170, 150), 0x800080);
+    assert_eq!(pixel(&state, 230, 150), WHITE);
+}
+
+#[test]
+fn test_multiply_bucket_that_changes_nothing_fills_nothing() {
+    let mut state = drawn(&["blend multiply", "fill #FFFFFF"]);
+    let before = state.canvas.clone();
+    assert_eq!(run(&mut state, "bucket 400,300"), None);
+    assert_eq!(state.canvas, before);
+}
+
+#[test]
+fn test_shadow_casts_under_any_mode() {
+    let state = drawn(&[
+        "shadow on 10,10 #404040",
+        "blend multiply",
+        "edge none",
+        "fill #FF0000",
+        "rect 100,100 150,150",
+    ]);
+    assert_eq!(pixel(&state, 155, 155), 0x404040);
+    assert_eq!(pixel(&state, 105, 105), 0xFF0000);
+    // The shape multiplies with its own shadow
+    assert_eq!(pixel(&state, 120, 120), 0x400000);
+}
+
+#[test]
+fn test_mouse_strokes_use_blend_mode() {
+    let mut state = drawn(&["edge none", "fill #FF0000", "rect 100,100 200,200"]);
+    run(&mut state, "blend multiply");
+    state.fill_color = Some(0x00FF00);
+    state.draw_shape(ToolMode::Rectangle, 150, 100, 250, 200);
+    assert_eq!(pixel(&state, 170, 150), BLACK);
+}
+
+// ===================
+// Export Tests
+// ===================
+
+#[test]
+fn test_svg_writes_opacity() {
+    let state = drawn(&["edge #FF000080", "fill #0000FF40", "rect 100,100 200,200"]);
+    let svg = state.display_list.to_svg();
+    assert!(
+        svg.contains("fill=\"#0000FF\" fill-opacity=\"0.251\""),
+        "{}",
+        svg
+    );
+    assert!(
+        svg.contains("stroke=\"#FF0000\" stroke-opacity=\"0.502\""),
+        "{}",
+        svg
+    );
+    let opaque = drawn(&["fill #0000FF", "rect 100,100 200,200"]);
+    assert!(!opaque.display_list.to_svg().contains("opacity"));
+}
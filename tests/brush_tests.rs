@@ -0,0 +1,230 @@
+mod common;
+
+use common::{drawn, pixel};
+use displai::*;
+
+fn inked(state: &AppState) -> Vec<(usize, usize)> {
+    (0..WIDTH * HEIGHT)
+        .filter(|&i| state.canvas[i] != WHITE)
+        .map(|i| (i % WIDTH, i / WIDTH))
+        .collect()
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_brush_shape() {
+    for shape in BrushShape::ALL {
+        let line = format!("brush shape {}", shape);
+        let cmd = parse_command(&line).unwrap();
+        assert_eq!(cmd, Command::BrushShape(shape));
+        assert_eq!(cmd.to_string(), line);
+        assert_eq!(cmd.name(), "brush");
+        assert!(validate(&cmd).is_ok());
+    }
+
+    // Invalid formats
+    assert_eq!(parse_command("brush"), None);
+    assert_eq!(parse_command("brush shape"), None);
+    assert_eq!(parse_command("brush shape star"), None);
+    assert_eq!(parse_command("brush shape square round"), None);
+    assert_eq!(parse_command("brush size 3"), None);
+}
+
+#[test]
+fn test_brush_shape_cycles() {
+    assert_eq!(BrushShape::Circle.next(), BrushShape::Square);
+    assert_eq!(BrushShape::Square.next(), BrushShape::Slash);
+    assert_eq!(BrushShape::Slash.next(), BrushShape::Spray);
+    assert_eq!(BrushShape::Spray.next(), BrushShape::Circle);
+}
+
+#[test]
+fn test_with_brush_shape_restores_shape() {
+    assert_eq!(brush_shape(), BrushShape::Circle);
+    with_brush_shape(BrushShape::Spray, || {
+        assert_eq!(brush_shape(), BrushShape::Spray)
+    });
+    assert_eq!(brush_shape(), BrushShape::Circle);
+}
+
+#[test]
+fn test_brush_shape_sets_state() {
+    let state = drawn(&["brush shape slash"]);
+    assert_eq!(state.brush_shape, BrushShape::Slash);
+    assert_eq!(AppState::new().brush_shape, BrushShape::Circle);
+}
+
+// ===================
+// Stamp Tests
+// ===================
+
+#[test]
+fn test_square_dot() {
+    let state = drawn(&["size 3", "brush shape square", "dot 100,100"]);
+    let mut expected: Vec<_> = (98..=102)
+        .flat_map(|y| (98..=102).map(move |x| (x, y)))
+        .collect();
+    expected.sort_by_key(|&(x, y)| (y, x));
+    assert_eq!(inked(&state), expected);
+    // A round dot leaves the corners
+    let round = drawn(&["size 3", "dot 100,100"]);
+    assert_eq!(pixel(&round, 98, 98), WHITE);
+}
+
+#[test]
+fn test_slash_dot() {
+    let state = drawn(&["size 3", "brush shape slash", "dot 100,100"]);
+    assert_eq!(inked(&state).len(), 10);
+    for (x, y) in [(98, 102), (99, 102), (100, 100), (102, 98), (103, 98)] {
+        assert_eq!(pixel(&state, x, y), BLACK, "{},{}", x, y);
+    }
+    assert_eq!(pixel(&state, 98, 98), WHITE);
+    assert_eq!(pixel(&state, 102, 102), WHITE);
+}
+
+#[test]
+fn test_spray_dot_stays_inside_the_radius() {
+    let state = drawn(&["size 10", "brush shape spray", "dot 200,200"]);
+    let specks = inked(&state);
+    assert!(!specks.is_empty() && specks.len() <= 4, "{:?}", specks);
+    for (x, y) in specks {
+        let (dx, dy) = (x as isize - 200, y as isize - 200);
+        assert!(dx * dx + dy * dy <= 81, "{},{}", x, y);
+    }
+}
+
+#[test]
+fn test_spray_offsets_repeat_for_a_position() {
+    assert_eq!(spray_offsets(10, 20, 9), spray_offsets(10, 20, 9));
+    assert_ne!(spray_offsets(10, 20, 9), spray_offsets(11, 20, 9));
+    assert_eq!(spray_offsets(10, 20, 9).len(), 4);
+    assert_eq!(spray_offsets(10, 20, 0), vec![(0, 0)]);
+    for r in 1..20 {
+        for (dx, dy) in spray_offsets(r, 7, r) {
+            let r = r as isize;
+            assert!(dx * dx + dy * dy <= r * r);
+        }
+    }
+}
+
+// ===================
+// Brush Line Tests
+// ===================
+
+#[test]
+fn test_square_brush_line_has_square_ends() {
+    let state = drawn(&["size 3", "brush shape square", "stroke 100,100 120,100"]);
+    for y in 98..=102 {
+        assert_eq!(pixel(&state, 98, y), BLACK);
+        assert_eq!(pixel(&state, 122, y), BLACK);
+    }
+    assert_eq!(pixel(&state, 123, 100), WHITE);
+    assert_eq!(inked(&state).len(), 25 * 5);
+}
+
+#[test]
+fn test_slash_brush_is_thin_along_its_nib() {
+    let along = drawn(&["size 5", "brush shape slash", "stroke 100,240 140,200"]);
+    let across = drawn(&["size 5", "brush shape slash", "stroke 100,100 140,140"]);
+    let (along, across) = (inked(&along).len(), inked(&across).len());
+    assert!(across > along * 3, "{} across vs {} along", across, along);
+}
+
+#[test]
+fn test_spray_line_stipples() {
+    let solid = drawn(&["size 10", "stroke 100,200 300,200"]);
+    let sprayed = drawn(&["size 10", "brush shape spray", "stroke 100,200 300,200"]);
+    let specks = inked(&sprayed);
+    assert!(specks.len() > 100 && specks.len() < inked(&solid).len() / 2);
+    // Drawn again, the same specks
+    let again = drawn(&["size 10", "brush shape spray", "stroke 100,200 300,200"]);
+    assert_eq!(again.canvas, sprayed.canvas);
+}
+
+#[test]
+fn test_shape_outlines_use_the_brush() {
+    let state = drawn(&["size 3", "brush shape square", "rect 100,100 200,200"]);
+    // Square corners where a round brush rounds them off
+    assert_eq!(pixel(&state, 98, 98), BLACK);
+    let round = drawn(&["size 3", "rect 100,100 200,200"]);
+    assert_eq!(pixel(&round, 98, 98), WHITE);
+}
+
+#[test]
+fn test_antialiased_square_brush_stays_hard() {
+    let state = drawn(&[
+        "aa on",
+        "size 3",
+        "brush shape square",
+        "stroke 100,100 140,120",
+    ]);
+    assert!(state.canvas.iter().all(|&p| p == WHITE || p == BLACK));
+}
+
+#[test]
+fn test_translucent_spray_composites_once() {
+    let state = drawn(&[
+        "edge #00000080",
+        "size 10",
+        "brush shape spray",
+        "stroke 100,200 300,200",
+    ]);
+    let colors: std::collections::BTreeSet<u32> = state.canvas.iter().copied().collect();
+    assert_eq!(colors.len(), 2, "{:X?}", colors);
+}
+
+#[test]
+fn test_dashes_with_a_square_brush() {
+    let state = drawn(&[
+        "size 1",
+        "brush shape square",
+        "stroke-style dashed 4 2",
+        "line 100,100 115,100",
+    ]);
+    let row: String = (100..=116)
+        .map(|x| {
+            if pixel(&state, x, 100) == WHITE {
+                '.'
+            } else {
+                '#'
+            }
+        })
+        .collect();
+    assert_eq!(row, "####..####..####.");
+}
+
+// ===================
+// Toolbar Tests
+// ===================
+
+#[test]
+fn test_toolbar_brush_shape_button() {
+    let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
+    let left = (0..WIDTH)
+        .find(|&x| is_in_brush_shape_button(x, row1_y + BUTTON_SIZE / 2))
+        .expect("brush shape button on the toolbar");
+    assert!(left + BUTTON_SIZE <= MIN_WIDTH);
+    assert!(!is_in_brush_shape_button(left, row1_y - 1));
+    assert!(!is_in_eyedropper_button(left, row1_y));
+
+    // Each shape draws its own icon
+    let icons: Vec<Vec<u32>> = BrushShape::ALL
+        .into_iter()
+        .map(|shape| {
+            let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+            with_brush_shape(shape, || {
+                draw_bottom_toolbar(&mut buffer, None, None, 1, ToolMode::Brush)
+            });
+            (row1_y..row1_y + BUTTON_SIZE)
+                .flat_map(|y| buffer[y * WIDTH + left..y * WIDTH + left + BUTTON_SIZE].to_vec())
+                .collect()
+        })
+        .collect();
+    for (i, icon) in icons.iter().enumerate() {
+        assert!(icon.contains(&BLACK));
+        assert!(icons[i + 1..].iter().all(|other| other != icon));
+    }
+}
@@ -151,8 +151,9 @@ fn test_color_buttons_do_not_overlap_each_other() {
 #[test]
 fn test_minus_button_detection() {
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    // Size display is now after 7 tool buttons
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    // Size display is after the tool buttons
+    let size_display_x =
+        BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
     let minus_x = size_display_x + 44 + BUTTON_MARGIN;
 
     // Center of minus button
@@ -176,8 +177,9 @@ fn test_minus_button_detection() {
 #[test]
 fn test_plus_button_detection() {
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    // Size display is now after 7 tool buttons
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    // Size display is after the tool buttons
+    let size_display_x =
+        BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
     let minus_x = size_display_x + 44 + BUTTON_MARGIN;
     let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
 
@@ -212,6 +214,12 @@ fn test_tool_button_detection() {
         ToolMode::Circle,
         ToolMode::Oval,
         ToolMode::Triangle,
+        ToolMode::Bucket,
+        ToolMode::Polygon,
+        ToolMode::Bezier,
+        ToolMode::RoundedRect,
+        ToolMode::Arrow,
+        ToolMode::Select,
     ];
 
     for (i, &expected_tool) in expected_tools.iter().enumerate() {
@@ -235,8 +243,9 @@ fn test_tool_button_detection() {
 #[test]
 fn test_row2_buttons_do_not_overlap() {
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    // Size display is now after 7 tool buttons
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    // Size display is after the tool buttons
+    let size_display_x =
+        BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
     let minus_x = size_display_x + 44 + BUTTON_MARGIN;
     let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
 
@@ -280,7 +289,8 @@ fn test_row2_buttons_do_not_overlap() {
 #[test]
 fn test_clear_button_detection() {
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    let size_display_x =
+        BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
     let minus_x = size_display_x + 44 + BUTTON_MARGIN;
     let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
     let clear_x = plus_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
@@ -349,3 +359,34 @@ fn test_transparent_button_does_not_overlap_colors() {
         }
     }
 }
+
+#[test]
+fn test_undo_redo_button_detection() {
+    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let size_display_x =
+        BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    let minus_x = size_display_x + 44 + BUTTON_MARGIN;
+    let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
+    let clear_x = plus_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
+    let undo_x = clear_x + BUTTON_SIZE + BUTTON_MARGIN * 2;
+    let redo_x = undo_x + BUTTON_SIZE + BUTTON_MARGIN;
+
+    assert!(is_in_undo_button(undo_x, row2_y));
+    assert!(is_in_undo_button(
+        undo_x + BUTTON_SIZE - 1,
+        row2_y + BUTTON_SIZE - 1
+    ));
+    assert!(!is_in_undo_button(undo_x - 1, row2_y));
+    assert!(!is_in_undo_button(redo_x, row2_y));
+
+    assert!(is_in_redo_button(redo_x, row2_y));
+    assert!(is_in_redo_button(
+        redo_x + BUTTON_SIZE - 1,
+        row2_y + BUTTON_SIZE - 1
+    ));
+    assert!(!is_in_redo_button(redo_x + BUTTON_SIZE, row2_y));
+    assert!(!is_in_redo_button(redo_x, row2_y - 1));
+
+    // No overlap with the clear button
+    assert!(!is_in_clear_button(undo_x, row2_y));
+}
@@ -0,0 +1,96 @@
+use displai::*;
+
+fn new_buffer() -> Vec<u32> {
+    vec![WHITE; WIDTH * HEIGHT]
+}
+
+// ===================
+// Capture Command Parsing
+// ===================
+
+#[test]
+fn test_parse_capture() {
+    assert_eq!(
+        parse_command("capture /tmp/frames.raw"),
+        Some(Command::Capture(Some("/tmp/frames.raw".to_string())))
+    );
+    assert_eq!(parse_command("capture stop"), Some(Command::Capture(None)));
+    assert_eq!(parse_command("capture"), None);
+    assert_eq!(
+        Command::Capture(Some("a.raw".to_string())).to_string(),
+        "capture a.raw"
+    );
+    assert_eq!(Command::Capture(None).to_string(), "capture stop");
+}
+
+// ===================
+// Frame Capture Tests
+// ===================
+
+#[test]
+fn test_capture_round_trip() {
+    let path = "/tmp/test_capture_round_trip.raw";
+    let mut buffer = new_buffer();
+    let mut capture = FrameCapture::start(path).expect("Should start");
+    capture.write_frame(&buffer).unwrap();
+    draw_circle(&mut buffer, 50, CANVAS_TOP + 10, 3, COLOR_PALETTE[2]);
+    capture.write_frame(&buffer).unwrap();
+    assert_eq!(capture.frames(), 2);
+    assert_eq!(capture.finish(), Ok(2));
+
+    let file = read_capture(path).expect("Should read");
+    assert_eq!(file.width, WIDTH);
+    assert_eq!(file.height, CANVAS_BOTTOM - CANVAS_TOP);
+    assert_eq!(file.frames.len(), 2);
+    assert!(file.frames[0].iter().all(|&p| p == WHITE));
+    assert_eq!(file.frames[1][10 * WIDTH + 50], COLOR_PALETTE[2]);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_capture_file_grows_by_one_frame() {
+    let path = "/tmp/test_capture_growth.raw";
+    let buffer = new_buffer();
+    let mut capture = FrameCapture::start(path).unwrap();
+    for _ in 0..3 {
+        capture.write_frame(&buffer).unwrap();
+    }
+    capture.finish().unwrap();
+    let len = std::fs::metadata(path).unwrap().len() as usize;
+    assert_eq!(len, 16 + 3 * WIDTH * (CANVAS_BOTTOM - CANVAS_TOP) * 3);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_read_capture_rejects_other_files() {
+    let path = "/tmp/test_capture_bad.raw";
+    std::fs::write(path, b"not a capture file").unwrap();
+    assert!(read_capture(path).is_err());
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_apply_capture_command_start_and_stop() {
+    let path = "/tmp/test_capture_command.raw";
+    let mut capture = None;
+    assert_eq!(
+        apply_capture_command(&mut capture, Some(path)),
+        reply(format!("capturing {}", path))
+    );
+    capture
+        .as_mut()
+        .unwrap()
+        .write_frame(&new_buffer())
+        .unwrap();
+    assert_eq!(
+        apply_capture_command(&mut capture, None),
+        reply(format!("captured 1 frames to {}", path))
+    );
+    assert!(capture.is_none());
+    assert_eq!(
+        apply_capture_command(&mut capture, None),
+        failed("not capturing")
+    );
+    assert!(apply_capture_command(&mut capture, Some("/nonexistent_dir/x.raw")).is_err());
+    std::fs::remove_file(path).ok();
+}
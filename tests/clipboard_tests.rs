@@ -0,0 +1,107 @@
+use displai::*;
+use image::{Rgba, RgbaImage};
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_copy() {
+    let cmd = parse_command("copy").unwrap();
+    assert_eq!(cmd, Command::Copy(None));
+    assert_eq!(cmd.to_string(), "copy");
+    assert_eq!(cmd.name(), "copy");
+    assert!(validate(&cmd).is_ok());
+    assert!(!cmd.is_mutating());
+
+    let cmd = parse_command("copy 200,300 100,100").unwrap();
+    assert_eq!(cmd, Command::Copy(Some((200, 300, 100, 100))));
+    assert_eq!(cmd.to_string(), "copy 200,300 100,100");
+    assert!(validate(&cmd).is_ok());
+    assert!(validate(&parse_command("copy 0,0 10,40").unwrap()).is_err());
+
+    // Invalid formats
+    assert_eq!(parse_command("copy 100,100"), None);
+    assert_eq!(parse_command("copy 100,100 200,200 300,300"), None);
+    assert_eq!(parse_command("copy all"), None);
+}
+
+#[test]
+fn test_parse_paste() {
+    let cmd = parse_command("paste").unwrap();
+    assert_eq!(
+        cmd,
+        Command::Paste {
+            x: 0,
+            y: CANVAS_TOP
+        }
+    );
+    let cmd = parse_command("paste 120,80").unwrap();
+    assert_eq!(cmd, Command::Paste { x: 120, y: 80 });
+    assert_eq!(cmd.to_string(), "paste 120,80");
+    assert_eq!(cmd.name(), "paste");
+    assert!(validate(&cmd).is_ok());
+    assert!(cmd.is_mutating());
+    assert!(cmd.runs_in_pane());
+    assert_eq!(cmd.translated(10, 20), Command::Paste { x: 130, y: 100 });
+    assert_eq!(
+        command_region(&cmd, 1),
+        Some(Region::new(120, 80, usize::MAX, usize::MAX))
+    );
+    assert!(validate(&parse_command("paste 10,10").unwrap()).is_err());
+
+    // Invalid formats
+    assert_eq!(parse_command("paste 120"), None);
+    assert_eq!(parse_command("paste 120,80 5"), None);
+}
+
+// ===================
+// Conversion Tests
+// ===================
+
+#[test]
+fn test_clip_image_is_opaque() {
+    let clip = Clip {
+        width: 2,
+        height: 1,
+        pixels: vec![RED, BLUE],
+    };
+    let img = clip_image(&clip);
+    assert_eq!(img.dimensions(), (2, 1));
+    assert_eq!(img.get_pixel(0, 0).0, [0xE0, 0x40, 0x40, 255]);
+    assert_eq!(img.get_pixel(1, 0).0, [0x40, 0x40, 0xE0, 255]);
+    assert_eq!(image_clip(&img), clip);
+}
+
+#[test]
+fn test_image_clip_lays_transparency_over_white() {
+    let mut img = RgbaImage::new(3, 1);
+    img.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+    img.put_pixel(1, 0, Rgba([0, 0, 0, 255]));
+    img.put_pixel(2, 0, Rgba([0, 0, 0, 128]));
+    let clip = image_clip(&img);
+    assert_eq!((clip.width, clip.height), (3, 1));
+    assert_eq!(clip.pixels, vec![WHITE, BLACK, 0x7F7F7F]);
+}
+
+// ===================
+// Feature Tests
+// ===================
+
+#[test]
+fn test_clipboard_feature_flag() {
+    assert_eq!(clipboard_supported(), cfg!(feature = "clipboard"));
+}
+
+#[cfg(not(feature = "clipboard"))]
+#[test]
+fn test_copy_and_paste_need_the_clipboard_feature() {
+    let mut state = AppState::new();
+    let before = state.clone();
+    let error = "error: the system clipboard needs a build with --features clipboard";
+    let copy = parse_command("copy").unwrap();
+    assert_eq!(state.execute(&copy), Some(error.to_string()));
+    let paste = parse_command("paste 100,100").unwrap();
+    assert_eq!(state.execute(&paste), Some(error.to_string()));
+    assert!(state.canvas == before.canvas);
+}
@@ -0,0 +1,167 @@
+use displai::*;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+fn clock(x: usize, y: usize, format: &str) -> Command {
+    Command::Object(ObjectCommand::Clock {
+        x,
+        y,
+        format: format.to_string(),
+    })
+}
+
+fn countdown(x: usize, y: usize, secs: u64) -> Command {
+    Command::Object(ObjectCommand::Countdown { x, y, secs })
+}
+
+fn shown(state: &AppState, id: usize) -> String {
+    state.objects.get(id).unwrap().text(&state.vars)
+}
+
+// ===================
+// Formatting Tests
+// ===================
+
+#[test]
+fn test_format_clock() {
+    assert_eq!(format_clock(DEFAULT_CLOCK_FORMAT, 0), "00:00:00");
+    assert_eq!(
+        format_clock("%Y-%m-%d %H:%M:%S", 1_700_000_000),
+        "2023-11-14 22:13:20"
+    );
+    // Leap day, and the day after
+    assert_eq!(format_clock("%Y-%m-%d", 951_782_400), "2000-02-29");
+    assert_eq!(format_clock("%Y-%m-%d", 951_868_800), "2000-03-01");
+    assert_eq!(format_clock("%H%%", 3_600), "01%");
+    assert_eq!(format_clock("%Q %", 0), "%Q %");
+}
+
+#[test]
+fn test_format_countdown() {
+    assert_eq!(format_countdown(0), "00:00");
+    assert_eq!(format_countdown(65), "01:05");
+    assert_eq!(format_countdown(3599), "59:59");
+    assert_eq!(format_countdown(3600), "1:00:00");
+    assert_eq!(format_countdown(MAX_COUNTDOWN_SECS), "99:59:59");
+}
+
+// ===================
+// Clock Command Tests
+// ===================
+
+#[test]
+fn test_parse_clock_and_countdown() {
+    assert_eq!(
+        parse_command("clock 10,40"),
+        Some(clock(10, 40, DEFAULT_CLOCK_FORMAT))
+    );
+    assert_eq!(
+        parse_command("clock 10,40 %H:%M"),
+        Some(clock(10, 40, "%H:%M"))
+    );
+    assert_eq!(
+        parse_command("clock 10,40 \"%d %H:%M\""),
+        Some(clock(10, 40, "%d %H:%M"))
+    );
+    assert_eq!(
+        parse_command("countdown 10,60 90"),
+        Some(countdown(10, 60, 90))
+    );
+
+    assert_eq!(parse_command("clock"), None);
+    assert_eq!(parse_command("clock 10"), None);
+    assert_eq!(parse_command("clock 10,40 %H %M"), None);
+    assert_eq!(parse_command("countdown 10,60"), None);
+    assert_eq!(parse_command("countdown 10,60 -5"), None);
+    assert_eq!(
+        parse_command(&format!("countdown 10,60 {}", MAX_COUNTDOWN_SECS + 1)),
+        None
+    );
+}
+
+#[test]
+fn test_clock_display_round_trips() {
+    for cmd in [
+        clock(10, 40, DEFAULT_CLOCK_FORMAT),
+        clock(10, 40, "%Y-%m-%d %H:%M"),
+        countdown(5, 50, 300),
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    }
+    assert_eq!(clock(1, 31, DEFAULT_CLOCK_FORMAT).to_string(), "clock 1,31");
+    assert_eq!(clock(1, 31, "x").name(), "clock");
+    assert_eq!(countdown(1, 31, 2).name(), "countdown");
+}
+
+#[test]
+fn test_validate_clock_and_countdown() {
+    assert!(validate(&clock(10, 40, "%H")).is_ok());
+    assert!(validate(&clock(10, 5, "%H")).is_err());
+    assert!(validate(&countdown(10, 40, MAX_COUNTDOWN_SECS)).is_ok());
+    assert!(validate(&countdown(10, 40, MAX_COUNTDOWN_SECS + 1)).is_err());
+}
+
+// ===================
+// Clock Object Tests
+// ===================
+
+#[test]
+fn test_countdown_ticks_down_and_stops_at_zero() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.execute(&countdown(100, 100, 120)),
+        Some("id:1".to_string())
+    );
+    assert_eq!(shown(&state, 1), "02:00");
+
+    let start = Instant::now();
+    assert!(state
+        .objects
+        .tick(start + Duration::from_secs(65), SystemTime::now()));
+    assert_eq!(shown(&state, 1), "00:55");
+    // Nothing changes until the next whole second
+    assert!(!state
+        .objects
+        .tick(start + Duration::from_secs(65), SystemTime::now()));
+
+    state
+        .objects
+        .tick(start + Duration::from_secs(500), SystemTime::now());
+    assert_eq!(shown(&state, 1), "00:00");
+}
+
+#[test]
+fn test_clock_follows_wall_time() {
+    let mut state = AppState::new();
+    state.execute(&clock(100, 100, "%H:%M:%S"));
+
+    let wall = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    assert!(state.objects.tick(Instant::now(), wall));
+    assert_eq!(shown(&state, 1), "22:13:20");
+    state
+        .objects
+        .tick(Instant::now(), wall + Duration::from_secs(1));
+    assert_eq!(shown(&state, 1), "22:13:21");
+
+    let mut expected = state.canvas.clone();
+    draw_text(&mut expected, 100, 100, "22:13:21", BLACK);
+    assert_eq!(*state.render(), expected);
+}
+
+#[test]
+fn test_clock_objects_are_not_text() {
+    let mut state = AppState::new();
+    state.execute(&clock(100, 100, DEFAULT_CLOCK_FORMAT));
+    state.execute(&countdown(100, 120, 5));
+    assert_eq!(
+        state.execute(&parse_command("obj settext 1 \"x\"").unwrap()),
+        Some("error: object 1 is a clock, not text".to_string())
+    );
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::List)),
+        Some("1:clock 2:countdown".to_string())
+    );
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::Delete(2))),
+        None
+    );
+}
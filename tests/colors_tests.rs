@@ -0,0 +1,108 @@
+use displai::*;
+
+// ===================
+// Name Table Tests
+// ===================
+
+#[test]
+fn test_named_color_table_is_sorted() {
+    for pair in NAMED_COLORS.windows(2) {
+        assert!(pair[0].0 < pair[1].0, "{} before {}", pair[0].0, pair[1].0);
+    }
+}
+
+#[test]
+fn test_named_color_lookup() {
+    assert_eq!(named_color("steelblue"), Some(0x4682B4));
+    assert_eq!(named_color("SteelBlue"), Some(0x4682B4));
+    assert_eq!(named_color("rebeccapurple"), Some(0x663399));
+    assert_eq!(named_color("notacolor"), None);
+}
+
+#[test]
+fn test_nearest_palette_index_exact_match() {
+    for (i, &color) in COLOR_PALETTE.iter().enumerate() {
+        assert_eq!(nearest_palette_index(&COLOR_PALETTE, color), i);
+    }
+}
+
+#[test]
+fn test_nearest_palette_index_closest_color() {
+    let palette = [0x000000, 0xFFFFFF];
+    assert_eq!(nearest_palette_index(&palette, 0x101010), 0);
+    assert_eq!(nearest_palette_index(&palette, 0xEEEEEE), 1);
+}
+
+#[test]
+fn test_named_color_index_picks_nearest_palette_entry() {
+    assert_eq!(named_color_index("black"), Some(0));
+    assert_eq!(named_color_index("white"), Some(1));
+    assert_eq!(named_color_index("red"), Some(2));
+    assert_eq!(named_color_index("yellow"), Some(5));
+    assert_eq!(named_color_index("lime"), Some(7));
+    assert_eq!(named_color_index("steelblue"), Some(10));
+}
+
+// ===================
+// Protocol Tests
+// ===================
+
+#[test]
+fn test_parse_named_colors() {
+    assert_eq!(
+        parse_command("edge red"),
+        Some(Command::Edge(Some(COLOR_PALETTE[2])))
+    );
+    assert_eq!(
+        parse_command("fill steelblue"),
+        Some(Command::Fill(Some(COLOR_PALETTE[10])))
+    );
+    assert_eq!(parse_command("color black"), Some(Command::Color(0)));
+    assert_eq!(parse_command("edge none"), Some(Command::Edge(None)));
+    assert_eq!(parse_command("edge bogus"), None);
+}
+
+#[test]
+fn test_numeric_colors_still_parse() {
+    assert_eq!(
+        parse_command("edge 3"),
+        Some(Command::Edge(Some(COLOR_PALETTE[3])))
+    );
+    assert_eq!(parse_command("fill 14"), None);
+}
+
+#[test]
+fn test_parse_hex_colors() {
+    assert_eq!(
+        parse_command("edge #FF8000"),
+        Some(Command::Edge(Some(0xFF8000)))
+    );
+    assert_eq!(
+        parse_command("fill #1a2b3c"),
+        Some(Command::Fill(Some(0x1A2B3C)))
+    );
+    assert_eq!(parse_command("edge #FF80"), None);
+    assert_eq!(parse_command("edge #GGGGGG"), None);
+    assert_eq!(parse_command("color #FF8000"), None, "color takes an index");
+
+    // Palette colors keep their index syntax when written back
+    assert_eq!(Command::Edge(Some(0xFF8000)).to_string(), "edge #FF8000");
+    assert_eq!(Command::Fill(Some(COLOR_PALETTE[4])).to_string(), "fill 4");
+    assert!(validate(&Command::Edge(Some(0xFF8000))).is_ok());
+}
+
+#[test]
+fn test_palette_index() {
+    assert_eq!(palette_index(COLOR_PALETTE[7]), Some(7));
+    assert_eq!(palette_index(0x123456), None);
+}
+
+#[test]
+fn test_parse_color_value() {
+    assert_eq!(parse_color_value("2"), Some(COLOR_PALETTE[2]));
+    assert_eq!(parse_color_value("steelblue"), Some(0x4682B4));
+    assert_eq!(parse_color_value("#A0b0C0"), Some(0xA0B0C0));
+    assert_eq!(parse_color_value("14"), None);
+    assert_eq!(parse_color_value("#12"), None);
+    assert_eq!(parse_color_value("#GGGGGG"), None);
+}
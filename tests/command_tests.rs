@@ -11,8 +11,39 @@ fn new_buffer() -> Vec<u32> {
 
 #[test]
 fn test_parse_snapshot() {
-    assert_eq!(parse_command("snapshot"), Some(Command::Snapshot));
-    assert_eq!(parse_command("  snapshot  "), Some(Command::Snapshot));
+    assert_eq!(parse_command("snapshot"), Some(Command::Snapshot(None)));
+    assert_eq!(parse_command("  snapshot  "), Some(Command::Snapshot(None)));
+    let cmd = Command::Snapshot(Some("/tmp/run 1/frame.jpg".to_string()));
+    assert_eq!(
+        parse_command("snapshot /tmp/run 1/frame.jpg"),
+        Some(cmd.clone())
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+}
+
+#[test]
+fn test_parse_snapshot_region() {
+    let region = |path: Option<&str>| Command::SnapshotRegion {
+        x1: 10,
+        y1: 40,
+        x2: 110,
+        y2: 90,
+        path: path.map(str::to_string),
+    };
+    assert_eq!(
+        parse_command("snapshot region 10,40 110,90"),
+        Some(region(None))
+    );
+    assert_eq!(
+        parse_command("snapshot  region 10,40  110,90  /tmp/a b.png "),
+        Some(region(Some("/tmp/a b.png")))
+    );
+    for cmd in [region(None), region(Some("/tmp/out.jpg"))] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    }
+    assert_eq!(parse_command("snapshot region 10,40"), None);
+    assert_eq!(parse_command("snapshot region 10,40 110"), None);
+    assert_eq!(parse_command("snapshot region"), None);
 }
 
 #[test]
@@ -45,6 +76,18 @@ fn test_parse_eraser_no_longer_valid() {
     assert_eq!(parse_command("eraser off"), None);
 }
 
+#[test]
+fn test_parse_fps() {
+    assert_eq!(parse_command("fps 30"), Some(Command::Fps(30)));
+    assert_eq!(parse_command("fps 1"), Some(Command::Fps(1)));
+    assert_eq!(parse_command("fps 240"), Some(Command::Fps(240)));
+
+    // Invalid rates
+    assert_eq!(parse_command("fps 0"), None);
+    assert_eq!(parse_command("fps 241"), None);
+    assert_eq!(parse_command("fps"), None);
+}
+
 #[test]
 fn test_parse_size() {
     assert_eq!(parse_command("size 1"), Some(Command::Size(1)));
@@ -98,9 +141,18 @@ fn test_parse_unknown_command() {
 
 #[test]
 fn test_parse_edge() {
-    assert_eq!(parse_command("edge 0"), Some(Command::Edge(Some(0))));
-    assert_eq!(parse_command("edge 5"), Some(Command::Edge(Some(5))));
-    assert_eq!(parse_command("edge 13"), Some(Command::Edge(Some(13))));
+    assert_eq!(
+        parse_command("edge 0"),
+        Some(Command::Edge(Some(COLOR_PALETTE[0])))
+    );
+    assert_eq!(
+        parse_command("edge 5"),
+        Some(Command::Edge(Some(COLOR_PALETTE[5])))
+    );
+    assert_eq!(
+        parse_command("edge 13"),
+        Some(Command::Edge(Some(COLOR_PALETTE[13])))
+    );
     assert_eq!(parse_command("edge none"), Some(Command::Edge(None)));
 
     // Invalid
@@ -111,9 +163,18 @@ fn test_parse_edge() {
 
 #[test]
 fn test_parse_fill() {
-    assert_eq!(parse_command("fill 0"), Some(Command::Fill(Some(0))));
-    assert_eq!(parse_command("fill 5"), Some(Command::Fill(Some(5))));
-    assert_eq!(parse_command("fill 13"), Some(Command::Fill(Some(13))));
+    assert_eq!(
+        parse_command("fill 0"),
+        Some(Command::Fill(Some(COLOR_PALETTE[0])))
+    );
+    assert_eq!(
+        parse_command("fill 5"),
+        Some(Command::Fill(Some(COLOR_PALETTE[5])))
+    );
+    assert_eq!(
+        parse_command("fill 13"),
+        Some(Command::Fill(Some(COLOR_PALETTE[13])))
+    );
     assert_eq!(parse_command("fill none"), Some(Command::Fill(None)));
 
     // Invalid
@@ -229,6 +290,227 @@ fn test_parse_triangle() {
     assert_eq!(parse_command("triangle"), None);
 }
 
+#[test]
+fn test_parse_bucket() {
+    assert_eq!(
+        parse_command("bucket 120,240"),
+        Some(Command::Bucket { x: 120, y: 240 })
+    );
+    assert_eq!(Command::Bucket { x: 1, y: 31 }.to_string(), "bucket 1,31");
+    assert!(Command::Bucket { x: 1, y: 31 }.is_mutating());
+
+    // Invalid formats
+    assert_eq!(parse_command("bucket"), None);
+    assert_eq!(parse_command("bucket 120"), None);
+    assert_eq!(parse_command("bucket a,b"), None);
+}
+
+#[test]
+fn test_parse_rounded_rect() {
+    let cmd = Command::RoundedRect {
+        x1: 100,
+        y1: 100,
+        x2: 300,
+        y2: 200,
+        r: 20,
+    };
+    assert_eq!(parse_command("rrect 100,100 300,200 20"), Some(cmd.clone()));
+    assert_eq!(cmd.to_string(), "rrect 100,100 300,200 20");
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(cmd.name(), "rrect");
+    assert!(cmd.is_mutating());
+    assert!(validate(&cmd).is_ok());
+    // Radii beyond half the shorter side are clamped when drawn, not rejected
+    assert_eq!(
+        cmd.rounded_rect(),
+        Some(RoundedRect::new(100, 100, 300, 200, 20))
+    );
+    assert!(validate(&parse_command("rrect 100,100 300,200 1000").unwrap()).is_ok());
+    assert!(validate(&parse_command("rrect 100,10 300,200 20").unwrap()).is_err());
+
+    // Invalid formats
+    assert_eq!(parse_command("rrect 100,100 300,200"), None);
+    assert_eq!(parse_command("rrect 100,100 300,200 20 5"), None);
+    assert_eq!(parse_command("rrect 100,100 300 20"), None);
+    assert_eq!(parse_command("rrect 100,100 300,200 -1"), None);
+}
+
+#[test]
+fn test_parse_arrow() {
+    let arrow = |head, both| Command::Arrow {
+        x1: 100,
+        y1: 100,
+        x2: 300,
+        y2: 200,
+        head,
+        both,
+    };
+    assert_eq!(
+        parse_command("arrow 100,100 300,200"),
+        Some(arrow(None, false))
+    );
+    assert_eq!(
+        parse_command("arrow 100,100 300,200 16"),
+        Some(arrow(Some(16), false))
+    );
+    assert_eq!(
+        parse_command("arrow 100,100 300,200 both"),
+        Some(arrow(None, true))
+    );
+    assert_eq!(
+        parse_command("arrow 100,100 300,200 16 both"),
+        Some(arrow(Some(16), true))
+    );
+    assert_eq!(
+        arrow(Some(16), true).to_string(),
+        "arrow 100,100 300,200 16 both"
+    );
+    for cmd in [arrow(None, false), arrow(Some(16), true)] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert_eq!(cmd.name(), "arrow");
+        assert!(cmd.is_mutating());
+        assert!(validate(&cmd).is_ok());
+    }
+    // The head defaults to one sized for the brush
+    assert_eq!(arrow(None, false).arrow(5).unwrap().head, 20);
+    assert_eq!(arrow(None, false).arrow(1).unwrap().head, MIN_ARROW_HEAD);
+    assert_eq!(
+        validate(&arrow(Some(0), false)),
+        Err("arrow head size must be at least 1".to_string())
+    );
+    assert!(validate(&parse_command("arrow 100,10 300,200").unwrap()).is_err());
+
+    // Invalid formats
+    assert_eq!(parse_command("arrow 100,100"), None);
+    assert_eq!(parse_command("arrow 100,100 300,200 both 16"), None);
+    assert_eq!(parse_command("arrow 100,100 300,200 16 17"), None);
+    assert_eq!(parse_command("arrow 100,100 300,200 big"), None);
+    assert_eq!(parse_command("arrow 100,100 300"), None);
+}
+
+#[test]
+fn test_parse_ngon_and_star() {
+    let ngon = |rotation| Command::Ngon {
+        x: 200,
+        y: 300,
+        r: 50,
+        sides: 6,
+        rotation,
+    };
+    assert_eq!(parse_command("ngon 200,300 50 6"), Some(ngon(0.0)));
+    assert_eq!(parse_command("ngon 200,300 50 6 30"), Some(ngon(30.0)));
+    assert_eq!(ngon(0.0).to_string(), "ngon 200,300 50 6");
+    assert_eq!(ngon(22.5).to_string(), "ngon 200,300 50 6 22.5");
+    let star = Command::Star {
+        x: 200,
+        y: 300,
+        outer: 50,
+        inner: 20,
+        points: 5,
+    };
+    assert_eq!(parse_command("star 200,300 50 20 5"), Some(star.clone()));
+    assert_eq!(star.to_string(), "star 200,300 50 20 5");
+    for (cmd, name) in [(ngon(0.0), "ngon"), (ngon(22.5), "ngon"), (star, "star")] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert_eq!(cmd.name(), name);
+        assert!(cmd.is_mutating());
+        assert!(validate(&cmd).is_ok());
+    }
+
+    // Invalid values
+    assert_eq!(
+        validate(&parse_command("ngon 200,300 50 2").unwrap()),
+        Err("ngon needs 3 to 1000 sides".to_string())
+    );
+    assert_eq!(
+        validate(&parse_command("ngon 200,300 50 6 inf").unwrap()),
+        Err("ngon rotation must be a number".to_string())
+    );
+    assert_eq!(
+        validate(&parse_command("star 200,300 50 20 1001").unwrap()),
+        Err("star needs 3 to 1000 points".to_string())
+    );
+    // Off the canvas, by either radius
+    assert!(validate(&parse_command("ngon 200,60 50 6").unwrap()).is_err());
+    assert!(validate(&parse_command("star 200,60 20 50 5").unwrap()).is_err());
+
+    // Invalid formats
+    assert_eq!(parse_command("ngon 200,300 50"), None);
+    assert_eq!(parse_command("ngon 200,300 50 6 30 1"), None);
+    assert_eq!(parse_command("ngon 200,300 50 six"), None);
+    assert_eq!(parse_command("star 200,300 50 5"), None);
+    assert_eq!(parse_command("star 200 50 20 5"), None);
+}
+
+#[test]
+fn test_parse_arc_and_pie() {
+    let arc = Command::Arc {
+        x: 400,
+        y: 300,
+        r: 100,
+        start: 0.0,
+        end: 90.0,
+    };
+    assert_eq!(parse_command("arc 400,300 100 0 90"), Some(arc.clone()));
+    let pie = Command::Pie {
+        x: 400,
+        y: 300,
+        r: 100,
+        start: -45.5,
+        end: 225.0,
+    };
+    assert_eq!(
+        parse_command("pie 400,300 100 -45.5 225"),
+        Some(pie.clone())
+    );
+    assert_eq!(pie.to_string(), "pie 400,300 100 -45.5 225");
+    for cmd in [arc, pie] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(cmd.is_mutating());
+        assert!(validate(&cmd).is_ok());
+    }
+
+    // Invalid formats
+    assert_eq!(parse_command("arc 400,300 100 0"), None);
+    assert_eq!(parse_command("arc 400,300 100 0 90 180"), None);
+    assert_eq!(parse_command("arc 400 300 100 0"), None);
+    assert_eq!(parse_command("pie 400,300 -100 0 90"), None);
+    assert_eq!(parse_command("pie 400,300 100 north 90"), None);
+}
+
+#[test]
+fn test_validate_arc() {
+    assert_eq!(
+        validate(&parse_command("arc 400,300 100 0 nan").unwrap()),
+        Err("arc angles must be numbers".to_string())
+    );
+    assert!(validate(&parse_command("pie 400,300 100 inf 90").unwrap()).is_err());
+    // The whole circle must fit the canvas, as for `circle`
+    assert!(validate(&parse_command("arc 50,300 100 -90 90").unwrap()).is_err());
+}
+
+#[test]
+fn test_parse_polygon() {
+    let polygon = Command::Polygon(vec![(100, 100), (200, 120), (150, 220), (90, 180)]);
+    assert_eq!(
+        parse_command("polygon 100,100 200,120 150,220 90,180"),
+        Some(polygon.clone())
+    );
+    assert_eq!(
+        polygon.to_string(),
+        "polygon 100,100 200,120 150,220 90,180"
+    );
+    assert!(polygon.is_mutating());
+    assert!(validate(&polygon).is_ok());
+    assert!(validate(&Command::Polygon(vec![(10, 40), (20, 5), (30, 40)])).is_err());
+
+    // Invalid formats
+    assert_eq!(parse_command("polygon"), None);
+    assert_eq!(parse_command("polygon 1,40 2,40"), None);
+    assert_eq!(parse_command("polygon 1,40 2,40 3"), None);
+    assert_eq!(parse_command("polygon 1,40 2,40 3,40:2"), None);
+}
+
 // ===================
 // Command Execution Tests
 // ===================
@@ -236,90 +518,90 @@ fn test_parse_triangle() {
 #[test]
 fn test_execute_color_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0);
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = None;
     let mut size = 5;
 
     let result = execute_command(
         &Command::Color(5),
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
-    assert_eq!(edge_color_index, Some(5));
-    assert!(result.is_none());
+    assert_eq!(edge_color, Some(COLOR_PALETTE[5]));
+    assert_eq!(result, Ok(CommandOutput::Ok));
 }
 
 #[test]
 fn test_execute_edge_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0);
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = None;
     let mut size = 5;
 
     // Set edge to color 7
-    execute_command(
-        &Command::Edge(Some(7)),
+    let _ = execute_command(
+        &Command::Edge(Some(COLOR_PALETTE[7])),
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
-    assert_eq!(edge_color_index, Some(7));
+    assert_eq!(edge_color, Some(COLOR_PALETTE[7]));
 
     // Set edge to transparent
-    execute_command(
+    let _ = execute_command(
         &Command::Edge(None),
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
-    assert_eq!(edge_color_index, None);
+    assert_eq!(edge_color, None);
 }
 
 #[test]
 fn test_execute_fill_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0);
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = None;
     let mut size = 5;
 
     // Set fill to color 3
-    execute_command(
-        &Command::Fill(Some(3)),
+    let _ = execute_command(
+        &Command::Fill(Some(COLOR_PALETTE[3])),
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
-    assert_eq!(fill_color_index, Some(3));
+    assert_eq!(fill_color, Some(COLOR_PALETTE[3]));
 
     // Set fill to transparent
-    execute_command(
+    let _ = execute_command(
         &Command::Fill(None),
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
-    assert_eq!(fill_color_index, None);
+    assert_eq!(fill_color, None);
 }
 
 #[test]
 fn test_execute_size_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0);
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = None;
     let mut size = 5;
 
-    execute_command(
+    let _ = execute_command(
         &Command::Size(15),
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
     assert_eq!(size, 15);
@@ -328,18 +610,18 @@ fn test_execute_size_command() {
 #[test]
 fn test_execute_dot_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     // Draw a dot in the canvas area
     let x = 100;
     let y = CANVAS_TOP + 50;
-    execute_command(
+    let _ = execute_command(
         &Command::Dot { x, y },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -350,29 +632,29 @@ fn test_execute_dot_command() {
 #[test]
 fn test_execute_dot_with_white_erases() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     // First draw a black dot
     let x = 100;
     let y = CANVAS_TOP + 50;
-    execute_command(
+    let _ = execute_command(
         &Command::Dot { x, y },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
     assert_eq!(buffer[y * WIDTH + x], COLOR_PALETTE[0]); // Black
 
     // Now use white (index 1) to erase it
-    edge_color_index = Some(1); // White
-    execute_command(
+    edge_color = Some(COLOR_PALETTE[1]); // White
+    let _ = execute_command(
         &Command::Dot { x, y },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
     assert_eq!(buffer[y * WIDTH + x], WHITE);
@@ -381,12 +663,12 @@ fn test_execute_dot_with_white_erases() {
 #[test]
 fn test_execute_stroke_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(2); // Red (index 2 after Black, White)
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[2]); // Red (index 2 after Black, White)
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
-    execute_command(
+    let _ = execute_command(
         &Command::Stroke {
             x1: 50,
             y1: y,
@@ -394,8 +676,8 @@ fn test_execute_stroke_command() {
             y2: y,
         },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -413,27 +695,27 @@ fn test_execute_stroke_command() {
 #[test]
 fn test_execute_clear_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0);
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = None;
     let mut size = 5;
 
     // Draw something first
     let y = CANVAS_TOP + 100;
-    execute_command(
+    let _ = execute_command(
         &Command::Dot { x: 100, y },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
     assert_ne!(buffer[y * WIDTH + 100], WHITE);
 
     // Clear
-    execute_command(
+    let _ = execute_command(
         &Command::Clear,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -454,62 +736,97 @@ fn test_execute_clear_command() {
 #[test]
 fn test_execute_state_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(5);
-    let mut fill_color_index: Option<usize> = Some(3);
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[5]);
+    let mut fill_color: Option<u32> = Some(COLOR_PALETTE[3]);
     let mut size = 10;
 
     let result = execute_command(
         &Command::State,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
-    assert_eq!(result, Some("edge:5 fill:3 size:10".to_string()));
+    assert_eq!(result, reply("edge:5 fill:3 size:10"));
 }
 
 #[test]
 fn test_execute_state_command_no_fill() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(5);
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[5]);
+    let mut fill_color: Option<u32> = None;
     let mut size = 10;
 
     let result = execute_command(
         &Command::State,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
-    assert_eq!(result, Some("edge:5 fill:none size:10".to_string()));
+    assert_eq!(result, reply("edge:5 fill:none size:10"));
+}
+
+#[test]
+fn test_hex_colors_draw_exactly_and_report_as_hex() {
+    let mut state = AppState::new();
+    state.execute(&parse_command("edge #123456").unwrap());
+    state.execute(&parse_command("fill #ABCDEF").unwrap());
+    assert_eq!(state.edge_color, Some(0x123456));
+    state.execute(&Command::Rect {
+        x1: 100,
+        y1: 100,
+        x2: 200,
+        y2: 200,
+    });
+
+    assert_eq!(state.canvas[100 * WIDTH + 150], 0x123456);
+    assert_eq!(state.canvas[150 * WIDTH + 150], 0xABCDEF);
+    assert_eq!(
+        state.execute(&Command::State),
+        Some("edge:#123456 fill:#ABCDEF size:1".to_string())
+    );
+}
+
+#[test]
+fn test_hex_point_attributes() {
+    let pt = parse_attributed_point("100,200:#FF8000:4").unwrap();
+    assert_eq!(pt.color, Some(0xFF8000));
+    assert_eq!(pt.size, Some(4));
+    assert_eq!(pt.to_string(), "100,200:#FF8000:4");
+    assert_eq!(parse_attributed_point("100,200:#FF80"), None);
+
+    let mut state = AppState::new();
+    state.execute(&parse_command("points 100,100:#FF8000 150,100").unwrap());
+    assert_eq!(state.canvas[100 * WIDTH + 100], 0xFF8000);
+    assert_eq!(state.canvas[100 * WIDTH + 150], BLACK);
 }
 
 #[test]
 fn test_execute_state_command_transparent_edge() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = None; // Transparent edge
-    let mut fill_color_index: Option<usize> = Some(3);
+    let mut edge_color: Option<u32> = None; // Transparent edge
+    let mut fill_color: Option<u32> = Some(COLOR_PALETTE[3]);
     let mut size = 10;
 
     let result = execute_command(
         &Command::State,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
-    assert_eq!(result, Some("edge:none fill:3 size:10".to_string()));
+    assert_eq!(result, reply("edge:none fill:3 size:10"));
 }
 
 #[test]
 fn test_execute_dot_with_transparent_edge_does_nothing() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = None; // Transparent edge
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = None; // Transparent edge
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let x = 100;
@@ -518,11 +835,11 @@ fn test_execute_dot_with_transparent_edge_does_nothing() {
     // Store original pixel value
     let original = buffer[y * WIDTH + x];
 
-    execute_command(
+    let _ = execute_command(
         &Command::Dot { x, y },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -537,12 +854,12 @@ fn test_execute_dot_with_transparent_edge_does_nothing() {
 #[test]
 fn test_execute_line_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(2); // Red
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[2]); // Red
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
-    execute_command(
+    let _ = execute_command(
         &Command::Line {
             x1: 100,
             y1: y,
@@ -550,8 +867,8 @@ fn test_execute_line_command() {
             y2: y,
         },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -569,8 +886,8 @@ fn test_execute_line_command() {
 #[test]
 fn test_execute_rect_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let x1 = 100;
@@ -578,11 +895,11 @@ fn test_execute_rect_command() {
     let x2 = 200;
     let y2 = CANVAS_TOP + 150;
 
-    execute_command(
+    let _ = execute_command(
         &Command::Rect { x1, y1, x2, y2 },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -605,8 +922,8 @@ fn test_execute_rect_command() {
 #[test]
 fn test_execute_rect_with_fill() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = Some(2); // Red fill
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = Some(COLOR_PALETTE[2]); // Red fill
     let mut size = 1;
 
     let x1 = 100;
@@ -614,11 +931,11 @@ fn test_execute_rect_with_fill() {
     let x2 = 200;
     let y2 = CANVAS_TOP + 150;
 
-    execute_command(
+    let _ = execute_command(
         &Command::Rect { x1, y1, x2, y2 },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -633,23 +950,23 @@ fn test_execute_rect_with_fill() {
 #[test]
 fn test_execute_square_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let x = 100;
     let y = CANVAS_TOP + 50;
     let sq_size = 50;
 
-    execute_command(
+    let _ = execute_command(
         &Command::Square {
             x,
             y,
             size: sq_size,
         },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -666,19 +983,19 @@ fn test_execute_square_command() {
 #[test]
 fn test_execute_circle_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let cx = 200;
     let cy = CANVAS_TOP + 100;
     let r = 30;
 
-    execute_command(
+    let _ = execute_command(
         &Command::Circle { x: cx, y: cy, r },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -695,8 +1012,8 @@ fn test_execute_circle_command() {
 #[test]
 fn test_execute_oval_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let cx = 200;
@@ -704,7 +1021,7 @@ fn test_execute_oval_command() {
     let rx = 50;
     let ry = 30;
 
-    execute_command(
+    let _ = execute_command(
         &Command::Oval {
             x: cx,
             y: cy,
@@ -712,8 +1029,8 @@ fn test_execute_oval_command() {
             ry,
         },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -724,8 +1041,8 @@ fn test_execute_oval_command() {
 #[test]
 fn test_execute_triangle_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let x1 = 100;
@@ -733,11 +1050,11 @@ fn test_execute_triangle_command() {
     let x2 = 200;
     let y2 = CANVAS_TOP + 150;
 
-    execute_command(
+    let _ = execute_command(
         &Command::Triangle { x1, y1, x2, y2 },
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -751,6 +1068,70 @@ fn test_execute_triangle_command() {
     assert_eq!(buffer[y2 * WIDTH + apex_x], BLACK, "Apex should be black");
 }
 
+#[test]
+fn test_execute_bucket_uses_fill_color() {
+    let mut buffer = new_buffer();
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = None;
+    let mut size = 1;
+    let y = CANVAS_TOP + 100;
+    draw_shape_rectangle(&mut buffer, 100, y, 200, y + 100, BLACK, 1);
+    let bucket = Command::Bucket { x: 150, y: y + 50 };
+
+    // No fill color: nothing happens
+    let _ = execute_command(
+        &bucket,
+        &mut buffer,
+        &mut edge_color,
+        &mut fill_color,
+        &mut size,
+    );
+    assert_eq!(buffer[(y + 50) * WIDTH + 150], WHITE);
+
+    fill_color = Some(COLOR_PALETTE[2]);
+    let result = execute_command(
+        &bucket,
+        &mut buffer,
+        &mut edge_color,
+        &mut fill_color,
+        &mut size,
+    );
+    assert_eq!(result, Ok(CommandOutput::Ok));
+    assert_eq!(buffer[(y + 50) * WIDTH + 150], COLOR_PALETTE[2]);
+    assert_eq!(buffer[(y + 50) * WIDTH + 250], WHITE, "Outside the outline");
+    assert!(validate(&bucket).is_ok());
+    assert!(validate(&Command::Bucket { x: 10, y: 5 }).is_err());
+}
+
+#[test]
+fn test_execute_polygon_fills_then_outlines() {
+    let mut buffer = new_buffer();
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = Some(COLOR_PALETTE[7]);
+    let mut size = 1;
+    let points = vec![(100, 100), (300, 100), (300, 300), (100, 300)];
+    let result = execute_command(
+        &Command::Polygon(points.clone()),
+        &mut buffer,
+        &mut edge_color,
+        &mut fill_color,
+        &mut size,
+    );
+    assert_eq!(result, Ok(CommandOutput::Ok));
+
+    let mut expected = new_buffer();
+    draw_polygon_with_fill(
+        &mut expected,
+        &points,
+        Some(BLACK),
+        Some(COLOR_PALETTE[7]),
+        1,
+    );
+    assert_eq!(buffer, expected);
+    assert_eq!(buffer[200 * WIDTH + 200], COLOR_PALETTE[7]);
+    assert_eq!(buffer[100 * WIDTH + 200], BLACK);
+}
+
 // ===================
 // Clear Canvas Tests
 // ===================
@@ -853,10 +1234,11 @@ fn test_parse_points_invalid() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_polyline_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(2); // Red
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[2]); // Red
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
@@ -866,11 +1248,11 @@ fn test_execute_polyline_command() {
         AttributedPoint { x: 150, y, color: None, size: None },
         AttributedPoint { x: 200, y, color: None, size: None },
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -886,10 +1268,11 @@ fn test_execute_polyline_command() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_polyline_with_transparent_edge() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = None; // Transparent
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = None; // Transparent
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
@@ -899,11 +1282,11 @@ fn test_execute_polyline_with_transparent_edge() {
         AttributedPoint { x: 100, y, color: None, size: None },
         AttributedPoint { x: 200, y, color: None, size: None },
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -912,10 +1295,11 @@ fn test_execute_polyline_with_transparent_edge() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_points_command() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
@@ -924,11 +1308,11 @@ fn test_execute_points_command() {
         AttributedPoint { x: 150, y, color: None, size: None },
         AttributedPoint { x: 200, y, color: None, size: None },
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -942,10 +1326,11 @@ fn test_execute_points_command() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_points_with_transparent_edge() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = None; // Transparent
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = None; // Transparent
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
@@ -955,11 +1340,11 @@ fn test_execute_points_with_transparent_edge() {
         AttributedPoint { x: 100, y, color: None, size: None },
         AttributedPoint { x: 150, y, color: None, size: None },
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -968,10 +1353,11 @@ fn test_execute_points_with_transparent_edge() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_points_with_brush_size() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 5; // Larger brush
 
     let x = 200;
@@ -979,11 +1365,11 @@ fn test_execute_points_with_brush_size() {
     let cmd = Command::Points(vec![
         AttributedPoint { x, y, color: None, size: None },
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -995,11 +1381,11 @@ fn test_execute_points_with_brush_size() {
 }
 
 // ===================
-// PNG Export Tests
+// Image Export Tests
 // ===================
 
 #[test]
-fn test_save_canvas_png_creates_file() {
+fn test_save_canvas_image_creates_file() {
     let mut buffer = new_buffer();
 
     // Draw something on canvas
@@ -1009,9 +1395,9 @@ fn test_save_canvas_png_creates_file() {
     }
 
     let path = "/tmp/test_canvas.png";
-    let result = save_canvas_png(&buffer, path);
+    let result = save_canvas_image(&buffer, path);
 
-    assert!(result.is_ok(), "save_canvas_png should succeed");
+    assert!(result.is_ok(), "save_canvas_image should succeed");
     assert!(std::path::Path::new(path).exists(), "PNG file should exist");
 
     // Clean up
@@ -1019,11 +1405,11 @@ fn test_save_canvas_png_creates_file() {
 }
 
 #[test]
-fn test_save_canvas_png_correct_dimensions() {
+fn test_save_canvas_image_correct_dimensions() {
     let buffer = new_buffer();
     let path = "/tmp/test_canvas_dimensions.png";
 
-    save_canvas_png(&buffer, path).expect("Should save");
+    save_canvas_image(&buffer, path).expect("Should save");
 
     // Read the image and check dimensions
     let img = image::open(path).expect("Should open");
@@ -1035,7 +1421,7 @@ fn test_save_canvas_png_correct_dimensions() {
 }
 
 #[test]
-fn test_save_canvas_png_pixel_colors() {
+fn test_save_canvas_image_pixel_colors() {
     let mut buffer = new_buffer();
 
     // Draw a red pixel at a known location in canvas coordinates
@@ -1045,7 +1431,7 @@ fn test_save_canvas_png_pixel_colors() {
     buffer[buffer_y * WIDTH + x] = RED;
 
     let path = "/tmp/test_canvas_colors.png";
-    save_canvas_png(&buffer, path).expect("Should save");
+    save_canvas_image(&buffer, path).expect("Should save");
 
     // Read and verify the pixel
     let img = image::open(path).expect("Should open").into_rgb8();
@@ -1060,6 +1446,116 @@ fn test_save_canvas_png_pixel_colors() {
     std::fs::remove_file(path).ok();
 }
 
+#[test]
+fn test_save_canvas_image_formats_from_extension() {
+    let mut buffer = new_buffer();
+    buffer[(CANVAS_TOP + 10) * WIDTH + 10] = RED;
+    for (ext, format) in [
+        ("png", image::ImageFormat::Png),
+        ("jpg", image::ImageFormat::Jpeg),
+        ("JPEG", image::ImageFormat::Jpeg),
+        ("bmp", image::ImageFormat::Bmp),
+        ("webp", image::ImageFormat::WebP),
+    ] {
+        let path = format!("/tmp/test_canvas_format.{}", ext);
+        save_canvas_image(&buffer, &path).expect("Should save");
+        let bytes = std::fs::read(&path).expect("Should exist");
+        assert_eq!(image::guess_format(&bytes).unwrap(), format, "{}", ext);
+        let img = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(img.width() as usize, WIDTH);
+        std::fs::remove_file(&path).ok();
+    }
+}
+
+#[test]
+fn test_save_canvas_image_errors() {
+    let buffer = new_buffer();
+    let err = save_canvas_image(&buffer, "/tmp/test_canvas.gif2").unwrap_err();
+    assert!(err.contains("unsupported snapshot format"), "{}", err);
+    assert!(save_canvas_image(&buffer, "/tmp/test_canvas").is_err());
+
+    let err = save_canvas_image(&buffer, "/tmp/no/such/dir/canvas.png").unwrap_err();
+    assert_eq!(err, "directory /tmp/no/such/dir does not exist");
+    assert!(!std::path::Path::new("/tmp/no/such/dir/canvas.png").exists());
+}
+
+#[test]
+fn test_snapshot_to_path_responds_with_path() {
+    let mut state = AppState::new();
+    let path = "/tmp/test_snapshot_path.bmp";
+    let cmd = parse_command(&format!("snapshot {}", path)).unwrap();
+    assert!(validate(&cmd).is_ok());
+    assert_eq!(state.execute(&cmd), Some(format!("saved {}", path)));
+    assert!(std::path::Path::new(path).exists());
+    std::fs::remove_file(path).ok();
+
+    let cmd = parse_command("snapshot /tmp/no/such/dir/x.png").unwrap();
+    assert_eq!(
+        state.execute(&cmd),
+        Some("error: directory /tmp/no/such/dir does not exist".to_string())
+    );
+    assert!(validate(&parse_command("snapshot out.tiff").unwrap()).is_err());
+}
+
+#[test]
+fn test_save_region_image_crops_canvas() {
+    let mut buffer = new_buffer();
+    buffer[50 * WIDTH + 20] = RED;
+    buffer[60 * WIDTH + 29] = BLUE;
+    let path = "/tmp/test_region_crop.png";
+    // Corners in either order give the same inclusive rectangle
+    save_region_image(&buffer, (29, 60), (20, 50), path).expect("Should save");
+    let img = image::open(path).unwrap().to_rgb8();
+    std::fs::remove_file(path).ok();
+    assert_eq!(img.dimensions(), (10, 11));
+    assert_eq!(img.get_pixel(0, 0).0, [0xE0, 0x40, 0x40]);
+    assert_eq!(img.get_pixel(9, 10).0, [0x40, 0x40, 0xE0]);
+    assert_eq!(img.get_pixel(5, 5).0, [0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_save_region_image_clips_to_canvas() {
+    let buffer = new_buffer();
+    let path = "/tmp/test_region_clip.png";
+    save_region_image(&buffer, (WIDTH - 5, 0), (WIDTH + 50, CANVAS_TOP + 4), path)
+        .expect("Should save");
+    let img = image::open(path).unwrap();
+    std::fs::remove_file(path).ok();
+    assert_eq!((img.width(), img.height()), (5, 5));
+
+    let err = save_region_image(&buffer, (10, 0), (20, 10), path).unwrap_err();
+    assert!(err.contains("outside the canvas"), "{}", err);
+    assert!(!std::path::Path::new(path).exists());
+}
+
+#[test]
+fn test_snapshot_region_command() {
+    let mut state = AppState::new();
+    let path = "/tmp/test_snapshot_region.png";
+    let cmd = parse_command(&format!("snapshot region 100,100 199,149 {}", path)).unwrap();
+    assert!(validate(&cmd).is_ok());
+    assert_eq!(state.execute(&cmd), Some(format!("saved {}", path)));
+    let img = image::open(path).unwrap();
+    std::fs::remove_file(path).ok();
+    assert_eq!((img.width(), img.height()), (100, 50));
+
+    assert!(validate(&parse_command("snapshot region 0,0 10,40").unwrap()).is_err());
+    assert!(validate(&parse_command("snapshot region 0,40 10,50 out.tiff").unwrap()).is_err());
+}
+
+#[test]
+fn test_snapshot_region_includes_objects() {
+    let mut state = AppState::new();
+    state.execute(&parse_command("button 100,100,40,20 \"OK\" ok").unwrap());
+    let path = "/tmp/test_snapshot_region_objects.png";
+    let cmd = parse_command(&format!("snapshot region 100,100 139,119 {}", path)).unwrap();
+    assert_eq!(state.execute(&cmd), Some(format!("saved {}", path)));
+    let img = image::open(path).unwrap().to_rgb8();
+    std::fs::remove_file(path).ok();
+    // The button's border, not the white canvas beneath it
+    assert_ne!(img.get_pixel(0, 0).0, [0xFF, 0xFF, 0xFF]);
+}
+
 // ===================
 // Attributed Point Parsing Tests
 // ===================
@@ -1082,7 +1578,7 @@ fn test_parse_attributed_point_with_color() {
     let pt = pt.unwrap();
     assert_eq!(pt.x, 100);
     assert_eq!(pt.y, 200);
-    assert_eq!(pt.color, Some(5));
+    assert_eq!(pt.color, Some(COLOR_PALETTE[5]));
     assert_eq!(pt.size, None);
 }
 
@@ -1093,7 +1589,7 @@ fn test_parse_attributed_point_with_color_and_size() {
     let pt = pt.unwrap();
     assert_eq!(pt.x, 100);
     assert_eq!(pt.y, 200);
-    assert_eq!(pt.color, Some(5));
+    assert_eq!(pt.color, Some(COLOR_PALETTE[5]));
     assert_eq!(pt.size, Some(3));
 }
 
@@ -1130,9 +1626,9 @@ fn test_parse_polyline_with_attributes() {
             assert_eq!(points.len(), 3);
             assert_eq!(points[0].x, 100);
             assert_eq!(points[0].y, 50);
-            assert_eq!(points[0].color, Some(2));
-            assert_eq!(points[1].color, Some(5));
-            assert_eq!(points[2].color, Some(0));
+            assert_eq!(points[0].color, Some(COLOR_PALETTE[2]));
+            assert_eq!(points[1].color, Some(COLOR_PALETTE[5]));
+            assert_eq!(points[2].color, Some(COLOR_PALETTE[0]));
         }
         _ => panic!("Expected Polyline command"),
     }
@@ -1144,11 +1640,11 @@ fn test_parse_polyline_with_color_and_size() {
     match cmd {
         Some(Command::Polyline(points)) => {
             assert_eq!(points.len(), 3);
-            assert_eq!(points[0].color, Some(2));
+            assert_eq!(points[0].color, Some(COLOR_PALETTE[2]));
             assert_eq!(points[0].size, Some(5));
-            assert_eq!(points[1].color, Some(5));
+            assert_eq!(points[1].color, Some(COLOR_PALETTE[5]));
             assert_eq!(points[1].size, Some(3));
-            assert_eq!(points[2].color, Some(0));
+            assert_eq!(points[2].color, Some(COLOR_PALETTE[0]));
             assert_eq!(points[2].size, Some(1));
         }
         _ => panic!("Expected Polyline command"),
@@ -1166,10 +1662,10 @@ fn test_parse_points_mixed_attributes() {
             assert_eq!(points[0].color, None);
             assert_eq!(points[0].size, None);
             // Second point: color only
-            assert_eq!(points[1].color, Some(5));
+            assert_eq!(points[1].color, Some(COLOR_PALETTE[5]));
             assert_eq!(points[1].size, None);
             // Third point: color and size
-            assert_eq!(points[2].color, Some(2));
+            assert_eq!(points[2].color, Some(COLOR_PALETTE[2]));
             assert_eq!(points[2].size, Some(8));
         }
         _ => panic!("Expected Points command"),
@@ -1181,24 +1677,25 @@ fn test_parse_points_mixed_attributes() {
 // ===================
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_points_with_per_point_color() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black (default)
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black (default)
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
     // Draw points with different colors
     let cmd = Command::Points(vec![
-        AttributedPoint { x: 100, y, color: Some(2), size: None }, // Red
-        AttributedPoint { x: 150, y, color: Some(7), size: None }, // Green
-        AttributedPoint { x: 200, y, color: None, size: None },    // Uses default (Black)
+        AttributedPoint { x: 100, y, color: Some(COLOR_PALETTE[2]), size: None }, // Red
+        AttributedPoint { x: 150, y, color: Some(COLOR_PALETTE[7]), size: None }, // Green
+        AttributedPoint { x: 200, y, color: None, size: None },                   // Uses default (Black)
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -1209,24 +1706,25 @@ fn test_execute_points_with_per_point_color() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_points_mixed_attributes() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black (default)
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black (default)
+    let mut fill_color: Option<u32> = None;
     let mut size = 3; // Default size
 
     let y = CANVAS_TOP + 100;
     // Draw points: first uses defaults, second overrides color, third overrides both
     let cmd = Command::Points(vec![
-        AttributedPoint { x: 100, y, color: None, size: None },       // Uses defaults
-        AttributedPoint { x: 200, y, color: Some(2), size: None },    // Red, default size
-        AttributedPoint { x: 300, y, color: Some(5), size: Some(8) }, // Yellow, size 8
+        AttributedPoint { x: 100, y, color: None, size: None },                      // Uses defaults
+        AttributedPoint { x: 200, y, color: Some(COLOR_PALETTE[2]), size: None },    // Red, default size
+        AttributedPoint { x: 300, y, color: Some(COLOR_PALETTE[5]), size: Some(8) }, // Yellow, size 8
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -1241,24 +1739,25 @@ fn test_execute_points_mixed_attributes() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_polyline_with_per_segment_color() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black (default)
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black (default)
+    let mut fill_color: Option<u32> = None;
     let mut size = 1;
 
     let y = CANVAS_TOP + 100;
     // Draw a rainbow line: segment colors are determined by the END point
     let cmd = Command::Polyline(vec![
-        AttributedPoint { x: 100, y, color: None, size: None },    // Start point (no segment yet)
-        AttributedPoint { x: 200, y, color: Some(2), size: None }, // Red segment (100->200)
-        AttributedPoint { x: 300, y, color: Some(7), size: None }, // Green segment (200->300)
+        AttributedPoint { x: 100, y, color: None, size: None },                   // Start point (no segment yet)
+        AttributedPoint { x: 200, y, color: Some(COLOR_PALETTE[2]), size: None }, // Red segment (100->200)
+        AttributedPoint { x: 300, y, color: Some(COLOR_PALETTE[7]), size: None }, // Green segment (200->300)
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -1270,10 +1769,11 @@ fn test_execute_polyline_with_per_segment_color() {
 }
 
 #[test]
+#[rustfmt::skip]
 fn test_execute_polyline_with_per_segment_size() {
     let mut buffer = new_buffer();
-    let mut edge_color_index: Option<usize> = Some(0); // Black
-    let mut fill_color_index: Option<usize> = None;
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]); // Black
+    let mut fill_color: Option<u32> = None;
     let mut size = 1; // Default size
 
     let y = CANVAS_TOP + 100;
@@ -1283,11 +1783,11 @@ fn test_execute_polyline_with_per_segment_size() {
         AttributedPoint { x: 150, y, color: None, size: Some(1) },  // Thin segment
         AttributedPoint { x: 200, y, color: None, size: Some(10) }, // Thick segment
     ]);
-    execute_command(
+    let _ = execute_command(
         &cmd,
         &mut buffer,
-        &mut edge_color_index,
-        &mut fill_color_index,
+        &mut edge_color,
+        &mut fill_color,
         &mut size,
     );
 
@@ -1299,3 +1799,126 @@ fn test_execute_polyline_with_per_segment_size() {
     assert_eq!(buffer[y * WIDTH + 175], BLACK, "Thick segment center");
     assert_eq!(buffer[(y + 5) * WIDTH + 175], BLACK, "Thick segment should extend");
 }
+
+// ===================
+// Validation Tests
+// ===================
+
+#[test]
+fn test_validate_accepts_on_canvas_geometry() {
+    let y = CANVAS_TOP + 100;
+    assert_eq!(validate(&Command::Dot { x: 10, y }), Ok(()));
+    assert_eq!(
+        validate(&Command::Rect {
+            x1: 0,
+            y1: CANVAS_TOP,
+            x2: WIDTH - 1,
+            y2: CANVAS_BOTTOM - 1
+        }),
+        Ok(())
+    );
+    assert_eq!(
+        validate(&Command::Circle {
+            x: 400,
+            y: 300,
+            r: 50
+        }),
+        Ok(())
+    );
+    assert_eq!(validate(&Command::State), Ok(()));
+    assert_eq!(validate(&Command::Edge(None)), Ok(()));
+}
+
+#[test]
+fn test_validate_rejects_off_canvas_points() {
+    assert!(validate(&Command::Dot { x: WIDTH, y: 100 }).is_err());
+    assert!(validate(&Command::Dot { x: 10, y: 5 }).is_err());
+    assert!(validate(&Command::Line {
+        x1: 10,
+        y1: 100,
+        x2: 10,
+        y2: CANVAS_BOTTOM
+    })
+    .is_err());
+}
+
+#[test]
+fn test_validate_rejects_overflowing_extents() {
+    let err = validate(&Command::Square {
+        x: 10,
+        y: 100,
+        size: usize::MAX,
+    });
+    assert!(err.unwrap_err().contains("overflows"));
+
+    assert!(validate(&Command::Circle {
+        x: 10,
+        y: 100,
+        r: 50
+    })
+    .is_err());
+    assert!(validate(&Command::Oval {
+        x: 400,
+        y: 300,
+        rx: usize::MAX,
+        ry: 1
+    })
+    .is_err());
+}
+
+#[test]
+fn test_validate_rejects_bad_palette_and_size() {
+    assert!(validate(&Command::Color(14)).is_err());
+    // Palette entries can't be translucent
+    assert!(validate(&Command::Palette(PaletteCommand::Set {
+        index: 3,
+        color: 0x1000000
+    }))
+    .is_err());
+    assert!(validate(&Command::Size(0)).is_err());
+    assert!(validate(&Command::Size(MAX_BRUSH_SIZE + 1)).is_err());
+
+    let bad_point = AttributedPoint {
+        x: 100,
+        y: 100,
+        color: None,
+        size: Some(0),
+    };
+    assert!(validate(&Command::Points(vec![bad_point])).is_err());
+}
+
+#[test]
+fn test_execute_shapes_with_extreme_arguments_do_not_panic() {
+    let mut buffer = new_buffer();
+    let mut edge_color: Option<u32> = Some(COLOR_PALETTE[0]);
+    let mut fill_color: Option<u32> = Some(COLOR_PALETTE[2]);
+    let mut size = 3;
+
+    let commands = [
+        Command::Square {
+            x: usize::MAX,
+            y: usize::MAX,
+            size: usize::MAX,
+        },
+        Command::Circle {
+            x: usize::MAX,
+            y: 100,
+            r: usize::MAX,
+        },
+        Command::Oval {
+            x: 400,
+            y: 300,
+            rx: usize::MAX,
+            ry: usize::MAX,
+        },
+    ];
+    for cmd in &commands {
+        let _ = execute_command(
+            cmd,
+            &mut buffer,
+            &mut edge_color,
+            &mut fill_color,
+            &mut size,
+        );
+    }
+}
@@ -0,0 +1,38 @@
+//! Helpers shared by the integration tests that drive an `AppState`.
+//!
+//! Each test file is its own crate and uses only some of these.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use displai::*;
+
+/// Run `line` on `state`, returning the command's response if any; palette
+/// indices and aliases mean what they do in `state`
+pub fn run(state: &mut AppState, line: &str) -> Option<String> {
+    let cmd = state.parse(line).expect("command parses");
+    state.execute(&cmd)
+}
+
+/// The color at `(x, y)` on the state's canvas, whatever its size
+pub fn pixel(state: &AppState, x: usize, y: usize) -> u32 {
+    state.canvas[y * state.canvas.width() + x]
+}
+
+/// An empty temporary directory for one test, named after the test file
+/// so files running side by side don't share it
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("displai_{}_{}", env!("CARGO_CRATE_NAME"), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The canvas after running `lines`
+pub fn drawn(lines: &[&str]) -> AppState {
+    let mut state = AppState::new();
+    for line in lines {
+        assert_eq!(run(&mut state, line), None, "{}", line);
+    }
+    state
+}
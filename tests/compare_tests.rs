@@ -0,0 +1,114 @@
+use displai::*;
+use image::{Rgba, RgbaImage};
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_compare_commands() {
+    let image = Command::Compare(CompareCommand::Image("/tmp/my refs/target.png".to_string()));
+    assert_eq!(
+        parse_command("compare /tmp/my refs/target.png"),
+        Some(image.clone())
+    );
+    for cmd in [
+        image,
+        Command::Compare(CompareCommand::Checkpoint),
+        Command::Compare(CompareCommand::Divider(250)),
+        Command::Compare(CompareCommand::Off),
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(validate(&cmd).is_ok());
+        assert!(!cmd.is_mutating());
+    }
+    assert_eq!(parse_command("compare"), None);
+    assert_eq!(parse_command("compare divider x"), None);
+    assert_eq!(parse_command("compareoff"), None);
+}
+
+#[test]
+fn test_validate_compare_divider() {
+    let divider = |x| Command::Compare(CompareCommand::Divider(x));
+    assert!(validate(&divider(0)).is_ok());
+    assert!(validate(&divider(WIDTH)).is_ok());
+    assert_eq!(
+        validate(&divider(WIDTH + 1)),
+        Err(format!(
+            "divider {} is outside the window (0-{})",
+            WIDTH + 1,
+            WIDTH
+        ))
+    );
+}
+
+// ===================
+// Split View Tests
+// ===================
+
+#[test]
+fn test_split_shows_canvas_left_and_reference_right() {
+    let reference = vec![RED; WIDTH * HEIGHT];
+    let mut view = CompareView::from_canvas(&reference, "checkpoint");
+    assert_eq!(view.label(), "checkpoint");
+    assert_eq!(view.divider(), WIDTH / 2);
+    view.set_divider(300);
+
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    view.draw(&mut frame);
+    let y = CANVAS_TOP + 10;
+    assert_eq!(frame[y * WIDTH + 10], WHITE);
+    assert_eq!(frame[y * WIDTH + 297], WHITE);
+    // A two-pixel divider just left of the first reference column
+    assert_eq!(frame[y * WIDTH + 299], DARK_GRAY);
+    assert_eq!(frame[y * WIDTH + 300], DARK_GRAY);
+    assert_eq!(frame[y * WIDTH + 301], RED);
+    assert_eq!(frame[y * WIDTH + WIDTH - 1], RED);
+    // The title bar and toolbar are left alone
+    assert_eq!(frame[5 * WIDTH + 400], WHITE);
+    assert_eq!(frame[CANVAS_BOTTOM * WIDTH + 400], WHITE);
+}
+
+#[test]
+fn test_divider_grip_is_drawn_halfway_down() {
+    let reference = vec![WHITE; WIDTH * HEIGHT];
+    let view = CompareView::from_canvas(&reference, "checkpoint");
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    view.draw(&mut frame);
+    let mid = (CANVAS_TOP + CANVAS_BOTTOM) / 2;
+    let gray = (view.divider() - 8..view.divider() + 8)
+        .filter(|&x| frame[mid * WIDTH + x] == DARK_GRAY)
+        .count();
+    assert!(gray > 2);
+}
+
+#[test]
+fn test_reference_image_sits_at_canvas_corner() {
+    let img = RgbaImage::from_pixel(10, 10, Rgba([0, 0, 255, 255]));
+    let mut view = CompareView::from_image(&img, "target.png");
+    assert_eq!(view.label(), "target.png");
+    view.set_divider(0);
+    let mut frame = vec![BLACK; WIDTH * HEIGHT];
+    view.draw(&mut frame);
+    assert_eq!(frame[(CANVAS_TOP + 5) * WIDTH + 5], 0x0000FF);
+    // White past the image
+    assert_eq!(frame[(CANVAS_TOP + 5) * WIDTH + 50], WHITE);
+}
+
+#[test]
+fn test_divider_is_clamped_and_grabbed_nearby() {
+    let reference = vec![WHITE; WIDTH * HEIGHT];
+    let mut view = CompareView::from_canvas(&reference, "checkpoint");
+    view.set_divider(WIDTH + 100);
+    assert_eq!(view.divider(), WIDTH);
+
+    view.set_divider(200);
+    let y = CANVAS_TOP + 50;
+    assert!(view.grabs(200, y));
+    assert!(view.grabs(200 - DIVIDER_GRAB, y));
+    assert!(view.grabs(200 + DIVIDER_GRAB, y));
+    assert!(!view.grabs(200 + DIVIDER_GRAB + 1, y));
+    // Only on the canvas
+    assert!(!view.grabs(200, CANVAS_TOP - 1));
+    assert!(!view.grabs(200, CANVAS_BOTTOM));
+}
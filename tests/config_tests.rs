@@ -0,0 +1,440 @@
+use displai::*;
+use std::time::Duration;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+// ===================
+// Config Parsing Tests
+// ===================
+
+#[test]
+fn test_config_default_fps() {
+    assert_eq!(Config::default().fps, DEFAULT_FPS);
+    assert_eq!(Config::from_args(args(&[])), Ok(Config::default()));
+}
+
+#[test]
+fn test_config_fps_separate_value() {
+    let config = Config::from_args(args(&["--fps", "30"])).unwrap();
+    assert_eq!(config.fps, 30);
+}
+
+#[test]
+fn test_config_fps_inline_value() {
+    let config = Config::from_args(args(&["--fps=144"])).unwrap();
+    assert_eq!(config.fps, 144);
+}
+
+#[test]
+fn test_config_fps_out_of_range() {
+    assert!(Config::from_args(args(&["--fps", "0"])).is_err());
+    assert!(Config::from_args(args(&["--fps", "241"])).is_err());
+    assert!(Config::from_args(args(&["--fps=abc"])).is_err());
+}
+
+#[test]
+fn test_config_fps_missing_value() {
+    assert!(Config::from_args(args(&["--fps"])).is_err());
+}
+
+#[test]
+fn test_config_headless_flag() {
+    assert!(!Config::default().headless);
+    let config = Config::from_args(args(&["--headless", "--fps", "30"])).unwrap();
+    assert!(config.headless);
+    assert_eq!(config.fps, 30);
+    assert!(Config::from_args(args(&["--headless=yes"])).is_err());
+}
+
+#[test]
+fn test_config_size() {
+    assert_eq!(Config::default().size, Dimensions::default());
+    let config = Config::from_args(args(&["--size", "1920x1080"])).unwrap();
+    assert_eq!(config.size, Dimensions::new(1920, 1080).unwrap());
+    let config = Config::from_args(args(&["--size=1024x768"])).unwrap();
+    assert_eq!(config.size.width, 1024);
+    assert!(Config::from_args(args(&["--size", "20x20"])).is_err());
+    assert!(Config::from_args(args(&["--size"])).is_err());
+}
+
+#[test]
+fn test_config_unknown_argument() {
+    let err = Config::from_args(args(&["--verbose"])).unwrap_err();
+    assert!(err.contains("--verbose"));
+}
+
+// ===================
+// Frame Rate Helpers
+// ===================
+
+#[test]
+fn test_parse_fps_bounds() {
+    assert_eq!(parse_fps("1"), Some(MIN_FPS));
+    assert_eq!(parse_fps("240"), Some(MAX_FPS));
+    assert_eq!(parse_fps("0"), None);
+    assert_eq!(parse_fps("-5"), None);
+}
+
+#[test]
+fn test_frame_interval() {
+    assert_eq!(frame_interval(1), Duration::from_secs(1));
+    assert_eq!(frame_interval(60), Duration::from_micros(16_666));
+    // Zero is clamped rather than dividing by zero
+    assert_eq!(frame_interval(0), Duration::from_secs(1));
+}
+
+#[test]
+fn test_config_access_flags() {
+    let config = Config::default();
+    assert_eq!(config.stdin_access, Access::ReadWrite);
+    assert_eq!(config.socket_access, Access::ReadWrite);
+    assert!(config.tokens.is_empty());
+
+    let config = Config::from_args(args(&[
+        "--socket-access",
+        "read-only",
+        "--stdin-access=read-only",
+        "--token",
+        "abc:read-write",
+        "--token=a:b:read-only",
+    ]))
+    .unwrap();
+    assert_eq!(config.stdin_access, Access::ReadOnly);
+    assert_eq!(config.socket_access, Access::ReadOnly);
+    assert_eq!(
+        config.tokens,
+        vec![
+            ("abc".to_string(), Access::ReadWrite),
+            ("a:b".to_string(), Access::ReadOnly),
+        ]
+    );
+}
+
+#[test]
+fn test_config_access_flags_invalid() {
+    assert!(Config::from_args(args(&["--socket-access", "admin"])).is_err());
+    assert!(Config::from_args(args(&["--stdin-access"])).is_err());
+    assert!(Config::from_args(args(&["--token", "abc"])).is_err());
+    assert!(Config::from_args(args(&["--token", ":read-only"])).is_err());
+    assert!(Config::from_args(args(&["--token", "abc:root"])).is_err());
+}
+
+#[test]
+fn test_config_io_defaults() {
+    let config = Config::default();
+    assert_eq!(config.socket, Some(default_socket_path()));
+    assert!(config.stdin);
+    assert_eq!(config.load, None);
+    assert_eq!(config.title, DEFAULT_TITLE);
+}
+
+#[test]
+fn test_socket_path_prefers_environment_variable() {
+    assert_eq!(
+        socket_path_from(
+            Some("/tmp/mine.sock".to_string()),
+            Some("/run/user/1000".to_string()),
+            42
+        ),
+        "/tmp/mine.sock"
+    );
+}
+
+#[test]
+fn test_socket_path_defaults_to_one_per_process() {
+    let runtime = || Some("/run/user/1000/".to_string());
+    assert_eq!(
+        socket_path_from(None, runtime(), 42),
+        "/run/user/1000/displai-42.sock"
+    );
+    assert_ne!(
+        socket_path_from(None, runtime(), 42),
+        socket_path_from(None, runtime(), 43)
+    );
+    // Empty variables count as unset
+    assert_eq!(
+        socket_path_from(Some(String::new()), Some(String::new()), 7),
+        "/tmp/displai-7.sock"
+    );
+    assert_eq!(socket_path_from(None, None, 7), "/tmp/displai-7.sock");
+}
+
+#[test]
+fn test_config_socket_and_stdin_flags() {
+    let config = Config::from_args(args(&["--socket", "/tmp/other.sock", "--no-stdin"])).unwrap();
+    assert_eq!(config.socket.as_deref(), Some("/tmp/other.sock"));
+    assert!(!config.stdin);
+
+    let config = Config::from_args(args(&["--no-socket", "--no-stdin", "--stdin"])).unwrap();
+    assert_eq!(config.socket, None);
+    assert!(config.stdin);
+
+    let config = Config::from_args(args(&["--no-socket", "--socket=/tmp/x.sock"])).unwrap();
+    assert_eq!(config.socket.as_deref(), Some("/tmp/x.sock"));
+
+    assert!(Config::from_args(args(&["--socket"])).is_err());
+    assert!(Config::from_args(args(&["--socket="])).is_err());
+    assert!(Config::from_args(args(&["--no-stdin=1"])).is_err());
+}
+
+#[test]
+fn test_config_headless_needs_an_input() {
+    assert!(Config::from_args(args(&["--headless", "--no-stdin"])).is_ok());
+    assert!(Config::from_args(args(&["--headless", "--no-socket"])).is_ok());
+    assert!(Config::from_args(args(&["--no-stdin", "--no-socket"])).is_ok());
+    assert_eq!(
+        Config::from_args(args(&["--headless", "--no-stdin", "--no-socket"])),
+        Err("--headless needs stdin, a socket, or a script to take commands from".to_string())
+    );
+}
+
+#[test]
+fn test_config_script_and_defines() {
+    assert!(Config::default().script.is_empty());
+    let path = std::env::temp_dir().join("displai_config_script.txt");
+    std::fs::write(&path, "fill ${fill}\nobj text 10,40 \"${title}\"\n").unwrap();
+    let path = path.to_str().unwrap();
+
+    // A --define may come before or after the script
+    let config = Config::from_args(args(&[
+        "--define",
+        "fill=2",
+        "--script",
+        path,
+        "--define=title=Sales = up",
+    ]))
+    .unwrap();
+    assert_eq!(
+        config.defines,
+        vec![
+            ("fill".to_string(), "2".to_string()),
+            ("title".to_string(), "Sales = up".to_string())
+        ]
+    );
+    assert_eq!(
+        config.script,
+        vec!["fill 2", "obj text 10,40 \"Sales = up\""]
+    );
+
+    // A script is enough input for a headless run
+    let config = Config::from_args(args(&[
+        "--headless",
+        "--no-stdin",
+        "--no-socket",
+        &format!("--script={}", path),
+        "--define=fill=3",
+        "--define=title=",
+    ]))
+    .unwrap();
+    assert_eq!(config.script, vec!["fill 3", "obj text 10,40 \"\""]);
+
+    let err = Config::from_args(args(&["--script", path, "--define", "fill=2"])).unwrap_err();
+    assert!(
+        err.ends_with(
+            "${title} is not set (pass --define title=VALUE or set it in the environment)"
+        ),
+        "{}",
+        err
+    );
+    let _ = std::fs::remove_file(path);
+
+    assert_eq!(
+        Config::from_args(args(&["--define", "title"])),
+        Err("--define needs KEY=VALUE, not 'title'".to_string())
+    );
+    assert!(Config::from_args(args(&["--define"])).is_err());
+    assert!(Config::from_args(args(&["--script"])).is_err());
+    assert!(Config::from_args(args(&["--script", "/nonexistent/script.txt"])).is_err());
+}
+
+#[test]
+fn test_config_title() {
+    let config = Config::from_args(args(&["--title", "Team board"])).unwrap();
+    assert_eq!(config.title, "Team board");
+    let config = Config::from_args(args(&["--title=x"])).unwrap();
+    assert_eq!(config.title, "x");
+    assert!(Config::from_args(args(&["--title"])).is_err());
+}
+
+#[test]
+fn test_config_load() {
+    let path = std::env::temp_dir().join("displai_config_load.png");
+    image::RgbaImage::from_pixel(3, 2, image::Rgba([0, 0, 0, 255]))
+        .save(&path)
+        .unwrap();
+    let config = Config::from_args(args(&["--load", path.to_str().unwrap()])).unwrap();
+    assert_eq!(config.load.map(|img| img.dimensions()), Some((3, 2)));
+
+    let err = Config::from_args(args(&["--load", "/nonexistent/start.png"])).unwrap_err();
+    assert!(
+        err.starts_with("cannot load /nonexistent/start.png"),
+        "{}",
+        err
+    );
+    assert!(Config::from_args(args(&["--load"])).is_err());
+}
+
+#[test]
+fn test_config_annotate() {
+    assert_eq!(Config::default().annotate, None);
+    let path = std::env::temp_dir().join("displai_config_annotate.png");
+    image::RgbaImage::from_pixel(900, 400, image::Rgba([0, 0, 0, 255]))
+        .save(&path)
+        .unwrap();
+    let path = path.to_str().unwrap();
+    let config = Config::from_args(args(&["--annotate", path])).unwrap();
+    let annotation = config.annotate.unwrap();
+    assert_eq!((annotation.width, annotation.height), (900, 400));
+    assert!(annotation
+        .output
+        .ends_with("displai_config_annotate-annotated.png"));
+    // The window holds the image, which starts the canvas
+    assert_eq!(config.size.width, 900);
+    assert_eq!(config.size.canvas_height(), 400);
+    assert_eq!(config.load.map(|img| img.dimensions()), Some((900, 400)));
+    // No session is restored over it, unless asked for
+    assert_eq!(config.session, None);
+    let config = Config::from_args(args(&["--annotate", path, "--session", "/tmp/s"])).unwrap();
+    assert_eq!(config.session.as_deref(), Some("/tmp/s"));
+
+    for other in [
+        ["--size", "800x600"],
+        ["--load", path],
+        ["--persist", "board.png"],
+    ] {
+        let mut list = vec!["--annotate", path];
+        list.extend(other);
+        assert_eq!(
+            Config::from_args(args(&list)),
+            Err(format!("--annotate can't be used with {}", other[0]))
+        );
+    }
+    assert!(Config::from_args(args(&["--annotate", "/nonexistent/shot.png"])).is_err());
+    assert!(Config::from_args(args(&["--annotate"])).is_err());
+}
+
+#[test]
+fn test_config_persist() {
+    assert_eq!(Config::default().persist, None);
+    // Nothing saved yet: start as usual
+    let missing = std::env::temp_dir().join("displai_config_persist_missing.png");
+    let _ = std::fs::remove_file(&missing);
+    let config = Config::from_args(args(&["--persist", missing.to_str().unwrap()])).unwrap();
+    assert_eq!(config.persist.as_deref(), missing.to_str());
+    assert_eq!(config.load, None);
+
+    // A saved canvas is restored in place of --load
+    let saved = std::env::temp_dir().join("displai_config_persist.png");
+    image::RgbaImage::from_pixel(5, 1, image::Rgba([0, 0, 0, 255]))
+        .save(&saved)
+        .unwrap();
+    let start = std::env::temp_dir().join("displai_config_persist_load.png");
+    image::RgbaImage::from_pixel(3, 2, image::Rgba([0, 0, 0, 255]))
+        .save(&start)
+        .unwrap();
+    let config = Config::from_args(args(&[
+        "--load",
+        start.to_str().unwrap(),
+        &format!("--persist={}", saved.display()),
+    ]))
+    .unwrap();
+    assert_eq!(config.load.map(|img| img.dimensions()), Some((5, 1)));
+
+    assert_eq!(
+        Config::from_args(args(&["--persist", "board.jpg"])),
+        Err("--persist needs a .png path, not 'board.jpg'".to_string())
+    );
+    assert!(Config::from_args(args(&["--persist"])).is_err());
+}
+
+#[test]
+fn test_config_session() {
+    // Instances keep a session only when given one, so none share it
+    let config = Config::default();
+    assert_eq!(config.session, None);
+    assert!(config.restore);
+    let config = Config::from_args(args(&["--title", "board"])).unwrap();
+    assert_eq!(config.session, None);
+
+    let config = Config::from_args(args(&["--session", "/tmp/board", "--no-restore"])).unwrap();
+    assert_eq!(config.session.as_deref(), Some("/tmp/board"));
+    assert!(!config.restore);
+
+    let config = Config::from_args(args(&["--headless", "--session=/tmp/board"])).unwrap();
+    assert_eq!(config.session.as_deref(), Some("/tmp/board"));
+
+    assert!(Config::from_args(args(&["--session"])).is_err());
+    assert!(Config::from_args(args(&["--session="])).is_err());
+    assert!(Config::from_args(args(&["--no-restore=yes"])).is_err());
+}
+
+#[test]
+fn test_config_announcements() {
+    let config = Config::default();
+    assert_eq!((config.ready_file, config.beacon), (None, None));
+    let config = Config::from_args(args(&[
+        "--ready-file",
+        "/tmp/displai.ready",
+        "--beacon=127.0.0.1:9000",
+    ]))
+    .unwrap();
+    assert_eq!(config.ready_file.as_deref(), Some("/tmp/displai.ready"));
+    assert_eq!(config.beacon.as_deref(), Some("127.0.0.1:9000"));
+    assert_eq!(
+        Config::from_args(args(&["--beacon", "localhost:9000"]))
+            .unwrap()
+            .beacon
+            .as_deref(),
+        Some("localhost:9000")
+    );
+
+    for bad in [
+        "9000",
+        "localhost",
+        ":9000",
+        "localhost:0",
+        "localhost:70000",
+    ] {
+        assert_eq!(
+            Config::from_args(args(&["--beacon", bad])),
+            Err(format!("--beacon needs HOST:PORT, not '{}'", bad))
+        );
+    }
+    assert!(Config::from_args(args(&["--ready-file"])).is_err());
+    assert!(Config::from_args(args(&["--ready-file="])).is_err());
+}
+
+#[test]
+fn test_config_aa() {
+    assert!(!Config::default().antialias);
+    assert!(Config::from_args(args(&["--aa"])).unwrap().antialias);
+    assert!(Config::from_args(args(&["--aa=on"])).is_err());
+}
+
+#[test]
+fn test_usage_lists_every_flag() {
+    for flag in [
+        "--size",
+        "--fps",
+        "--title",
+        "--load",
+        "--persist",
+        "--aa",
+        "--headless",
+        "--socket",
+        "--no-socket",
+        "--ready-file",
+        "--beacon",
+        "--stdin",
+        "--no-stdin",
+        "--stdin-access",
+        "--socket-access",
+        "--token",
+        "--keymap",
+        "--help",
+    ] {
+        assert!(USAGE.contains(flag), "{}", flag);
+    }
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use common::drawn;
+use displai::*;
+
+/// Which pixels of row `y` from `x0` to `x1` are drawn, as `#` and `.`
+fn row(state: &AppState, y: usize, x0: usize, x1: usize) -> String {
+    (x0..=x1)
+        .map(|x| match state.canvas[y * WIDTH + x] {
+            WHITE => '.',
+            _ => '#',
+        })
+        .collect()
+}
+
+fn inked(state: &AppState) -> usize {
+    state.canvas.iter().filter(|&&p| p != WHITE).count()
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_stroke_style() {
+    assert_eq!(
+        parse_command("stroke-style solid"),
+        Some(Command::StrokeStyle(StrokeStyle::Solid))
+    );
+    assert_eq!(
+        parse_command("stroke-style dashed 5 2"),
+        Some(Command::StrokeStyle(StrokeStyle::Dashed {
+            dash: 5,
+            gap: 2
+        }))
+    );
+    assert_eq!(
+        parse_command("stroke-style dotted 1 6"),
+        Some(Command::StrokeStyle(StrokeStyle::Dotted {
+            dash: 1,
+            gap: 6
+        }))
+    );
+    // Lengths default by style
+    assert_eq!(
+        parse_command("stroke-style dashed"),
+        Some(Command::StrokeStyle(StrokeStyle::Dashed {
+            dash: 8,
+            gap: 4
+        }))
+    );
+    assert_eq!(
+        parse_command("stroke-style dotted"),
+        Some(Command::StrokeStyle(StrokeStyle::Dotted {
+            dash: 1,
+            gap: 3
+        }))
+    );
+
+    // Invalid formats
+    assert_eq!(parse_command("stroke-style"), None);
+    assert_eq!(parse_command("stroke-style wavy"), None);
+    assert_eq!(parse_command("stroke-style solid 4 2"), None);
+    assert_eq!(parse_command("stroke-style dashed 4"), None);
+    assert_eq!(parse_command("stroke-style dashed 4 2 1"), None);
+    assert_eq!(parse_command("stroke-style dashed a b"), None);
+    assert_eq!(parse_command("stroke-style dashed -4 2"), None);
+}
+
+#[test]
+fn test_stroke_style_round_trips() {
+    for line in [
+        "stroke-style solid",
+        "stroke-style dashed 8 4",
+        "stroke-style dotted 1 3",
+    ] {
+        let cmd = parse_command(line).unwrap();
+        assert_eq!(cmd.to_string(), line);
+        assert_eq!(cmd.name(), "stroke-style");
+        assert!(validate(&cmd).is_ok(), "{}", line);
+    }
+}
+
+#[test]
+fn test_validate_stroke_style_lengths() {
+    let cmd = parse_command("stroke-style dashed 0 4").unwrap();
+    assert_eq!(
+        validate(&cmd),
+        Err("dash length 0 is outside 1-1000".to_string())
+    );
+    let cmd = parse_command("stroke-style dotted 1 1001").unwrap();
+    assert_eq!(
+        validate(&cmd),
+        Err("gap length 1001 is outside 1-1000".to_string())
+    );
+}
+
+// ===================
+// Pattern Tests
+// ===================
+
+#[test]
+fn test_pattern_allows_for_the_brush() {
+    assert_eq!(StrokeStyle::Solid.pattern(3), None);
+    let dashed = StrokeStyle::Dashed { dash: 8, gap: 4 };
+    assert_eq!(dashed.pattern(1), Some((8.0, 4.0)));
+    // A size 3 brush reaches 2 pixels past each end of the path
+    assert_eq!(dashed.pattern(3), Some((4.0, 8.0)));
+    // Dashes shorter than the brush are single dots
+    let dotted = StrokeStyle::Dotted { dash: 1, gap: 3 };
+    assert_eq!(dotted.pattern(5), Some((1.0, 11.0)));
+}
+
+#[test]
+fn test_dash_on_and_spans() {
+    let pattern = (4.0, 2.0);
+    let on: Vec<bool> = (0..8).map(|d| dash_on(pattern, d as f64)).collect();
+    assert_eq!(on, [true, true, true, true, false, false, true, true]);
+    // Distances are relative to the start of the stretch
+    assert_eq!(dash_spans(pattern, 0.0, 10.0), vec![(0.0, 3.0), (6.0, 9.0)]);
+    assert_eq!(dash_spans(pattern, 2.0, 10.0), vec![(0.0, 1.0), (4.0, 7.0)]);
+    assert_eq!(dash_spans(pattern, 4.0, 5.0), vec![]);
+}
+
+#[test]
+fn test_with_stroke_style_restores_style() {
+    assert_eq!(stroke_style(), StrokeStyle::Solid);
+    let dotted = StrokeStyle::Dotted { dash: 1, gap: 3 };
+    with_stroke_style(dotted, || {
+        assert_eq!(stroke_style(), dotted);
+        assert_eq!(advance_stroke(5.0), 0.0);
+        assert_eq!(advance_stroke(2.0), 5.0);
+        // A nested shape starts its own pattern
+        with_stroke_style(dotted, || assert_eq!(advance_stroke(1.0), 0.0));
+        assert_eq!(advance_stroke(0.0), 7.0);
+    });
+    assert_eq!(stroke_style(), StrokeStyle::Solid);
+}
+
+// ===================
+// Drawing Tests
+// ===================
+
+#[test]
+fn test_dashed_line() {
+    let state = drawn(&["size 1", "stroke-style dashed 4 2", "line 100,100 115,100"]);
+    assert_eq!(row(&state, 100, 100, 116), "####..####..####.");
+}
+
+#[test]
+fn test_dashes_run_on_across_segments() {
+    let whole = drawn(&["size 1", "stroke-style dashed 4 2", "line 100,100 130,100"]);
+    let joined = drawn(&[
+        "size 1",
+        "stroke-style dashed 4 2",
+        "polyline 100,100 107,100 113,100 130,100",
+    ]);
+    assert_eq!(row(&joined, 100, 95, 135), row(&whole, 100, 95, 135));
+}
+
+#[test]
+fn test_each_shape_starts_a_dash() {
+    let state = drawn(&[
+        "size 1",
+        "stroke-style dashed 4 2",
+        "line 100,100 102,100",
+        "line 100,110 110,110",
+    ]);
+    assert_eq!(row(&state, 110, 100, 110), "####..####.");
+}
+
+#[test]
+fn test_wide_dashes_keep_their_lengths() {
+    let state = drawn(&["size 3", "stroke-style dashed 8 4", "line 100,100 140,100"]);
+    // Dashes 8 wide, round ends included, 4 apart
+    assert_eq!(
+        row(&state, 100, 98, 142),
+        "########....########....########....########."
+    );
+}
+
+#[test]
+fn test_dotted_shapes_draw_less() {
+    for shape in [
+        "rect 100,100 200,200",
+        "circle 300,300 50",
+        "oval 100,300 200,360",
+    ] {
+        let solid = drawn(&["size 2", shape]);
+        let dotted = drawn(&["size 2", "stroke-style dotted", shape]);
+        let (solid, dotted) = (inked(&solid), inked(&dotted));
+        assert!(dotted > 0 && dotted < solid * 3 / 4, "{}", shape);
+    }
+}
+
+#[test]
+fn test_antialiased_dashes() {
+    let state = drawn(&[
+        "aa on",
+        "size 1",
+        "stroke-style dashed 4 2",
+        "line 100,100 115,100",
+    ]);
+    let drawn_row = row(&state, 100, 100, 115);
+    assert!(drawn_row.starts_with("####"), "{}", drawn_row);
+    assert!(drawn_row.contains(".."), "{}", drawn_row);
+}
+
+#[test]
+fn test_solid_restores_unbroken_lines() {
+    let state = drawn(&[
+        "stroke-style dashed",
+        "stroke-style solid",
+        "line 100,100 140,100",
+    ]);
+    assert_eq!(state.stroke_style, StrokeStyle::Solid);
+    assert_eq!(row(&state, 100, 100, 140), "#".repeat(41));
+}
+
+#[test]
+fn test_mouse_shapes_use_stroke_style() {
+    let mut state = drawn(&["size 1", "stroke-style dashed 4 2"]);
+    state.draw_shape(ToolMode::Line, 100, 100, 115, 100);
+    assert_eq!(row(&state, 100, 100, 116), "####..####..####.");
+}
@@ -0,0 +1,150 @@
+use displai::*;
+
+/// Write a script to a scratch file unique to one test, returning its path
+fn write_script(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir()
+        .join(format!("displai_debugger_{}", name))
+        .to_string_lossy()
+        .into_owned();
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_debug_commands() {
+    let load = Command::Debug(DebugCommand::Step("/tmp/my scripts/a.txt".to_string()));
+    assert_eq!(
+        parse_command("debug step /tmp/my scripts/a.txt"),
+        Some(load.clone())
+    );
+    for cmd in [
+        load,
+        Command::Debug(DebugCommand::Status),
+        Command::Debug(DebugCommand::Stop),
+        Command::Step,
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(validate(&cmd).is_ok());
+        assert!(!cmd.is_mutating());
+    }
+    assert_eq!(
+        parse_command("debug"),
+        Some(Command::Debug(DebugCommand::Status))
+    );
+    assert_eq!(parse_command("step"), Some(Command::Step));
+}
+
+#[test]
+fn test_parse_debug_rejects_malformed() {
+    assert_eq!(parse_command("debug step"), None);
+    assert_eq!(parse_command("debug stepper a.txt"), None);
+    assert_eq!(parse_command("debug go"), None);
+    assert_eq!(parse_command("debugger"), None);
+    assert_eq!(parse_command("step 2"), None);
+}
+
+// ===================
+// Script Reading Tests
+// ===================
+
+#[test]
+fn test_read_script_skips_blanks_and_comments() {
+    let path = write_script(
+        "comments.txt",
+        "# setup\nfill 2\n\n   rect 100,100 200,200  \n# done\nsnapshot\n",
+    );
+    assert_eq!(
+        read_script(&path).unwrap(),
+        vec!["fill 2", "rect 100,100 200,200", "snapshot"]
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_read_script_accepts_recordings() {
+    let path = write_script(
+        "recording.rec",
+        &format!("{}\n0 clear\n120 dot 50,50\n", RECORDING_HEADER),
+    );
+    assert_eq!(read_script(&path).unwrap(), vec!["clear", "dot 50,50"]);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_read_script_missing_file() {
+    let err = read_script("/nonexistent/displai/script.txt").unwrap_err();
+    assert!(err.starts_with("cannot read /nonexistent/displai/script.txt"));
+}
+
+// ===================
+// Stepper Tests
+// ===================
+
+#[test]
+fn test_stepper_hands_out_commands_in_order() {
+    let path = write_script("steps.txt", "clear\ndot 50,50\n");
+    let mut stepper = Stepper::load(&path).unwrap();
+    assert_eq!(stepper.path(), path);
+    assert_eq!(stepper.len(), 2);
+    assert_eq!(stepper.position(), 0);
+    assert_eq!(stepper.status(), "step 1/2: clear");
+
+    assert_eq!(stepper.advance().as_deref(), Some("clear"));
+    assert_eq!(stepper.peek(), Some("dot 50,50"));
+    assert_eq!(stepper.status(), "step 2/2: dot 50,50");
+    assert!(!stepper.is_finished());
+
+    assert_eq!(stepper.advance().as_deref(), Some("dot 50,50"));
+    assert!(stepper.is_finished());
+    assert_eq!(stepper.position(), 2);
+    assert_eq!(stepper.status(), "finished 2/2");
+    assert_eq!(stepper.advance(), None);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_stepper_rejects_empty_script() {
+    let path = write_script("empty.txt", "# nothing yet\n\n");
+    let err = Stepper::load(&path).unwrap_err();
+    assert_eq!(err, format!("{} has no commands", path));
+    let _ = std::fs::remove_file(&path);
+}
+
+// ===================
+// Status Drawing Tests
+// ===================
+
+#[test]
+fn test_step_status_drawn_in_title_bar() {
+    let path = write_script("status.txt", "rect 100,100 200,200\n");
+    let stepper = Stepper::load(&path).unwrap();
+    let mut buffer = vec![GRAY; WIDTH * HEIGHT];
+    draw_step_status(&mut buffer, &stepper);
+    let text_pixels = buffer.iter().filter(|&&p| p == BLACK).count();
+    assert!(text_pixels > 0);
+    // Only the title bar is touched
+    assert!(buffer[TITLE_BAR_HEIGHT * WIDTH..]
+        .iter()
+        .all(|&p| p == GRAY));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_long_step_status_stops_before_close_button() {
+    let long = format!("notify \"{}\"", "x".repeat(300));
+    let path = write_script("long.txt", &format!("{}\n", long));
+    let stepper = Stepper::load(&path).unwrap();
+    let mut buffer = vec![GRAY; WIDTH * HEIGHT];
+    draw_step_status(&mut buffer, &stepper);
+    let close_x = WIDTH - BUTTON_SIZE - BUTTON_MARGIN;
+    for y in 0..TITLE_BAR_HEIGHT {
+        assert!(buffer[y * WIDTH + close_x..(y + 1) * WIDTH]
+            .iter()
+            .all(|&p| p == GRAY));
+    }
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,227 @@
+mod common;
+
+use common::run;
+use displai::*;
+
+fn open(kind: DialogKind, message: &str) -> Command {
+    Command::Dialog(DialogCommand::Open {
+        kind,
+        message: message.to_string(),
+    })
+}
+
+/// Center of a rectangle from a `DialogLayout`
+fn center((x, y, w, h): (usize, usize, usize, usize)) -> (usize, usize) {
+    (x + w / 2, y + h / 2)
+}
+
+// ===================
+// Dialog Command Tests
+// ===================
+
+#[test]
+fn test_parse_dialog_commands() {
+    let confirm = open(DialogKind::Confirm, "Erase everything?");
+    assert_eq!(
+        parse_command("dialog confirm \"Erase everything?\""),
+        Some(confirm.clone())
+    );
+    assert_eq!(parse_command(&confirm.to_string()), Some(confirm));
+    let prompt = open(DialogKind::Prompt, "Filename:");
+    assert_eq!(parse_command(&prompt.to_string()), Some(prompt));
+    assert_eq!(
+        parse_command("dialog close"),
+        Some(Command::Dialog(DialogCommand::Close))
+    );
+
+    assert_eq!(parse_command("dialog confirm Erase"), None);
+    assert_eq!(parse_command("dialog confirm \"Erase\" now"), None);
+    assert_eq!(parse_command("dialog alert \"Hi\""), None);
+    assert_eq!(parse_command("dialog close now"), None);
+    assert_eq!(parse_command("dialog"), None);
+}
+
+#[test]
+fn test_validate_dialog_message() {
+    assert!(validate(&open(DialogKind::Confirm, "Sure?")).is_ok());
+    assert!(validate(&open(DialogKind::Confirm, "  ")).is_err());
+    assert!(validate(&open(
+        DialogKind::Prompt,
+        &"x".repeat(MAX_DIALOG_MESSAGE_LEN)
+    ))
+    .is_ok());
+    assert!(validate(&open(
+        DialogKind::Prompt,
+        &"x".repeat(MAX_DIALOG_MESSAGE_LEN + 1)
+    ))
+    .is_err());
+}
+
+#[test]
+fn test_one_dialog_at_a_time() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, "dialog confirm \"Erase everything?\""),
+        None
+    );
+    assert_eq!(
+        run(&mut state, "dialog prompt \"Filename:\""),
+        Some("error: a dialog is already open".to_string())
+    );
+    assert_eq!(state.dialog.as_ref().unwrap().kind, DialogKind::Confirm);
+    assert_eq!(run(&mut state, "dialog close"), None);
+    assert_eq!(state.dialog, None);
+    assert_eq!(
+        run(&mut state, "dialog close"),
+        Some("error: no dialog is open".to_string())
+    );
+}
+
+// ===================
+// Answer Tests
+// ===================
+
+#[test]
+fn test_answer_event_lines() {
+    let line = |answer| Event::Answered(answer).to_string();
+    assert_eq!(line(DialogAnswer::Confirmed(true)), "confirm yes");
+    assert_eq!(line(DialogAnswer::Confirmed(false)), "confirm no");
+    assert_eq!(
+        line(DialogAnswer::Prompted(Some("cat.png".to_string()))),
+        "prompt ok cat.png"
+    );
+    assert_eq!(line(DialogAnswer::Prompted(None)), "prompt cancel");
+}
+
+#[test]
+fn test_confirm_keys() {
+    let mut dialog = Dialog::new(DialogKind::Confirm, "Sure?");
+    assert_eq!(dialog.key(KeyInput::Char('y')), None);
+    assert_eq!(dialog.text, "");
+    assert_eq!(
+        dialog.key(KeyInput::Enter),
+        Some(DialogAnswer::Confirmed(true))
+    );
+    assert_eq!(
+        dialog.key(KeyInput::Escape),
+        Some(DialogAnswer::Confirmed(false))
+    );
+}
+
+#[test]
+fn test_prompt_typing() {
+    let mut dialog = Dialog::new(DialogKind::Prompt, "Filename:");
+    for c in "cat.pngx\"".chars() {
+        assert_eq!(dialog.key(KeyInput::Char(c)), None);
+    }
+    dialog.key(KeyInput::Backspace);
+    assert_eq!(dialog.text, "cat.png");
+    assert_eq!(
+        dialog.key(KeyInput::Enter),
+        Some(DialogAnswer::Prompted(Some("cat.png".to_string())))
+    );
+    assert_eq!(
+        dialog.key(KeyInput::Escape),
+        Some(DialogAnswer::Prompted(None))
+    );
+
+    for _ in 0..MAX_INPUT_LEN + 5 {
+        dialog.key(KeyInput::Char('a'));
+    }
+    assert_eq!(dialog.text.len(), MAX_INPUT_LEN);
+}
+
+#[test]
+fn test_clicking_dialog_buttons() {
+    let dialog = Dialog::new(DialogKind::Prompt, "Filename:");
+    let layout = dialog.layout();
+    let (x, y) = center(layout.buttons[0]);
+    assert_eq!(
+        dialog.click(x, y),
+        Some(DialogAnswer::Prompted(Some(String::new())))
+    );
+    let (x, y) = center(layout.buttons[1]);
+    assert_eq!(dialog.click(x, y), Some(DialogAnswer::Prompted(None)));
+    // The rest of the dialog, and the canvas around it, answer nothing
+    let (x, y) = center(layout.field.unwrap());
+    assert_eq!(dialog.click(x, y), None);
+    assert_eq!(dialog.click(5, CANVAS_TOP + 5), None);
+}
+
+// ===================
+// Layout and Rendering Tests
+// ===================
+
+#[test]
+fn test_dialog_layout_is_centered_on_canvas() {
+    let confirm = Dialog::new(DialogKind::Confirm, "Erase everything?").layout();
+    let (x, y, w, h) = confirm.frame;
+    assert_eq!(w, DIALOG_WIDTH);
+    assert_eq!(x, (WIDTH - w) / 2);
+    assert_eq!(y, CANVAS_TOP + (CANVAS_BOTTOM - CANVAS_TOP - h) / 2);
+    assert_eq!(confirm.field, None);
+    assert_eq!(confirm.lines.len(), 1);
+
+    // Prompts are taller by the text field
+    let prompt = Dialog::new(DialogKind::Prompt, "Erase everything?").layout();
+    assert!(prompt.frame.3 > h);
+    let field = prompt.field.unwrap();
+    assert!(field.1 + field.3 <= prompt.buttons[0].1);
+}
+
+#[test]
+fn test_long_messages_wrap() {
+    let message = "word ".repeat(40);
+    let layout = Dialog::new(DialogKind::Confirm, message.trim()).layout();
+    assert!(layout.lines.len() > 1);
+    let (x, _, w, _) = layout.frame;
+    for (lx, _, line) in &layout.lines {
+        assert!(lx + text_width(line) <= x + w - DIALOG_PADDING, "{}", line);
+        assert!(!line.starts_with(' '));
+    }
+    let rejoined: Vec<&str> = layout.lines.iter().map(|(_, _, l)| l.as_str()).collect();
+    assert_eq!(rejoined.join(" "), message.trim());
+
+    // Unbroken text, even multibyte, is split mid-word
+    let layout = Dialog::new(DialogKind::Confirm, &"é".repeat(120)).layout();
+    assert!(layout.lines.len() > 1);
+}
+
+#[test]
+fn test_dialog_draws_over_canvas_and_objects() {
+    let mut state = AppState::new();
+    run(&mut state, "obj text 390,300 \"UNDER\"");
+    let before = state.render().into_owned();
+    run(&mut state, "dialog confirm \"Erase everything?\"");
+    let layout = state.dialog.as_ref().unwrap().layout();
+    let (x, y, w, h) = layout.frame;
+
+    let frame = state.render().into_owned();
+    assert_ne!(frame, before);
+    assert_eq!(frame[y * WIDTH + x], BLACK);
+    assert_eq!(frame[(y + h - 1) * WIDTH + x + w - 1], BLACK);
+    assert_eq!(frame[(y + 2) * WIDTH + x + 2], GRAY);
+    // The canvas itself is untouched
+    assert_eq!(state.canvas[(y + 2) * WIDTH + x + 2], WHITE);
+
+    run(&mut state, "dialog close");
+    assert_eq!(*state.render(), before);
+}
+
+#[test]
+fn test_snapshot_includes_dialog() {
+    let mut state = AppState::new();
+    run(&mut state, "dialog prompt \"Filename:\"");
+    let path = "/tmp/test_dialog_snapshot.png";
+    assert_eq!(
+        run(&mut state, &format!("snapshot {}", path)),
+        Some(format!("saved {}", path))
+    );
+    let img = image::open(path).unwrap().to_rgb8();
+    std::fs::remove_file(path).ok();
+    let (x, y, _, _) = state.dialog.as_ref().unwrap().layout().frame;
+    assert_eq!(
+        img.get_pixel(x as u32, (y - CANVAS_TOP) as u32).0,
+        [0, 0, 0]
+    );
+}
@@ -0,0 +1,119 @@
+use displai::*;
+use image::{Rgba, RgbaImage};
+
+/// A white image the size of the default canvas, with one black pixel
+fn reference_image(x: u32, y: u32) -> RgbaImage {
+    let mut img = RgbaImage::from_pixel(
+        WIDTH as u32,
+        (CANVAS_BOTTOM - CANVAS_TOP) as u32,
+        Rgba([255, 255, 255, 255]),
+    );
+    img.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+    img
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_diff_commands() {
+    let image = Command::Diff(DiffCommand::Image("/tmp/my refs/target.png".to_string()));
+    assert_eq!(
+        parse_command("diff /tmp/my refs/target.png"),
+        Some(image.clone())
+    );
+    assert_eq!(
+        parse_command("diff overlay  /tmp/my refs/target.png"),
+        Some(Command::Diff(DiffCommand::Overlay(
+            "/tmp/my refs/target.png".to_string()
+        )))
+    );
+    for cmd in [
+        image,
+        Command::Diff(DiffCommand::Overlay("ref.png".to_string())),
+        Command::Diff(DiffCommand::OverlayOff),
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(validate(&cmd).is_ok());
+        assert!(!cmd.is_mutating());
+    }
+    assert_eq!(parse_command("diff"), None);
+    assert_eq!(parse_command("diff overlay"), None);
+    assert_eq!(parse_command("diffref.png"), None);
+}
+
+// ===================
+// Summary Tests
+// ===================
+
+#[test]
+fn test_diff_frames_counts_canvas_pixels_only() {
+    let reference = vec![WHITE; WIDTH * HEIGHT];
+    let mut frame = reference.clone();
+    assert_eq!(
+        diff_frames(&frame, &reference),
+        DiffSummary {
+            count: 0,
+            bounds: None
+        }
+    );
+    frame[(CANVAS_TOP + 10) * WIDTH + 20] = RED;
+    frame[(CANVAS_TOP + 30) * WIDTH + 5] = BLACK;
+    // The title bar and toolbar aren't compared
+    frame[5 * WIDTH + 5] = BLACK;
+    frame[CANVAS_BOTTOM * WIDTH + 5] = BLACK;
+    let summary = diff_frames(&frame, &reference);
+    assert_eq!(summary.count, 2);
+    assert_eq!(
+        summary.bounds,
+        Some(Region::new(5, CANVAS_TOP + 10, 16, 21))
+    );
+}
+
+#[test]
+fn test_diff_summary_response() {
+    let none = DiffSummary {
+        count: 0,
+        bounds: None,
+    };
+    assert_eq!(none.response(), "diff 0 pixels");
+    let some = DiffSummary {
+        count: 3,
+        bounds: Some(Region::new(10, 40, 5, 2)),
+    };
+    assert_eq!(some.response(), "diff 3 pixels in 10,40,5,2");
+}
+
+// ===================
+// Overlay Tests
+// ===================
+
+#[test]
+fn test_overlay_lines_reference_up_with_canvas() {
+    let overlay = DiffOverlay::from_image(&reference_image(7, 3), "ref.png");
+    assert_eq!(overlay.label(), "ref.png");
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    frame[(CANVAS_TOP + 3) * WIDTH + 7] = BLACK;
+    assert_eq!(overlay.summary(&frame).count, 0);
+}
+
+#[test]
+fn test_overlay_tints_mismatched_pixels_red() {
+    let overlay = DiffOverlay::from_image(&reference_image(7, 3), "ref.png");
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    let extra = (CANVAS_TOP + 50) * WIDTH + 60;
+    frame[extra] = BLACK;
+    overlay.draw(&mut frame);
+    // Drawn where the reference has nothing, and missing where it has a pixel
+    assert_eq!(frame[extra], 0x702020);
+    assert_eq!(frame[(CANVAS_TOP + 3) * WIDTH + 7], 0xEF9F9F);
+    // Matching pixels are untouched
+    assert_eq!(frame[(CANVAS_TOP + 50) * WIDTH + 61], WHITE);
+    assert_eq!(frame[(CANVAS_TOP + 3) * WIDTH + 8], WHITE);
+}
+
+#[test]
+fn test_overlay_tint_is_red() {
+    assert_eq!(DIFF_TINT, RED);
+}
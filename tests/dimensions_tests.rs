@@ -0,0 +1,165 @@
+use displai::*;
+
+// ===================
+// Dimensions Tests
+// ===================
+
+#[test]
+fn test_default_dimensions_match_constants() {
+    let dims = Dimensions::default();
+    assert_eq!((dims.width, dims.height), (WIDTH, HEIGHT));
+    assert_eq!(dims.canvas_bottom(), CANVAS_BOTTOM);
+    assert_eq!(dimensions(), dims);
+}
+
+#[test]
+fn test_parse_dimensions() {
+    let dims = Dimensions::parse("1920x1080").unwrap();
+    assert_eq!((dims.width, dims.height), (1920, 1080));
+    assert_eq!(dims.canvas_bottom(), 1080 - BOTTOM_TOOLBAR_HEIGHT);
+    assert_eq!(
+        dims.canvas_height(),
+        1080 - BOTTOM_TOOLBAR_HEIGHT - CANVAS_TOP
+    );
+    assert_eq!(Dimensions::parse("640X480").unwrap().width, 640);
+}
+
+#[test]
+fn test_parse_dimensions_rejects_bad_sizes() {
+    assert!(Dimensions::parse("1920").is_err());
+    assert!(Dimensions::parse("axb").is_err());
+    assert!(Dimensions::parse("100x100").is_err());
+    assert!(Dimensions::parse("10000x600").is_err());
+    assert!(Dimensions::new(MIN_WIDTH, MIN_HEIGHT).is_ok());
+}
+
+#[test]
+fn test_with_dimensions_restores_previous_size() {
+    let big = Dimensions::new(1024, 768).unwrap();
+    let inner = with_dimensions(big, || (window_width(), window_height(), canvas_bottom()));
+    assert_eq!(inner, (1024, 768, 768 - BOTTOM_TOOLBAR_HEIGHT));
+    assert_eq!(dimensions(), Dimensions::default());
+}
+
+// ===================
+// Sized Canvas Tests
+// ===================
+
+#[test]
+fn test_canvas_new_allocates_requested_size() {
+    let canvas = Canvas::new(1920, 1080);
+    assert_eq!((canvas.width(), canvas.height()), (1920, 1080));
+    assert_eq!(canvas.len(), 1920 * 1080);
+    assert_eq!(Canvas::default().len(), WIDTH * HEIGHT);
+}
+
+#[test]
+#[should_panic]
+fn test_canvas_new_rejects_tiny_size() {
+    Canvas::new(10, 10);
+}
+
+#[test]
+fn test_state_draws_across_large_canvas() {
+    let mut state = AppState::with_canvas(Canvas::new(1920, 1080));
+    state.execute(&Command::Size(3));
+    state.execute(&Command::Dot { x: 1800, y: 900 });
+    state.execute(&Command::Rect {
+        x1: 1500,
+        y1: 700,
+        x2: 1900,
+        y2: 1000,
+    });
+
+    assert_eq!(state.canvas[900 * 1920 + 1800], BLACK);
+    assert_eq!(state.canvas[700 * 1920 + 1600], BLACK);
+    // The canvas ends above the toolbar, wherever that is
+    state.execute(&Command::Dot { x: 100, y: 1050 });
+    assert_eq!(state.canvas[1050 * 1920 + 100], WHITE);
+}
+
+#[test]
+fn test_state_clear_covers_whole_canvas() {
+    let mut state = AppState::with_canvas(Canvas::new(1024, 768));
+    state.execute(&Command::Fill(Some(COLOR_PALETTE[2])));
+    state.execute(&Command::Rect {
+        x1: 0,
+        y1: CANVAS_TOP,
+        x2: 1023,
+        y2: 700,
+    });
+    state.execute(&Command::Clear);
+    assert!(state.canvas.iter().all(|&p| p == WHITE));
+}
+
+#[test]
+fn test_validate_uses_current_size() {
+    let cmd = Command::Dot { x: 1500, y: 900 };
+    assert!(validate(&cmd).is_err());
+    let big = Dimensions::new(1920, 1080).unwrap();
+    assert!(with_dimensions(big, || validate(&cmd)).is_ok());
+}
+
+#[test]
+fn test_state_run_checks_against_its_own_size() {
+    let mut big = AppState::with_canvas(Canvas::new(1920, 1080));
+    assert_eq!(big.run("line 1000,40 1500,900"), Ok(CommandOutput::Ok));
+    assert_eq!(big.canvas[900 * 1920 + 1500], BLACK);
+
+    let mut small = AppState::with_canvas(Canvas::new(600, 300));
+    assert!(small.run("dot 700,500").is_err());
+    assert!(small.canvas.iter().all(|&p| p == WHITE));
+}
+
+// ===================
+// Layout Tests
+// ===================
+
+#[test]
+fn test_toolbar_and_close_button_follow_size() {
+    let dims = Dimensions::new(1280, 720).unwrap();
+    let mut canvas = Canvas::with_size(dims);
+    with_dimensions(dims, || {
+        draw_title_bar(&mut canvas);
+        draw_bottom_toolbar(
+            &mut canvas,
+            Some(COLOR_PALETTE[0]),
+            None,
+            1,
+            ToolMode::Brush,
+        );
+
+        assert!(is_in_close_button(
+            1280 - BUTTON_MARGIN - 1,
+            BUTTON_MARGIN + 1
+        ));
+        assert!(!is_in_close_button(
+            WIDTH - BUTTON_MARGIN - 1,
+            BUTTON_MARGIN + 1
+        ));
+        let row1_y = dims.canvas_bottom() + BUTTON_MARGIN + 1;
+        assert_eq!(
+            get_clicked_color_index_bottom(BUTTON_MARGIN + 1, row1_y),
+            Some(0)
+        );
+    });
+
+    // Toolbar background spans the full width at the new bottom
+    assert_eq!(canvas[(720 - 1) * 1280 + 1279], GRAY);
+    assert_eq!(canvas[(CANVAS_BOTTOM + 20) * 1280 + 10], WHITE);
+}
+
+#[test]
+fn test_png_export_uses_canvas_size() {
+    let dims = Dimensions::new(1024, 768).unwrap();
+    let canvas = Canvas::with_size(dims);
+    let path = std::env::temp_dir().join("displai_dimensions_export.png");
+    let path = path.to_str().unwrap();
+
+    with_dimensions(dims, || save_canvas_image(&canvas, path)).unwrap();
+
+    let img = image::open(path).unwrap();
+    assert_eq!(img.width(), 1024);
+    assert_eq!(img.height() as usize, dims.canvas_height());
+    let _ = std::fs::remove_file(path);
+}
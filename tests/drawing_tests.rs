@@ -922,3 +922,729 @@ fn test_shapes_respect_canvas_boundaries() {
         }
     }
 }
+
+// ===================
+// Overflow Safety Tests
+// ===================
+
+#[test]
+fn test_draw_line_with_extreme_endpoint_does_not_panic() {
+    let mut buffer = new_buffer();
+    let y = CANVAS_TOP + 100;
+    draw_line(&mut buffer, 100, y, usize::MAX, y, BLACK);
+
+    // The on-canvas part of the line is still drawn
+    assert_eq!(buffer[y * WIDTH + 100], BLACK);
+    assert_eq!(buffer[y * WIDTH + (WIDTH - 1)], BLACK);
+}
+
+#[test]
+fn test_draw_line_entirely_off_canvas_draws_nothing() {
+    let mut buffer = new_buffer();
+    draw_line(
+        &mut buffer,
+        usize::MAX,
+        0,
+        usize::MAX - 5,
+        usize::MAX,
+        BLACK,
+    );
+    assert!(buffer.iter().all(|&p| p == WHITE));
+}
+
+#[test]
+fn test_draw_brush_line_clipped_matches_unclipped_on_canvas() {
+    // A line that leaves the canvas keeps the same pixels where it is visible
+    let y = CANVAS_TOP + 100;
+    let mut clipped = new_buffer();
+    draw_brush_line(&mut clipped, 700, y, 5000, y, BLACK, 3);
+    let mut reference = new_buffer();
+    draw_brush_line(&mut reference, 700, y, WIDTH + 10, y, BLACK, 3);
+    assert_eq!(clipped, reference);
+}
+
+#[test]
+fn test_draw_circle_with_extreme_center_or_size() {
+    let mut buffer = new_buffer();
+    draw_circle(&mut buffer, usize::MAX, usize::MAX, 5, BLACK);
+    assert!(buffer.iter().all(|&p| p == WHITE));
+
+    // An enormous brush covers the whole canvas without panicking
+    draw_circle(&mut buffer, 400, 300, usize::MAX, BLACK);
+    assert_eq!(buffer[CANVAS_TOP * WIDTH], BLACK);
+    assert_eq!(buffer[(CANVAS_BOTTOM - 1) * WIDTH + WIDTH - 1], BLACK);
+    assert_eq!(buffer[0], WHITE, "Title bar must stay untouched");
+}
+
+#[test]
+fn test_fill_functions_with_extreme_bounds() {
+    let mut buffer = new_buffer();
+    fill_square(
+        &mut buffer,
+        10,
+        CANVAS_TOP + 10,
+        usize::MAX,
+        usize::MAX,
+        RED,
+    );
+    fill_rectangle(&mut buffer, usize::MAX, usize::MAX, 0, 0, RED);
+    fill_circle(&mut buffer, 0, 0, usize::MAX, usize::MAX, RED);
+    fill_oval(&mut buffer, 0, 0, usize::MAX, usize::MAX, RED);
+    fill_triangle(&mut buffer, 0, usize::MAX, usize::MAX, 0, RED);
+
+    // Rectangle filled everything on the canvas and nothing outside it
+    assert_eq!(buffer[CANVAS_TOP * WIDTH], RED);
+    assert_eq!(buffer[(CANVAS_TOP - 1) * WIDTH], WHITE);
+    assert_eq!(buffer[CANVAS_BOTTOM * WIDTH], WHITE);
+}
+
+#[test]
+fn test_shape_outlines_with_extreme_bounds() {
+    let mut buffer = new_buffer();
+    let tools = [
+        ToolMode::Line,
+        ToolMode::Square,
+        ToolMode::Rectangle,
+        ToolMode::Circle,
+        ToolMode::Oval,
+        ToolMode::Triangle,
+    ];
+    for tool in tools {
+        draw_shape(&mut buffer, tool, 0, 0, usize::MAX, usize::MAX, BLACK, 3);
+        draw_shape(&mut buffer, tool, usize::MAX, usize::MAX, 1, 1, BLACK, 3);
+    }
+    // Rectangle's left edge runs down the canvas
+    assert_eq!(buffer[(CANVAS_TOP + 100) * WIDTH], BLACK);
+}
+
+// ===================
+// Deterministic (Fixed-Point) Rendering Tests
+// ===================
+
+// FNV-1a over the buffer, for compact golden-image checks
+fn buffer_hash(buffer: &[u32]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &pixel in buffer {
+        for byte in pixel.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[test]
+fn test_draw_shape_circle_fixed_outline() {
+    let mut buffer = new_buffer();
+    // 100x100 bounding box -> center (400,300), radius 50
+    draw_shape_circle_fixed(&mut buffer, 350, 250, 450, 350, BLACK, 1);
+
+    assert_eq!(buffer[300 * WIDTH + 450], BLACK, "Right point of circle");
+    assert_eq!(buffer[300 * WIDTH + 350], BLACK, "Left point of circle");
+    assert_eq!(buffer[250 * WIDTH + 400], BLACK, "Top point of circle");
+    assert_eq!(buffer[350 * WIDTH + 400], BLACK, "Bottom point of circle");
+    assert_eq!(buffer[300 * WIDTH + 400], WHITE, "Center stays empty");
+}
+
+#[test]
+fn test_draw_shape_circle_fixed_is_symmetric() {
+    let mut buffer = new_buffer();
+    draw_shape_circle_fixed(&mut buffer, 300, 200, 380, 280, BLACK, 1);
+
+    // Mirror across the vertical axis through the center (x = 340)
+    for y in 200..=280 {
+        for dx in 0..=40 {
+            assert_eq!(
+                buffer[y * WIDTH + 340 + dx],
+                buffer[y * WIDTH + 340 - dx],
+                "Asymmetry at dx={} y={}",
+                dx,
+                y
+            );
+        }
+    }
+}
+
+#[test]
+fn test_draw_shape_oval_fixed_outline() {
+    let mut buffer = new_buffer();
+    // Center (400,300), rx=100, ry=50
+    draw_shape_oval_fixed(&mut buffer, 300, 250, 500, 350, BLACK, 1);
+
+    assert_eq!(buffer[300 * WIDTH + 500], BLACK, "Right point of oval");
+    assert_eq!(buffer[300 * WIDTH + 300], BLACK, "Left point of oval");
+    assert_eq!(buffer[250 * WIDTH + 400], BLACK, "Top point of oval");
+    assert_eq!(buffer[350 * WIDTH + 400], BLACK, "Bottom point of oval");
+}
+
+#[test]
+fn test_fixed_outlines_are_continuous() {
+    // Every outline pixel of a thin circle has an 8-connected neighbour
+    let mut buffer = new_buffer();
+    draw_shape_circle_fixed(&mut buffer, 200, 150, 320, 270, BLACK, 1);
+
+    for y in 150..=270 {
+        for x in 200..=320 {
+            if buffer[y * WIDTH + x] != BLACK {
+                continue;
+            }
+            let mut neighbours = 0;
+            for ny in y - 1..=y + 1 {
+                for nx in x - 1..=x + 1 {
+                    if (nx, ny) != (x, y) && buffer[ny * WIDTH + nx] == BLACK {
+                        neighbours += 1;
+                    }
+                }
+            }
+            assert!(neighbours >= 2, "Gap in outline at ({},{})", x, y);
+        }
+    }
+}
+
+#[test]
+fn test_fixed_outlines_match_golden_hash() {
+    let mut buffer = new_buffer();
+    draw_shape_circle_fixed(&mut buffer, 100, 100, 220, 220, BLACK, 1);
+    draw_shape_circle_fixed(&mut buffer, 500, 300, 541, 341, RED, 4);
+    draw_shape_oval_fixed(&mut buffer, 250, 350, 600, 500, BLUE, 2);
+    draw_shape_oval_fixed(&mut buffer, 0, 40, 799, 530, BLACK, 1);
+
+    // Recompute and update only when the fixed-point rasterizer changes on purpose
+    assert_eq!(buffer_hash(&buffer), 0x7318cb2e9a106185);
+}
+
+// ===================
+// Flood Fill Tests
+// ===================
+
+#[test]
+fn test_flood_fill_stays_inside_outline() {
+    let mut buffer = new_buffer();
+    draw_shape_rectangle(&mut buffer, 100, 100, 200, 200, BLACK, 1);
+    let filled = flood_fill(&mut buffer, 150, 150, RED);
+
+    assert_eq!(filled, 99 * 99);
+    assert_eq!(buffer[150 * WIDTH + 150], RED);
+    assert_eq!(buffer[101 * WIDTH + 101], RED);
+    assert_eq!(buffer[100 * WIDTH + 150], BLACK, "Outline kept");
+    assert_eq!(buffer[150 * WIDTH + 250], WHITE, "Outside untouched");
+}
+
+#[test]
+fn test_flood_fill_follows_concave_regions() {
+    let mut buffer = new_buffer();
+    // A U shape: the fill must turn back up both arms
+    draw_shape_rectangle(&mut buffer, 100, 100, 300, 300, BLACK, 1);
+    fill_rectangle(&mut buffer, 150, 100, 250, 250, BLACK);
+    flood_fill(&mut buffer, 120, 120, BLUE);
+
+    assert_eq!(buffer[120 * WIDTH + 120], BLUE, "Left arm");
+    assert_eq!(buffer[280 * WIDTH + 200], BLUE, "Bottom of the U");
+    assert_eq!(buffer[120 * WIDTH + 280], BLUE, "Right arm");
+    assert_eq!(buffer[200 * WIDTH + 200], BLACK, "Block in the middle");
+    assert_eq!(buffer[50 * WIDTH + 50], WHITE, "Outside");
+}
+
+#[test]
+fn test_flood_fill_full_canvas_stays_in_canvas_area() {
+    let mut buffer = new_buffer();
+    draw_title_bar(&mut buffer);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
+    let before = buffer.clone();
+
+    let filled = flood_fill(&mut buffer, 400, 300, RED);
+    assert_eq!(filled, WIDTH * (CANVAS_BOTTOM - CANVAS_TOP));
+    assert_eq!(buffer[..CANVAS_TOP * WIDTH], before[..CANVAS_TOP * WIDTH]);
+    assert_eq!(
+        buffer[CANVAS_BOTTOM * WIDTH..],
+        before[CANVAS_BOTTOM * WIDTH..]
+    );
+}
+
+#[test]
+fn test_flood_fill_no_ops() {
+    let mut buffer = new_buffer();
+    // Already the fill color
+    assert_eq!(flood_fill(&mut buffer, 400, 300, WHITE), 0);
+    // Off the canvas
+    assert_eq!(flood_fill(&mut buffer, 400, 5, RED), 0);
+    assert_eq!(flood_fill(&mut buffer, WIDTH, 300, RED), 0);
+    assert_eq!(flood_fill(&mut buffer, usize::MAX, usize::MAX, RED), 0);
+    assert!(buffer.iter().all(|&p| p == WHITE));
+}
+
+#[test]
+fn test_flood_fill_diagonal_gaps_do_not_leak() {
+    let mut buffer = new_buffer();
+    // A 1px diagonal line separates the regions (4-connected fill)
+    for i in 0..CANVAS_BOTTOM - CANVAS_TOP {
+        set_pixel(&mut buffer, 100 + i, CANVAS_TOP + i, BLACK);
+    }
+    flood_fill(&mut buffer, 50, 300, RED);
+    assert_eq!(buffer[300 * WIDTH + 50], RED);
+    assert_eq!(buffer[100 * WIDTH + 400], WHITE);
+}
+
+// ===================
+// Polygon Tests
+// ===================
+
+#[test]
+fn test_fill_polygon_square_covers_interior() {
+    let mut buffer = new_buffer();
+    fill_polygon(
+        &mut buffer,
+        &[(100, 100), (200, 100), (200, 200), (100, 200)],
+        RED,
+    );
+
+    let filled = buffer.iter().filter(|&&p| p == RED).count();
+    assert_eq!(filled, 100 * 100);
+    assert_eq!(buffer[100 * WIDTH + 100], RED);
+    assert_eq!(buffer[199 * WIDTH + 199], RED);
+    assert_eq!(buffer[200 * WIDTH + 150], WHITE);
+}
+
+#[test]
+fn test_fill_polygon_concave() {
+    let mut buffer = new_buffer();
+    let l_shape = [
+        (100, 100),
+        (300, 100),
+        (300, 150),
+        (150, 150),
+        (150, 300),
+        (100, 300),
+    ];
+    fill_polygon(&mut buffer, &l_shape, BLUE);
+
+    assert_eq!(buffer[120 * WIDTH + 250], BLUE, "Top arm");
+    assert_eq!(buffer[250 * WIDTH + 120], BLUE, "Left arm");
+    assert_eq!(buffer[200 * WIDTH + 200], WHITE, "Inside the corner");
+}
+
+#[test]
+fn test_fill_polygon_self_intersecting_uses_even_odd() {
+    let mut buffer = new_buffer();
+    // Pentagram: vertices of a pentagon visited in 0, 2, 4, 1, 3 order
+    let star: Vec<(usize, usize)> = [0, 2, 4, 1, 3]
+        .iter()
+        .map(|&i| {
+            let angle = -std::f64::consts::FRAC_PI_2 + i as f64 * 2.0 * std::f64::consts::PI / 5.0;
+            (
+                (400.0 + 150.0 * angle.cos()).round() as usize,
+                (300.0 + 150.0 * angle.sin()).round() as usize,
+            )
+        })
+        .collect();
+    fill_polygon(&mut buffer, &star, RED);
+
+    assert_eq!(buffer[170 * WIDTH + 400], RED, "Top point");
+    assert_eq!(buffer[300 * WIDTH + 400], WHITE, "Center crossed twice");
+}
+
+#[test]
+fn test_fill_polygon_clips_to_canvas() {
+    let mut buffer = new_buffer();
+    fill_polygon(&mut buffer, &[(0, 0), (WIDTH * 2, 0), (0, HEIGHT * 2)], RED);
+
+    assert!(buffer[..CANVAS_TOP * WIDTH].iter().all(|&p| p == WHITE));
+    assert!(buffer[CANVAS_BOTTOM * WIDTH..].iter().all(|&p| p == WHITE));
+    assert_eq!(buffer[CANVAS_TOP * WIDTH], RED);
+}
+
+#[test]
+fn test_draw_polygon_closes_outline() {
+    let mut buffer = new_buffer();
+    draw_polygon(&mut buffer, &[(100, 100), (200, 100), (150, 200)], BLACK, 1);
+
+    assert_eq!(buffer[100 * WIDTH + 150], BLACK, "First edge");
+    assert_eq!(
+        buffer[150 * WIDTH + 125],
+        BLACK,
+        "Closing edge back to the start"
+    );
+    assert_eq!(buffer[130 * WIDTH + 150], WHITE, "Interior");
+}
+
+// ===================
+// Arc Tests
+// ===================
+
+fn arc(start: f64, end: f64, pie: bool) -> ArcShape {
+    ArcShape {
+        cx: 400,
+        cy: 300,
+        r: 100,
+        start,
+        end,
+        pie,
+    }
+}
+
+#[test]
+fn test_arc_outline_runs_clockwise_from_three_oclock() {
+    let points = arc(0.0, 90.0, false).outline();
+    assert_eq!(points.first(), Some(&(500, 300)));
+    assert_eq!(points.last(), Some(&(400, 400)));
+    // Halfway round, the arc passes below and right of the center
+    let (x, y) = points[points.len() / 2];
+    assert!(x > 400 && y > 300);
+}
+
+#[test]
+fn test_arc_outline_runs_counterclockwise_when_end_is_smaller() {
+    let points = arc(0.0, -90.0, false).outline();
+    assert_eq!(points.last(), Some(&(400, 200)));
+    let (_, y) = points[points.len() / 2];
+    assert!(y < 300);
+}
+
+#[test]
+fn test_arc_sweep_is_at_most_one_turn() {
+    assert_eq!(arc(10.0, 1000.0, false).sweep(), 360.0);
+    assert_eq!(arc(0.0, -720.0, false).sweep(), -360.0);
+    let full = arc(0.0, 360.0, false).outline();
+    assert_eq!(full.first(), full.last());
+}
+
+#[test]
+fn test_pie_outline_ends_at_center() {
+    let points = arc(0.0, 90.0, true).outline();
+    assert_eq!(points.last(), Some(&(400, 300)));
+}
+
+#[test]
+fn test_draw_arc_strokes_only_the_curve() {
+    let mut buffer = new_buffer();
+    draw_arc_with_fill(&mut buffer, &arc(0.0, 180.0, false), Some(BLACK), None, 1);
+
+    assert_eq!(buffer[300 * WIDTH + 500], BLACK, "Start");
+    assert_eq!(buffer[400 * WIDTH + 400], BLACK, "Bottom of the arc");
+    assert_eq!(buffer[300 * WIDTH + 300], BLACK, "End");
+    assert_eq!(buffer[200 * WIDTH + 400], WHITE, "Top half is not drawn");
+    assert_eq!(buffer[300 * WIDTH + 400], WHITE, "No chord");
+}
+
+#[test]
+fn test_draw_pie_outlines_both_radii() {
+    let mut buffer = new_buffer();
+    draw_arc_with_fill(&mut buffer, &arc(0.0, 90.0, true), Some(BLACK), None, 1);
+
+    assert_eq!(buffer[300 * WIDTH + 450], BLACK, "Radius to 3 o'clock");
+    assert_eq!(buffer[350 * WIDTH + 400], BLACK, "Radius to 6 o'clock");
+    assert_eq!(buffer[340 * WIDTH + 440], WHITE, "Interior");
+}
+
+#[test]
+fn test_draw_pie_fills_wedge() {
+    let mut buffer = new_buffer();
+    draw_arc_with_fill(&mut buffer, &arc(0.0, 90.0, true), None, Some(RED), 1);
+
+    assert_eq!(buffer[340 * WIDTH + 440], RED, "Inside the wedge");
+    assert_eq!(buffer[260 * WIDTH + 440], WHITE, "Outside the sweep");
+    assert_eq!(buffer[390 * WIDTH + 490], WHITE, "Beyond the radius");
+    // A quarter of the disc, give or take the rounded edge
+    let filled = buffer.iter().filter(|&&p| p == RED).count() as f64;
+    let quarter = std::f64::consts::PI * 100.0 * 100.0 / 4.0;
+    assert!((filled - quarter).abs() < quarter * 0.03, "{}", filled);
+}
+
+#[test]
+fn test_draw_arc_fills_segment_under_chord() {
+    let mut buffer = new_buffer();
+    draw_arc_with_fill(&mut buffer, &arc(0.0, 90.0, false), None, Some(RED), 1);
+
+    assert_eq!(
+        buffer[360 * WIDTH + 460],
+        RED,
+        "Between the chord and the arc"
+    );
+    assert_eq!(
+        buffer[320 * WIDTH + 420],
+        WHITE,
+        "Between the chord and the center"
+    );
+}
+
+#[test]
+fn test_draw_arc_zero_radius_is_a_dot() {
+    let mut buffer = new_buffer();
+    let dot = ArcShape {
+        r: 0,
+        ..arc(0.0, 90.0, false)
+    };
+    draw_arc_with_fill(&mut buffer, &dot, Some(BLACK), None, 1);
+    assert_eq!(count_drawn_pixels(&buffer), 1);
+}
+
+#[test]
+fn test_draw_arc_huge_radius_does_not_panic() {
+    let mut buffer = new_buffer();
+    let huge = ArcShape {
+        cx: usize::MAX,
+        cy: usize::MAX,
+        r: usize::MAX,
+        ..arc(-45.0, 315.0, true)
+    };
+    draw_arc_with_fill(&mut buffer, &huge, Some(BLACK), Some(RED), 5);
+}
+
+// ===================
+// Rounded Rectangle Tests
+// ===================
+
+#[test]
+fn test_rounded_rect_spans_narrow_at_corners() {
+    let rect = RoundedRect::new(300, 100, 100, 200, 20);
+    assert_eq!(
+        (rect.left, rect.top, rect.right, rect.bottom),
+        (100, 100, 300, 200)
+    );
+    assert_eq!(
+        rect.span(100),
+        Some((120, 280)),
+        "Top row starts past the corner"
+    );
+    assert_eq!(rect.span(200), Some((120, 280)), "Bottom row likewise");
+    assert_eq!(
+        rect.span(150),
+        Some((100, 300)),
+        "Full width between corners"
+    );
+    assert_eq!(rect.span(99), None);
+    assert_eq!(rect.span(201), None);
+    // Narrower toward the top, symmetrically
+    for y in 100..120 {
+        let (left, right) = rect.span(y).unwrap();
+        let (next_left, _) = rect.span(y + 1).unwrap();
+        assert!(next_left <= left);
+        assert_eq!(left - 100, 300 - right);
+        assert_eq!(rect.span(y), rect.span(300 - y));
+    }
+}
+
+#[test]
+fn test_rounded_rect_radius_is_clamped() {
+    assert_eq!(RoundedRect::new(100, 100, 140, 120, 50).r, 10);
+    assert_eq!(RoundedRect::new(100, 100, 100, 300, 5).r, 0);
+    // No radius is a plain rectangle
+    let rect = RoundedRect::new(100, 100, 200, 150, 0);
+    assert_eq!(rect.span(100), Some((100, 200)));
+}
+
+#[test]
+fn test_rounded_rect_outline_lies_on_fill() {
+    let rect = RoundedRect::new(100, 100, 300, 200, 30);
+    let mut filled = new_buffer();
+    fill_rounded_rect(&mut filled, &rect, RED);
+    let mut outlined = new_buffer();
+    draw_rounded_rect(&mut outlined, &rect, BLACK, 1);
+
+    let inside = |x: usize, y: usize| filled[y * WIDTH + x] == RED;
+    for y in 90..210 {
+        for x in 90..310 {
+            // Fill pixels with an unfilled neighbor are exactly the outline
+            let boundary = inside(x, y)
+                && !(inside(x - 1, y) && inside(x + 1, y) && inside(x, y - 1) && inside(x, y + 1));
+            assert_eq!(outlined[y * WIDTH + x] == BLACK, boundary, "({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn test_rounded_rect_edge_over_fill() {
+    let mut buffer = new_buffer();
+    let rect = RoundedRect::new(100, 100, 300, 200, 30);
+    draw_rounded_rect_with_fill(&mut buffer, &rect, Some(BLACK), Some(RED), 1);
+    assert_eq!(buffer[150 * WIDTH + 200], RED);
+    assert_eq!(buffer[150 * WIDTH + 100], BLACK);
+    assert_eq!(buffer[100 * WIDTH + 200], BLACK);
+    assert_eq!(buffer[101 * WIDTH + 101], WHITE, "Cut-away corner");
+}
+
+#[test]
+fn test_draw_shape_rounded_rect_uses_drag_radius() {
+    let mut buffer = new_buffer();
+    draw_shape(
+        &mut buffer,
+        ToolMode::RoundedRect,
+        100,
+        100,
+        300,
+        200,
+        BLACK,
+        1,
+    );
+    let rect = RoundedRect::new(100, 100, 300, 200, DRAG_CORNER_RADIUS);
+    let (left, right) = rect.span(100).unwrap();
+    assert_eq!(buffer[100 * WIDTH + left], BLACK);
+    assert_eq!(buffer[100 * WIDTH + right], BLACK);
+    assert_eq!(buffer[100 * WIDTH + left - 1], WHITE);
+}
+
+#[test]
+fn test_rounded_rect_huge_does_not_panic() {
+    let mut buffer = new_buffer();
+    let rect = RoundedRect::new(0, 0, usize::MAX, usize::MAX, usize::MAX);
+    assert_eq!(rect.r, 1 << 31);
+    draw_rounded_rect_with_fill(&mut buffer, &rect, Some(BLACK), Some(RED), 5);
+}
+
+// ===================
+// Arrow Tests
+// ===================
+
+fn arrow(x2: usize, y2: usize, head: usize, both: bool) -> Arrow {
+    Arrow {
+        x1: 100,
+        y1: 300,
+        x2,
+        y2,
+        head,
+        both,
+    }
+}
+
+#[test]
+fn test_arrow_head_points_at_end() {
+    let right = arrow(300, 300, 20, false);
+    assert_eq!(right.heads(), vec![[(300, 300), (280, 310), (280, 290)]]);
+    assert_eq!(right.shaft(), ((100, 300), (280, 300)));
+    // Pointing up
+    let up = arrow(100, 100, 20, false);
+    assert_eq!(up.heads(), vec![[(100, 100), (110, 120), (90, 120)]]);
+}
+
+#[test]
+fn test_double_arrow_has_a_head_each_end() {
+    let double = arrow(300, 300, 20, true);
+    assert_eq!(
+        double.heads(),
+        vec![
+            [(300, 300), (280, 310), (280, 290)],
+            [(100, 300), (120, 290), (120, 310)]
+        ]
+    );
+    assert_eq!(double.shaft(), ((120, 300), (280, 300)));
+}
+
+#[test]
+fn test_arrow_head_fits_short_arrows() {
+    // No longer than the arrow, or half of it with two heads
+    assert_eq!(arrow(110, 300, 20, false).heads()[0][1], (100, 305));
+    assert_eq!(arrow(110, 300, 20, true).shaft(), ((105, 300), (105, 300)));
+    // Too short for a head: just the line
+    assert!(arrow(100, 300, 20, false).heads().is_empty());
+    assert_eq!(arrow(100, 300, 20, false).shaft(), ((100, 300), (100, 300)));
+}
+
+#[test]
+fn test_draw_arrow_fills_head() {
+    let mut buffer = new_buffer();
+    draw_arrow(&mut buffer, &arrow(300, 300, 20, false), RED, 1);
+    assert_eq!(buffer[300 * WIDTH + 200], RED, "Shaft");
+    assert_eq!(buffer[300 * WIDTH + 299], RED, "Tip");
+    assert_eq!(buffer[305 * WIDTH + 285], RED, "Inside the head");
+    assert_eq!(buffer[305 * WIDTH + 200], WHITE, "Beside the shaft");
+    assert_eq!(buffer[300 * WIDTH + 302], WHITE, "Past the tip");
+}
+
+#[test]
+fn test_draw_arrow_wide_brush_keeps_tip_sharp() {
+    let mut buffer = new_buffer();
+    draw_arrow(&mut buffer, &arrow(300, 300, 20, false), RED, 6);
+    // The round end of the shaft stops short of the tip
+    assert_eq!(buffer[297 * WIDTH + 296], WHITE);
+    assert_eq!(buffer[300 * WIDTH + 296], RED);
+}
+
+#[test]
+fn test_draw_shape_arrow_uses_default_head() {
+    let mut buffer = new_buffer();
+    draw_shape(&mut buffer, ToolMode::Arrow, 100, 300, 300, 300, BLACK, 1);
+    assert_eq!(
+        buffer[(300 + MIN_ARROW_HEAD / 2) * WIDTH + 300 - MIN_ARROW_HEAD],
+        BLACK
+    );
+    assert_eq!(buffer[(300 + MIN_ARROW_HEAD / 2) * WIDTH + 200], WHITE);
+}
+
+#[test]
+fn test_draw_arrow_near_edges_does_not_panic() {
+    let mut buffer = new_buffer();
+    draw_arrow(&mut buffer, &arrow(0, CANVAS_TOP, 50, true), RED, 5);
+    let huge = Arrow {
+        x1: 0,
+        y1: 0,
+        x2: usize::MAX,
+        y2: usize::MAX,
+        head: usize::MAX,
+        both: true,
+    };
+    draw_arrow(&mut buffer, &huge, RED, 5);
+}
+
+// ===================
+// Ngon and Star Tests
+// ===================
+
+#[test]
+fn test_ngon_vertices_start_straight_up() {
+    assert_eq!(
+        ngon_vertices(200, 300, 100, 4, 0.0),
+        vec![(200, 200), (300, 300), (200, 400), (100, 300)]
+    );
+    // Turned a quarter of the way round, the square sits flat
+    assert_eq!(
+        ngon_vertices(200, 300, 100, 4, 45.0),
+        vec![(271, 229), (271, 371), (129, 371), (129, 229)]
+    );
+    assert_eq!(ngon_vertices(200, 300, 100, 4, -90.0)[0], (100, 300));
+}
+
+#[test]
+fn test_star_vertices_alternate_radii() {
+    let star = star_vertices(200, 300, 100, 40, 5);
+    assert_eq!(star.len(), 10);
+    assert_eq!(star[0], (200, 200), "Tip straight up");
+    assert_eq!(star[5], (200, 340), "Inner corner straight down");
+    for (i, &(x, y)) in star.iter().enumerate() {
+        let r = (x as f64 - 200.0).hypot(y as f64 - 300.0);
+        let expected = if i % 2 == 0 { 100.0 } else { 40.0 };
+        assert!((r - expected).abs() < 1.0, "vertex {} is {} out", i, r);
+    }
+}
+
+#[test]
+fn test_vertex_count_is_capped() {
+    assert_eq!(
+        ngon_vertices(200, 300, 10, usize::MAX, 0.0).len(),
+        MAX_SIDES
+    );
+    assert_eq!(
+        star_vertices(200, 300, 10, 5, usize::MAX).len(),
+        2 * MAX_SIDES
+    );
+}
+
+#[test]
+fn test_ngon_and_star_commands_fill_and_stroke() {
+    let mut state = AppState::new();
+    state.execute(&parse_command("fill #FF0000").unwrap());
+    state.execute(&parse_command("ngon 200,300 50 6").unwrap());
+    assert_eq!(state.canvas[300 * WIDTH + 200], 0xFF0000, "Center");
+    assert_eq!(state.canvas[250 * WIDTH + 200], BLACK, "Top corner");
+    assert_eq!(state.canvas[260 * WIDTH + 160], WHITE, "Outside");
+
+    state.execute(&parse_command("star 500,300 100 30 5").unwrap());
+    assert_eq!(state.canvas[300 * WIDTH + 500], 0xFF0000, "Center");
+    assert_eq!(state.canvas[220 * WIDTH + 500], 0xFF0000, "Along a point");
+    assert_eq!(state.canvas[220 * WIDTH + 540], WHITE, "Between points");
+}
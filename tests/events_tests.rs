@@ -0,0 +1,205 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use displai::*;
+
+/// A writer whose output the test can read back
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A writer for a client that has hung up
+struct ClosedPipe;
+
+impl Write for ClosedPipe {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn text(x: usize, y: usize, s: &str) -> Command {
+    Command::Object(ObjectCommand::Text {
+        x,
+        y,
+        text: s.to_string(),
+        scale: 2,
+    })
+}
+
+// ===================
+// Subscription Tests
+// ===================
+
+#[test]
+fn test_parse_subscribe() {
+    assert_eq!(parse_command("subscribe"), Some(Command::Subscribe));
+    assert_eq!(Command::Subscribe.to_string(), "subscribe");
+    assert_eq!(parse_command("subscribe all"), None);
+    assert!(!Command::Subscribe.is_mutating());
+    assert!(validate(&Command::Subscribe).is_ok());
+}
+
+#[test]
+fn test_event_lines() {
+    assert_eq!(Event::Clicked(3).to_string(), "clicked 3");
+    assert_eq!(Event::Hover(12).to_string(), "hover 12");
+    let ready = |socket: Option<&str>| Event::Ready {
+        instance: "42-1000".to_string(),
+        socket: socket.map(str::to_string),
+    };
+    assert_eq!(ready(None).to_string(), "ready 42-1000");
+    assert_eq!(
+        ready(Some("/tmp/displai.sock")).to_string(),
+        "ready 42-1000 /tmp/displai.sock"
+    );
+}
+
+#[test]
+fn test_subscribers_each_get_events() {
+    let (a, b) = (SharedBuffer::default(), SharedBuffer::default());
+    let mut subscribers = Subscribers::new();
+    assert!(subscribers.is_empty());
+    subscribers.add(Box::new(a.clone()));
+    subscribers.add(Box::new(b.clone()));
+
+    subscribers.emit(&Event::Hover(1));
+    subscribers.emit(&Event::Clicked(1));
+    assert_eq!(a.contents(), "hover 1\nclicked 1\n");
+    assert_eq!(b.contents(), a.contents());
+}
+
+#[test]
+fn test_subscribers_drop_closed_connections() {
+    let live = SharedBuffer::default();
+    let mut subscribers = Subscribers::new();
+    subscribers.add(Box::new(ClosedPipe));
+    subscribers.add(Box::new(live.clone()));
+
+    subscribers.emit(&Event::Clicked(2));
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(live.contents(), "clicked 2\n");
+}
+
+#[test]
+fn test_subscribers_greeted_before_later_events() {
+    let (old, new) = (SharedBuffer::default(), SharedBuffer::default());
+    let mut subscribers = Subscribers::new();
+    subscribers.add(Box::new(old.clone()));
+    subscribers.add_greeted(Box::new(new.clone()), &Event::Named("hello".to_string()));
+    subscribers.emit(&Event::Clicked(1));
+    assert_eq!(new.contents(), "hello\nclicked 1\n");
+    // Only the new subscriber is greeted
+    assert_eq!(old.contents(), "clicked 1\n");
+
+    // One that has already hung up isn't added
+    subscribers.add_greeted(Box::new(ClosedPipe), &Event::Named("hello".to_string()));
+    assert_eq!(subscribers.len(), 2);
+}
+
+#[test]
+fn test_instance_id_names_the_process() {
+    let id = instance_id();
+    let (pid, started) = id.split_once('-').unwrap();
+    assert_eq!(pid, std::process::id().to_string());
+    assert!(started.parse::<u128>().unwrap() > 0);
+}
+
+// ===================
+// Pointer Event Tests
+// ===================
+
+#[test]
+fn test_hover_reported_once_per_entry() {
+    let mut pointer = PointerTracker::new();
+    assert_eq!(pointer.update(None, false), vec![]);
+    assert_eq!(pointer.update(Some(1), false), vec![Event::Hover(1)]);
+    assert_eq!(pointer.update(Some(1), false), vec![]);
+    assert_eq!(pointer.update(Some(2), false), vec![Event::Hover(2)]);
+    assert_eq!(pointer.update(None, false), vec![]);
+    assert_eq!(pointer.hovered(), None);
+    assert_eq!(pointer.update(Some(2), false), vec![Event::Hover(2)]);
+}
+
+#[test]
+fn test_click_on_object() {
+    let mut pointer = PointerTracker::new();
+    assert_eq!(
+        pointer.update(Some(4), true),
+        vec![Event::Hover(4), Event::Clicked(4)]
+    );
+    assert_eq!(pointer.update(Some(4), true), vec![Event::Clicked(4)]);
+    // Clicks on empty canvas are not events
+    assert_eq!(pointer.update(None, true), vec![]);
+}
+
+#[test]
+fn test_hit_finds_topmost_visible_object() {
+    let mut state = AppState::new();
+    state.execute(&text(100, 100, "BUTTON"));
+    state.execute(&text(120, 100, "OVER"));
+    let (x, y, w, h) = state.objects.get(1).unwrap().bounds(&state.vars);
+
+    assert_eq!(state.objects.hit(x, y, &state.vars), Some(1));
+    assert_eq!(state.objects.hit(125, 105, &state.vars), Some(2));
+    assert_eq!(state.objects.hit(x + w, y, &state.vars), None);
+    assert_eq!(state.objects.hit(x, y + h, &state.vars), None);
+
+    // Hidden (blinked off) objects can't be hit
+    let start = Instant::now();
+    state
+        .objects
+        .set_blink(2, Some(Duration::from_millis(100)), start)
+        .unwrap();
+    state
+        .objects
+        .tick(start + Duration::from_millis(150), SystemTime::now());
+    assert_eq!(state.objects.hit(125, 105, &state.vars), Some(1));
+}
+
+#[test]
+fn test_named_events_and_names() {
+    assert_eq!(Event::Named("start".to_string()).to_string(), "start");
+    assert!(is_valid_event_name("start-2_b"));
+    assert!(!is_valid_event_name(""));
+    assert!(!is_valid_event_name("two words"));
+    assert!(!is_valid_event_name(&"x".repeat(MAX_EVENT_NAME_LEN + 1)));
+}
+
+#[test]
+fn test_change_event_line() {
+    let event = Event::Changed {
+        name: "gain".to_string(),
+        value: "7".to_string(),
+    };
+    assert_eq!(event.to_string(), "change gain 7");
+}
+
+#[test]
+fn test_submit_event_line() {
+    let event = Event::Submitted {
+        name: "name".to_string(),
+        value: "Ada Lovelace".to_string(),
+    };
+    assert_eq!(event.to_string(), "submit name Ada Lovelace");
+}
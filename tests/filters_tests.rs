@@ -0,0 +1,199 @@
+mod common;
+
+use common::{drawn, pixel, run};
+use displai::*;
+
+fn gray(level: u32) -> u32 {
+    level << 16 | level << 8 | level
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_filters() {
+    let cases = [
+        ("filter grayscale", Filter::Grayscale, None),
+        ("filter invert", Filter::Invert, None),
+        ("filter blur 3", Filter::Blur(3), None),
+        ("filter brightness -40", Filter::Brightness(-40), None),
+        (
+            "filter grayscale 10,40 200,300",
+            Filter::Grayscale,
+            Some((10, 40, 200, 300)),
+        ),
+        (
+            "filter brightness 25 300,300 100,100",
+            Filter::Brightness(25),
+            Some((300, 300, 100, 100)),
+        ),
+    ];
+    for (line, filter, region) in cases {
+        let cmd = parse_command(line).unwrap();
+        assert_eq!(cmd, Command::Filter { filter, region });
+        assert_eq!(cmd.to_string(), line);
+        assert_eq!(cmd.name(), "filter");
+        assert!(validate(&cmd).is_ok(), "{}", line);
+        assert!(cmd.is_mutating());
+    }
+    assert_eq!(
+        parse_command("filter brightness +25").unwrap().to_string(),
+        "filter brightness 25"
+    );
+
+    // Invalid formats
+    assert_eq!(parse_command("filter"), None);
+    assert_eq!(parse_command("filter sepia"), None);
+    assert_eq!(parse_command("filter blur"), None);
+    assert_eq!(parse_command("filter blur big"), None);
+    assert_eq!(parse_command("filter brightness"), None);
+    assert_eq!(parse_command("filter invert 10,40"), None);
+    assert_eq!(parse_command("filter invert 10,40 20,50 30,60"), None);
+    assert_eq!(parse_command("filter invert 10 40"), None);
+}
+
+#[test]
+fn test_validate_filters() {
+    let error = |line: &str| validate(&parse_command(line).unwrap()).unwrap_err();
+    assert_eq!(error("filter blur 0"), "blur radius 0 is outside 1-50");
+    assert_eq!(error("filter blur 51"), "blur radius 51 is outside 1-50");
+    assert_eq!(
+        error("filter brightness -256"),
+        "brightness -256 is outside -255-255"
+    );
+    assert!(error("filter invert 10,10 20,50").contains("outside the canvas"));
+    assert!(validate(&parse_command("filter brightness 255").unwrap()).is_ok());
+}
+
+// ===================
+// Filter Tests
+// ===================
+
+#[test]
+fn test_grayscale() {
+    let state = drawn(&["fill 2", "rect 100,100 200,200", "filter grayscale"]);
+    let [r, g, b] = [
+        COLOR_PALETTE[2] >> 16,
+        COLOR_PALETTE[2] >> 8,
+        COLOR_PALETTE[2],
+    ]
+    .map(|channel| channel & 0xFF);
+    let luma = (299 * r + 587 * g + 114 * b + 500) / 1000;
+    assert_eq!(pixel(&state, 150, 150), gray(luma));
+    assert_eq!(pixel(&state, 50, 50), WHITE);
+}
+
+#[test]
+fn test_invert_twice_restores() {
+    let before = drawn(&["fill 2", "rect 100,100 200,200"]);
+    let mut state = before.clone();
+    run(&mut state, "filter invert");
+    assert_eq!(pixel(&state, 50, 50), BLACK);
+    assert_eq!(pixel(&state, 150, 150), COLOR_PALETTE[2] ^ 0xFFFFFF);
+    run(&mut state, "filter invert");
+    assert!(state.canvas == before.canvas);
+}
+
+#[test]
+fn test_brightness_holds_to_the_channel_range() {
+    let state = drawn(&["filter brightness 40"]);
+    assert_eq!(pixel(&state, 50, 50), WHITE);
+    let state = drawn(&["filter brightness -55"]);
+    assert_eq!(pixel(&state, 50, 50), gray(200));
+    let state = drawn(&["filter brightness -255", "filter brightness 20"]);
+    assert_eq!(pixel(&state, 50, 50), gray(20));
+}
+
+#[test]
+fn test_blur_spreads_a_dot() {
+    let state = drawn(&["size 1", "dot 100,100", "filter blur 1"]);
+    // Eight white pixels and one black in every 3x3 window
+    for y in 99..=101 {
+        for x in 99..=101 {
+            assert_eq!(pixel(&state, x, y), gray(227), "{},{}", x, y);
+        }
+    }
+    assert_eq!(pixel(&state, 102, 100), WHITE);
+    assert_eq!(pixel(&state, 100, 98), WHITE);
+}
+
+#[test]
+fn test_blur_keeps_flat_areas_and_edges() {
+    // Averaging only pixels inside the rectangle, a flat one stays flat
+    let state = drawn(&["filter blur 5 0,30 799,200"]);
+    assert!(state.canvas.pixels().iter().all(|&p| p == WHITE));
+}
+
+#[test]
+fn test_filter_stays_in_its_region() {
+    let before = drawn(&["fill 0", "rect 100,100 300,300"]);
+    let mut state = before.clone();
+    run(&mut state, "filter blur 4 80,150 120,200");
+    run(&mut state, "filter invert 250,250 260,260");
+    assert_eq!(pixel(&state, 255, 255), WHITE);
+    for (i, (&now, &was)) in state
+        .canvas
+        .pixels()
+        .iter()
+        .zip(before.canvas.pixels())
+        .enumerate()
+    {
+        let (x, y) = (i % WIDTH, i / WIDTH);
+        let inside = |(x1, y1, x2, y2)| (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+        if !inside((80, 150, 120, 200)) && !inside((250, 250, 260, 260)) {
+            assert_eq!(now, was, "{},{}", x, y);
+        }
+    }
+    // The rectangle's edge is blurred where the region crosses it
+    assert_ne!(pixel(&state, 98, 175), pixel(&before, 98, 175));
+    assert_ne!(pixel(&state, 102, 175), pixel(&before, 102, 175));
+}
+
+#[test]
+fn test_filter_region_is_clipped_to_the_canvas() {
+    let mut state = AppState::new();
+    assert_eq!(run(&mut state, "filter invert 700,500 5000,5000"), None);
+    assert_eq!(pixel(&state, 799, canvas_bottom() - 1), BLACK);
+    assert_eq!(
+        pixel(&state, 799, canvas_bottom()),
+        state.canvas[canvas_bottom() * WIDTH]
+    );
+}
+
+#[test]
+fn test_invalid_filter_leaves_the_canvas() {
+    let before = drawn(&["fill 0", "rect 100,100 300,300"]);
+    let mut state = before.clone();
+    assert_eq!(
+        run(&mut state, "filter blur 0"),
+        Some("error: blur radius 0 is outside 1-50".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "filter invert 10,0 20,10"),
+        Some("error: region 10,0 20,10 is outside the canvas".to_string())
+    );
+    assert!(state.canvas == before.canvas);
+}
+
+#[test]
+fn test_filter_in_a_pane() {
+    let mut state = drawn(&["panes 2x1", "@1 filter invert"]);
+    let (left, right) = (state.pane(1).unwrap(), state.pane(2).unwrap());
+    assert_eq!(pixel(&state, left.x + 5, left.y + 5), BLACK);
+    assert_eq!(pixel(&state, right.x + 5, right.y + 5), WHITE);
+    // A region is pane-relative
+    run(&mut state, "@2 filter invert 0,0 9,9");
+    assert_eq!(pixel(&state, right.x + 5, right.y + 5), BLACK);
+    assert_eq!(pixel(&state, right.x + 15, right.y + 5), WHITE);
+}
+
+#[test]
+fn test_filter_locks_its_region() {
+    let region = |line: &str| command_region(&parse_command(line).unwrap(), 1);
+    assert_eq!(
+        region("filter blur 3 200,100 100,50"),
+        Some(Region::new(100, 50, 101, 51))
+    );
+    assert_eq!(region("filter grayscale"), Some(Region::ALL));
+}
@@ -0,0 +1,199 @@
+mod common;
+
+use common::run;
+use displai::*;
+
+fn key(state: &mut AppState, key: KeyInput) -> KeyRoute {
+    route_key(&mut state.objects, &mut state.vars, key)
+}
+
+/// A text object, button, text input, checkbox, and slider (IDs 1-5)
+fn form() -> AppState {
+    let mut state = AppState::new();
+    run(&mut state, "obj text 10,40 \"Settings\"");
+    run(&mut state, "button 10,60,80,24 \"Save\" save");
+    run(&mut state, "input 10,100,200 name");
+    run(&mut state, "checkbox 10,140 \"Loud\" loud");
+    run(&mut state, "slider 10,170,100 0 10 volume");
+    state
+}
+
+// ===================
+// Focus Cycling Tests
+// ===================
+
+#[test]
+fn test_tab_cycles_through_widgets_in_order() {
+    let mut state = form();
+    let mut order = Vec::new();
+    for _ in 0..5 {
+        assert_eq!(key(&mut state, KeyInput::Tab), KeyRoute::Consumed(None));
+        order.push(state.objects.focused().unwrap());
+    }
+    // The text object is skipped, and focus wraps around
+    assert_eq!(order, vec![2, 3, 4, 5, 2]);
+}
+
+#[test]
+fn test_shift_tab_cycles_backward() {
+    let mut state = form();
+    key(&mut state, KeyInput::BackTab);
+    assert_eq!(state.objects.focused(), Some(5));
+    key(&mut state, KeyInput::BackTab);
+    assert_eq!(state.objects.focused(), Some(4));
+    state.objects.set_focus(Some(2));
+    key(&mut state, KeyInput::BackTab);
+    assert_eq!(state.objects.focused(), Some(5));
+}
+
+#[test]
+fn test_tab_without_widgets_is_unclaimed() {
+    let mut state = AppState::new();
+    run(&mut state, "obj text 10,40 \"Hello\"");
+    assert_eq!(key(&mut state, KeyInput::Tab), KeyRoute::Unclaimed);
+    assert_eq!(state.objects.focused(), None);
+}
+
+#[test]
+fn test_escape_drops_focus_then_is_unclaimed() {
+    let mut state = form();
+    state.objects.set_focus(Some(3));
+    assert_eq!(key(&mut state, KeyInput::Escape), KeyRoute::Consumed(None));
+    assert_eq!(state.objects.focused(), None);
+    assert_eq!(key(&mut state, KeyInput::Escape), KeyRoute::Unclaimed);
+}
+
+#[test]
+fn test_deleting_widget_drops_its_focus() {
+    let mut state = form();
+    state.objects.set_focus(Some(4));
+    run(&mut state, "obj delete 4");
+    assert_eq!(state.objects.focused(), None);
+    key(&mut state, KeyInput::Tab);
+    key(&mut state, KeyInput::Tab);
+    key(&mut state, KeyInput::Tab);
+    assert_eq!(state.objects.focused(), Some(5));
+}
+
+// ===================
+// Key Routing Tests
+// ===================
+
+#[test]
+fn test_unfocused_keys_are_unclaimed() {
+    let mut state = form();
+    for k in [KeyInput::Char('a'), KeyInput::Enter, KeyInput::Left] {
+        assert_eq!(key(&mut state, k), KeyRoute::Unclaimed);
+    }
+    assert_eq!(state.vars.get("name"), Some(""));
+}
+
+#[test]
+fn test_focused_input_takes_typing() {
+    let mut state = form();
+    state.objects.set_focus(Some(3));
+    for c in "Bob".chars() {
+        assert_eq!(key(&mut state, KeyInput::Char(c)), KeyRoute::Consumed(None));
+    }
+    key(&mut state, KeyInput::Backspace);
+    key(&mut state, KeyInput::Char('x'));
+    assert_eq!(state.vars.get("name"), Some("Box"));
+    // Characters it can't hold are still its keys, not shortcuts
+    assert_eq!(
+        key(&mut state, KeyInput::Char('"')),
+        KeyRoute::Consumed(None)
+    );
+    assert_eq!(
+        key(&mut state, KeyInput::Enter),
+        KeyRoute::Consumed(Some(Event::Submitted {
+            name: "name".to_string(),
+            value: "Box".to_string()
+        }))
+    );
+    assert_eq!(key(&mut state, KeyInput::Up), KeyRoute::Unclaimed);
+}
+
+#[test]
+fn test_enter_and_space_activate_buttons_and_checkboxes() {
+    let mut state = form();
+    state.objects.set_focus(Some(2));
+    assert_eq!(
+        key(&mut state, KeyInput::Enter),
+        KeyRoute::Consumed(Some(Event::Named("save".to_string())))
+    );
+    assert_eq!(key(&mut state, KeyInput::Char('s')), KeyRoute::Unclaimed);
+
+    state.objects.set_focus(Some(4));
+    let route = key(&mut state, KeyInput::Char(' '));
+    assert_eq!(
+        route,
+        KeyRoute::Consumed(Some(Event::Changed {
+            name: "loud".to_string(),
+            value: "on".to_string()
+        }))
+    );
+    key(&mut state, KeyInput::Enter);
+    assert_eq!(state.vars.get("loud"), Some("off"));
+}
+
+#[test]
+fn test_arrows_step_focused_slider() {
+    let mut state = form();
+    state.objects.set_focus(Some(5));
+    assert_eq!(state.vars.get("volume"), Some("0"));
+    // Already at the minimum: used, but nothing changes
+    assert_eq!(key(&mut state, KeyInput::Left), KeyRoute::Consumed(None));
+    key(&mut state, KeyInput::Right);
+    key(&mut state, KeyInput::Up);
+    assert_eq!(state.vars.get("volume"), Some("2"));
+    key(&mut state, KeyInput::Down);
+    assert_eq!(state.vars.get("volume"), Some("1"));
+
+    assert_eq!(
+        key(&mut state, KeyInput::End),
+        KeyRoute::Consumed(Some(Event::Changed {
+            name: "volume".to_string(),
+            value: "10".to_string()
+        }))
+    );
+    key(&mut state, KeyInput::Home);
+    assert_eq!(state.vars.get("volume"), Some("0"));
+    assert_eq!(key(&mut state, KeyInput::Char(' ')), KeyRoute::Unclaimed);
+}
+
+#[test]
+fn test_home_end_on_extreme_slider_range() {
+    let mut state = AppState::new();
+    run(
+        &mut state,
+        &format!("slider 10,40,100 {} {} v", i64::MIN, i64::MAX),
+    );
+    state.objects.set_focus(Some(1));
+    key(&mut state, KeyInput::End);
+    assert_eq!(state.vars.get("v"), Some(i64::MAX.to_string().as_str()));
+    key(&mut state, KeyInput::Home);
+    assert_eq!(state.vars.get("v"), Some(i64::MIN.to_string().as_str()));
+}
+
+#[test]
+fn test_key_names() {
+    assert_eq!(KeyInput::Char('a').name(), "a");
+    assert_eq!(KeyInput::Char(' ').name(), "space");
+    assert_eq!(KeyInput::BackTab.name(), "shift-tab");
+    assert_eq!(Event::Key(KeyInput::Left.name()).to_string(), "key left");
+}
+
+// ===================
+// Focus Rendering Tests
+// ===================
+
+#[test]
+fn test_focused_widget_draws_focus_ring() {
+    let mut state = form();
+    let ring_y = 60 - FOCUS_RING_GAP - 1;
+    assert_eq!(state.render()[ring_y * WIDTH + 50], WHITE);
+    state.objects.set_focus(Some(2));
+    assert_eq!(state.render()[ring_y * WIDTH + 50], BLACK);
+    let ring_x = 10 + 80 + FOCUS_RING_GAP;
+    assert_eq!(state.render()[70 * WIDTH + ring_x], BLACK);
+}
@@ -0,0 +1,157 @@
+use displai::*;
+
+// ===================
+// Command Generator Tests
+// ===================
+
+#[test]
+fn test_generator_is_deterministic() {
+    let a: Vec<Command> = CommandGenerator::new(42).take(200).collect();
+    let b: Vec<Command> = CommandGenerator::new(42).take(200).collect();
+    assert_eq!(a, b);
+
+    let c: Vec<Command> = CommandGenerator::new(43).take(200).collect();
+    assert_ne!(a, c, "Different seeds should produce different commands");
+}
+
+#[test]
+fn test_generator_never_produces_snapshot() {
+    for cmd in CommandGenerator::new(7).take(2000) {
+        assert!(!matches!(cmd, Command::Snapshot(_)));
+    }
+}
+
+#[test]
+fn test_generator_below_stays_in_range() {
+    let mut rng = CommandGenerator::new(0);
+    for n in 1..50 {
+        assert!(rng.below(n) < n);
+    }
+    assert_eq!(rng.below(0), 0);
+}
+
+// ===================
+// Parser Property Tests
+// ===================
+
+#[test]
+fn test_generated_commands_round_trip_through_parser() {
+    for seed in 0..20 {
+        for cmd in CommandGenerator::new(seed).take(200) {
+            let line = cmd.to_string();
+            assert_eq!(
+                parse_command(&line),
+                Some(cmd.clone()),
+                "Round trip failed for '{}'",
+                line
+            );
+        }
+    }
+}
+
+#[test]
+fn test_parser_never_panics_on_generated_lines() {
+    let mut rng = CommandGenerator::new(1234);
+    for _ in 0..5000 {
+        let line = rng.next_line();
+        let _ = parse_command(&line);
+    }
+}
+
+#[test]
+fn test_command_display_protocol_syntax() {
+    assert_eq!(Command::Edge(None).to_string(), "edge none");
+    assert_eq!(Command::Fill(Some(COLOR_PALETTE[3])).to_string(), "fill 3");
+    assert_eq!(
+        Command::Oval {
+            x: 1,
+            y: 2,
+            rx: 3,
+            ry: 4
+        }
+        .to_string(),
+        "oval 1,2 3,4"
+    );
+    let cmd = Command::Points(vec![
+        AttributedPoint {
+            x: 1,
+            y: 2,
+            color: None,
+            size: None,
+        },
+        AttributedPoint {
+            x: 3,
+            y: 4,
+            color: Some(COLOR_PALETTE[5]),
+            size: Some(6),
+        },
+    ]);
+    assert_eq!(cmd.to_string(), "points 1,2 3,4:5:6");
+}
+
+// ===================
+// apply_commands Tests
+// ===================
+
+#[test]
+fn test_apply_commands_empty_is_blank_canvas() {
+    let canvas = apply_commands(0, &[]);
+    assert_eq!(canvas.len(), WIDTH * HEIGHT);
+    assert!(canvas.iter().all(|&p| p == WHITE));
+}
+
+#[test]
+fn test_apply_commands_is_deterministic() {
+    let commands: Vec<Command> = CommandGenerator::new(99).take(100).collect();
+    assert_eq!(apply_commands(5, &commands), apply_commands(5, &commands));
+}
+
+#[test]
+fn test_apply_commands_draws() {
+    let y = CANVAS_TOP + 50;
+    let commands = vec![
+        Command::Edge(Some(COLOR_PALETTE[2])),
+        Command::Size(1),
+        Command::Dot { x: 100, y },
+    ];
+    let canvas = apply_commands(0, &commands);
+    assert_eq!(canvas[y * WIDTH + 100], COLOR_PALETTE[2]);
+}
+
+#[test]
+fn test_apply_random_commands_never_touches_ui_area() {
+    for seed in 0..5 {
+        let commands: Vec<Command> = CommandGenerator::new(seed).take(60).collect();
+        let canvas = apply_commands(seed, &commands);
+
+        for y in (0..CANVAS_TOP).chain(CANVAS_BOTTOM..HEIGHT) {
+            for x in 0..WIDTH {
+                assert_eq!(
+                    canvas[y * WIDTH + x],
+                    WHITE,
+                    "Seed {} drew outside the canvas at ({},{})",
+                    seed,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_generator_produces_extreme_coordinates() {
+    let has_extreme = CommandGenerator::new(3).take(2000).any(|cmd| match cmd {
+        Command::Dot { x, y } => x > WIDTH * 2 || y > HEIGHT * 2,
+        _ => false,
+    });
+    assert!(has_extreme, "Generator should mix in overflow-prone values");
+}
+
+#[test]
+fn test_apply_commands_survives_many_seeds() {
+    for seed in 100..130 {
+        let commands: Vec<Command> = CommandGenerator::new(seed).take(25).collect();
+        let _ = apply_commands(seed, &commands);
+    }
+}
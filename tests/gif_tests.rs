@@ -0,0 +1,167 @@
+use std::time::{Duration, Instant};
+
+use displai::*;
+use image::codecs::gif::GifDecoder;
+use image::AnimationDecoder;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("displai_gif_{}", name))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Decode a GIF into (delay in ms, frame) pairs
+fn read_gif(path: &str) -> Vec<(u32, image::RgbaImage)> {
+    let file = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+    GifDecoder::new(file)
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap()
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            (numer / denom, frame.into_buffer())
+        })
+        .collect()
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_record_gif() {
+    let start = |path: &str, interval_ms| {
+        Command::Gif(GifCommand::Start {
+            path: path.to_string(),
+            interval_ms,
+        })
+    };
+    assert_eq!(
+        parse_command("record gif /tmp/out.gif"),
+        Some(start("/tmp/out.gif", None))
+    );
+    assert_eq!(
+        parse_command("record gif /tmp/my runs/out.gif 100"),
+        Some(start("/tmp/my runs/out.gif", Some(100)))
+    );
+    assert_eq!(
+        parse_command("record gif stop"),
+        Some(Command::Gif(GifCommand::Stop))
+    );
+    for cmd in [
+        start("/tmp/a.gif", None),
+        start("/tmp/a b.gif", Some(250)),
+        Command::Gif(GifCommand::Stop),
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    }
+    assert_eq!(parse_command("record gif"), None);
+    assert_eq!(parse_command("record gifout.gif"), None);
+}
+
+#[test]
+fn test_validate_gif_interval() {
+    let start = |interval_ms| {
+        Command::Gif(GifCommand::Start {
+            path: "/tmp/a.gif".to_string(),
+            interval_ms,
+        })
+    };
+    assert!(validate(&start(None)).is_ok());
+    assert!(validate(&start(Some(MIN_GIF_INTERVAL_MS))).is_ok());
+    assert!(validate(&start(Some(MAX_GIF_INTERVAL_MS))).is_ok());
+    assert!(validate(&start(Some(MIN_GIF_INTERVAL_MS - 1))).is_err());
+    assert!(validate(&start(Some(MAX_GIF_INTERVAL_MS + 1))).is_err());
+    assert!(!start(None).is_mutating());
+}
+
+// ===================
+// Recorder Tests
+// ===================
+
+#[test]
+fn test_gif_records_each_change() {
+    let path = temp_path("changes.gif");
+    let t0 = Instant::now();
+    let mut state = AppState::new();
+    let mut recorder = GifRecorder::start(&path, None).unwrap();
+
+    assert!(recorder.offer(&state.canvas, t0));
+    // Nothing changed, so no frame
+    assert!(!recorder.offer(&state.canvas, t0 + Duration::from_millis(100)));
+    state.execute(&parse_command("fill 2").unwrap());
+    state.execute(&parse_command("rect 10,40 110,90").unwrap());
+    assert!(recorder.offer(&state.canvas, t0 + Duration::from_millis(300)));
+    assert_eq!(recorder.frames(), 2);
+    assert_eq!(recorder.finish(), Ok(2));
+
+    let frames = read_gif(&path);
+    assert_eq!(frames.len(), 2);
+    // The first frame shows until the change; the last for LAST_GIF_FRAME_MS
+    assert_eq!(frames[0].0, 300);
+    assert_eq!(frames[1].0, LAST_GIF_FRAME_MS as u32);
+    // Frames hold the canvas rows only
+    let (_, last) = &frames[1];
+    assert_eq!(
+        last.dimensions(),
+        (WIDTH as u32, (CANVAS_BOTTOM - CANVAS_TOP) as u32)
+    );
+    assert_eq!(last.get_pixel(50, 50 - CANVAS_TOP as u32).0[3], 0xFF);
+    assert_eq!(frames[0].1.get_pixel(50, 30).0, [0xFF, 0xFF, 0xFF, 0xFF]);
+    assert_ne!(last.get_pixel(50, 30).0, [0xFF, 0xFF, 0xFF, 0xFF]);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_gif_records_at_interval() {
+    let path = temp_path("interval.gif");
+    let t0 = Instant::now();
+    let state = AppState::new();
+    let mut recorder = GifRecorder::start(&path, Some(Duration::from_millis(100))).unwrap();
+
+    assert!(recorder.offer(&state.canvas, t0));
+    assert!(!recorder.offer(&state.canvas, t0 + Duration::from_millis(50)));
+    // Unchanged frames are still taken on the interval
+    assert!(recorder.offer(&state.canvas, t0 + Duration::from_millis(100)));
+    assert!(recorder.offer(&state.canvas, t0 + Duration::from_millis(200)));
+    assert_eq!(recorder.finish(), Ok(3));
+    assert_eq!(read_gif(&path).len(), 3);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_apply_gif_command_responses() {
+    let path = temp_path("apply.gif");
+    let mut recorder = None;
+    assert_eq!(
+        apply_gif_command(&mut recorder, &GifCommand::Stop),
+        failed("not recording a gif")
+    );
+    let start = GifCommand::Start {
+        path: path.clone(),
+        interval_ms: None,
+    };
+    assert_eq!(
+        apply_gif_command(&mut recorder, &start),
+        reply(format!("recording gif {}", path))
+    );
+    recorder
+        .as_mut()
+        .unwrap()
+        .offer(&AppState::new().canvas, Instant::now());
+    assert_eq!(
+        apply_gif_command(&mut recorder, &GifCommand::Stop),
+        reply(format!("recorded 1 frames to {}", path))
+    );
+    assert!(recorder.is_none());
+
+    let bad = GifCommand::Start {
+        path: "/nonexistent/dir/a.gif".to_string(),
+        interval_ms: None,
+    };
+    assert!(apply_gif_command(&mut recorder, &bad).is_err());
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,280 @@
+mod common;
+
+use common::{drawn, pixel, run};
+use displai::*;
+
+fn red(color: u32) -> u32 {
+    (color >> 16) & 0xFF
+}
+
+fn blue(color: u32) -> u32 {
+    color & 0xFF
+}
+
+const BLACK_TO_WHITE: Gradient = Gradient {
+    shape: GradientShape::Linear { angle: 0.0 },
+    from: 0x000000,
+    to: 0xFFFFFF,
+};
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_fill_gradient() {
+    assert_eq!(
+        parse_command("fill-gradient linear #FF0000 #0000FF 90"),
+        Some(Command::FillGradient(Some(Gradient {
+            shape: GradientShape::Linear { angle: 90.0 },
+            from: 0xFF0000,
+            to: 0x0000FF,
+        })))
+    );
+    // The angle defaults to left to right
+    assert_eq!(
+        parse_command("fill-gradient linear #000000 #FFFFFF"),
+        Some(Command::FillGradient(Some(BLACK_TO_WHITE)))
+    );
+    assert_eq!(
+        parse_command("fill-gradient radial #FFFFFF #00000080"),
+        Some(Command::FillGradient(Some(Gradient {
+            shape: GradientShape::Radial,
+            from: 0xFFFFFF,
+            to: with_alpha(0x000000, 0x80),
+        })))
+    );
+    assert_eq!(
+        parse_command("fill-gradient off"),
+        Some(Command::FillGradient(None))
+    );
+
+    // Invalid formats
+    assert_eq!(parse_command("fill-gradient"), None);
+    assert_eq!(parse_command("fill-gradient linear #000000"), None);
+    assert_eq!(
+        parse_command("fill-gradient linear #000000 #FFFFFF up"),
+        None
+    );
+    assert_eq!(
+        parse_command("fill-gradient radial #000000 #FFFFFF 90"),
+        None
+    );
+    assert_eq!(parse_command("fill-gradient conic #000000 #FFFFFF"), None);
+    assert_eq!(parse_command("fill-gradient linear #00000 #FFFFFF"), None);
+}
+
+#[test]
+fn test_fill_gradient_round_trips() {
+    for line in [
+        "fill-gradient linear #FF0000 #0000FF 45",
+        "fill-gradient linear #102030 #F0E0D0 0",
+        "fill-gradient radial #FFFFF0 #00000080",
+        "fill-gradient off",
+    ] {
+        let cmd = parse_command(line).unwrap();
+        assert_eq!(cmd.to_string(), line);
+        assert_eq!(cmd.name(), "fill-gradient");
+        assert!(validate(&cmd).is_ok(), "{}", line);
+    }
+}
+
+#[test]
+fn test_validate_gradient_angle() {
+    let cmd = parse_command("fill-gradient linear #000000 #FFFFFF NaN").unwrap();
+    assert_eq!(
+        validate(&cmd),
+        Err("gradient angle must be a number".to_string())
+    );
+}
+
+// ===================
+// Color Tests
+// ===================
+
+#[test]
+fn test_gradient_color_at() {
+    assert_eq!(BLACK_TO_WHITE.color_at(0.0), 0x000000);
+    assert_eq!(BLACK_TO_WHITE.color_at(1.0), 0xFFFFFF);
+    assert_eq!(BLACK_TO_WHITE.color_at(0.5), 0x808080);
+    // Clamped beyond the ends
+    assert_eq!(BLACK_TO_WHITE.color_at(-1.0), 0x000000);
+    assert_eq!(BLACK_TO_WHITE.color_at(2.0), 0xFFFFFF);
+    // Transparency is mixed too
+    let fading = Gradient {
+        to: with_alpha(0x000000, 0x00),
+        ..BLACK_TO_WHITE
+    };
+    assert_eq!(alpha(fading.color_at(1.0)), 0x00);
+    assert_eq!(alpha(fading.color_at(0.0)), 0xFF);
+}
+
+#[test]
+fn test_linear_paint_spans_the_box() {
+    let paint = Fill::from(BLACK_TO_WHITE).over(100, 100, 200, 150);
+    assert_eq!(paint.at(100, 120), 0x000000);
+    assert_eq!(paint.at(200, 120), 0xFFFFFF);
+    assert_eq!(paint.at(150, 100), paint.at(150, 150));
+    // Downwards, the top is the start
+    let down = Gradient {
+        shape: GradientShape::Linear { angle: 90.0 },
+        ..BLACK_TO_WHITE
+    };
+    let paint = Fill::from(down).over(100, 100, 200, 150);
+    assert_eq!(paint.at(120, 100), 0x000000);
+    assert_eq!(paint.at(120, 150), 0xFFFFFF);
+    assert_eq!(paint.at(100, 125), paint.at(200, 125));
+}
+
+#[test]
+fn test_radial_paint_from_the_center() {
+    let radial = Gradient {
+        shape: GradientShape::Radial,
+        ..BLACK_TO_WHITE
+    };
+    let paint = Fill::from(radial).over(100, 100, 200, 140);
+    assert_eq!(paint.at(150, 120), 0x000000);
+    // The enclosed ellipse's edge is the end color
+    assert_eq!(paint.at(200, 120), 0xFFFFFF);
+    assert_eq!(paint.at(150, 100), 0xFFFFFF);
+    assert_eq!(paint.at(100, 100), 0xFFFFFF);
+}
+
+#[test]
+fn test_solid_paint() {
+    let paint = Fill::from(0x123456).over(0, 0, 10, 10);
+    assert_eq!(paint, Paint::Solid(0x123456));
+    assert_eq!(paint.at(3, 4), 0x123456);
+    assert_eq!(paint.color(), 0x123456);
+}
+
+// ===================
+// Drawing Tests
+// ===================
+
+#[test]
+fn test_rect_fills_with_linear_gradient() {
+    let state = drawn(&[
+        "edge none",
+        "fill-gradient linear #FF0000 #0000FF",
+        "rect 100,100 300,200",
+    ]);
+    let (left, middle, right) = (
+        pixel(&state, 100, 150),
+        pixel(&state, 200, 150),
+        pixel(&state, 300, 150),
+    );
+    assert_eq!(left, 0xFF0000);
+    assert_eq!(right, 0x0000FF);
+    assert!(red(middle) < red(left) && blue(middle) > blue(left));
+    // Constant down each column
+    assert_eq!(pixel(&state, 200, 100), pixel(&state, 200, 200));
+    // Nothing outside the rectangle
+    assert_eq!(pixel(&state, 99, 150), 0xFFFFFF);
+    assert_eq!(pixel(&state, 301, 150), 0xFFFFFF);
+}
+
+#[test]
+fn test_circle_fills_with_radial_gradient() {
+    let state = drawn(&[
+        "edge none",
+        "fill-gradient radial #000000 #FFFFFF",
+        "circle 200,200 50",
+    ]);
+    let center = pixel(&state, 200, 200);
+    let near_edge = pixel(&state, 240, 200);
+    assert_eq!(center, 0x000000);
+    assert!(red(near_edge) > 0xC0);
+    assert_eq!(pixel(&state, 180, 200), pixel(&state, 220, 200));
+}
+
+#[test]
+fn test_edge_draws_over_gradient() {
+    let state = drawn(&[
+        "edge #00FF00",
+        "size 1",
+        "fill-gradient linear #FF0000 #0000FF",
+        "rect 100,100 300,200",
+    ]);
+    assert_eq!(pixel(&state, 100, 150), 0x00FF00);
+    assert_eq!(pixel(&state, 300, 150), 0x00FF00);
+    assert_ne!(pixel(&state, 150, 150), pixel(&state, 250, 150));
+}
+
+#[test]
+fn test_gradient_fills_polygons_and_rounded_rects() {
+    let state = drawn(&[
+        "edge none",
+        "fill-gradient linear #000000 #FFFFFF",
+        "polygon 100,100 300,100 300,200 100,200",
+        "rrect 100,250 300,350 20",
+    ]);
+    assert!(red(pixel(&state, 120, 150)) < red(pixel(&state, 280, 150)));
+    assert!(red(pixel(&state, 120, 300)) < red(pixel(&state, 280, 300)));
+    // The rounded corner stays blank
+    assert_eq!(pixel(&state, 100, 250), 0xFFFFFF);
+}
+
+#[test]
+fn test_antialiased_gradient_fill() {
+    let state = drawn(&[
+        "aa on",
+        "edge none",
+        "fill-gradient linear #000000 #FFFFFF",
+        "circle 200,200 50",
+    ]);
+    assert!(red(pixel(&state, 160, 200)) < red(pixel(&state, 240, 200)));
+}
+
+#[test]
+fn test_fill_clears_gradient() {
+    let mut state = drawn(&["fill-gradient linear #FF0000 #0000FF"]);
+    assert_eq!(
+        state.gradient,
+        Some(Gradient {
+            shape: GradientShape::Linear { angle: 0.0 },
+            from: 0xFF0000,
+            to: 0x0000FF,
+        })
+    );
+    // Its start color stands in as the fill
+    assert_eq!(state.fill_color, Some(0xFF0000));
+    run(&mut state, "fill #00FF00");
+    assert_eq!(state.gradient, None);
+    run(&mut state, "edge none");
+    run(&mut state, "rect 100,100 300,200");
+    assert_eq!(pixel(&state, 300, 150), 0x00FF00);
+
+    let mut state = drawn(&["fill-gradient radial #FF0000 #0000FF"]);
+    run(&mut state, "fill-gradient off");
+    assert_eq!(state.gradient, None);
+    // The start color is left as a flat fill
+    assert_eq!(state.fill_color, Some(0xFF0000));
+}
+
+#[test]
+fn test_no_fill_draws_no_gradient() {
+    let state = drawn(&[
+        "fill-gradient linear #FF0000 #0000FF",
+        "fill none",
+        "edge none",
+        "rect 100,100 300,200",
+    ]);
+    assert_eq!(pixel(&state, 200, 150), 0xFFFFFF);
+}
+
+#[test]
+fn test_set_fill_drops_gradient() {
+    let mut state = drawn(&["fill-gradient linear #FF0000 #0000FF"]);
+    state.set_fill(Some(0x00FF00));
+    assert_eq!(state.gradient, None);
+    assert_eq!(state.fill_color, Some(0x00FF00));
+}
+
+#[test]
+fn test_mouse_shapes_use_gradient() {
+    let mut state = drawn(&["edge none", "fill-gradient linear #000000 #FFFFFF"]);
+    state.draw_shape(ToolMode::Rectangle, 100, 100, 300, 200);
+    assert_eq!(pixel(&state, 100, 150), 0x000000);
+    assert_eq!(pixel(&state, 300, 150), 0xFFFFFF);
+}
@@ -0,0 +1,902 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// ===================
+// Headless Mode Tests
+// ===================
+
+/// Run the binary headless in a scratch directory, piping `input` to stdin
+fn run_headless(name: &str, input: &str) -> (std::path::PathBuf, String) {
+    run_headless_with_args(name, &[], input)
+}
+
+/// `run_headless` with extra command-line arguments
+fn run_headless_with_args(name: &str, args: &[&str], input: &str) -> (std::path::PathBuf, String) {
+    let dir = std::env::temp_dir().join(format!("displai_headless_{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_displai"))
+        .args(["--headless", "--no-socket"])
+        .args(args)
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start displai");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    (dir, String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn test_headless_draws_and_snapshots_until_stdin_closes() {
+    let (dir, stdout) = run_headless(
+        "snapshot",
+        "fill 2\nrect 100,100 200,200\nstate\nsnapshot\n",
+    );
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["ok", "ok", "edge:0 fill:2 size:1", "saved canvas.png"]
+    );
+    let img = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    // Canvas rows start at CANVAS_TOP, so window y=150 is image row 120
+    assert_eq!(img.get_pixel(150, 120).0, [0xE0, 0x40, 0x40]);
+    assert_eq!(img.get_pixel(50, 50).0, [0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_headless_supports_history_and_tagged_replies() {
+    let (_, stdout) = run_headless(
+        "history",
+        "#1 dot 50,50\n#2 undo\n#3 undo\nbogus\nrect 1,2\n",
+    );
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "#1 ok",
+            "#2 ok",
+            "#3 error: nothing to undo",
+            "error: unknown command 'bogus'",
+            "error: invalid arguments for 'rect'",
+        ]
+    );
+}
+
+#[test]
+fn test_headless_failed_command_keeps_redo() {
+    let (_, stdout) = run_headless(
+        "history_failed",
+        "#1 line 10,40 100,100\n#2 undo\n#3 image load /nonexistent.png 10,40\n#4 redo\n#5 undo\n#6 undo\n",
+    );
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[..2], ["#1 ok", "#2 ok"]);
+    assert!(lines[2].starts_with("#3 error: "), "{}", lines[2]);
+    assert_eq!(lines[3..], ["#4 ok", "#5 ok", "#6 error: nothing to undo"]);
+}
+
+#[test]
+fn test_headless_refuses_out_of_range_arguments() {
+    let (dir, stdout) = run_headless(
+        "validate",
+        "ngon 400,300 50 2\nnotify \"hi\" 61\nimage load a.png 10,40 17\n\
+         record gif a.gif 5\nsnap on 0.6\nshadow on 2,2 1 21\nreplay a.rec 0\n\
+         beep 5\ndot 50,50\n",
+    );
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "error: ngon needs 3 to 1000 sides",
+            "error: notify duration 61 is outside 1-60",
+            "error: image scale 17 is out of range (above 0, up to 16)",
+            "error: gif interval 5 is outside 20-60000",
+            "error: snap tolerance 0.6 is outside 0-0.5 (above 0)",
+            "error: shadow blur 21 is outside 0-20",
+            "error: replay speed 0 is outside 0.1-100",
+            "error: beep frequency 5 is outside 20-20000",
+            "ok",
+        ]
+    );
+    assert!(!dir.join("a.gif").exists());
+}
+
+#[test]
+fn test_headless_snapshot_shows_edited_text_object() {
+    let (dir, stdout) = run_headless(
+        "objects",
+        "obj text 100,100 \"OLD\" 4\nobj settext 1 \"I\"\nsnapshot\n",
+    );
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["id:1", "ok", "saved canvas.png"]
+    );
+    let img = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    // "I" at scale 4: top bar spans x 104..116, stem x 108..112
+    assert_eq!(img.get_pixel(105, 71).0, [0, 0, 0]);
+    assert_eq!(img.get_pixel(109, 85).0, [0, 0, 0]);
+    // The old text's second glyph is gone
+    assert_eq!(img.get_pixel(126, 72).0, [0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_headless_records_and_replays() {
+    let recording = std::env::temp_dir().join("displai_headless_recording.rec");
+    let recording = recording.to_string_lossy().into_owned();
+    let (_, stdout) = run_headless(
+        "record",
+        &format!(
+            "record start {}\nfill 2\nrect 100,100 200,200\nsnapshot\nrecord stop\n",
+            recording
+        ),
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            format!("recording {}", recording),
+            "ok".to_string(),
+            "ok".to_string(),
+            "saved canvas.png".to_string(),
+            format!("recorded 3 commands to {}", recording),
+        ]
+    );
+
+    // The replay's own replies are dropped, but its snapshot is still taken
+    let (dir, stdout) = run_headless("replay", &format!("replay {} 100\n", recording));
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![format!("replaying 3 commands from {}", recording)]
+    );
+    let img = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    assert_eq!(img.get_pixel(150, 120).0, [0xE0, 0x40, 0x40]);
+    let _ = std::fs::remove_file(&recording);
+}
+
+#[test]
+fn test_headless_records_only_commands_that_run() {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let dir = std::env::temp_dir().join("displai_headless_record_refused");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let (socket, ready_file) = (dir.join("displai.sock"), dir.join("displai.ready"));
+    let recording = dir.join("session.rec").to_string_lossy().into_owned();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_displai"))
+        .args(["--headless", "--socket", socket.to_str().unwrap()])
+        .args(["--ready-file", ready_file.to_str().unwrap()])
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start displai");
+    while !ready_file.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    // Another client holds a region before stdin starts recording
+    let mut client = UnixStream::connect(&socket).unwrap();
+    client.write_all(b"lockregion 300,300,50,50\n").unwrap();
+    let mut locked = String::new();
+    BufReader::new(&client).read_line(&mut locked).unwrap();
+    assert_eq!(locked, "locked 1\n");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            format!(
+                "record start {}\n!k1 dot 50,50\n!k1 dot 50,50\nrect 310,310 320,320\nrecord stop\n",
+                recording
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .collect::<Vec<_>>(),
+        vec![
+            format!("recording {}", recording),
+            "ok".to_string(),
+            "ok duplicate".to_string(),
+            "error: region is locked by another client (lock 1)".to_string(),
+            format!("recorded 1 commands to {}", recording),
+        ]
+    );
+
+    // Neither the retried duplicate nor the refused rect is replayed
+    let text = std::fs::read_to_string(&recording).unwrap();
+    let commands: Vec<_> = text
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(' ').map(|(_, cmd)| cmd))
+        .collect();
+    assert_eq!(commands, ["dot 50,50"]);
+}
+
+#[test]
+fn test_headless_steps_through_script() {
+    let script = std::env::temp_dir().join("displai_headless_script.txt");
+    std::fs::write(
+        &script,
+        "# two shapes\nfill 2\nrect 100,100 200,200\nstate\n",
+    )
+    .unwrap();
+    let script = script.to_string_lossy().into_owned();
+    let (_, stdout) = run_headless(
+        "debug",
+        &format!(
+            "#1 debug step {}\n#2 debug\n#3 step\n#4 step\n#5 debug\n#6 step\n#7 step\n#8 debug\n",
+            script
+        ),
+    );
+    // Each step replies as the command it ran; the script unloads after its last
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            format!("#1 debugging 3 commands from {}", script),
+            "#2 step 1/3: fill 2".to_string(),
+            "#3 ok".to_string(),
+            "#4 ok".to_string(),
+            "#5 step 3/3: state".to_string(),
+            "#6 edge:0 fill:2 size:1".to_string(),
+            "#7 error: not debugging".to_string(),
+            "#8 error: not debugging".to_string(),
+        ]
+    );
+    let _ = std::fs::remove_file(&script);
+}
+
+#[test]
+fn test_headless_watch_reports_the_command_that_changed_a_region() {
+    let (_, stdout) = run_headless(
+        "watch",
+        "#1 subscribe\n#2 watch 100,100,10,10\n#3 dot 50,50\n#4 dot 105,105\n#5 unwatch\n#6 dot 106,106\n",
+    );
+    // Subscribing starts with the ready event, whose instance varies
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines[0].starts_with("ready "), "{}", lines[0]);
+    assert_eq!(
+        lines[1..],
+        [
+            "#1 ok",
+            "#2 watching 1",
+            "#3 ok",
+            "#4 ok",
+            "watch 1 dot 105,105",
+            "#5 unwatched 1",
+            "#6 ok",
+        ]
+    );
+}
+
+#[test]
+fn test_headless_watch_pauses_a_replay() {
+    let recording = std::env::temp_dir().join("displai_headless_watch_replay.rec");
+    std::fs::write(
+        &recording,
+        "# displai recording\n0 dot 50,50\n0 dot 105,105\n0 dot 200,200\n",
+    )
+    .unwrap();
+    let recording = recording.to_string_lossy().into_owned();
+    let (dir, stdout) = run_headless_with_args(
+        "watch_replay",
+        &["--persist", "canvas.png"],
+        &format!(
+            "#1 subscribe\n#2 watch 100,100,10,10\n#3 replay {} 100\n",
+            recording
+        ),
+    );
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines[1..],
+        [
+            "#1 ok",
+            "#2 watching 1",
+            &format!("#3 replaying 3 commands from {}", recording),
+            "watch 1 dot 105,105",
+        ]
+    );
+    // The replay stops after the watched dot, so the last one is never drawn
+    // (the persisted image starts below the 30-pixel title bar)
+    let img = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    assert_eq!(img.get_pixel(50, 20).0, [0, 0, 0]);
+    assert_eq!(img.get_pixel(105, 75).0, [0, 0, 0]);
+    assert_eq!(img.get_pixel(200, 170).0, [0xFF, 0xFF, 0xFF]);
+    let _ = std::fs::remove_file(&recording);
+}
+
+#[test]
+fn test_headless_compare_responses() {
+    let (_, stdout) = run_headless(
+        "compare",
+        "#1 compare divider 10\n#2 compare checkpoint\n#3 compare divider 10\n#4 compare /nonexistent/ref.png\n#5 compare off\n#6 compare off\n",
+    );
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 6, "{:?}", lines);
+    assert_eq!(lines[0], "#1 error: not comparing");
+    assert_eq!(lines[1], "#2 comparing with checkpoint");
+    assert_eq!(lines[2], "#3 ok");
+    assert!(lines[3].starts_with("#4 error: cannot load /nonexistent/ref.png"));
+    // A failed load leaves the checkpoint in place
+    assert_eq!(lines[4], "#5 ok");
+    assert_eq!(lines[5], "#6 error: not comparing");
+}
+
+#[test]
+fn test_headless_layout_reports_the_moved_divider() {
+    let (_, stdout) = run_headless(
+        "compare_divider",
+        "#1 layout\n#2 compare checkpoint\n#3 compare divider 10\n#4 layout\n#5 compare off\n#6 layout\n",
+    );
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 6, "{:?}", lines);
+    assert!(!lines[0].contains("divider"), "{}", lines[0]);
+    assert!(lines[3].ends_with(" divider:10"), "{}", lines[3]);
+    assert_eq!(lines[5], lines[0].replace("#1", "#6"));
+}
+
+#[test]
+fn test_headless_diff_responses() {
+    let (_, stdout) = run_headless(
+        "diff",
+        "dot 100,100\nsnapshot ref.png\n#1 diff ref.png\ndot 200,200\n#2 diff ref.png\n#3 diff overlay ref.png\n#4 diff overlay off\n#5 diff overlay off\n#6 diff /nonexistent/ref.png\n",
+    );
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 9, "{:?}", lines);
+    assert_eq!(lines[..2], ["ok", "saved ref.png"]);
+    assert_eq!(lines[2], "#1 diff 0 pixels");
+    assert_eq!(lines[3], "ok");
+    // Only the second dot differs
+    assert_eq!(lines[4], "#2 diff 1 pixels in 200,200,1,1");
+    assert_eq!(lines[5], "#3 diff 1 pixels in 200,200,1,1");
+    assert_eq!(lines[6], "#4 ok");
+    assert_eq!(lines[7], "#5 error: no diff overlay");
+    assert!(lines[8].starts_with("#6 error: cannot load /nonexistent/ref.png"));
+}
+
+#[test]
+fn test_headless_toasts_stay_out_of_snapshots() {
+    let (dir, stdout) = run_headless("notify", "notify \"Saved\" 5 error\nsnapshot\n");
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["ok", "saved canvas.png"]
+    );
+    let img = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    assert!(img.pixels().all(|p| p.0 == [0xFF, 0xFF, 0xFF]));
+}
+
+#[test]
+fn test_headless_load_starts_with_image() {
+    let path = std::env::temp_dir().join("displai_headless_load.png");
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([224, 64, 64, 255]))
+        .save(&path)
+        .unwrap();
+    let (_, stdout) = run_headless_with_args(
+        "load",
+        &["--load", path.to_str().unwrap()],
+        "getpixel 3,33\ngetpixel 4,33\n#1 undo\ngetpixel 0,30\n",
+    );
+    // The image can't be undone: it is where history starts
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["#E04040", "#FFFFFF", "#1 error: nothing to undo", "#E04040"]
+    );
+}
+
+#[test]
+fn test_headless_annotate_starts_with_a_red_pen_over_the_image() {
+    let path = std::env::temp_dir().join("displai_headless_annotate.png");
+    image::RgbaImage::from_pixel(600, 300, image::Rgba([0, 0, 255, 255]))
+        .save(&path)
+        .unwrap();
+    let (dir, stdout) = run_headless_with_args(
+        "annotate",
+        &["--annotate", path.to_str().unwrap()],
+        "state
+getpixel 599,329
+snapshot
+",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["edge:2 fill:none size:3", "#0000FF", "saved canvas.png"]
+    );
+    // The canvas is the image's size
+    let img = image::open(dir.join("canvas.png")).unwrap();
+    assert_eq!((img.width(), img.height()), (600, 300));
+}
+
+#[test]
+fn test_headless_persists_the_canvas_across_runs() {
+    let path = std::env::temp_dir().join("displai_headless_persist.png");
+    let _ = std::fs::remove_file(&path);
+    let persist = ["--persist", path.to_str().unwrap()];
+    let (_, stdout) = run_headless_with_args(
+        "persist_first",
+        &persist,
+        "getpixel 10,40\nline 0,40 20,40\n",
+    );
+    assert_eq!(stdout, "#FFFFFF\nok\n");
+    assert!(path.exists());
+
+    // The next run starts where the last one left off
+    let (_, stdout) =
+        run_headless_with_args("persist_second", &persist, "getpixel 10,40\n#1 undo\n");
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["#000000", "#1 error: nothing to undo"]
+    );
+}
+
+#[test]
+fn test_headless_session_restores_canvas_and_tools() {
+    let dir = std::env::temp_dir().join("displai_headless_session");
+    let _ = std::fs::remove_dir_all(&dir);
+    let session = ["--session", dir.to_str().unwrap()];
+    let (_, stdout) = run_headless_with_args(
+        "session_first",
+        &session,
+        "edge 2\nsize 5\nline 0,40 20,40\n",
+    );
+    assert_eq!(stdout, "ok\nok\nok\n");
+    assert!(dir.join("session.png").exists());
+
+    // The next run picks up the drawing and the tools, with nothing to undo
+    let (_, stdout) = run_headless_with_args(
+        "session_second",
+        &session,
+        "getpixel 10,40\nstate\n#1 undo\n",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "#E04040",
+            "edge:2 fill:none size:5",
+            "#1 error: nothing to undo"
+        ]
+    );
+
+    // --no-restore starts afresh, and saves over the session
+    let fresh = ["--session", dir.to_str().unwrap(), "--no-restore"];
+    let (_, stdout) = run_headless_with_args("session_fresh", &fresh, "getpixel 10,40\n");
+    assert_eq!(stdout, "#FFFFFF\n");
+    let (_, stdout) = run_headless_with_args("session_after", &session, "getpixel 10,40\n");
+    assert_eq!(stdout, "#FFFFFF\n");
+}
+
+#[test]
+fn test_headless_project_reopens_with_undo() {
+    let (dir, stdout) = run_headless(
+        "project_first",
+        "edge 2\nline 0,40 20,40\nsize 5\nline 0,60 20,60\nsave drawing.displai\n",
+    );
+    assert_eq!(stdout, "ok\nok\nok\nok\nsaved drawing.displai\n");
+    let path = dir.join("drawing.displai");
+    let load = format!(
+        "clear\nload {}\ngetpixel 10,60\nstate\n#1 undo\ngetpixel 10,60\ngetpixel 10,40\n",
+        path.display()
+    );
+
+    // Another run draws over it, then loads it back and undoes the last line
+    let (_, stdout) = run_headless("project_second", &load);
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "ok".to_string(),
+            format!("loaded {}", path.display()),
+            "#E04040".to_string(),
+            "edge:2 fill:none size:5".to_string(),
+            "#1 ok".to_string(),
+            "#FFFFFF".to_string(),
+            "#E04040".to_string(),
+        ]
+    );
+
+    let (_, stdout) = run_headless(
+        "project_errors",
+        "load missing.displai\ngroup begin g\nsave g.displai\n",
+    );
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines[0].starts_with("error: cannot load missing.displai: "));
+    assert_eq!(
+        lines[1..],
+        ["ok", "error: cannot save a project inside a group"]
+    );
+}
+
+#[test]
+fn test_headless_prints_socket_path_and_removes_it_on_exit() {
+    let socket = std::env::temp_dir().join("displai_headless_socket.sock");
+    let output = Command::new(env!("CARGO_BIN_EXE_displai"))
+        .args(["--headless", "--socket", socket.to_str().unwrap()])
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to start displai");
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("displai: listening on {}", socket.display())));
+    assert!(!socket.exists());
+}
+
+#[test]
+fn test_headless_announces_ready() {
+    let dir = std::env::temp_dir().join("displai_headless_ready");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let (socket, ready_file) = (dir.join("displai.sock"), dir.join("displai.ready"));
+    let beacon = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    beacon
+        .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+        .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_displai"))
+        .args(["--headless", "--socket", socket.to_str().unwrap()])
+        .args(["--ready-file", ready_file.to_str().unwrap()])
+        .args(["--beacon", &beacon.local_addr().unwrap().to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start displai");
+    let mut datagram = [0; 512];
+    let len = beacon.recv(&mut datagram).expect("beacon sent");
+    let line = String::from_utf8(datagram[..len].to_vec()).unwrap();
+
+    // The beacon, the ready file, and a subscriber's first event all agree
+    let (instance, socket_path) = line
+        .strip_prefix("ready ")
+        .and_then(|rest| rest.split_once(' '))
+        .unwrap();
+    assert_eq!(socket_path, socket.to_str().unwrap());
+    assert!(socket.exists());
+    assert_eq!(
+        std::fs::read_to_string(&ready_file).unwrap(),
+        format!("{}\n", line)
+    );
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"subscribe\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("ready {} {}\nok\n", instance, socket_path)
+    );
+
+    // A clean exit leaves no stale announcement
+    assert!(!ready_file.exists());
+    assert!(!socket.exists());
+}
+
+#[test]
+fn test_headless_timing_appends_microseconds() {
+    let (_, stdout) = run_headless("timing", "timing on\ndot 50,50\nstate\ntiming off\nstate\n");
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 5, "{:?}", lines);
+    // Every response while timing is on ends in "<n>us"
+    let untimed = |line: &str| {
+        let (head, time) = line.rsplit_once(' ').unwrap();
+        let micros = time.strip_suffix("us").unwrap();
+        assert!(micros.parse::<u64>().is_ok(), "{}", line);
+        head.to_string()
+    };
+    assert_eq!(untimed(lines[0]), "ok");
+    assert_eq!(untimed(lines[1]), "ok");
+    assert_eq!(untimed(lines[2]), "edge:0 fill:none size:1");
+    // `timing off` takes effect at once, so it and later replies are plain
+    assert_eq!(lines[3..], ["ok", "edge:0 fill:none size:1"]);
+}
+
+#[test]
+fn test_headless_stdin_draws_in_its_own_locked_region() {
+    let (_, stdout) = run_headless(
+        "lockregion",
+        "#1 lockregion 0,30,100,100\n#2 rect 10,40 50,80\n#3 unlockregion 1\n#4 unlockregion 1\n",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["#1 locked 1", "#2 ok", "#3 ok", "#4 error: no lock 1"]
+    );
+}
+
+#[test]
+fn test_headless_refused_command_keeps_its_idempotency_key() {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let dir = std::env::temp_dir().join("displai_headless_refused_key");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let (socket, ready_file) = (dir.join("displai.sock"), dir.join("displai.ready"));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_displai"))
+        .args(["--headless", "--socket", socket.to_str().unwrap()])
+        .args(["--ready-file", ready_file.to_str().unwrap()])
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start displai");
+    while !ready_file.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut client = UnixStream::connect(&socket).unwrap();
+    let mut client_replies = BufReader::new(client.try_clone().unwrap());
+    let read = |reader: &mut dyn BufRead| {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    };
+
+    client.write_all(b"lockregion 300,300,50,50\n").unwrap();
+    assert_eq!(read(&mut client_replies), "locked 1");
+    stdin.write_all(b"#1 !k1 line 310,310 320,320\n").unwrap();
+    assert_eq!(
+        read(&mut stdout),
+        "#1 error: region is locked by another client (lock 1)"
+    );
+
+    // Once the region is free, the retry draws rather than counting as a duplicate
+    client.write_all(b"unlockregion 1\n").unwrap();
+    assert_eq!(read(&mut client_replies), "ok");
+    stdin
+        .write_all(
+            b"#2 !k1 line 310,310 320,320\n#3 getpixel 315,315\n#4 !k1 line 310,310 320,320\n",
+        )
+        .unwrap();
+    drop(stdin);
+    assert_eq!(read(&mut stdout), "#2 ok");
+    assert_eq!(read(&mut stdout), "#3 #000000");
+    assert_eq!(read(&mut stdout), "#4 ok duplicate");
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn test_headless_read_only_stdin_until_auth() {
+    let (_, stdout) = run_headless_with_args(
+        "access",
+        &[
+            "--stdin-access",
+            "read-only",
+            "--token",
+            "s3cret:read-write",
+        ],
+        "#1 dot 50,50\n#2 state\n#3 auth wrong\n#4 auth s3cret\n#5 dot 50,50\n",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "#1 error: dot is not allowed for read-only clients",
+            "#2 edge:0 fill:none size:1",
+            "#3 error: invalid token",
+            "#4 access read-write",
+            "#5 ok",
+        ]
+    );
+}
+
+#[test]
+fn test_headless_state_and_layout_report_locks() {
+    let (_, stdout) = run_headless(
+        "layout",
+        "panes 2x1\n#1 lockregion 400,30,10,10\n#2 state\n#3 layout\n",
+    );
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines[1..3],
+        [
+            "#1 locked 1",
+            "#2 edge:0 fill:none size:1 panes:2x1 free:1 mine:2 taken:- locks:1"
+        ]
+    );
+    assert!(
+        lines[3].starts_with("#3 panes:2x1 margin:0 pane1:0,"),
+        "{}",
+        lines[3]
+    );
+    assert!(
+        lines[3].ends_with(":mine lock1:400,30,10,10:mine"),
+        "{}",
+        lines[3]
+    );
+}
+
+#[test]
+fn test_headless_alias_replies_once_and_undoes_as_one() {
+    let (dir, stdout) = run_headless(
+        "alias",
+        concat!(
+            "#1 alias rl \"rect 100,100 200,200; line 100,100 200,200\"\n",
+            "#2 rl\n",
+            "#3 alias info \"size 3; state\"\n",
+            "#4 info\n",
+            "#5 undo\n",
+            "#6 snapshot\n",
+            "#7 undo\n",
+        ),
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "#1 ok",
+            "#2 ok",
+            "#3 ok",
+            "#4 edge:0 fill:none size:3",
+            "#5 ok",
+            "#6 saved canvas.png",
+            "#7 error: nothing to undo",
+        ]
+    );
+    // One undo took away both the rectangle and the line
+    let img = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    assert!(img.pixels().all(|p| p.0 == [0xFF, 0xFF, 0xFF]));
+}
+
+#[test]
+fn test_headless_canvas_transform_undoes() {
+    let (dir, stdout) = run_headless(
+        "transform",
+        "fill 0\nrect 10,40 50,80\ncanvas flip h\nsnapshot\nundo\nsnapshot after.png\n",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "ok",
+            "ok",
+            "ok",
+            "saved canvas.png",
+            "ok",
+            "saved after.png"
+        ]
+    );
+    // Canvas rows start at CANVAS_TOP, so window y=60 is image row 30
+    let flipped = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    assert_eq!(flipped.get_pixel(30, 30).0, [0xFF, 0xFF, 0xFF]);
+    assert_eq!(flipped.get_pixel(769, 30).0, [0, 0, 0]);
+    let undone = image::open(dir.join("after.png")).unwrap().to_rgb8();
+    assert_eq!(undone.get_pixel(30, 30).0, [0, 0, 0]);
+    assert_eq!(undone.get_pixel(769, 30).0, [0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_headless_canvas_resize_changes_the_snapshot() {
+    let (dir, stdout) = run_headless(
+        "resize",
+        "fill 0\nrect 10,40 50,80\ncanvas resize 1000x700 se\nundo\nsnapshot\ncanvas crop 200,140 799,339\nsnapshot cropped.png\n",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "ok",
+            "ok",
+            "ok",
+            "error: nothing to undo",
+            "saved canvas.png",
+            "ok",
+            "saved cropped.png"
+        ]
+    );
+    // The picture moved 200 across and 100 down
+    let resized = image::open(dir.join("canvas.png")).unwrap().to_rgb8();
+    assert_eq!(resized.dimensions(), (1000, 610));
+    assert_eq!(resized.get_pixel(30, 30).0, [0xFF, 0xFF, 0xFF]);
+    assert_eq!(resized.get_pixel(230, 130).0, [0, 0, 0]);
+    let cropped = image::open(dir.join("cropped.png")).unwrap().to_rgb8();
+    assert_eq!(cropped.dimensions(), (600, 200));
+    assert_eq!(cropped.get_pixel(30, 20).0, [0, 0, 0]);
+    assert_eq!(cropped.get_pixel(60, 20).0, [0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_headless_canvas_resize_refused_inside_a_group() {
+    let (_, stdout) = run_headless(
+        "resize_group",
+        "group begin chart\ncanvas resize 1000x700\ngroup end\ncanvas resize 1000x700\n",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "ok",
+            "error: cannot change the canvas size inside a group",
+            "ok",
+            "ok"
+        ]
+    );
+}
+
+#[test]
+fn test_headless_canvases_keep_their_own_pixels_and_undo() {
+    let (dir, stdout) = run_headless(
+        "tabs",
+        "line 0,40 20,40
+canvas new
+getpixel 10,40
+line 0,50 20,50
+snapshot
+canvas resize 1000x700
+canvas switch 1
+getpixel 10,40
+getpixel 10,50
+undo
+undo
+canvas close 2
+canvas close 1
+snapshot
+",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "ok",
+            "canvas 2",
+            "#FFFFFF",
+            "ok",
+            "saved canvas-2.png",
+            "error: cannot change the canvas size while several canvases are open",
+            "canvas 1",
+            "#000000",
+            "#FFFFFF",
+            "ok",
+            "error: nothing to undo",
+            "canvas 1",
+            "error: the last canvas can't be closed",
+            "saved canvas.png",
+        ]
+    );
+    // The second canvas's snapshot has only its own line
+    let img = image::open(dir.join("canvas-2.png")).unwrap().to_rgb8();
+    assert_eq!(img.get_pixel(10, 20).0, [0, 0, 0]);
+    assert_eq!(img.get_pixel(10, 10).0, [0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn test_headless_runs_a_template_script_and_exits() {
+    let script = std::env::temp_dir().join("displai_headless_template.txt");
+    std::fs::write(
+        &script,
+        "fill ${fill}\nrect ${x},100 200,200\nstate\nsnapshot ${out}\n",
+    )
+    .unwrap();
+    let (dir, stdout) = run_headless_with_args(
+        "template",
+        &[
+            "--no-stdin",
+            "--script",
+            script.to_str().unwrap(),
+            "--define",
+            "fill=2",
+            "--define",
+            "x=100",
+            "--define",
+            "out=sales.png",
+        ],
+        "",
+    );
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec!["ok", "ok", "edge:0 fill:2 size:1", "saved sales.png"]
+    );
+    // Canvas rows start at CANVAS_TOP, so window y=150 is image row 120
+    let img = image::open(dir.join("sales.png")).unwrap().to_rgb8();
+    assert_eq!(img.get_pixel(150, 120).0, [0xE0, 0x40, 0x40]);
+    let _ = std::fs::remove_file(&script);
+}
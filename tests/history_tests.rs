@@ -0,0 +1,406 @@
+use displai::*;
+
+fn new_buffer() -> Vec<u32> {
+    vec![WHITE; WIDTH * HEIGHT]
+}
+
+// ===================
+// Group Command Parsing
+// ===================
+
+#[test]
+fn test_parse_group_begin() {
+    assert_eq!(
+        parse_command("group begin \"bar chart\""),
+        Some(Command::GroupBegin("bar chart".to_string()))
+    );
+    assert_eq!(
+        parse_command("group begin chart"),
+        Some(Command::GroupBegin("chart".to_string()))
+    );
+    assert_eq!(
+        parse_command("group begin"),
+        Some(Command::GroupBegin(String::new()))
+    );
+}
+
+#[test]
+fn test_parse_group_end() {
+    assert_eq!(parse_command("group end"), Some(Command::GroupEnd));
+}
+
+#[test]
+fn test_parse_group_invalid() {
+    assert_eq!(parse_command("group"), None);
+    assert_eq!(parse_command("group start"), None);
+    assert_eq!(parse_command("group beginx"), None);
+    assert_eq!(parse_command("group begin \"unterminated"), None);
+    assert_eq!(parse_command("group begin \"a\"b\""), None);
+}
+
+#[test]
+fn test_group_display_round_trip() {
+    let cmd = Command::GroupBegin("sales by month".to_string());
+    assert_eq!(cmd.to_string(), "group begin \"sales by month\"");
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(Command::GroupEnd.to_string(), "group end");
+}
+
+// ===================
+// History Tests
+// ===================
+
+#[test]
+fn test_history_records_each_operation() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    set_pixel(&mut buffer, 100, 100, BLACK);
+    history.record("line", &buffer);
+    set_pixel(&mut buffer, 200, 200, BLACK);
+    history.record("circle", &buffer);
+    let labels: Vec<&str> = history.entries().iter().map(|e| e.label.as_str()).collect();
+    assert_eq!(labels, vec!["line", "circle"]);
+}
+
+#[test]
+fn test_history_skips_operations_that_changed_nothing() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    set_pixel(&mut buffer, 100, 100, BLACK);
+    history.record("line", &buffer);
+    history.undo(&mut buffer).unwrap();
+    history.record("image", &buffer);
+    assert!(history.is_empty());
+    assert!(history.can_redo());
+    history.redo(&mut buffer).unwrap();
+    assert_eq!(buffer[100 * WIDTH + 100], BLACK);
+}
+
+#[test]
+fn test_history_group_is_one_entry() {
+    let buffer = new_buffer();
+    let mut history = History::new();
+    history.begin_group("chart");
+    assert_eq!(history.open_group_label(), Some("chart"));
+    for _ in 0..5 {
+        history.record("rect", &buffer);
+    }
+    assert!(history.is_empty());
+    history.end_group(&buffer).unwrap();
+
+    assert_eq!(
+        history.entries(),
+        vec![&HistoryEntry {
+            label: "chart".to_string(),
+            operations: 5,
+        }]
+    );
+    assert_eq!(history.open_group_label(), None);
+}
+
+#[test]
+fn test_history_nested_groups_fold_into_outer() {
+    let buffer = new_buffer();
+    let mut history = History::new();
+    history.begin_group("outer");
+    history.record("dot", &buffer);
+    history.begin_group("inner");
+    history.record("dot", &buffer);
+    history.end_group(&buffer).unwrap();
+    assert_eq!(history.len(), 0);
+    history.end_group(&buffer).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history.entries()[0].label, "outer");
+    assert_eq!(history.entries()[0].operations, 2);
+}
+
+#[test]
+fn test_history_end_without_begin() {
+    let buffer = new_buffer();
+    let mut history = History::new();
+    assert!(history.end_group(&buffer).is_err());
+}
+
+#[test]
+fn test_history_empty_group_leaves_no_entry() {
+    let buffer = new_buffer();
+    let mut history = History::new();
+    history.begin_group("nothing");
+    history.end_group(&buffer).unwrap();
+    assert!(history.is_empty());
+}
+
+#[test]
+fn test_history_is_bounded() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    for i in 0..MAX_HISTORY_ENTRIES + 5 {
+        let color = if i % 2 == 0 { BLACK } else { WHITE };
+        set_pixel(&mut buffer, 100, 100, color);
+        history.record(&i.to_string(), &buffer);
+    }
+    assert_eq!(history.len(), MAX_HISTORY_ENTRIES);
+    assert_eq!(history.entries()[0].label, "5");
+}
+
+#[test]
+fn test_tool_mode_names_match_commands() {
+    assert_eq!(ToolMode::Rectangle.name(), "rect");
+    assert_eq!(ToolMode::Brush.name(), "brush");
+}
+
+// ===================
+// Undo/Redo Tests
+// ===================
+
+#[test]
+fn test_parse_undo_redo() {
+    assert_eq!(parse_command("undo"), Some(Command::Undo));
+    assert_eq!(parse_command("redo"), Some(Command::Redo));
+    assert_eq!(Command::Undo.to_string(), "undo");
+    assert!(!Command::Undo.is_mutating());
+}
+
+#[test]
+fn test_undo_restores_pixels() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    draw_line(&mut buffer, 10, 100, 200, 100, BLACK);
+    history.record("line", &buffer);
+    let after_line = buffer.clone();
+
+    draw_circle(&mut buffer, 300, 300, 20, COLOR_PALETTE[2]);
+    history.record("circle", &buffer);
+
+    assert_eq!(history.undo(&mut buffer), Ok("circle".to_string()));
+    assert_eq!(buffer, after_line);
+    assert_eq!(history.undo(&mut buffer), Ok("line".to_string()));
+    assert_eq!(buffer, new_buffer());
+    assert!(history.undo(&mut buffer).is_err());
+}
+
+#[test]
+fn test_redo_reapplies_pixels() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    draw_line(&mut buffer, 10, 100, 200, 150, BLACK);
+    history.record("line", &buffer);
+    let drawn = buffer.clone();
+
+    history.undo(&mut buffer).unwrap();
+    assert!(history.can_redo());
+    assert_eq!(history.redo(&mut buffer), Ok("line".to_string()));
+    assert_eq!(buffer, drawn);
+    assert!(history.redo(&mut buffer).is_err());
+}
+
+#[test]
+fn test_new_record_clears_redo() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    draw_line(&mut buffer, 10, 100, 200, 100, BLACK);
+    history.record("line", &buffer);
+    history.undo(&mut buffer).unwrap();
+
+    set_pixel(&mut buffer, 50, 50, BLACK);
+    history.record("dot", &buffer);
+    assert!(!history.can_redo());
+}
+
+#[test]
+fn test_undo_group_reverts_whole_group() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    history.begin_group("chart");
+    for i in 0..4 {
+        draw_line(&mut buffer, 100 + i * 20, 400, 100 + i * 20, 300, BLACK);
+        history.record("line", &buffer);
+    }
+    // Undo is refused while the group is still open
+    assert!(history.undo(&mut buffer).is_err());
+    history.end_group(&buffer).unwrap();
+
+    assert_eq!(history.undo(&mut buffer), Ok("chart".to_string()));
+    assert_eq!(buffer, new_buffer());
+}
+
+#[test]
+fn test_undo_ignores_toolbar_pixels() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    draw_title_bar(&mut buffer);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
+    let chrome = buffer.clone();
+
+    set_pixel(&mut buffer, 400, 300, BLACK);
+    history.record("dot", &buffer);
+    history.undo(&mut buffer).unwrap();
+    assert_eq!(buffer, chrome);
+}
+
+#[test]
+fn test_history_stores_only_changed_rectangle() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    set_pixel(&mut buffer, 400, 300, BLACK);
+    history.record("dot", &buffer);
+    // One pixel before and after
+    assert_eq!(history.pixel_bytes(), 2 * 4);
+}
+
+#[test]
+fn test_history_bytes_round_trip() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    set_pixel(&mut buffer, 400, 300, BLACK);
+    history.record("dot", &buffer);
+    history.begin_group("pair");
+    set_pixel(&mut buffer, 100, 100, BLACK);
+    set_pixel(&mut buffer, 120, 110, BLACK);
+    history.record("dot", &buffer);
+    history.end_group(&buffer).unwrap();
+    set_pixel(&mut buffer, 200, 200, BLACK);
+    history.record("last", &buffer);
+    history.undo(&mut buffer).unwrap();
+
+    let mut restored = History::from_bytes(&history.to_bytes(), &buffer).unwrap();
+    assert_eq!(restored.entries(), history.entries());
+    assert_eq!(restored.pixel_bytes(), history.pixel_bytes());
+    assert!(restored.can_redo());
+
+    // Undo and redo carry on where they left off
+    assert_eq!(restored.redo(&mut buffer), Ok("last".to_string()));
+    assert_eq!(buffer[200 * WIDTH + 200], BLACK);
+    assert_eq!(restored.undo(&mut buffer), Ok("last".to_string()));
+    assert_eq!(restored.undo(&mut buffer), Ok("pair".to_string()));
+    assert_eq!(buffer[100 * WIDTH + 100], WHITE);
+    assert_eq!(buffer[110 * WIDTH + 120], WHITE);
+    assert_eq!(restored.undo(&mut buffer), Ok("dot".to_string()));
+    assert_eq!(buffer, new_buffer());
+}
+
+#[test]
+fn test_damaged_history_bytes_fail() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    set_pixel(&mut buffer, 400, 300, BLACK);
+    history.record("dot", &buffer);
+    let bytes = history.to_bytes();
+    assert_eq!(
+        History::from_bytes(&bytes[..bytes.len() - 1], &buffer).unwrap_err(),
+        "history is cut short"
+    );
+    let mut extra = bytes.clone();
+    extra.push(0);
+    assert_eq!(
+        History::from_bytes(&extra, &buffer).unwrap_err(),
+        "history is damaged"
+    );
+    // A rectangle outside the canvas is refused, not drawn
+    let mut moved = bytes;
+    moved[8 + 3 + 4..8 + 3 + 8].copy_from_slice(&(WIDTH as u32).to_le_bytes());
+    assert_eq!(
+        History::from_bytes(&moved, &buffer).unwrap_err(),
+        "history doesn't fit the canvas"
+    );
+}
+
+// ===================
+// History Panel Tests
+// ===================
+
+/// A history of one dot per label, each at its own spot
+fn dotted(labels: &[&str]) -> (Vec<u32>, History) {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    for (i, label) in labels.iter().enumerate() {
+        set_pixel(&mut buffer, 100 + i, 100, BLACK);
+        history.record(label, &buffer);
+    }
+    (buffer, history)
+}
+
+#[test]
+fn test_undo_to_keeps_the_first_entries() {
+    let (mut buffer, mut history) = dotted(&["a", "b", "c"]);
+    assert_eq!(history.undo_to(1, &mut buffer), Ok(2));
+    assert_eq!(history.len(), 1);
+    assert_eq!(buffer[100 * WIDTH + 100], BLACK);
+    assert_eq!(buffer[100 * WIDTH + 101], WHITE);
+    // The undone entries can be redone
+    assert_eq!(history.redo(&mut buffer), Ok("b".to_string()));
+    assert_eq!(history.undo_to(5, &mut buffer), Ok(0));
+
+    history.begin_group("chart");
+    assert!(history.undo_to(0, &mut buffer).is_err());
+}
+
+#[test]
+fn test_history_panel_rows() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    set_pixel(&mut buffer, 50, 50, BLACK);
+    history.record("clear", &buffer);
+    history.begin_group("chart");
+    for i in 0..3 {
+        set_pixel(&mut buffer, 100 + i, 100, BLACK);
+        history.record("dot", &buffer);
+    }
+    history.end_group(&buffer).unwrap();
+    assert_eq!(
+        history_panel_rows(&history.entries()),
+        vec![
+            (0, "start".to_string()),
+            (1, "clear".to_string()),
+            (2, "chart (3)".to_string()),
+        ]
+    );
+
+    // Only the latest entries are listed, without the start row
+    let labels: Vec<String> = (0..HISTORY_PANEL_ROWS + 2).map(|i| i.to_string()).collect();
+    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+    let (_, history) = dotted(&labels);
+    let rows = history_panel_rows(&history.entries());
+    assert_eq!(rows.len(), HISTORY_PANEL_ROWS);
+    assert_eq!(rows[0], (3, "2".to_string()));
+    assert_eq!(rows[HISTORY_PANEL_ROWS - 1].0, HISTORY_PANEL_ROWS + 2);
+}
+
+#[test]
+fn test_history_panel_clicks_find_their_entry() {
+    let (_, history) = dotted(&["a", "b"]);
+    let entries = history.entries();
+    let (x, y, width, height) = history_panel_bounds(3);
+    assert_eq!(history_panel_entry_at(x + 10, y + 6, &entries), Some(0));
+    assert_eq!(
+        history_panel_entry_at(x + 10, y + height - 6, &entries),
+        Some(2)
+    );
+    assert_eq!(history_panel_entry_at(x + width + 1, y + 6, &entries), None);
+    assert!(is_in_history_panel(x + 1, y + 1, &entries));
+    assert!(!is_in_history_panel(x + 10, y + height + 1, &entries));
+}
+
+#[test]
+fn test_history_panel_draws_in_its_bounds_and_marks_the_latest_entry() {
+    let (_, history) = dotted(&["a", "b"]);
+    let mut frame = new_buffer();
+    draw_history_panel(&mut frame, &history.entries());
+    let (x, y, width, height) = history_panel_bounds(3);
+    for (i, &pixel) in frame.iter().enumerate() {
+        let (px, py) = (i % WIDTH, i / WIDTH);
+        if pixel != WHITE {
+            assert!((x..x + width).contains(&px) && (y..y + height).contains(&py));
+        }
+    }
+    assert_eq!(frame[y * WIDTH + x], DARK_GRAY);
+    // The latest row (the last) is highlighted, the others aren't
+    assert_eq!(frame[(y + height - 6) * WIDTH + x + width - 3], GRAY);
+    assert_eq!(frame[(y + 6) * WIDTH + x + width - 3], STATS_BACKGROUND);
+}
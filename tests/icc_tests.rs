@@ -0,0 +1,243 @@
+mod common;
+
+use common::{run, scratch};
+use displai::*;
+use image::codecs::png::PngDecoder;
+use image::ImageDecoder;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// The ICC profile embedded in the PNG at `path`, if any
+fn png_profile(path: &PathBuf) -> Option<Vec<u8>> {
+    let bytes = std::fs::read(path).unwrap();
+    PngDecoder::new(Cursor::new(bytes))
+        .unwrap()
+        .icc_profile()
+        .unwrap()
+}
+
+fn be_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// The XYZ value of tag `signature` in `profile`
+fn xyz_tag(profile: &[u8], signature: &[u8; 4]) -> [f64; 3] {
+    let count = be_u32(profile, 128) as usize;
+    let entry = (0..count)
+        .map(|i| 132 + 12 * i)
+        .find(|&at| &profile[at..at + 4] == signature)
+        .expect("tag is present");
+    let offset = be_u32(profile, entry + 4) as usize;
+    assert_eq!(&profile[offset..offset + 4], b"XYZ ");
+    [0, 1, 2].map(|i| be_u32(profile, offset + 8 + 4 * i) as i32 as f64 / 65536.0)
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_color_profile() {
+    for profile in ColorProfile::ALL {
+        let line = format!("color-profile {}", profile.name());
+        let cmd = parse_command(&line).unwrap();
+        assert_eq!(cmd, Command::ColorProfile(Some(profile)));
+        assert_eq!(cmd.to_string(), line);
+        assert_eq!(ColorProfile::parse(profile.name()), Some(profile));
+    }
+    let cmd = parse_command("color-profile off").unwrap();
+    assert_eq!(cmd, Command::ColorProfile(None));
+    assert_eq!(cmd.to_string(), "color-profile off");
+    assert_eq!(cmd.name(), "color-profile");
+    assert!(validate(&cmd).is_ok());
+    assert!(!cmd.is_mutating());
+    assert!(!cmd.runs_in_pane());
+
+    // Invalid formats
+    assert_eq!(parse_command("color-profile"), None);
+    assert_eq!(parse_command("color-profile prophoto"), None);
+    assert_eq!(parse_command("color-profile srgb on"), None);
+}
+
+#[test]
+fn test_color_profile_is_a_setting() {
+    let mut state = AppState::new();
+    assert_eq!(state.color_profile, None);
+    assert_eq!(run(&mut state, "color-profile adobe-rgb"), None);
+    assert_eq!(state.color_profile, Some(ColorProfile::AdobeRgb));
+    assert_eq!(run(&mut state, "color-profile off"), None);
+    assert_eq!(state.color_profile, None);
+    // Outside a command, nothing is tagged
+    assert_eq!(color_profile(), None);
+}
+
+// ===================
+// Profile Tests
+// ===================
+
+#[test]
+fn test_icc_profile_header() {
+    for profile in ColorProfile::ALL {
+        let icc = profile.icc_profile();
+        assert_eq!(be_u32(&icc, 0) as usize, icc.len(), "{:?}", profile);
+        assert_eq!(icc.len() % 4, 0);
+        assert_eq!(&icc[8..10], &[2, 0x40]);
+        assert_eq!(&icc[12..24], b"mntrRGB XYZ ");
+        assert_eq!(&icc[36..40], b"acsp");
+        // The same profile is built the same way every time
+        assert_eq!(icc, profile.icc_profile());
+        // Every tag lies inside the profile
+        let count = be_u32(&icc, 128) as usize;
+        assert_eq!(count, 10);
+        for i in 0..count {
+            let at = 132 + 12 * i;
+            let (offset, size) = (be_u32(&icc, at + 4), be_u32(&icc, at + 8));
+            assert!((offset + size) as usize <= icc.len());
+        }
+    }
+}
+
+#[test]
+fn test_icc_colorants_add_up_to_white() {
+    // Full red, green, and blue together are the D50 white point
+    for profile in ColorProfile::ALL {
+        let icc = profile.icc_profile();
+        let [r, g, b] = [b"rXYZ", b"gXYZ", b"bXYZ"].map(|tag| xyz_tag(&icc, tag));
+        let white = xyz_tag(&icc, b"wtpt");
+        for (i, expected) in [0.9642, 1.0, 0.8249].into_iter().enumerate() {
+            assert!((white[i] - expected).abs() < 0.001, "{:?}", profile);
+            assert!(
+                (r[i] + g[i] + b[i] - expected).abs() < 0.002,
+                "{:?}",
+                profile
+            );
+        }
+    }
+}
+
+// ===================
+// Conversion Tests
+// ===================
+
+#[test]
+fn test_from_srgb() {
+    for profile in ColorProfile::ALL {
+        assert_eq!(profile.from_srgb([255, 255, 255]), [255, 255, 255]);
+        assert_eq!(profile.from_srgb([0, 0, 0]), [0, 0, 0]);
+    }
+    // Tagging as sRGB leaves colors alone
+    assert_eq!(ColorProfile::Srgb.from_srgb([12, 200, 99]), [12, 200, 99]);
+    // sRGB red sits inside the wider spaces' red
+    assert_eq!(
+        ColorProfile::DisplayP3.from_srgb([255, 0, 0]),
+        [234, 51, 35]
+    );
+    assert_eq!(ColorProfile::AdobeRgb.from_srgb([255, 0, 0]), [219, 0, 0]);
+    assert_eq!(
+        ColorProfile::AdobeRgb.from_srgb([0, 255, 0]),
+        [144, 255, 60]
+    );
+}
+
+// ===================
+// Export Tests
+// ===================
+
+#[test]
+fn test_snapshot_embeds_the_profile() {
+    let dir = scratch("snapshot");
+    let path = dir.join("p3.png");
+    let mut state = AppState::new();
+    run(&mut state, "fill #FF0000");
+    run(&mut state, "rect 100,100 200,200");
+    run(&mut state, "color-profile display-p3");
+    let line = format!("snapshot {}", path.display());
+    assert_eq!(
+        run(&mut state, &line),
+        Some(format!("saved {}", path.display()))
+    );
+    assert_eq!(
+        png_profile(&path),
+        Some(ColorProfile::DisplayP3.icc_profile())
+    );
+    let img = image::open(&path).unwrap().to_rgb8();
+    assert_eq!(img.get_pixel(150, 120).0, [234, 51, 35]);
+    assert_eq!(img.get_pixel(10, 10).0, [255, 255, 255]);
+
+    // Off again, snapshots are untagged sRGB
+    run(&mut state, "color-profile off");
+    assert_eq!(
+        run(&mut state, &line),
+        Some(format!("saved {}", path.display()))
+    );
+    assert_eq!(png_profile(&path), None);
+    let img = image::open(&path).unwrap().to_rgb8();
+    assert_eq!(img.get_pixel(150, 120).0, [255, 0, 0]);
+}
+
+#[test]
+fn test_jpeg_snapshot_embeds_the_profile() {
+    let dir = scratch("jpeg");
+    let path = dir.join("tagged.jpg");
+    let mut state = AppState::new();
+    run(&mut state, "color-profile srgb");
+    run(&mut state, &format!("snapshot {}", path.display()));
+    let bytes = std::fs::read(&path).unwrap();
+    // ICC profiles ride in an APP2 segment named ICC_PROFILE
+    assert!(bytes.windows(12).any(|w| w == b"ICC_PROFILE\0"));
+}
+
+#[test]
+fn test_bmp_cannot_carry_a_profile() {
+    let dir = scratch("bmp");
+    let path = dir.join("tagged.bmp");
+    let mut state = AppState::new();
+    run(&mut state, "color-profile srgb");
+    let response = run(&mut state, &format!("snapshot {}", path.display())).unwrap();
+    assert!(
+        response.contains("can't carry a color profile"),
+        "{}",
+        response
+    );
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_snapshot_worker_keeps_the_profile_of_each_request() {
+    let dir = scratch("worker");
+    let (tagged, untagged) = (dir.join("tagged.png"), dir.join("untagged.png"));
+    let buffer = vec![WHITE; WIDTH * HEIGHT];
+    let worker: SnapshotWorker<()> = SnapshotWorker::spawn();
+    with_color_profile(Some(ColorProfile::AdobeRgb), || {
+        worker.request(&buffer, &tagged.display().to_string(), ())
+    });
+    worker.request(&buffer, &untagged.display().to_string(), ());
+    for _ in 0..2 {
+        assert_eq!(worker.wait().unwrap().result, Ok(()));
+    }
+    assert_eq!(
+        png_profile(&tagged),
+        Some(ColorProfile::AdobeRgb.icc_profile())
+    );
+    assert_eq!(png_profile(&untagged), None);
+}
+
+#[test]
+fn test_export_tiles_embed_the_profile() {
+    let dir = scratch("tiles");
+    let mut state = AppState::new();
+    run(&mut state, "color-profile srgb");
+    let response = run(&mut state, &format!("export tiles {} 2x1", dir.display()));
+    assert_eq!(
+        response,
+        Some(format!("saved 2 tiles to {}", dir.display()))
+    );
+    for name in [tile_name(1, 1), tile_name(1, 2)] {
+        assert_eq!(
+            png_profile(&dir.join(&name)),
+            Some(ColorProfile::Srgb.icc_profile()),
+            "{}",
+            name
+        );
+    }
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::run;
+use displai::*;
+use image::{Rgba, RgbaImage};
+
+/// A 2x2 image (red, green / blue, half-transparent black) saved to `path`
+fn write_test_image(path: &str) {
+    let mut img = RgbaImage::new(2, 2);
+    img.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+    img.put_pixel(1, 0, Rgba([0, 255, 0, 255]));
+    img.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
+    img.put_pixel(1, 1, Rgba([0, 0, 0, 128]));
+    img.save(path).unwrap();
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_image_load() {
+    let cmd = |path: &str, scale| Command::Image {
+        path: path.to_string(),
+        x: 10,
+        y: 40,
+        scale,
+    };
+    assert_eq!(
+        parse_command("image load /tmp/cat.png 10,40"),
+        Some(cmd("/tmp/cat.png", 1.0))
+    );
+    assert_eq!(
+        parse_command("image load /tmp/my pics/cat.png  10,40 2.5"),
+        Some(cmd("/tmp/my pics/cat.png", 2.5))
+    );
+    for c in [cmd("/tmp/cat.png", 1.0), cmd("/tmp/a b.png", 0.5)] {
+        assert_eq!(parse_command(&c.to_string()), Some(c));
+    }
+    assert_eq!(parse_command("image load /tmp/cat.png"), None);
+    assert_eq!(parse_command("image load 10,40"), None);
+    assert_eq!(parse_command("image load /tmp/cat.png 10,40 big"), None);
+    assert_eq!(parse_command("image load /tmp/cat.png 10,40 inf"), None);
+    assert_eq!(parse_command("image /tmp/cat.png 10,40"), None);
+    assert_eq!(parse_command("image loaded /tmp/cat.png 10,40"), None);
+}
+
+#[test]
+fn test_validate_image_load() {
+    let cmd = |x, y, scale| Command::Image {
+        path: "/tmp/cat.png".to_string(),
+        x,
+        y,
+        scale,
+    };
+    assert!(validate(&cmd(10, 40, 1.0)).is_ok());
+    assert!(validate(&cmd(10, 40, MAX_IMAGE_SCALE)).is_ok());
+    assert!(validate(&cmd(10, 40, 0.0)).is_err());
+    assert!(validate(&cmd(10, 40, MAX_IMAGE_SCALE + 0.5)).is_err());
+    assert!(validate(&cmd(10, 5, 1.0)).is_err());
+    assert!(cmd(10, 40, 1.0).is_mutating());
+}
+
+// ===================
+// Pasting Tests
+// ===================
+
+#[test]
+fn test_image_load_pastes_pixels() {
+    let path = "/tmp/test_import_paste.png";
+    write_test_image(path);
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, &format!("image load {} 100,100", path)),
+        None
+    );
+    std::fs::remove_file(path).ok();
+
+    let at = |x: usize, y: usize| state.canvas[y * WIDTH + x];
+    assert_eq!(at(100, 100), 0xFF0000);
+    assert_eq!(at(101, 100), 0x00FF00);
+    assert_eq!(at(100, 101), 0x0000FF);
+    // Half-transparent black over white blends to gray
+    assert_eq!(at(101, 101), 0x7F7F7F);
+    assert_eq!(at(102, 100), WHITE);
+}
+
+#[test]
+fn test_image_load_scales_by_nearest_neighbour() {
+    let path = "/tmp/test_import_scale.png";
+    write_test_image(path);
+    let mut state = AppState::new();
+    run(&mut state, &format!("image load {} 100,100 3", path));
+    std::fs::remove_file(path).ok();
+
+    let at = |x: usize, y: usize| state.canvas[y * WIDTH + x];
+    assert_eq!(at(102, 102), 0xFF0000);
+    assert_eq!(at(103, 100), 0x00FF00);
+    assert_eq!(at(105, 102), 0x00FF00);
+    assert_eq!(at(100, 105), 0x0000FF);
+    assert_eq!(at(106, 100), WHITE);
+}
+
+#[test]
+fn test_fully_transparent_pixels_leave_canvas() {
+    let img = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 0]));
+    let mut buffer = vec![BLUE; WIDTH * HEIGHT];
+    paste_image(&mut buffer, &img, 100, 100, 1.0);
+    assert!(buffer.iter().all(|&p| p == BLUE));
+}
+
+#[test]
+fn test_paste_clips_to_canvas() {
+    let img = RgbaImage::from_pixel(50, 50, Rgba([255, 0, 0, 255]));
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    paste_image(&mut buffer, &img, WIDTH - 10, CANVAS_BOTTOM - 10, 1.0);
+    assert_eq!(buffer[(CANVAS_BOTTOM - 1) * WIDTH + WIDTH - 1], 0xFF0000);
+    // The toolbar rows below the canvas are untouched
+    assert!(buffer[CANVAS_BOTTOM * WIDTH..].iter().all(|&p| p == WHITE));
+
+    // Shrinking never leaves an image smaller than one pixel
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    paste_image(&mut buffer, &img, 100, 100, 0.001);
+    assert_eq!(buffer[100 * WIDTH + 100], 0xFF0000);
+    assert_eq!(buffer[100 * WIDTH + 101], WHITE);
+}
+
+#[test]
+fn test_image_load_errors() {
+    let mut state = AppState::new();
+    let reply = run(&mut state, "image load /tmp/no/such/image.png 100,100").unwrap();
+    assert!(
+        reply.starts_with("error: cannot load /tmp/no/such/image.png"),
+        "{}",
+        reply
+    );
+
+    let path = "/tmp/test_import_not_an_image.png";
+    std::fs::write(path, b"not an image").unwrap();
+    let reply = run(&mut state, &format!("image load {} 100,100", path)).unwrap();
+    std::fs::remove_file(path).ok();
+    assert!(reply.starts_with("error: cannot load"), "{}", reply);
+    assert!(state.canvas.iter().all(|&p| p == WHITE));
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use common::scratch;
+use displai::*;
+
+/// Write `text` to `name` in `dir`, returning its path
+fn write_script(dir: &std::path::Path, name: &str, text: &str) -> String {
+    let path = dir.join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    std::fs::write(&path, text).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_include_path() {
+    assert_eq!(include_path("include setup.txt"), Some("setup.txt"));
+    assert_eq!(
+        include_path("include   shared/my setup.txt "),
+        Some("shared/my setup.txt")
+    );
+    assert_eq!(
+        include_path("include /etc/displai/base.txt"),
+        Some("/etc/displai/base.txt")
+    );
+    assert_eq!(include_path("include"), None);
+    assert_eq!(include_path("include   "), None);
+    assert_eq!(include_path("includes setup.txt"), None);
+    assert_eq!(include_path("rect 10,40 20,50"), None);
+}
+
+// ===================
+// Reading Tests
+// ===================
+
+#[test]
+fn test_include_puts_lines_in_place() {
+    let dir = scratch("in_place");
+    write_script(&dir, "palette.txt", "# shared colors\nfill 2\nedge 0\n");
+    let main = write_script(
+        &dir,
+        "main.txt",
+        "clear\ninclude palette.txt\nrect 100,100 200,200\n",
+    );
+    assert_eq!(
+        read_script(&main).unwrap(),
+        vec!["clear", "fill 2", "edge 0", "rect 100,100 200,200"]
+    );
+}
+
+#[test]
+fn test_include_paths_are_relative_to_the_including_script() {
+    let dir = scratch("relative");
+    write_script(&dir, "lib/colors.txt", "fill 3\n");
+    write_script(&dir, "lib/base.txt", "include colors.txt\nclear\n");
+    write_script(&dir, "my shapes.txt", "circle 300,300 20\n");
+    let absolute = write_script(&dir, "abs.txt", "dot 1,40\n");
+    let main = write_script(
+        &dir,
+        "charts/main.txt",
+        &format!(
+            "include ../lib/base.txt\ninclude ../my shapes.txt\ninclude {}\n",
+            absolute
+        ),
+    );
+    assert_eq!(
+        read_script(&main).unwrap(),
+        vec!["fill 3", "clear", "circle 300,300 20", "dot 1,40"]
+    );
+}
+
+#[test]
+fn test_a_script_may_be_included_twice() {
+    let dir = scratch("twice");
+    write_script(&dir, "dot.txt", "dot 10,40\n");
+    write_script(&dir, "pair.txt", "include dot.txt\ninclude dot.txt\n");
+    let main = write_script(&dir, "main.txt", "include pair.txt\ninclude dot.txt\n");
+    assert_eq!(read_script(&main).unwrap(), vec!["dot 10,40"; 3]);
+}
+
+#[test]
+fn test_include_cycles_fail() {
+    let dir = scratch("cycle");
+    let a = write_script(&dir, "a.txt", "clear\ninclude b.txt\n");
+    write_script(&dir, "b.txt", "include ./a.txt\n");
+    let b = dir.join("b.txt").display().to_string();
+    let back = dir.join("./a.txt").display().to_string();
+    assert_eq!(
+        read_script(&a),
+        Err(format!("include cycle: {} -> {} -> {}", a, b, back))
+    );
+
+    let own = write_script(&dir, "own.txt", "include own.txt\n");
+    assert!(read_script(&own)
+        .unwrap_err()
+        .starts_with("include cycle: "));
+}
+
+#[test]
+fn test_includes_too_deep_fail() {
+    let dir = scratch("deep");
+    for i in 0..MAX_INCLUDE_DEPTH {
+        write_script(
+            &dir,
+            &format!("{}.txt", i),
+            &format!("include {}.txt\n", i + 1),
+        );
+    }
+    let last = write_script(&dir, &format!("{}.txt", MAX_INCLUDE_DEPTH), "clear\n");
+    let first = dir.join("0.txt").display().to_string();
+    assert_eq!(
+        read_script(&first),
+        Err(format!("{}: includes go more than 16 deep", last))
+    );
+
+    // One shallower is fine
+    let second = dir.join("1.txt").display().to_string();
+    assert_eq!(read_script(&second).unwrap(), vec!["clear"]);
+}
+
+#[test]
+fn test_missing_include_fails() {
+    let dir = scratch("missing");
+    let main = write_script(&dir, "main.txt", "include nowhere.txt\n");
+    let err = read_script(&main).unwrap_err();
+    let missing = dir.join("nowhere.txt").display().to_string();
+    assert!(
+        err.starts_with(&format!("cannot read {}", missing)),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_stepper_loads_includes() {
+    let dir = scratch("stepper");
+    write_script(&dir, "setup.txt", "fill 2\n");
+    let main = write_script(
+        &dir,
+        "main.txt",
+        "include setup.txt\nrect 100,100 200,200\n",
+    );
+    let stepper = Stepper::load(&main).unwrap();
+    assert_eq!(stepper.len(), 2);
+
+    // A script of nothing but an empty include has no commands
+    write_script(&dir, "empty.txt", "# nothing yet\n");
+    let hollow = write_script(&dir, "hollow.txt", "include empty.txt\n");
+    assert!(Stepper::load(&hollow).is_err());
+}
+
+// ===================
+// Template Script Tests
+// ===================
+
+#[test]
+fn test_included_scripts_are_templates_too() {
+    let dir = scratch("template");
+    write_script(&dir, "themes/dark.txt", "fill ${accent}\n");
+    let main = write_script(
+        &dir,
+        "main.txt",
+        "include themes/${theme}.txt\nobj text 10,40 \"${title}\"\n",
+    );
+    let defines = vec![
+        ("theme".to_string(), "dark".to_string()),
+        ("accent".to_string(), "5".to_string()),
+        ("title".to_string(), "Q3".to_string()),
+    ];
+    assert_eq!(
+        read_template_script(&main, &defines).unwrap(),
+        vec!["fill 5", "obj text 10,40 \"Q3\""]
+    );
+
+    // An unset variable is reported in the file it is in
+    let err = read_template_script(&main, &defines[..1]).unwrap_err();
+    let dark = dir.join("themes/dark.txt").display().to_string();
+    assert_eq!(
+        err,
+        format!(
+            "{}: ${{accent}} is not set (pass --define accent=VALUE or set it in the environment)",
+            dark
+        )
+    );
+}
@@ -0,0 +1,242 @@
+use displai::*;
+use minifb::Key;
+
+fn chord(s: &str) -> Chord {
+    Chord::parse(s).expect("chord parses")
+}
+
+/// Write a keymap file unique to one test, returning its path
+fn write_keymap(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir()
+        .join(format!("displai_keymap_{}", name))
+        .to_string_lossy()
+        .into_owned();
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+// ===================
+// Chord Tests
+// ===================
+
+#[test]
+fn test_chord_parse() {
+    assert_eq!(chord("b"), Chord::key(Key::B));
+    assert_eq!(chord("["), Chord::key(Key::LeftBracket));
+    assert_eq!(chord("ctrl+s"), Chord::ctrl(Key::S));
+    assert_eq!(
+        chord("Ctrl+Shift+Z"),
+        Chord {
+            ctrl: true,
+            shift: true,
+            key: Key::Z
+        }
+    );
+    assert_eq!(chord("f10"), Chord::key(Key::F10));
+    assert_eq!(chord("ctrl+="), Chord::ctrl(Key::Equal));
+}
+
+#[test]
+fn test_chord_parse_rejects_unknown_keys() {
+    for s in ["", "ctrl+", "bb", "f13", "f0", "alt+b", "escape"] {
+        assert_eq!(Chord::parse(s), None, "{:?}", s);
+    }
+}
+
+#[test]
+fn test_chord_display_round_trips() {
+    for s in ["b", "]", "0", "ctrl+n", "ctrl+shift+z", "shift+f3", "space"] {
+        assert_eq!(chord(s).to_string(), s);
+    }
+}
+
+#[test]
+fn test_chord_typed_char() {
+    assert_eq!(chord("b").typed_char(), Some('b'));
+    assert_eq!(chord("shift+b").typed_char(), Some('B'));
+    assert_eq!(chord("[").typed_char(), Some('['));
+    assert_eq!(chord("ctrl+b").typed_char(), None);
+    assert_eq!(chord("f2").typed_char(), None);
+}
+
+// ===================
+// Action Tests
+// ===================
+
+#[test]
+fn test_action_parse() {
+    assert_eq!(
+        Action::parse("tool rect"),
+        Some(Action::Tool(ToolMode::Rectangle))
+    );
+    assert_eq!(
+        Action::parse("tool eyedropper"),
+        Some(Action::Tool(ToolMode::Eyedropper))
+    );
+    assert_eq!(Action::parse("size  up"), Some(Action::SizeUp));
+    assert_eq!(Action::parse("color 13"), Some(Action::Color(13)));
+    assert_eq!(Action::parse("snapshot"), Some(Action::Snapshot));
+}
+
+#[test]
+fn test_action_parse_rejects_unknown_actions() {
+    for s in ["", "tool pencil", "size", "color 14", "color red", "quit"] {
+        assert_eq!(Action::parse(s), None, "{:?}", s);
+    }
+}
+
+#[test]
+fn test_action_display_round_trips() {
+    for s in [
+        "tool brush",
+        "size down",
+        "size up",
+        "color 3",
+        "clear",
+        "snapshot",
+        "undo",
+        "redo",
+    ] {
+        assert_eq!(Action::parse(s).unwrap().to_string(), s);
+    }
+}
+
+// ===================
+// Keymap Tests
+// ===================
+
+#[test]
+fn test_default_keymap() {
+    let keymap = Keymap::default();
+    let lookup = |s: &str| keymap.lookup(chord(s));
+    assert_eq!(lookup("b"), Some(Action::Tool(ToolMode::Brush)));
+    assert_eq!(lookup("l"), Some(Action::Tool(ToolMode::Line)));
+    assert_eq!(lookup("r"), Some(Action::Tool(ToolMode::Rectangle)));
+    assert_eq!(lookup("c"), Some(Action::Tool(ToolMode::Circle)));
+    assert_eq!(lookup("o"), Some(Action::Tool(ToolMode::Oval)));
+    assert_eq!(lookup("t"), Some(Action::Tool(ToolMode::Triangle)));
+    assert_eq!(lookup("["), Some(Action::SizeDown));
+    assert_eq!(lookup("]"), Some(Action::SizeUp));
+    assert_eq!(lookup("ctrl+n"), Some(Action::Clear));
+    assert_eq!(lookup("ctrl+s"), Some(Action::Snapshot));
+    assert_eq!(lookup("ctrl+z"), Some(Action::Undo));
+    assert_eq!(lookup("ctrl+y"), Some(Action::Redo));
+    assert_eq!(lookup("ctrl+shift+z"), Some(Action::Redo));
+    // Modifiers must match exactly
+    assert_eq!(lookup("shift+b"), None);
+    assert_eq!(lookup("ctrl+b"), None);
+}
+
+#[test]
+fn test_default_keymap_digits_pick_colors_in_keyboard_order() {
+    let keymap = Keymap::default();
+    for (i, key) in ["1", "2", "3", "4", "5", "6", "7", "8", "9", "0"]
+        .iter()
+        .enumerate()
+    {
+        assert_eq!(keymap.lookup(chord(key)), Some(Action::Color(i)));
+    }
+}
+
+#[test]
+fn test_keymap_apply_overrides_and_unbinds() {
+    let mut keymap = Keymap::default();
+    keymap
+        .apply("# my keys\n\nb = tool bucket\np = tool polygon\nctrl+n = none\n")
+        .unwrap();
+    assert_eq!(
+        keymap.lookup(chord("b")),
+        Some(Action::Tool(ToolMode::Bucket))
+    );
+    assert_eq!(
+        keymap.lookup(chord("p")),
+        Some(Action::Tool(ToolMode::Polygon))
+    );
+    assert_eq!(keymap.lookup(chord("ctrl+n")), None);
+    // Other defaults are kept
+    assert_eq!(keymap.lookup(chord("ctrl+s")), Some(Action::Snapshot));
+}
+
+#[test]
+fn test_keymap_apply_binds_equals_key() {
+    let mut keymap = Keymap::empty();
+    keymap.apply("= = size up\nctrl+= = redo").unwrap();
+    assert_eq!(keymap.lookup(chord("=")), Some(Action::SizeUp));
+    assert_eq!(keymap.lookup(chord("ctrl+=")), Some(Action::Redo));
+}
+
+#[test]
+fn test_keymap_apply_reports_line_numbers() {
+    let mut keymap = Keymap::default();
+    assert_eq!(
+        keymap.apply("b = tool line\n\nalt+b = undo"),
+        Err("line 3: unknown key 'alt+b'".to_string())
+    );
+    assert_eq!(
+        keymap.apply("b = fly"),
+        Err("line 1: unknown action 'fly'".to_string())
+    );
+    assert_eq!(
+        keymap.apply("b tool line"),
+        Err("line 1: expected 'chord = action'".to_string())
+    );
+}
+
+#[test]
+fn test_keymap_keys_lists_each_key_once() {
+    let keys = Keymap::default().keys();
+    assert_eq!(keys.iter().filter(|&&k| k == Key::Z).count(), 1);
+    assert!(keys.contains(&Key::Key0));
+    assert!(Keymap::empty().keys().is_empty());
+}
+
+#[test]
+fn test_keymap_load() {
+    let path = write_keymap("load", "x = clear\n");
+    let keymap = Keymap::load(&path).unwrap();
+    assert_eq!(keymap.lookup(chord("x")), Some(Action::Clear));
+    assert_eq!(
+        keymap.lookup(chord("b")),
+        Some(Action::Tool(ToolMode::Brush))
+    );
+}
+
+#[test]
+fn test_keymap_load_errors_name_the_file() {
+    let path = write_keymap("bad", "x = nothing\n");
+    let err = Keymap::load(&path).unwrap_err();
+    assert_eq!(err, format!("{}: line 1: unknown action 'nothing'", path));
+    assert!(Keymap::load("/nonexistent/keys.conf").is_err());
+}
+
+#[test]
+fn test_config_keymap_flag() {
+    let path = write_keymap("config", "b = none\n");
+    let args = vec!["--keymap".to_string(), path];
+    let config = Config::from_args(args).unwrap();
+    assert_eq!(config.keymap.lookup(chord("b")), None);
+    assert_eq!(Config::default().keymap, Keymap::default());
+    assert!(Config::from_args(vec!["--keymap".to_string()]).is_err());
+}
+
+// ===================
+// Edge Detection Tests
+// ===================
+
+#[test]
+fn test_key_edges_report_presses_once() {
+    let mut edges = KeyEdges::new(vec![Key::B, Key::L]);
+    assert_eq!(edges.update(|k| k == Key::B), vec![Key::B]);
+    // Held down: no new press
+    assert_eq!(edges.update(|k| k == Key::B), vec![]);
+    assert_eq!(edges.update(|_| true), vec![Key::L]);
+    assert_eq!(edges.update(|_| false), vec![]);
+    // Released and pressed again
+    assert_eq!(edges.update(|k| k == Key::B), vec![Key::B]);
+}
+
+#[test]
+fn test_key_edges_ignore_untracked_keys() {
+    let mut edges = KeyEdges::new(vec![Key::B]);
+    assert_eq!(edges.update(|k| k == Key::Q), vec![]);
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::{pixel, run};
+use displai::*;
+
+fn entry(label: &str, color: u32) -> LegendEntry {
+    LegendEntry {
+        label: label.to_string(),
+        color,
+    }
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_legend() {
+    let cmd = Command::Legend {
+        x: 600,
+        y: 50,
+        entries: vec![entry("sales", 0xE04040), entry("2024 (est.)", 0x123456)],
+    };
+    assert_eq!(
+        parse_command("legend 600,50 entry \"sales\" red entry \"2024 (est.)\" #123456"),
+        Some(cmd.clone())
+    );
+    assert_eq!(
+        cmd.to_string(),
+        "legend 600,50 entry \"sales\" 2 entry \"2024 (est.)\" #123456"
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(cmd.name(), "legend");
+    assert!(cmd.is_mutating());
+    assert!(validate(&cmd).is_ok());
+
+    // Invalid formats
+    assert_eq!(parse_command("legend 600,50"), None);
+    assert_eq!(parse_command("legend 600,50 entry sales red"), None);
+    assert_eq!(parse_command("legend 600,50 entry \"sales\""), None);
+    assert_eq!(parse_command("legend 600,50 entry \"sales\" nocolor"), None);
+    assert_eq!(parse_command("legend 600,50 entry\"sales\" red"), None);
+    assert_eq!(parse_command("legend 600,50 item \"sales\" red"), None);
+    assert_eq!(parse_command("legend 600 entry \"sales\" red"), None);
+}
+
+#[test]
+fn test_validate_legend() {
+    let legend = |x, entries: Vec<LegendEntry>| Command::Legend { x, y: 50, entries };
+    // The whole box must fit the canvas
+    assert!(validate(&legend(700, vec![entry("ok", BLACK)])).is_ok());
+    assert!(validate(&legend(790, vec![entry("ok", BLACK)])).is_err());
+    assert_eq!(
+        validate(&legend(10, vec![entry("a", BLACK); MAX_LEGEND_ENTRIES + 1])),
+        Err("legend has 17 entries (max 16)".to_string())
+    );
+    assert_eq!(
+        validate(&legend(10, vec![entry(&"x".repeat(41), BLACK)])),
+        Err("legend label is longer than 40 characters".to_string())
+    );
+}
+
+#[test]
+fn test_run_refuses_oversized_legends() {
+    let legend = |x, entries: Vec<LegendEntry>| Command::Legend { x, y: 530, entries };
+    let mut state = AppState::new();
+    let before = state.canvas.clone();
+    for (cmd, reason) in [
+        (
+            legend(10, vec![entry(&"x".repeat(41), BLACK)]),
+            "legend label is longer than 40 characters",
+        ),
+        (
+            legend(10, vec![entry("a", BLACK); MAX_LEGEND_ENTRIES + 1]),
+            "legend has 17 entries (max 16)",
+        ),
+    ] {
+        assert_eq!(
+            state.run(&cmd.to_string()),
+            Err(CommandError::Failed(reason.to_string()))
+        );
+    }
+    let off_canvas = legend(790, vec![entry("ok", BLACK)]);
+    assert!(state.run(&off_canvas.to_string()).is_err());
+    assert!(state.canvas == before);
+}
+
+// ===================
+// Layout Tests
+// ===================
+
+#[test]
+fn test_legend_size_fits_widest_label() {
+    // Border and padding, a 9px swatch, a 4px gap, then the label
+    assert_eq!(
+        legend_size(&[entry("ab", BLACK)]),
+        (12 + 9 + 4 + 11, 12 + 9)
+    );
+    assert_eq!(
+        legend_size(&[entry("ab", BLACK), entry("abcd", RED)]),
+        (12 + 9 + 4 + 23, 12 + 9 + 3 + 9)
+    );
+}
+
+// ===================
+// Drawing Tests
+// ===================
+
+#[test]
+fn test_legend_draws_box_swatches_and_labels() {
+    let mut state = AppState::new();
+    run(&mut state, "rect 95,95 400,300");
+    run(
+        &mut state,
+        "legend 100,100 entry \"up\" #00FF00 entry \"down\" #FF0000",
+    );
+    // Border in the edge color, white inside
+    assert_eq!(pixel(&state, 100, 100), BLACK);
+    assert_eq!(pixel(&state, 102, 102), WHITE);
+    // Swatches start inside the padding, one row apart
+    assert_eq!(pixel(&state, 110, 110), 0x00FF00);
+    assert_eq!(pixel(&state, 110, 122), 0xFF0000);
+    // Labels right of the swatches
+    let (width, _) = legend_size(&[entry("down", 0)]);
+    let label_pixels = (106..130)
+        .flat_map(|y| (119..100 + width).map(move |x| (x, y)))
+        .filter(|&(x, y)| pixel(&state, x, y) == BLACK)
+        .count();
+    assert!(label_pixels > 20);
+    // Drawn pixels only: nothing for SVG export
+    assert_eq!(state.display_list.len(), 1);
+}
+
+#[test]
+fn test_legend_uses_edge_color() {
+    let mut state = AppState::new();
+    run(&mut state, "edge #0000FF");
+    run(&mut state, "legend 100,100 entry \"a\" #FFFFFF");
+    assert_eq!(pixel(&state, 100, 100), 0x0000FF);
+    // A white swatch is outlined
+    assert_eq!(pixel(&state, 106, 106), 0x0000FF);
+    assert_eq!(pixel(&state, 108, 108), WHITE);
+}
+
+#[test]
+fn test_legend_in_pane() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@2 legend 10,10 entry \"a\" red");
+    assert_eq!(pixel(&state, 410, CANVAS_TOP + 10), BLACK);
+    assert_eq!(pixel(&state, 10, CANVAS_TOP + 10), WHITE);
+    let cmd = parse_command("@2 legend 10,10 entry \"a\" red").unwrap();
+    assert!(matches!(
+        *state.placed(&cmd),
+        Command::Legend { x: 410, y: 40, .. }
+    ));
+}
+
+#[test]
+fn test_legend_region() {
+    let cmd = parse_command("legend 100,100 entry \"ab\" red").unwrap();
+    assert_eq!(command_region(&cmd, 0), Some(Region::new(100, 100, 36, 21)));
+}
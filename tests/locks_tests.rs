@@ -0,0 +1,310 @@
+use displai::*;
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_lockregion() {
+    let cmd = Command::LockRegion {
+        x: 10,
+        y: 40,
+        width: 100,
+        height: 50,
+    };
+    assert_eq!(parse_command("lockregion 10,40,100,50"), Some(cmd.clone()));
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(parse_command("lockregion 10,40,100"), None);
+    assert_eq!(parse_command("lockregion 10,40 100,50"), None);
+    assert_eq!(parse_command("lockregion"), None);
+    assert!(!cmd.is_mutating());
+}
+
+#[test]
+fn test_parse_unlockregion() {
+    for cmd in [Command::UnlockRegion(Some(3)), Command::UnlockRegion(None)] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    }
+    assert_eq!(parse_command("unlockregion x"), None);
+    assert_eq!(parse_command("unlockregion 1 2"), None);
+}
+
+#[test]
+fn test_validate_lockregion() {
+    let lock = |x, y, width, height| Command::LockRegion {
+        x,
+        y,
+        width,
+        height,
+    };
+    assert!(validate(&lock(0, CANVAS_TOP, WIDTH, CANVAS_BOTTOM - CANVAS_TOP)).is_ok());
+    assert!(validate(&lock(10, 40, 0, 10)).is_err());
+    assert!(validate(&lock(10, 40, WIDTH, 10)).is_err());
+    assert!(validate(&lock(10, 0, 10, 10)).is_err());
+    assert!(validate(&lock(usize::MAX, 40, 2, 2)).is_err());
+}
+
+// ===================
+// Region Tests
+// ===================
+
+#[test]
+fn test_region_intersects() {
+    let a = Region::new(10, 40, 10, 10);
+    assert!(a.intersects(&Region::new(19, 49, 5, 5)));
+    // Edges are exclusive: touching is not overlapping
+    assert!(!a.intersects(&Region::new(20, 40, 5, 5)));
+    assert!(!a.intersects(&Region::new(10, 50, 5, 5)));
+    assert!(a.intersects(&Region::ALL));
+}
+
+#[test]
+fn test_command_region() {
+    let region = |line: &str, reach| command_region(&parse_command(line).unwrap(), reach);
+    assert_eq!(region("dot 50,50", 2), Some(Region::new(48, 48, 5, 5)));
+    assert_eq!(
+        region("rect 60,90 10,40", 0),
+        Some(Region::new(10, 40, 51, 51))
+    );
+    assert_eq!(
+        region("circle 100,100 20", 1),
+        Some(Region::new(79, 79, 43, 43))
+    );
+    // A point's own brush size grows the region
+    assert_eq!(
+        region("points 50,50 60,50:#FF0000:4", 1),
+        Some(Region::new(46, 46, 19, 9))
+    );
+    // Arrowheads stick out past the ends
+    assert_eq!(
+        region("arrow 100,100 200,100 12", 1),
+        Some(Region::new(87, 87, 127, 27))
+    );
+    assert_eq!(region("clear", 1), Some(Region::ALL));
+    assert_eq!(region("undo", 1), Some(Region::ALL));
+    assert_eq!(region("state", 1), None);
+    assert_eq!(region("size 3", 1), None);
+}
+
+#[test]
+fn test_draw_reach_includes_shadow() {
+    let shadow = Shadow {
+        dx: -4,
+        dy: 2,
+        color: BLACK,
+        blur: 3,
+    };
+    assert_eq!(draw_reach(2, None), 2);
+    assert_eq!(draw_reach(2, Some(&shadow)), 9);
+}
+
+// ===================
+// Lock Tests
+// ===================
+
+#[test]
+fn test_other_clients_are_refused() {
+    let mut locks = RegionLocks::new();
+    let id = locks.lock(1, Region::new(0, 30, 100, 100)).unwrap();
+    let inside = parse_command("rect 10,40 50,80").unwrap();
+    let outside = parse_command("rect 200,200 250,250").unwrap();
+
+    assert!(locks.check(1, &inside, 1).is_ok());
+    assert_eq!(
+        locks.check(2, &inside, 1),
+        Err(format!("region is locked by another client (lock {})", id))
+    );
+    assert!(locks.check(2, &outside, 1).is_ok());
+    // Read-only commands are never blocked
+    assert!(locks
+        .check(2, &parse_command("getpixel 10,40").unwrap(), 1)
+        .is_ok());
+    // Whole-canvas commands are blocked while anyone else holds a lock
+    assert!(locks.check(2, &Command::Clear, 1).is_err());
+}
+
+#[test]
+fn test_overlapping_locks() {
+    let mut locks = RegionLocks::new();
+    let first = locks.lock(1, Region::new(0, 30, 100, 100)).unwrap();
+    // A client's own locks may overlap; another client's may not
+    assert!(locks.lock(1, Region::new(50, 50, 100, 100)).is_ok());
+    assert_eq!(
+        locks.lock(2, Region::new(90, 120, 20, 20)),
+        Err(format!("region overlaps lock {}", first))
+    );
+    assert!(locks.lock(2, Region::new(300, 300, 20, 20)).is_ok());
+    assert_eq!(locks.len(), 3);
+}
+
+#[test]
+fn test_unlock_and_release() {
+    let mut locks = RegionLocks::new();
+    let a = locks.lock(1, Region::new(0, 30, 10, 10)).unwrap();
+    locks.lock(1, Region::new(20, 30, 10, 10)).unwrap();
+    let b = locks.lock(2, Region::new(40, 30, 10, 10)).unwrap();
+
+    assert_eq!(
+        locks.unlock(2, a),
+        Err(format!("lock {} is held by another client", a))
+    );
+    assert!(locks.unlock(1, a).is_ok());
+    assert_eq!(locks.unlock(1, a), Err(format!("no lock {}", a)));
+    assert_eq!(locks.release(1), 1);
+    assert_eq!(locks.iter().map(|l| l.id).collect::<Vec<_>>(), [b]);
+}
+
+#[test]
+fn test_apply_responses() {
+    let mut locks = RegionLocks::new();
+    let run = |locks: &mut RegionLocks, client, line: &str| {
+        locks.apply(client, &parse_command(line).unwrap())
+    };
+    assert_eq!(
+        run(&mut locks, 1, "lockregion 0,30,100,100"),
+        reply("locked 1")
+    );
+    assert_eq!(
+        run(&mut locks, 2, "lockregion 50,50,10,10"),
+        failed("region overlaps lock 1")
+    );
+    assert_eq!(
+        run(&mut locks, 2, "unlockregion 1"),
+        failed("lock 1 is held by another client")
+    );
+    assert_eq!(run(&mut locks, 1, "unlockregion 1"), Ok(CommandOutput::Ok));
+    assert_eq!(run(&mut locks, 1, "unlockregion"), reply("unlocked 0"));
+}
+
+// ===================
+// Ownership Tests
+// ===================
+
+#[test]
+fn test_parse_layout() {
+    assert_eq!(parse_command("layout"), Some(Command::Layout));
+    assert_eq!(Command::Layout.to_string(), "layout");
+    assert_eq!(Command::Layout.name(), "layout");
+    assert!(validate(&Command::Layout).is_ok());
+    assert!(!Command::Layout.is_mutating());
+    assert_eq!(parse_command("layout 2x1"), None);
+}
+
+#[test]
+fn test_region_formats_as_lockregion_arguments() {
+    let region = Region::new(10, 40, 100, 50);
+    assert_eq!(region.to_string(), "10,40,100,50");
+    assert_eq!(
+        parse_command(&format!("lockregion {}", region)),
+        Some(Command::LockRegion {
+            x: 10,
+            y: 40,
+            width: 100,
+            height: 50
+        })
+    );
+}
+
+#[test]
+fn test_ownership() {
+    let mut locks = RegionLocks::new();
+    let pane = Region::new(0, 30, 100, 100);
+    assert_eq!(locks.ownership(1, &pane), Ownership::Free);
+    locks.lock(1, Region::new(10, 40, 10, 10)).unwrap();
+    assert_eq!(locks.ownership(1, &pane), Ownership::Mine);
+    assert_eq!(locks.ownership(2, &pane), Ownership::Taken(1));
+    assert_eq!(
+        locks.ownership(2, &Region::new(200, 30, 10, 10)),
+        Ownership::Free
+    );
+    // Another client's lock outweighs the asker's own
+    locks.lock(2, Region::new(50, 40, 10, 10)).unwrap();
+    assert_eq!(locks.ownership(1, &pane), Ownership::Taken(2));
+    let held: Vec<_> = locks.iter().map(|lock| lock.ownership(1)).collect();
+    assert_eq!(held, [Ownership::Mine, Ownership::Taken(2)]);
+}
+
+#[test]
+fn test_ownership_names() {
+    assert_eq!(Ownership::Free.to_string(), "free");
+    assert_eq!(Ownership::Mine.to_string(), "mine");
+    assert_eq!(Ownership::Taken(STDIN_CLIENT).to_string(), "stdin");
+    assert_eq!(Ownership::Taken(3).to_string(), "client3");
+}
+
+#[test]
+fn test_state_reports_pane_ownership() {
+    let mut state = AppState::new();
+    // Unsplit and unlocked, `state` is just the settings
+    assert_eq!(
+        state.describe(&RegionLocks::new(), 1),
+        "edge:0 fill:none size:1"
+    );
+    state.execute(&parse_command("panes 2x2").unwrap());
+    let mut locks = RegionLocks::new();
+    locks.lock(1, state.pane(2).unwrap()).unwrap();
+    locks.lock(2, state.pane(4).unwrap()).unwrap();
+    assert_eq!(
+        state.describe(&locks, 1),
+        "edge:0 fill:none size:1 panes:2x2 free:1,3 mine:2 taken:4 locks:2"
+    );
+    assert_eq!(
+        state.describe(&locks, 3),
+        "edge:0 fill:none size:1 panes:2x2 free:1,3 mine:- taken:2,4 locks:2"
+    );
+    // Run alone, `state` sees no locks
+    assert_eq!(
+        state.execute(&Command::State),
+        Some("edge:0 fill:none size:1 panes:2x2 free:1,2,3,4 mine:- taken:- locks:0".to_string())
+    );
+}
+
+#[test]
+fn test_state_reports_locks_without_panes() {
+    let state = AppState::new();
+    let mut locks = RegionLocks::new();
+    locks.lock(2, Region::new(0, 30, 10, 10)).unwrap();
+    assert_eq!(
+        state.describe(&locks, 1),
+        "edge:0 fill:none size:1 panes:1x1 free:- mine:- taken:1 locks:1"
+    );
+}
+
+#[test]
+fn test_layout_lists_panes_and_locks() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.execute(&Command::Layout),
+        Some(format!(
+            "panes:1x1 margin:0 pane1:{}:free",
+            state.pane(1).unwrap()
+        ))
+    );
+    state.execute(&parse_command("panes 2x1 10").unwrap());
+    let (left, right) = (state.pane(1).unwrap(), state.pane(2).unwrap());
+    let mut locks = RegionLocks::new();
+    let id = locks.lock(STDIN_CLIENT, right).unwrap();
+    let corner = locks.lock(4, Region::new(0, 0, 5, 5)).unwrap();
+    assert_eq!(
+        state.layout(&locks, 4),
+        format!(
+            "panes:2x1 margin:10 pane1:{}:free pane2:{}:stdin lock{}:{}:stdin lock{}:0,0,5,5:mine",
+            left, right, id, right, corner
+        )
+    );
+}
+
+#[test]
+fn test_layout_pane_locks_to_its_pane() {
+    // A pane's region, locked as `layout` reports it, covers what `@N` draws
+    let mut state = AppState::new();
+    state.execute(&parse_command("panes 2x1").unwrap());
+    let mut locks = RegionLocks::new();
+    let pane = state.pane(2).unwrap();
+    let cmd = parse_command(&format!("lockregion {}", pane)).unwrap();
+    assert_eq!(locks.apply(1, &cmd), reply("locked 1"));
+    let inside = parse_command("@2 rect 10,10 50,50").unwrap();
+    let outside = parse_command("@1 rect 10,10 50,50").unwrap();
+    assert!(locks.check(2, &state.placed(&inside), 1).is_err());
+    assert!(locks.check(2, &state.placed(&outside), 1).is_ok());
+}
@@ -0,0 +1,172 @@
+use std::time::{Duration, Instant};
+
+use displai::*;
+
+fn notify(text: &str, secs: u64, level: NotifyLevel) -> Command {
+    Command::Notify {
+        text: text.to_string(),
+        secs,
+        level,
+        beep: false,
+    }
+}
+
+// ===================
+// Notify Command Tests
+// ===================
+
+#[test]
+fn test_parse_notify_commands() {
+    assert_eq!(
+        parse_command("notify \"Saved\""),
+        Some(notify("Saved", DEFAULT_NOTIFY_SECS, NotifyLevel::Info))
+    );
+    assert_eq!(
+        parse_command("notify \"Saved\" 5"),
+        Some(notify("Saved", 5, NotifyLevel::Info))
+    );
+    assert_eq!(
+        parse_command("notify \"Disk full\" error"),
+        Some(notify("Disk full", DEFAULT_NOTIFY_SECS, NotifyLevel::Error))
+    );
+    let warn = notify("Low battery", 10, NotifyLevel::Warn);
+    assert_eq!(
+        parse_command("notify \"Low battery\" 10 warn"),
+        Some(warn.clone())
+    );
+    assert_eq!(parse_command(&warn.to_string()), Some(warn));
+
+    assert_eq!(parse_command("notify Saved"), None);
+    assert_eq!(parse_command("notify \"Saved\" loud"), None);
+    assert_eq!(parse_command("notify \"Saved\" warn 5"), None);
+    assert_eq!(parse_command("notify \"Saved\" 5 warn extra"), None);
+    assert_eq!(parse_command("notify"), None);
+}
+
+#[test]
+fn test_parse_notify_beep() {
+    let with_beep = |secs, level| Command::Notify {
+        text: "Door open".to_string(),
+        secs,
+        level,
+        beep: true,
+    };
+    assert_eq!(
+        parse_command("notify \"Door open\" beep"),
+        Some(with_beep(DEFAULT_NOTIFY_SECS, NotifyLevel::Info))
+    );
+    assert_eq!(
+        parse_command("notify \"Door open\" error beep"),
+        Some(with_beep(DEFAULT_NOTIFY_SECS, NotifyLevel::Error))
+    );
+    let cmd = with_beep(10, NotifyLevel::Warn);
+    assert_eq!(
+        parse_command("notify \"Door open\" 10 warn beep"),
+        Some(cmd.clone())
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("notify \"Door open\" beep 10"), None);
+    assert_eq!(parse_command("notify \"Door open\" beep beep"), None);
+}
+
+#[test]
+fn test_validate_notify() {
+    assert!(validate(&notify("Saved", 1, NotifyLevel::Info)).is_ok());
+    assert!(validate(&notify("Saved", MAX_NOTIFY_SECS, NotifyLevel::Info)).is_ok());
+    assert!(validate(&notify("Saved", 0, NotifyLevel::Info)).is_err());
+    assert!(validate(&notify("Saved", MAX_NOTIFY_SECS + 1, NotifyLevel::Info)).is_err());
+    assert!(validate(&notify(" ", 3, NotifyLevel::Info)).is_err());
+    assert!(validate(&notify(&"x".repeat(MAX_NOTIFY_LEN), 3, NotifyLevel::Info)).is_ok());
+    assert!(validate(&notify(
+        &"x".repeat(MAX_NOTIFY_LEN + 1),
+        3,
+        NotifyLevel::Info
+    ))
+    .is_err());
+}
+
+// ===================
+// Toast Tests
+// ===================
+
+#[test]
+fn test_toasts_expire() {
+    let now = Instant::now();
+    let mut toasts = Toasts::new();
+    toasts.push("short", 1, NotifyLevel::Info, now);
+    toasts.push("long", 5, NotifyLevel::Warn, now);
+    assert_eq!(toasts.len(), 2);
+
+    assert!(!toasts.tick(now));
+    assert!(toasts.tick(now + Duration::from_secs(1)));
+    let left: Vec<_> = toasts.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(left, ["long"]);
+    assert!(!toasts.tick(now + Duration::from_secs(4)));
+    assert!(toasts.tick(now + Duration::from_secs(5)));
+    assert!(toasts.is_empty());
+}
+
+#[test]
+fn test_toasts_drop_oldest_past_limit() {
+    let now = Instant::now();
+    let mut toasts = Toasts::new();
+    for i in 0..MAX_TOASTS + 2 {
+        toasts.push(&format!("toast {}", i), 3, NotifyLevel::Info, now);
+    }
+    assert_eq!(toasts.len(), MAX_TOASTS);
+    assert_eq!(toasts.iter().next().unwrap().text, "toast 2");
+}
+
+#[test]
+fn test_toasts_stack_up_from_canvas_bottom() {
+    let now = Instant::now();
+    let mut toasts = Toasts::new();
+    toasts.push("first", 3, NotifyLevel::Info, now);
+    toasts.push("second toast", 3, NotifyLevel::Info, now);
+    let bounds = toasts.bounds();
+    let (x0, y0, w0, h0) = bounds[0];
+    let (x1, y1, w1, h1) = bounds[1];
+
+    // The newest is lowest, and both are centered above the toolbar
+    assert!(y0 + h0 < y1);
+    assert!(y1 + h1 <= CANVAS_BOTTOM);
+    assert!(y0 >= CANVAS_TOP);
+    assert!((2 * x0 + w0).abs_diff(WIDTH) <= 1);
+    assert!((2 * x1 + w1).abs_diff(WIDTH) <= 1);
+    assert!(w1 > w0);
+}
+
+#[test]
+fn test_toasts_draw_level_colors() {
+    for level in [NotifyLevel::Info, NotifyLevel::Warn, NotifyLevel::Error] {
+        let mut toasts = Toasts::new();
+        toasts.push("Hello", 3, level, Instant::now());
+        let mut frame = vec![WHITE; WIDTH * HEIGHT];
+        toasts.draw(&mut frame);
+
+        let (background, color) = level.colors();
+        let (x, y, w, h) = toasts.bounds()[0];
+        // The corner is background, and the text is drawn inside
+        assert_eq!(frame[y * WIDTH + x], background);
+        let mut inside = (y..y + h).flat_map(|py| (x..x + w).map(move |px| (px, py)));
+        assert!(inside
+            .clone()
+            .any(|(px, py)| frame[py * WIDTH + px] == color));
+        assert!(inside.all(|(px, py)| [background, color].contains(&frame[py * WIDTH + px])));
+    }
+}
+
+#[test]
+fn test_long_toast_fits_window() {
+    let mut toasts = Toasts::new();
+    toasts.push(
+        &"W".repeat(MAX_NOTIFY_LEN),
+        3,
+        NotifyLevel::Info,
+        Instant::now(),
+    );
+    let (x, _, w, _) = toasts.bounds()[0];
+    assert!(x + w <= WIDTH);
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    toasts.draw(&mut frame);
+}
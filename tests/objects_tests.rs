@@ -0,0 +1,745 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use displai::*;
+
+fn text(x: usize, y: usize, s: &str, scale: usize) -> Command {
+    Command::Object(ObjectCommand::Text {
+        x,
+        y,
+        text: s.to_string(),
+        scale,
+    })
+}
+
+fn set_text(id: usize, s: &str) -> Command {
+    Command::Object(ObjectCommand::SetText {
+        id,
+        text: s.to_string(),
+    })
+}
+
+// ===================
+// Object Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_object_commands() {
+    assert_eq!(
+        parse_command("obj text 10,40 \"CPU: 37%\""),
+        Some(text(10, 40, "CPU: 37%", 1))
+    );
+    assert_eq!(
+        parse_command("obj text 10,40 \"big\" 3"),
+        Some(text(10, 40, "big", 3))
+    );
+    assert_eq!(
+        parse_command("obj text 0,30 \"\""),
+        Some(text(0, 30, "", 1))
+    );
+    assert_eq!(
+        parse_command("obj settext 2 \"new label\""),
+        Some(set_text(2, "new label"))
+    );
+    assert_eq!(
+        parse_command("obj delete 7"),
+        Some(Command::Object(ObjectCommand::Delete(7)))
+    );
+    assert_eq!(
+        parse_command("obj list"),
+        Some(Command::Object(ObjectCommand::List))
+    );
+    assert_eq!(
+        parse_command("obj blink 2 500"),
+        Some(Command::Object(ObjectCommand::Blink { id: 2, ms: 500 }))
+    );
+}
+
+#[test]
+fn test_parse_object_rejects_malformed() {
+    assert_eq!(parse_command("obj"), None);
+    assert_eq!(parse_command("obj text 10,40 unquoted"), None);
+    assert_eq!(parse_command("obj text 10,40 \"open"), None);
+    assert_eq!(parse_command("obj text 10 \"x\""), None);
+    assert_eq!(parse_command("obj text 10,40 \"x\"y"), None);
+    assert_eq!(parse_command("obj text 10,40 \"x\" 0"), None);
+    assert_eq!(
+        parse_command(&format!("obj text 10,40 \"x\" {}", MAX_TEXT_SCALE + 1)),
+        None
+    );
+    assert_eq!(parse_command("obj text 10,40 \"x\" 2 3"), None);
+    assert_eq!(parse_command("obj settext x \"a\""), None);
+    assert_eq!(parse_command("obj settext 1 \"a\" extra"), None);
+    assert_eq!(parse_command("obj delete"), None);
+    assert_eq!(parse_command("obj list all"), None);
+    assert_eq!(parse_command("obj move 1"), None);
+    assert_eq!(parse_command("obj blink 1"), None);
+    assert_eq!(parse_command("obj blink 1 fast"), None);
+}
+
+#[test]
+fn test_object_display_round_trips() {
+    for cmd in [
+        text(10, 40, "CPU: 37%", 1),
+        text(5, 60, "two words", 4),
+        set_text(3, "done"),
+        Command::Object(ObjectCommand::Delete(9)),
+        Command::Object(ObjectCommand::List),
+        Command::Object(ObjectCommand::Blink { id: 2, ms: 250 }),
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    }
+    assert_eq!(text(1, 31, "a", 1).to_string(), "obj text 1,31 \"a\"");
+    assert_eq!(text(1, 31, "a", 2).to_string(), "obj text 1,31 \"a\" 2");
+}
+
+#[test]
+fn test_validate_object_commands() {
+    assert!(validate(&text(10, 40, "ok", 1)).is_ok());
+    assert!(validate(&text(10, 5, "title bar", 1)).is_err());
+    assert!(validate(&text(10, 40, "quote\"", 1)).is_err());
+    assert!(validate(&text(10, 40, "x", MAX_TEXT_SCALE + 1)).is_err());
+    assert!(validate(&set_text(1, "line\nbreak")).is_err());
+    assert!(!text(10, 40, "x", 1).is_mutating());
+    let blink = |ms| Command::Object(ObjectCommand::Blink { id: 1, ms });
+    assert!(validate(&blink(0)).is_ok());
+    assert!(validate(&blink(MIN_BLINK_MS)).is_ok());
+    assert!(validate(&blink(MAX_BLINK_MS)).is_ok());
+    assert!(validate(&blink(MIN_BLINK_MS - 1)).is_err());
+    assert!(validate(&blink(MAX_BLINK_MS + 1)).is_err());
+}
+
+// ===================
+// Object Execution Tests
+// ===================
+
+#[test]
+fn test_text_object_ids_and_list() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::List)),
+        Some("none".to_string())
+    );
+    assert_eq!(
+        state.execute(&text(10, 40, "a", 1)),
+        Some("id:1".to_string())
+    );
+    assert_eq!(
+        state.execute(&text(10, 60, "b", 1)),
+        Some("id:2".to_string())
+    );
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::Delete(1))),
+        None
+    );
+    // IDs are not reused after a delete
+    assert_eq!(
+        state.execute(&text(10, 80, "c", 1)),
+        Some("id:3".to_string())
+    );
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::List)),
+        Some("2:text 3:text".to_string())
+    );
+}
+
+#[test]
+fn test_unknown_object_ids_are_errors() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.execute(&set_text(4, "x")),
+        Some("error: no object 4".to_string())
+    );
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::Delete(4))),
+        Some("error: no object 4".to_string())
+    );
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::Blink { id: 4, ms: 500 })),
+        Some("error: no object 4".to_string())
+    );
+}
+
+#[test]
+fn test_text_objects_stay_off_the_canvas() {
+    let mut state = AppState::new();
+    state.execute(&text(100, 100, "HELLO", 2));
+    assert!(state.canvas.iter().all(|&p| p == WHITE));
+
+    let rendered = state.render();
+    assert!(rendered.contains(&BLACK));
+    let (x, y, w, h) = state.objects.get(1).unwrap().bounds(&state.vars);
+    assert_eq!(
+        (x, y, w, h),
+        (100, 100, text_width("HELLO") * 2, GLYPH_HEIGHT * 2)
+    );
+    for (i, &p) in rendered.iter().enumerate() {
+        if p == BLACK {
+            let (px, py) = (i % WIDTH, i / WIDTH);
+            assert!((x..x + w).contains(&px) && (y..y + h).contains(&py));
+        }
+    }
+}
+
+#[test]
+fn test_settext_rerenders_in_place_over_drawing() {
+    let mut state = AppState::new();
+    state.execute(&Command::Fill(Some(COLOR_PALETTE[7])));
+    state.execute(&Command::Rect {
+        x1: 90,
+        y1: 90,
+        x2: 300,
+        y2: 130,
+    });
+    state.execute(&Command::Edge(Some(COLOR_PALETTE[2])));
+    state.execute(&text(100, 100, "CPU: 88%", 3));
+    state.execute(&set_text(1, "CPU: 1%"));
+
+    let mut expected = state.canvas.clone();
+    draw_text_scaled(&mut expected, 100, 100, "CPU: 1%", COLOR_PALETTE[2], 3);
+    assert_eq!(*state.render(), expected);
+
+    // Deleting the object leaves the drawing beneath untouched
+    state.execute(&Command::Object(ObjectCommand::Delete(1)));
+    assert_eq!(*state.render(), state.canvas);
+}
+
+#[test]
+fn test_text_takes_edge_color_at_creation() {
+    let mut state = AppState::new();
+    state.execute(&Command::Edge(Some(COLOR_PALETTE[10])));
+    state.execute(&text(100, 100, "A", 1));
+    state.execute(&Command::Edge(Some(COLOR_PALETTE[2])));
+    state.execute(&set_text(1, "B"));
+    assert!(state.render().contains(&COLOR_PALETTE[10]));
+    assert!(!state.render().contains(&COLOR_PALETTE[2]));
+
+    // Transparent edge makes invisible text
+    state.execute(&Command::Edge(None));
+    state.execute(&text(200, 200, "C", 1));
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::List)),
+        Some("1:text 2:text".to_string())
+    );
+}
+
+#[test]
+fn test_objects_never_cover_toolbars() {
+    let mut state = AppState::new();
+    draw_title_bar(&mut state.canvas);
+    draw_bottom_toolbar(
+        &mut state.canvas,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
+    state.execute(&text(0, CANVAS_BOTTOM - 10, "XXXXXXXX", 8));
+    state.execute(&text(0, CANVAS_TOP, "XXXXXXXX", 8));
+
+    let rendered = state.render();
+    assert_eq!(
+        rendered[CANVAS_BOTTOM * WIDTH..],
+        state.canvas[CANVAS_BOTTOM * WIDTH..]
+    );
+    assert_eq!(
+        rendered[..CANVAS_TOP * WIDTH],
+        state.canvas[..CANVAS_TOP * WIDTH]
+    );
+    assert_ne!(*rendered, state.canvas);
+}
+
+#[test]
+fn test_blinking_object_toggles_with_tick() {
+    let mut state = AppState::new();
+    state.execute(&text(100, 100, "ALERT", 2));
+    let start = Instant::now();
+    state
+        .objects
+        .set_blink(1, Some(Duration::from_millis(500)), start)
+        .unwrap();
+    let drawn = state.render().into_owned();
+    assert_ne!(drawn, state.canvas);
+
+    let at = |ms| start + Duration::from_millis(ms);
+    assert!(!state.objects.tick(at(100), SystemTime::now()));
+    assert!(state.objects.tick(at(600), SystemTime::now()));
+    assert!(!state.objects.get(1).unwrap().visible);
+    assert_eq!(*state.render(), state.canvas);
+    assert!(!state.objects.tick(at(900), SystemTime::now()));
+    assert!(state.objects.tick(at(1100), SystemTime::now()));
+    assert_eq!(*state.render(), drawn);
+
+    // Stopping the blink shows the object for good
+    state.objects.tick(at(1600), SystemTime::now());
+    assert_eq!(
+        state.execute(&Command::Object(ObjectCommand::Blink { id: 1, ms: 0 })),
+        None
+    );
+    assert!(state.objects.get(1).unwrap().visible);
+    assert!(!state.objects.tick(at(2600), SystemTime::now()));
+    assert_eq!(*state.render(), drawn);
+}
+
+// ===================
+// Button Widget Tests
+// ===================
+
+fn button(x: usize, y: usize, w: usize, h: usize, label: &str, event: &str) -> Command {
+    Command::Object(ObjectCommand::Button {
+        x,
+        y,
+        w,
+        h,
+        label: label.to_string(),
+        event: event.to_string(),
+    })
+}
+
+#[test]
+fn test_parse_button() {
+    let cmd = button(10, 40, 80, 24, "Start now", "start");
+    assert_eq!(
+        parse_command("button 10,40,80,24 \"Start now\" start"),
+        Some(cmd.clone())
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("button 10,40,80 \"Go\" go"), None);
+    assert_eq!(parse_command("button 10,40,80,24 Go go"), None);
+    assert_eq!(parse_command("button 10,40,80,24 \"Go\""), None);
+    assert_eq!(parse_command("button 10,40,80,24 \"Go\" go now"), None);
+}
+
+#[test]
+fn test_button_validation() {
+    assert!(validate(&button(10, 40, 80, 24, "Go", "go")).is_ok());
+    assert!(validate(&button(10, 40, 0, 24, "Go", "go")).is_err());
+    assert!(validate(&button(10, 40, 80, 0, "Go", "go")).is_err());
+    // The whole button must fit on the canvas
+    assert!(validate(&button(WIDTH - 10, 40, 80, 24, "Go", "go")).is_err());
+    assert!(validate(&button(10, CANVAS_BOTTOM - 10, 80, 24, "Go", "go")).is_err());
+    assert!(validate(&button(usize::MAX, 40, 80, 24, "Go", "go")).is_err());
+    assert!(validate(&button(10, 40, 80, 24, "Go", "no!")).is_err());
+}
+
+#[test]
+fn test_button_renders_face_border_and_label() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.execute(&button(100, 100, 80, 24, "OK", "ok")),
+        Some("id:1".to_string())
+    );
+    assert_eq!(state.objects.get(1).unwrap().kind.name(), "button");
+    assert_eq!(
+        state.objects.get(1).unwrap().bounds(&state.vars),
+        (100, 100, 80, 24)
+    );
+
+    let frame = state.render();
+    assert_eq!(frame[100 * WIDTH + 100], DARK_GRAY);
+    assert_eq!(frame[123 * WIDTH + 179], DARK_GRAY);
+    assert_eq!(frame[102 * WIDTH + 102], GRAY);
+    let label = (100..124)
+        .flat_map(|y| (100..180).map(move |x| (x, y)))
+        .filter(|&(x, y)| frame[y * WIDTH + x] == BLACK)
+        .count();
+    assert!(label > 0);
+    // Objects are overlays; the canvas underneath is untouched
+    assert_eq!(state.canvas[102 * WIDTH + 102], WHITE);
+}
+
+#[test]
+fn test_button_press_shows_pressed_state_and_names_event() {
+    let mut state = AppState::new();
+    state.execute(&button(100, 100, 80, 24, "OK", "ok"));
+    state.execute(&text(300, 100, "LABEL", 1));
+    let idle = state.render().into_owned();
+
+    assert_eq!(state.objects.press(2, &mut state.vars), None);
+    assert_eq!(
+        state.objects.press(1, &mut state.vars),
+        Some(Event::Named("ok".to_string()))
+    );
+    let pressed = state.render().into_owned();
+    assert_ne!(pressed, idle);
+    assert_eq!(pressed[100 * WIDTH + 100], BLACK);
+    assert_eq!(pressed[102 * WIDTH + 102], DARK_GRAY);
+
+    assert!(state.objects.release());
+    assert!(!state.objects.release());
+    // The button keeps keyboard focus after the press
+    assert_eq!(state.objects.focused(), Some(1));
+    state.objects.set_focus(None);
+    assert_eq!(*state.render(), idle);
+}
+
+#[test]
+fn test_button_label_is_templated_and_settable() {
+    let mut state = AppState::new();
+    state.execute(&button(100, 100, 80, 24, "{n}", "go"));
+    assert_eq!(state.objects.get(1).unwrap().text(&state.vars), "{n}");
+    state.execute(&parse_command("var set n 3").unwrap());
+    assert_eq!(state.objects.get(1).unwrap().text(&state.vars), "3");
+    assert_eq!(state.execute(&set_text(1, "Stop")), None);
+    assert_eq!(state.objects.get(1).unwrap().text(&state.vars), "Stop");
+}
+
+// ===================
+// Slider Widget Tests
+// ===================
+
+fn slider(x: usize, y: usize, w: usize, min: i64, max: i64, var: &str) -> Command {
+    Command::Object(ObjectCommand::Slider {
+        x,
+        y,
+        w,
+        min,
+        max,
+        var: var.to_string(),
+    })
+}
+
+#[test]
+fn test_parse_slider() {
+    let cmd = slider(10, 40, 101, -50, 50, "gain");
+    assert_eq!(
+        parse_command("slider 10,40,101 -50 50 gain"),
+        Some(cmd.clone())
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("slider 10,40 0 10 gain"), None);
+    assert_eq!(parse_command("slider 10,40,100 0 10"), None);
+    assert_eq!(parse_command("slider 10,40,100 0 1.5 gain"), None);
+}
+
+#[test]
+fn test_slider_validation() {
+    assert!(validate(&slider(10, 40, 100, 0, 10, "gain")).is_ok());
+    assert!(validate(&slider(10, 40, MIN_SLIDER_WIDTH - 1, 0, 10, "gain")).is_err());
+    assert!(validate(&slider(10, 40, 100, 10, 10, "gain")).is_err());
+    assert!(validate(&slider(10, 40, 100, 10, 0, "gain")).is_err());
+    assert!(validate(&slider(WIDTH - 50, 40, 100, 0, 10, "gain")).is_err());
+    assert!(validate(&slider(10, CANVAS_BOTTOM - 8, 100, 0, 10, "gain")).is_err());
+    assert!(validate(&slider(10, 40, 100, 0, 10, "bad name")).is_err());
+}
+
+#[test]
+fn test_slider_initializes_its_variable_in_range() {
+    let mut state = AppState::new();
+    state.execute(&slider(100, 100, 101, 0, 10, "gain"));
+    assert_eq!(state.vars.get("gain"), Some("0"));
+
+    // An existing value is kept, clamped into range
+    state.execute(&parse_command("var set level 99").unwrap());
+    state.execute(&slider(100, 200, 101, 0, 10, "level"));
+    assert_eq!(state.vars.get("level"), Some("10"));
+
+    let (x, y, w, h) = state.objects.get(1).unwrap().bounds(&state.vars);
+    assert_eq!((x, y, w, h), (100, 100, 101, SLIDER_HEIGHT));
+    assert!(state.objects.get(1).unwrap().kind.is_interactive());
+}
+
+#[test]
+fn test_slider_value_spreads_across_width() {
+    let mut state = AppState::new();
+    state.execute(&slider(100, 100, 101, -10, 10, "gain"));
+    let object = state.objects.get(1).unwrap();
+    assert_eq!(object.slider_value_at(100), Some(-10));
+    assert_eq!(object.slider_value_at(150), Some(0));
+    assert_eq!(object.slider_value_at(200), Some(10));
+    // Past either end clamps
+    assert_eq!(object.slider_value_at(0), Some(-10));
+    assert_eq!(object.slider_value_at(500), Some(10));
+}
+
+#[test]
+fn test_slider_drag_sets_variable_and_reports_changes() {
+    let mut state = AppState::new();
+    state.execute(&slider(100, 100, 101, 0, 100, "gain"));
+    state.execute(&text(100, 150, "gain={gain}", 1));
+
+    let event = state.objects.drag(1, 175, &mut state.vars);
+    assert_eq!(
+        event,
+        Some(Event::Changed {
+            name: "gain".to_string(),
+            value: "75".to_string()
+        })
+    );
+    assert_eq!(
+        state.execute(&parse_command("var get gain").unwrap()),
+        Some("75".to_string())
+    );
+    assert_eq!(state.objects.get(2).unwrap().text(&state.vars), "gain=75");
+
+    // No event while the value stays the same; other objects can't be dragged
+    assert_eq!(state.objects.drag(1, 175, &mut state.vars), None);
+    assert_eq!(state.objects.drag(2, 175, &mut state.vars), None);
+}
+
+#[test]
+fn test_slider_knob_follows_variable() {
+    let mut state = AppState::new();
+    state.execute(&slider(100, 100, 101, 0, 100, "gain"));
+    let knob_y = 100 + SLIDER_HEIGHT / 2;
+    let knob_at = |state: &AppState, x: usize| state.render()[(knob_y - 3) * WIDTH + x] == BLACK;
+
+    assert!(knob_at(&state, 102));
+    assert!(!knob_at(&state, 150));
+    state.execute(&parse_command("var set gain 50").unwrap());
+    assert!(knob_at(&state, 150));
+    assert!(!knob_at(&state, 102));
+    // The track spans the whole width
+    assert_eq!(state.render()[knob_y * WIDTH + 190], DARK_GRAY);
+    assert_eq!(state.canvas[knob_y * WIDTH + 190], WHITE);
+}
+
+// ===================
+// Checkbox and Toggle Widget Tests
+// ===================
+
+fn check(style: CheckStyle, x: usize, y: usize, label: &str, var: &str) -> Command {
+    Command::Object(ObjectCommand::Check {
+        x,
+        y,
+        style,
+        label: label.to_string(),
+        var: var.to_string(),
+    })
+}
+
+#[test]
+fn test_parse_checkbox_and_toggle() {
+    let cmd = check(CheckStyle::Checkbox, 10, 40, "Show grid", "grid");
+    assert_eq!(
+        parse_command("checkbox 10,40 \"Show grid\" grid"),
+        Some(cmd.clone())
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    let cmd = check(CheckStyle::Toggle, 10, 40, "", "live");
+    assert_eq!(parse_command("toggle 10,40 \"\" live"), Some(cmd.clone()));
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+
+    assert_eq!(parse_command("checkbox 10,40 grid"), None);
+    assert_eq!(parse_command("checkbox 10,40 \"Grid\""), None);
+    assert_eq!(parse_command("toggle 10 \"Live\" live"), None);
+}
+
+#[test]
+fn test_checkbox_validation() {
+    assert!(validate(&check(CheckStyle::Checkbox, 10, 40, "Grid", "grid")).is_ok());
+    assert!(validate(&check(CheckStyle::Toggle, WIDTH - 10, 40, "", "live")).is_err());
+    assert!(validate(&check(CheckStyle::Checkbox, 10, 5, "Grid", "grid")).is_err());
+    assert!(validate(&check(CheckStyle::Checkbox, 10, 40, "Grid", "no way")).is_err());
+}
+
+#[test]
+fn test_checkbox_starts_off_and_flips_on_press() {
+    let mut state = AppState::new();
+    state.execute(&check(CheckStyle::Checkbox, 100, 100, "Grid", "grid"));
+    assert_eq!(state.vars.get("grid"), Some("off"));
+    assert_eq!(state.objects.list(), "1:checkbox");
+
+    let on = state.objects.press(1, &mut state.vars);
+    assert_eq!(
+        on,
+        Some(Event::Changed {
+            name: "grid".to_string(),
+            value: "on".to_string()
+        })
+    );
+    assert_eq!(
+        state.execute(&parse_command("var get grid").unwrap()),
+        Some("on".to_string())
+    );
+    state.objects.press(1, &mut state.vars);
+    assert_eq!(state.vars.get("grid"), Some("off"));
+}
+
+#[test]
+fn test_toggle_keeps_existing_value() {
+    let mut state = AppState::new();
+    state.execute(&parse_command("var set live on").unwrap());
+    state.execute(&check(CheckStyle::Toggle, 100, 100, "Live", "live"));
+    assert_eq!(state.vars.get("live"), Some("on"));
+    assert_eq!(state.objects.list(), "1:toggle");
+    let (_, _, w, h) = state.objects.get(1).unwrap().bounds(&state.vars);
+    assert_eq!(h, CHECK_SIZE);
+    assert_eq!(w, TOGGLE_WIDTH + CHECK_LABEL_GAP + text_width("Live"));
+}
+
+#[test]
+fn test_checkbox_draws_tick_when_on() {
+    let mut state = AppState::new();
+    state.execute(&check(CheckStyle::Checkbox, 100, 100, "", "grid"));
+    let inside = |state: &AppState| {
+        let frame = state.render();
+        (101..111)
+            .flat_map(|y| (101..111).map(move |x| (x, y)))
+            .filter(|&(x, y)| frame[y * WIDTH + x] == BLACK)
+            .count()
+    };
+    assert_eq!(state.render()[100 * WIDTH + 100], DARK_GRAY);
+    assert_eq!(inside(&state), 0);
+    state.execute(&parse_command("var set grid on").unwrap());
+    assert!(inside(&state) > 0);
+}
+
+#[test]
+fn test_toggle_knob_moves_when_on() {
+    let mut state = AppState::new();
+    state.execute(&check(CheckStyle::Toggle, 100, 100, "", "live"));
+    let mid = 100 + CHECK_SIZE / 2;
+    let frame = state.render().into_owned();
+    assert_eq!(frame[mid * WIDTH + 103], WHITE);
+    assert_eq!(frame[mid * WIDTH + 118], GRAY);
+
+    state.execute(&parse_command("var set live on").unwrap());
+    let frame = state.render();
+    assert_eq!(frame[mid * WIDTH + 103], BLACK);
+    assert_eq!(frame[mid * WIDTH + 118], WHITE);
+}
+
+// ===================
+// Text Input Widget Tests
+// ===================
+
+fn input(x: usize, y: usize, w: usize, var: &str) -> Command {
+    Command::Object(ObjectCommand::Input {
+        x,
+        y,
+        w,
+        var: var.to_string(),
+    })
+}
+
+fn type_str(state: &mut AppState, text: &str) {
+    for c in text.chars() {
+        state.objects.type_char(c, &mut state.vars);
+    }
+}
+
+#[test]
+fn test_parse_input() {
+    let cmd = input(10, 40, 200, "name");
+    assert_eq!(parse_command("input 10,40,200 name"), Some(cmd.clone()));
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("input 10,40 name"), None);
+    assert_eq!(parse_command("input 10,40,200"), None);
+    assert_eq!(parse_command("input 10,40,200 name extra"), None);
+}
+
+#[test]
+fn test_input_validation() {
+    assert!(validate(&input(10, 40, 200, "name")).is_ok());
+    assert!(validate(&input(10, 40, MIN_INPUT_WIDTH - 1, "name")).is_err());
+    assert!(validate(&input(WIDTH - 100, 40, 200, "name")).is_err());
+    assert!(validate(&input(10, CANVAS_BOTTOM - 5, 200, "name")).is_err());
+    assert!(validate(&input(10, 40, 200, "a b")).is_err());
+}
+
+#[test]
+fn test_input_focus_follows_presses() {
+    let mut state = AppState::new();
+    state.execute(&input(100, 100, 200, "name"));
+    state.execute(&button(100, 200, 80, 24, "OK", "ok"));
+    state.execute(&parse_command("obj text 100,300 \"Name\"").unwrap());
+    assert_eq!(state.vars.get("name"), Some(""));
+    assert_eq!(state.objects.focused(), None);
+
+    // Typing without focus goes nowhere
+    type_str(&mut state, "lost");
+    assert_eq!(state.vars.get("name"), Some(""));
+
+    state.objects.press(1, &mut state.vars);
+    assert_eq!(state.objects.focused(), Some(1));
+    state.objects.press(2, &mut state.vars);
+    assert_eq!(state.objects.focused(), Some(2));
+    // A focused button doesn't take typing
+    type_str(&mut state, "lost");
+    assert_eq!(state.vars.get("name"), Some(""));
+    state.objects.press(3, &mut state.vars);
+    assert_eq!(state.objects.focused(), None);
+
+    // Only widgets take focus
+    assert!(state.objects.set_focus(Some(1)));
+    assert!(state.objects.set_focus(Some(3)));
+    assert_eq!(state.objects.focused(), None);
+    assert!(!state.objects.set_focus(Some(3)));
+
+    // Deleting the focused input drops focus
+    state.objects.set_focus(Some(1));
+    state.execute(&parse_command("obj delete 1").unwrap());
+    assert_eq!(state.objects.focused(), None);
+}
+
+#[test]
+fn test_input_typing_backspace_and_submit() {
+    let mut state = AppState::new();
+    state.execute(&input(100, 100, 200, "name"));
+    state.objects.set_focus(Some(1));
+
+    type_str(&mut state, "Ada L\"\n\u{8}");
+    assert_eq!(state.vars.get("name"), Some("Ada L"));
+    assert!(state.objects.backspace(&mut state.vars));
+    assert_eq!(
+        state.execute(&parse_command("var get name").unwrap()),
+        Some("Ada ".to_string())
+    );
+    type_str(&mut state, "B");
+
+    let event = state.objects.submit(&mut state.vars).unwrap();
+    assert_eq!(event.to_string(), "submit name Ada B");
+    // The field clears for the next entry and keeps focus
+    assert_eq!(state.vars.get("name"), Some(""));
+    assert_eq!(state.objects.focused(), Some(1));
+    assert!(!state.objects.backspace(&mut state.vars));
+
+    state.objects.set_focus(None);
+    assert_eq!(state.objects.submit(&mut state.vars), None);
+}
+
+#[test]
+fn test_input_length_is_capped() {
+    let mut state = AppState::new();
+    state.execute(&input(100, 100, 200, "name"));
+    state.objects.set_focus(Some(1));
+    type_str(&mut state, &"x".repeat(MAX_INPUT_LEN + 10));
+    assert_eq!(state.vars.get("name").unwrap().len(), MAX_INPUT_LEN);
+}
+
+#[test]
+fn test_input_draws_text_and_caret_when_focused() {
+    let mut state = AppState::new();
+    state.execute(&input(100, 100, 200, "name"));
+    let (_, _, w, h) = state.objects.get(1).unwrap().bounds(&state.vars);
+    assert_eq!((w, h), (200, INPUT_HEIGHT));
+
+    let blank = state.render().into_owned();
+    assert_eq!(blank[100 * WIDTH + 100], DARK_GRAY);
+    assert_eq!(blank[105 * WIDTH + 150], WHITE);
+
+    state.objects.set_focus(Some(1));
+    let focused = state.render().into_owned();
+    assert_eq!(focused[100 * WIDTH + 100], BLACK);
+    // Caret just inside the left padding
+    let caret_x = 100 + INPUT_PADDING + 1;
+    assert_eq!(focused[(100 + INPUT_PADDING + 2) * WIDTH + caret_x], BLACK);
+
+    type_str(&mut state, "W");
+    let typed = state.render();
+    let caret_x = 100 + INPUT_PADDING + text_width("W") + 1;
+    assert_eq!(typed[(100 + INPUT_PADDING + 2) * WIDTH + caret_x], BLACK);
+}
+
+#[test]
+fn test_long_input_text_scrolls_to_the_end() {
+    let mut state = AppState::new();
+    state.execute(&input(100, 100, MIN_INPUT_WIDTH, "name"));
+    state.objects.set_focus(Some(1));
+    type_str(&mut state, "ABCDEFGHIJ");
+    // Nothing is drawn past the field's right edge
+    let frame = state.render();
+    for y in 100..100 + INPUT_HEIGHT {
+        assert_eq!(frame[y * WIDTH + 100 + MIN_INPUT_WIDTH], WHITE);
+    }
+}
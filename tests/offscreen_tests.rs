@@ -0,0 +1,97 @@
+use displai::*;
+
+// ===================
+// Offscreen Renderer Tests
+// ===================
+
+#[test]
+fn test_offscreen_renderer_draws_like_execute_command() {
+    let y = CANVAS_TOP + 100;
+    let commands = vec![
+        Command::Edge(Some(COLOR_PALETTE[2])),
+        Command::Size(3),
+        Command::Stroke {
+            x1: 50,
+            y1: y,
+            x2: 150,
+            y2: y,
+        },
+    ];
+
+    let mut renderer = OffscreenRenderer::new();
+    renderer.execute_all(&commands);
+
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    let mut edge = Some(0);
+    let mut fill = None;
+    let mut size = DEFAULT_BRUSH_SIZE;
+    for cmd in &commands {
+        let _ = execute_command(cmd, &mut buffer, &mut edge, &mut fill, &mut size);
+    }
+
+    assert_eq!(renderer.canvas().pixels(), &buffer[..]);
+    assert_eq!(renderer.canvas()[y * WIDTH + 100], COLOR_PALETTE[2]);
+}
+
+#[test]
+fn test_offscreen_renderer_records_one_timing_per_command() {
+    let mut renderer = OffscreenRenderer::new();
+    let durations = renderer.execute_all(&[
+        Command::Dot { x: 10, y: 100 },
+        Command::Clear,
+        Command::State,
+    ]);
+
+    assert_eq!(durations.len(), 3);
+    assert_eq!(renderer.timings().len(), 3);
+    assert_eq!(renderer.timings()[1].command, Command::Clear);
+    assert_eq!(renderer.timings()[1].duration, durations[1]);
+}
+
+#[test]
+fn test_offscreen_renderer_returns_responses() {
+    let mut renderer = OffscreenRenderer::new();
+    let (response, _) = renderer.execute(&Command::State);
+    assert_eq!(response, Some("edge:0 fill:none size:1".to_string()));
+}
+
+#[test]
+fn test_offscreen_summary_groups_by_command() {
+    let mut renderer = OffscreenRenderer::new();
+    renderer.execute_all(&[
+        Command::Dot { x: 10, y: 100 },
+        Command::Dot { x: 20, y: 100 },
+        Command::Clear,
+    ]);
+
+    let summary = renderer.summary();
+    assert_eq!(summary.len(), 2);
+    let dots = summary.iter().find(|s| s.name == "dot").unwrap();
+    assert_eq!(dots.count, 2);
+    assert_eq!(
+        dots.total,
+        renderer.timings()[0].duration + renderer.timings()[1].duration
+    );
+
+    // Sorted with the most expensive kind first
+    assert!(summary[0].total >= summary[1].total);
+}
+
+#[test]
+fn test_offscreen_reset_timings_keeps_canvas() {
+    let mut renderer = OffscreenRenderer::new();
+    let y = CANVAS_TOP + 10;
+    renderer.execute(&Command::Dot { x: 10, y });
+    renderer.reset_timings();
+
+    assert!(renderer.timings().is_empty());
+    assert_eq!(renderer.canvas()[y * WIDTH + 10], BLACK);
+}
+
+#[test]
+fn test_command_name_matches_protocol_verb() {
+    for cmd in CommandGenerator::new(11).take(200) {
+        let line = cmd.to_string();
+        assert_eq!(line.split_whitespace().next(), Some(cmd.name()));
+    }
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::run;
+use displai::*;
+
+// ===================
+// Palette Type Tests
+// ===================
+
+#[test]
+fn test_palette_set_get_reset() {
+    let mut palette = Palette::new();
+    assert_eq!(palette.colors(), &COLOR_PALETTE);
+    assert_eq!(palette.get(3), Some(COLOR_PALETTE[3]));
+    assert_eq!(palette.get(PALETTE_LEN), None);
+
+    palette.set(3, 0x123456).unwrap();
+    assert_eq!(palette.get(3), Some(0x123456));
+    assert_eq!(palette.index_of(0x123456), Some(3));
+    assert!(palette.set(PALETTE_LEN, 0x123456).is_err());
+
+    palette.reset();
+    assert_eq!(palette, Palette::default());
+}
+
+#[test]
+fn test_palette_summary_lists_every_slot() {
+    let summary = Palette::new().summary();
+    let slots: Vec<&str> = summary.split(' ').collect();
+    assert_eq!(slots.len(), PALETTE_LEN);
+    assert_eq!(slots[0], "#000000");
+    assert_eq!(slots[1], "#FFFFFF");
+}
+
+#[test]
+fn test_with_palette_restores_previous() {
+    let mut palette = Palette::new();
+    palette.set(0, 0xABCDEF).unwrap();
+    let inner = with_palette(&palette, || current_palette().get(0));
+    assert_eq!(inner, Some(0xABCDEF));
+    assert_eq!(current_palette(), Palette::new());
+}
+
+// ===================
+// Palette Command Tests
+// ===================
+
+#[test]
+fn test_parse_palette_commands() {
+    let set = Command::Palette(PaletteCommand::Set {
+        index: 2,
+        color: 0xFF8000,
+    });
+    assert_eq!(parse_command("palette set 2 #FF8000"), Some(set.clone()));
+    assert_eq!(parse_command(&set.to_string()), Some(set));
+    assert_eq!(
+        parse_command("palette get"),
+        Some(Command::Palette(PaletteCommand::Get))
+    );
+    assert_eq!(
+        parse_command("palette reset"),
+        Some(Command::Palette(PaletteCommand::Reset))
+    );
+    assert_eq!(parse_command("palette set 2"), None);
+    assert_eq!(parse_command("palette set x #FF8000"), None);
+    assert_eq!(parse_command("palette set 2 #FF80"), None);
+    assert_eq!(parse_command("palette get 2"), None);
+    assert_eq!(parse_command("palette"), None);
+}
+
+#[test]
+fn test_validate_palette_index() {
+    let set = |index| {
+        Command::Palette(PaletteCommand::Set {
+            index,
+            color: 0x112233,
+        })
+    };
+    assert!(validate(&set(PALETTE_LEN - 1)).is_ok());
+    assert!(validate(&set(PALETTE_LEN)).is_err());
+}
+
+#[test]
+fn test_palette_set_changes_what_indices_mean() {
+    let mut state = AppState::new();
+    assert_eq!(run(&mut state, "palette set 2 #123456"), None);
+
+    run(&mut state, "color 2");
+    assert_eq!(state.edge_color, Some(0x123456));
+    run(&mut state, "fill 2");
+    assert_eq!(state.fill_color, Some(0x123456));
+    // The state reply reports the color by its new index
+    assert!(run(&mut state, "state").unwrap().contains("edge:2"));
+
+    run(&mut state, "edge 3");
+    run(&mut state, "dot 100,100");
+    assert_eq!(state.canvas[100 * WIDTH + 100], COLOR_PALETTE[3]);
+}
+
+#[test]
+fn test_palette_get_and_reset() {
+    let mut state = AppState::new();
+    run(&mut state, "palette set 0 #ABCDEF");
+    let summary = run(&mut state, "palette get").unwrap();
+    assert!(summary.starts_with("#ABCDEF #FFFFFF"));
+
+    assert_eq!(run(&mut state, "palette reset"), None);
+    assert_eq!(state.palette, Palette::new());
+    run(&mut state, "color 0");
+    assert_eq!(state.edge_color, Some(COLOR_PALETTE[0]));
+}
+
+#[test]
+fn test_palette_does_not_recolor_canvas() {
+    let mut state = AppState::new();
+    run(&mut state, "color 2");
+    run(&mut state, "dot 100,100");
+    run(&mut state, "palette set 2 #123456");
+    assert_eq!(state.canvas[100 * WIDTH + 100], COLOR_PALETTE[2]);
+}
+
+#[test]
+fn test_toolbar_draws_current_palette() {
+    let mut palette = Palette::new();
+    palette.set(5, 0x123456).unwrap();
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    with_palette(&palette, || {
+        draw_bottom_toolbar(&mut buffer, None, None, 1, ToolMode::Brush)
+    });
+
+    // Center of the sixth swatch
+    let x = BUTTON_MARGIN + 5 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_SIZE / 2;
+    let y = CANVAS_BOTTOM + BUTTON_MARGIN + BUTTON_SIZE / 2;
+    assert_eq!(buffer[y * WIDTH + x], 0x123456);
+    assert_eq!(get_clicked_color_index_bottom(x, y), Some(5));
+}
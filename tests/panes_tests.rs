@@ -0,0 +1,909 @@
+mod common;
+
+use common::{pixel, run};
+use displai::*;
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_panes() {
+    let side_by_side = Panes {
+        cols: 2,
+        rows: 1,
+        margin: 0,
+    };
+    assert_eq!(
+        parse_command("panes 2x1"),
+        Some(Command::Panes(side_by_side))
+    );
+    let grid = Panes {
+        cols: 2,
+        rows: 2,
+        margin: 10,
+    };
+    assert_eq!(parse_command("panes 2x2 10"), Some(Command::Panes(grid)));
+    assert_eq!(
+        parse_command("panes off"),
+        Some(Command::Panes(Panes::default()))
+    );
+    for cmd in [
+        Command::Panes(side_by_side),
+        Command::Panes(grid),
+        Command::Panes(Panes::default()),
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(!cmd.is_mutating());
+    }
+    assert_eq!(Command::Panes(grid).to_string(), "panes 2x2 10");
+    assert_eq!(parse_command("panes"), None);
+    assert_eq!(parse_command("panes 2"), None);
+    assert_eq!(parse_command("panes 2x1 -3"), None);
+}
+
+#[test]
+fn test_parse_pane_prefix() {
+    let cmd = parse_command("@2 line 0,0 100,50").unwrap();
+    assert_eq!(
+        cmd,
+        Command::InPane(
+            2,
+            Box::new(Command::Line {
+                x1: 0,
+                y1: 0,
+                x2: 100,
+                y2: 50
+            })
+        )
+    );
+    assert_eq!(cmd.to_string(), "@2 line 0,0 100,50");
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(cmd.name(), "line");
+    assert!(cmd.is_mutating());
+    assert!(!parse_command("@1 state").unwrap().is_mutating());
+    assert_eq!(parse_command("@1"), None);
+    assert_eq!(parse_command("@x dot 1,1"), None);
+    assert_eq!(parse_command("@1 @2 dot 1,1"), None);
+}
+
+#[test]
+fn test_pane_prefix_errors_name_the_inner_command() {
+    assert_eq!(
+        parse_command_line("@1 rect 1,2"),
+        Err(CommandError::InvalidArguments("rect"))
+    );
+    assert_eq!(
+        parse_command_line("@1 frobnicate"),
+        Err(CommandError::Unknown("frobnicate".to_string()))
+    );
+}
+
+#[test]
+fn test_validate_panes() {
+    assert!(validate(&parse_command("panes 8x8 5").unwrap()).is_ok());
+    assert_eq!(
+        validate(&parse_command("panes 9x1").unwrap()),
+        Err("panes 9x1 is outside 1x1-8x8".to_string())
+    );
+    assert!(validate(&parse_command("panes 0x1").unwrap()).is_err());
+    assert!(validate(&parse_command("panes 2x1 101").unwrap()).is_err());
+    // 8 rows of 510 canvas pixels can't keep margins of 60
+    assert_eq!(
+        validate(&parse_command("panes 1x8 60").unwrap()),
+        Err("panes 1x8 with margin 60 don't fit the canvas".to_string())
+    );
+    assert!(validate(&parse_command("@1 dot 5,5").unwrap()).is_ok());
+    assert_eq!(
+        validate(&parse_command("@0 dot 5,5").unwrap()),
+        Err("panes are numbered from 1".to_string())
+    );
+    assert_eq!(
+        validate(&parse_command("@1 snapshot").unwrap()),
+        Err("@1 only prefixes drawing commands".to_string())
+    );
+}
+
+// ===================
+// Layout Tests
+// ===================
+
+#[test]
+fn test_default_layout_is_the_whole_canvas() {
+    let panes = Panes::default();
+    assert_eq!(panes.count(), 1);
+    assert_eq!(
+        panes.pane(1),
+        Some(Region::new(
+            0,
+            CANVAS_TOP,
+            WIDTH,
+            CANVAS_BOTTOM - CANVAS_TOP
+        ))
+    );
+    assert_eq!(panes.pane(0), None);
+    assert_eq!(panes.pane(2), None);
+}
+
+#[test]
+fn test_panes_are_numbered_across_then_down() {
+    let panes = Panes {
+        cols: 2,
+        rows: 2,
+        margin: 0,
+    };
+    assert_eq!(panes.pane(1), Some(Region::new(0, 30, 400, 255)));
+    assert_eq!(panes.pane(2), Some(Region::new(400, 30, 400, 255)));
+    assert_eq!(panes.pane(3), Some(Region::new(0, 285, 400, 255)));
+    assert_eq!(panes.pane(4), Some(Region::new(400, 285, 400, 255)));
+    assert_eq!(panes.pane(5), None);
+}
+
+#[test]
+fn test_margins_surround_and_separate_panes() {
+    let panes = Panes {
+        cols: 3,
+        rows: 1,
+        margin: 10,
+    };
+    // 800 - 4 * 10 = 760 pixels shared by three panes
+    assert_eq!(panes.pane(1), Some(Region::new(10, 40, 253, 490)));
+    assert_eq!(panes.pane(2), Some(Region::new(273, 40, 253, 490)));
+    assert_eq!(panes.pane(3), Some(Region::new(536, 40, 254, 490)));
+}
+
+// ===================
+// Execution Tests
+// ===================
+
+#[test]
+fn test_panes_command_sets_layout() {
+    let mut state = AppState::new();
+    assert_eq!(run(&mut state, "panes 2x1 4"), None);
+    assert_eq!(state.panes.count(), 2);
+    assert_eq!(state.pane(2), Some(Region::new(402, 34, 394, 502)));
+    assert_eq!(
+        run(&mut state, "panes 1x8 60"),
+        Some("error: panes 1x8 with margin 60 don't fit the canvas".to_string())
+    );
+    assert_eq!(state.panes.count(), 2);
+    run(&mut state, "panes off");
+    assert_eq!(state.panes, Panes::default());
+}
+
+#[test]
+fn test_pane_coordinates_start_at_its_corner() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@2 dot 10,5");
+    assert_eq!(pixel(&state, 410, CANVAS_TOP + 5), BLACK);
+    run(&mut state, "@1 dot 10,5");
+    assert_eq!(pixel(&state, 10, CANVAS_TOP + 5), BLACK);
+}
+
+#[test]
+fn test_pane_drawing_is_clipped_to_the_pane() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "size 5");
+    run(&mut state, "@1 line 0,100 799,100");
+    assert_eq!(pixel(&state, 399, CANVAS_TOP + 100), BLACK);
+    assert_eq!(pixel(&state, 400, CANVAS_TOP + 100), WHITE);
+    run(&mut state, "fill #00FF00");
+    run(&mut state, "@2 circle 0,200 100");
+    assert_eq!(pixel(&state, 450, CANVAS_TOP + 200), 0x00FF00);
+    assert_eq!(pixel(&state, 350, CANVAS_TOP + 200), WHITE);
+}
+
+#[test]
+fn test_pane_clear_whitens_only_its_pane() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "rect 100,100 700,200");
+    assert_eq!(run(&mut state, "@1 clear"), None);
+    assert_eq!(pixel(&state, 100, 150), WHITE);
+    assert_eq!(pixel(&state, 700, 150), BLACK);
+    // The shapes drawn so far are kept for export
+    assert_eq!(state.display_list.len(), 1);
+}
+
+#[test]
+fn test_pane_command_errors() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, "@2 dot 1,1"),
+        Some("error: no pane 2 in a 1x1 layout".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "@1 state"),
+        Some("error: @1 only prefixes drawing commands".to_string())
+    );
+    // Without a layout, pane 1 is the whole canvas
+    run(&mut state, "@1 dot 5,5");
+    assert_eq!(pixel(&state, 5, CANVAS_TOP + 5), BLACK);
+}
+
+#[test]
+fn test_placed_moves_pane_commands() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    let cmd = parse_command("@2 rect 0,0 10,10").unwrap();
+    assert_eq!(
+        *state.placed(&cmd),
+        Command::Rect {
+            x1: 400,
+            y1: 30,
+            x2: 410,
+            y2: 40
+        }
+    );
+    let plain = parse_command("rect 0,0 10,10").unwrap();
+    assert_eq!(*state.placed(&plain), plain);
+}
+
+#[test]
+fn test_translated_moves_every_point() {
+    let polyline = parse_command("polyline 1,2 3,4:red:2").unwrap();
+    assert_eq!(
+        polyline.translated(10, 20).to_string(),
+        "polyline 11,22 13,24:2:2"
+    );
+    assert_eq!(
+        parse_command("pie 5,6 7 0 90").unwrap().translated(1, 1),
+        parse_command("pie 6,7 7 0 90").unwrap()
+    );
+    assert_eq!(Command::Undo.translated(1, 1), Command::Undo);
+}
+
+// ===================
+// Scale and Plot Tests
+// ===================
+
+#[test]
+fn test_parse_scale_and_plot() {
+    let scale = Command::Scale(Some(PaneScale {
+        x0: 0.0,
+        y0: -1.5,
+        x1: 10.0,
+        y1: 1.5,
+        log_x: false,
+        log_y: false,
+        axes: true,
+    }));
+    assert_eq!(
+        parse_command("scale 0,-1.5 10,1.5 axes"),
+        Some(scale.clone())
+    );
+    assert_eq!(scale.to_string(), "scale 0,-1.5 10,1.5 axes");
+    let plot = Command::Plot(vec![(0.0, 0.0), (2.5, -1.0)]);
+    assert_eq!(parse_command("plot 0,0 2.5,-1"), Some(plot.clone()));
+    for cmd in [scale, Command::Scale(None), plot] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(validate(&cmd).is_ok());
+    }
+    assert!(parse_command("plot 1,2").unwrap().is_mutating());
+    assert!(!parse_command("scale 0,0 1,1").unwrap().is_mutating());
+    assert!(parse_command("scale 0,0 1,1 axes").unwrap().is_mutating());
+    assert_eq!(
+        parse_command("@2 scale 0,0 1,1"),
+        Some(Command::InPane(
+            2,
+            Box::new(parse_command("scale 0,0 1,1").unwrap())
+        ))
+    );
+
+    // Invalid formats
+    assert_eq!(parse_command("scale 0,0"), None);
+    assert_eq!(parse_command("scale 0,0 1,1 grid"), None);
+    assert_eq!(parse_command("plot"), None);
+    assert_eq!(parse_command("plot 1"), None);
+}
+
+#[test]
+fn test_validate_scale_and_plot() {
+    assert_eq!(
+        validate(&parse_command("scale 0,0 10,0").unwrap()),
+        Err("scale 0,0 10,0 is empty on an axis".to_string())
+    );
+    assert_eq!(
+        validate(&parse_command("scale 0,0 inf,1").unwrap()),
+        Err("scale bounds must be numbers".to_string())
+    );
+    assert_eq!(
+        validate(&parse_command("plot 1,nan").unwrap()),
+        Err("plot points must be numbers".to_string())
+    );
+    assert!(validate(&parse_command("@2 scale 0,0 1,1").unwrap()).is_ok());
+}
+
+#[test]
+fn test_ticks_fall_on_round_values() {
+    let labels = |from, to| -> Vec<String> { ticks(from, to).into_iter().map(|t| t.1).collect() };
+    assert_eq!(labels(0.0, 10.0), ["0", "2", "4", "6", "8", "10"]);
+    assert_eq!(labels(-1.0, 1.0), ["-1.0", "-0.5", "0.0", "0.5", "1.0"]);
+    // Either order, and ends that aren't round
+    assert_eq!(labels(730.0, 95.0), ["200", "400", "600"]);
+    assert!(ticks(1.0, 1.0).is_empty());
+}
+
+#[test]
+fn test_tick_labels_abbreviate_large_values() {
+    let labels = |from, to| -> Vec<String> { ticks(from, to).into_iter().map(|t| t.1).collect() };
+    // Below 10,000 values are written out
+    assert_eq!(
+        labels(0.0, 5000.0),
+        ["0", "1000", "2000", "3000", "4000", "5000"]
+    );
+    assert_eq!(labels(0.0, 10000.0), ["0", "2k", "4k", "6k", "8k", "10k"]);
+    assert_eq!(
+        labels(1.2e6, 1.7e6),
+        ["1.2M", "1.3M", "1.4M", "1.5M", "1.6M", "1.7M"]
+    );
+    assert_eq!(labels(-3.4e9, 0.0), ["-3G", "-2G", "-1G", "0"]);
+    assert_eq!(labels(0.0, 2e12)[1], "0.5T");
+    // Suffixes that would need many decimals give way to full values
+    assert_eq!(labels(1e6, 1e6 + 10.0)[1], "1000002");
+}
+
+#[test]
+fn test_tick_labels_use_scientific_notation_at_the_extremes() {
+    let labels = |from, to| -> Vec<String> { ticks(from, to).into_iter().map(|t| t.1).collect() };
+    assert_eq!(
+        labels(0.0, 5e15),
+        ["0", "1e15", "2e15", "3e15", "4e15", "5e15"]
+    );
+    assert_eq!(
+        labels(0.0, 5e-5),
+        ["0", "1e-5", "2e-5", "3e-5", "4e-5", "5e-5"]
+    );
+    assert_eq!(labels(1e-4, 3e-4)[1], "1.5e-4");
+}
+
+#[test]
+fn test_axis_labels_do_not_overlap() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 6x1");
+    // Six ticks on a 45px axis: some must go unlabelled
+    run(&mut state, "@1 scale 1.2e6,0 1.7e6,10 axes");
+    let pane = state.pane(1).unwrap();
+    let scale = state.scales[&1];
+    let area = scale.plot_area(pane).unwrap();
+    let label_rows = area.y + area.height + 5..area.y + area.height + 5 + GLYPH_HEIGHT;
+    let inked: Vec<usize> = (0..pane.x + pane.width)
+        .filter(|&x| {
+            label_rows
+                .clone()
+                .any(|y| state.canvas[y * WIDTH + x] == DARK_GRAY)
+        })
+        .collect();
+    // Labels are a glyph apart; glyphs within one are closer
+    let labels = 1 + inked.windows(2).filter(|w| w[1] - w[0] > 4).count();
+    assert!((2..6).contains(&labels), "{} labels", labels);
+}
+
+#[test]
+fn test_plot_maps_data_to_the_pane() {
+    let mut state = AppState::new();
+    run(&mut state, "edge #FF0000");
+    run(&mut state, "size 1");
+    assert_eq!(run(&mut state, "scale 0,0 10,10"), None);
+    run(&mut state, "plot 0,0 10,10");
+    // y grows upward: (0, 0) is the bottom-left corner of the canvas
+    assert_eq!(pixel(&state, 0, 539), 0xFF0000);
+    assert_eq!(pixel(&state, 799, CANVAS_TOP), 0xFF0000);
+    assert_eq!(pixel(&state, 0, CANVAS_TOP), WHITE);
+    // Plots are lines, kept for export
+    assert_eq!(state.display_list.len(), 1);
+}
+
+#[test]
+fn test_each_pane_has_its_own_scale() {
+    let mut state = AppState::new();
+    run(&mut state, "size 1");
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@1 scale 0,0 1,1");
+    run(&mut state, "@2 scale -100,0 100,1");
+    run(&mut state, "@2 plot 0,0 0,1");
+    // x = 0 is the middle of pane 2, not of pane 1
+    assert!(pixel(&state, 600, 300) == BLACK || pixel(&state, 599, 300) == BLACK);
+    assert_eq!(pixel(&state, 200, 300), WHITE);
+    assert_eq!(
+        run(&mut state, "@2 plot 1,1"),
+        None,
+        "Outside the range: nothing to draw"
+    );
+    // A new layout starts without scales
+    run(&mut state, "panes 1x2");
+    assert!(state.scales.is_empty());
+    assert_eq!(
+        run(&mut state, "@2 plot 0,0"),
+        Some("error: pane 2 has no scale".to_string())
+    );
+}
+
+#[test]
+fn test_plot_breaks_where_data_leaves_the_range() {
+    let mut state = AppState::new();
+    run(&mut state, "scale 0,0 10,10");
+    let runs = state
+        .plot_runs(1, &[(5.0, 5.0), (15.0, 5.0), (5.0, 6.0)])
+        .unwrap();
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0][0], (400, 285));
+    assert_eq!(runs[0][1], (799, 285));
+    assert_eq!(runs[1].last(), Some(&(400, 234)));
+    // Non-numbers break the line too
+    let runs = state
+        .plot_runs(1, &[(1.0, 1.0), (2.0, f64::NAN), (3.0, 3.0), (4.0, 4.0)])
+        .unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(state.plot_runs(2, &[(1.0, 1.0)]), None);
+}
+
+#[test]
+fn test_axes_frame_the_plot_area() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    assert_eq!(run(&mut state, "@2 scale 0,0 10,10 axes"), None);
+    let pane = state.pane(2).unwrap();
+    let area = PaneScale {
+        x0: 0.0,
+        y0: 0.0,
+        x1: 10.0,
+        y1: 10.0,
+        log_x: false,
+        log_y: false,
+        axes: true,
+    }
+    .plot_area(pane)
+    .unwrap();
+    assert_eq!(area, Region::new(440, 38, 352, 488));
+    // The y axis left of the area, the x axis below it
+    assert_eq!(pixel(&state, 439, 200), DARK_GRAY);
+    assert_eq!(pixel(&state, 600, 38 + 488), DARK_GRAY);
+    // Labels sit in the margin, none spill into pane 1
+    let rows = || state.canvas.chunks(WIDTH).skip(CANVAS_TOP).take(510);
+    assert!(rows().any(|row| row[400..436].contains(&DARK_GRAY)));
+    assert!(rows().all(|row| !row[..400].contains(&DARK_GRAY)));
+
+    // Plots land inside the axes
+    run(&mut state, "@2 plot 0,0");
+    assert_eq!(pixel(&state, 440, 525), BLACK);
+    // Clearing the pane keeps its axes
+    run(&mut state, "@2 clear");
+    assert_eq!(pixel(&state, 439, 200), DARK_GRAY);
+    assert_eq!(pixel(&state, 440, 525), WHITE);
+}
+
+#[test]
+fn test_scale_errors() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, "plot 1,1"),
+        Some("error: pane 1 has no scale".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "scale 5,0 5,1"),
+        Some("error: scale 5,0 5,1 is empty on an axis".to_string())
+    );
+    run(&mut state, "panes 8x8 40");
+    assert_eq!(
+        run(&mut state, "@3 scale 0,0 1,1 axes"),
+        Some("error: pane 3 is too small for axes".to_string())
+    );
+    // Without axes the whole pane is plotted in
+    assert_eq!(run(&mut state, "@3 scale 0,0 1,1"), None);
+    assert_eq!(run(&mut state, "@3 scale off"), None);
+    assert!(state.scales.is_empty());
+}
+
+#[test]
+fn test_placed_plot_is_the_lines_it_draws() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@2 scale 0,0 10,10");
+    let cmd = parse_command("@2 plot 0,0 10,10").unwrap();
+    assert_eq!(state.placed(&cmd).to_string(), "polyline 400,539 799,30");
+    let axes = parse_command("@2 scale 0,0 1,1 axes").unwrap();
+    assert_eq!(
+        *state.placed(&axes),
+        Command::Rect {
+            x1: 400,
+            y1: 30,
+            x2: 799,
+            y2: 539
+        }
+    );
+}
+
+// ===================
+// Log Scale Tests
+// ===================
+
+#[test]
+fn test_parse_log_scale() {
+    let scale = PaneScale {
+        x0: 1.0,
+        y0: 0.001,
+        x1: 1000.0,
+        y1: 1.0,
+        log_x: true,
+        log_y: true,
+        axes: true,
+    };
+    let cmd = Command::Scale(Some(scale));
+    assert_eq!(
+        parse_command("scale 1,0.001 1000,1 logx logy axes"),
+        Some(cmd.clone())
+    );
+    // Options in any order
+    assert_eq!(
+        parse_command("scale 1,0.001 1000,1 axes logy logx"),
+        Some(cmd.clone())
+    );
+    assert_eq!(cmd.to_string(), "scale 1,0.001 1000,1 logx logy axes");
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert!(validate(&cmd).is_ok());
+
+    // Invalid formats
+    assert_eq!(parse_command("scale 1,1 10,10 logx logx"), None);
+    assert_eq!(parse_command("scale 1,1 10,10 logz"), None);
+}
+
+#[test]
+fn test_validate_log_scale_needs_positive_bounds() {
+    assert_eq!(
+        validate(&parse_command("scale 0,1 100,2 logx").unwrap()),
+        Err("scale 0,1 100,2 logx has a log x axis reaching 0 or below".to_string())
+    );
+    assert_eq!(
+        validate(&parse_command("scale 1,-1 100,2 logy").unwrap()),
+        Err("scale 1,-1 100,2 logy has a log y axis reaching 0 or below".to_string())
+    );
+    // Linear axes may still reach below zero
+    assert!(validate(&parse_command("scale 1,-1 100,2 logx").unwrap()).is_ok());
+}
+
+#[test]
+fn test_log_scale_spaces_decades_equally() {
+    let scale = PaneScale {
+        x0: 1.0,
+        y0: 1.0,
+        x1: 1000.0,
+        y1: 1e6,
+        log_x: true,
+        log_y: true,
+        axes: false,
+    };
+    let area = Region::new(0, 40, 301, 301);
+    assert_eq!(scale.to_pixel(area, 1.0, 1.0), (0.0, 340.0));
+    assert_eq!(scale.to_pixel(area, 10.0, 1e6).0.round(), 100.0);
+    assert_eq!(scale.to_pixel(area, 100.0, 1e3).0.round(), 200.0);
+    assert_eq!(scale.to_pixel(area, 100.0, 1e3).1.round(), 190.0);
+    // Nothing to place at or below zero
+    assert!(scale.to_pixel(area, 0.0, 10.0).0.is_nan());
+    assert!(scale.to_pixel(area, 10.0, -5.0).1.is_nan());
+}
+
+#[test]
+fn test_log_ticks() {
+    let labels =
+        |from, to| -> Vec<String> { log_ticks(from, to).into_iter().map(|t| t.1).collect() };
+    assert_eq!(labels(1.0, 1e5), ["1", "10", "100", "1000", "10k", "100k"]);
+    // Narrow ranges get 2s and 5s too
+    assert_eq!(
+        labels(0.01, 1.0),
+        ["0.01", "0.02", "0.05", "0.1", "0.2", "0.5", "1"]
+    );
+    assert_eq!(labels(30.0, 300.0), ["50", "100", "200"]);
+    // Wide ranges skip decades
+    assert_eq!(labels(1.0, 1e12), ["1", "1000", "1M", "1G", "1T"]);
+    assert!(log_ticks(0.0, 10.0).is_empty());
+}
+
+#[test]
+fn test_plot_on_log_scale() {
+    let mut state = AppState::new();
+    run(&mut state, "edge #FF0000");
+    run(&mut state, "size 1");
+    run(&mut state, "scale 0,1 10,1000 logy");
+    run(&mut state, "plot 0,1 5,10 10,1000");
+    // A third of the way up the canvas at 10
+    assert_eq!(pixel(&state, 400, 539 - 170), 0xFF0000);
+    // Points at or below zero break the line
+    let runs = state
+        .plot_runs(1, &[(1.0, 10.0), (2.0, 0.0), (3.0, 10.0), (4.0, 100.0)])
+        .unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0][0].0, 240);
+}
+
+// ===================
+// Time Series Tests
+// ===================
+
+#[test]
+fn test_parse_tsplot() {
+    let cmd = Command::TsPlot {
+        pane: 2,
+        value: -1.5,
+    };
+    assert_eq!(parse_command("tsplot 2 -1.5"), Some(cmd.clone()));
+    assert_eq!(cmd.to_string(), "tsplot 2 -1.5");
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(cmd.name(), "tsplot");
+    assert!(cmd.is_mutating());
+    assert!(!cmd.runs_in_pane());
+    assert!(validate(&cmd).is_ok());
+
+    // Invalid formats
+    assert_eq!(parse_command("tsplot 1"), None);
+    assert_eq!(parse_command("tsplot x 1"), None);
+    assert_eq!(parse_command("tsplot 1 2 3"), None);
+    assert_eq!(parse_command("tsplot 1 high"), None);
+}
+
+#[test]
+fn test_validate_tsplot() {
+    let tsplot = |pane, value| Command::TsPlot { pane, value };
+    assert_eq!(
+        validate(&tsplot(0, 1.0)),
+        Err("panes are numbered from 1".to_string())
+    );
+    assert_eq!(
+        validate(&tsplot(1, f64::NAN)),
+        Err("tsplot value must be a number".to_string())
+    );
+}
+
+#[test]
+fn test_tsplot_steps_right_joining_samples() {
+    let mut state = AppState::new();
+    run(&mut state, "scale 0,0 10,10");
+    // Ten samples fit across, 80 pixels apart; the first is a point
+    assert_eq!(run(&mut state, "tsplot 1 10"), None);
+    assert_eq!(pixel(&state, 0, 30), BLACK);
+    assert_eq!(pixel(&state, 1, 31), WHITE);
+    run(&mut state, "tsplot 1 0");
+    assert_eq!(pixel(&state, 80, 539), BLACK);
+    assert_eq!(pixel(&state, 40, 285), BLACK);
+    assert_eq!(state.series[&1].count, 2);
+    // Drawn pixels only: nothing for SVG export
+    assert!(state.display_list.is_empty());
+}
+
+#[test]
+fn test_tsplot_scrolls_when_full() {
+    let mut state = AppState::new();
+    run(&mut state, "scale 0,0 10,10");
+    run(&mut state, "tsplot 1 10");
+    for _ in 0..9 {
+        run(&mut state, "tsplot 1 0");
+    }
+    assert_eq!(pixel(&state, 0, 30), BLACK);
+    assert_eq!(pixel(&state, 720, 539), BLACK);
+
+    // The eleventh moves everything a step left and lands in the last slot
+    run(&mut state, "tsplot 1 10");
+    assert_eq!(pixel(&state, 0, 30), WHITE);
+    assert_eq!(pixel(&state, 0, 539), BLACK);
+    assert_eq!(pixel(&state, 640, 539), BLACK);
+    assert_eq!(pixel(&state, 720, 30), BLACK);
+    assert!((721..WIDTH).all(|x| pixel(&state, x, 30) == WHITE));
+}
+
+#[test]
+fn test_tsplot_stays_inside_the_plot_area() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@2 scale 0,0 4,1 axes");
+    let axes = state.canvas.pixels().to_vec();
+    run(&mut state, "size 5");
+    for value in [0.0, 2.0, -1.0, 0.5, 1.0, 0.0, 1.0] {
+        run(&mut state, &format!("tsplot 2 {}", value));
+    }
+    let area = state.scales[&2].plot_area(state.pane(2).unwrap()).unwrap();
+    for y in CANVAS_TOP..canvas_bottom() {
+        for x in 0..WIDTH {
+            let inside = (area.x..area.x + area.width).contains(&x)
+                && (area.y..area.y + area.height).contains(&y);
+            if !inside {
+                assert_eq!(state.canvas[y * WIDTH + x], axes[y * WIDTH + x]);
+            }
+        }
+    }
+    assert_ne!(state.canvas.pixels(), &axes[..]);
+}
+
+#[test]
+fn test_tsplot_starts_over() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@1 scale 0,0 10,10");
+    run(&mut state, "@2 scale 0,0 10,10");
+    run(&mut state, "tsplot 1 5");
+    run(&mut state, "tsplot 2 5");
+    // A new range, or clearing the pane, drops its series
+    run(&mut state, "@1 scale 0,0 20,10");
+    assert!(!state.series.contains_key(&1));
+    run(&mut state, "tsplot 1 5");
+    run(&mut state, "@2 clear");
+    assert!(!state.series.contains_key(&2));
+    assert_eq!(state.series[&1].count, 1);
+    run(&mut state, "clear");
+    assert!(state.series.is_empty());
+}
+
+#[test]
+fn test_tsplot_errors() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, "tsplot 1 5"),
+        Some("error: pane 1 has no scale".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "tsplot 3 5"),
+        Some("error: no pane 3 in a 1x1 layout".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "@1 tsplot 1 5"),
+        Some("error: @1 only prefixes drawing commands".to_string())
+    );
+    assert!(state.series.is_empty());
+}
+
+#[test]
+fn test_placed_tsplot_is_the_area_it_scrolls() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@2 scale 0,0 10,10");
+    let cmd = parse_command("tsplot 2 5").unwrap();
+    assert_eq!(
+        *state.placed(&cmd),
+        Command::Rect {
+            x1: 400,
+            y1: 30,
+            x2: 799,
+            y2: 539
+        }
+    );
+    let unscaled = parse_command("tsplot 1 5").unwrap();
+    assert_eq!(*state.placed(&unscaled), unscaled);
+}
+
+#[test]
+fn test_parse_hold_and_flip() {
+    let hold = Command::Hold { pane: 2, on: true };
+    assert_eq!(parse_command("hold 2 on"), Some(hold.clone()));
+    assert_eq!(hold.to_string(), "hold 2 on");
+    assert_eq!(hold.name(), "hold");
+    let release = Command::Hold { pane: 1, on: false };
+    assert_eq!(parse_command("hold 1 off"), Some(release.clone()));
+    assert_eq!(parse_command(&release.to_string()), Some(release));
+    let flip = Command::Flip(3);
+    assert_eq!(parse_command("flip 3"), Some(flip.clone()));
+    assert_eq!(flip.to_string(), "flip 3");
+    assert_eq!(flip.name(), "flip");
+    // They change what is shown, not the canvas
+    assert!(!hold.is_mutating());
+    assert!(!flip.is_mutating());
+    assert!(!hold.runs_in_pane());
+
+    // Invalid formats
+    assert_eq!(parse_command("hold 1"), None);
+    assert_eq!(parse_command("hold 1 maybe"), None);
+    assert_eq!(parse_command("hold x on"), None);
+    assert_eq!(parse_command("flip"), None);
+    assert_eq!(parse_command("flip 1 2"), None);
+
+    assert_eq!(
+        validate(&Command::Flip(0)),
+        Err("panes are numbered from 1".to_string())
+    );
+    assert_eq!(
+        validate(&Command::Hold { pane: 0, on: true }),
+        Err("panes are numbered from 1".to_string())
+    );
+}
+
+#[test]
+fn test_held_pane_shows_its_last_flip() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "@2 rect 10,10 50,50");
+    assert_eq!(run(&mut state, "hold 2 on"), None);
+    let shown = state.render().pixels().to_vec();
+
+    // Clearing and redrawing lands on the canvas, out of sight
+    run(&mut state, "@2 clear");
+    assert_eq!(state.render().pixels(), &shown[..]);
+    run(&mut state, "@2 rect 100,100 150,150");
+    assert_eq!(pixel(&state, 500, 130), BLACK);
+    assert_eq!(state.render()[130 * WIDTH + 500], WHITE);
+    assert_eq!(state.render()[40 * WIDTH + 410], BLACK);
+
+    // A flip shows the whole redraw at once, and the pane stays held
+    assert_eq!(run(&mut state, "flip 2"), None);
+    assert_eq!(state.render().pixels(), state.canvas.pixels());
+    run(&mut state, "@2 clear");
+    assert_eq!(state.render()[130 * WIDTH + 500], BLACK);
+}
+
+#[test]
+fn test_held_pane_leaves_the_rest_of_the_canvas_live() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x1");
+    run(&mut state, "hold 2 on");
+    run(&mut state, "@1 line 0,0 100,0");
+    run(&mut state, "@2 line 0,0 100,0");
+    assert_eq!(state.render()[30 * WIDTH + 50], BLACK);
+    assert_eq!(state.render()[30 * WIDTH + 450], WHITE);
+    // Toolbar and title bar are outside every pane
+    run(&mut state, "color 1");
+    assert!(state.render()[..30 * WIDTH] == state.canvas[..30 * WIDTH]);
+}
+
+#[test]
+fn test_releasing_a_hold_shows_the_pane_as_drawn() {
+    let mut state = AppState::new();
+    run(&mut state, "hold 1 on");
+    run(&mut state, "line 0,100 799,100");
+    // Holding again keeps the first view
+    run(&mut state, "hold 1 on");
+    assert_eq!(state.render()[100 * WIDTH + 400], WHITE);
+    assert_eq!(run(&mut state, "hold 1 off"), None);
+    assert!(state.held.is_empty());
+    assert!(!state.has_overlays());
+    assert_eq!(state.render()[100 * WIDTH + 400], BLACK);
+    // Releasing a pane that isn't held does nothing
+    assert_eq!(run(&mut state, "hold 1 off"), None);
+}
+
+#[test]
+fn test_new_layout_releases_held_panes() {
+    let mut state = AppState::new();
+    run(&mut state, "panes 2x2");
+    run(&mut state, "hold 4 on");
+    run(&mut state, "hold 1 on");
+    assert_eq!(state.held.len(), 2);
+    run(&mut state, "panes off");
+    assert!(state.held.is_empty());
+}
+
+#[test]
+fn test_hold_errors() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, "hold 2 on"),
+        Some("error: no pane 2 in a 1x1 layout".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "flip 1"),
+        Some("error: pane 1 isn't held".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "@1 flip 1"),
+        Some("error: @1 only prefixes drawing commands".to_string())
+    );
+    assert!(state.held.is_empty());
+}
+
+#[test]
+fn test_held_pane_region() {
+    let mut canvas = vec![WHITE; 6 * 4];
+    canvas[6 + 2] = BLACK;
+    let held = HeldPane::capture(&canvas, 6, Region::new(1, 1, 3, 2));
+    assert_eq!(held.pixels, vec![WHITE, BLACK, WHITE, WHITE, WHITE, WHITE]);
+
+    let mut frame = vec![RED; 6 * 4];
+    held.draw(&mut frame, 6);
+    assert_eq!(frame[6 + 2], BLACK);
+    assert_eq!(frame[2 * 6 + 3], WHITE);
+    assert_eq!(frame[6], RED);
+    assert_eq!(frame[3 * 6 + 2], RED);
+
+    // A pane that no longer fits the frame isn't drawn
+    let mut small = vec![RED; 3 * 2];
+    held.draw(&mut small, 3);
+    assert!(small.iter().all(|&p| p == RED));
+}
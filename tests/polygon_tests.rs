@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use displai::*;
+
+fn ms(start: Instant, ms: u64) -> Instant {
+    start + Duration::from_millis(ms)
+}
+
+// ===================
+// Polygon Tool Tests
+// ===================
+
+#[test]
+fn test_double_click_closes_polygon() {
+    let start = Instant::now();
+    let mut polygon = PolygonBuilder::new();
+    assert_eq!(polygon.click(100, 100, start), None);
+    assert_eq!(polygon.click(200, 100, ms(start, 1000)), None);
+    assert_eq!(polygon.click(150, 200, ms(start, 2000)), None);
+    assert_eq!(polygon.vertices(), &[(100, 100), (200, 100), (150, 200)]);
+
+    // The second click of the double-click lands near the last vertex
+    assert_eq!(
+        polygon.click(151, 202, ms(start, 2200)),
+        Some(vec![(100, 100), (200, 100), (150, 200)])
+    );
+    assert!(polygon.is_empty());
+}
+
+#[test]
+fn test_slow_or_distant_clicks_add_vertices() {
+    let start = Instant::now();
+    let mut polygon = PolygonBuilder::new();
+    polygon.click(100, 100, start);
+    // Same spot, but too late to be a double-click
+    polygon.click(100, 100, ms(start, DOUBLE_CLICK_MS + 1));
+    // Quick, but too far away
+    polygon.click(
+        100 + DOUBLE_CLICK_DISTANCE + 1,
+        100,
+        ms(start, DOUBLE_CLICK_MS + 50),
+    );
+    assert_eq!(polygon.vertices().len(), 3);
+}
+
+#[test]
+fn test_double_click_needs_three_vertices() {
+    let start = Instant::now();
+    let mut polygon = PolygonBuilder::new();
+    polygon.click(100, 100, start);
+    polygon.click(200, 100, ms(start, 1000));
+    assert_eq!(polygon.click(200, 100, ms(start, 1100)), None);
+    assert_eq!(polygon.vertices(), &[(100, 100), (200, 100)]);
+
+    // Placing continues, and a later double-click closes it
+    polygon.click(150, 200, ms(start, 2000));
+    assert!(polygon.click(150, 200, ms(start, 2100)).is_some());
+}
+
+#[test]
+fn test_cancel_discards_vertices() {
+    let start = Instant::now();
+    let mut polygon = PolygonBuilder::new();
+    polygon.click(100, 100, start);
+    polygon.cancel();
+    assert!(polygon.is_empty());
+    // A click right after cancelling starts a new polygon rather than closing one
+    assert_eq!(polygon.click(100, 100, ms(start, 100)), None);
+    assert_eq!(polygon.vertices(), &[(100, 100)]);
+}
+
+#[test]
+fn test_preview_draws_edges_and_cursor_line() {
+    let start = Instant::now();
+    let mut polygon = PolygonBuilder::new();
+    polygon.click(100, 100, start);
+    polygon.click(200, 100, ms(start, 1000));
+
+    let mut frame = vec![WHITE; WIDTH * HEIGHT];
+    polygon.draw_preview(&mut frame, Some((200, 200)), BLACK);
+    assert_eq!(frame[100 * WIDTH + 150], BLACK, "Placed edge");
+    assert_eq!(frame[150 * WIDTH + 200], BLACK, "Edge to the cursor");
+    assert_eq!(frame[150 * WIDTH + 150], WHITE, "Not closed yet");
+}
+
+// ===================
+// Polygon State Tests
+// ===================
+
+#[test]
+fn test_state_polygon_matches_command() {
+    let points = vec![(100, 100), (300, 120), (250, 300), (120, 250)];
+    let mut from_tool = AppState::new();
+    from_tool.fill_color = Some(COLOR_PALETTE[4]);
+    from_tool.brush_size = 3;
+    let mut from_command = from_tool.clone();
+
+    from_tool.draw_polygon(&points);
+    from_command.execute(&Command::Polygon(points));
+    assert_eq!(from_tool.canvas, from_command.canvas);
+    assert!(from_tool.canvas.contains(&COLOR_PALETTE[4]));
+}
+
+#[test]
+fn test_polygon_casts_shadow() {
+    let points = vec![(100, 100), (200, 100), (150, 200)];
+    let mut state = AppState::new();
+    state.execute(&Command::Shadow(Some(Shadow {
+        dx: 10,
+        dy: 10,
+        color: DARK_GRAY,
+        blur: 0,
+    })));
+    state.execute(&Command::Polygon(points));
+
+    // Below the bottom vertex, where only the offset shadow reaches
+    assert_eq!(state.canvas[210 * WIDTH + 160], DARK_GRAY);
+    assert_eq!(state.canvas[150 * WIDTH + 150], WHITE, "Unfilled interior");
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use common::{run, scratch};
+use displai::*;
+
+fn open_tile(dir: &std::path::Path, row: usize, col: usize) -> image::RgbImage {
+    image::open(dir.join(tile_name(row, col)))
+        .expect("Should open")
+        .to_rgb8()
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_export_tiles() {
+    let cmd = Command::ExportTiles {
+        dir: "/tmp/my posters".to_string(),
+        cols: 3,
+        rows: 2,
+    };
+    assert_eq!(
+        parse_command("export tiles /tmp/my posters 3x2"),
+        Some(cmd.clone())
+    );
+    assert_eq!(cmd.to_string(), "export tiles /tmp/my posters 3x2");
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(cmd.name(), "export");
+    assert!(!cmd.is_mutating());
+    assert!(!Access::ReadOnly.allows(&cmd));
+
+    // Invalid formats
+    assert_eq!(parse_command("export tiles 3x2"), None);
+    assert_eq!(parse_command("export tiles /tmp/out"), None);
+    assert_eq!(parse_command("export tiles /tmp/out 3"), None);
+    assert_eq!(parse_command("export tiles /tmp/out 3x"), None);
+    assert_eq!(parse_command("export tiles /tmp/out threextwo"), None);
+    assert_eq!(parse_command("export tiles /tmp/out -1x2"), None);
+}
+
+#[test]
+fn test_validate_export_tiles() {
+    let check = |line: &str| validate(&parse_command(line).unwrap());
+    assert!(check("export tiles /tmp 1x1").is_ok());
+    assert!(check("export tiles /tmp 16x16").is_ok());
+    assert_eq!(
+        check("export tiles /tmp 0x2"),
+        Err("tile grid 0x2 is outside 1x1-16x16".to_string())
+    );
+    assert_eq!(
+        check("export tiles /tmp 2x17"),
+        Err("tile grid 2x17 is outside 1x1-16x16".to_string())
+    );
+}
+
+// ===================
+// Layout Tests
+// ===================
+
+#[test]
+fn test_tile_span() {
+    assert_eq!(
+        TileSpan::new(800, 1),
+        TileSpan {
+            size: 800,
+            starts: vec![0]
+        }
+    );
+    // Three tiles sharing about TILE_OVERLAP pixels at each seam
+    let span = TileSpan::new(800, 3);
+    assert_eq!(span.size, 283);
+    assert_eq!(span.starts, vec![0, 258, 517]);
+    assert_eq!(span.seam(0), 270);
+    assert_eq!(span.seam(1), 529);
+}
+
+#[test]
+fn test_tile_spans_cover_the_side() {
+    for length in [97, 570, 800, 1920] {
+        for count in 1..=MAX_TILE_GRID {
+            let span = TileSpan::new(length, count);
+            assert_eq!(span.starts[0], 0, "{} in {}", length, count);
+            assert_eq!(span.starts[count - 1] + span.size, length);
+            for pair in span.starts.windows(2) {
+                assert!(pair[0] < pair[1], "{} in {}", length, count);
+                assert!(pair[1] <= pair[0] + span.size, "{} in {}", length, count);
+            }
+        }
+    }
+}
+
+// ===================
+// Export Tests
+// ===================
+
+#[test]
+fn test_export_tiles_writes_the_grid() {
+    let dir = scratch("grid");
+    let dir_name = dir.display().to_string();
+    let mut state = AppState::new();
+    run(&mut state, "fill 2");
+    run(&mut state, "rect 100,100 300,300");
+    assert_eq!(
+        run(&mut state, &format!("export tiles {} 3x2", dir_name)),
+        Some(format!("saved 6 tiles to {}", dir_name))
+    );
+
+    let across = TileSpan::new(WIDTH, 3);
+    let down = TileSpan::new(canvas_bottom() - CANVAS_TOP, 2);
+    for row in 0..2 {
+        for col in 0..3 {
+            let img = open_tile(&dir, row + 1, col + 1);
+            assert_eq!(img.dimensions(), (across.size as u32, down.size as u32));
+        }
+    }
+
+    // A pixel away from the marks is the canvas pixel under it
+    let tile = open_tile(&dir, 1, 1);
+    let pixel = state.canvas[150 * WIDTH + 150];
+    let rgb = [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8];
+    assert_eq!(tile.get_pixel(150, (150 - CANVAS_TOP) as u32).0, rgb);
+    let tile = open_tile(&dir, 2, 2);
+    let (x, y) = (400 - across.starts[1], 50);
+    assert_eq!(tile.get_pixel(x as u32, y as u32).0, [255; 3]);
+}
+
+#[test]
+fn test_tiles_share_alignment_marks() {
+    let dir = scratch("marks");
+    let mut state = AppState::new();
+    run(&mut state, &format!("export tiles {} 2x1", dir.display()));
+
+    let across = TileSpan::new(WIDTH, 2);
+    let seam = across.seam(0);
+    let (left, right) = (open_tile(&dir, 1, 1), open_tile(&dir, 1, 2));
+    let height = left.height() - 1;
+    for y in [0, MARK_LENGTH as u32 - 1, height] {
+        assert_eq!(left.get_pixel(seam as u32, y).0, [0; 3]);
+        let x = (seam - across.starts[1]) as u32;
+        assert_eq!(right.get_pixel(x, y).0, [0; 3]);
+    }
+    // Only the ends of the seam are marked
+    assert_eq!(left.get_pixel(seam as u32, height / 2).0, [255; 3]);
+    assert_eq!(left.get_pixel(seam as u32, MARK_LENGTH as u32).0, [255; 3]);
+    // No seam runs across a single row
+    assert_eq!(left.get_pixel(5, 0).0, [255; 3]);
+}
+
+#[test]
+fn test_marks_show_over_dark_pixels() {
+    let dir = scratch("dark");
+    let mut state = AppState::new();
+    run(&mut state, "filter invert");
+    run(&mut state, &format!("export tiles {} 1x2", dir.display()));
+
+    let down = TileSpan::new(canvas_bottom() - CANVAS_TOP, 2);
+    let top = open_tile(&dir, 1, 1);
+    let seam = down.seam(0) as u32;
+    assert_eq!(top.get_pixel(0, seam).0, [255; 3]);
+    assert_eq!(top.get_pixel(top.width() - 1, seam).0, [255; 3]);
+    assert_eq!(top.get_pixel(top.width() / 2, seam).0, [0; 3]);
+}
+
+#[test]
+fn test_export_tiles_errors() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, "export tiles /nonexistent/posters 2x2"),
+        Some("error: directory /nonexistent/posters does not exist".to_string())
+    );
+    let dir = scratch("errors");
+    assert_eq!(
+        run(&mut state, &format!("export tiles {} 0x2", dir.display())),
+        Some("error: tile grid 0x2 is outside 1x1-16x16".to_string())
+    );
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+}
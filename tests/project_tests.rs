@@ -0,0 +1,194 @@
+mod common;
+
+use common::{pixel, run, scratch};
+use displai::*;
+
+/// Run a drawing command and record it, as the session does
+fn draw(state: &mut AppState, history: &mut History, line: &str) {
+    assert_eq!(run(state, line), None, "{}", line);
+    history.record(line, &state.canvas);
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_save_and_load() {
+    let cmd = parse_command("save /tmp/my drawings/cat.displai").unwrap();
+    assert_eq!(
+        cmd,
+        Command::Save("/tmp/my drawings/cat.displai".to_string())
+    );
+    assert_eq!(cmd.to_string(), "save /tmp/my drawings/cat.displai");
+    assert_eq!(cmd.name(), "save");
+    assert!(validate(&cmd).is_ok());
+    assert!(!cmd.is_mutating());
+
+    let cmd = parse_command("load cat.DISPLAI").unwrap();
+    assert_eq!(cmd, Command::Load("cat.DISPLAI".to_string()));
+    assert_eq!(cmd.to_string(), "load cat.DISPLAI");
+    assert_eq!(cmd.name(), "load");
+    assert!(validate(&cmd).is_ok());
+    assert!(cmd.is_mutating());
+    assert!(!cmd.runs_in_pane());
+    assert_eq!(command_region(&cmd, 1), Some(Region::ALL));
+
+    assert_eq!(parse_command("save"), None);
+    assert_eq!(parse_command("load"), None);
+    assert_eq!(
+        validate(&parse_command("save cat.png").unwrap()),
+        Err("project file 'cat.png' must end in .displai".to_string())
+    );
+    assert!(validate(&parse_command("load cat").unwrap()).is_err());
+}
+
+// ===================
+// Saving and Reading Tests
+// ===================
+
+#[test]
+fn test_project_round_trip() {
+    let dir = scratch("round_trip");
+    let path = dir.join("drawing.displai").display().to_string();
+    let mut state = AppState::new();
+    let mut history = History::new();
+    run(&mut state, "palette set 3 #123456");
+    run(&mut state, "fill 3");
+    draw(&mut state, &mut history, "rect 100,100 200,200");
+    run(&mut state, "size 7");
+    draw(&mut state, &mut history, "line 300,300 400,400");
+    state.current_tool = ToolMode::Circle;
+    save_project(&path, &state, &history).unwrap();
+    // Nothing is left beside the file
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    let project = read_project(&path).unwrap();
+    assert!(project.canvas == state.canvas);
+    assert_eq!(project.settings, session_settings(&state));
+    assert_eq!(project.history.entries(), history.entries());
+
+    let mut opened = AppState::new();
+    apply_project(&mut opened, &project).unwrap();
+    assert!(opened.canvas == state.canvas);
+    assert_eq!(opened.palette, state.palette);
+    assert_eq!(opened.fill_color, Some(0x123456));
+    assert_eq!(opened.brush_size, 7);
+    assert_eq!(opened.current_tool, ToolMode::Circle);
+
+    // Undo goes back through what was drawn before saving
+    let mut history = project.history;
+    history.undo(&mut opened.canvas).unwrap();
+    history.undo(&mut opened.canvas).unwrap();
+    assert!(opened.canvas == AppState::new().canvas);
+}
+
+#[test]
+fn test_project_keeps_the_canvas_size() {
+    let dir = scratch("size");
+    let path = dir.join("wide.displai").display().to_string();
+    let mut state = AppState::new();
+    run(&mut state, "canvas resize 1024x700");
+    let mut history = with_dimensions(state.canvas.dimensions(), || {
+        History::with_baseline(&state.canvas)
+    });
+    with_dimensions(state.canvas.dimensions(), || {
+        draw(&mut state, &mut history, "dot 1000,600");
+    });
+    save_project(&path, &state, &history).unwrap();
+
+    let project = read_project(&path).unwrap();
+    assert_eq!(
+        project.canvas.dimensions(),
+        Dimensions::new(1024, 700).unwrap()
+    );
+    let mut opened = AppState::new();
+    apply_project(&mut opened, &project).unwrap();
+    assert_eq!(pixel(&opened, 1000, 600), BLACK);
+    assert_eq!(project.history.len(), 1);
+}
+
+#[test]
+fn test_loading_starts_the_drawing_over() {
+    let mut state = AppState::new();
+    run(&mut state, "palette set 5 #ABCDEF");
+    run(&mut state, "rect 10,40 50,80");
+    run(&mut state, "panes 2x1");
+    run(&mut state, "tsplot 1 5");
+    let project = Project {
+        canvas: Canvas::default(),
+        settings: vec!["size 4".to_string()],
+        history: History::new(),
+    };
+    apply_project(&mut state, &project).unwrap();
+    assert!(state.canvas == Canvas::default());
+    // The palette is the project's (the default, here)
+    assert_eq!(state.palette, Palette::new());
+    assert_eq!(state.brush_size, 4);
+    assert!(state.display_list.is_empty());
+    assert!(state.series.is_empty());
+    // The pane layout still fits, so it stays
+    assert_eq!(state.panes.cols, 2);
+}
+
+#[test]
+fn test_refused_settings_leave_the_state() {
+    let mut state = AppState::new();
+    run(&mut state, "rect 10,40 50,80");
+    let before = state.clone();
+    let project = Project {
+        canvas: Canvas::default(),
+        settings: vec!["rect 0,30 10,40".to_string()],
+        history: History::new(),
+    };
+    assert_eq!(
+        apply_project(&mut state, &project),
+        Err("line 1: rect 0,30 10,40: not a tool setting".to_string())
+    );
+    assert!(state.canvas == before.canvas);
+}
+
+#[test]
+fn test_unreadable_projects_fail() {
+    let dir = scratch("unreadable");
+    let path = |name: &str| dir.join(name).display().to_string();
+    let error = |name: &str| read_project(&path(name)).unwrap_err();
+
+    assert!(error("missing.displai").starts_with("cannot load "));
+    std::fs::write(path("text.displai"), "rect 10,40 50,80\n").unwrap();
+    assert!(error("text.displai").ends_with(": not a displai project"));
+
+    // A file cut short anywhere is refused, not half-loaded
+    let state = AppState::new();
+    save_project(&path("whole.displai"), &state, &History::new()).unwrap();
+    let bytes = std::fs::read(path("whole.displai")).unwrap();
+    for len in [12, 20, bytes.len() / 2, bytes.len() - 1] {
+        std::fs::write(path("cut.displai"), &bytes[..len]).unwrap();
+        assert!(read_project(&path("cut.displai")).is_err(), "{}", len);
+    }
+
+    // Files from a later version are refused
+    let mut newer = bytes.clone();
+    newer[8..12].copy_from_slice(&2u32.to_le_bytes());
+    std::fs::write(path("newer.displai"), newer).unwrap();
+    assert!(error("newer.displai").ends_with("is newer than this displai reads (1)"));
+
+    // Unknown chunks are skipped
+    let mut extended = bytes;
+    extended.extend_from_slice(b"LAYR");
+    extended.extend_from_slice(&3u32.to_le_bytes());
+    extended.extend_from_slice(&[1, 2, 3]);
+    std::fs::write(path("extended.displai"), extended).unwrap();
+    assert!(read_project(&path("extended.displai")).is_ok());
+}
+
+#[test]
+fn test_save_refuses_other_extensions() {
+    let dir = scratch("extension");
+    let path = dir.join("canvas.png").display().to_string();
+    assert_eq!(
+        save_project(&path, &AppState::new(), &History::new()),
+        Err(format!("project file '{}' must end in .displai", path))
+    );
+    assert!(!dir.join("canvas.png").exists());
+}
@@ -0,0 +1,262 @@
+use displai::*;
+
+// ===================
+// Request ID Framing Tests
+// ===================
+
+#[test]
+fn test_split_request_id_tagged() {
+    assert_eq!(
+        split_request_id("#42 line 0,0 10,10"),
+        (Some("42"), "line 0,0 10,10")
+    );
+    assert_eq!(
+        split_request_id("  #a-b_c   clear"),
+        (Some("a-b_c"), "clear")
+    );
+}
+
+#[test]
+fn test_split_request_id_untagged() {
+    assert_eq!(split_request_id("clear"), (None, "clear"));
+    assert_eq!(split_request_id("dot 5,5"), (None, "dot 5,5"));
+}
+
+#[test]
+fn test_split_request_id_malformed_tags_are_kept() {
+    // Empty, punctuated, or overlong tags are not request IDs
+    assert_eq!(split_request_id("# clear"), (None, "# clear"));
+    assert_eq!(split_request_id("#4!2 clear"), (None, "#4!2 clear"));
+    let long = format!("#{} clear", "9".repeat(MAX_REQUEST_ID_LEN + 1));
+    assert_eq!(split_request_id(&long), (None, long.as_str()));
+}
+
+#[test]
+fn test_split_request_id_without_command() {
+    // A bare tag is still tagged, so the client gets an error back for it
+    assert_eq!(split_request_id("#7"), (Some("7"), ""));
+    assert_eq!(parse_command(split_request_id("#7").1), None);
+}
+
+#[test]
+fn test_tagged_line_parses_like_untagged() {
+    let (_, command) = split_request_id("#1 circle 100,100 20");
+    assert_eq!(parse_command(command), parse_command("circle 100,100 20"));
+}
+
+#[test]
+fn test_frame_response() {
+    assert_eq!(frame_response(Some("42"), "ok"), "#42 ok");
+    assert_eq!(
+        frame_response(Some("x"), "error: unknown command"),
+        "#x error: unknown command"
+    );
+    assert_eq!(frame_response(None, "ok"), "ok");
+}
+
+// ===================
+// Idempotency Key Tests
+// ===================
+
+#[test]
+fn test_split_idempotency_key() {
+    assert_eq!(
+        split_idempotency_key("!abc rect 0,0 9,9"),
+        (Some("abc"), "rect 0,0 9,9")
+    );
+    assert_eq!(
+        split_idempotency_key("rect 0,0 9,9"),
+        (None, "rect 0,0 9,9")
+    );
+    assert_eq!(split_idempotency_key("! rect"), (None, "! rect"));
+}
+
+#[test]
+fn test_request_id_then_idempotency_key() {
+    let (id, rest) = split_request_id("#42 !k7 dot 5,5");
+    let (key, command) = split_idempotency_key(rest);
+    assert_eq!(id, Some("42"));
+    assert_eq!(key, Some("k7"));
+    assert_eq!(parse_command(command), Some(Command::Dot { x: 5, y: 5 }));
+}
+
+#[test]
+fn test_idempotency_cache_rejects_duplicates() {
+    let mut cache = IdempotencyCache::default();
+    assert!(cache.is_empty());
+    assert!(cache.insert("a"));
+    assert!(!cache.insert("a"));
+    assert!(cache.insert("b"));
+    assert_eq!(cache.len(), 2);
+    assert!(cache.contains("a"));
+}
+
+#[test]
+fn test_idempotency_cache_forgets_oldest() {
+    let mut cache = IdempotencyCache::new(2);
+    cache.insert("a");
+    cache.insert("b");
+    cache.insert("c");
+    assert_eq!(cache.len(), 2);
+    assert!(!cache.contains("a"));
+    assert!(cache.contains("b"));
+    // A forgotten key is accepted again
+    assert!(cache.insert("a"));
+}
+
+#[test]
+fn test_is_mutating() {
+    assert!(Command::Clear.is_mutating());
+    assert!(Command::Dot { x: 1, y: 1 }.is_mutating());
+    assert!(Command::Points(vec![]).is_mutating());
+    assert!(!Command::State.is_mutating());
+    assert!(!Command::Snapshot(None).is_mutating());
+    assert!(!Command::Size(3).is_mutating());
+    assert!(!Command::Fps(30).is_mutating());
+}
+
+// ===================
+// Response Timing Tests
+// ===================
+
+#[test]
+fn test_timed_response() {
+    let elapsed = std::time::Duration::from_micros(1250);
+    assert_eq!(
+        timed_response(Ok(CommandOutput::Ok), elapsed),
+        reply("ok 1250us")
+    );
+    assert_eq!(
+        timed_response(reply("edge:0 fill:none size:1"), elapsed),
+        reply("edge:0 fill:none size:1 1250us")
+    );
+    let refused = timed_response(failed("nothing to undo"), std::time::Duration::ZERO);
+    assert_eq!(response_line(&refused), "error: nothing to undo 0us");
+}
+
+#[test]
+fn test_parse_timing() {
+    assert_eq!(parse_command("timing on"), Some(Command::Timing(true)));
+    assert_eq!(parse_command("timing off"), Some(Command::Timing(false)));
+    for cmd in [Command::Timing(true), Command::Timing(false)] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    }
+    assert_eq!(parse_command("timing"), None);
+    assert_eq!(parse_command("timing on now"), None);
+    assert!(!Command::Timing(true).is_mutating());
+}
+
+// ===================
+// Structured Response Tests
+// ===================
+
+#[test]
+fn test_parse_command_line_reasons() {
+    assert_eq!(
+        parse_command_line("dot 5,40"),
+        Ok(Command::Dot { x: 5, y: 40 })
+    );
+    assert_eq!(
+        parse_command_line("recct 1,2 3,4"),
+        Err(CommandError::Unknown("recct".to_string()))
+    );
+    assert_eq!(
+        parse_command_line("rect 1,2"),
+        Err(CommandError::InvalidArguments("rect"))
+    );
+    assert_eq!(
+        parse_command_line("   "),
+        Err(CommandError::Unknown(String::new()))
+    );
+}
+
+#[test]
+fn test_command_error_lines() {
+    let line = |e: CommandError| response_line(&Err(e));
+    assert_eq!(
+        line(CommandError::Unknown("recct".to_string())),
+        "error: unknown command 'recct'"
+    );
+    assert_eq!(
+        line(CommandError::InvalidArguments("rect")),
+        "error: invalid arguments for 'rect'"
+    );
+    assert_eq!(
+        line(CommandError::Unknown(String::new())),
+        "error: empty command"
+    );
+    assert_eq!(
+        line(CommandError::Failed("nothing to undo".to_string())),
+        "error: nothing to undo"
+    );
+}
+
+#[test]
+fn test_response_text() {
+    assert_eq!(response_text(&Ok(CommandOutput::Ok)), None);
+    assert_eq!(response_line(&Ok(CommandOutput::Ok)), "ok");
+    assert_eq!(
+        response_text(&reply("saved canvas.png")),
+        Some("saved canvas.png".to_string())
+    );
+    assert_eq!(
+        response_text(&failed("nothing to undo")),
+        Some("error: nothing to undo".to_string())
+    );
+}
+
+#[test]
+fn test_app_state_run() {
+    let mut state = AppState::new();
+    assert_eq!(state.run("dot 50,50"), Ok(CommandOutput::Ok));
+    assert_eq!(
+        state.run("state"),
+        Ok(CommandOutput::Text("edge:0 fill:none size:1".to_string()))
+    );
+    assert_eq!(
+        state.run("obj delete 9"),
+        Err(CommandError::Failed("no object 9".to_string()))
+    );
+    assert_eq!(
+        state.run("bogus"),
+        Err(CommandError::Unknown("bogus".to_string()))
+    );
+}
+
+#[test]
+fn test_app_state_run_keeps_output_that_looks_like_an_error() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.run("var set msg \"error: disk full\""),
+        Ok(CommandOutput::Ok)
+    );
+    assert_eq!(state.run("var get msg"), reply("error: disk full"));
+}
+
+#[test]
+fn test_app_state_run_refuses_out_of_range_arguments() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.run("ngon 400,300 50 2"),
+        Err(CommandError::Failed(
+            "ngon needs 3 to 1000 sides".to_string()
+        ))
+    );
+    assert!(state.run("replay /tmp/a.rec 0").is_err());
+    assert!(state.run("beep 5").is_err());
+}
+
+#[test]
+fn test_command_verbs_cover_every_command() {
+    for cmd in CommandGenerator::new(7).take(2000) {
+        assert!(COMMAND_VERBS.contains(&cmd.name()), "{}", cmd);
+    }
+    // Each verb alone is a command or a command missing its arguments
+    for verb in COMMAND_VERBS {
+        assert!(
+            !matches!(parse_command_line(verb), Err(CommandError::Unknown(_))),
+            "{}",
+            verb
+        );
+    }
+}
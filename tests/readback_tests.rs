@@ -0,0 +1,233 @@
+mod common;
+
+use common::run;
+use displai::*;
+
+fn base64_decode(text: &str) -> Vec<u8> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => panic!("not base64: {}", c as char),
+    };
+    let mut out = Vec::new();
+    for chunk in text.as_bytes().chunks(4) {
+        let digits: Vec<u8> = chunk
+            .iter()
+            .filter(|&&c| c != b'=')
+            .map(|&c| value(c))
+            .collect();
+        let n = digits
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &d)| n | u32::from(d) << (18 - 6 * i));
+        out.extend(n.to_be_bytes()[1..digits.len()].iter());
+    }
+    out
+}
+
+/// Split a `getregion` reply into its size and decoded pixels
+fn decode_region(reply: &str) -> (usize, usize, Vec<u32>) {
+    let (size, data) = reply.split_once(' ').expect("size and data");
+    let (w, h) = size.split_once('x').expect("WxH");
+    let pixels = rle_decode(&base64_decode(data)).expect("valid RLE");
+    (w.parse().unwrap(), h.parse().unwrap(), pixels)
+}
+
+// ===================
+// Run-Length Encoding Tests
+// ===================
+
+#[test]
+fn test_rle_round_trip() {
+    let pixels = [vec![WHITE; 300], vec![RED; 3], vec![BLACK], vec![WHITE; 2]].concat();
+    let encoded = rle_encode(&pixels);
+    // 300 whites need two runs (255 + 45)
+    assert_eq!(encoded.len(), 5 * 4);
+    assert_eq!(&encoded[..4], &[255, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(&encoded[4..8], &[45, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(&encoded[8..12], &[3, 0xE0, 0x40, 0x40]);
+    assert_eq!(rle_decode(&encoded), Some(pixels));
+    assert_eq!(rle_encode(&[]), Vec::<u8>::new());
+}
+
+#[test]
+fn test_rle_decode_rejects_malformed_input() {
+    assert_eq!(rle_decode(&[1, 2, 3]), None);
+    assert_eq!(rle_decode(&[0, 0, 0, 0]), None);
+    assert_eq!(rle_decode(&[]), Some(vec![]));
+}
+
+// ===================
+// getpixel Tests
+// ===================
+
+#[test]
+fn test_parse_getpixel() {
+    let cmd = Command::GetPixel { x: 10, y: 40 };
+    assert_eq!(parse_command("getpixel 10,40"), Some(cmd.clone()));
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("getpixel 10"), None);
+    assert_eq!(parse_command("getpixel 10,40 20,50"), None);
+    assert_eq!(parse_command("getpixel"), None);
+    assert!(validate(&parse_command("getpixel 10,10").unwrap()).is_err());
+}
+
+#[test]
+fn test_getpixel_reads_canvas_colors() {
+    let mut state = AppState::new();
+    assert_eq!(run(&mut state, "getpixel 100,100"), Some("#FFFFFF".into()));
+    run(&mut state, "edge #123456");
+    run(&mut state, "dot 100,100");
+    assert_eq!(run(&mut state, "getpixel 100,100"), Some("#123456".into()));
+    assert!(!Command::GetPixel { x: 0, y: 0 }.is_mutating());
+}
+
+#[test]
+fn test_getpixel_outside_canvas_is_error() {
+    let mut state = AppState::new();
+    let reply = run(&mut state, "getpixel 10,5").unwrap();
+    assert!(reply.starts_with("error: point 10,5 is outside the canvas"));
+    let reply = run(&mut state, &format!("getpixel {},100", WIDTH)).unwrap();
+    assert!(reply.starts_with("error:"));
+}
+
+#[test]
+fn test_getpixel_ignores_objects() {
+    let mut state = AppState::new();
+    run(&mut state, "button 100,100,40,20 \"OK\" ok");
+    assert_eq!(run(&mut state, "getpixel 100,100"), Some("#FFFFFF".into()));
+}
+
+// ===================
+// pick Tests
+// ===================
+
+#[test]
+fn test_parse_pick() {
+    let cmd = Command::Pick { x: 10, y: 40 };
+    assert_eq!(parse_command("pick 10,40"), Some(cmd.clone()));
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("pick 10"), None);
+    assert_eq!(parse_command("pick"), None);
+    assert!(validate(&parse_command("pick 10,10").unwrap()).is_err());
+    assert!(!Command::Pick { x: 0, y: 0 }.is_mutating());
+}
+
+#[test]
+fn test_pick_palette_color_gives_index() {
+    let mut state = AppState::new();
+    assert_eq!(
+        run(&mut state, "pick 100,100"),
+        palette_index(WHITE).map(|i| i.to_string())
+    );
+    run(&mut state, "color 3");
+    run(&mut state, "dot 100,100");
+    assert_eq!(run(&mut state, "pick 100,100"), Some("3".into()));
+}
+
+#[test]
+fn test_pick_other_color_gives_hex_and_closest_index() {
+    let mut state = AppState::new();
+    // Just off palette red
+    let near_red = COLOR_PALETTE[2] ^ 0x010101;
+    run(&mut state, &format!("edge #{:06X}", near_red));
+    run(&mut state, "dot 100,100");
+    assert_eq!(
+        run(&mut state, "pick 100,100"),
+        Some(format!("#{:06X} closest:2", near_red))
+    );
+    assert_eq!(nearest_palette_color(near_red), 2);
+}
+
+#[test]
+fn test_pick_uses_current_palette() {
+    let mut state = AppState::new();
+    run(&mut state, "edge #123456");
+    run(&mut state, "dot 100,100");
+    run(&mut state, "palette set 5 #123456");
+    assert_eq!(run(&mut state, "pick 100,100"), Some("5".into()));
+}
+
+#[test]
+fn test_pick_outside_canvas_is_error() {
+    let mut state = AppState::new();
+    let reply = run(&mut state, "pick 10,5").unwrap();
+    assert!(reply.starts_with("error: point 10,5 is outside the canvas"));
+}
+
+// ===================
+// getregion Tests
+// ===================
+
+#[test]
+fn test_parse_getregion() {
+    let cmd = Command::GetRegion {
+        x1: 10,
+        y1: 40,
+        x2: 20,
+        y2: 50,
+    };
+    assert_eq!(parse_command("getregion 10,40 20,50"), Some(cmd.clone()));
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("getregion 10,40"), None);
+    assert_eq!(parse_command("getregion 10,40 20"), None);
+    assert!(validate(&parse_command("getregion 0,0 20,50").unwrap()).is_err());
+}
+
+#[test]
+fn test_getregion_dumps_rectangle() {
+    let mut state = AppState::new();
+    run(&mut state, "edge #FF0000");
+    run(&mut state, "dot 12,42");
+    let reply = run(&mut state, "getregion 14,44 10,40").unwrap();
+    let (w, h, pixels) = decode_region(&reply);
+    assert_eq!((w, h), (5, 5));
+    assert_eq!(pixels.len(), 25);
+    // Row-major from the top-left corner
+    assert_eq!(pixels[2 * 5 + 2], 0xFF0000);
+    assert_eq!(pixels[0], WHITE);
+    assert_eq!(pixels.iter().filter(|&&p| p == 0xFF0000).count(), 1);
+}
+
+#[test]
+fn test_getregion_of_blank_canvas_is_compact() {
+    let mut state = AppState::new();
+    let bottom = CANVAS_BOTTOM - 1;
+    let reply = run(
+        &mut state,
+        &format!("getregion 0,{} {},{}", CANVAS_TOP, WIDTH - 1, bottom),
+    )
+    .unwrap();
+    let (w, h, pixels) = decode_region(&reply);
+    assert_eq!((w, h), (WIDTH, CANVAS_BOTTOM - CANVAS_TOP));
+    assert!(pixels.iter().all(|&p| p == WHITE));
+    // One 4-byte record per 255 pixels
+    let records = (w * h).div_ceil(255);
+    assert_eq!(
+        reply.len(),
+        format!("{}x{} ", w, h).len() + (records * 4).div_ceil(3) * 4
+    );
+}
+
+#[test]
+fn test_getregion_clips_to_canvas() {
+    let mut state = AppState::new();
+    let reply = run(
+        &mut state,
+        &format!(
+            "getregion {},0 {},{}",
+            WIDTH - 3,
+            WIDTH + 10,
+            CANVAS_TOP + 1
+        ),
+    )
+    .unwrap();
+    let (w, h, _) = decode_region(&reply);
+    assert_eq!((w, h), (3, 2));
+
+    let reply = run(&mut state, "getregion 0,0 10,10").unwrap();
+    assert!(reply.starts_with("error: region 0,0 10,10 is outside the canvas"));
+}
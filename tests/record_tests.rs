@@ -0,0 +1,248 @@
+use std::time::{Duration, Instant};
+
+use displai::*;
+
+/// A scratch file path unique to one test
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("displai_record_{}", name))
+        .to_string_lossy()
+        .into_owned()
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_record_commands() {
+    let start = Command::Record(Some("/tmp/my runs/session.rec".to_string()));
+    assert_eq!(
+        parse_command("record start /tmp/my runs/session.rec"),
+        Some(start.clone())
+    );
+    assert_eq!(parse_command(&start.to_string()), Some(start));
+    assert_eq!(parse_command("record stop"), Some(Command::Record(None)));
+    assert_eq!(parse_command("record start"), None);
+    assert_eq!(parse_command("record startx.rec"), None);
+    assert_eq!(parse_command("record"), None);
+    assert_eq!(parse_command("record stop now"), None);
+}
+
+#[test]
+fn test_parse_replay() {
+    let replay = |path: &str, speed| Command::Replay {
+        path: path.to_string(),
+        speed,
+    };
+    assert_eq!(
+        parse_command("replay /tmp/session.rec"),
+        Some(replay("/tmp/session.rec", 1.0))
+    );
+    assert_eq!(
+        parse_command("replay /tmp/my runs/session.rec 2.5"),
+        Some(replay("/tmp/my runs/session.rec", 2.5))
+    );
+    for cmd in [replay("/tmp/a.rec", 1.0), replay("/tmp/a b.rec", 0.5)] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    }
+    assert_eq!(parse_command("replay"), None);
+}
+
+#[test]
+fn test_parse_replay_realtime() {
+    let realtime = Command::Replay {
+        path: "/tmp/my runs/session.rec".to_string(),
+        speed: 1.0,
+    };
+    assert_eq!(
+        parse_command("replay /tmp/my runs/session.rec --realtime"),
+        Some(realtime.clone())
+    );
+    // Same as giving no speed, so it displays (and records) without the flag
+    assert_eq!(realtime.to_string(), "replay /tmp/my runs/session.rec");
+    assert_eq!(parse_command("replay --realtime"), None);
+    // Only a separate word is the flag
+    assert_eq!(
+        parse_command("replay /tmp/a.rec--realtime"),
+        Some(Command::Replay {
+            path: "/tmp/a.rec--realtime".to_string(),
+            speed: 1.0,
+        })
+    );
+}
+
+#[test]
+fn test_validate_replay_speed() {
+    let replay = |speed| Command::Replay {
+        path: "/tmp/a.rec".to_string(),
+        speed,
+    };
+    assert!(validate(&replay(1.0)).is_ok());
+    assert!(validate(&replay(MIN_REPLAY_SPEED)).is_ok());
+    assert!(validate(&replay(MAX_REPLAY_SPEED)).is_ok());
+    assert!(validate(&replay(0.0)).is_err());
+    assert!(validate(&replay(MAX_REPLAY_SPEED * 2.0)).is_err());
+    assert!(validate(&replay(f64::NAN)).is_err());
+}
+
+// ===================
+// Recorder Tests
+// ===================
+
+#[test]
+fn test_recorder_round_trip() {
+    let path = temp_path("round_trip.rec");
+    let t0 = Instant::now();
+    let mut recorder = Recorder::start(&path, t0).unwrap();
+    recorder
+        .record(&parse_command("rect 10,40 110,90").unwrap(), t0)
+        .unwrap();
+    recorder
+        .record(
+            &parse_command("obj text 10,40 \"hi there\"").unwrap(),
+            t0 + Duration::from_millis(250),
+        )
+        .unwrap();
+    assert_eq!(recorder.commands(), 2);
+    assert_eq!(recorder.finish(), Ok(2));
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with(RECORDING_HEADER));
+    assert_eq!(
+        read_recording(&path).unwrap(),
+        vec![
+            (0, "rect 10,40 110,90".to_string()),
+            (250, "obj text 10,40 \"hi there\"".to_string()),
+        ]
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_apply_record_command_responses() {
+    let path = temp_path("apply.rec");
+    let now = Instant::now();
+    let mut recorder = None;
+    assert_eq!(
+        apply_record_command(&mut recorder, None, now),
+        failed("not recording")
+    );
+    assert_eq!(
+        apply_record_command(&mut recorder, Some(&path), now),
+        reply(format!("recording {}", path))
+    );
+    recorder
+        .as_mut()
+        .unwrap()
+        .record(&Command::Clear, now)
+        .unwrap();
+    assert_eq!(
+        apply_record_command(&mut recorder, None, now),
+        reply(format!("recorded 1 commands to {}", path))
+    );
+    assert!(recorder.is_none());
+    assert!(apply_record_command(&mut recorder, Some("/nonexistent/dir/a.rec"), now).is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_read_recording_errors() {
+    assert!(read_recording("/nonexistent/dir/a.rec")
+        .unwrap_err()
+        .starts_with("cannot read"));
+
+    let path = temp_path("not_a_recording.rec");
+    std::fs::write(&path, "rect 10,40 110,90\n").unwrap();
+    assert!(read_recording(&path)
+        .unwrap_err()
+        .contains("is not a displai recording"));
+
+    std::fs::write(
+        &path,
+        format!("{}\n0 clear\nsoon clear\n", RECORDING_HEADER),
+    )
+    .unwrap();
+    assert!(read_recording(&path).unwrap_err().contains("line 3"));
+
+    // Blank lines and comments are skipped
+    std::fs::write(&path, format!("{}\n\n# setup\n0 clear\n", RECORDING_HEADER)).unwrap();
+    assert_eq!(
+        read_recording(&path).unwrap(),
+        vec![(0, "clear".to_string())]
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+// ===================
+// Replay Tests
+// ===================
+
+#[test]
+fn test_replay_hands_out_lines_as_they_come_due() {
+    let path = temp_path("due.rec");
+    std::fs::write(
+        &path,
+        format!(
+            "{}\n0 clear\n100 dot 5,40\n1000 dot 6,40\n",
+            RECORDING_HEADER
+        ),
+    )
+    .unwrap();
+    let t0 = Instant::now();
+    let mut replay = Replay::start(&path, 2.0, t0).unwrap();
+    assert_eq!(replay.len(), 3);
+
+    assert_eq!(replay.due(t0), vec!["clear".to_string()]);
+    // At double speed, 100ms in the recording is 50ms of replay
+    assert!(replay.due(t0 + Duration::from_millis(49)).is_empty());
+    assert_eq!(
+        replay.due(t0 + Duration::from_millis(50)),
+        vec!["dot 5,40".to_string()]
+    );
+    assert!(!replay.is_finished());
+    assert_eq!(
+        replay.due(t0 + Duration::from_millis(500)),
+        vec!["dot 6,40".to_string()]
+    );
+    assert!(replay.is_finished());
+    assert!(replay.due(t0 + Duration::from_secs(10)).is_empty());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_paused_replay_keeps_its_gaps_after_resuming() {
+    let path = temp_path("pause.rec");
+    std::fs::write(
+        &path,
+        format!(
+            "{}
+0 clear
+100 dot 5,40
+",
+            RECORDING_HEADER
+        ),
+    )
+    .unwrap();
+    let t0 = Instant::now();
+    let at = |ms| t0 + Duration::from_millis(ms);
+    let mut replay = Replay::start(&path, 1.0, t0).unwrap();
+    assert_eq!(replay.next_due(t0).as_deref(), Some("clear"));
+
+    replay.pause(at(20));
+    assert!(replay.is_paused());
+    assert_eq!(replay.next_due(at(5000)), None);
+
+    // Paused for 980ms, so the second line comes due 100ms + 980ms in
+    replay.resume(at(1000));
+    assert!(!replay.is_paused());
+    assert_eq!(replay.next_due(at(1079)), None);
+    assert_eq!(replay.next_due(at(1080)).as_deref(), Some("dot 5,40"));
+    assert!(replay.is_finished());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_replay_missing_file() {
+    assert!(Replay::start("/nonexistent/dir/a.rec", 1.0, Instant::now()).is_err());
+}
@@ -0,0 +1,74 @@
+use displai::*;
+use std::time::{Duration, Instant};
+
+fn input(pos: Option<(f32, f32)>, left_down: bool) -> InputSnapshot {
+    InputSnapshot {
+        mouse_pos: pos,
+        left_down,
+        right_down: false,
+    }
+}
+
+// ===================
+// Redraw Tracker Tests
+// ===================
+
+#[test]
+fn test_first_frame_is_presented() {
+    let tracker = RedrawTracker::new();
+    assert!(tracker.should_present(Instant::now()));
+}
+
+#[test]
+fn test_idle_frames_are_skipped() {
+    let mut tracker = RedrawTracker::new();
+    let start = Instant::now();
+    tracker.observe_input(input(Some((10.0, 10.0)), false));
+    tracker.presented(start);
+
+    tracker.observe_input(input(Some((10.0, 10.0)), false));
+    assert!(!tracker.is_dirty());
+    assert!(!tracker.should_present(start + Duration::from_millis(100)));
+}
+
+#[test]
+fn test_heartbeat_presents_while_idle() {
+    let mut tracker = RedrawTracker::new();
+    let start = Instant::now();
+    tracker.presented(start);
+    assert!(!tracker.should_present(start + HEARTBEAT_INTERVAL / 2));
+    assert!(tracker.should_present(start + HEARTBEAT_INTERVAL));
+}
+
+#[test]
+fn test_mark_dirty_forces_present() {
+    let mut tracker = RedrawTracker::new();
+    let start = Instant::now();
+    tracker.presented(start);
+    tracker.mark_dirty();
+    assert!(tracker.should_present(start));
+    tracker.presented(start);
+    assert!(!tracker.should_present(start));
+}
+
+#[test]
+fn test_input_change_marks_dirty() {
+    let mut tracker = RedrawTracker::new();
+    let start = Instant::now();
+    tracker.observe_input(input(Some((10.0, 10.0)), false));
+    tracker.presented(start);
+
+    // Mouse moved
+    tracker.observe_input(input(Some((11.0, 10.0)), false));
+    assert!(tracker.should_present(start));
+    tracker.presented(start);
+
+    // Button pressed without moving
+    tracker.observe_input(input(Some((11.0, 10.0)), true));
+    assert!(tracker.should_present(start));
+    tracker.presented(start);
+
+    // Mouse left the window
+    tracker.observe_input(input(None, true));
+    assert!(tracker.should_present(start));
+}
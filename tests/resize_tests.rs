@@ -0,0 +1,236 @@
+mod common;
+
+use common::{drawn, pixel, run};
+use displai::*;
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_canvas_size_changes() {
+    let cases = [
+        (
+            "canvas crop 10,40 600,300",
+            CanvasSize::Crop {
+                x1: 10,
+                y1: 40,
+                x2: 600,
+                y2: 300,
+            },
+        ),
+        (
+            "canvas resize 1024x768",
+            CanvasSize::Resize {
+                width: 1024,
+                height: 768,
+                anchor: Anchor::NorthWest,
+            },
+        ),
+        (
+            "canvas resize 640x480 center",
+            CanvasSize::Resize {
+                width: 640,
+                height: 480,
+                anchor: Anchor::Center,
+            },
+        ),
+        (
+            "canvas resize 900x700 se",
+            CanvasSize::Resize {
+                width: 900,
+                height: 700,
+                anchor: Anchor::SouthEast,
+            },
+        ),
+    ];
+    for (line, change) in cases {
+        let cmd = parse_command(line).unwrap();
+        assert_eq!(cmd, Command::CanvasSize(change));
+        assert_eq!(cmd.to_string(), line);
+        assert_eq!(cmd.name(), "canvas");
+        assert!(validate(&cmd).is_ok(), "{}", line);
+        assert!(cmd.is_mutating());
+        assert!(!cmd.runs_in_pane());
+        assert_eq!(command_region(&cmd, 1), Some(Region::ALL));
+    }
+
+    // The top-left corner is the default anchor, so it isn't printed
+    let cmd = parse_command("canvas resize 1024x768 nw").unwrap();
+    assert_eq!(cmd.to_string(), "canvas resize 1024x768");
+    for (name, anchor) in Anchor::ALL {
+        assert_eq!(Anchor::parse(name), Some(anchor));
+        assert_eq!(anchor.name(), name);
+    }
+
+    // Invalid formats
+    assert_eq!(parse_command("canvas crop"), None);
+    assert_eq!(parse_command("canvas crop 10,40"), None);
+    assert_eq!(parse_command("canvas crop 10,40 600"), None);
+    assert_eq!(parse_command("canvas crop 10,40 600,300 700,400"), None);
+    assert_eq!(parse_command("canvas resize"), None);
+    assert_eq!(parse_command("canvas resize 1024"), None);
+    assert_eq!(parse_command("canvas resize 1024x768 middle"), None);
+    assert_eq!(parse_command("canvas resize 1024x768 center nw"), None);
+
+    // A size change is for the whole canvas, not a pane
+    let cmd = parse_command("@1 canvas resize 1024x768").unwrap();
+    assert_eq!(
+        validate(&cmd),
+        Err("@1 only prefixes drawing commands".to_string())
+    );
+}
+
+#[test]
+fn test_validate_canvas_size_changes() {
+    let error = |line: &str| validate(&parse_command(line).unwrap()).unwrap_err();
+    assert_eq!(
+        error("canvas resize 100x768"),
+        "size 100x768 out of range (544x200 to 8192x8192)"
+    );
+    assert_eq!(
+        error("canvas crop 10,40 300,300"),
+        "cropped window size 291x351 out of range (544x200 to 8192x8192)"
+    );
+    assert_eq!(
+        error("canvas crop 10,0 600,20"),
+        "region 10,0 600,20 is outside the canvas"
+    );
+}
+
+// ===================
+// Layout Tests
+// ===================
+
+#[test]
+fn test_resize_layout_follows_the_anchor() {
+    let current = Dimensions::default();
+    let layout = |anchor| {
+        CanvasSize::Resize {
+            width: 1000,
+            height: 700,
+            anchor,
+        }
+        .layout(current)
+        .unwrap()
+    };
+    let dims = Dimensions::new(1000, 700).unwrap();
+    assert_eq!(layout(Anchor::NorthWest), (dims, (0, 0)));
+    assert_eq!(layout(Anchor::Center), (dims, (100, 50)));
+    assert_eq!(layout(Anchor::SouthEast), (dims, (200, 100)));
+    assert_eq!(layout(Anchor::North), (dims, (100, 0)));
+    assert_eq!(layout(Anchor::West), (dims, (0, 50)));
+
+    // Shrinking moves the picture up and left instead
+    let smaller = CanvasSize::Resize {
+        width: 600,
+        height: 500,
+        anchor: Anchor::SouthEast,
+    };
+    assert_eq!(
+        smaller.layout(current).unwrap(),
+        (Dimensions::new(600, 500).unwrap(), (-200, -100))
+    );
+}
+
+#[test]
+fn test_crop_layout_keeps_the_bars() {
+    let crop = CanvasSize::Crop {
+        x1: 700,
+        y1: 400,
+        x2: 100,
+        y2: 50,
+    };
+    // 351 canvas rows, plus the title bar and toolbar
+    assert_eq!(
+        crop.layout(Dimensions::default()).unwrap(),
+        (Dimensions::new(601, 441).unwrap(), (-100, -20))
+    );
+    // A crop is clipped to the canvas
+    let crop = CanvasSize::Crop {
+        x1: 0,
+        y1: 0,
+        x2: 5000,
+        y2: 5000,
+    };
+    assert_eq!(
+        crop.layout(Dimensions::default()).unwrap(),
+        (Dimensions::default(), (0, 0))
+    );
+}
+
+// ===================
+// Execution Tests
+// ===================
+
+#[test]
+fn test_crop_keeps_the_rectangle() {
+    let mut state = drawn(&["fill 0", "rect 150,100 160,110"]);
+    assert_eq!(run(&mut state, "canvas crop 100,50 700,400"), None);
+    assert_eq!(
+        state.canvas.dimensions(),
+        Dimensions::new(601, 441).unwrap()
+    );
+    assert_eq!(state.canvas.len(), 601 * 441);
+    // (150, 100) is now 50 across and 50 down from the crop's corner
+    assert_eq!(pixel(&state, 50, CANVAS_TOP + 50), BLACK);
+    assert_eq!(pixel(&state, 49, CANVAS_TOP + 50), WHITE);
+    assert_eq!(pixel(&state, 60, CANVAS_TOP + 60), BLACK);
+    assert_eq!(pixel(&state, 61, CANVAS_TOP + 61), WHITE);
+}
+
+#[test]
+fn test_resize_pads_with_white() {
+    let mut state = drawn(&["fill 0", "rect 0,30 799,539"]);
+    assert_eq!(run(&mut state, "canvas resize 1000x700 center"), None);
+    assert_eq!(
+        state.canvas.dimensions(),
+        Dimensions::new(1000, 700).unwrap()
+    );
+    assert_eq!(pixel(&state, 99, CANVAS_TOP + 60), WHITE);
+    assert_eq!(pixel(&state, 100, CANVAS_TOP + 60), BLACK);
+    assert_eq!(pixel(&state, 899, CANVAS_TOP + 50), BLACK);
+    assert_eq!(pixel(&state, 900, CANVAS_TOP + 50), WHITE);
+    assert_eq!(pixel(&state, 500, CANVAS_TOP + 49), WHITE);
+    assert_eq!(pixel(&state, 500, CANVAS_TOP + 50 + 509), BLACK);
+    assert_eq!(pixel(&state, 500, CANVAS_TOP + 50 + 510), WHITE);
+
+    // Drawing reaches the new area
+    run(&mut state, "dot 990,600");
+    assert_eq!(pixel(&state, 990, 600), BLACK);
+}
+
+#[test]
+fn test_resize_and_back_restores_an_anchored_picture() {
+    let before = drawn(&["fill 2", "circle 400,300 60"]);
+    let mut state = before.clone();
+    run(&mut state, "canvas resize 1200x900 center");
+    run(&mut state, "canvas resize 800x600 center");
+    assert!(state.canvas == before.canvas);
+}
+
+#[test]
+fn test_invalid_size_change_leaves_the_canvas() {
+    let before = drawn(&["fill 0", "rect 100,100 300,300"]);
+    let mut state = before.clone();
+    assert_eq!(
+        run(&mut state, "canvas resize 9000x600"),
+        Some("error: size 9000x600 out of range (544x200 to 8192x8192)".to_string())
+    );
+    assert_eq!(
+        run(&mut state, "canvas crop 0,0 799,10"),
+        Some("error: region 0,0 799,10 is outside the canvas".to_string())
+    );
+    assert!(state.canvas == before.canvas);
+}
+
+#[test]
+fn test_resize_starts_pane_data_over() {
+    let mut state = drawn(&["panes 2x1", "@1 scale 0,0 10,10", "tsplot 1 5"]);
+    assert_eq!(run(&mut state, "canvas resize 1000x700"), None);
+    assert_eq!(state.panes.cols, 2);
+    assert!(state.scales.is_empty());
+    assert!(state.series.is_empty());
+    // The panes are laid out on the new canvas
+    assert_eq!(state.pane(2).unwrap().width, 500);
+}
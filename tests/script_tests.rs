@@ -0,0 +1,156 @@
+use displai::*;
+
+fn write_script(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir()
+        .join(format!("displai_script_{}", name))
+        .to_string_lossy()
+        .into_owned();
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+fn defines(list: &[(&str, &str)]) -> Vec<(String, String)> {
+    list.iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Look names up in `list` only, not the environment
+fn lookup<'a>(list: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    |name| {
+        list.iter()
+            .find(|(defined, _)| *defined == name)
+            .map(|(_, value)| value.to_string())
+    }
+}
+
+// ===================
+// Define Tests
+// ===================
+
+#[test]
+fn test_define_names() {
+    assert!(is_valid_define_name("title"));
+    assert!(is_valid_define_name("_x"));
+    assert!(is_valid_define_name("SERIES_2"));
+    assert!(!is_valid_define_name(""));
+    assert!(!is_valid_define_name("2nd"));
+    assert!(!is_valid_define_name("my-title"));
+    assert!(!is_valid_define_name("a b"));
+}
+
+#[test]
+fn test_parse_define() {
+    assert_eq!(
+        parse_define("title=Sales"),
+        Some(("title".to_string(), "Sales".to_string()))
+    );
+    assert_eq!(
+        parse_define("query=a=b"),
+        Some(("query".to_string(), "a=b".to_string()))
+    );
+    assert_eq!(
+        parse_define("empty="),
+        Some(("empty".to_string(), String::new()))
+    );
+    assert_eq!(parse_define("title"), None);
+    assert_eq!(parse_define("=Sales"), None);
+    assert_eq!(parse_define("bad name=1"), None);
+}
+
+#[test]
+fn test_script_variable_prefers_the_last_define() {
+    let list = defines(&[("color", "1"), ("color", "2")]);
+    assert_eq!(script_variable(&list, "color"), Some("2".to_string()));
+    assert_eq!(script_variable(&list, "DISPLAI_SCRIPT_TEST_UNSET"), None);
+}
+
+#[test]
+fn test_script_variable_falls_back_to_the_environment() {
+    let path = std::env::var("PATH").unwrap();
+    assert_eq!(script_variable(&[], "PATH"), Some(path));
+    let list = defines(&[("PATH", "defined")]);
+    assert_eq!(script_variable(&list, "PATH"), Some("defined".to_string()));
+}
+
+// ===================
+// Interpolation Tests
+// ===================
+
+#[test]
+fn test_interpolate_puts_in_values() {
+    let vars = [("x", "100"), ("y", "40"), ("title", "Sales Q3")];
+    assert_eq!(
+        interpolate("dot ${x},${y}", lookup(&vars)),
+        Ok("dot 100,40".to_string())
+    );
+    assert_eq!(
+        interpolate("obj text 10,40 \"${title}\"", lookup(&vars)),
+        Ok("obj text 10,40 \"Sales Q3\"".to_string())
+    );
+    assert_eq!(
+        interpolate("line ${x},${x} ${x},${y}", lookup(&vars)),
+        Ok("line 100,100 100,40".to_string())
+    );
+    assert_eq!(interpolate("clear", lookup(&vars)), Ok("clear".to_string()));
+}
+
+#[test]
+fn test_interpolate_reports_an_unset_name() {
+    assert_eq!(
+        interpolate("dot ${x},${missing}", lookup(&[("x", "1")])),
+        Err("missing".to_string())
+    );
+}
+
+#[test]
+fn test_interpolate_leaves_other_dollars() {
+    let none = lookup(&[]);
+    for line in [
+        "alias box \"rect $1,$2 $3,$4\"",
+        "alias box \"rect ${1},${2} 5,5\"",
+        "var cost $5",
+        "var x ${not a name}",
+        "var x ${unclosed",
+        "var x ${}",
+    ] {
+        assert_eq!(interpolate(line, &none), Ok(line.to_string()), "{}", line);
+    }
+}
+
+#[test]
+fn test_interpolate_does_not_expand_values() {
+    let vars = [("a", "${b}"), ("b", "oops")];
+    assert_eq!(interpolate("${a}", lookup(&vars)), Ok("${b}".to_string()));
+}
+
+// ===================
+// Template Script Tests
+// ===================
+
+#[test]
+fn test_read_template_script() {
+    let path = write_script(
+        "template.txt",
+        "# ${ignored} in a comment\nfill ${fill}\nrect ${x},40 200,200\n",
+    );
+    let list = defines(&[("fill", "2"), ("x", "100")]);
+    assert_eq!(
+        read_template_script(&path, &list).unwrap(),
+        vec!["fill 2", "rect 100,40 200,200"]
+    );
+
+    let err = read_template_script(&path, &defines(&[("fill", "2")])).unwrap_err();
+    assert_eq!(
+        err,
+        format!(
+            "{}: ${{x}} is not set (pass --define x=VALUE or set it in the environment)",
+            path
+        )
+    );
+    let _ = std::fs::remove_file(&path);
+
+    assert!(read_template_script("/nonexistent/script.txt", &[])
+        .unwrap_err()
+        .starts_with("cannot read /nonexistent/script.txt"));
+}
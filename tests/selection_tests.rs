@@ -0,0 +1,298 @@
+use std::time::Duration;
+
+use displai::*;
+
+/// A blank window buffer with a red 20x10 block at (100, 100)
+fn canvas_with_block() -> Vec<u32> {
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    for y in 100..110 {
+        for x in 100..120 {
+            buffer[y * WIDTH + x] = RED;
+        }
+    }
+    buffer
+}
+
+fn pixel(buffer: &[u32], x: usize, y: usize) -> u32 {
+    buffer[y * WIDTH + x]
+}
+
+/// Drag out a selection from one corner to the other
+fn select(selection: &mut Selection, buffer: &mut [u32], from: (usize, usize), to: (usize, usize)) {
+    selection.press(buffer, from.0, from.1);
+    selection.drag(to.0, to.1);
+    selection.release();
+}
+
+// ===================
+// Selecting Tests
+// ===================
+
+#[test]
+fn test_drag_selects_rectangle() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    assert!(selection.is_empty());
+    select(&mut selection, &mut buffer, (120, 110), (100, 100));
+    // Either drag direction, corners inclusive
+    assert_eq!(selection.bounds(), Some((100, 100, 21, 11)));
+    assert!(selection.contains(100, 100));
+    assert!(selection.contains(120, 110));
+    assert!(!selection.contains(121, 110));
+    assert!(!selection.is_floating());
+    // Selecting leaves the canvas alone
+    assert_eq!(buffer, canvas_with_block());
+}
+
+#[test]
+fn test_click_without_drag_selects_nothing() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (100, 100));
+    assert!(selection.is_empty());
+    // A drag that is only one pixel tall is no selection either
+    select(&mut selection, &mut buffer, (100, 100), (150, 100));
+    assert!(selection.is_empty());
+}
+
+#[test]
+fn test_selection_is_clamped_to_canvas() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(
+        &mut selection,
+        &mut buffer,
+        (WIDTH - 10, 50),
+        (WIDTH + 50, 5),
+    );
+    assert_eq!(
+        selection.bounds(),
+        Some(((WIDTH - 10) as isize, CANVAS_TOP as isize, 10, 21))
+    );
+    select(
+        &mut selection,
+        &mut buffer,
+        (10, CANVAS_BOTTOM - 5),
+        (20, HEIGHT),
+    );
+    assert_eq!(
+        selection.bounds(),
+        Some((10, (CANVAS_BOTTOM - 5) as isize, 11, 5))
+    );
+}
+
+// ===================
+// Move Tests
+// ===================
+
+#[test]
+fn test_drag_moves_selection() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+
+    // Picking it up lifts the pixels off the canvas
+    assert_eq!(selection.press(&mut buffer, 105, 105), None);
+    assert!(selection.is_floating());
+    assert_eq!(pixel(&buffer, 105, 105), WHITE);
+    selection.drag(205, 155);
+    selection.release();
+    assert_eq!(selection.bounds(), Some((200, 150, 20, 10)));
+
+    // Nothing lands until it is committed
+    assert_eq!(pixel(&buffer, 200, 150), WHITE);
+    assert_eq!(selection.commit(&mut buffer), Some("move"));
+    assert!(selection.is_empty());
+    assert_eq!(pixel(&buffer, 200, 150), RED);
+    assert_eq!(pixel(&buffer, 219, 159), RED);
+    assert_eq!(pixel(&buffer, 100, 100), WHITE);
+}
+
+#[test]
+fn test_press_outside_commits_and_starts_new_selection() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    selection.press(&mut buffer, 105, 105);
+    selection.drag(305, 305);
+    selection.release();
+
+    assert_eq!(selection.press(&mut buffer, 50, 50), Some("move"));
+    assert_eq!(pixel(&buffer, 300, 300), RED);
+    assert!(!selection.is_floating());
+    assert!(!selection.is_empty());
+}
+
+#[test]
+fn test_cancel_puts_lifted_pixels_back() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    selection.press(&mut buffer, 105, 105);
+    selection.drag(305, 305);
+    selection.cancel(&mut buffer);
+    assert!(selection.is_empty());
+    assert_eq!(buffer, canvas_with_block());
+}
+
+#[test]
+fn test_floating_selection_is_clipped_to_canvas() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    selection.press(&mut buffer, 100, 100);
+    // Drag it so it hangs off the top-left corner of the canvas
+    selection.drag(0, CANVAS_TOP);
+    selection.drag(0, 0);
+    selection.release();
+    assert_eq!(selection.bounds(), Some((0, 0, 20, 10)));
+    assert_eq!(selection.commit(&mut buffer), Some("move"));
+    // The title bar is untouched; nothing landed above the canvas
+    assert!(buffer[..CANVAS_TOP * WIDTH].iter().all(|&p| p == WHITE));
+}
+
+// ===================
+// Clipboard Tests
+// ===================
+
+#[test]
+fn test_copy_and_paste() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    assert!(!selection.copy(&buffer));
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    assert!(selection.copy(&buffer));
+    let clip = selection.clipboard().unwrap();
+    assert_eq!((clip.width, clip.height), (20, 10));
+    assert!(clip.pixels.iter().all(|&p| p == RED));
+
+    // Pasting floats the clipboard; copying left the original in place
+    assert_eq!(selection.paste(&mut buffer, 300, 300), None);
+    assert!(selection.is_floating());
+    assert_eq!(selection.bounds(), Some((300, 300, 20, 10)));
+    assert_eq!(pixel(&buffer, 100, 100), RED);
+    assert_eq!(selection.commit(&mut buffer), Some("paste"));
+    assert_eq!(pixel(&buffer, 300, 300), RED);
+}
+
+#[test]
+fn test_paste_commits_previous_paste() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    selection.copy(&buffer);
+    selection.paste(&mut buffer, 300, 300);
+    assert_eq!(selection.paste(&mut buffer, 400, 300), Some("paste"));
+    assert_eq!(pixel(&buffer, 300, 300), RED);
+    assert_eq!(pixel(&buffer, 400, 300), WHITE);
+}
+
+#[test]
+fn test_paste_clip_floats_outside_pixels() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    let clip = Clip {
+        width: 2,
+        height: 1,
+        pixels: vec![BLUE, BLACK],
+    };
+    assert_eq!(selection.paste_clip(&mut buffer, clip, 300, 300), None);
+    assert!(selection.is_floating());
+    assert_eq!(selection.bounds(), Some((300, 300, 2, 1)));
+    // The clipboard is left alone
+    assert!(selection.clipboard().is_none());
+    assert_eq!(selection.commit(&mut buffer), Some("paste"));
+    assert_eq!(pixel(&buffer, 301, 300), BLACK);
+}
+
+#[test]
+fn test_paste_latest_prefers_the_newer_clip() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    selection.copy(&buffer);
+    let screenshot = Clip {
+        width: 3,
+        height: 2,
+        pixels: vec![BLUE; 6],
+    };
+    selection.paste_latest(&mut buffer, Some(screenshot), 300, 300);
+    assert_eq!(selection.bounds(), Some((300, 300, 3, 2)));
+    // Without one, the clipboard's pixels are pasted
+    assert_eq!(
+        selection.paste_latest(&mut buffer, None, 400, 300),
+        Some("paste")
+    );
+    assert_eq!(selection.bounds(), Some((400, 300, 20, 10)));
+    assert_eq!(pixel(&buffer, 302, 301), BLUE);
+}
+
+#[test]
+fn test_cancel_drops_paste() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    selection.copy(&buffer);
+    selection.paste(&mut buffer, 300, 300);
+    selection.cancel(&mut buffer);
+    assert_eq!(buffer, canvas_with_block());
+    // The clipboard survives for another paste
+    assert!(selection.clipboard().is_some());
+}
+
+#[test]
+fn test_cut_and_delete() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    assert_eq!(selection.cut(&mut buffer), None);
+
+    select(&mut selection, &mut buffer, (100, 100), (109, 109));
+    assert_eq!(selection.cut(&mut buffer), Some("cut"));
+    assert!(selection.is_empty());
+    assert_eq!(pixel(&buffer, 100, 100), WHITE);
+    assert_eq!(pixel(&buffer, 110, 100), RED);
+    assert_eq!(selection.clipboard().unwrap().width, 10);
+
+    select(&mut selection, &mut buffer, (110, 100), (119, 109));
+    assert_eq!(selection.delete(&mut buffer), Some("delete"));
+    assert_eq!(buffer, vec![WHITE; WIDTH * HEIGHT]);
+    assert_eq!(selection.delete(&mut buffer), None);
+}
+
+// ===================
+// Preview Tests
+// ===================
+
+#[test]
+fn test_preview_draws_floating_pixels_and_ants() {
+    let mut buffer = canvas_with_block();
+    let mut selection = Selection::new();
+    select(&mut selection, &mut buffer, (100, 100), (119, 109));
+    selection.copy(&buffer);
+    selection.paste(&mut buffer, 300, 300);
+
+    let mut frame = buffer.clone();
+    selection.draw_preview(&mut frame, 0);
+    // Floating pixels show inside, the border is dashed black and white
+    assert_eq!(pixel(&frame, 310, 305), RED);
+    let top: Vec<_> = (300..300 + 2 * ANTS_DASH)
+        .map(|x| pixel(&frame, x, 300))
+        .collect();
+    assert_eq!(top[..ANTS_DASH], [BLACK; ANTS_DASH]);
+    assert_eq!(top[ANTS_DASH..], [WHITE; ANTS_DASH]);
+    // The canvas itself is untouched
+    assert_eq!(pixel(&buffer, 310, 305), WHITE);
+
+    // A later phase shifts the dashes
+    let mut later = buffer.clone();
+    selection.draw_preview(&mut later, 1);
+    assert_eq!(pixel(&later, 300 + ANTS_DASH - 1, 300), WHITE);
+}
+
+#[test]
+fn test_ants_phase_cycles() {
+    assert_eq!(ants_phase(Duration::ZERO), 0);
+    assert_eq!(ants_phase(Duration::from_millis(ANTS_STEP_MS as u64)), 1);
+    let cycle = ANTS_STEP_MS as u64 * 2 * ANTS_DASH as u64;
+    assert_eq!(ants_phase(Duration::from_millis(cycle)), 0);
+}
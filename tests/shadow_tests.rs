@@ -0,0 +1,175 @@
+use displai::*;
+
+fn shadow(dx: isize, dy: isize, color: u32, blur: usize) -> Command {
+    Command::Shadow(Some(Shadow {
+        dx,
+        dy,
+        color,
+        blur,
+    }))
+}
+
+fn filled_rect_state(cmd: Command) -> AppState {
+    let mut state = AppState::new();
+    state.execute(&Command::Fill(Some(COLOR_PALETTE[2])));
+    state.execute(&cmd);
+    state.execute(&Command::Rect {
+        x1: 100,
+        y1: 100,
+        x2: 150,
+        y2: 150,
+    });
+    state
+}
+
+// ===================
+// Shadow Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_shadow() {
+    assert_eq!(
+        parse_command("shadow on 4,6"),
+        Some(shadow(4, 6, DEFAULT_SHADOW_COLOR, 0))
+    );
+    assert_eq!(
+        parse_command("shadow on -3,5 10 2"),
+        Some(shadow(-3, 5, COLOR_PALETTE[10], 2))
+    );
+    assert_eq!(
+        parse_command("shadow on 2,2 steelblue"),
+        Some(shadow(2, 2, 0x4682B4, 0))
+    );
+    assert_eq!(
+        parse_command("shadow on 2,2 #102030 4"),
+        Some(shadow(2, 2, 0x102030, 4))
+    );
+    assert_eq!(parse_command("shadow off"), Some(Command::Shadow(None)));
+}
+
+#[test]
+fn test_parse_shadow_rejects_malformed() {
+    assert_eq!(parse_command("shadow"), None);
+    assert_eq!(parse_command("shadow on"), None);
+    assert_eq!(parse_command("shadow on 4"), None);
+    assert_eq!(parse_command("shadow on 4,4 nocolor"), None);
+    assert_eq!(parse_command("shadow on 4,4 #12345"), None);
+    assert_eq!(parse_command("shadow on 4,4 0 x"), None);
+    assert_eq!(parse_command("shadow on 4,4 0 1 extra"), None);
+}
+
+#[test]
+fn test_shadow_display_round_trips() {
+    for cmd in [shadow(-4, 7, 0x4682B4, 3), Command::Shadow(None)] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    }
+    assert_eq!(
+        shadow(1, 2, 0x808080, 0).to_string(),
+        "shadow on 1,2 #808080 0"
+    );
+}
+
+#[test]
+fn test_validate_shadow_limits() {
+    assert!(validate(&shadow(
+        MAX_SHADOW_OFFSET,
+        -MAX_SHADOW_OFFSET,
+        0,
+        MAX_SHADOW_BLUR
+    ))
+    .is_ok());
+    assert!(validate(&shadow(MAX_SHADOW_OFFSET + 1, 0, 0, 0)).is_err());
+    assert!(validate(&shadow(0, 0, 0, MAX_SHADOW_BLUR + 1)).is_err());
+    assert!(validate(&shadow(0, 0, 0x1000000, 0)).is_err());
+    assert!(!shadow(1, 1, 0, 0).is_mutating());
+}
+
+// ===================
+// Shadow Drawing Tests
+// ===================
+
+#[test]
+fn test_shape_casts_offset_shadow() {
+    let state = filled_rect_state(shadow(10, 10, DARK_GRAY, 0));
+    let at = |x: usize, y: usize| state.canvas[y * WIDTH + x];
+
+    // Shape drawn on top of its own shadow
+    assert_eq!(at(125, 125), COLOR_PALETTE[2]);
+    // Shadow visible past the shape's bottom-right corner
+    assert_eq!(at(155, 155), DARK_GRAY);
+    assert_eq!(at(158, 120), DARK_GRAY);
+    // Nothing above or left of the shape
+    assert_eq!(at(95, 95), WHITE);
+    assert_eq!(at(165, 165), WHITE);
+}
+
+#[test]
+fn test_blurred_shadow_fades_out() {
+    let state = filled_rect_state(shadow(6, 6, BLACK, 4));
+    let at = |x: usize, y: usize| state.canvas[y * WIDTH + x] & 0xFF;
+
+    let inside = at(153, 130);
+    let edge = at(157, 130);
+    let beyond = at(162, 130);
+    assert!(inside < edge, "shadow lightens toward its edge");
+    assert!(edge < beyond);
+    assert_eq!(state.canvas[130 * WIDTH + 170], WHITE);
+}
+
+#[test]
+fn test_shadow_off_and_non_shapes() {
+    let mut state = filled_rect_state(Command::Shadow(None));
+    assert_eq!(state.canvas[155 * WIDTH + 155], WHITE);
+
+    // Strokes and dots are not shapes and cast no shadow
+    state.execute(&shadow(10, 10, DARK_GRAY, 0));
+    state.execute(&Command::Stroke {
+        x1: 300,
+        y1: 300,
+        x2: 400,
+        y2: 300,
+    });
+    assert_eq!(state.canvas[310 * WIDTH + 350], WHITE);
+    assert_eq!(state.shadow.map(|s| s.dx), Some(10));
+}
+
+#[test]
+fn test_shadow_stays_on_canvas() {
+    let mut state = AppState::new();
+    state.execute(&shadow(0, MAX_SHADOW_OFFSET, BLACK, 5));
+    state.execute(&Command::Fill(Some(COLOR_PALETTE[0])));
+    state.execute(&Command::Rect {
+        x1: 100,
+        y1: CANVAS_BOTTOM - 20,
+        x2: 200,
+        y2: CANVAS_BOTTOM - 1,
+    });
+    state.execute(&shadow(0, -MAX_SHADOW_OFFSET, BLACK, 5));
+    state.execute(&Command::Rect {
+        x1: 300,
+        y1: CANVAS_TOP,
+        x2: 400,
+        y2: CANVAS_TOP + 20,
+    });
+
+    assert!(state.canvas[..CANVAS_TOP * WIDTH]
+        .iter()
+        .all(|&p| p == WHITE));
+    assert!(state.canvas[CANVAS_BOTTOM * WIDTH..]
+        .iter()
+        .all(|&p| p == WHITE));
+}
+
+#[test]
+fn test_draw_shape_uses_shadow() {
+    let mut state = AppState::new();
+    state.shadow = Some(Shadow {
+        dx: 5,
+        dy: 5,
+        color: DARK_GRAY,
+        blur: 0,
+    });
+    state.draw_shape(ToolMode::Rectangle, 100, 100, 150, 150);
+    assert_eq!(state.canvas[100 * WIDTH + 100], BLACK);
+    assert_eq!(state.canvas[155 * WIDTH + 155], DARK_GRAY);
+}
@@ -0,0 +1,160 @@
+use displai::*;
+
+/// Points around a circle, from angle 0 back to (close to) where they began
+fn circle_stroke(cx: f64, cy: f64, r: f64, wobble: f64) -> Vec<(usize, usize)> {
+    (0..=36)
+        .map(|i| {
+            let angle = i as f64 * std::f64::consts::TAU / 36.0;
+            // Alternate in and out to mimic an unsteady hand
+            let r = if i % 2 == 0 { r + wobble } else { r - wobble };
+            (
+                (cx + r * angle.cos()).round() as usize,
+                (cy + r * angle.sin()).round() as usize,
+            )
+        })
+        .collect()
+}
+
+/// The corners of a polygon, walked in order and back to the first
+fn closed(corners: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut points = corners.to_vec();
+    points.push(corners[0]);
+    points
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_snap() {
+    assert_eq!(
+        parse_command("snap on"),
+        Some(Command::Snap(Some(DEFAULT_SNAP_TOLERANCE)))
+    );
+    assert_eq!(parse_command("snap on 0.3"), Some(Command::Snap(Some(0.3))));
+    assert_eq!(parse_command("snap off"), Some(Command::Snap(None)));
+    for cmd in [Command::Snap(Some(0.25)), Command::Snap(None)] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(!cmd.is_mutating());
+    }
+    assert_eq!(parse_command("snap"), None);
+    assert_eq!(parse_command("snap on x"), None);
+    assert_eq!(parse_command("snap on 0.1 0.2"), None);
+}
+
+#[test]
+fn test_validate_snap_tolerance() {
+    assert!(validate(&Command::Snap(Some(MAX_SNAP_TOLERANCE))).is_ok());
+    assert!(validate(&Command::Snap(Some(0.01))).is_ok());
+    assert!(validate(&Command::Snap(None)).is_ok());
+    assert_eq!(
+        validate(&Command::Snap(Some(0.0))),
+        Err("snap tolerance 0 is outside 0-0.5 (above 0)".to_string())
+    );
+    assert!(validate(&Command::Snap(Some(0.6))).is_err());
+    assert!(validate(&Command::Snap(Some(f64::NAN))).is_err());
+}
+
+#[test]
+fn test_snap_sets_state() {
+    let mut state = AppState::new();
+    assert_eq!(state.snap, None);
+    assert_eq!(state.execute(&parse_command("snap on 0.2").unwrap()), None);
+    assert_eq!(state.snap, Some(0.2));
+    state.execute(&parse_command("snap off").unwrap());
+    assert_eq!(state.snap, None);
+}
+
+#[test]
+fn test_snapped_event_line() {
+    let event = Event::Snapped("circle 100,100 50".to_string());
+    assert_eq!(event.to_string(), "snap circle 100,100 50");
+}
+
+// ===================
+// Recognition Tests
+// ===================
+
+#[test]
+fn test_recognize_line() {
+    let stroke = [(100, 100), (150, 103), (200, 98), (300, 101)];
+    assert_eq!(
+        recognize(&stroke, DEFAULT_SNAP_TOLERANCE),
+        Some(Command::Line {
+            x1: 100,
+            y1: 100,
+            x2: 300,
+            y2: 101
+        })
+    );
+    // Two far-apart mouse positions are a line too
+    assert_eq!(
+        recognize(&[(50, 60), (50, 200)], DEFAULT_SNAP_TOLERANCE),
+        Some(Command::Line {
+            x1: 50,
+            y1: 60,
+            x2: 50,
+            y2: 200
+        })
+    );
+}
+
+#[test]
+fn test_recognize_circle() {
+    let stroke = circle_stroke(300.0, 250.0, 80.0, 3.0);
+    assert_eq!(
+        recognize(&stroke, DEFAULT_SNAP_TOLERANCE),
+        Some(Command::Circle {
+            x: 300,
+            y: 250,
+            r: 80
+        })
+    );
+}
+
+#[test]
+fn test_recognize_rectangle() {
+    let stroke = closed(&[(100, 100), (302, 98), (300, 200), (99, 203)]);
+    let Some(Command::Rect { x1, y1, x2, y2 }) = recognize(&stroke, DEFAULT_SNAP_TOLERANCE) else {
+        panic!("expected a rectangle");
+    };
+    assert_eq!((x1, y1, x2, y2), (99, 98, 302, 203));
+}
+
+#[test]
+fn test_recognize_rejects_other_strokes() {
+    let tolerance = DEFAULT_SNAP_TOLERANCE;
+    // A triangle, a squashed oval, and a zig-zag
+    let triangle = closed(&[(100, 300), (200, 100), (300, 300)]);
+    assert_eq!(recognize(&triangle, tolerance), None);
+    let oval: Vec<_> = circle_stroke(300.0, 250.0, 80.0, 0.0)
+        .into_iter()
+        .map(|(x, y)| (x, 250 + (y - 170) / 3))
+        .collect();
+    assert_eq!(recognize(&oval, tolerance), None);
+    let zigzag = [(100, 100), (150, 160), (200, 100), (250, 160), (300, 100)];
+    assert_eq!(recognize(&zigzag, tolerance), None);
+}
+
+#[test]
+fn test_recognize_ignores_tiny_strokes() {
+    let tolerance = DEFAULT_SNAP_TOLERANCE;
+    assert_eq!(recognize(&[], tolerance), None);
+    assert_eq!(recognize(&[(100, 100)], tolerance), None);
+    assert_eq!(recognize(&[(100, 100), (105, 100)], tolerance), None);
+    assert_eq!(
+        recognize(&circle_stroke(100.0, 100.0, 3.0, 0.0), tolerance),
+        None
+    );
+}
+
+#[test]
+fn test_recognize_tolerance_controls_wobble() {
+    let wobbly = circle_stroke(300.0, 250.0, 80.0, 12.0);
+    assert_eq!(recognize(&wobbly, 0.1), None);
+    assert!(matches!(
+        recognize(&wobbly, 0.3),
+        Some(Command::Circle { .. })
+    ));
+}
@@ -0,0 +1,261 @@
+use displai::*;
+
+fn new_buffer() -> Vec<u32> {
+    vec![WHITE; WIDTH * HEIGHT]
+}
+
+// ===================
+// Snapshot Worker Tests
+// ===================
+
+#[test]
+fn test_worker_saves_snapshot() {
+    let mut buffer = new_buffer();
+    draw_circle(&mut buffer, 100, 100, 5, COLOR_PALETTE[2]);
+    let path = "/tmp/test_snapshot_worker.png";
+
+    let worker: SnapshotWorker<u32> = SnapshotWorker::spawn();
+    worker.request(&buffer, path, 7);
+    let done = worker.wait().expect("worker finishes");
+
+    assert_eq!(done.path, path);
+    assert_eq!(done.result, Ok(()));
+    assert_eq!(done.tokens, vec![7]);
+    let img = image::open(path).expect("Should open").to_rgb8();
+    assert_eq!(
+        img.get_pixel(100, (100 - CANVAS_TOP) as u32).0,
+        [0xE0, 0x40, 0x40]
+    );
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_worker_copies_frame_at_request_time() {
+    let mut buffer = new_buffer();
+    let path = "/tmp/test_snapshot_copy.png";
+
+    let worker: SnapshotWorker<()> = SnapshotWorker::spawn();
+    worker.request(&buffer, path, ());
+    // Drawing after the request must not leak into the snapshot
+    buffer.fill(BLACK);
+    worker.wait().expect("worker finishes");
+
+    let img = image::open(path).expect("Should open").to_rgb8();
+    assert_eq!(img.get_pixel(0, 0).0, [0xFF, 0xFF, 0xFF]);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_worker_crops_annotation_snapshots() {
+    let mut buffer = new_buffer();
+    draw_circle(&mut buffer, 10, CANVAS_TOP + 10, 3, COLOR_PALETTE[2]);
+    let path = "/tmp/test_snapshot_cropped.png";
+
+    let worker: SnapshotWorker<u32> = SnapshotWorker::spawn();
+    worker.request_cropped(&buffer, path, (40, 30), 1);
+    let done = worker.wait().expect("worker finishes");
+
+    assert_eq!(done.result, Ok(()));
+    let img = image::open(path).expect("Should open").to_rgb8();
+    assert_eq!(img.dimensions(), (40, 30));
+    assert_eq!(img.get_pixel(10, 10).0, [0xE0, 0x40, 0x40]);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_worker_coalesces_pending_requests() {
+    let buffer = new_buffer();
+    let worker: SnapshotWorker<u32> = SnapshotWorker::spawn();
+
+    // Fill the queue; every request after the first for a path may join it
+    let mut coalesced = 0;
+    for token in 0..20 {
+        if worker.request(&buffer, "/tmp/test_snapshot_coalesce.png", token) {
+            coalesced += 1;
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut encodes = 0;
+    while tokens.len() < 20 {
+        let done = worker.wait().expect("worker finishes");
+        assert_eq!(done.result, Ok(()));
+        tokens.extend(done.tokens);
+        encodes += 1;
+    }
+    tokens.sort();
+    assert_eq!(tokens, (0..20).collect::<Vec<_>>());
+    assert_eq!(encodes, 20 - coalesced);
+    std::fs::remove_file("/tmp/test_snapshot_coalesce.png").ok();
+}
+
+#[test]
+fn test_worker_reports_errors() {
+    let worker: SnapshotWorker<()> = SnapshotWorker::spawn();
+    worker.request(&new_buffer(), "/nonexistent_dir/snap.png", ());
+    let done = worker.wait().expect("worker finishes");
+    assert!(done.result.is_err());
+    assert!(snapshot_response(&done.path, &done.result).is_err());
+}
+
+#[test]
+fn test_snapshot_response_matches_command_reply() {
+    assert_eq!(
+        snapshot_response(SNAPSHOT_PATH, &Ok(())),
+        reply("saved canvas.png")
+    );
+}
+
+#[test]
+fn test_drop_finishes_queued_snapshots() {
+    let path = "/tmp/test_snapshot_drop.png";
+    std::fs::remove_file(path).ok();
+    {
+        let worker: SnapshotWorker<()> = SnapshotWorker::spawn();
+        worker.request(&new_buffer(), path, ());
+    }
+    assert!(std::path::Path::new(path).exists());
+    std::fs::remove_file(path).ok();
+}
+
+// ===================
+// Base64 Snapshot Tests
+// ===================
+
+/// Decode standard padded base64 (test helper; the app only encodes)
+fn base64_decode(text: &str) -> Vec<u8> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => panic!("not base64: {}", c as char),
+    };
+    let mut out = Vec::new();
+    for chunk in text.as_bytes().chunks(4) {
+        let digits: Vec<u8> = chunk
+            .iter()
+            .filter(|&&c| c != b'=')
+            .map(|&c| value(c))
+            .collect();
+        let n = digits
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &d)| n | u32::from(d) << (18 - 6 * i));
+        out.extend(n.to_be_bytes()[1..digits.len()].iter());
+    }
+    out
+}
+
+#[test]
+fn test_base64_encode_rfc4648_vectors() {
+    assert_eq!(base64_encode(b""), "");
+    assert_eq!(base64_encode(b"f"), "Zg==");
+    assert_eq!(base64_encode(b"fo"), "Zm8=");
+    assert_eq!(base64_encode(b"foo"), "Zm9v");
+    assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+    assert_eq!(base64_encode(b"fooba"), "Zm9vYmE=");
+    assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    assert_eq!(base64_encode(&[0xFF, 0xEF]), "/+8=");
+}
+
+#[test]
+fn test_parse_snapshot_base64() {
+    assert_eq!(
+        parse_command("snapshot base64"),
+        Some(Command::SnapshotBase64)
+    );
+    assert_eq!(Command::SnapshotBase64.to_string(), "snapshot base64");
+    assert_eq!(Command::SnapshotBase64.name(), "snapshot");
+    assert!(!Command::SnapshotBase64.is_mutating());
+}
+
+#[test]
+fn test_snapshot_base64_is_the_canvas_png() {
+    let mut state = AppState::new();
+    state.execute(&parse_command("edge 2").unwrap());
+    state.execute(&parse_command("dot 100,100").unwrap());
+
+    let reply = state.execute(&Command::SnapshotBase64).unwrap();
+    assert!(!reply.contains(':'));
+    let png = base64_decode(&reply);
+    assert_eq!(image::guess_format(&png).unwrap(), image::ImageFormat::Png);
+    let img = image::load_from_memory(&png).unwrap().into_rgb8();
+    assert_eq!(img.width() as usize, WIDTH);
+    assert_eq!(img.height() as usize, CANVAS_BOTTOM - CANVAS_TOP);
+    let pixel = img.get_pixel(100, (100 - CANVAS_TOP) as u32).0;
+    let color = COLOR_PALETTE[2];
+    assert_eq!(
+        pixel,
+        [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+    );
+}
+
+#[test]
+fn test_snapshot_base64_includes_objects() {
+    let mut state = AppState::new();
+    let plain = state.execute(&Command::SnapshotBase64).unwrap();
+    state.execute(&parse_command("obj text 100,100 \"HI\"").unwrap());
+    let with_text = state.execute(&Command::SnapshotBase64).unwrap();
+    assert_ne!(plain, with_text);
+    // Objects are in the image, not the canvas
+    assert_eq!(state.canvas[100 * WIDTH + 100], WHITE);
+}
+
+// ===================
+// Format Tests
+// ===================
+
+#[test]
+fn test_webp_snapshot_is_lossless() {
+    let mut buffer = new_buffer();
+    draw_circle(&mut buffer, 100, 100, 5, COLOR_PALETTE[2]);
+    let path = "/tmp/test_snapshot_lossless.webp";
+    save_canvas_image(&buffer, path).unwrap();
+    let img = image::open(path).expect("Should open").to_rgb8();
+    assert_eq!(
+        img.get_pixel(100, (100 - CANVAS_TOP) as u32).0,
+        [0xE0, 0x40, 0x40]
+    );
+    // Smaller than the same snapshot as PNG
+    let png = "/tmp/test_snapshot_lossless.png";
+    save_canvas_image(&buffer, png).unwrap();
+    let size = |path: &str| std::fs::metadata(path).unwrap().len();
+    assert!(size(path) < size(png), "{} vs {}", size(path), size(png));
+    std::fs::remove_file(path).ok();
+    std::fs::remove_file(png).ok();
+}
+
+#[test]
+fn test_avif_snapshot_needs_the_feature() {
+    assert_eq!(
+        snapshot_format("/tmp/frame.AVIF").is_ok(),
+        cfg!(feature = "avif")
+    );
+}
+
+#[cfg(feature = "avif")]
+#[test]
+fn test_avif_snapshot() {
+    let mut buffer = new_buffer();
+    draw_circle(&mut buffer, 100, 100, 20, COLOR_PALETTE[2]);
+    let path = "/tmp/test_snapshot_frame.avif";
+    save_canvas_image(&buffer, path).unwrap();
+    let bytes = std::fs::read(path).unwrap();
+    assert_eq!(
+        image::guess_format(&bytes).unwrap(),
+        image::ImageFormat::Avif
+    );
+    std::fs::remove_file(path).ok();
+}
+
+#[cfg(not(feature = "avif"))]
+#[test]
+fn test_avif_snapshot_without_the_feature_is_refused() {
+    let err = save_canvas_image(&new_buffer(), "/tmp/test_snapshot_frame.avif").unwrap_err();
+    assert_eq!(
+        err,
+        "unsupported snapshot format '/tmp/test_snapshot_frame.avif' (use .png, .jpg, .bmp, or .webp)"
+    );
+}
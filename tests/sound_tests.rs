@@ -0,0 +1,49 @@
+use displai::*;
+
+fn beep(freq: u32, ms: u64) -> Command {
+    Command::Beep { freq, ms }
+}
+
+// ===================
+// Beep Command Tests
+// ===================
+
+#[test]
+fn test_parse_beep() {
+    assert_eq!(
+        parse_command("beep"),
+        Some(beep(DEFAULT_BEEP_FREQ, DEFAULT_BEEP_MS))
+    );
+    assert_eq!(parse_command("beep 440"), Some(beep(440, DEFAULT_BEEP_MS)));
+    assert_eq!(parse_command("beep 440 1000"), Some(beep(440, 1000)));
+    assert_eq!(
+        parse_command(&beep(660, 50).to_string()),
+        Some(beep(660, 50))
+    );
+
+    assert_eq!(parse_command("beep high"), None);
+    assert_eq!(parse_command("beep 440 long"), None);
+    assert_eq!(parse_command("beep 440 100 2"), None);
+    assert_eq!(parse_command("beep -440"), None);
+}
+
+#[test]
+fn test_validate_beep() {
+    assert!(validate(&beep(MIN_BEEP_FREQ, 1)).is_ok());
+    assert!(validate(&beep(MAX_BEEP_FREQ, MAX_BEEP_MS)).is_ok());
+    assert!(validate(&beep(MIN_BEEP_FREQ - 1, 100)).is_err());
+    assert!(validate(&beep(MAX_BEEP_FREQ + 1, 100)).is_err());
+    assert!(validate(&beep(440, 0)).is_err());
+    assert!(validate(&beep(440, MAX_BEEP_MS + 1)).is_err());
+}
+
+#[test]
+fn test_sound_feature_flag() {
+    assert_eq!(sound_supported(), cfg!(feature = "sound"));
+}
+
+#[cfg(not(feature = "sound"))]
+#[test]
+fn test_play_beep_without_sound_is_a_no_op() {
+    play_beep(DEFAULT_BEEP_FREQ, DEFAULT_BEEP_MS);
+}
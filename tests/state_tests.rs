@@ -0,0 +1,107 @@
+use displai::*;
+
+// ===================
+// AppState Tests
+// ===================
+
+#[test]
+fn test_new_state_matches_app_defaults() {
+    let state = AppState::new();
+    assert!(state.canvas.iter().all(|&p| p == WHITE));
+    assert_eq!(state.edge_color, Some(BLACK));
+    assert_eq!(state.fill_color, None);
+    assert_eq!(state.brush_size, DEFAULT_BRUSH_SIZE);
+    assert_eq!(state.current_tool, ToolMode::Brush);
+}
+
+#[test]
+fn test_execute_updates_tool_settings() {
+    let mut state = AppState::new();
+    state.execute(&Command::Edge(Some(COLOR_PALETTE[2])));
+    state.execute(&Command::Fill(None));
+    state.execute(&Command::Size(5));
+
+    assert_eq!(state.edge_color, Some(COLOR_PALETTE[2]));
+    assert_eq!(state.fill_color, None);
+    assert_eq!(
+        state.execute(&Command::State),
+        Some("edge:2 fill:none size:5".to_string())
+    );
+}
+
+#[test]
+fn test_execute_draws_on_owned_canvas() {
+    let mut state = AppState::new();
+    state.execute(&Command::Fill(Some(COLOR_PALETTE[10])));
+    state.execute(&Command::Rect {
+        x1: 100,
+        y1: 100,
+        x2: 200,
+        y2: 200,
+    });
+
+    assert_eq!(state.canvas[100 * WIDTH + 100], COLOR_PALETTE[0]);
+    assert_eq!(state.canvas[150 * WIDTH + 150], COLOR_PALETTE[10]);
+}
+
+#[test]
+fn test_execute_matches_execute_command() {
+    let commands = [
+        Command::Size(4),
+        Command::Stroke {
+            x1: 20,
+            y1: 80,
+            x2: 300,
+            y2: 260,
+        },
+        Command::Edge(None),
+        Command::Dot { x: 50, y: 50 },
+    ];
+
+    let mut state = AppState::new();
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    let (mut edge, mut fill, mut size) = (Some(0), None, DEFAULT_BRUSH_SIZE);
+    for cmd in &commands {
+        state.execute(cmd);
+        let _ = execute_command(cmd, &mut buffer, &mut edge, &mut fill, &mut size);
+    }
+
+    assert_eq!(state.canvas.pixels(), &buffer[..]);
+}
+
+#[test]
+fn test_states_are_independent() {
+    let mut a = AppState::new();
+    let b = AppState::new();
+    a.execute(&Command::Dot { x: 100, y: 100 });
+    assert_ne!(a.canvas, b.canvas);
+}
+
+#[test]
+fn test_session_commands_are_refused_without_a_session() {
+    let mut state = AppState::new();
+    let before = state.clone();
+    for (line, verb) in [
+        ("dot 100,100", None),
+        ("undo", Some("undo")),
+        ("fps 30", Some("fps")),
+        ("lockregion 0,30,10,10", Some("lockregion")),
+        ("compare checkpoint", Some("compare")),
+        ("record start /tmp/a.rec", Some("record")),
+    ] {
+        let result = state.run(line);
+        match verb {
+            Some(verb) => assert_eq!(
+                result,
+                Err(CommandError::Failed(format!("{} needs a session", verb)))
+            ),
+            None => assert_eq!(result, Ok(CommandOutput::Ok)),
+        }
+    }
+    // Only the dot was drawn
+    assert_ne!(state.canvas, before.canvas);
+    assert_eq!(
+        state.execute(&Command::Undo),
+        Some("error: undo needs a session".to_string())
+    );
+}
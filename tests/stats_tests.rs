@@ -0,0 +1,144 @@
+use displai::*;
+use std::time::{Duration, Instant};
+
+fn new_buffer() -> Vec<u32> {
+    vec![WHITE; WIDTH * HEIGHT]
+}
+
+// ===================
+// Font Tests
+// ===================
+
+#[test]
+fn test_text_width() {
+    assert_eq!(text_width(""), 0);
+    assert_eq!(text_width("A"), GLYPH_WIDTH);
+    assert_eq!(text_width("AB"), GLYPH_WIDTH + GLYPH_ADVANCE);
+}
+
+#[test]
+fn test_draw_text_stays_in_its_box() {
+    let mut buffer = new_buffer();
+    draw_text(&mut buffer, 100, 100, "FPS 60.0", BLACK);
+    let width = text_width("FPS 60.0");
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            if buffer[y * WIDTH + x] == BLACK {
+                assert!((100..100 + width).contains(&x), "x {} outside text", x);
+                assert!(
+                    (100..100 + GLYPH_HEIGHT).contains(&y),
+                    "y {} outside text",
+                    y
+                );
+            }
+        }
+    }
+    assert!(buffer.contains(&BLACK));
+}
+
+#[test]
+fn test_draw_text_lowercase_matches_uppercase() {
+    let mut lower = new_buffer();
+    let mut upper = new_buffer();
+    draw_text(&mut lower, 10, 40, "mem", BLACK);
+    draw_text(&mut upper, 10, 40, "MEM", BLACK);
+    assert_eq!(lower, upper);
+}
+
+#[test]
+fn test_draw_text_clips_at_window_edge() {
+    let mut buffer = new_buffer();
+    draw_text(&mut buffer, WIDTH - 3, HEIGHT - 3, "WWW", BLACK);
+}
+
+// ===================
+// Stats Tests
+// ===================
+
+#[test]
+fn test_parse_stats() {
+    assert_eq!(parse_command("stats"), Some(Command::Stats(None)));
+    assert_eq!(parse_command("stats on"), Some(Command::Stats(Some(true))));
+    assert_eq!(
+        parse_command("stats off"),
+        Some(Command::Stats(Some(false)))
+    );
+    assert_eq!(parse_command("stats maybe"), None);
+    assert_eq!(Command::Stats(Some(true)).to_string(), "stats on");
+}
+
+#[test]
+fn test_stats_summary() {
+    let stats = SessionStats {
+        pixels_drawn: 1234,
+        commands: 56,
+        clients: 2,
+        fps: 59.94,
+        memory_bytes: 2048 * 1024,
+    };
+    assert_eq!(
+        stats.summary(),
+        "pixels:1234 commands:56 clients:2 fps:59.9 mem:2048KB"
+    );
+    assert_eq!(stats.lines().len(), 5);
+}
+
+#[test]
+fn test_fps_counter_measures_one_second_windows() {
+    let mut counter = FpsCounter::new();
+    let start = Instant::now();
+    for i in 0..30 {
+        counter.tick(start + Duration::from_millis(i * 33));
+    }
+    assert_eq!(counter.fps(), 0.0);
+    let fps = counter.tick(start + Duration::from_secs(1));
+    assert!((fps - 31.0).abs() < 0.5, "fps {}", fps);
+}
+
+#[test]
+fn test_stats_overlay_in_top_right_of_canvas() {
+    let stats = SessionStats::default();
+    let (x, y, width, height) = stats_overlay_bounds(&stats);
+    assert!(x + width <= WIDTH);
+    assert!(y >= CANVAS_TOP);
+    assert!(y + height <= CANVAS_BOTTOM);
+    assert!(x > WIDTH / 2);
+
+    let mut buffer = new_buffer();
+    draw_stats_overlay(&mut buffer, &stats);
+    assert_eq!(buffer[y * WIDTH + x], DARK_GRAY);
+    assert!(buffer[y * WIDTH..(y + height) * WIDTH].contains(&BLACK));
+    // Nothing outside the box is touched
+    assert_eq!(buffer[(y + height) * WIDTH + x], WHITE);
+    assert_eq!(buffer[y * WIDTH + x - 1], WHITE);
+}
+
+#[test]
+fn test_history_counts_changed_pixels() {
+    let mut buffer = new_buffer();
+    let mut history = History::new();
+    set_pixel(&mut buffer, 100, 100, BLACK);
+    set_pixel(&mut buffer, 200, 300, BLACK);
+    history.record("points", &buffer);
+    assert_eq!(history.pixels_changed(), 2);
+    history.undo(&mut buffer).unwrap();
+    assert_eq!(history.pixels_changed(), 2);
+}
+
+#[test]
+fn test_draw_text_scaled_enlarges_glyphs() {
+    let mut small = new_buffer();
+    let mut big = new_buffer();
+    draw_text(&mut small, 10, 40, "I", BLACK);
+    draw_text_scaled(&mut big, 10, 40, "I", BLACK, 3);
+    for y in 0..GLYPH_HEIGHT {
+        for x in 0..GLYPH_WIDTH {
+            let lit = small[(40 + y) * WIDTH + 10 + x];
+            for dy in 0..3 {
+                for dx in 0..3 {
+                    assert_eq!(big[(40 + y * 3 + dy) * WIDTH + 10 + x * 3 + dx], lit);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::run;
+use displai::*;
+
+/// A red stroke of size 3 whose points are 10ms apart, from `start`
+fn timed_stroke(points: &[(usize, usize)], start: u64) -> Stroke {
+    Stroke {
+        points: points.to_vec(),
+        times: (0..points.len() as u64).map(|i| start + i * 10).collect(),
+        color: 0xFF0000,
+        brush_size: 3,
+    }
+}
+
+fn stroke(points: &[(usize, usize)]) -> Stroke {
+    timed_stroke(points, 0)
+}
+
+/// Write a stroke file unique to one test, returning its path
+fn write_strokes(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir()
+        .join(format!("displai_strokes_{}.json", name))
+        .to_string_lossy()
+        .into_owned();
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_export_strokes() {
+    let cmd = Command::ExportStrokes("/tmp/my strokes/out.json".to_string());
+    assert_eq!(
+        parse_command("export strokes /tmp/my strokes/out.json"),
+        Some(cmd.clone())
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(cmd.name(), "export");
+    assert!(!cmd.is_mutating());
+    assert_eq!(parse_command("export strokes"), None);
+    assert_eq!(parse_command("export strokesout.json"), None);
+}
+
+#[test]
+fn test_parse_playstrokes() {
+    let cmd = Command::PlayStrokes {
+        path: "/tmp/my strokes/in.json".to_string(),
+        speed: 1.0,
+    };
+    assert_eq!(
+        parse_command("playstrokes /tmp/my strokes/in.json"),
+        Some(cmd.clone())
+    );
+    let fast = Command::PlayStrokes {
+        path: "in.json".to_string(),
+        speed: 4.0,
+    };
+    assert_eq!(parse_command("playstrokes in.json 4"), Some(fast.clone()));
+    for cmd in [cmd, fast] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(validate(&cmd).is_ok());
+        assert!(!cmd.is_mutating());
+    }
+    assert_eq!(parse_command("playstrokes"), None);
+}
+
+#[test]
+fn test_validate_playstrokes_speed() {
+    assert_eq!(
+        validate(&parse_command("playstrokes in.json 500").unwrap()),
+        Err("playstrokes speed 500 is outside 0.1-100".to_string())
+    );
+}
+
+// ===================
+// Simplification Tests
+// ===================
+
+#[test]
+fn test_simplify_drops_points_on_a_straight_path() {
+    let points: Vec<_> = (0..=20).map(|i| (100 + i * 5, 200)).collect();
+    assert_eq!(simplify(&points, 1.0), vec![(100, 200), (200, 200)]);
+}
+
+#[test]
+fn test_simplify_keeps_corners() {
+    let mut points: Vec<_> = (0..=10).map(|i| (100 + i * 10, 100)).collect();
+    points.extend((1..=10).map(|i| (200, 100 + i * 10)));
+    assert_eq!(
+        simplify(&points, 1.0),
+        vec![(100, 100), (200, 100), (200, 200)]
+    );
+}
+
+#[test]
+fn test_simplify_epsilon_controls_jitter() {
+    let jittery = [(0, 0), (10, 1), (20, 0), (30, 2), (40, 0)];
+    assert_eq!(simplify(&jittery, 3.0), vec![(0, 0), (40, 0)]);
+    // Only the bump of 2 survives; the point 1 off the chord is dropped
+    assert_eq!(simplify(&jittery, 1.5), vec![(0, 0), (30, 2), (40, 0)]);
+    assert_eq!(simplify(&jittery, 0.0).len(), jittery.len());
+}
+
+#[test]
+fn test_simplify_short_strokes_are_kept_whole() {
+    assert_eq!(simplify(&[], 1.0), vec![]);
+    assert_eq!(simplify(&[(5, 40)], 1.0), vec![(5, 40)]);
+    assert_eq!(simplify(&[(5, 40), (5, 41)], 1.0), vec![(5, 40), (5, 41)]);
+}
+
+#[test]
+fn test_simplify_closed_stroke_keeps_its_shape() {
+    // A square walked back to its start: the chord is a single point
+    let square = [
+        (100, 100),
+        (150, 100),
+        (200, 100),
+        (200, 200),
+        (100, 200),
+        (100, 100),
+    ];
+    assert_eq!(
+        simplify(&square, 1.0),
+        vec![(100, 100), (200, 100), (200, 200), (100, 200), (100, 100)]
+    );
+}
+
+// ===================
+// Stroke Log Tests
+// ===================
+
+#[test]
+fn test_stroke_log_ignores_empty_strokes() {
+    let mut log = StrokeLog::new();
+    log.push(stroke(&[]));
+    assert!(log.is_empty());
+    // Each point needs a time
+    log.push(Stroke {
+        times: vec![0],
+        ..stroke(&[(10, 40), (20, 40)])
+    });
+    assert!(log.is_empty());
+    log.push(stroke(&[(10, 40)]));
+    assert_eq!(log.len(), 1);
+}
+
+#[test]
+fn test_stroke_log_to_json() {
+    let mut log = StrokeLog::new();
+    assert_eq!(
+        log.to_json(1.0),
+        "{\"width\":800,\"height\":510,\"top\":30,\"strokes\":[\n]}\n"
+    );
+    // Times count from the first stroke's start
+    log.push(timed_stroke(&[(10, 40), (20, 40), (30, 40)], 5000));
+    log.push(Stroke {
+        points: vec![(5, 50)],
+        times: vec![5500],
+        color: BLACK,
+        brush_size: 1,
+    });
+    assert_eq!(
+        log.to_json(1.0),
+        "{\"width\":800,\"height\":510,\"top\":30,\"strokes\":[\n\
+         {\"color\":\"#FF0000\",\"size\":3,\"points\":[[10,40,0],[30,40,20]]},\n\
+         {\"color\":\"#000000\",\"size\":1,\"points\":[[5,50,500]]}\n]}\n"
+    );
+}
+
+#[test]
+fn test_clear_empties_stroke_log() {
+    let mut state = AppState::new();
+    state.strokes.push(stroke(&[(10, 40), (20, 50)]));
+    run(&mut state, "rect 10,40 50,80");
+    assert_eq!(state.strokes.len(), 1);
+    run(&mut state, "clear");
+    assert!(state.strokes.is_empty());
+}
+
+#[test]
+fn test_export_strokes_writes_file() {
+    let path = "/tmp/test_export_strokes.json";
+    let mut state = AppState::new();
+    state.strokes.push(stroke(&[(10, 40), (20, 40), (30, 40)]));
+    assert_eq!(
+        run(&mut state, &format!("export strokes {}", path)),
+        Some(format!("saved {}", path))
+    );
+    let written = std::fs::read_to_string(path).unwrap();
+    assert_eq!(written, state.strokes.to_json(SIMPLIFY_EPSILON));
+    assert!(written.contains("\"points\":[[10,40,0],[30,40,20]]"));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_export_strokes_bad_path() {
+    let mut state = AppState::new();
+    let response = run(&mut state, "export strokes /nonexistent/dir/out.json").unwrap();
+    assert!(response.starts_with("error: cannot write /nonexistent/dir/out.json"));
+}
+
+// ===================
+// Reading Tests
+// ===================
+
+#[test]
+fn test_read_strokes_round_trips_export() {
+    let path = "/tmp/test_read_strokes_round_trip.json";
+    let mut state = AppState::new();
+    state
+        .strokes
+        .push(timed_stroke(&[(10, 40), (20, 45), (30, 40)], 1000));
+    run(&mut state, &format!("export strokes {}", path));
+    assert_eq!(
+        read_strokes(path).unwrap(),
+        vec![timed_stroke(&[(10, 40), (20, 45), (30, 40)], 0)]
+    );
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_read_strokes_paces_untimed_points() {
+    let path = write_strokes(
+        "untimed",
+        r#"{"strokes": [
+            {"points": [[10, 40], [20, 40]]},
+            {"color": "red", "size": 99, "points": [[20, 50], [20, 60, 5000]]},
+            {"points": []}
+        ]}"#,
+    );
+    let strokes = read_strokes(&path).unwrap();
+    assert_eq!(strokes.len(), 2);
+    // 10px at UNTIMED_PACE px/ms, then a pause before the next stroke
+    assert_eq!(strokes[0].times, vec![0, (10.0 / UNTIMED_PACE) as u64]);
+    assert_eq!(strokes[0].color, BLACK);
+    assert_eq!(strokes[0].brush_size, MIN_BRUSH_SIZE);
+    assert_eq!(strokes[1].times, vec![20 + MAX_STROKE_GAP_MS, 5000]);
+    assert_eq!(strokes[1].brush_size, MAX_BRUSH_SIZE);
+}
+
+#[test]
+fn test_read_strokes_errors() {
+    let bad_json = write_strokes("bad_json", "{\"strokes\": [");
+    assert_eq!(
+        read_strokes(&bad_json),
+        Err(format!("{}: invalid JSON at byte 13", bad_json))
+    );
+    let no_strokes = write_strokes("no_strokes", "[1, 2]");
+    assert_eq!(
+        read_strokes(&no_strokes),
+        Err(format!("{}: expected {{\"strokes\": [...]}}", no_strokes))
+    );
+    let bad_point = write_strokes(
+        "bad_point",
+        r#"{"strokes": [{"points": [[1, 2], [-3, 4]]}]}"#,
+    );
+    assert_eq!(
+        read_strokes(&bad_point),
+        Err(format!(
+            "{}: stroke 1: point 2: expected [x, y] or [x, y, ms]",
+            bad_point
+        ))
+    );
+    assert!(read_strokes("/nonexistent/strokes.json")
+        .unwrap_err()
+        .starts_with("cannot read /nonexistent/strokes.json"));
+}
+
+// ===================
+// Playback Tests
+// ===================
+
+#[test]
+fn test_playback_draws_points_as_they_come_due() {
+    let start = Instant::now();
+    let strokes = vec![timed_stroke(&[(100, 100), (200, 100)], 0)];
+    let mut playback = StrokePlayback::new("in.json", strokes, 1.0, start);
+    let mut canvas = vec![WHITE; WIDTH * HEIGHT];
+    assert!(playback.advance(&mut canvas, start));
+    assert_eq!(canvas[100 * WIDTH + 100], 0xFF0000);
+    assert_eq!(canvas[100 * WIDTH + 150], WHITE);
+    assert!(!playback.is_finished());
+    // Nothing new until the next point is due
+    assert!(!playback.advance(&mut canvas, start + ms(5)));
+    assert!(playback.advance(&mut canvas, start + ms(10)));
+    assert_eq!(canvas[100 * WIDTH + 150], 0xFF0000);
+    assert!(playback.is_finished());
+}
+
+#[test]
+fn test_playback_speed_scales_times() {
+    let start = Instant::now();
+    let strokes = vec![timed_stroke(&[(100, 100), (200, 100)], 0)];
+    let mut playback = StrokePlayback::new("in.json", strokes, 2.0, start);
+    let mut canvas = vec![WHITE; WIDTH * HEIGHT];
+    playback.advance(&mut canvas, start + ms(5));
+    assert!(playback.is_finished());
+}
+
+#[test]
+fn test_playback_shortens_long_pauses() {
+    let start = Instant::now();
+    let strokes = vec![
+        timed_stroke(&[(100, 100)], 60_000),
+        timed_stroke(&[(200, 200)], 120_000),
+    ];
+    let mut playback = StrokePlayback::new("in.json", strokes, 1.0, start);
+    assert_eq!(playback.len(), 2);
+    let mut canvas = vec![WHITE; WIDTH * HEIGHT];
+    playback.advance(&mut canvas, start);
+    assert!(!playback.is_finished());
+    playback.advance(&mut canvas, start + ms(MAX_STROKE_GAP_MS));
+    assert!(playback.is_finished());
+    assert_eq!(canvas[200 * WIDTH + 200], 0xFF0000);
+}
@@ -0,0 +1,269 @@
+mod common;
+
+use common::run;
+use displai::*;
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_export_svg() {
+    let cmd = Command::ExportSvg("/tmp/my drawings/out.svg".to_string());
+    assert_eq!(
+        parse_command("export svg /tmp/my drawings/out.svg"),
+        Some(cmd.clone())
+    );
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd));
+    assert_eq!(parse_command("export svg"), None);
+    assert_eq!(parse_command("export svg "), None);
+    assert_eq!(parse_command("export svgout.svg"), None);
+    assert_eq!(parse_command("export pdf /tmp/out.pdf"), None);
+    assert!(!Command::ExportSvg("/tmp/out.svg".to_string()).is_mutating());
+}
+
+// ===================
+// Display List Tests
+// ===================
+
+#[test]
+fn test_shape_commands_are_recorded() {
+    let mut state = AppState::new();
+    run(&mut state, "size 3");
+    run(&mut state, "fill #00FF00");
+    run(&mut state, "rect 10,40 110,90");
+    run(&mut state, "line 0,50 100,50");
+    run(&mut state, "circle 200,200 50");
+    run(&mut state, "oval 300,300 40,20");
+    run(&mut state, "polyline 10,100 20,110 30,100");
+    // Strokes and dots have no vector form
+    run(&mut state, "stroke 0,100 10,100");
+    run(&mut state, "dot 50,50");
+
+    let items: Vec<_> = state.display_list.iter().cloned().collect();
+    assert_eq!(items.len(), 5);
+    assert_eq!(
+        items[0],
+        Primitive {
+            shape: Shape::Rect {
+                x: 10,
+                y: 40,
+                width: 100,
+                height: 50
+            },
+            edge: Some(BLACK),
+            fill: Some(0x00FF00),
+            brush_size: 3,
+        }
+    );
+    // Lines and polylines are never filled
+    assert_eq!(items[1].fill, None);
+    assert_eq!(items[4].fill, None);
+    assert_eq!(
+        items[2].shape,
+        Shape::Ellipse {
+            cx: 200.0,
+            cy: 200.0,
+            rx: 50.0,
+            ry: 50.0
+        }
+    );
+    assert_eq!(
+        items[3].shape,
+        Shape::Ellipse {
+            cx: 300.0,
+            cy: 300.0,
+            rx: 40.0,
+            ry: 20.0
+        }
+    );
+}
+
+#[test]
+fn test_clear_empties_display_list() {
+    let mut state = AppState::new();
+    run(&mut state, "rect 10,40 110,90");
+    assert_eq!(state.display_list.len(), 1);
+    run(&mut state, "clear");
+    assert!(state.display_list.is_empty());
+}
+
+#[test]
+fn test_invisible_shapes_are_not_recorded() {
+    let mut state = AppState::new();
+    run(&mut state, "edge none");
+    run(&mut state, "rect 10,40 110,90");
+    assert!(state.display_list.is_empty());
+}
+
+#[test]
+fn test_mouse_shapes_are_recorded() {
+    let mut state = AppState::new();
+    state.draw_shape(ToolMode::Square, 10, 40, 110, 90);
+    state.draw_polygon(&[(10, 40), (50, 40), (30, 80)]);
+    let shapes: Vec<_> = state.display_list.iter().map(|p| p.shape.clone()).collect();
+    assert_eq!(
+        shapes,
+        [
+            Shape::Rect {
+                x: 10,
+                y: 40,
+                width: 50,
+                height: 50
+            },
+            Shape::Polygon(vec![(10, 40), (50, 40), (30, 80)]),
+        ]
+    );
+}
+
+#[test]
+fn test_triangle_direction_follows_drag() {
+    let up = Shape::from_drag(ToolMode::Triangle, 10, 100, 30, 60);
+    assert_eq!(
+        up,
+        Some(Shape::Polygon(vec![(20, 60), (30, 100), (10, 100)]))
+    );
+    let down = Shape::from_drag(ToolMode::Triangle, 10, 60, 30, 100);
+    assert_eq!(
+        down,
+        Some(Shape::Polygon(vec![(10, 60), (30, 60), (20, 100)]))
+    );
+    assert_eq!(Shape::from_drag(ToolMode::Brush, 0, 0, 1, 1), None);
+}
+
+#[test]
+fn test_attributed_polyline_records_segments() {
+    let mut state = AppState::new();
+    run(&mut state, "polyline 10,100 20,110:#FF0000:4 30,100");
+    let items: Vec<_> = state.display_list.iter().collect();
+    assert_eq!(items.len(), 2);
+    assert_eq!((items[0].edge, items[0].brush_size), (Some(0xFF0000), 4));
+    assert_eq!((items[1].edge, items[1].brush_size), (Some(BLACK), 1));
+}
+
+#[test]
+fn test_stroke_width_matches_brush() {
+    let primitive = |brush_size| Primitive {
+        shape: Shape::Line {
+            x1: 0,
+            y1: 0,
+            x2: 1,
+            y2: 1,
+        },
+        edge: Some(BLACK),
+        fill: None,
+        brush_size,
+    };
+    assert_eq!(primitive(1).stroke_width(), 1);
+    assert_eq!(primitive(2).stroke_width(), 3);
+    assert_eq!(primitive(5).stroke_width(), 9);
+}
+
+// ===================
+// SVG Output Tests
+// ===================
+
+#[test]
+fn test_to_svg_elements() {
+    let mut state = AppState::new();
+    run(&mut state, "fill #00FF00");
+    run(&mut state, "rect 10,40 110,90");
+    run(&mut state, "edge none");
+    run(&mut state, "triangle 10,100 30,60");
+
+    let svg = state.display_list.to_svg();
+    let height = CANVAS_BOTTOM - CANVAS_TOP;
+    assert!(svg.starts_with(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 {} {} {}\">",
+        WIDTH, height, CANVAS_TOP, WIDTH, height
+    )));
+    assert!(svg.contains(
+        "<rect x=\"10\" y=\"40\" width=\"100\" height=\"50\" fill=\"#00FF00\" stroke=\"#000000\" stroke-width=\"1\""
+    ));
+    assert!(svg.contains("<polygon points=\"20,60 30,100 10,100\" fill=\"#00FF00\"/>"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+}
+
+#[test]
+fn test_to_svg_rounded_rect() {
+    let mut state = AppState::new();
+    run(&mut state, "fill #00FF00");
+    run(&mut state, "rrect 300,200 100,100 20");
+    // The radius is clamped to half the shorter side
+    run(&mut state, "rrect 100,300 140,320 50");
+    let svg = state.display_list.to_svg();
+    assert!(svg.contains(
+        "<rect x=\"100\" y=\"100\" width=\"200\" height=\"100\" rx=\"20\" fill=\"#00FF00\""
+    ));
+    assert!(svg.contains("<rect x=\"100\" y=\"300\" width=\"40\" height=\"20\" rx=\"10\""));
+}
+
+#[test]
+fn test_to_svg_arrow() {
+    let mut state = AppState::new();
+    run(&mut state, "edge #0000FF");
+    run(&mut state, "arrow 100,100 200,100 20 both");
+    // The shaft between the heads, then each head filled with the edge color
+    assert_eq!(state.display_list.len(), 3);
+    let svg = state.display_list.to_svg();
+    assert!(svg.contains("<line x1=\"120\" y1=\"100\" x2=\"180\" y2=\"100\" fill=\"none\""));
+    assert!(svg.contains("<polygon points=\"200,100 180,110 180,90\" fill=\"#0000FF\""));
+    assert!(svg.contains("<polygon points=\"100,100 120,90 120,110\" fill=\"#0000FF\""));
+    // Nothing without an edge color
+    run(&mut state, "edge none");
+    run(&mut state, "arrow 100,300 200,300");
+    assert_eq!(state.display_list.len(), 3);
+}
+
+#[test]
+fn test_to_svg_ngon_and_star() {
+    let mut state = AppState::new();
+    run(&mut state, "ngon 200,300 100 4");
+    run(&mut state, "star 500,300 100 40 3");
+    let svg = state.display_list.to_svg();
+    assert!(svg.contains("<polygon points=\"200,200 300,300 200,400 100,300\""));
+    assert!(svg.contains("<polygon points=\"500,200 535,280 587,350"));
+}
+
+#[test]
+fn test_to_svg_arcs() {
+    let mut state = AppState::new();
+    run(&mut state, "arc 400,300 100 0 90");
+    run(&mut state, "fill #00FF00");
+    run(&mut state, "pie 400,300 100 0 270");
+    assert_eq!(state.display_list.len(), 2);
+
+    let svg = state.display_list.to_svg();
+    assert!(svg.contains("<path d=\"M 500 300 A 100 100 0 0 1 400 400\" fill=\"none\""));
+    // Past half a turn the curve is split in two
+    assert!(svg.contains(
+        "<path d=\"M 400 300 L 500 300 A 100 100 0 0 1 329.29 370.71 A 100 100 0 0 1 400 200 Z\" fill=\"#00FF00\""
+    ));
+    run(&mut state, "pie 400,300 100 0 -90");
+    assert!(state
+        .display_list
+        .to_svg()
+        .contains("A 100 100 0 0 0 400 200 Z"));
+}
+
+#[test]
+fn test_export_svg_writes_file() {
+    let path = "/tmp/test_export_svg.svg";
+    let mut state = AppState::new();
+    run(&mut state, "circle 200,200 50");
+    assert_eq!(
+        run(&mut state, &format!("export svg {}", path)),
+        Some(format!("saved {}", path))
+    );
+    let written = std::fs::read_to_string(path).unwrap();
+    assert_eq!(written, state.display_list.to_svg());
+    assert!(written.contains("<ellipse cx=\"200\" cy=\"200\" rx=\"50\" ry=\"50\""));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_export_svg_bad_path() {
+    let mut state = AppState::new();
+    let response = run(&mut state, "export svg /nonexistent/dir/out.svg").unwrap();
+    assert!(response.starts_with("error: cannot write /nonexistent/dir/out.svg"));
+}
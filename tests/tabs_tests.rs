@@ -0,0 +1,172 @@
+use displai::*;
+
+/// A canvas of the default size with one black pixel at index `mark`
+fn marked(mark: usize) -> CanvasTab {
+    let mut tab = CanvasTab::blank(&Canvas::new(WIDTH, HEIGHT));
+    tab.canvas[mark] = BLACK;
+    tab
+}
+
+fn blank() -> CanvasTab {
+    CanvasTab::blank(&Canvas::new(WIDTH, HEIGHT))
+}
+
+// ===================
+// Command Tests
+// ===================
+
+#[test]
+fn test_tab_command_parse_and_display() {
+    for (args, op) in [
+        (&["new"][..], TabCommand::New),
+        (&["switch", "2"][..], TabCommand::Switch(2)),
+        (&["close", "9"][..], TabCommand::Close(9)),
+    ] {
+        assert_eq!(TabCommand::parse(args), Some(op));
+        assert_eq!(op.to_string(), args.join(" "));
+    }
+    assert_eq!(TabCommand::parse(&["switch"]), None);
+    assert_eq!(TabCommand::parse(&["switch", "two"]), None);
+    assert_eq!(TabCommand::parse(&["new", "2"]), None);
+}
+
+#[test]
+fn test_canvas_commands_parse() {
+    assert_eq!(
+        parse_command("canvas new"),
+        Some(Command::Tab(TabCommand::New))
+    );
+    assert_eq!(
+        parse_command("canvas switch 3"),
+        Some(Command::Tab(TabCommand::Switch(3)))
+    );
+    assert_eq!(
+        parse_command("canvas close 1").map(|cmd| cmd.to_string()),
+        Some("canvas close 1".to_string())
+    );
+    // The other `canvas` commands still parse
+    assert!(matches!(
+        parse_command("canvas resize 800x600"),
+        Some(Command::CanvasSize(_))
+    ));
+}
+
+#[test]
+fn test_tab_command_check() {
+    assert!(TabCommand::New.check().is_ok());
+    assert!(TabCommand::Switch(MAX_CANVASES).check().is_ok());
+    assert_eq!(
+        TabCommand::Switch(0).check(),
+        Err("canvas 0 is outside 1-9".to_string())
+    );
+    assert!(TabCommand::Close(MAX_CANVASES + 1).check().is_err());
+}
+
+// ===================
+// Canvases Tests
+// ===================
+
+#[test]
+fn test_canvases_open_and_switch() {
+    let mut canvases = Canvases::new();
+    assert!(canvases.is_single());
+    let shown = canvases.open(marked(0), blank());
+    assert_eq!((canvases.count(), canvases.active()), (2, 2));
+    assert_eq!(shown.canvas[0], WHITE);
+
+    let shown = canvases.switch(1, shown);
+    assert_eq!(canvases.active(), 1);
+    assert_eq!(shown.canvas[0], BLACK);
+    // Switching to the shown canvas keeps it
+    let shown = canvases.switch(1, shown);
+    assert_eq!(shown.canvas[0], BLACK);
+}
+
+#[test]
+fn test_canvases_close() {
+    let mut canvases = Canvases::new();
+    let shown = canvases.open(marked(0), marked(1));
+    let shown = canvases.open(shown, marked(2));
+    // Closing one before the shown canvas renumbers it
+    let shown = canvases.close(1, shown);
+    assert_eq!((canvases.count(), canvases.active()), (2, 2));
+    assert_eq!(shown.canvas[2], BLACK);
+    // Closing the shown (last) canvas shows the one before it
+    let shown = canvases.close(2, shown);
+    assert!(canvases.is_single());
+    assert_eq!(shown.canvas[1], BLACK);
+}
+
+#[test]
+fn test_canvases_checks() {
+    let mut canvases = Canvases::new();
+    assert_eq!(
+        canvases.check(2),
+        Err("no canvas 2 (only 1 is open)".to_string())
+    );
+    assert_eq!(
+        canvases.check_close(1),
+        Err("the last canvas can't be closed".to_string())
+    );
+    let mut shown = blank();
+    for _ in 1..MAX_CANVASES {
+        assert!(canvases.check_open().is_ok());
+        shown = canvases.open(shown, blank());
+    }
+    assert_eq!(
+        canvases.check_open(),
+        Err("9 canvases are already open".to_string())
+    );
+    assert_eq!(
+        canvases.check(10),
+        Err("no canvas 10 (1-9 are open)".to_string())
+    );
+    assert!(canvases.check_close(4).is_ok());
+}
+
+#[test]
+fn test_blank_tab_has_nothing_to_undo() {
+    let tab = blank();
+    assert!(!tab.history.can_undo());
+    assert!(tab.canvas.iter().all(|&pixel| pixel == WHITE));
+}
+
+// ===================
+// Snapshot Name Tests
+// ===================
+
+#[test]
+fn test_default_snapshot_path_names_the_active_canvas() {
+    assert_eq!(default_snapshot_path(), SNAPSHOT_PATH);
+    set_active_canvas(3);
+    assert_eq!(default_snapshot_path(), "canvas-3.png");
+    set_active_canvas(1);
+    assert_eq!(default_snapshot_path(), "canvas.png");
+}
+
+// ===================
+// Tab Strip Tests
+// ===================
+
+#[test]
+fn test_tab_strip_hidden_with_one_canvas() {
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    let before = buffer.clone();
+    draw_tab_strip(&mut buffer, 1, 1);
+    assert_eq!(buffer, before);
+    assert_eq!(tab_at(WIDTH - 40, 15, 1), None);
+}
+
+#[test]
+fn test_tab_strip_clicks_find_their_tab() {
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    draw_tab_strip(&mut buffer, 3, 2);
+    // Tabs end left of the close button, the last one rightmost
+    let end = WIDTH - BUTTON_SIZE - 2 * BUTTON_MARGIN;
+    assert_eq!(tab_at(end - 3, 15, 3), Some(3));
+    assert_eq!(tab_at(end - 3 - TAB_WIDTH - 2, 15, 3), Some(2));
+    assert_eq!(tab_at(end + 1, 15, 3), None);
+    assert_eq!(tab_at(end - 3, CANVAS_TOP + 5, 3), None);
+    // Inactive tabs are dark
+    assert_eq!(buffer[15 * WIDTH + end - 4], DARK_GRAY);
+}
@@ -0,0 +1,248 @@
+mod common;
+
+use common::{drawn, pixel, run};
+use displai::*;
+
+fn inked(state: &AppState) -> Vec<(usize, usize)> {
+    (CANVAS_TOP * WIDTH..canvas_bottom() * WIDTH)
+        .filter(|&i| state.canvas[i] != WHITE)
+        .map(|i| (i % WIDTH, i / WIDTH))
+        .collect()
+}
+
+/// The last row of the canvas
+fn last_row() -> usize {
+    canvas_bottom() - 1
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_canvas_transforms() {
+    let cases = [
+        ("canvas flip h", CanvasTransform::Flip(FlipAxis::Horizontal)),
+        ("canvas flip v", CanvasTransform::Flip(FlipAxis::Vertical)),
+        ("canvas rotate 90", CanvasTransform::Rotate(90)),
+        ("canvas rotate 270", CanvasTransform::Rotate(270)),
+        (
+            "canvas scale 400x300",
+            CanvasTransform::Scale {
+                width: 400,
+                height: 300,
+                resample: Resample::Nearest,
+            },
+        ),
+        (
+            "canvas scale 20x10 bilinear",
+            CanvasTransform::Scale {
+                width: 20,
+                height: 10,
+                resample: Resample::Bilinear,
+            },
+        ),
+    ];
+    for (line, transform) in cases {
+        let cmd = parse_command(line).unwrap();
+        assert_eq!(cmd, Command::Canvas(transform));
+        assert_eq!(cmd.to_string(), line);
+        assert_eq!(cmd.name(), "canvas");
+        assert!(validate(&cmd).is_ok(), "{}", line);
+        assert!(cmd.is_mutating());
+        assert!(!cmd.runs_in_pane());
+    }
+
+    // Nearest is the default, so it isn't printed
+    let cmd = parse_command("canvas scale 20x10 nearest").unwrap();
+    assert_eq!(cmd.to_string(), "canvas scale 20x10");
+
+    // Invalid formats
+    assert_eq!(parse_command("canvas"), None);
+    assert_eq!(parse_command("canvas flip"), None);
+    assert_eq!(parse_command("canvas flip x"), None);
+    assert_eq!(parse_command("canvas flip h v"), None);
+    assert_eq!(parse_command("canvas rotate"), None);
+    assert_eq!(parse_command("canvas rotate -90"), None);
+    assert_eq!(parse_command("canvas scale 400"), None);
+    assert_eq!(parse_command("canvas scale 400x"), None);
+    assert_eq!(parse_command("canvas scale 400x300 cubic"), None);
+    assert_eq!(parse_command("canvas spin 90"), None);
+}
+
+#[test]
+fn test_validate_canvas_transforms() {
+    let error = |line: &str| validate(&parse_command(line).unwrap()).unwrap_err();
+    assert_eq!(
+        error("canvas rotate 45"),
+        "rotation 45 is not 90, 180, or 270 degrees"
+    );
+    assert!(validate(&parse_command("canvas rotate 360").unwrap()).is_err());
+    assert!(error("canvas scale 0x10").contains("outside"));
+    assert!(error("canvas scale 10x9000").contains("outside"));
+    assert!(validate(&parse_command("canvas scale 8192x1").unwrap()).is_ok());
+}
+
+#[test]
+fn test_invalid_transform_leaves_the_canvas() {
+    let before = drawn(&["rect 20,50 300,200"]);
+    let mut state = before.clone();
+    assert_eq!(
+        run(&mut state, "canvas rotate 45"),
+        Some("error: rotation 45 is not 90, 180, or 270 degrees".to_string())
+    );
+    assert!(run(&mut state, "canvas scale 0x10")
+        .unwrap()
+        .starts_with("error: scale 0x10 is outside"));
+    assert!(state.canvas == before.canvas);
+}
+
+// ===================
+// Transform Tests
+// ===================
+
+#[test]
+fn test_flip_horizontal() {
+    let state = drawn(&["size 1", "dot 10,100", "canvas flip h"]);
+    assert_eq!(inked(&state), vec![(WIDTH - 1 - 10, 100)]);
+}
+
+#[test]
+fn test_flip_vertical() {
+    let state = drawn(&["size 1", "dot 10,100", "canvas flip v"]);
+    let y = CANVAS_TOP + last_row() - 100;
+    assert_eq!(inked(&state), vec![(10, y)]);
+}
+
+#[test]
+fn test_flipping_twice_restores_the_canvas() {
+    let before = drawn(&["rect 20,50 300,200", "circle 500,300 40"]);
+    for axis in ["h", "v"] {
+        let mut state = before.clone();
+        let flip = format!("canvas flip {}", axis);
+        run(&mut state, &flip);
+        assert!(state.canvas != before.canvas);
+        run(&mut state, &flip);
+        assert!(state.canvas == before.canvas);
+    }
+}
+
+#[test]
+fn test_rotate_turns_clockwise() {
+    let center_x = WIDTH / 2;
+    let center_y = (CANVAS_TOP + last_row()) / 2;
+    let east = format!("dot {},{}", center_x + 100, center_y);
+
+    let state = drawn(&["size 1", &east, "canvas rotate 90"]);
+    let [(x, y)] = inked(&state)[..] else {
+        panic!("one pixel")
+    };
+    // East of the center turns to south of it
+    assert!(x.abs_diff(center_x) <= 1, "{},{}", x, y);
+    assert!(y.abs_diff(center_y + 100) <= 1, "{},{}", x, y);
+
+    let state = drawn(&["size 1", &east, "canvas rotate 270"]);
+    let [(x, y)] = inked(&state)[..] else {
+        panic!("one pixel")
+    };
+    assert!(x.abs_diff(center_x) <= 1, "{},{}", x, y);
+    assert!(y.abs_diff(center_y - 100) <= 1, "{},{}", x, y);
+}
+
+#[test]
+fn test_rotate_half_turn_twice_restores_the_canvas() {
+    let before = drawn(&["rect 20,50 300,200", "line 600,40 780,500"]);
+    let mut state = before.clone();
+    run(&mut state, "canvas rotate 180");
+    assert!(state.canvas != before.canvas);
+    run(&mut state, "canvas rotate 180");
+    assert!(state.canvas == before.canvas);
+}
+
+#[test]
+fn test_quarter_turn_crops_the_wide_canvas() {
+    // The ends of a wide canvas have nowhere to go after a quarter turn
+    let state = drawn(&["size 1", "dot 2,100", "canvas rotate 90"]);
+    assert!(inked(&state).is_empty());
+    // And there is nothing to turn into its sides
+    let state = drawn(&["fill 0", "rect 0,30 799,500", "canvas rotate 90"]);
+    assert_eq!(pixel(&state, 0, 200), WHITE);
+    assert_eq!(pixel(&state, WIDTH - 1, 200), WHITE);
+    assert_ne!(pixel(&state, WIDTH / 2, 200), WHITE);
+}
+
+#[test]
+fn test_scale_nearest() {
+    let height = canvas_bottom() - CANVAS_TOP;
+    let scale = format!("canvas scale {}x{}", WIDTH / 2, height / 2);
+    let rect = format!("rect 100,{} 300,{}", CANVAS_TOP + 100, CANVAS_TOP + 200);
+    let state = drawn(&["fill 0", &rect, &scale]);
+    // The rectangle is half the size, half as far from the top-left corner
+    assert_eq!(pixel(&state, 100, CANVAS_TOP + 75), BLACK);
+    assert_eq!(pixel(&state, 40, CANVAS_TOP + 75), WHITE);
+    assert_eq!(pixel(&state, 160, CANVAS_TOP + 75), WHITE);
+    assert_eq!(pixel(&state, 100, CANVAS_TOP + 40), WHITE);
+    // Nothing but white beyond the scaled picture
+    assert!(inked(&state)
+        .iter()
+        .all(|&(x, y)| x < WIDTH / 2 && y < CANVAS_TOP + height / 2));
+    // Nearest makes no new colors
+    assert!(inked(&state)
+        .iter()
+        .all(|&(x, y)| pixel(&state, x, y) == BLACK));
+}
+
+#[test]
+fn test_scale_up_crops() {
+    let state = drawn(&["size 1", "dot 700,100", "canvas scale 1600x2000"]);
+    assert!(inked(&state).is_empty());
+    let state = drawn(&["size 1", "dot 10,40", "canvas scale 1600x2000"]);
+    assert!(!inked(&state).is_empty());
+}
+
+#[test]
+fn test_scale_bilinear_blends() {
+    let height = canvas_bottom() - CANVAS_TOP;
+    // Not a whole fraction, so most pixels fall between source pixels
+    let scale = format!("canvas scale {}x{} bilinear", WIDTH * 3 / 8, height * 3 / 8);
+    let rect = format!("rect 100,{} 300,{}", CANVAS_TOP + 100, CANVAS_TOP + 200);
+    let state = drawn(&["fill 0", &rect, &scale]);
+    let colors: Vec<u32> = inked(&state)
+        .iter()
+        .map(|&(x, y)| pixel(&state, x, y))
+        .collect();
+    assert!(colors.contains(&BLACK));
+    assert!(colors.iter().any(|&color| color != BLACK), "no gray edges");
+}
+
+#[test]
+fn test_transform_leaves_the_toolbar_alone() {
+    let before = drawn(&["rect 20,50 300,200"]);
+    let mut state = before.clone();
+    run(&mut state, "canvas rotate 90");
+    let toolbar = canvas_bottom() * WIDTH..HEIGHT * WIDTH;
+    assert_eq!(
+        state.canvas.pixels()[toolbar.clone()],
+        before.canvas.pixels()[toolbar]
+    );
+    let title = 0..CANVAS_TOP * WIDTH;
+    assert_eq!(
+        state.canvas.pixels()[title.clone()],
+        before.canvas.pixels()[title]
+    );
+}
+
+#[test]
+fn test_transform_is_not_a_pane_command() {
+    let mut state = drawn(&["panes 2x1"]);
+    assert_eq!(
+        run(&mut state, "@1 canvas flip h"),
+        Some("error: @1 only prefixes drawing commands".to_string())
+    );
+}
+
+#[test]
+fn test_transform_locks_the_whole_canvas() {
+    let cmd = parse_command("canvas scale 10x10").unwrap();
+    assert_eq!(command_region(&cmd, 1), Some(Region::ALL));
+}
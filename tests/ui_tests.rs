@@ -84,7 +84,13 @@ fn test_close_button_is_red() {
 fn test_draw_bottom_toolbar_covers_bottom() {
     let mut buffer = new_buffer();
 
-    draw_bottom_toolbar(&mut buffer, Some(0), None, 1, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
 
     // Bottom toolbar area should be filled
     let mid_x = WIDTH / 2;
@@ -94,10 +100,17 @@ fn test_draw_bottom_toolbar_covers_bottom() {
 }
 
 #[test]
+#[allow(clippy::needless_range_loop)]
 fn test_all_14_palette_colors_rendered() {
     let mut buffer = new_buffer();
 
-    draw_bottom_toolbar(&mut buffer, Some(0), None, 1, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
 
     // Verify each of the 14 color buttons shows its corresponding color
     let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
@@ -121,7 +134,13 @@ fn test_selected_color_has_white_border() {
     let mut buffer = new_buffer();
 
     // Select color index 5
-    draw_bottom_toolbar(&mut buffer, Some(5), None, 1, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[5]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
 
     let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
 
@@ -136,11 +155,18 @@ fn test_selected_color_has_white_border() {
 }
 
 #[test]
+#[allow(clippy::identity_op)]
 fn test_white_color_selected_has_blue_border() {
     let mut buffer = new_buffer();
 
     // Select white color (index 1)
-    draw_bottom_toolbar(&mut buffer, Some(1), None, 1, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[1]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
 
     let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
 
@@ -154,11 +180,18 @@ fn test_white_color_selected_has_blue_border() {
 fn test_plus_minus_buttons_rendered() {
     let mut buffer = new_buffer();
 
-    draw_bottom_toolbar(&mut buffer, Some(0), None, 5, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        5,
+        ToolMode::Brush,
+    );
 
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    // Size display is now after 7 tool buttons
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    // Size display is after the tool buttons
+    let size_display_x =
+        BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
     let minus_x = size_display_x + 44 + BUTTON_MARGIN;
     let plus_x = minus_x + BUTTON_SIZE + BUTTON_MARGIN;
 
@@ -173,11 +206,18 @@ fn test_plus_minus_buttons_rendered() {
 fn test_size_display_rendered() {
     let mut buffer = new_buffer();
 
-    draw_bottom_toolbar(&mut buffer, Some(0), None, 10, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        10,
+        ToolMode::Brush,
+    );
 
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
-    // Size display is now after 7 tool buttons
-    let size_display_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
+    // Size display is after the tool buttons
+    let size_display_x =
+        BUTTON_MARGIN + TOOLBAR_TOOLS.len() * (BUTTON_SIZE + BUTTON_MARGIN) + BUTTON_MARGIN;
 
     // Size display should have white background (check near edge to avoid number)
     assert_eq!(buffer[(row2_y + 1) * WIDTH + (size_display_x + 1)], WHITE);
@@ -219,12 +259,18 @@ fn test_brush_size_constants() {
 fn test_tool_buttons_rendered() {
     let mut buffer = new_buffer();
 
-    draw_bottom_toolbar(&mut buffer, Some(0), None, 1, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
 
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
 
-    // Check that 7 tool buttons are rendered (they have gray background)
-    for i in 0..7 {
+    // Check that every tool button is rendered (they have gray background)
+    for i in 0..TOOLBAR_TOOLS.len() {
         let bx = BUTTON_MARGIN + i * (BUTTON_SIZE + BUTTON_MARGIN);
         // Tool buttons have GRAY background
         let center_x = bx + BUTTON_SIZE / 2;
@@ -235,11 +281,18 @@ fn test_tool_buttons_rendered() {
 }
 
 #[test]
+#[allow(clippy::identity_op)]
 fn test_selected_tool_has_blue_border() {
     let mut buffer = new_buffer();
 
     // Brush is selected (index 0)
-    draw_bottom_toolbar(&mut buffer, Some(0), None, 1, ToolMode::Brush);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
 
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
     let brush_x = BUTTON_MARGIN;
@@ -257,7 +310,13 @@ fn test_different_tool_selected() {
     let mut buffer = new_buffer();
 
     // Circle is selected
-    draw_bottom_toolbar(&mut buffer, Some(0), None, 1, ToolMode::Circle);
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Circle,
+    );
 
     let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
 
@@ -269,3 +328,179 @@ fn test_different_tool_selected() {
     let brush_x = BUTTON_MARGIN;
     assert_eq!(buffer[row2_y * WIDTH + brush_x], DARK_GRAY);
 }
+
+#[test]
+fn test_bucket_tool_button() {
+    let mut buffer = new_buffer();
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Bucket,
+    );
+
+    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let bucket_x = BUTTON_MARGIN + 7 * (BUTTON_SIZE + BUTTON_MARGIN);
+    assert_eq!(TOOLBAR_TOOLS[7], ToolMode::Bucket);
+    assert_eq!(buffer[row2_y * WIDTH + bucket_x], 0x4040E0);
+    // The icon draws something inside the button
+    let icon_pixels = (row2_y + 5..row2_y + BUTTON_SIZE - 4)
+        .flat_map(|y| (bucket_x + 5..bucket_x + BUTTON_SIZE - 4).map(move |x| (x, y)))
+        .filter(|&(x, y)| buffer[y * WIDTH + x] == BLACK)
+        .count();
+    assert!(icon_pixels > 10);
+    // Row 2 still fits in the narrowest supported window
+    let redo_right = (0..WIDTH)
+        .filter(|&x| is_in_redo_button(x, row2_y))
+        .max()
+        .unwrap();
+    assert!(redo_right < MIN_WIDTH);
+}
+
+#[test]
+fn test_polygon_tool_button() {
+    let mut buffer = new_buffer();
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Polygon,
+    );
+
+    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let polygon_x = BUTTON_MARGIN + 8 * (BUTTON_SIZE + BUTTON_MARGIN);
+    assert_eq!(TOOLBAR_TOOLS[8], ToolMode::Polygon);
+    assert_eq!(
+        get_clicked_tool(polygon_x + BUTTON_SIZE / 2, row2_y + BUTTON_SIZE / 2),
+        Some(ToolMode::Polygon)
+    );
+    assert_eq!(buffer[row2_y * WIDTH + polygon_x], 0x4040E0);
+    let icon_pixels = (row2_y + 5..row2_y + BUTTON_SIZE - 4)
+        .flat_map(|y| (polygon_x + 5..polygon_x + BUTTON_SIZE - 4).map(move |x| (x, y)))
+        .filter(|&(x, y)| buffer[y * WIDTH + x] == BLACK)
+        .count();
+    assert!(icon_pixels > 10);
+}
+
+#[test]
+fn test_rounded_rect_tool_button() {
+    let mut buffer = new_buffer();
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::RoundedRect,
+    );
+
+    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let rrect_x = BUTTON_MARGIN + 10 * (BUTTON_SIZE + BUTTON_MARGIN);
+    assert_eq!(TOOLBAR_TOOLS[10], ToolMode::RoundedRect);
+    assert_eq!(
+        get_clicked_tool(rrect_x + BUTTON_SIZE / 2, row2_y + BUTTON_SIZE / 2),
+        Some(ToolMode::RoundedRect)
+    );
+    assert_eq!(buffer[row2_y * WIDTH + rrect_x], 0x4040E0);
+    // An outline with its corner pixels cut away
+    let (left, top) = (rrect_x + 5, row2_y + 7);
+    assert_eq!(buffer[top * WIDTH + left + 8], BLACK);
+    assert_eq!(buffer[(top + 5) * WIDTH + left], BLACK);
+    assert_ne!(buffer[top * WIDTH + left], BLACK);
+    assert_eq!(ToolMode::RoundedRect.name(), "rrect");
+}
+
+#[test]
+fn test_arrow_tool_button() {
+    let mut buffer = new_buffer();
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Arrow,
+    );
+
+    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let arrow_x = BUTTON_MARGIN + 11 * (BUTTON_SIZE + BUTTON_MARGIN);
+    assert_eq!(TOOLBAR_TOOLS[11], ToolMode::Arrow);
+    assert_eq!(
+        get_clicked_tool(arrow_x + BUTTON_SIZE / 2, row2_y + BUTTON_SIZE / 2),
+        Some(ToolMode::Arrow)
+    );
+    assert_eq!(buffer[row2_y * WIDTH + arrow_x], 0x4040E0);
+    // A solid head in the top-right corner of the icon
+    let head_pixels = (row2_y + 5..row2_y + 9)
+        .flat_map(|y| (arrow_x + 15..arrow_x + 20).map(move |x| (x, y)))
+        .filter(|&(x, y)| buffer[y * WIDTH + x] == BLACK)
+        .count();
+    assert!(head_pixels > 10);
+    assert_eq!(ToolMode::Arrow.name(), "arrow");
+}
+
+#[test]
+fn test_eyedropper_button() {
+    let mut buffer = new_buffer();
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Eyedropper,
+    );
+
+    // Row 1, right of the edge/fill indicator
+    let row1_y = CANVAS_BOTTOM + BUTTON_MARGIN;
+    let xs: Vec<usize> = (0..WIDTH)
+        .filter(|&x| is_in_eyedropper_button(x, row1_y + BUTTON_SIZE / 2))
+        .collect();
+    assert_eq!(xs.len(), BUTTON_SIZE);
+    let (left, right) = (xs[0], xs[BUTTON_SIZE - 1]);
+    assert!(left > BUTTON_MARGIN + 15 * (BUTTON_SIZE + BUTTON_MARGIN));
+    assert!(right < MIN_WIDTH);
+    assert!(!is_in_eyedropper_button(left, row1_y - 1));
+    assert!(!is_in_eyedropper_button(left, row1_y + BUTTON_SIZE));
+    assert_eq!(get_clicked_tool(left, row1_y), None);
+    assert!(!TOOLBAR_TOOLS.contains(&ToolMode::Eyedropper));
+
+    // Highlighted while selected, with an icon inside
+    assert_eq!(buffer[row1_y * WIDTH + left], 0x4040E0);
+    let icon_pixels = (row1_y + 5..row1_y + BUTTON_SIZE - 4)
+        .flat_map(|y| (left + 5..left + BUTTON_SIZE - 4).map(move |x| (x, y)))
+        .filter(|&(x, y)| buffer[y * WIDTH + x] == BLACK)
+        .count();
+    assert!(icon_pixels > 10);
+
+    draw_bottom_toolbar(&mut buffer, None, None, 1, ToolMode::Brush);
+    assert_eq!(buffer[row1_y * WIDTH + left], DARK_GRAY);
+}
+
+#[test]
+fn test_toolbar_draws_undo_redo_arrows() {
+    let mut buffer = new_buffer();
+    draw_bottom_toolbar(
+        &mut buffer,
+        Some(COLOR_PALETTE[0]),
+        None,
+        1,
+        ToolMode::Brush,
+    );
+
+    let row2_y = CANVAS_BOTTOM + TOOLBAR_ROW_HEIGHT + BUTTON_MARGIN;
+    let undo_x = (0..WIDTH).find(|&x| is_in_undo_button(x, row2_y)).unwrap();
+    let redo_x = (0..WIDTH).find(|&x| is_in_redo_button(x, row2_y)).unwrap();
+    let mid_y = row2_y + BUTTON_SIZE / 2;
+
+    // Button background and white arrow shaft
+    assert_eq!(buffer[row2_y * WIDTH + undo_x], DARK_GRAY);
+    assert_eq!(buffer[mid_y * WIDTH + undo_x + BUTTON_SIZE / 2], WHITE);
+    assert_eq!(buffer[mid_y * WIDTH + redo_x + BUTTON_SIZE / 2], WHITE);
+
+    // Arrow heads point in opposite directions
+    assert_eq!(buffer[(mid_y - 3) * WIDTH + undo_x + 6 + 3], WHITE);
+    assert_eq!(
+        buffer[(mid_y - 3) * WIDTH + redo_x + BUTTON_SIZE - 7 - 3],
+        WHITE
+    );
+}
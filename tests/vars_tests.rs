@@ -0,0 +1,143 @@
+use displai::*;
+
+fn var_set(name: &str, value: &str) -> Command {
+    Command::Var(VarCommand::Set {
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn var_get(name: &str) -> Command {
+    Command::Var(VarCommand::Get(name.to_string()))
+}
+
+fn vars(pairs: &[(&str, &str)]) -> Variables {
+    let mut vars = Variables::new();
+    for (name, value) in pairs {
+        vars.set(name, value);
+    }
+    vars
+}
+
+// ===================
+// Template Tests
+// ===================
+
+#[test]
+fn test_var_names() {
+    assert!(is_valid_var_name("cpu"));
+    assert!(is_valid_var_name("disk_2-free"));
+    assert!(!is_valid_var_name(""));
+    assert!(!is_valid_var_name("a b"));
+    assert!(!is_valid_var_name("cpu%"));
+    assert!(!is_valid_var_name(&"x".repeat(MAX_VAR_NAME_LEN + 1)));
+}
+
+#[test]
+fn test_expand_substitutes_placeholders() {
+    let vars = vars(&[("cpu", "37"), ("host", "db-1")]);
+    assert_eq!(vars.expand("CPU: {cpu}%"), "CPU: 37%");
+    assert_eq!(vars.expand("{host} {cpu}{cpu}"), "db-1 3737");
+    assert_eq!(vars.expand("no placeholders"), "no placeholders");
+}
+
+#[test]
+fn test_expand_keeps_unknown_and_malformed_braces() {
+    let vars = vars(&[("cpu", "37")]);
+    assert_eq!(vars.expand("{mem} {cpu}"), "{mem} 37");
+    assert_eq!(vars.expand("{ cpu } {"), "{ cpu } {");
+    assert_eq!(vars.expand("{{cpu}}"), "{37}");
+    assert_eq!(vars.expand("}{cpu"), "}{cpu");
+}
+
+// ===================
+// Var Command Tests
+// ===================
+
+#[test]
+fn test_parse_var_commands() {
+    assert_eq!(parse_command("var set cpu 37"), Some(var_set("cpu", "37")));
+    assert_eq!(
+        parse_command("var set status \"all good\""),
+        Some(var_set("status", "all good"))
+    );
+    assert_eq!(
+        parse_command("var set empty \"\""),
+        Some(var_set("empty", ""))
+    );
+    assert_eq!(parse_command("var get cpu"), Some(var_get("cpu")));
+}
+
+#[test]
+fn test_parse_var_rejects_malformed() {
+    assert_eq!(parse_command("var"), None);
+    assert_eq!(parse_command("var set"), None);
+    assert_eq!(parse_command("var set cpu"), None);
+    assert_eq!(parse_command("var set cpu 1 2"), None);
+    assert_eq!(parse_command("var set c%u 1"), None);
+    assert_eq!(parse_command("var set cpu \"open"), None);
+    assert_eq!(parse_command("var get"), None);
+    assert_eq!(parse_command("var get cpu extra"), None);
+    assert_eq!(parse_command("var unset cpu"), None);
+}
+
+#[test]
+fn test_var_display_round_trips() {
+    for cmd in [
+        var_set("cpu", "37"),
+        var_set("status", "all good"),
+        var_set("empty", ""),
+        var_get("cpu"),
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    }
+    assert_eq!(var_set("cpu", "37").to_string(), "var set cpu 37");
+}
+
+#[test]
+fn test_validate_var_commands() {
+    assert!(validate(&var_set("cpu", "37")).is_ok());
+    assert!(validate(&var_set("bad name", "1")).is_err());
+    assert!(validate(&var_set("cpu", "say \"hi\"")).is_err());
+    assert!(validate(&var_get("")).is_err());
+    assert!(!var_set("cpu", "1").is_mutating());
+}
+
+#[test]
+fn test_execute_var_set_and_get() {
+    let mut state = AppState::new();
+    assert_eq!(
+        state.execute(&var_get("cpu")),
+        Some("error: no variable cpu".to_string())
+    );
+    assert_eq!(state.execute(&var_set("cpu", "37")), None);
+    assert_eq!(state.execute(&var_get("cpu")), Some("37".to_string()));
+    state.execute(&var_set("cpu", "41"));
+    assert_eq!(state.execute(&var_get("cpu")), Some("41".to_string()));
+    assert!(state.canvas.iter().all(|&p| p == WHITE));
+}
+
+#[test]
+fn test_templated_text_refreshes_when_variable_changes() {
+    let mut state = AppState::new();
+    state.execute(&parse_command("obj text 100,100 \"CPU: {cpu}%\" 2").unwrap());
+
+    let render = |text: &str| {
+        let mut expected = state.canvas.clone();
+        draw_text_scaled(&mut expected, 100, 100, text, BLACK, 2);
+        expected
+    };
+    let unset = render("CPU: {cpu}%");
+    let at_37 = render("CPU: 37%");
+    let at_100 = render("CPU: 100%");
+
+    assert_eq!(*state.render(), unset);
+    state.execute(&var_set("cpu", "37"));
+    assert_eq!(*state.render(), at_37);
+    state.execute(&var_set("cpu", "100"));
+    assert_eq!(*state.render(), at_100);
+    assert_eq!(
+        state.objects.get(1).unwrap().bounds(&state.vars).2,
+        text_width("CPU: 100%") * 2
+    );
+}
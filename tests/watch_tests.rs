@@ -0,0 +1,157 @@
+use displai::*;
+
+fn watch(x: usize, y: usize, width: usize, height: usize) -> Command {
+    Command::Watch {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+// ===================
+// Parsing Tests
+// ===================
+
+#[test]
+fn test_parse_watch() {
+    let cmd = watch(10, 40, 100, 50);
+    assert_eq!(parse_command("watch 10,40,100,50"), Some(cmd.clone()));
+    assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+    assert_eq!(parse_command("watch 10,40,100"), None);
+    assert_eq!(parse_command("watch 10,40 100,50"), None);
+    assert_eq!(parse_command("watch"), None);
+    assert!(!cmd.is_mutating());
+}
+
+#[test]
+fn test_parse_unwatch_and_resume() {
+    for cmd in [
+        Command::Unwatch(Some(3)),
+        Command::Unwatch(None),
+        Command::Resume,
+    ] {
+        assert_eq!(parse_command(&cmd.to_string()), Some(cmd.clone()));
+        assert!(validate(&cmd).is_ok());
+    }
+    assert_eq!(parse_command("unwatch x"), None);
+    assert_eq!(parse_command("unwatch 1 2"), None);
+    assert_eq!(parse_command("resume now"), None);
+}
+
+#[test]
+fn test_validate_watch() {
+    assert!(validate(&watch(0, CANVAS_TOP, WIDTH, CANVAS_BOTTOM - CANVAS_TOP)).is_ok());
+    assert!(validate(&watch(10, 40, 1, 1)).is_ok());
+    assert_eq!(
+        validate(&watch(10, 40, 0, 5)),
+        Err("watch region must be at least 1x1".to_string())
+    );
+    assert!(validate(&watch(10, 10, 5, 5)).is_err());
+    assert!(validate(&watch(WIDTH - 5, 40, 10, 5)).is_err());
+    assert_eq!(
+        validate(&watch(10, 40, usize::MAX, 5)),
+        Err("watch region at 10,40 overflows".to_string())
+    );
+}
+
+// ===================
+// Watches Tests
+// ===================
+
+#[test]
+fn test_changed_reports_watches_touched_since_last_check() {
+    let mut buffer = vec![WHITE; WIDTH * HEIGHT];
+    let mut watches = Watches::new();
+    assert!(watches.is_empty());
+    let a = watches.add(&buffer, Region::new(100, 100, 10, 10)).unwrap();
+    let b = watches.add(&buffer, Region::new(300, 300, 10, 10)).unwrap();
+    assert_eq!((a, b), (1, 2));
+    assert_eq!(watches.len(), 2);
+    assert!(watches.changed(&buffer).is_empty());
+
+    // Just outside the first region
+    buffer[100 * WIDTH + 110] = RED;
+    assert!(watches.changed(&buffer).is_empty());
+
+    // Its bottom-right corner
+    buffer[109 * WIDTH + 109] = RED;
+    assert_eq!(watches.changed(&buffer), vec![a]);
+    // The change is now the baseline
+    assert!(watches.changed(&buffer).is_empty());
+
+    buffer[300 * WIDTH + 300] = RED;
+    buffer[105 * WIDTH + 105] = RED;
+    assert_eq!(watches.changed(&buffer), vec![a, b]);
+}
+
+#[test]
+fn test_remove_and_clear_watches() {
+    let buffer = vec![WHITE; WIDTH * HEIGHT];
+    let mut watches = Watches::new();
+    watches.add(&buffer, Region::new(100, 100, 10, 10)).unwrap();
+    watches.add(&buffer, Region::new(200, 100, 10, 10)).unwrap();
+    assert_eq!(watches.remove(1), Ok(()));
+    assert_eq!(watches.remove(1), Err("no watch 1".to_string()));
+    assert_eq!(watches.iter().map(|w| w.id).collect::<Vec<_>>(), vec![2]);
+    // Ids are not reused
+    assert_eq!(watches.add(&buffer, Region::new(0, 40, 1, 1)), Ok(3));
+    assert_eq!(watches.clear(), 2);
+    assert!(watches.is_empty());
+}
+
+#[test]
+fn test_watch_limit() {
+    let buffer = vec![WHITE; WIDTH * HEIGHT];
+    let mut watches = Watches::new();
+    for _ in 0..MAX_WATCHES {
+        watches.add(&buffer, Region::new(10, 40, 1, 1)).unwrap();
+    }
+    assert_eq!(
+        watches.add(&buffer, Region::new(10, 40, 1, 1)),
+        Err(format!("too many watches (max {})", MAX_WATCHES))
+    );
+}
+
+#[test]
+fn test_watch_refuses_empty_and_off_canvas_regions() {
+    let buffer = vec![WHITE; WIDTH * HEIGHT];
+    let mut watches = Watches::new();
+    assert_eq!(
+        watches.add(&buffer, Region::new(0, 40, 0, 5)),
+        Err("watch region must be at least 1x1".to_string())
+    );
+    assert!(watches
+        .add(&buffer, Region::new(WIDTH - 10, HEIGHT - 10, 100, 100))
+        .is_err());
+    assert!(watches.is_empty());
+    let mut apply = |line: &str| watches.apply(&buffer, &parse_command(line).unwrap());
+    assert!(apply("watch 0,0,0,5").is_err());
+    assert!(apply("watch 790,590,100,100").is_err());
+}
+
+#[test]
+fn test_apply_watch_commands() {
+    let buffer = vec![WHITE; WIDTH * HEIGHT];
+    let mut watches = Watches::new();
+    let mut apply = |line: &str| watches.apply(&buffer, &parse_command(line).unwrap());
+    assert_eq!(apply("watch 10,40,5,5"), reply("watching 1"));
+    assert_eq!(apply("watch 20,40,5,5"), reply("watching 2"));
+    assert_eq!(apply("unwatch 1"), Ok(CommandOutput::Ok));
+    assert_eq!(apply("unwatch 1"), failed("no watch 1"));
+    assert_eq!(apply("unwatch"), reply("unwatched 1"));
+    assert_eq!(apply("state"), Ok(CommandOutput::Ok));
+}
+
+// ===================
+// Event Tests
+// ===================
+
+#[test]
+fn test_watch_event_names_the_command() {
+    let event = Event::Watch {
+        id: 2,
+        command: "rect 10,40 50,80".to_string(),
+    };
+    assert_eq!(event.to_string(), "watch 2 rect 10,40 50,80");
+}